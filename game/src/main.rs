@@ -5,11 +5,24 @@ mod assets;
 // `gcrecomp recompile`); referenced directly as `recompiled::...`.
 
 use anyhow::Result;
+use gcrecomp_core::config::LoadedConfig;
+use gcrecomp_core::recompiler::analysis::pointer::DEFAULT_SDA_BASE;
+use gcrecomp_core::runtime::cheats::CheatEngine;
 use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::debug::gdbstub::{self, GdbServer, Resume};
+use gcrecomp_core::runtime::debug::StopReason;
+use gcrecomp_core::runtime::diagnostics::{generate_crash_bundle, CrashReport, FunctionCallLogger};
 use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::rng::SeededRng;
+use gcrecomp_core::runtime::sdk::card::MemoryCard;
 use gcrecomp_core::runtime::sdk::os::OsState;
+use gcrecomp_core::runtime::sdk::region::Region;
+use gcrecomp_core::runtime::sdk::sram::Sram;
+use gcrecomp_core::runtime::sdk::timer::{OsTimer, TimeSource};
+use gcrecomp_core::runtime::ShutdownRequest;
+use gcrecomp_runtime::graphics::splash::{SplashScreen, CANVAS_H, CANVAS_W};
 use log::info;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use winit::application::ApplicationHandler;
 use winit::event::{KeyEvent, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoop};
@@ -26,41 +39,83 @@ struct GameApp {
     xfb_addr: u32,
     xfb_w: u32,
     xfb_h: u32,
+    /// CPU state the recompiled entry stopped in; its PC is shown on the debug
+    /// overlay and gdb reads/writes it while halted.
+    ctx: CpuContext,
+    /// gdb client from `GCRECOMP_GDB=host:port`, shared with the trap handler.
+    gdb: Option<Arc<Mutex<GdbServer>>>,
+    /// A gdb single-step advanced one frame; halt again on the next one.
+    gdb_halt_next: bool,
+    /// Gecko/AR codes from `GCRECOMP_CHEATS`, re-applied every frame.
+    cheats: CheatEngine,
+    /// Shown instead of the XFB until it finishes or a key is pressed.
+    splash: Option<SplashScreen>,
+    /// `GCRECOMP_SEED`: RAM contents and the OS timebase come from this seed
+    /// instead of zeros and the host clock, for reproducible runs.
+    seed: Option<u64>,
+    /// Region chosen at boot, applied to the runtime once it exists.
+    region: Region,
+    /// User config, re-read by `boot` on a restart.
+    config: Option<LoadedConfig>,
 }
 
 impl GameApp {
     fn new() -> Self {
+        let seed = std::env::var("GCRECOMP_SEED").ok().and_then(|s| {
+            let seed = parse_u32(&s)
+                .map(u64::from)
+                .or_else(|| s.trim().parse().ok());
+            if seed.is_none() {
+                log::warn!("GCRECOMP_SEED={s:?} is not a number; running unseeded");
+            }
+            seed
+        });
+        let config = match gcrecomp_core::config::Config::load() {
+            Ok(loaded) => {
+                loaded.apply_logging();
+                gcrecomp_core::runtime::watchdog::set_loop_budget(loaded.config.loop_budget());
+                gcrecomp_core::runtime::set_catch_panics(loaded.config.runtime.catch_panics);
+                gcrecomp_core::runtime::clock::set_clock_ratio(loaded.config.clock_ratio());
+                Some(loaded)
+            }
+            Err(e) => {
+                log::warn!("Config load failed, using defaults: {e:#}");
+                None
+            }
+        };
+
         let mut memory = MemoryManager::new();
-        let mut os_state = OsState::new();
         let mut ctx = CpuContext::new();
+        let region = boot(&mut memory, &mut ctx, seed, config.as_ref());
 
-        // SDK init + DVD filesystem + load the DOL's real memory image into RAM.
-        gcrecomp_core::runtime::sdk::os::os_init(&mut os_state, &mut memory);
-        os_state.init_dvd(assets::ARCHIVE);
-        recompiled::load_image(&mut memory);
+        let cheats = load_cheats();
+        if let Err(e) = cheats.apply(&mut memory) {
+            log::warn!("Cheat apply failed: {e:#}");
+        }
 
-        ctx.set_register(1, 0x817F_FF00); // r1 = stack pointer (top of MEM1)
-        ctx.set_register(2, 0x8040_0000); // SDA2 base
-        ctx.set_register(13, 0x8040_0000); // SDA base
+        let gdb = std::env::var("GCRECOMP_GDB").ok().and_then(|addr| {
+            match GdbServer::accept(addr.as_str()) {
+                Ok(server) => Some(Arc::new(Mutex::new(server))),
+                Err(e) => {
+                    log::warn!("gdb stub on {addr} failed: {e}");
+                    None
+                }
+            }
+        });
+        if let Some(server) = &gdb {
+            // Start halted so breakpoints can be set before the entry runs.
+            gdbstub::install(server.clone());
+            ctx.pc = recompiled::ENTRY_POINT;
+            gcrecomp_core::runtime::debug::halt(StopReason::Interrupt, &mut ctx, &mut memory);
+        }
 
-        // Run the recompiled entry once; its writes to RAM persist in `memory`,
-        // which the render loop then presents as the framebuffer.
-        let entry = recompiled::ENTRY_POINT;
-        info!("Running recompiled entry point 0x{:08X}...", entry);
         if std::env::var("GCRECOMP_TRACE").is_ok() {
             gcrecomp_core::runtime::enable_trace();
         }
-        // Give the recompiled boot code a few seconds, then stop it (it spins on
-        // hardware we don't fully emulate). The window then shows the resulting XFB.
-        gcrecomp_core::runtime::arm_watchdog(5);
-        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-            recompiled::call_function_by_address(entry, &mut ctx, &mut memory)
-        }));
-        match r {
-            Ok(Ok(v)) => info!("Recompiled entry 0x{:08X} returned {:?}", entry, v),
-            Ok(Err(e)) => log::warn!("Recompiled entry 0x{:08X} error: {e}", entry),
-            Err(_) => log::warn!("Recompiled entry 0x{:08X} panicked (contained)", entry),
+        if std::env::var("GCRECOMP_PROFILE").is_ok() {
+            gcrecomp_core::runtime::enable_call_profile();
         }
+        run_entry(&mut ctx, &mut memory);
 
         let env_u32 = |k: &str, d: u32| {
             std::env::var(k)
@@ -99,8 +154,209 @@ impl GameApp {
             xfb_addr,
             xfb_w: env_u32("GCRECOMP_XFB_W", 640),
             xfb_h: env_u32("GCRECOMP_XFB_H", 480),
+            ctx,
+            gdb,
+            gdb_halt_next: false,
+            cheats,
+            splash: load_splash(),
+            seed,
+            region,
+            config,
+        }
+    }
+}
+
+/// Fresh OS state and RAM image, with registers set up for the entry point.
+/// With a seed, RAM the image doesn't cover holds a pattern from it and the
+/// timebase is virtual. Returns the region the game runs as (the disc's
+/// unless the config forces one).
+fn boot(
+    memory: &mut MemoryManager,
+    ctx: &mut CpuContext,
+    seed: Option<u64>,
+    config: Option<&LoadedConfig>,
+) -> Region {
+    let mut os_state = match seed {
+        Some(seed) => {
+            info!("Seeded run: {seed}");
+            let mut rng = SeededRng::new(seed);
+            *memory = MemoryManager::with_fill(rng.memory_fill());
+            OsState::seeded(&mut rng)
+        }
+        None => OsState::new(),
+    };
+    if let Some(config) = config.filter(|c| c.config.time_source() == TimeSource::Instructions) {
+        let per_instruction = config.config.runtime.ticks_per_instruction;
+        info!("Timebase: {per_instruction} tick(s) per executed instruction");
+        os_state.timer = OsTimer::instruction_clock(os_state.timer.get_time(), per_instruction);
+    }
+
+    // SDK init + DVD filesystem + load the DOL's real memory image into RAM.
+    gcrecomp_core::runtime::sdk::os::os_init(&mut os_state, memory);
+    os_state.init_dvd(assets::ARCHIVE);
+    // Slot A memory card (.raw image or single-save .gci).
+    if let Ok(path) = std::env::var("GCRECOMP_MEMCARD") {
+        match MemoryCard::open(std::path::Path::new(&path)) {
+            Ok(card) => os_state.card.insert(0, card),
+            Err(e) => log::warn!("Memory card {path}: {e}"),
+        }
+    }
+    recompiled::load_image(memory);
+    let region_override = config.and_then(|c| c.config.region_override());
+    let region = Region::resolve(Region::detect(memory), region_override);
+    // Console settings: the SRAM file, then whatever the config sets.
+    if let Some(config) = config {
+        let path = &config.config.system.sram;
+        if !path.is_empty() {
+            match Sram::open(std::path::Path::new(path), region) {
+                Ok(sram) => os_state.sram = sram,
+                Err(e) => log::warn!("{e:#}"),
+            }
+        }
+        config.apply_system(&mut os_state.sram);
+    }
+    os_state.set_region(region, memory);
+
+    ctx.set_register(1, 0x817F_FF00); // r1 = stack pointer (top of MEM1)
+    ctx.set_register(2, DEFAULT_SDA_BASE); // SDA2 base
+    ctx.set_register(13, DEFAULT_SDA_BASE); // SDA base
+    region
+}
+
+/// Run the recompiled entry once; its writes to RAM persist in `memory`,
+/// which the render loop then presents as the framebuffer.
+fn run_entry(ctx: &mut CpuContext, memory: &mut MemoryManager) {
+    let entry = recompiled::ENTRY_POINT;
+    info!("Running recompiled entry point 0x{:08X}...", entry);
+    // Give the recompiled boot code a few seconds, then stop it (it spins on
+    // hardware we don't fully emulate). The window then shows the resulting XFB.
+    gcrecomp_core::runtime::arm_watchdog(5);
+    FunctionCallLogger::enable();
+    let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        recompiled::call_function_by_address(entry, ctx, memory)
+    }));
+    let error = match r {
+        Ok(Ok(v)) => {
+            info!("Recompiled entry 0x{:08X} returned {:?}", entry, v);
+            None
+        }
+        Ok(Err(e)) => {
+            log::warn!("Recompiled entry 0x{:08X} error: {e}", entry);
+            Some(format!("entry 0x{entry:08X}: {e:#}"))
+        }
+        Err(_) => {
+            log::warn!("Recompiled entry 0x{:08X} panicked (contained)", entry);
+            Some(format!("entry 0x{entry:08X} panicked"))
+        }
+    };
+    // A callee's panic its caller carried on past (`[runtime] catch_panics`).
+    let fault = gcrecomp_core::runtime::take_panic();
+    if let Some(fault) = &fault {
+        log::warn!("Recompiled code faulted during the entry: {fault}");
+    }
+    FunctionCallLogger::disable();
+    if let Some(error) = error.or_else(|| fault.map(|f| f.to_string())) {
+        write_crash_report(error, ctx, memory);
+    }
+}
+
+/// Write a crash bundle to `gcrecomp-crash-<unix time>.zip` in the working
+/// directory, for attaching to a bug report.
+fn write_crash_report(error: String, ctx: &CpuContext, memory: &MemoryManager) {
+    let config = gcrecomp_core::config::Config::load().ok();
+    let report = CrashReport {
+        error,
+        context: Some(ctx),
+        memory: Some(memory),
+        config: config.as_ref(),
+        ..Default::default()
+    };
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let path = std::path::PathBuf::from(format!("gcrecomp-crash-{secs}.zip"));
+    match generate_crash_bundle(&path, &report) {
+        Ok(()) => info!("Wrote crash report to {}", path.display()),
+        Err(e) => log::warn!("Crash report {} failed: {e:#}", path.display()),
+    }
+}
+
+/// Write the hot-function profile to `GCRECOMP_PROFILE`, if set, for
+/// `gcrecomp recompile --profile-guided`.
+fn write_profile(runtime: &mut gcrecomp_runtime::runtime::Runtime) {
+    let Ok(path) = std::env::var("GCRECOMP_PROFILE") else {
+        return;
+    };
+    runtime
+        .perf_mut()
+        .record_calls(gcrecomp_core::runtime::take_call_counts());
+    let hot = runtime.perf().hot_functions();
+    let result = serde_json::to_string(&hot)
+        .map_err(anyhow::Error::from)
+        .and_then(|json| std::fs::write(&path, json).map_err(anyhow::Error::from));
+    match result {
+        Ok(()) => info!("Wrote profile of {} functions to {path}", hot.len()),
+        Err(e) => log::warn!("Profile write to {path} failed: {e}"),
+    }
+}
+
+/// Halt the frame loop for gdb on Ctrl-C, or after a single step. gdb's `s`
+/// advances one frame (through the runtime's pause/step), `c` resumes. Returns
+/// false once the client is gone.
+fn service_gdb(
+    server: &Mutex<GdbServer>,
+    halt_next: &mut bool,
+    runtime: &mut gcrecomp_runtime::runtime::Runtime,
+    ctx: &mut CpuContext,
+    memory: &mut MemoryManager,
+) -> bool {
+    let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+    let reason = if std::mem::take(halt_next) {
+        StopReason::Step
+    } else {
+        match server.poll_interrupt() {
+            Ok(true) => StopReason::Interrupt,
+            Ok(false) => return true,
+            Err(e) => {
+                info!("gdb disconnected: {e}");
+                return false;
+            }
+        }
+    };
+    runtime.set_paused(true);
+    match server.session(reason, ctx, memory) {
+        Ok(Resume::Continue) => runtime.set_paused(false),
+        Ok(Resume::Step) => {
+            runtime.step_frame();
+            *halt_next = true;
         }
+        Ok(Resume::Detach) => {
+            runtime.set_paused(false);
+            return false;
+        }
+        Err(e) => {
+            log::warn!("gdb session ended: {e}");
+            runtime.set_paused(false);
+            return false;
+        }
+    }
+    true
+}
+
+/// Cheat list (Dolphin `$Name` + code lines) from the file in `GCRECOMP_CHEATS`.
+fn load_cheats() -> CheatEngine {
+    let mut cheats = CheatEngine::new();
+    let Ok(path) = std::env::var("GCRECOMP_CHEATS") else {
+        return cheats;
+    };
+    match std::fs::read_to_string(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|text| cheats.load_list(&text))
+    {
+        Ok(n) => info!("Loaded {n} cheat code(s) from {path}"),
+        Err(e) => log::warn!("Failed to load cheats from {path}: {e:#}"),
     }
+    cheats
 }
 
 /// Parse a u32 from decimal or `0x`-prefixed hex.
@@ -113,6 +369,44 @@ fn parse_u32(s: &str) -> Option<u32> {
     }
 }
 
+/// Default F12 screenshot location: `screenshots/<unix millis>.png`.
+fn screenshot_path() -> std::path::PathBuf {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    std::path::PathBuf::from("screenshots").join(format!("{millis}.png"))
+}
+
+/// Splash logo from `GCRECOMP_SPLASH` (image path; built-in logo otherwise),
+/// on screen for `GCRECOMP_SPLASH_MS` (default 2000, `0` disables).
+fn load_splash() -> Option<SplashScreen> {
+    let ms = std::env::var("GCRECOMP_SPLASH_MS")
+        .ok()
+        .and_then(|s| s.parse::<u64>().ok())
+        .unwrap_or(2000);
+    if ms == 0 {
+        return None;
+    }
+    let mut splash = SplashScreen::new().with_duration(ms);
+    if let Ok(path) = std::env::var("GCRECOMP_SPLASH") {
+        splash = splash.with_image(std::path::PathBuf::from(path));
+    }
+    Some(splash)
+}
+
+/// F9 recording directory: `GCRECOMP_RECORD` if set, else `recordings/<unix millis>`.
+fn recording_dir() -> std::path::PathBuf {
+    if let Ok(dir) = std::env::var("GCRECOMP_RECORD") {
+        return dir.into();
+    }
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    std::path::PathBuf::from("recordings").join(millis.to_string())
+}
+
 /// BT.601 YUV (0-255) -> RGB (0-255).
 fn yuv_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
     let y = y as f32;
@@ -170,7 +464,11 @@ impl ApplicationHandler for GameApp {
             }
         };
 
-        let mut runtime = match gcrecomp_runtime::runtime::Runtime::new() {
+        let runtime = match self.seed {
+            Some(seed) => gcrecomp_runtime::runtime::Runtime::new_seeded(seed),
+            None => gcrecomp_runtime::runtime::Runtime::new(),
+        };
+        let mut runtime = match runtime {
             Ok(r) => r,
             Err(e) => {
                 log::warn!("Runtime init failed ({e}); exiting event loop.");
@@ -191,6 +489,13 @@ impl ApplicationHandler for GameApp {
         if let Err(e) = runtime.initialize_audio() {
             log::warn!("Audio init failed (continuing without audio): {e}");
         }
+        if std::env::var("GCRECOMP_RECORD").is_ok() {
+            if let Err(e) = runtime.start_recording(&recording_dir()) {
+                log::warn!("Could not start recording: {e}");
+            }
+        }
+        runtime.attach_mmio(&mut self.memory);
+        runtime.set_region(self.region);
         info!("Runtime initialized");
 
         self.window = Some(window);
@@ -208,9 +513,22 @@ impl ApplicationHandler for GameApp {
             None => return,
         };
 
+        // Any key skips the splash (and does nothing else).
+        if let (Some(splash), WindowEvent::KeyboardInput { event, .. }) = (&mut self.splash, &event)
+        {
+            if event.state == winit::event::ElementState::Pressed {
+                splash.skip();
+                return;
+            }
+        }
+
         match event {
             WindowEvent::CloseRequested => {
                 info!("Window close requested");
+                write_profile(runtime);
+                if let Err(e) = runtime.stop_recording() {
+                    log::warn!("Failed to finalize recording: {e}");
+                }
                 event_loop.exit();
             }
             WindowEvent::Resized(size) => {
@@ -230,35 +548,202 @@ impl ApplicationHandler for GameApp {
                 self.menu_visible = !self.menu_visible;
                 info!("Menu toggle: {}", self.menu_visible);
             }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F12),
+                        state: winit::event::ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(renderer) = runtime.renderer_mut() {
+                    if let Err(e) = renderer.save_screenshot(&screenshot_path()) {
+                        log::warn!("Screenshot failed: {e}");
+                    }
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::Pause | NamedKey::F5),
+                        state: winit::event::ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let paused = !runtime.is_paused();
+                runtime.set_paused(paused);
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F6),
+                        state: winit::event::ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => {
+                runtime.step_frame();
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F9),
+                        state: winit::event::ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                let result = if runtime.is_recording() {
+                    runtime.stop_recording().map(|_| ())
+                } else {
+                    runtime.start_recording(&recording_dir())
+                };
+                if let Err(e) = result {
+                    log::warn!("Recording toggle failed: {e}");
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key: Key::Named(NamedKey::F3),
+                        state: winit::event::ElementState::Pressed,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => {
+                if let Some(renderer) = runtime.renderer_mut() {
+                    renderer.overlay_mut().toggle();
+                    info!("Debug overlay: {}", renderer.overlay().is_visible());
+                }
+            }
             WindowEvent::RedrawRequested => {
+                if let Some(splash) = self.splash.as_mut() {
+                    match splash.frame(std::time::Instant::now()) {
+                        Some(rgba) => {
+                            if let Some(renderer) = runtime.renderer_mut() {
+                                if let Err(e) =
+                                    renderer.present_framebuffer(&rgba, CANVAS_W, CANVAS_H)
+                                {
+                                    log::warn!("Present error: {e}");
+                                }
+                            }
+                            return;
+                        }
+                        None => self.splash = None,
+                    }
+                }
+                if let Some(server) = &self.gdb {
+                    let (ctx, memory) = (&mut self.ctx, &mut self.memory);
+                    if !service_gdb(server, &mut self.gdb_halt_next, runtime, ctx, memory) {
+                        self.gdb = None;
+                    }
+                }
+                if !runtime.is_paused() {
+                    if let Err(e) = self.cheats.apply(&mut self.memory) {
+                        log::warn!("Cheat apply failed: {e:#}");
+                    }
+                }
                 // Present the emulated external framebuffer (XFB) read from RAM.
                 let (addr, w, h) = (self.xfb_addr, self.xfb_w, self.xfb_h);
                 let rgba = read_xfb_rgba(&self.memory, addr, w, h);
+                if !runtime.is_paused() {
+                    if let Err(e) = runtime.record_frame() {
+                        log::warn!("Recording stopped: {e}");
+                    }
+                    runtime.perf_mut().record_frame();
+                }
+                runtime.update_overlay(self.ctx.pc);
                 if let Some(renderer) = runtime.renderer_mut() {
                     if let Err(e) = renderer.present_framebuffer(&rgba, w, h) {
                         log::warn!("Present error: {e}");
                     }
+                    for path in gcrecomp_lua::bindings::runtime::take_screenshot_requests() {
+                        if let Err(e) = renderer.save_screenshot(std::path::Path::new(&path)) {
+                            log::warn!("Screenshot to {path} failed: {e}");
+                        }
+                    }
                 }
             }
             _ => {}
         }
     }
 
-    fn about_to_wait(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(request) = gcrecomp_core::runtime::take_shutdown_request() {
+            info!("Game requested {:?}", request);
+            if let Some(runtime) = self.runtime.as_mut() {
+                write_profile(runtime);
+                runtime.shutdown();
+            }
+            match request {
+                ShutdownRequest::Restart { .. } => {
+                    self.memory = MemoryManager::new();
+                    self.ctx = CpuContext::new();
+                    self.region = boot(
+                        &mut self.memory,
+                        &mut self.ctx,
+                        self.seed,
+                        self.config.as_ref(),
+                    );
+                    if let Some(runtime) = self.runtime.as_mut() {
+                        runtime.attach_mmio(&mut self.memory);
+                        runtime.set_region(self.region);
+                    }
+                    if let Err(e) = self.cheats.apply(&mut self.memory) {
+                        log::warn!("Cheat apply failed: {e:#}");
+                    }
+                    run_entry(&mut self.ctx, &mut self.memory);
+                    if let Some(runtime) = self.runtime.as_mut() {
+                        if let Err(e) = runtime.initialize_audio() {
+                            log::warn!("Audio restart failed: {e}");
+                        }
+                    }
+                }
+                ShutdownRequest::ReturnToMenu | ShutdownRequest::PowerOff => {
+                    event_loop.exit();
+                    return;
+                }
+            }
+        }
+        let mut redraw = true;
         if let Some(runtime) = self.runtime.as_mut() {
             if let Err(e) = runtime.update() {
                 log::warn!("Runtime update error: {}", e);
             }
+            // Paused games run no handlers, except for the field a step just ran
+            if !runtime.is_paused() || runtime.step_pending() {
+                if let Err(e) = runtime.dispatch_interrupts(
+                    &mut self.ctx,
+                    &mut self.memory,
+                    recompiled::call_function_by_address,
+                ) {
+                    log::warn!("Interrupt handler failed: {e:#}");
+                }
+            }
+            // With the frame limiter on, sleep until the next frame instead of spinning.
+            redraw = runtime.frame_due(std::time::Instant::now());
+            event_loop.set_control_flow(match runtime.next_frame_deadline() {
+                Some(deadline) => ControlFlow::WaitUntil(deadline),
+                None => ControlFlow::Poll,
+            });
         }
-        if let Some(window) = &self.window {
-            window.request_redraw();
+        if redraw {
+            if let Some(window) = &self.window {
+                window.request_redraw();
+            }
         }
     }
 }
 
 fn main() -> Result<()> {
     // 1. Init logging
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let _ = gcrecomp_core::logging::init(None, log::LevelFilter::Info);
     info!("GCRecomp game runtime starting");
 
     // 2. Init Lua engine and load UI screens
@@ -1,10 +1,22 @@
 // CLI command handlers
 use anyhow::{Context, Result};
-use gcrecomp_core::recompiler::{parser::DolFile, pipeline::RecompilationPipeline};
+use gcrecomp_core::config::Config;
+use gcrecomp_core::recompiler::{
+    analysis::similarity::SimilarityIndex,
+    fidb::FidDatabase,
+    manifest::BatchManifest,
+    optimizer::{OptLevel, Optimizer},
+    parser::DolFile,
+    pipeline::{RecompilationPipeline, RecompileOptions},
+    profile::HotProfile,
+    report::{self, ReportDiff},
+    symbols::SymbolMap,
+};
+use gcrecomp_core::runtime::sdk::dvd::{self, Codec};
 use std::fs;
 use std::path::{Path, PathBuf};
 
-pub fn analyze_dol(dol_file: &Path, _use_reoxide: bool) -> Result<()> {
+pub fn analyze_dol(dol_file: &Path, json: Option<&Path>, _use_reoxide: bool) -> Result<()> {
     println!("Reading DOL file: {}", dol_file.display());
 
     let data = fs::read(dol_file)
@@ -52,12 +64,67 @@ pub fn analyze_dol(dol_file: &Path, _use_reoxide: bool) -> Result<()> {
         );
     }
 
+    if let Some(path) = json {
+        let metadata = RecompilationPipeline::analyze_metadata(&dol).context("Analysis failed")?;
+        let text = serde_json::to_string_pretty(&metadata)?;
+        fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("\nAnalysis metadata written to: {}", path.display());
+    }
+
     Ok(())
 }
 
-pub fn recompile_dol(dol_file: &Path, output_dir: Option<&Path>, _use_reoxide: bool) -> Result<()> {
+/// Where function names come from besides discovery.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NamingSources<'a> {
+    /// Symbol map (`.map`); trusted over everything else.
+    pub symbols: Option<&'a Path>,
+    /// Function ID database (JSON signatures).
+    pub fidb: Option<&'a Path>,
+    /// Also suggest names by structural similarity to the `fidb` signatures.
+    pub native_bsim: bool,
+}
+
+/// How functions are optimized and generated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CodegenSettings<'a> {
+    /// Optimizer passes; `[recompiler] opt_level` when unset.
+    pub opt_level: Option<OptLevel>,
+    /// Runtime profile: hot functions aggressively, the rest lightly.
+    pub profile: Option<&'a Path>,
+    /// Functions generated at once; one per core when unset.
+    pub jobs: Option<usize>,
+    /// Generate only these functions and their direct callees (all when empty).
+    pub only: &'a [u32],
+    /// Reuse unchanged functions' generated code from this directory.
+    pub cache: Option<&'a Path>,
+}
+
+pub fn recompile_dol(
+    dol_file: &Path,
+    output_dir: Option<&Path>,
+    naming: NamingSources,
+    codegen: CodegenSettings,
+    report: Option<&Path>,
+    _use_reoxide: bool,
+) -> Result<()> {
     println!("Recompiling DOL file: {}", dol_file.display());
 
+    // --opt-level wins; otherwise `[recompiler] opt_level` from the config file
+    // or GCRECOMP_RECOMPILER_OPT_LEVEL.
+    let opt_level = match codegen.opt_level {
+        Some(level) => level,
+        None => {
+            let loaded = Config::load()?;
+            println!(
+                "Optimization level: {} ({})",
+                loaded.config.recompiler.opt_level,
+                loaded.source("recompiler.opt_level")
+            );
+            loaded.config.opt_level()
+        }
+    };
+
     let data = fs::read(dol_file)
         .with_context(|| format!("Failed to read DOL file: {}", dol_file.display()))?;
     let dol = DolFile::parse(&data, dol_file.to_str().unwrap_or("unknown.dol"))
@@ -74,21 +141,95 @@ pub fn recompile_dol(dol_file: &Path, output_dir: Option<&Path>, _use_reoxide: b
         None => PathBuf::from("recompiled/src/lib.rs"),
     };
 
+    let symbol_map = match naming.symbols {
+        Some(path) => {
+            let text = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read symbol map: {}", path.display()))?;
+            let map = SymbolMap::parse(&text);
+            println!("Loaded {} symbols from {}", map.len(), path.display());
+            Some(map)
+        }
+        None => None,
+    };
+
+    let profile = match codegen.profile {
+        Some(path) => {
+            let profile = HotProfile::load(path)?;
+            println!(
+                "Profile-guided: {} hot functions from {}",
+                profile.hot_count(),
+                path.display()
+            );
+            Some(profile)
+        }
+        None => None,
+    };
+
+    let fidb = match naming.fidb {
+        Some(path) => {
+            let db = FidDatabase::load(path)?;
+            println!(
+                "Loaded {} function signatures from {}",
+                db.len(),
+                path.display()
+            );
+            Some(db)
+        }
+        None => None,
+    };
+    let similarity = match &fidb {
+        Some(db) if naming.native_bsim => Some(SimilarityIndex::from_database(db)),
+        _ => None,
+    };
+
     // Run the real decode -> analyze -> codegen pipeline (no Ghidra required).
-    RecompilationPipeline::recompile(&dol, output_file.to_str().context("Invalid output path")?)
-        .context("Recompilation pipeline failed")?;
+    let options = RecompileOptions {
+        symbols: symbol_map,
+        fidb,
+        similarity,
+        optimizer: Optimizer::with_level(opt_level),
+        profile,
+        report_dir: report.map(Path::to_path_buf),
+        jobs: codegen.jobs,
+        only: (!codegen.only.is_empty()).then(|| codegen.only.to_vec()),
+        cache_dir: codegen.cache.map(Path::to_path_buf),
+        ..Default::default()
+    };
+    let summary = RecompilationPipeline::recompile_with_options(
+        &dol,
+        output_file.to_str().context("Invalid output path")?,
+        &options,
+    )
+    .context("Recompilation pipeline failed")?;
 
     println!("Generated Rust code written to: {}", output_file.display());
+    if let Some(dir) = codegen.cache {
+        println!(
+            "Function cache: {} hits, {} misses ({})",
+            summary.stats.cache_hits,
+            summary.stats.cache_misses,
+            dir.display()
+        );
+    }
+    if let Some(dir) = report {
+        println!("Report written to: {}", dir.join("index.md").display());
+    }
 
     Ok(())
 }
 
-pub fn build_dol(dol_file: &Path, output_dir: Option<&Path>, use_reoxide: bool) -> Result<()> {
+pub fn build_dol(
+    dol_file: &Path,
+    output_dir: Option<&Path>,
+    naming: NamingSources,
+    codegen: CodegenSettings,
+    use_reoxide: bool,
+) -> Result<()> {
     println!("Building recompiled game from: {}", dol_file.display());
 
     // Step 1: Recompile DOL -> Rust (decode + codegen, no Ghidra required).
     println!("Step 1/2: Recompiling to Rust...");
-    recompile_dol(dol_file, output_dir, use_reoxide)?;
+    recompile_dol(dol_file, output_dir, naming, codegen, None, use_reoxide)?;
 
     // Step 2: Build the `game` crate into a native executable.
     println!("\nStep 2/2: Building the game crate...");
@@ -105,3 +246,105 @@ pub fn build_dol(dol_file: &Path, output_dir: Option<&Path>, use_reoxide: bool)
 
     Ok(())
 }
+
+/// Recompile the DOLs in `manifest`, carrying on past failures. Fails at the
+/// end if any entry did, after the summary is written.
+pub fn batch_recompile(manifest: &Path, jobs: Option<usize>, summary: Option<&Path>) -> Result<()> {
+    let batch = BatchManifest::load(manifest)?;
+    // Entries without an opt_level use `[recompiler] opt_level`.
+    let default_level = Config::load()?.config.opt_level();
+    println!(
+        "Recompiling {} DOLs from {}",
+        batch.entries.len(),
+        manifest.display()
+    );
+    let result = batch.run(jobs, default_level);
+
+    for entry in &result.entries {
+        match &entry.error {
+            None => println!(
+                "  ok      {}  {} functions, {:.1}% translated",
+                entry.dol.display(),
+                entry.functions,
+                entry.coverage
+            ),
+            Some(error) => println!("  FAILED  {}  {error}", entry.dol.display()),
+        }
+    }
+    let summary_path = match summary {
+        Some(path) => path.to_path_buf(),
+        None => manifest
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join("batch-summary.json"),
+    };
+    result.write(&summary_path)?;
+    println!(
+        "{} succeeded, {} failed; summary written to {}",
+        result.succeeded(),
+        result.failed(),
+        summary_path.display()
+    );
+    if result.failed() > 0 {
+        anyhow::bail!(
+            "{} of {} DOLs failed",
+            result.failed(),
+            result.entries.len()
+        );
+    }
+    Ok(())
+}
+
+/// Compare two `--report` directories function by function.
+pub fn diff_reports(old: &Path, new: &Path) -> Result<()> {
+    let read = |dir: &Path| {
+        report::read_report(dir).with_context(|| format!("Failed to read report {}", dir.display()))
+    };
+    let diff = ReportDiff::compare(&read(old)?, &read(new)?);
+
+    let list = |heading: &str, pages: &[report::FunctionPage]| {
+        if !pages.is_empty() {
+            println!("{heading}:");
+            for page in pages {
+                println!("  0x{:08X}  {}  ({})", page.address, page.name, page.status);
+            }
+        }
+    };
+    list("Newly succeeded", &diff.newly_succeeded);
+    list("Newly stubbed", &diff.newly_stubbed);
+    if !diff.resized.is_empty() {
+        println!("Changed size:");
+        for (before, after) in &diff.resized {
+            println!(
+                "  0x{:08X}  {}  {} -> {} bytes ({:+})",
+                after.address,
+                after.name,
+                before.rust_len,
+                after.rust_len,
+                after.rust_len as i64 - before.rust_len as i64
+            );
+        }
+    }
+    list("Added", &diff.added);
+    list("Removed", &diff.removed);
+    println!("{}", diff.summary());
+    Ok(())
+}
+
+/// Archive `input_dir` for `include_bytes!` (see `VirtualFilesystem`).
+pub fn pack_assets(input_dir: &Path, output: &Path, compress: bool) -> Result<()> {
+    let codec = if compress { Codec::Yaz0 } else { Codec::Stored };
+    let (archive, files) = dvd::pack_directory(input_dir, codec)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to pack {}", input_dir.display()))?;
+    fs::write(output, &archive).with_context(|| format!("Failed to write {}", output.display()))?;
+    println!(
+        "Packed {} files ({} bytes, {:?}) into {}",
+        files,
+        archive.len(),
+        codec,
+        output.display()
+    );
+    println!("Embed it by copying it to game/assets.bin before building.");
+    Ok(())
+}
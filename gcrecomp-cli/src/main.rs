@@ -3,7 +3,11 @@ mod commands;
 mod output;
 
 use clap::Parser;
-use commands::{analyze_dol, build_dol, recompile_dol};
+use commands::{
+    analyze_dol, batch_recompile, build_dol, diff_reports, pack_assets, recompile_dol,
+    CodegenSettings, NamingSources,
+};
+use gcrecomp_core::recompiler::optimizer::OptLevel;
 use indicatif::{ProgressBar, ProgressStyle};
 use std::path::PathBuf;
 
@@ -14,6 +18,15 @@ use std::path::PathBuf;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Log filter, e.g. `warn` or `info,gcrecomp_core::recompiler=debug`
+    /// (default: `[logging] filter`, then RUST_LOG)
+    #[arg(long, global = true, value_name = "FILTER")]
+    log: Option<String>,
+
+    /// Also append log output to this file
+    #[arg(long, global = true, value_name = "PATH")]
+    log_file: Option<PathBuf>,
 }
 
 #[derive(clap::Subcommand)]
@@ -24,6 +37,11 @@ enum Commands {
         #[arg(short, long)]
         dol_file: PathBuf,
 
+        /// Also write the functions, call graph, unreachable functions and
+        /// coverage as JSON to this file
+        #[arg(long, value_name = "PATH")]
+        json: Option<PathBuf>,
+
         /// Use ReOxide backend (default: headless CLI)
         #[arg(long)]
         use_reoxide: bool,
@@ -38,6 +56,47 @@ enum Commands {
         #[arg(short, long)]
         output_dir: Option<PathBuf>,
 
+        /// Symbol map (CodeWarrior or Dolphin `.map`) naming the functions
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+
+        /// Optimizer passes: none, basic or aggressive (default: from config)
+        #[arg(long)]
+        opt_level: Option<OptLevel>,
+
+        /// Runtime profile (`GCRECOMP_PROFILE` output): optimize its hot
+        /// functions aggressively and the rest lightly
+        #[arg(long)]
+        profile_guided: Option<PathBuf>,
+
+        /// Functions to generate at once (default: one per CPU core)
+        #[arg(long)]
+        jobs: Option<usize>,
+
+        /// Generate only the function at this address (hex) and its direct
+        /// callees, stubbing the rest; repeatable
+        #[arg(long, value_name = "ADDR", value_parser = parse_address)]
+        only: Vec<u32>,
+
+        /// Keep generated functions in this directory and reuse the ones
+        /// whose code hasn't changed on the next run
+        #[arg(long, value_name = "DIR")]
+        cache: Option<PathBuf>,
+
+        /// Function ID database (JSON signatures) naming known SDK functions
+        #[arg(long)]
+        fidb: Option<PathBuf>,
+
+        /// Suggest names for functions the database doesn't match exactly by
+        /// structural similarity to its signatures (native BSim stand-in)
+        #[arg(long, requires = "fidb")]
+        native_bsim: bool,
+
+        /// Write a Markdown report (disassembly beside generated Rust for
+        /// each function, plus an index) into this directory
+        #[arg(long)]
+        report: Option<PathBuf>,
+
         /// Use ReOxide backend (default: headless CLI)
         #[arg(long)]
         use_reoxide: bool,
@@ -52,49 +111,194 @@ enum Commands {
         #[arg(short, long)]
         output_dir: Option<PathBuf>,
 
+        /// Symbol map (CodeWarrior or Dolphin `.map`) naming the functions
+        #[arg(long)]
+        symbols: Option<PathBuf>,
+
+        /// Optimizer passes: none, basic or aggressive (default: from config)
+        #[arg(long)]
+        opt_level: Option<OptLevel>,
+
+        /// Runtime profile (`GCRECOMP_PROFILE` output): optimize its hot
+        /// functions aggressively and the rest lightly
+        #[arg(long)]
+        profile_guided: Option<PathBuf>,
+
+        /// Functions to generate at once (default: one per CPU core)
+        #[arg(long)]
+        jobs: Option<usize>,
+
+        /// Generate only the function at this address (hex) and its direct
+        /// callees, stubbing the rest; repeatable
+        #[arg(long, value_name = "ADDR", value_parser = parse_address)]
+        only: Vec<u32>,
+
+        /// Keep generated functions in this directory and reuse the ones
+        /// whose code hasn't changed on the next run
+        #[arg(long, value_name = "DIR")]
+        cache: Option<PathBuf>,
+
+        /// Function ID database (JSON signatures) naming known SDK functions
+        #[arg(long)]
+        fidb: Option<PathBuf>,
+
+        /// Suggest names for functions the database doesn't match exactly by
+        /// structural similarity to its signatures (native BSim stand-in)
+        #[arg(long, requires = "fidb")]
+        native_bsim: bool,
+
         /// Use ReOxide backend (default: headless CLI)
         #[arg(long)]
         use_reoxide: bool,
     },
+    /// Recompile every DOL a manifest lists, each with its own options, and
+    /// write a summary of how each one went
+    Batch {
+        /// TOML (or `.json`) manifest with one `[[entries]]` table per DOL
+        manifest: PathBuf,
+
+        /// DOLs to recompile at once (default: the manifest's `jobs`)
+        #[arg(long)]
+        jobs: Option<usize>,
+
+        /// Summary JSON to write (default: `batch-summary.json` beside the
+        /// manifest)
+        #[arg(long)]
+        summary: Option<PathBuf>,
+    },
+    /// Compare two `recompile --report` directories: functions that newly
+    /// succeed, newly fall back to stubs, or changed generated size
+    Diff {
+        /// Report from before the change
+        #[arg(long)]
+        old: PathBuf,
+
+        /// Report from after the change
+        #[arg(long)]
+        new: PathBuf,
+    },
+    /// Pack a directory of extracted game files into the GCFS archive the
+    /// game embeds (`game/assets.bin`)
+    PackAssets {
+        /// Directory whose files (and subdirectories) become the disc contents
+        input_dir: PathBuf,
+
+        /// Archive to write
+        output: PathBuf,
+
+        /// Yaz0-compress each file (default: stored uncompressed)
+        #[arg(long)]
+        compress: bool,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
-    env_logger::init();
-
     let cli = Cli::parse();
+    init_logging(&cli)?;
 
     match cli.command {
         Commands::Analyze {
             dol_file,
+            json,
             use_reoxide,
         } => {
             let pb = create_progress_bar("Analyzing DOL file...");
-            analyze_dol(&dol_file, use_reoxide)?;
+            analyze_dol(&dol_file, json.as_deref(), use_reoxide)?;
             pb.finish_with_message("Analysis complete");
         }
         Commands::Recompile {
             dol_file,
             output_dir,
+            symbols,
+            opt_level,
+            profile_guided,
+            jobs,
+            only,
+            cache,
+            fidb,
+            native_bsim,
+            report,
             use_reoxide,
         } => {
             let pb = create_progress_bar("Recompiling DOL file...");
-            recompile_dol(&dol_file, output_dir.as_deref(), use_reoxide)?;
+            recompile_dol(
+                &dol_file,
+                output_dir.as_deref(),
+                NamingSources {
+                    symbols: symbols.as_deref(),
+                    fidb: fidb.as_deref(),
+                    native_bsim,
+                },
+                CodegenSettings {
+                    opt_level,
+                    profile: profile_guided.as_deref(),
+                    jobs,
+                    only: &only,
+                    cache: cache.as_deref(),
+                },
+                report.as_deref(),
+                use_reoxide,
+            )?;
             pb.finish_with_message("Recompilation complete");
         }
         Commands::Build {
             dol_file,
             output_dir,
+            symbols,
+            opt_level,
+            profile_guided,
+            jobs,
+            only,
+            cache,
+            fidb,
+            native_bsim,
             use_reoxide,
         } => {
             let pb = create_progress_bar("Building recompiled game...");
-            build_dol(&dol_file, output_dir.as_deref(), use_reoxide)?;
+            build_dol(
+                &dol_file,
+                output_dir.as_deref(),
+                NamingSources {
+                    symbols: symbols.as_deref(),
+                    fidb: fidb.as_deref(),
+                    native_bsim,
+                },
+                CodegenSettings {
+                    opt_level,
+                    profile: profile_guided.as_deref(),
+                    jobs,
+                    only: &only,
+                    cache: cache.as_deref(),
+                },
+                use_reoxide,
+            )?;
             pb.finish_with_message("Build complete");
         }
+        Commands::Batch {
+            manifest,
+            jobs,
+            summary,
+        } => batch_recompile(&manifest, jobs, summary.as_deref())?,
+        Commands::Diff { old, new } => diff_reports(&old, &new)?,
+        Commands::PackAssets {
+            input_dir,
+            output,
+            compress,
+        } => pack_assets(&input_dir, &output, compress)?,
     }
 
     Ok(())
 }
 
+/// A hex address, with or without `0x`.
+fn parse_address(s: &str) -> Result<u32, String> {
+    let hex = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u32::from_str_radix(hex, 16).map_err(|e| format!("invalid address '{s}': {e}"))
+}
+
 fn create_progress_bar(message: &str) -> ProgressBar {
     let pb = ProgressBar::new_spinner();
     pb.set_style(
@@ -106,3 +310,22 @@ fn create_progress_bar(message: &str) -> ProgressBar {
     pb.set_message(message.to_string());
     pb
 }
+
+/// `--log` wins over `[logging] filter`, which wins over RUST_LOG; `--log-file`
+/// over `[logging] file`.
+fn init_logging(cli: &Cli) -> anyhow::Result<()> {
+    use gcrecomp_core::logging;
+    let _ = logging::init(None, log::LevelFilter::Info);
+    // A broken config file is reported by the commands that need it.
+    if let Ok(loaded) = gcrecomp_core::config::Config::load() {
+        loaded.apply_logging();
+    }
+    if let Some(spec) = &cli.log {
+        logging::set_filter_spec(spec).map_err(|e| anyhow::anyhow!("--log: {e}"))?;
+    }
+    if let Some(path) = &cli.log_file {
+        logging::set_file_sink(Some(path))
+            .map_err(|e| anyhow::anyhow!("--log-file {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! `gcrecomp analyze --json` over a small hand-built DOL
+
+use serde_json::Value;
+use std::process::Command;
+
+mod common;
+use common::build_dol;
+
+const BLR: u32 = 0x4E80_0020;
+
+#[test]
+fn analyze_writes_functions_and_call_graph() {
+    let words = [
+        // 0x80003000: calls 0x80003014 twice
+        0x7C08_02A6, // mflr r0
+        0x4800_0011, // bl 0x80003014
+        0x4800_000D, // bl 0x80003014
+        0x7C08_03A6, // mtlr r0
+        BLR,
+        // 0x80003014
+        0x3860_0001, // li r3,1
+        BLR,
+        // 0x8000301C: never called
+        0x3860_0002, // li r3,2
+        BLR,
+    ];
+    let dir = std::env::temp_dir().join(format!("gcrecomp_analyze_json_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dol = dir.join("test.dol");
+    std::fs::write(&dol, build_dol(&words)).unwrap();
+    let json = dir.join("analysis.json");
+
+    let status = Command::new(env!("CARGO_BIN_EXE_gcrecomp"))
+        .arg("analyze")
+        .arg("--dol-file")
+        .arg(&dol)
+        .arg("--json")
+        .arg(&json)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let metadata: Value = serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(metadata["schema_version"], 1);
+    assert_eq!(metadata["entry_point"], 0x8000_3000u32);
+    let addresses: Vec<u64> = metadata["functions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| f["address"].as_u64().unwrap())
+        .collect();
+    assert_eq!(addresses, [0x8000_3000, 0x8000_3014, 0x8000_301C]);
+
+    let edges = metadata["call_graph"]["edges"].as_array().unwrap();
+    assert_eq!(edges.len(), 1);
+    assert_eq!(edges[0]["caller"], 0);
+    assert_eq!(edges[0]["callee"], 1);
+    assert_eq!(
+        edges[0]["call_sites"],
+        serde_json::json!([0x8000_3004u32, 0x8000_3008u32])
+    );
+    assert_eq!(metadata["call_graph"]["nodes"][0]["is_entry_point"], true);
+    assert_eq!(
+        metadata["unreachable_functions"],
+        serde_json::json!([0x8000_301Cu32])
+    );
+    assert_eq!(metadata["coverage"]["functions"], 3);
+    assert_eq!(metadata["coverage"]["total_instructions"], 9);
+}
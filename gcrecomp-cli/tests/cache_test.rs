@@ -0,0 +1,96 @@
+//! `gcrecomp recompile --cache`: unchanged functions are reused across runs
+
+use std::path::Path;
+use std::process::Command;
+
+mod common;
+use common::build_dol;
+
+const BLR: u32 = 0x4E80_0020;
+
+/// Three functions; the last is a leaf the middle one inlines.
+fn program(leaf_value: u32) -> Vec<u32> {
+    vec![
+        // 0x80003000: calls 0x80003014
+        0x7C08_02A6, // mflr r0
+        0x4800_0011, // bl 0x80003014
+        0x7C08_03A6, // mtlr r0
+        0x6000_0000, // nop
+        BLR,
+        // 0x80003014: calls 0x80003024
+        0x7C08_02A6, // mflr r0
+        0x4800_000D, // bl 0x80003024
+        0x7C08_03A6, // mtlr r0
+        BLR,
+        // 0x80003024
+        0x3860_0000 | leaf_value, // li r3,leaf_value
+        BLR,
+    ]
+}
+
+/// Recompile `words` into `dir` with the cache in `dir/cache`; returns
+/// stdout and the generated module.
+fn recompile(dir: &Path, words: &[u32]) -> (String, String) {
+    let dol = dir.join("test.dol");
+    std::fs::write(&dol, build_dol(words)).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_gcrecomp"))
+        .arg("recompile")
+        .arg("--dol-file")
+        .arg(&dol)
+        .arg("--output-dir")
+        .arg(dir)
+        .arg("--cache")
+        .arg(dir.join("cache"))
+        .args(["--opt-level", "none"])
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    (
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        std::fs::read_to_string(dir.join("recompiled.rs")).unwrap(),
+    )
+}
+
+#[test]
+fn second_identical_run_is_all_hits() {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_cache_same_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let (first, first_module) = recompile(&dir, &program(2));
+    assert!(
+        first.contains("Function cache: 0 hits, 3 misses"),
+        "{first}"
+    );
+    let (second, second_module) = recompile(&dir, &program(2));
+    assert!(
+        second.contains("Function cache: 3 hits, 0 misses"),
+        "{second}"
+    );
+    assert_eq!(first_module, second_module);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn changed_function_and_its_inliner_regenerate() {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_cache_changed_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    recompile(&dir, &program(2));
+    let (stdout, module) = recompile(&dir, &program(3));
+    // The leaf changed, and so did the function it's inlined into.
+    assert!(
+        stdout.contains("Function cache: 1 hits, 2 misses"),
+        "{stdout}"
+    );
+
+    std::fs::remove_dir_all(dir.join("cache")).ok();
+    let (_, uncached) = recompile(&dir, &program(3));
+    assert_eq!(module, uncached);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
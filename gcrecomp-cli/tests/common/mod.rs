@@ -0,0 +1,14 @@
+//! Fixtures shared by the integration tests
+
+/// A DOL with one text section at 0x80003000 holding `words`, entry at its start.
+pub fn build_dol(words: &[u32]) -> Vec<u8> {
+    let mut dol = vec![0u8; 0x100];
+    dol[0x00..0x04].copy_from_slice(&0x100u32.to_be_bytes()); // text0 offset
+    dol[0x48..0x4C].copy_from_slice(&0x8000_3000u32.to_be_bytes()); // text0 address
+    dol[0x90..0x94].copy_from_slice(&(words.len() as u32 * 4).to_be_bytes()); // text0 size
+    dol[0xE0..0xE4].copy_from_slice(&0x8000_3000u32.to_be_bytes()); // entry
+    for w in words {
+        dol.extend_from_slice(&w.to_be_bytes());
+    }
+    dol
+}
@@ -0,0 +1,84 @@
+//! `gcrecomp diff` over two small hand-written reports
+
+use gcrecomp_core::recompiler::decoder::{DecodedInstruction, Instruction};
+use gcrecomp_core::recompiler::pipeline::Translation;
+use gcrecomp_core::recompiler::report::ReportWriter;
+use std::path::Path;
+use std::process::Command;
+
+fn blr(address: u32) -> Vec<DecodedInstruction> {
+    vec![Instruction::decode(0x4E80_0020, address).unwrap()]
+}
+
+/// A report with one page per `(address, rust, translation)`.
+fn write_report(dir: &Path, functions: &[(u32, &str, Option<Translation>)]) {
+    let mut report = ReportWriter::create(dir).unwrap();
+    for &(address, rust, translation) in functions {
+        let name = format!("sub_{address:08x}");
+        report
+            .write_function(&name, address, &blr(address), rust, translation)
+            .unwrap();
+    }
+    report.finish().unwrap();
+}
+
+#[test]
+fn diff_reports_status_changes_and_size() {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_diff_{}", std::process::id()));
+    let (old, new) = (dir.join("old"), dir.join("new"));
+    let stub = "// Stub for function\npub fn f() {}";
+    let body = "pub fn f() { ctx.set_register(3, 1); }";
+    write_report(
+        &old,
+        &[
+            (0x8000_3000, stub, None),
+            (0x8000_3100, body, Some(Translation::Recompiled)),
+            (0x8000_3200, body, Some(Translation::Recompiled)),
+            (0x8000_3300, body, Some(Translation::Partial)),
+            (0x8000_3400, body, Some(Translation::Recompiled)),
+        ],
+    );
+    write_report(
+        &new,
+        &[
+            (0x8000_3000, body, Some(Translation::Recompiled)),
+            (0x8000_3100, stub, None),
+            (0x8000_3200, "pub fn f() {}", Some(Translation::Recompiled)),
+            (0x8000_3300, body, Some(Translation::Partial)),
+            (0x8000_3500, body, Some(Translation::Interpreted)),
+        ],
+    );
+
+    let output = Command::new(env!("CARGO_BIN_EXE_gcrecomp"))
+        .args(["diff", "--old"])
+        .arg(&old)
+        .arg("--new")
+        .arg(&new)
+        .output()
+        .unwrap();
+    let missing = Command::new(env!("CARGO_BIN_EXE_gcrecomp"))
+        .args(["diff", "--old"])
+        .arg(dir.join("missing"))
+        .arg("--new")
+        .arg(&new)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for expected in [
+        "Newly succeeded:\n  0x80003000  sub_80003000  (fully translated)",
+        "Newly stubbed:\n  0x80003100  sub_80003100  (stubbed)",
+        "Changed size:\n  0x80003200  sub_80003200  38 -> 13 bytes (-25)",
+        "Added:\n  0x80003500",
+        "Removed:\n  0x80003400",
+        "1 newly succeeded, 1 newly stubbed, 1 changed size, 1 added, 1 removed, 1 unchanged",
+    ] {
+        assert!(stdout.contains(expected), "missing `{expected}`:\n{stdout}");
+    }
+
+    assert!(!missing.status.success());
+    let stderr = String::from_utf8(missing.stderr).unwrap();
+    assert!(stderr.contains("Failed to read report"), "{stderr}");
+}
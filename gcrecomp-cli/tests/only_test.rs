@@ -0,0 +1,97 @@
+//! `gcrecomp recompile --only` over a small hand-built DOL
+
+use std::process::Command;
+
+mod common;
+use common::build_dol;
+
+const BLR: u32 = 0x4E80_0020;
+
+/// Run `recompile --only` for each of `only` and return the generated module.
+fn recompile_only(name: &str, only: &[&str]) -> (bool, String, String) {
+    let words = [
+        // 0x80003000: calls 0x80003014
+        0x7C08_02A6, // mflr r0
+        0x4800_0011, // bl 0x80003014
+        0x7C08_03A6, // mtlr r0
+        0x6000_0000, // nop
+        BLR,
+        // 0x80003014: calls 0x80003024
+        0x7C08_02A6, // mflr r0
+        0x4800_000D, // bl 0x80003024
+        0x7C08_03A6, // mtlr r0
+        BLR,
+        // 0x80003024
+        0x3860_0002, // li r3,2
+        BLR,
+    ];
+    let dir = std::env::temp_dir().join(format!("gcrecomp_only_{name}_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dol = dir.join("test.dol");
+    std::fs::write(&dol, build_dol(&words)).unwrap();
+
+    let mut command = Command::new(env!("CARGO_BIN_EXE_gcrecomp"));
+    command
+        .arg("recompile")
+        .arg("--dol-file")
+        .arg(&dol)
+        .arg("--output-dir")
+        .arg(&dir)
+        .args(["--opt-level", "none"]);
+    for address in only {
+        command.args(["--only", address]);
+    }
+    let output = command.output().unwrap();
+    let module = std::fs::read_to_string(dir.join("recompiled.rs")).unwrap_or_default();
+    std::fs::remove_dir_all(&dir).ok();
+    (
+        output.status.success(),
+        module,
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+fn generated(module: &str, address: u32) -> bool {
+    module.contains(&format!("trace_call(0x{address:08X}u32)"))
+}
+
+fn stubbed(module: &str, address: u32) -> bool {
+    module.contains(&format!(
+        "at 0x{address:08X} (not selected by --only)\npub fn func_0x{address:08X}("
+    ))
+}
+
+#[test]
+fn only_generates_the_function_and_stubs_the_rest() {
+    let (ok, module, stderr) = recompile_only("leaf", &["0x80003024"]);
+    assert!(ok, "{stderr}");
+    assert!(generated(&module, 0x8000_3024), "{module}");
+    assert!(stubbed(&module, 0x8000_3000), "{module}");
+    assert!(stubbed(&module, 0x8000_3014), "{module}");
+    // Stubs keep the module whole: the dispatcher still names every function.
+    for address in [0x8000_3000u32, 0x8000_3014, 0x8000_3024] {
+        assert!(
+            module.contains(&format!(
+                "0x{address:08X}u32 => func_0x{address:08X}(ctx, memory)"
+            )),
+            "{module}"
+        );
+    }
+}
+
+#[test]
+fn only_includes_direct_callees() {
+    let (ok, module, stderr) = recompile_only("caller", &["80003000"]);
+    assert!(ok, "{stderr}");
+    assert!(generated(&module, 0x8000_3000), "{module}");
+    assert!(generated(&module, 0x8000_3014), "{module}");
+    // A callee's callee isn't pulled in.
+    assert!(stubbed(&module, 0x8000_3024), "{module}");
+
+    let (ok, _, stderr) = recompile_only("missing", &["0x80003004"]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("no function starts at 0x80003004"),
+        "{stderr}"
+    );
+}
@@ -0,0 +1,152 @@
+//! Decoder and code generator hot paths.
+//!
+//! `cargo bench -p gcrecomp-core --bench hot_paths`. Decode throughput is
+//! reported in instructions per second (criterion's `elem/s`). Numbers to
+//! compare against, and how CI does it, are in `benches/BASELINE.md`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use gcrecomp_core::recompiler::analysis::FunctionMetadata;
+use gcrecomp_core::recompiler::batch;
+use gcrecomp_core::recompiler::codegen::CodeGenerator;
+use gcrecomp_core::recompiler::decoder::{DecodedInstruction, Instruction};
+
+/// Roughly what game code looks like: frame setup, loads/stores, integer and
+/// float arithmetic, compares and branches.
+const MIX: &[u32] = &[
+    0x9421_FFE0, // stwu r1, -32(r1)
+    0x7C08_02A6, // mflr r0
+    0x9001_0024, // stw r0, 36(r1)
+    0x93E1_001C, // stw r31, 28(r1)
+    0x7C7F_1B78, // mr r31, r3
+    0x3860_0000, // li r3, 0
+    0x3C80_8010, // lis r4, 0x8010
+    0x8064_0010, // lwz r3, 16(r4)
+    0x2C03_0000, // cmpwi r3, 0
+    0x2803_8000, // cmplwi r3, 0x8000
+    0x7C63_2214, // add r3, r3, r4
+    0x7C64_1850, // subf r3, r4, r3
+    0x5463_103A, // rlwinm r3, r3, 2, 0, 29
+    0x6063_0001, // ori r3, r3, 1
+    0x7C63_21D6, // mullw r3, r3, r4
+    0xC023_0008, // lfs f1, 8(r3)
+    0xEC21_102A, // fadds f1, f1, f2
+    0xFC20_0890, // fmr f1, f1
+    0xD023_000C, // stfs f1, 12(r3)
+    0x4182_0008, // beq +8
+    0x8BE3_0001, // lbz r31, 1(r3)
+    0x7FE3_FB78, // mr r3, r31
+    0x83E1_001C, // lwz r31, 28(r1)
+    0x8001_0024, // lwz r0, 36(r1)
+    0x7C08_03A6, // mtlr r0
+    0x3821_0020, // addi r1, r1, 32
+    0x4E80_0020, // blr
+];
+
+/// Opcode 31/59/63 words, which all go through `decode_extended`.
+const EXTENDED: &[u32] = &[
+    0x7C08_02A6, // mflr r0
+    0x7C08_03A6, // mtlr r0
+    0x7C63_2214, // add
+    0x7C64_1850, // subf
+    0x7C63_21D6, // mullw
+    0x7C63_23D6, // divw
+    0x7C63_2038, // and
+    0x7C63_2378, // or
+    0x7C03_2000, // cmpw
+    0x7C03_2040, // cmplw
+    0x7C63_202E, // lwzx
+    0x7C63_0734, // extsh
+    0xEC21_102A, // fadds
+    0xFC20_0890, // fmr
+    0xFC01_1000, // fcmpu
+];
+
+fn decode_all(words: &[u32], base: u32) -> Vec<DecodedInstruction> {
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| Instruction::decode(w, base + i as u32 * 4).expect("bench word decodes"))
+        .collect()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for (name, words) in [("mix", MIX), ("decode_extended", EXTENDED)] {
+        // Repeat to a block of ~4k words so per-iteration overhead vanishes.
+        let block: Vec<u32> = words.iter().copied().cycle().take(4096).collect();
+        group.throughput(Throughput::Elements(block.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &block, |b, block| {
+            b.iter(|| {
+                for (i, &w) in block.iter().enumerate() {
+                    let _ = black_box(Instruction::decode(
+                        black_box(w),
+                        0x8000_0000 + i as u32 * 4,
+                    ));
+                }
+            })
+        });
+    }
+
+    // A text section's bytes into a `Vec`, scalar (one `decode` per word, as
+    // the pipeline used to) and through the SIMD batch decoder.
+    let section: Vec<u8> = MIX
+        .iter()
+        .cycle()
+        .take(4096)
+        .flat_map(|w| w.to_be_bytes())
+        .collect();
+    group.throughput(Throughput::Elements(4096));
+    group.bench_function("section_scalar", |b| {
+        b.iter(|| {
+            black_box(&section)
+                .chunks_exact(4)
+                .enumerate()
+                .map(|(i, c)| {
+                    let w = u32::from_be_bytes([c[0], c[1], c[2], c[3]]);
+                    Instruction::decode(w, 0x8000_0000 + i as u32 * 4)
+                })
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("section_batch", |b| {
+        b.iter(|| batch::decode_section(black_box(&section), 0x8000_0000))
+    });
+    group.finish();
+}
+
+fn bench_codegen(c: &mut Criterion) {
+    // A medium function: the mix's body repeated to ~250 instructions,
+    // between its prologue and epilogue.
+    let body = &MIX[5..MIX.len() - 5];
+    let words: Vec<u32> = MIX[..5]
+        .iter()
+        .chain(body.iter().cycle().take(240))
+        .chain(&MIX[MIX.len() - 5..])
+        .copied()
+        .collect();
+    let instructions = decode_all(&words, 0x8000_4000);
+    let metadata = FunctionMetadata {
+        address: 0x8000_4000,
+        name: "medium".to_string(),
+        size: words.len() as u32 * 4,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+
+    let mut group = c.benchmark_group("codegen");
+    group.throughput(Throughput::Elements(instructions.len() as u64));
+    group.bench_function("generate_function", |b| {
+        b.iter(|| {
+            CodeGenerator::new()
+                .generate_function(black_box(&metadata), black_box(&instructions))
+                .expect("medium function generates")
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode, bench_codegen);
+criterion_main!(benches);
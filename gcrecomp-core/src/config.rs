@@ -0,0 +1,764 @@
+//! User configuration shared by the runtime and the CLI.
+//!
+//! Values come from three layers, later ones winning:
+//!
+//! 1. built-in defaults ([`Config::default`]),
+//! 2. a TOML file: `$GCRECOMP_CONFIG` if set, otherwise
+//!    `<config dir>/gcrecomp/config.toml` when it exists,
+//! 3. `GCRECOMP_<SECTION>_<KEY>` environment variables, e.g.
+//!    `GCRECOMP_AUDIO_LATENCY_MS=80` for `[audio] latency_ms`.
+//!
+//! [`LoadedConfig`] remembers where each value came from, so a validation
+//! error (or `describe()`) can point at the file or variable to fix.
+//!
+//! ```toml
+//! [graphics]
+//! backend = "vulkan"
+//! present_mode = "low_latency"
+//! frame_limit = true
+//! resolution_scale = 2
+//!
+//! [audio]
+//! latency_ms = 60
+//! master_volume = 80
+//!
+//! [input]
+//! profile = "xbox"
+//!
+//! [recompiler]
+//! opt_level = "basic"
+//!
+//! [runtime]
+//! loop_budget = 8000000
+//! region = "pal"
+//! time_source = "instructions"
+//! cpu_clock_percent = 150
+//!
+//! [system]
+//! language = "german"
+//! sound = "stereo"
+//! progressive = true
+//! sram = "sram.bin"
+//!
+//! [logging]
+//! filter = "info,gcrecomp_runtime::graphics=debug"
+//! file = "gcrecomp.log"
+//! ```
+
+use crate::recompiler::optimizer::OptLevel;
+use crate::runtime::sdk::region::{Language, Region};
+use crate::runtime::sdk::sram::Sram;
+use crate::runtime::sdk::timer::TimeSource;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Environment variable naming the config file to load instead of the default.
+pub const CONFIG_PATH_ENV: &str = "GCRECOMP_CONFIG";
+
+/// Graphics backends accepted by `[graphics] backend`.
+pub const GRAPHICS_BACKENDS: [&str; 5] = ["auto", "vulkan", "metal", "dx12", "gl"];
+
+/// Present modes accepted by `[graphics] present_mode`.
+pub const PRESENT_MODES: [&str; 3] = ["vsync", "low_latency", "uncapped"];
+
+/// Accepted `[audio] latency_ms` range. Below ~5 ms the host device underruns;
+/// above half a second audio is visibly out of sync.
+pub const AUDIO_LATENCY_RANGE_MS: std::ops::RangeInclusive<u32> = 5..=500;
+
+/// Accepted `[graphics] resolution_scale` range (multiples of the native EFB).
+pub const RESOLUTION_SCALE_RANGE: std::ops::RangeInclusive<u32> = 1..=8;
+
+/// Accepted `[audio] master_volume` range, in percent.
+pub const MASTER_VOLUME_RANGE: std::ops::RangeInclusive<u32> = 0..=100;
+
+/// Accepted `[runtime] ticks_per_instruction` range. One tick (~25 ns of
+/// timebase) is already faster-than-hardware time per instruction; above 1000
+/// each instruction would take longer than a real 25 µs.
+pub const TICKS_PER_INSTRUCTION_RANGE: std::ops::RangeInclusive<u64> = 1..=1000;
+
+/// Accepted `[runtime] cpu_clock_percent` range (`clock::CLOCK_RATIO_RANGE`).
+pub const CPU_CLOCK_PERCENT_RANGE: std::ops::RangeInclusive<u32> = 25..=400;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsConfig {
+    /// One of [`GRAPHICS_BACKENDS`]; `auto` lets wgpu pick.
+    pub backend: String,
+    /// One of [`PRESENT_MODES`]; unsupported modes fall back at runtime.
+    pub present_mode: String,
+    /// Cap presentation at the VI field rate in software.
+    pub frame_limit: bool,
+    /// Anisotropic filtering level (1-16) forced on game textures; `0` keeps
+    /// the filtering each texture was set up with.
+    pub anisotropy: u32,
+    /// Directory of replacement PNGs named by texture content hash; empty for none.
+    pub texture_pack: String,
+    /// Internal rendering resolution as a multiple of the native one.
+    pub resolution_scale: u32,
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        Self {
+            backend: "auto".to_string(),
+            present_mode: "vsync".to_string(),
+            frame_limit: false,
+            anisotropy: 0,
+            texture_pack: String::new(),
+            resolution_scale: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Host output buffer length.
+    pub latency_ms: u32,
+    /// Output volume in percent, applied on top of the game's own.
+    pub master_volume: u32,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            latency_ms: 40,
+            master_volume: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputConfig {
+    /// Controller profile applied to newly connected pads; `default` keeps the
+    /// built-in per-controller mapping.
+    pub profile: String,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            profile: "default".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecompilerConfig {
+    /// `none`, `basic` or `aggressive` (see [`OptLevel`]).
+    pub opt_level: String,
+}
+
+impl Default for RecompilerConfig {
+    fn default() -> Self {
+        Self {
+            opt_level: "none".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuntimeConfig {
+    /// Back-edges one call may take before it fails with a timeout (see
+    /// `runtime::watchdog`); `0` disables the check.
+    pub loop_budget: u64,
+    /// `auto` to use the disc's region, or `ntsc-u`, `pal`, `ntsc-j` to
+    /// force one (see `runtime::sdk::region`).
+    pub region: String,
+    /// Turn a panic in recompiled code into an error for that function
+    /// instead of unwinding the runtime thread (see `runtime::set_catch_panics`).
+    pub catch_panics: bool,
+    /// `wall` (host clock) or `instructions`: the timebase advances
+    /// `ticks_per_instruction` per executed instruction, so `OSGetTime`
+    /// waits take the same instructions every run (see `runtime::clock`).
+    pub time_source: String,
+    pub ticks_per_instruction: u64,
+    /// CPU speed relative to VI, in percent: how many instructions an
+    /// instruction-clock field holds (see `runtime::clock::set_clock_ratio`).
+    /// Anything but 100 breaks timing accuracy.
+    pub cpu_clock_percent: u32,
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        Self {
+            loop_budget: crate::runtime::watchdog::DEFAULT_LOOP_BUDGET,
+            region: "auto".to_string(),
+            catch_panics: false,
+            time_source: "wall".to_string(),
+            ticks_per_instruction: 1,
+            cpu_clock_percent: 100,
+        }
+    }
+}
+
+/// Console settings stored in SRAM. Only values that are set (in the file or
+/// the environment) replace what the SRAM file holds; see
+/// [`LoadedConfig::apply_system`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemConfig {
+    /// `english`, `german`, `french`, `spanish`, `italian` or `dutch`.
+    pub language: String,
+    /// `stereo` or `mono`.
+    pub sound: String,
+    pub progressive: bool,
+    /// 60 Hz output on PAL consoles.
+    pub eurgb60: bool,
+    /// Host file the SRAM block persists in; empty to keep it in memory.
+    pub sram: String,
+}
+
+impl Default for SystemConfig {
+    fn default() -> Self {
+        Self {
+            language: "english".to_string(),
+            sound: "stereo".to_string(),
+            progressive: false,
+            eurgb60: false,
+            sram: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// `RUST_LOG`-style filter (see [`crate::logging::LogFilter`]).
+    pub filter: String,
+    /// File every log line is appended to as well; empty for none.
+    pub file: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            filter: "info".to_string(),
+            file: String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub graphics: GraphicsConfig,
+    pub audio: AudioConfig,
+    pub input: InputConfig,
+    pub recompiler: RecompilerConfig,
+    pub runtime: RuntimeConfig,
+    pub system: SystemConfig,
+    pub logging: LoggingConfig,
+}
+
+/// Where a configuration value came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueSource {
+    Default,
+    File(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for ValueSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueSource::Default => write!(f, "default"),
+            ValueSource::File(path) => write!(f, "{}", path.display()),
+            ValueSource::Env(var) => write!(f, "{}", var),
+        }
+    }
+}
+
+/// A validated [`Config`] plus the source of every value, keyed `section.key`.
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: Config,
+    sources: BTreeMap<String, ValueSource>,
+}
+
+impl LoadedConfig {
+    /// Source of `key` (`"audio.latency_ms"`); unknown keys report `Default`.
+    pub fn source(&self, key: &str) -> &ValueSource {
+        self.sources.get(key).unwrap_or(&ValueSource::Default)
+    }
+
+    /// One `section.key = value (source)` line per setting.
+    pub fn describe(&self) -> String {
+        let values = flatten(&to_value(&self.config));
+        let mut out = String::new();
+        for (key, value) in values {
+            out.push_str(&format!("{} = {} ({})\n", key, value, self.source(&key)));
+        }
+        out
+    }
+}
+
+impl Config {
+    /// `<config dir>/gcrecomp/config.toml`, where the platform has a config dir.
+    pub fn default_path() -> Option<PathBuf> {
+        #[cfg(feature = "native")]
+        let config_dir = dirs::config_dir();
+        #[cfg(not(feature = "native"))]
+        let config_dir: Option<PathBuf> = None;
+        config_dir.map(|dir| dir.join("gcrecomp").join("config.toml"))
+    }
+
+    /// Load from the standard locations and the process environment.
+    pub fn load() -> Result<LoadedConfig> {
+        let path = match std::env::var_os(CONFIG_PATH_ENV) {
+            // An explicit path must exist; the default one is optional.
+            Some(path) => Some(PathBuf::from(path)),
+            None => Self::default_path().filter(|p| p.is_file()),
+        };
+        Self::load_from(path.as_deref(), |var| std::env::var(var).ok())
+    }
+
+    /// Load `path` (if any), apply overrides from `env`, and validate.
+    pub fn load_from(
+        path: Option<&Path>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<LoadedConfig> {
+        let mut values = flatten(&to_value(&Config::default()));
+        let mut sources: BTreeMap<String, ValueSource> = values
+            .keys()
+            .map(|k| (k.clone(), ValueSource::Default))
+            .collect();
+
+        if let Some(path) = path {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file {}", path.display()))?;
+            for (key, value) in parse_toml(&text, path)? {
+                let Some(slot) = values.get_mut(&key) else {
+                    log::warn!("{}: ignoring unknown setting '{}'", path.display(), key);
+                    continue;
+                };
+                if std::mem::discriminant(slot) != std::mem::discriminant(&value) {
+                    bail!(
+                        "{} in {}: expected {}, got {}",
+                        key,
+                        path.display(),
+                        kind(slot),
+                        value
+                    );
+                }
+                *slot = value;
+                sources.insert(key, ValueSource::File(path.to_path_buf()));
+            }
+        }
+
+        for (key, slot) in values.iter_mut() {
+            let var = env_var_name(key);
+            let Some(raw) = env(&var) else {
+                continue;
+            };
+            *slot = match slot {
+                Value::Number(_) => raw
+                    .trim()
+                    .parse::<u64>()
+                    .map(Value::from)
+                    .map_err(|_| anyhow::anyhow!("{}={:?} is not a whole number", var, raw))?,
+                Value::Bool(_) => match raw.trim() {
+                    "1" | "true" => Value::Bool(true),
+                    "0" | "false" => Value::Bool(false),
+                    _ => bail!("{}={:?} is not a boolean", var, raw),
+                },
+                _ => Value::String(raw),
+            };
+            sources.insert(key.clone(), ValueSource::Env(var));
+        }
+
+        let config: Config =
+            serde_json::from_value(unflatten(values)).context("Invalid configuration value")?;
+        let loaded = LoadedConfig { config, sources };
+        loaded.validate()?;
+        Ok(loaded)
+    }
+
+    /// Check the values the way loading does (for settings changed in place).
+    pub fn validate(&self) -> Result<()> {
+        LoadedConfig {
+            config: self.clone(),
+            sources: BTreeMap::new(),
+        }
+        .validate()
+    }
+
+    /// Write the settings to the TOML file at `path`, creating it if needed.
+    /// An existing file keeps its comments, layout and unknown keys; settings
+    /// are only added to it when they differ from the defaults.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.validate()?;
+        let mut doc: toml_edit::DocumentMut = match std::fs::read_to_string(path) {
+            Ok(text) => text
+                .parse()
+                .with_context(|| format!("Failed to parse config file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Default::default(),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))
+            }
+        };
+        let defaults = flatten(&to_value(&Config::default()));
+        for (key, value) in flatten(&to_value(self)) {
+            let (section, field) = key.split_once('.').unwrap_or(("", &key));
+            let present = doc
+                .get(section)
+                .and_then(|t| t.as_table_like())
+                .is_some_and(|t| t.contains_key(field));
+            if !present && defaults.get(&key) == Some(&value) {
+                continue;
+            }
+            let value = match value {
+                Value::Number(n) => toml_edit::Value::from(n.as_i64().unwrap_or(i64::MAX)),
+                Value::Bool(b) => toml_edit::Value::from(b),
+                Value::String(s) => toml_edit::Value::from(s),
+                _ => continue,
+            };
+            let table = doc
+                .entry(section)
+                .or_insert_with(toml_edit::table)
+                .as_table_like_mut()
+                .with_context(|| format!("{}: '{}' is not a table", path.display(), section))?;
+            match table.get_mut(field) {
+                // Replace the value only, so trailing comments stay.
+                Some(toml_edit::Item::Value(old)) => {
+                    let decor = old.decor().clone();
+                    *old = value;
+                    *old.decor_mut() = decor;
+                }
+                _ => {
+                    table.insert(field, toml_edit::Item::Value(value));
+                }
+            }
+        }
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(path, doc.to_string())
+            .with_context(|| format!("Failed to write config file {}", path.display()))
+    }
+
+    /// The recompiler optimization level (already validated when loaded).
+    pub fn opt_level(&self) -> OptLevel {
+        self.recompiler.opt_level.parse().unwrap_or(OptLevel::None)
+    }
+
+    /// The loop budget, `None` when disabled.
+    pub fn loop_budget(&self) -> Option<u64> {
+        (self.runtime.loop_budget != 0).then_some(self.runtime.loop_budget)
+    }
+
+    /// The timebase source (already validated when loaded).
+    pub fn time_source(&self) -> TimeSource {
+        self.runtime.time_source.parse().unwrap_or_default()
+    }
+
+    /// `cpu_clock_percent` as a ratio (1.0 = hardware speed).
+    pub fn clock_ratio(&self) -> f64 {
+        f64::from(self.runtime.cpu_clock_percent) / 100.0
+    }
+
+    /// The forced region, `None` for `auto` (or a value `validate` rejects).
+    pub fn region_override(&self) -> Option<Region> {
+        match self.runtime.region.trim() {
+            "" | "auto" => None,
+            s => s.parse().ok(),
+        }
+    }
+}
+
+impl LoadedConfig {
+    /// Apply `[logging]` to the installed logger. The filter only replaces
+    /// the one `logging::init` set up (from `RUST_LOG`) when it was actually
+    /// configured.
+    pub fn apply_logging(&self) {
+        let logging = &self.config.logging;
+        if *self.source("logging.filter") != ValueSource::Default {
+            // Validated on load.
+            let _ = crate::logging::set_filter_spec(&logging.filter);
+        }
+        if !logging.file.is_empty() {
+            if let Err(e) = crate::logging::set_file_sink(Some(Path::new(&logging.file))) {
+                log::warn!(
+                    "logging.file = {:?} (from {}): {}",
+                    logging.file,
+                    self.source("logging.file"),
+                    e
+                );
+            }
+        }
+    }
+
+    /// Apply the `[system]` settings that were configured to `sram`; the
+    /// rest keep the value the SRAM file holds.
+    pub fn apply_system(&self, sram: &mut Sram) {
+        let system = &self.config.system;
+        let set = |key: &str| *self.source(key) != ValueSource::Default;
+        if set("system.language") {
+            if let Ok(language) = system.language.parse::<Language>() {
+                sram.language = language;
+            }
+        }
+        if set("system.sound") {
+            sram.set_stereo(system.sound.trim() != "mono");
+        }
+        if set("system.progressive") {
+            sram.set_progressive(system.progressive);
+        }
+        if set("system.eurgb60") {
+            sram.set_eurgb60(system.eurgb60);
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let c = &self.config;
+
+        if !GRAPHICS_BACKENDS.contains(&c.graphics.backend.as_str()) {
+            bail!(
+                "graphics.backend = {:?} (from {}) is not supported; expected one of {}",
+                c.graphics.backend,
+                self.source("graphics.backend"),
+                GRAPHICS_BACKENDS.join(", ")
+            );
+        }
+
+        if !PRESENT_MODES.contains(&c.graphics.present_mode.as_str()) {
+            bail!(
+                "graphics.present_mode = {:?} (from {}) is not supported; expected one of {}",
+                c.graphics.present_mode,
+                self.source("graphics.present_mode"),
+                PRESENT_MODES.join(", ")
+            );
+        }
+
+        if c.graphics.anisotropy > 16 {
+            bail!(
+                "graphics.anisotropy = {} (from {}) is out of range; expected 0 (off) to 16",
+                c.graphics.anisotropy,
+                self.source("graphics.anisotropy")
+            );
+        }
+
+        if !RESOLUTION_SCALE_RANGE.contains(&c.graphics.resolution_scale) {
+            bail!(
+                "graphics.resolution_scale = {} (from {}) is out of range; expected {}-{}",
+                c.graphics.resolution_scale,
+                self.source("graphics.resolution_scale"),
+                RESOLUTION_SCALE_RANGE.start(),
+                RESOLUTION_SCALE_RANGE.end()
+            );
+        }
+
+        if !MASTER_VOLUME_RANGE.contains(&c.audio.master_volume) {
+            bail!(
+                "audio.master_volume = {} (from {}) is out of range; expected {}-{}%",
+                c.audio.master_volume,
+                self.source("audio.master_volume"),
+                MASTER_VOLUME_RANGE.start(),
+                MASTER_VOLUME_RANGE.end()
+            );
+        }
+
+        if !AUDIO_LATENCY_RANGE_MS.contains(&c.audio.latency_ms) {
+            bail!(
+                "audio.latency_ms = {} (from {}) is out of range; expected {}-{} ms",
+                c.audio.latency_ms,
+                self.source("audio.latency_ms"),
+                AUDIO_LATENCY_RANGE_MS.start(),
+                AUDIO_LATENCY_RANGE_MS.end()
+            );
+        }
+
+        if c.input.profile.trim().is_empty() {
+            bail!(
+                "input.profile (from {}) must not be empty",
+                self.source("input.profile")
+            );
+        }
+
+        if let Err(e) = c.recompiler.opt_level.parse::<OptLevel>() {
+            bail!(
+                "recompiler.opt_level (from {}): {}",
+                self.source("recompiler.opt_level"),
+                e
+            );
+        }
+
+        let region = c.runtime.region.trim();
+        if !matches!(region, "" | "auto") {
+            if let Err(e) = region.parse::<Region>() {
+                bail!(
+                    "runtime.region (from {}): {}",
+                    self.source("runtime.region"),
+                    e
+                );
+            }
+        }
+
+        if let Err(e) = c.runtime.time_source.parse::<TimeSource>() {
+            bail!(
+                "runtime.time_source (from {}): {}",
+                self.source("runtime.time_source"),
+                e
+            );
+        }
+        if !TICKS_PER_INSTRUCTION_RANGE.contains(&c.runtime.ticks_per_instruction) {
+            bail!(
+                "runtime.ticks_per_instruction = {} (from {}) is out of range {}..={}",
+                c.runtime.ticks_per_instruction,
+                self.source("runtime.ticks_per_instruction"),
+                TICKS_PER_INSTRUCTION_RANGE.start(),
+                TICKS_PER_INSTRUCTION_RANGE.end()
+            );
+        }
+        if !CPU_CLOCK_PERCENT_RANGE.contains(&c.runtime.cpu_clock_percent) {
+            bail!(
+                "runtime.cpu_clock_percent = {} (from {}) is out of range {}..={}",
+                c.runtime.cpu_clock_percent,
+                self.source("runtime.cpu_clock_percent"),
+                CPU_CLOCK_PERCENT_RANGE.start(),
+                CPU_CLOCK_PERCENT_RANGE.end()
+            );
+        }
+
+        if let Err(e) = c.system.language.parse::<Language>() {
+            bail!(
+                "system.language (from {}): {}",
+                self.source("system.language"),
+                e
+            );
+        }
+
+        if !matches!(c.system.sound.trim(), "stereo" | "mono") {
+            bail!(
+                "system.sound = {:?} (from {}) is not supported; expected stereo or mono",
+                c.system.sound,
+                self.source("system.sound")
+            );
+        }
+
+        if let Err(e) = c.logging.filter.parse::<crate::logging::LogFilter>() {
+            bail!(
+                "logging.filter (from {}): {}",
+                self.source("logging.filter"),
+                e
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// `audio.latency_ms` -> `GCRECOMP_AUDIO_LATENCY_MS`.
+fn env_var_name(key: &str) -> String {
+    format!("GCRECOMP_{}", key.replace('.', "_").to_uppercase())
+}
+
+fn to_value(config: &Config) -> Value {
+    serde_json::to_value(config).unwrap_or(Value::Null)
+}
+
+/// `{"audio": {"latency_ms": 40}}` -> `{"audio.latency_ms": 40}`.
+fn flatten(value: &Value) -> BTreeMap<String, Value> {
+    let mut out = BTreeMap::new();
+    if let Value::Object(sections) = value {
+        for (section, fields) in sections {
+            if let Value::Object(fields) = fields {
+                for (key, v) in fields {
+                    out.insert(format!("{}.{}", section, key), v.clone());
+                }
+            }
+        }
+    }
+    out
+}
+
+fn unflatten(values: BTreeMap<String, Value>) -> Value {
+    let mut sections = Map::new();
+    for (key, value) in values {
+        let (section, field) = key.split_once('.').unwrap_or(("", &key));
+        let entry = sections
+            .entry(section.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(fields) = entry {
+            fields.insert(field.to_string(), value);
+        }
+    }
+    Value::Object(sections)
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Number(_) => "a number",
+        Value::Bool(_) => "a boolean",
+        Value::String(_) => "a string",
+        _ => "a table",
+    }
+}
+
+/// Every `[section] key = value` in a TOML document as `section.key` -> JSON value.
+fn parse_toml(text: &str, path: &Path) -> Result<Vec<(String, Value)>> {
+    let doc: toml_edit::DocumentMut = text
+        .parse()
+        .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+    let mut out = Vec::new();
+    for (section, item) in doc.as_table().iter() {
+        let Some(table) = item.as_table_like() else {
+            bail!(
+                "{}: top-level key '{}' must be inside a [section]",
+                path.display(),
+                section
+            );
+        };
+        for (key, item) in table.iter() {
+            let name = format!("{}.{}", section, key);
+            let value = match item.as_value() {
+                Some(toml_edit::Value::Integer(i)) if *i.value() >= 0 => Value::from(*i.value()),
+                Some(toml_edit::Value::Integer(i)) => {
+                    bail!("{} in {}: {} is negative", name, path.display(), i.value())
+                }
+                Some(toml_edit::Value::String(s)) => Value::String(s.value().clone()),
+                Some(toml_edit::Value::Boolean(b)) => Value::Bool(*b.value()),
+                _ => bail!("{} in {}: unsupported value type", name, path.display()),
+            };
+            out.push((name, value));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_env(_: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let loaded = Config::load_from(None, no_env).unwrap();
+        assert_eq!(loaded.config, Config::default());
+        assert_eq!(loaded.source("audio.latency_ms"), &ValueSource::Default);
+    }
+
+    #[test]
+    fn env_names_follow_section_and_key() {
+        assert_eq!(
+            env_var_name("audio.latency_ms"),
+            "GCRECOMP_AUDIO_LATENCY_MS"
+        );
+        assert_eq!(
+            env_var_name("recompiler.opt_level"),
+            "GCRECOMP_RECOMPILER_OPT_LEVEL"
+        );
+    }
+}
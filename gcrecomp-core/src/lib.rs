@@ -1,2 +1,6 @@
+pub mod config;
+pub mod logging;
 pub mod recompiler;
 pub mod runtime;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,397 @@
+//! Runtime-Reconfigurable Logging
+//!
+//! The `log` backend for every binary in the workspace. Unlike a logger
+//! configured once at startup, its filter can change while the program runs:
+//! [`set_module_level`] raises or lowers one subsystem
+//! (`gcrecomp_runtime::graphics`) without touching the rest, and
+//! [`set_file_sink`] copies every record into a file.
+//!
+//! Filters use the `RUST_LOG` syntax: a default level plus
+//! `module::path=level` directives, comma-separated
+//! (`"warn,gcrecomp_runtime::graphics=debug"`). The most specific matching
+//! directive wins.
+//!
+//! Warnings and errors that keep firing from one call site (an unknown opcode
+//! hit in a hot loop, with a different address each time) are rate-limited:
+//! the first [`RATE_LIMIT_BURST`] copies print, the rest are counted and
+//! reported once per [`RATE_LIMIT_WINDOW`].
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, Instant};
+
+/// Copies of one warning printed per window before the rest are counted.
+pub const RATE_LIMIT_BURST: u32 = 5;
+/// How often a suppressed warning's repeat count is reported.
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+/// Distinct call sites tracked before the table is reset.
+const RATE_LIMIT_ENTRIES: usize = 1024;
+
+/// Which records pass: a default level and per-module overrides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    /// Module path -> level, longest path first.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    pub fn set_default(&mut self, level: LevelFilter) {
+        self.default = level;
+    }
+
+    /// Set the level for `module` and everything under it.
+    pub fn set_module_level(&mut self, module: &str, level: LevelFilter) {
+        self.modules.retain(|(m, _)| m != module);
+        self.modules.push((module.to_string(), level));
+        self.modules
+            .sort_by_key(|(m, _)| std::cmp::Reverse(m.len()));
+    }
+
+    /// The level that applies to records from `target`.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    pub fn enabled(&self, target: &str, level: Level) -> bool {
+        level <= self.level_for(target)
+    }
+
+    /// The most verbose level any target can log at.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, Ord::max)
+    }
+}
+
+impl FromStr for LogFilter {
+    type Err = String;
+
+    /// `RUST_LOG` syntax: `level`, `module=level`, or a comma-separated mix.
+    /// A bare module name enables it at `trace`.
+    fn from_str(spec: &str) -> Result<Self, String> {
+        let mut filter = LogFilter::new(LevelFilter::Info);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let level = |s: &str| {
+                s.parse::<LevelFilter>().map_err(|_| {
+                    format!("'{s}' is not a log level (off, error, warn, info, debug, trace)")
+                })
+            };
+            match directive.split_once('=') {
+                Some((module, lvl)) => filter.set_module_level(module.trim(), level(lvl.trim())?),
+                None => match level(directive) {
+                    Ok(lvl) => filter.set_default(lvl),
+                    Err(_) => filter.set_module_level(directive, LevelFilter::Trace),
+                },
+            }
+        }
+        Ok(filter)
+    }
+}
+
+/// Per-call-site repeat counts for rate limiting.
+#[derive(Debug, Default)]
+struct RateLimiter {
+    seen: HashMap<String, (Instant, u32)>,
+}
+
+impl RateLimiter {
+    /// Where `record` was logged from. Records without a location (built by
+    /// hand rather than through the `log` macros) fall back to their text.
+    fn key(record: &Record, message: &str) -> String {
+        match (record.file(), record.line()) {
+            (Some(file), Some(line)) => format!("{}@{file}:{line}", record.target()),
+            _ => format!("{}@{message}", record.target()),
+        }
+    }
+
+    /// `Some(suppressed)` if the call site `key` should print now, with how
+    /// many records were swallowed since it last did.
+    fn check(&mut self, key: &str, now: Instant) -> Option<u32> {
+        if self.seen.len() >= RATE_LIMIT_ENTRIES && !self.seen.contains_key(key) {
+            self.seen.clear();
+        }
+        let (window_start, count) = self.seen.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(*window_start) >= RATE_LIMIT_WINDOW {
+            let suppressed = count.saturating_sub(RATE_LIMIT_BURST);
+            *window_start = now;
+            *count = 1;
+            return Some(suppressed);
+        }
+        *count += 1;
+        (*count <= RATE_LIMIT_BURST).then_some(0)
+    }
+}
+
+/// The logger: filter, optional file copy and rate limiter.
+pub struct Logger {
+    filter: RwLock<LogFilter>,
+    file: Mutex<Option<File>>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl Logger {
+    pub fn new(filter: LogFilter) -> Self {
+        Self {
+            filter: RwLock::new(filter),
+            file: Mutex::new(None),
+            limiter: Mutex::new(RateLimiter::default()),
+        }
+    }
+
+    pub fn filter(&self) -> LogFilter {
+        self.filter
+            .read()
+            .map(|f| f.clone())
+            .unwrap_or_else(|e| e.into_inner().clone())
+    }
+
+    /// Replace the whole filter.
+    pub fn set_filter(&self, filter: LogFilter) {
+        self.update(|f| *f = filter);
+    }
+
+    pub fn set_level(&self, level: LevelFilter) {
+        self.update(|f| f.set_default(level));
+    }
+
+    pub fn set_module_level(&self, module: &str, level: LevelFilter) {
+        self.update(|f| f.set_module_level(module, level));
+    }
+
+    /// Append every record to `path` as well as stderr; `None` stops.
+    pub fn set_file_sink(&self, path: Option<&Path>) -> io::Result<()> {
+        let file = match path {
+            Some(path) => Some(File::options().create(true).append(true).open(path)?),
+            None => None,
+        };
+        *self.file.lock().unwrap_or_else(|e| e.into_inner()) = file;
+        Ok(())
+    }
+
+    fn update(&self, change: impl FnOnce(&mut LogFilter)) {
+        let mut filter = self.filter.write().unwrap_or_else(|e| e.into_inner());
+        change(&mut filter);
+        // Only the installed logger drives the global fast-path check.
+        if std::ptr::eq(self, logger()) {
+            log::set_max_level(filter.max_level());
+        }
+    }
+
+    /// The line to print for `record`, or `None` when it is filtered out or
+    /// rate-limited.
+    fn format(&self, record: &Record) -> Option<String> {
+        if !self.enabled(record.metadata()) {
+            return None;
+        }
+        let message = record.args().to_string();
+        let mut line = format!("[{:<5} {}] {}", record.level(), record.target(), message);
+        if record.level() <= Level::Warn {
+            let mut limiter = self.limiter.lock().unwrap_or_else(|e| e.into_inner());
+            let key = RateLimiter::key(record, &message);
+            let suppressed = limiter.check(&key, Instant::now())?;
+            if suppressed > 0 {
+                line.push_str(&format!(" (repeated {suppressed} more times)"));
+            }
+        }
+        Some(line)
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter
+            .read()
+            .map(|f| f.enabled(metadata.target(), metadata.level()))
+            .unwrap_or(true)
+    }
+
+    fn log(&self, record: &Record) {
+        let Some(line) = self.format(record) else {
+            return;
+        };
+        eprintln!("{line}");
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = writeln!(file, "{line}");
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            let _ = file.flush();
+        }
+    }
+}
+
+/// The process-wide logger (installed by [`init`]).
+pub fn logger() -> &'static Logger {
+    static LOGGER: OnceLock<Logger> = OnceLock::new();
+    LOGGER.get_or_init(|| Logger::new(LogFilter::new(LevelFilter::Info)))
+}
+
+/// Install the logger with `spec` (see [`LogFilter`]), or `RUST_LOG`, or
+/// `default`, in that order of preference.
+pub fn init(spec: Option<&str>, default: LevelFilter) -> Result<(), SetLoggerError> {
+    let env = std::env::var("RUST_LOG").ok();
+    let filter = match spec.or(env.as_deref()) {
+        Some(spec) => spec.parse().unwrap_or_else(|e| {
+            eprintln!("Ignoring log filter {spec:?}: {e}");
+            LogFilter::new(default)
+        }),
+        None => LogFilter::new(default),
+    };
+    let logger = logger();
+    log::set_logger(logger)?;
+    logger.set_filter(filter);
+    Ok(())
+}
+
+/// Set the default level for modules without their own.
+pub fn set_level(level: LevelFilter) {
+    logger().set_level(level);
+}
+
+/// `set_module_level("gcrecomp_runtime::graphics", LevelFilter::Debug)`.
+pub fn set_module_level(module: &str, level: LevelFilter) {
+    logger().set_module_level(module, level);
+}
+
+/// Replace the filter from a `RUST_LOG`-style spec.
+pub fn set_filter_spec(spec: &str) -> Result<(), String> {
+    logger().set_filter(spec.parse()?);
+    Ok(())
+}
+
+pub fn set_file_sink(path: Option<&Path>) -> io::Result<()> {
+    logger().set_file_sink(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record<'a>(target: &'a str, level: Level, args: std::fmt::Arguments<'a>) -> Record<'a> {
+        Record::builder()
+            .target(target)
+            .level(level)
+            .args(args)
+            .build()
+    }
+
+    #[test]
+    fn module_filter_suppresses_only_that_module() {
+        let logger = Logger::new(LogFilter::new(LevelFilter::Info));
+        logger.set_module_level("gcrecomp_runtime::graphics", LevelFilter::Warn);
+
+        let gx = "gcrecomp_runtime::graphics::gx";
+        assert!(logger
+            .format(&record(gx, Level::Info, format_args!("draw")))
+            .is_none());
+        assert!(logger
+            .format(&record(gx, Level::Warn, format_args!("slow")))
+            .is_some());
+        // Siblings and lookalike prefixes keep the default.
+        for other in ["gcrecomp_runtime::audio", "gcrecomp_runtime::graphicsx"] {
+            assert!(logger
+                .format(&record(other, Level::Info, format_args!("ok")))
+                .is_some());
+        }
+
+        // Raising it lets debug through for that module only.
+        logger.set_module_level("gcrecomp_runtime::graphics", LevelFilter::Debug);
+        assert!(logger
+            .format(&record(gx, Level::Debug, format_args!("draw")))
+            .is_some());
+        let audio = "gcrecomp_runtime::audio";
+        assert!(logger
+            .format(&record(audio, Level::Debug, format_args!("mix")))
+            .is_none());
+    }
+
+    #[test]
+    fn filter_spec_parses_rust_log_syntax() {
+        let filter: LogFilter = "warn,gcrecomp_core::recompiler=debug,game".parse().unwrap();
+        assert_eq!(filter.level_for("gcrecomp_cli"), LevelFilter::Warn);
+        assert_eq!(
+            filter.level_for("gcrecomp_core::recompiler::codegen"),
+            LevelFilter::Debug
+        );
+        assert_eq!(filter.level_for("game"), LevelFilter::Trace);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+        assert!("gcrecomp_core=loud".parse::<LogFilter>().is_err());
+    }
+
+    #[test]
+    fn repeated_warnings_are_rate_limited() {
+        let logger = Logger::new(LogFilter::new(LevelFilter::Info));
+        let printed = (0..20)
+            .filter(|_| {
+                let r = record(
+                    "gcrecomp_core",
+                    Level::Warn,
+                    format_args!("Unknown opcode 5"),
+                );
+                logger.format(&r).is_some()
+            })
+            .count();
+        assert_eq!(printed, RATE_LIMIT_BURST as usize);
+        let r = record(
+            "gcrecomp_core",
+            Level::Warn,
+            format_args!("Unknown opcode 6"),
+        );
+        assert!(logger.format(&r).is_some());
+
+        let mut limiter = RateLimiter::default();
+        let start = Instant::now();
+        for _ in 0..8 {
+            limiter.check("x", start);
+        }
+        assert_eq!(limiter.check("x", start + RATE_LIMIT_WINDOW), Some(3));
+    }
+
+    #[test]
+    fn rate_limit_is_per_call_site_not_per_message() {
+        let logger = Logger::new(LogFilter::new(LevelFilter::Info));
+        let at = |line: u32, addr: u32| {
+            logger
+                .format(
+                    &Record::builder()
+                        .target("gcrecomp_core")
+                        .level(Level::Warn)
+                        .file(Some("src/interp.rs"))
+                        .line(Some(line))
+                        .args(format_args!("Unknown opcode at {addr:#010x}"))
+                        .build(),
+                )
+                .is_some()
+        };
+        // A different address every time is still the same warning
+        let printed = (0..20).filter(|&i| at(40, 0x8000_0000 + i * 4)).count();
+        assert_eq!(printed, RATE_LIMIT_BURST as usize);
+        // Another call site has its own budget, even with identical text
+        assert!(at(41, 0x8000_0000));
+    }
+}
@@ -15,15 +15,17 @@
 //! 2. **Build basic blocks**: Linear sequences of instructions with single entry/exit
 //! 3. **Identify edges**: Connect blocks based on branch targets and fall-through
 //!
+//! Code generation builds each function's block state machine from this graph.
+//!
 //! # Loop Detection Algorithm
 //! Uses depth-first search (DFS) to find back edges, which indicate loops.
 //! A back edge is an edge from a node to an ancestor in the DFS tree.
 
-use crate::recompiler::decoder::{DecodedInstruction, Operand};
+use crate::recompiler::decoder::DecodedInstruction;
 use anyhow::Result;
 use bitvec::prelude::*;
 use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 /// Control flow graph representation.
 ///
@@ -146,16 +148,23 @@ impl ControlFlowAnalyzer {
     /// Build a control flow graph from a sequence of instructions.
     ///
     /// # Algorithm
-    /// 1. **Identify block boundaries**: Entry points, branch targets, and instructions after branches
-    /// 2. **Build basic blocks**: Group instructions into blocks with single entry/exit
-    /// 3. **Identify edges**: Connect blocks based on branch targets and fall-through
+    /// 1. **Identify block boundaries (leaders)**: The first instruction, `entry_address`,
+    ///    every `b`/`bc` target inside the sequence, and the instruction after every branch
+    /// 2. **Build basic blocks**: Each instruction goes to the block of the largest leader
+    ///    at or below its address, in sequence order. Optimized code may repeat an address
+    ///    (unrolled loops) or leave a leader with no instruction of its own (hoisted
+    ///    loop heads); both still land in the right block.
+    /// 3. **Identify edges**: From each block's last instruction: taken and not-taken
+    ///    edges for conditional branches, one edge for unconditional ones, fall-through
+    ///    after calls and non-branches, and none after `blr`/`bctr`
     ///
     /// # Arguments
-    /// * `instructions` - Sequence of decoded PowerPC instructions
+    /// * `instructions` - Sequence of decoded PowerPC instructions, in address order
     /// * `entry_address` - Entry address of the function (first instruction address)
     ///
     /// # Returns
-    /// `Result<ControlFlowGraph>` - Constructed control flow graph
+    /// `Result<ControlFlowGraph>` - Constructed control flow graph; block IDs follow
+    /// address order
     ///
     /// # Errors
     /// Returns error if CFG construction fails (invalid addresses, malformed instructions)
@@ -170,160 +179,168 @@ impl ControlFlowAnalyzer {
         instructions: &[DecodedInstruction],
         entry_address: u32,
     ) -> Result<ControlFlowGraph> {
-        let mut nodes: Vec<BasicBlock> = Vec::new();
-        let mut edges: Vec<Edge> = Vec::new();
-        let mut address_to_block: HashMap<u32, u32> = HashMap::new();
-        let mut block_id: u32 = 0u32;
-
-        // First pass: identify basic block boundaries
-        // Block boundaries occur at:
-        // 1. Function entry point
-        // 2. Branch targets
-        // 3. Instructions immediately after branches (fall-through)
-        let mut block_starts: std::collections::HashSet<u32> = std::collections::HashSet::new();
-        block_starts.insert(entry_address);
-
-        let mut current_address: u32 = entry_address;
-        for inst in instructions.iter() {
-            // Branch targets start new blocks
-            if let Some(target) = Self::get_branch_target(inst) {
-                block_starts.insert(target);
-                // Next instruction after branch also starts new block (fall-through)
-                if current_address.wrapping_add(4) < u32::MAX {
-                    block_starts.insert(current_address.wrapping_add(4));
-                }
+        let (Some(first), Some(last)) = (instructions.first(), instructions.last()) else {
+            return Ok(ControlFlowGraph {
+                nodes: Vec::new(),
+                edges: Vec::new(),
+                entry_block: 0u32,
+            });
+        };
+        let range = first.address..last.address.wrapping_add(4);
+
+        // First pass: leaders, in address order.
+        let mut leaders: BTreeSet<u32> = BTreeSet::new();
+        leaders.insert(first.address);
+        if range.contains(&entry_address) {
+            leaders.insert(entry_address);
+        }
+        for inst in instructions.iter().filter(|i| Self::is_branch(i)) {
+            if let Some(target) = Self::get_branch_target(inst).filter(|t| range.contains(t)) {
+                leaders.insert(target);
+            }
+            let after: u32 = inst.address.wrapping_add(4);
+            if range.contains(&after) {
+                leaders.insert(after);
             }
-            current_address = current_address.wrapping_add(4); // PowerPC instructions are 4 bytes
         }
+        let leaders: Vec<u32> = leaders.into_iter().collect();
+        let address_to_block: HashMap<u32, u32> = leaders
+            .iter()
+            .enumerate()
+            .map(|(i, &a)| (a, i as u32))
+            .collect();
 
         // Second pass: build basic blocks
-        let mut current_block: Option<BasicBlock> = None;
-        let mut current_address: u32 = entry_address;
-
+        let mut nodes: Vec<BasicBlock> = leaders
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| BasicBlock {
+                id: i as u32,
+                start_address: start,
+                end_address: start,
+                instructions: Vec::new(),
+                successors: SmallVec::new(),
+                predecessors: SmallVec::new(),
+            })
+            .collect();
         for inst in instructions.iter() {
-            if block_starts.contains(&current_address) {
-                // Start new block
-                if let Some(block) = current_block.take() {
-                    let block_idx: u32 = nodes.len() as u32;
-                    address_to_block.insert(block.start_address, block_idx);
-                    nodes.push(block);
-                }
-                current_block = Some(BasicBlock {
-                    id: block_id,
-                    start_address: current_address,
-                    end_address: current_address,
-                    instructions: vec![inst.clone()],
-                    successors: SmallVec::new(),
-                    predecessors: SmallVec::new(),
-                });
-                block_id = block_id.wrapping_add(1);
-            } else if let Some(ref mut block) = current_block {
-                block.instructions.push(inst.clone());
-                block.end_address = current_address;
-            }
-
-            current_address = current_address.wrapping_add(4); // PowerPC instructions are 4 bytes
-        }
-
-        // Add final block if exists
-        if let Some(block) = current_block {
-            let block_idx: u32 = nodes.len() as u32;
-            address_to_block.insert(block.start_address, block_idx);
-            nodes.push(block);
+            let block_idx: usize = leaders
+                .partition_point(|&l| l <= inst.address)
+                .saturating_sub(1);
+            let block: &mut BasicBlock = &mut nodes[block_idx];
+            block.end_address = inst.address;
+            block.instructions.push(inst.clone());
         }
 
         // Third pass: identify edges
-        // Collect updates to apply after iteration (avoids borrow checker issues)
-        let mut successor_updates: Vec<(usize, u32)> = Vec::new();
-        let mut predecessor_updates: Vec<(usize, u32)> = Vec::new();
-
+        let mut edges: Vec<Edge> = Vec::new();
         for (block_idx, block) in nodes.iter().enumerate() {
-            let block_idx_u32: u32 = block_idx as u32;
-            if let Some(last_inst) = block.instructions.last() {
-                if let Some(target) = Self::get_branch_target(last_inst) {
-                    if let Some(&target_block) = address_to_block.get(&target) {
-                        edges.push(Edge {
-                            from: block_idx_u32,
-                            to: target_block,
-                            edge_type: EdgeType::Unconditional,
-                        });
-                        // Queue updates for successors and predecessors
-                        successor_updates.push((block_idx, target_block));
-                        predecessor_updates.push((target_block as usize, block_idx_u32));
-                    }
-                } else {
-                    // Fall-through edge (no explicit branch, continue to next block)
-                    if block_idx + 1 < nodes.len() {
-                        let next_block_id: u32 = (block_idx + 1) as u32;
-                        edges.push(Edge {
-                            from: block_idx_u32,
-                            to: next_block_id,
-                            edge_type: EdgeType::Unconditional,
-                        });
-                        successor_updates.push((block_idx, next_block_id));
-                        predecessor_updates.push((block_idx + 1, block_idx_u32));
-                    }
-                }
+            let from: u32 = block_idx as u32;
+            let next: Option<u32> = (block_idx + 1 < nodes.len()).then_some(from + 1);
+            let (target, falls_through) = match block.instructions.last() {
+                Some(inst) if Self::is_branch(inst) => Self::branch_exits(inst),
+                _ => (None, true),
+            };
+            let target: Option<u32> = target.and_then(|t| address_to_block.get(&t).copied());
+            let conditional: bool = target.is_some() && falls_through;
+            if let Some(to) = target {
+                edges.push(Edge {
+                    from,
+                    to,
+                    edge_type: if conditional {
+                        EdgeType::ConditionalTrue
+                    } else {
+                        EdgeType::Unconditional
+                    },
+                });
             }
-        }
-
-        // Apply deferred updates
-        for (block_idx, successor) in successor_updates {
-            if let Some(block) = nodes.get_mut(block_idx) {
-                if !block.successors.contains(&successor) {
-                    block.successors.push(successor);
-                }
+            if let Some(to) = next.filter(|_| falls_through) {
+                edges.push(Edge {
+                    from,
+                    to,
+                    edge_type: if conditional {
+                        EdgeType::ConditionalFalse
+                    } else {
+                        EdgeType::Unconditional
+                    },
+                });
             }
         }
-        for (block_idx, predecessor) in predecessor_updates {
-            if let Some(block) = nodes.get_mut(block_idx) {
-                if !block.predecessors.contains(&predecessor) {
-                    block.predecessors.push(predecessor);
-                }
+        for edge in edges.iter() {
+            let (from, to) = (edge.from as usize, edge.to as usize);
+            if !nodes[from].successors.contains(&edge.to) {
+                nodes[from].successors.push(edge.to);
+            }
+            if !nodes[to].predecessors.contains(&edge.from) {
+                nodes[to].predecessors.push(edge.from);
             }
         }
 
+        let entry_block: u32 = address_to_block
+            .get(&entry_address)
+            .copied()
+            .unwrap_or(0u32);
         Ok(ControlFlowGraph {
             nodes,
             edges,
-            entry_block: 0u32,
+            entry_block,
         })
     }
 
+    #[inline]
+    fn is_branch(inst: &DecodedInstruction) -> bool {
+        matches!(
+            inst.instruction.instruction_type,
+            crate::recompiler::decoder::InstructionType::Branch
+        )
+    }
+
+    /// Where control can go after a branch: its target (if it has a static one and
+    /// may jump there) and whether it may continue with the next instruction.
+    ///
+    /// Calls (LK=1) continue after the callee returns, so they only fall through.
+    /// `bclr`/`bcctr` have no static target: unconditional ones end the block's
+    /// flow (return, or a jump through CTR), conditional ones may fall through.
+    #[inline]
+    fn branch_exits(inst: &DecodedInstruction) -> (Option<u32>, bool) {
+        let raw: u32 = inst.raw;
+        let always: bool = (raw >> 21) & 0x14 == 0x14; // BO = 1z1zz
+        if raw & 1 != 0 {
+            return (None, true);
+        }
+        match raw >> 26 {
+            18 => (Self::get_branch_target(inst), false),
+            16 => (Self::get_branch_target(inst), !always),
+            19 => (None, !always),
+            _ => (None, true),
+        }
+    }
+
     /// Extract branch target address from a branch instruction.
     ///
     /// # Arguments
     /// * `inst` - Decoded instruction (should be a branch instruction)
     ///
     /// # Returns
-    /// `Option<u32>` - Branch target address if instruction is a branch, None otherwise
+    /// `Option<u32>` - Branch target address for `b`/`bc` (and their link forms), None
+    /// for anything else, including `bclr`/`bcctr`, whose target is in a register
     ///
     /// # Algorithm
-    /// Checks if instruction is a branch and extracts target from operands:
-    /// - Address operand: direct address
-    /// - Immediate32 operand: relative offset (would need current PC to compute absolute)
-    /// - Register operand: indirect branch (would need register value)
+    /// Sign-extends the displacement field (LI for `b`, BD for `bc`) and adds it to the
+    /// instruction's own address, or uses it as is when AA=1.
     #[inline] // Hot path - called for every branch instruction
     fn get_branch_target(inst: &DecodedInstruction) -> Option<u32> {
-        // Extract branch target from instruction
-        if matches!(
-            inst.instruction.instruction_type,
-            crate::recompiler::decoder::InstructionType::Branch
-        ) {
-            if let Some(Operand::Address(addr)) = inst.instruction.operands.first() {
-                return Some(*addr);
-            }
-            if let Some(Operand::Immediate32(_imm)) = inst.instruction.operands.first() {
-                // Relative branch - would need current PC to compute absolute address
-                // For now, return None (caller should track PC)
-                return None;
-            }
-            if let Some(Operand::Register(0)) = inst.instruction.operands.first() {
-                // Branch to link register - would need LR value
-                return None;
-            }
+        let raw: u32 = inst.raw;
+        let disp: i32 = match raw >> 26 {
+            18 => ((raw & 0x03FF_FFFC) as i32) << 6 >> 6, // sign-extend 26-bit
+            16 => (raw & 0x0000_FFFC) as u16 as i16 as i32, // sign-extend 16-bit
+            _ => return None,
+        };
+        if raw & 2 != 0 {
+            Some(disp as u32) // absolute (AA=1)
+        } else {
+            Some(inst.address.wrapping_add(disp as u32))
         }
-        None
     }
 
     /// Detect loops in the control flow graph using depth-first search.
@@ -438,18 +455,16 @@ impl ControlFlowAnalyzer {
         let mut calls: Vec<FunctionCall> = Vec::new();
 
         for block in cfg.nodes.iter() {
-            let mut instruction_address: u32 = block.start_address;
             for inst in block.instructions.iter() {
                 if Self::is_function_call(inst) {
                     if let Some(target) = Self::get_branch_target(inst) {
                         calls.push(FunctionCall {
                             caller_block: block.id,
                             callee_address: target,
-                            instruction_address,
+                            instruction_address: inst.address,
                         });
                     }
                 }
-                instruction_address = instruction_address.wrapping_add(4); // PowerPC instructions are 4 bytes
             }
         }
 
@@ -492,3 +507,65 @@ pub struct FunctionCall {
     /// Instruction address where call occurs
     pub instruction_address: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recompiler::decoder::Instruction;
+
+    fn decode(words: &[u32]) -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, 0x8000_3000 + i as u32 * 4).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn a_forward_branch_target_splits_a_straight_line_run() {
+        // beq +12 ; li r3,1 ; li r4,2 ; li r5,3 (target) ; li r6,4 ; blr
+        let words = [
+            0x4182_000C,
+            0x3860_0001,
+            0x3880_0002,
+            0x38A0_0003,
+            0x38C0_0004,
+            0x4E80_0020,
+        ];
+        let cfg = ControlFlowAnalyzer::build_cfg(&decode(&words), 0x8000_3000).unwrap();
+        let blocks: Vec<(u32, Vec<u32>)> = cfg
+            .nodes
+            .iter()
+            .map(|b| {
+                (
+                    b.start_address,
+                    b.instructions.iter().map(|i| i.raw).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            blocks,
+            vec![
+                (0x8000_3000, vec![words[0]]),
+                (0x8000_3004, words[1..3].to_vec()),
+                (0x8000_300C, words[3..].to_vec()),
+            ]
+        );
+        let edges: Vec<(u32, u32, EdgeType)> = cfg
+            .edges
+            .iter()
+            .map(|e| (e.from, e.to, e.edge_type))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (0, 2, EdgeType::ConditionalTrue),
+                (0, 1, EdgeType::ConditionalFalse),
+                (1, 2, EdgeType::Unconditional),
+            ]
+        );
+        assert_eq!(cfg.nodes[2].predecessors.as_slice(), &[0, 1]);
+        // `blr` ends the function: no successors.
+        assert!(cfg.nodes[2].successors.is_empty());
+    }
+}
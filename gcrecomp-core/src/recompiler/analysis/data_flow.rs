@@ -229,6 +229,7 @@ impl DataFlowAnalyzer {
         // Check all operands for register uses
         let def_reg: Option<u8> = Self::get_definition_register(inst);
         for operand in inst.instruction.operands.iter() {
+            #[allow(clippy::collapsible_match)]
             match operand {
                 Operand::Register(reg) => {
                     // Skip if this is the definition register
@@ -288,7 +289,9 @@ impl DataFlowAnalyzer {
         while changed {
             changed = false;
 
-            for block in cfg.nodes.iter() {
+            // Liveness flows backwards: visiting blocks last to first settles
+            // straight-line code in one pass.
+            for block in cfg.nodes.iter().rev() {
                 // Compute live at exit (union of live at entry of successors)
                 let mut exit_live: BitVec<u32> = bitvec![u32, Lsb0; 0; 32];
                 for &succ in block.successors.iter() {
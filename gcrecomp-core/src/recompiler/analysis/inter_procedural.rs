@@ -1,13 +1,15 @@
 // Inter-Procedural Analysis
+use crate::recompiler::enrich::FunctionFacts;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CallGraph {
     pub nodes: Vec<FunctionNode>,
     pub edges: Vec<CallEdge>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FunctionNode {
     pub address: u32,
     pub name: String,
@@ -16,7 +18,7 @@ pub struct FunctionNode {
     pub callees: Vec<usize>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CallEdge {
     pub caller: usize,
     pub callee: usize,
@@ -50,6 +52,48 @@ impl InterProceduralAnalyzer {
         CallGraph { nodes, edges }
     }
 
+    /// The call graph of enriched functions: one edge per caller/callee
+    /// pair with every `bl` between them, calls to addresses that aren't a
+    /// known function dropped. The function at `entry_point` is the root.
+    pub fn call_graph_from_facts(facts: &[FunctionFacts], entry_point: u32) -> CallGraph {
+        let index: HashMap<u32, usize> = facts
+            .iter()
+            .enumerate()
+            .map(|(idx, f)| (f.address, idx))
+            .collect();
+        let mut nodes: Vec<FunctionNode> = facts
+            .iter()
+            .map(|f| FunctionNode {
+                address: f.address,
+                name: f.name.clone(),
+                is_entry_point: f.address == entry_point,
+                callers: vec![],
+                callees: vec![],
+            })
+            .collect();
+        let mut edges: Vec<CallEdge> = Vec::new();
+        let mut edge_index: HashMap<(usize, usize), usize> = HashMap::new();
+        for (caller, f) in facts.iter().enumerate() {
+            for &(site, target) in &f.call_sites {
+                let Some(&callee) = index.get(&target) else {
+                    continue;
+                };
+                let edge = *edge_index.entry((caller, callee)).or_insert_with(|| {
+                    edges.push(CallEdge {
+                        caller,
+                        callee,
+                        call_sites: vec![],
+                    });
+                    nodes[caller].callees.push(callee);
+                    nodes[callee].callers.push(caller);
+                    edges.len() - 1
+                });
+                edges[edge].call_sites.push(site);
+            }
+        }
+        CallGraph { nodes, edges }
+    }
+
     pub fn find_unreachable_functions(call_graph: &CallGraph) -> Vec<usize> {
         let mut reachable = HashSet::new();
         let mut queue = Vec::new();
@@ -2,6 +2,9 @@ pub mod control_flow;
 pub mod data_flow;
 pub mod inter_procedural;
 pub mod loop_analysis;
+pub mod pointer;
+pub mod similarity;
+pub mod structs;
 pub mod type_inference;
 
 /// Type information for decompiled/recompiled code
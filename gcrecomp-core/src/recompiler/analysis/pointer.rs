@@ -0,0 +1,419 @@
+//! Pointer Analysis
+//!
+//! Tracks what each GPR points at, relative to the bases the ABI fixes on
+//! entry: r1 (stack), r13 (small-data area, `_SDA_BASE_`) and r2 (read-only
+//! small data, `_SDA2_BASE_`), or as an absolute address built with
+//! `lis`/`addi`/`ori`. Every load and store whose base register is known is
+//! recorded with the memory region it touches, classified against the
+//! GameCube memory map: DOL code and data sections, the SDAs, the stack, and
+//! the hardware register blocks at `0xCC000000`. Two recorded accesses can
+//! be checked for overlap with [`MemoryAccess::may_alias`], which the
+//! optimizer's redundant-load elimination relies on.
+//!
+//! # Algorithm
+//! A single forward pass. Values are not merged at join points: at a branch
+//! target every register except r1, r2 and r13 reverts to unknown, which
+//! keeps the result sound for the dedicated registers the classification
+//! mostly hinges on.
+
+use crate::recompiler::decoder::DecodedInstruction;
+use crate::recompiler::parser::DolFile;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// `_SDA_BASE_`/`_SDA2_BASE_` the game binary's boot code sets r13/r2 to
+/// when a DOL doesn't say otherwise.
+pub const DEFAULT_SDA_BASE: u32 = 0x8040_0000;
+
+/// Hardware register blocks (CP, PE, VI, PI, MI, DSP, DI, SI, EXI, AI, GX FIFO).
+const HARDWARE: Range<u32> = 0xCC00_0000..0xCC01_0000;
+/// Reach of a signed 16-bit displacement from an SDA base.
+const SDA_REACH: u32 = 0x8000;
+
+/// What kind of memory a pointer targets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryRegion {
+    /// A global in the r13 or r2 small-data area.
+    SmallData,
+    /// A global in a DOL data section or BSS.
+    Data,
+    /// Inside a DOL text section (jump tables, function pointers).
+    Code,
+    /// The current stack frame or a caller's.
+    Stack,
+    /// Memory-mapped hardware registers.
+    Hardware,
+    Unknown,
+}
+
+/// The layout classification runs against.
+#[derive(Debug, Clone)]
+pub struct MemoryMap {
+    code: Vec<Range<u32>>,
+    data: Vec<Range<u32>>,
+    sda_bases: [u32; 2],
+}
+
+impl MemoryMap {
+    /// Sections and BSS of `dol`, with r13 = `sda_base` and r2 = `sda2_base`.
+    pub fn from_dol(dol: &DolFile, sda_base: u32, sda2_base: u32) -> Self {
+        let range = |address: u32, size: u32| address..address.wrapping_add(size);
+        let mut data: Vec<Range<u32>> = dol
+            .data_sections
+            .iter()
+            .map(|s| range(s.address, s.size))
+            .collect();
+        if dol.bss_size != 0 {
+            data.push(range(dol.bss_address, dol.bss_size));
+        }
+        Self {
+            code: dol
+                .text_sections
+                .iter()
+                .map(|s| range(s.address, s.size))
+                .collect(),
+            data,
+            sda_bases: [sda_base, sda2_base],
+        }
+    }
+
+    /// A map with no sections, for when the DOL isn't at hand: absolute
+    /// addresses outside the SDAs and hardware classify as `Unknown`.
+    pub fn without_sections() -> Self {
+        Self {
+            code: Vec::new(),
+            data: Vec::new(),
+            sda_bases: [DEFAULT_SDA_BASE; 2],
+        }
+    }
+
+    /// Region of an absolute address.
+    pub fn classify(&self, address: u32) -> MemoryRegion {
+        if HARDWARE.contains(&address) {
+            return MemoryRegion::Hardware;
+        }
+        let near_sda = self
+            .sda_bases
+            .iter()
+            .any(|&base| address.wrapping_sub(base).wrapping_add(SDA_REACH) < 2 * SDA_REACH);
+        if near_sda {
+            return MemoryRegion::SmallData;
+        }
+        if self.code.iter().any(|r| r.contains(&address)) {
+            return MemoryRegion::Code;
+        }
+        if self.data.iter().any(|r| r.contains(&address)) {
+            return MemoryRegion::Data;
+        }
+        MemoryRegion::Unknown
+    }
+}
+
+/// What a register is known to hold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointsTo {
+    /// r1 on entry plus an offset.
+    Stack(i32),
+    /// r13 on entry plus an offset.
+    SmallData(i32),
+    /// r2 on entry plus an offset.
+    SmallData2(i32),
+    Absolute(u32),
+}
+
+impl PointsTo {
+    fn offset_by(self, delta: i32) -> Self {
+        match self {
+            PointsTo::Stack(o) => PointsTo::Stack(o.wrapping_add(delta)),
+            PointsTo::SmallData(o) => PointsTo::SmallData(o.wrapping_add(delta)),
+            PointsTo::SmallData2(o) => PointsTo::SmallData2(o.wrapping_add(delta)),
+            PointsTo::Absolute(a) => PointsTo::Absolute(a.wrapping_add(delta as u32)),
+        }
+    }
+
+    pub fn region(self, map: &MemoryMap) -> MemoryRegion {
+        match self {
+            PointsTo::Stack(_) => MemoryRegion::Stack,
+            PointsTo::SmallData(_) | PointsTo::SmallData2(_) => MemoryRegion::SmallData,
+            PointsTo::Absolute(address) => map.classify(address),
+        }
+    }
+}
+
+/// A load or store through a register with a known target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// Address of the load/store instruction.
+    pub at: u32,
+    /// Base register of the access.
+    pub register: u8,
+    /// The accessed location (base register value plus displacement).
+    pub target: PointsTo,
+    pub region: MemoryRegion,
+    pub store: bool,
+    /// Bytes accessed.
+    pub size: u32,
+}
+
+impl MemoryAccess {
+    /// Whether the two accesses may touch a common byte. Exact for targets
+    /// off the same base; a stack slot never overlaps a global in a known
+    /// region; anything else may alias.
+    pub fn may_alias(&self, other: &MemoryAccess) -> bool {
+        let overlaps = |a: i64, b: i64| a < b + other.size as i64 && b < a + self.size as i64;
+        let global = |region: MemoryRegion| {
+            matches!(
+                region,
+                MemoryRegion::SmallData
+                    | MemoryRegion::Data
+                    | MemoryRegion::Code
+                    | MemoryRegion::Hardware
+            )
+        };
+        match (self.target, other.target) {
+            (PointsTo::Stack(a), PointsTo::Stack(b))
+            | (PointsTo::SmallData(a), PointsTo::SmallData(b))
+            | (PointsTo::SmallData2(a), PointsTo::SmallData2(b)) => overlaps(a.into(), b.into()),
+            (PointsTo::Absolute(a), PointsTo::Absolute(b)) => overlaps(a.into(), b.into()),
+            (PointsTo::Stack(_), _) => !global(other.region),
+            (_, PointsTo::Stack(_)) => !global(self.region),
+            _ => true,
+        }
+    }
+}
+
+/// Result of [`PointerAnalyzer::analyze`].
+#[derive(Debug, Clone, Default)]
+pub struct PointerAnalysis {
+    pub accesses: Vec<MemoryAccess>,
+}
+
+impl PointerAnalysis {
+    /// Accesses touching `region`.
+    pub fn in_region(&self, region: MemoryRegion) -> impl Iterator<Item = &MemoryAccess> {
+        self.accesses.iter().filter(move |a| a.region == region)
+    }
+
+    /// The access made by the instruction at `at`, if its target is known.
+    pub fn access_at(&self, at: u32) -> Option<&MemoryAccess> {
+        self.accesses.iter().find(|a| a.at == at)
+    }
+}
+
+pub struct PointerAnalyzer;
+
+impl PointerAnalyzer {
+    /// Analyze one function's instructions (in address order).
+    pub fn analyze(instructions: &[DecodedInstruction], map: &MemoryMap) -> PointerAnalysis {
+        let targets = branch_targets(instructions);
+        let mut regs = entry_state();
+        let mut result = PointerAnalysis::default();
+        let mut after_branch = false;
+
+        for inst in instructions {
+            if after_branch || targets.contains(&inst.address) {
+                let entry = entry_state();
+                for (r, value) in regs.iter_mut().enumerate() {
+                    if ![1, 2, 13].contains(&r) {
+                        *value = entry[r];
+                    }
+                }
+            }
+            let raw = inst.raw;
+            let primary = raw >> 26;
+            after_branch = matches!(primary, 16 | 18 | 19);
+
+            let rd = ((raw >> 21) & 0x1F) as usize;
+            let ra = ((raw >> 16) & 0x1F) as usize;
+            let rb = ((raw >> 11) & 0x1F) as usize;
+            let simm = (raw & 0xFFFF) as i16 as i32;
+            let uimm = raw & 0xFFFF;
+            match primary {
+                // addi / addis; rA = 0 means the literal 0.
+                14 | 15 => {
+                    let delta = if primary == 15 { simm << 16 } else { simm };
+                    regs[rd] = if ra == 0 {
+                        Some(PointsTo::Absolute(delta as u32))
+                    } else {
+                        regs[ra].map(|p| p.offset_by(delta))
+                    };
+                }
+                // ori rA,rS,UIMM on an absolute address (the `@l` half).
+                24 => {
+                    regs[ra] = match regs[rd] {
+                        Some(PointsTo::Absolute(a)) => Some(PointsTo::Absolute(a | uimm)),
+                        _ if uimm == 0 => regs[rd],
+                        _ => None,
+                    };
+                }
+                // D-form loads and stores.
+                32..=55 => {
+                    let store = matches!(primary, 36..=39 | 44 | 45 | 47 | 52..=55);
+                    if let Some(base) = regs[ra].filter(|_| ra != 0) {
+                        let target = base.offset_by(simm);
+                        result.accesses.push(MemoryAccess {
+                            at: inst.address,
+                            register: ra as u8,
+                            target,
+                            region: target.region(map),
+                            store,
+                            size: access_size(primary, rd),
+                        });
+                    }
+                    // Update forms move the base; integer loads overwrite rD.
+                    if primary % 2 == 1 && primary != 47 && primary != 46 {
+                        regs[ra] = regs[ra].map(|p| p.offset_by(simm));
+                    }
+                    if !store && primary < 48 {
+                        regs[rd] = None;
+                        if primary == 46 {
+                            regs[rd..].iter_mut().for_each(|r| *r = None); // lmw
+                        }
+                    }
+                }
+                // mr rA,rS (or rA,rS,rS)
+                31 if (raw >> 1) & 0x3FF == 444 && rd == rb => regs[ra] = regs[rd],
+                // Compares, branches, FP loads/stores leave GPRs alone.
+                10 | 11 | 16..=19 => {
+                    if primary == 18 && raw & 1 != 0 {
+                        clobber_volatile(&mut regs);
+                    }
+                }
+                // Rotates and logical immediates write rA.
+                20..=29 => regs[ra] = None,
+                _ => {
+                    regs[rd] = None;
+                    regs[ra] = None;
+                }
+            }
+        }
+        result
+    }
+}
+
+fn entry_state() -> [Option<PointsTo>; 32] {
+    let mut regs = [None; 32];
+    regs[1] = Some(PointsTo::Stack(0));
+    regs[2] = Some(PointsTo::SmallData2(0));
+    regs[13] = Some(PointsTo::SmallData(0));
+    regs
+}
+
+/// Bytes a D-form load or store with primary opcode `primary` touches.
+fn access_size(primary: u32, rd: usize) -> u32 {
+    match primary {
+        34 | 35 | 38 | 39 => 1,
+        40..=45 => 2,
+        46 | 47 => 4 * (32 - rd as u32), // lmw/stmw
+        50 | 51 | 54 | 55 => 8,
+        _ => 4,
+    }
+}
+
+/// Calls may change r0 and r3-r12.
+fn clobber_volatile(regs: &mut [Option<PointsTo>; 32]) {
+    regs[0] = None;
+    regs[3..=12].iter_mut().for_each(|r| *r = None);
+}
+
+/// Targets of relative `b`/`bc` within the instruction list.
+pub(crate) fn branch_targets(instructions: &[DecodedInstruction]) -> HashSet<u32> {
+    instructions
+        .iter()
+        .filter_map(|inst| {
+            let raw = inst.raw;
+            if raw & 2 != 0 {
+                return None; // absolute
+            }
+            let disp = match raw >> 26 {
+                18 if raw & 1 == 0 => ((raw & 0x03FF_FFFC) as i32) << 6 >> 6,
+                16 => (raw & 0xFFFC) as i16 as i32,
+                _ => return None,
+            };
+            Some(inst.address.wrapping_add(disp as u32))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recompiler::decoder::Instruction;
+    use crate::recompiler::parser::Section;
+
+    fn map() -> MemoryMap {
+        let section = |address: u32, size: u32, executable: bool| Section {
+            offset: 0x100,
+            address,
+            size,
+            data: vec![],
+            executable,
+        };
+        let dol = DolFile {
+            text_sections: vec![section(0x8000_3100, 0x1000, true)],
+            data_sections: vec![section(0x8020_0000, 0x1000, false)],
+            bss_address: 0x8030_0000,
+            bss_size: 0x1000,
+            entry_point: 0x8000_3100,
+            path: "test.dol".to_string(),
+        };
+        MemoryMap::from_dol(&dol, DEFAULT_SDA_BASE, DEFAULT_SDA_BASE)
+    }
+
+    fn analyze(words: &[u32]) -> PointerAnalysis {
+        let instructions: Vec<DecodedInstruction> = words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, 0x8000_3100 + i as u32 * 4).unwrap())
+            .collect();
+        PointerAnalyzer::analyze(&instructions, &map())
+    }
+
+    #[test]
+    fn register_from_r13_is_small_data() {
+        // addi r3,r13,-0x7F00 ; lwz r4,8(r3) ; blr
+        let result = analyze(&[0x386D_8100, 0x8083_0008, 0x4E80_0020]);
+        assert_eq!(
+            result.accesses,
+            vec![MemoryAccess {
+                at: 0x8000_3104,
+                register: 3,
+                target: PointsTo::SmallData(-0x7EF8),
+                region: MemoryRegion::SmallData,
+                store: false,
+                size: 4,
+            }]
+        );
+    }
+
+    #[test]
+    fn stack_hardware_and_data_accesses_are_classified() {
+        let result = analyze(&[
+            0x9421_FFF0, // stwu r1,-16(r1)
+            0x9001_0008, // stw r0,8(r1)
+            0x3CA0_CC00, // lis r5,0xCC00
+            0xA0C5_2000, // lhz r6,0x2000(r5)   VI
+            0x3CE0_8020, // lis r7,0x8020
+            0x38E7_0010, // addi r7,r7,0x10
+            0x8107_0000, // lwz r8,0(r7)        .data
+            0x4E80_0020, // blr
+        ]);
+        let regions: Vec<(MemoryRegion, bool)> = result
+            .accesses
+            .iter()
+            .map(|a| (a.region, a.store))
+            .collect();
+        assert_eq!(
+            regions,
+            vec![
+                (MemoryRegion::Stack, true),
+                (MemoryRegion::Stack, true),
+                (MemoryRegion::Hardware, false),
+                (MemoryRegion::Data, false),
+            ]
+        );
+        // stwu moved r1 down, so the second store is at frame offset -8.
+        assert_eq!(result.accesses[1].target, PointsTo::Stack(-8));
+        assert_eq!(result.accesses[3].target, PointsTo::Absolute(0x8020_0010));
+    }
+}
@@ -0,0 +1,272 @@
+//! Structural Function Similarity
+//!
+//! A native stand-in for Ghidra BSim. Where the function ID database
+//! (`recompiler::fidb`) needs the exact code, this compares the *shape* of a
+//! function, so a library function built with different register allocation
+//! or a slightly different compiler still gets a suggested name.
+//!
+//! Each function is reduced to a [`FeatureVector`]:
+//! - the share of each `InstructionType`,
+//! - control-flow shape: conditional branches, backward branches (loops) and
+//!   returns per instruction,
+//! - call fan-out: calls per instruction.
+//!
+//! Two functions' similarity is the cosine of their vectors scaled by the
+//! ratio of their sizes, so a three-instruction stub never "matches" a loop
+//! body with the same mix. References use the function ID database format.
+
+use crate::recompiler::decoder::{DecodedInstruction, Instruction, InstructionType};
+use crate::recompiler::fidb::FidDatabase;
+use crate::recompiler::ghidra::FunctionInfo;
+use std::collections::HashMap;
+
+/// Suggestions scoring below this are dropped.
+pub const DEFAULT_MIN_SIMILARITY: f32 = 0.95;
+
+/// Instruction-type shares plus the four shape features.
+const TYPE_FEATURES: usize = InstructionType::Unknown as usize + 1;
+const FEATURES: usize = TYPE_FEATURES + 4;
+
+/// Base address references are decoded at; only relative displacements matter.
+const REFERENCE_BASE: u32 = 0x8000_0000;
+
+/// Structural summary of one function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureVector {
+    pub instructions: usize,
+    values: [f32; FEATURES],
+}
+
+impl FeatureVector {
+    pub fn from_instructions(instructions: &[DecodedInstruction]) -> Self {
+        let mut values = [0.0f32; FEATURES];
+        for inst in instructions {
+            values[inst.instruction.instruction_type as usize] += 1.0;
+            let raw = inst.raw;
+            let lk = raw & 1 != 0;
+            match raw >> 26 {
+                16 => {
+                    let bo = (raw >> 21) & 0x1F;
+                    if bo & 0x14 != 0x14 {
+                        values[TYPE_FEATURES] += 1.0; // conditional
+                    }
+                    if (raw & 0xFFFC) as i16 as i32 <= 0 {
+                        values[TYPE_FEATURES + 1] += 1.0; // backward
+                    }
+                }
+                18 if lk => values[TYPE_FEATURES + 3] += 1.0,
+                18 if ((raw & 0x03FF_FFFC) << 6) as i32 <= 0 => values[TYPE_FEATURES + 1] += 1.0,
+                19 => match (raw >> 1) & 0x3FF {
+                    16 if !lk => values[TYPE_FEATURES + 2] += 1.0, // blr
+                    16 | 528 if lk => values[TYPE_FEATURES + 3] += 1.0, // blrl/bctrl
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        if !instructions.is_empty() {
+            let n = instructions.len() as f32;
+            values.iter_mut().for_each(|v| *v /= n);
+        }
+        Self {
+            instructions: instructions.len(),
+            values,
+        }
+    }
+
+    /// Decode `words` as one function and summarize it.
+    pub fn from_words(words: &[u32]) -> Self {
+        let instructions: Vec<DecodedInstruction> = words
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &w)| Instruction::decode(w, REFERENCE_BASE + i as u32 * 4).ok())
+            .collect();
+        Self::from_instructions(&instructions)
+    }
+
+    /// 0.0 (unrelated) to 1.0 (same shape and size).
+    pub fn similarity(&self, other: &FeatureVector) -> f32 {
+        let dot: f32 = self
+            .values
+            .iter()
+            .zip(&other.values)
+            .map(|(a, b)| a * b)
+            .sum();
+        let norm = |v: &[f32; FEATURES]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let norms = norm(&self.values) * norm(&other.values);
+        if norms == 0.0 {
+            return 0.0;
+        }
+        let (small, large) = if self.instructions < other.instructions {
+            (self.instructions, other.instructions)
+        } else {
+            (other.instructions, self.instructions)
+        };
+        (dot / norms) * (small as f32 / large as f32)
+    }
+}
+
+/// A suggested identity for a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub address: u32,
+    pub name: String,
+    pub similarity: f32,
+}
+
+/// Reference functions to search.
+#[derive(Debug, Clone, Default)]
+pub struct SimilarityIndex {
+    references: Vec<(String, FeatureVector)>,
+}
+
+impl SimilarityIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_database(db: &FidDatabase) -> Self {
+        let mut index = Self::new();
+        for signature in db.signatures() {
+            index.insert(&signature.name, FeatureVector::from_words(&signature.words));
+        }
+        index
+    }
+
+    pub fn insert(&mut self, name: &str, features: FeatureVector) {
+        self.references.push((name.to_string(), features));
+    }
+
+    pub fn len(&self) -> usize {
+        self.references.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.references.is_empty()
+    }
+
+    /// The closest reference and its similarity.
+    pub fn nearest(&self, features: &FeatureVector) -> Option<(&str, f32)> {
+        self.references
+            .iter()
+            .map(|(name, reference)| (name.as_str(), reference.similarity(features)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Name the still-unnamed (`sub_`) functions whose nearest reference
+    /// scores at least `min_similarity`.
+    pub fn apply(
+        &self,
+        functions: &mut [FunctionInfo],
+        instructions: &[DecodedInstruction],
+        min_similarity: f32,
+    ) -> Vec<Suggestion> {
+        let by_address: HashMap<u32, &DecodedInstruction> =
+            instructions.iter().map(|i| (i.address, i)).collect();
+        let mut suggestions = Vec::new();
+        for function in functions.iter_mut() {
+            if !function.name.is_empty() && !function.name.starts_with("sub_") {
+                continue;
+            }
+            let body: Vec<DecodedInstruction> = (0..function.size / 4)
+                .filter_map(|i| by_address.get(&function.address.wrapping_add(i * 4)))
+                .map(|&inst| inst.clone())
+                .collect();
+            let features = FeatureVector::from_instructions(&body);
+            let Some((name, similarity)) = self.nearest(&features) else {
+                continue;
+            };
+            if similarity < min_similarity {
+                continue;
+            }
+            function.name = name.to_string();
+            suggestions.push(Suggestion {
+                address: function.address,
+                name: name.to_string(),
+                similarity,
+            });
+        }
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Byte copy loop: addi ; mtctr ; lbzu ; stbu ; bdnz ; blr.
+    const COPY: [u32; 6] = [
+        0x3884_FFFF, // subi r4,r4,1
+        0x7CA9_03A6, // mtctr r5
+        0x8C04_0001, // lbzu r0,1(r4)
+        0x9C03_0001, // stbu r0,1(r3)
+        0x4200_FFF8, // bdnz -8
+        0x4E80_0020, // blr
+    ];
+
+    /// The same loop with other registers.
+    const COPY_RENAMED: [u32; 6] = [
+        0x38A5_FFFF, // subi r5,r5,1
+        0x7CC9_03A6, // mtctr r6
+        0x8CE5_0001, // lbzu r7,1(r5)
+        0x9CE4_0001, // stbu r7,1(r4)
+        0x4200_FFF8, // bdnz -8
+        0x4E80_0020, // blr
+    ];
+
+    /// Float arithmetic, no loop: lfs ; lfs ; fmuls ; fadds ; stfs ; blr.
+    const SCALE: [u32; 6] = [
+        0xC023_0000, // lfs f1,0(r3)
+        0xC043_0004, // lfs f2,4(r3)
+        0xEC21_00B2, // fmuls f1,f1,f2
+        0xEC21_102A, // fadds f1,f1,f2
+        0xD023_0008, // stfs f1,8(r3)
+        0x4E80_0020, // blr
+    ];
+
+    #[test]
+    fn identical_structure_matches_and_dissimilar_does_not() {
+        let mut index = SimilarityIndex::new();
+        index.insert("copy_bytes", FeatureVector::from_words(&COPY));
+
+        let renamed = FeatureVector::from_words(&COPY_RENAMED);
+        let (name, score) = index.nearest(&renamed).unwrap();
+        assert_eq!(name, "copy_bytes");
+        assert!(score >= DEFAULT_MIN_SIMILARITY, "{score}");
+
+        let (_, score) = index.nearest(&FeatureVector::from_words(&SCALE)).unwrap();
+        assert!(score < DEFAULT_MIN_SIMILARITY, "{score}");
+    }
+
+    #[test]
+    fn apply_names_only_unnamed_functions() {
+        let mut index = SimilarityIndex::new();
+        index.insert("copy_bytes", FeatureVector::from_words(&COPY));
+        let instructions: Vec<DecodedInstruction> = COPY_RENAMED
+            .iter()
+            .chain(&COPY_RENAMED)
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, 0x8000_4000 + i as u32 * 4).unwrap())
+            .collect();
+        let function = |address: u32, name: &str| FunctionInfo {
+            address,
+            name: name.to_string(),
+            size: 24,
+            calling_convention: "default".to_string(),
+            parameters: vec![],
+            return_type: None,
+            local_variables: vec![],
+            basic_blocks: vec![],
+        };
+        let mut functions = vec![
+            function(0x8000_4000, "sub_80004000"),
+            function(0x8000_4018, "memmove"),
+        ];
+
+        let suggestions = index.apply(&mut functions, &instructions, DEFAULT_MIN_SIMILARITY);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].address, 0x8000_4000);
+        assert_eq!(functions[0].name, "copy_bytes");
+        assert_eq!(functions[1].name, "memmove");
+    }
+}
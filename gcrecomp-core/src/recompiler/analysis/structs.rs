@@ -0,0 +1,454 @@
+//! Struct Layouts and Generated Accessors
+//!
+//! A [`StructInfo`] describes a structure in emulated memory: named fields at
+//! byte offsets from a base address. [`StructureGenerator`] turns each layout
+//! into Rust a porter can call from recompiled code:
+//!
+//! - a `#[repr(C)]` struct mirroring the layout, as documentation only. Guest
+//!   memory is big-endian and the host usually isn't, so the struct is never
+//!   cast onto RAM;
+//! - `get_<field>(memory, base)` / `set_<field>(memory, base, value)`
+//!   associated functions that go through the big-endian `MemoryManager`
+//!   accessors at `base + offset`, picking the width from the field's kind.
+//!
+//! [`StructDetector`] infers layouts from one function's loads and stores: a
+//! pointer value (a register, followed through `mr` copies) dereferenced at
+//! several offsets is taken as a struct, one field per offset. Values are
+//! numbered in a single forward pass; at a branch target every register the
+//! function writes outside its entry block gets a new number, as it may hold
+//! something else there.
+
+use crate::recompiler::analysis::pointer::branch_targets;
+use crate::recompiler::codegen::sda;
+use crate::recompiler::decoder::DecodedInstruction;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// Fewest distinct offsets through one pointer that make it a struct.
+pub const MIN_FIELDS: usize = 2;
+
+/// Type and width of a struct field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    U8,
+    U16,
+    U32,
+    U64,
+    F32,
+    F64,
+}
+
+impl FieldKind {
+    /// Width in bytes.
+    pub fn size(self) -> u32 {
+        match self {
+            FieldKind::U8 => 1,
+            FieldKind::U16 => 2,
+            FieldKind::U32 | FieldKind::F32 => 4,
+            FieldKind::U64 | FieldKind::F64 => 8,
+        }
+    }
+
+    /// Rust type of the field.
+    pub fn rust_type(self) -> &'static str {
+        match self {
+            FieldKind::U8 => "u8",
+            FieldKind::U16 => "u16",
+            FieldKind::U32 => "u32",
+            FieldKind::U64 => "u64",
+            FieldKind::F32 => "f32",
+            FieldKind::F64 => "f64",
+        }
+    }
+
+    /// The integer `MemoryManager` accessor width (floats travel as bits).
+    fn bits_type(self) -> &'static str {
+        match self {
+            FieldKind::F32 => "u32",
+            FieldKind::F64 => "u64",
+            other => other.rust_type(),
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, FieldKind::F32 | FieldKind::F64)
+    }
+}
+
+/// One field of a [`StructInfo`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldInfo {
+    pub name: String,
+    /// Byte offset from the struct base.
+    pub offset: u32,
+    pub kind: FieldKind,
+}
+
+/// A structure layout in emulated memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructInfo {
+    pub name: String,
+    /// Total size in bytes (may include trailing padding).
+    pub size: u32,
+    /// Fields in offset order.
+    pub fields: Vec<FieldInfo>,
+}
+
+impl StructInfo {
+    pub fn field_at(&self, offset: u32) -> Option<&FieldInfo> {
+        self.fields.iter().find(|f| f.offset == offset)
+    }
+}
+
+/// Result of [`StructDetector::detect`].
+#[derive(Debug, Clone, Default)]
+pub struct DetectedStructs {
+    pub structs: Vec<StructInfo>,
+    /// Load/store address -> (struct, field) indices.
+    accesses: HashMap<u32, (usize, usize)>,
+}
+
+impl DetectedStructs {
+    /// The struct and field the load or store at `address` accesses.
+    pub fn field_at(&self, address: u32) -> Option<(&StructInfo, &FieldInfo)> {
+        let &(s, f) = self.accesses.get(&address)?;
+        let info = &self.structs[s];
+        Some((info, &info.fields[f]))
+    }
+}
+
+/// Infers struct layouts from a function's D-form loads and stores.
+pub struct StructDetector;
+
+impl StructDetector {
+    /// Structs dereferenced in `instructions` (one function, in address
+    /// order), named `{prefix}_r{N}` after the register first seen holding
+    /// each, with fields `field_{offset}`. Stack and small-data bases (r1,
+    /// r2, r13) and negative offsets are left out, as are pointers whose
+    /// accesses partly overlap (a field read both whole and in pieces).
+    pub fn detect(prefix: &str, instructions: &[DecodedInstruction]) -> DetectedStructs {
+        let labels = branch_targets(instructions);
+        let entry_end = instructions
+            .iter()
+            .position(|i| matches!(i.raw >> 26, 16..=19))
+            .map_or(instructions.len(), |b| b + 1);
+        // Registers written after the entry block (or more than once).
+        let mut unstable = [false; 32];
+        let mut writes = [0usize; 32];
+        for (i, inst) in instructions.iter().enumerate() {
+            for r in 0..32u8 {
+                if writes_register(inst.raw, r) {
+                    writes[r as usize] += 1;
+                    unstable[r as usize] |= i >= entry_end || writes[r as usize] > 1;
+                }
+            }
+        }
+
+        let mut values: [usize; 32] = std::array::from_fn(|r| r);
+        let mut next_value = 32;
+        // value -> (register first seen, [(offset, kind, access address)])
+        let mut pointers: HashMap<usize, (u8, Vec<(u32, FieldKind, u32)>)> = HashMap::new();
+        let mut order: Vec<usize> = Vec::new();
+        let mut after_branch = false;
+        for inst in instructions {
+            if after_branch || labels.contains(&inst.address) {
+                for (r, value) in values.iter_mut().enumerate() {
+                    if unstable[r] {
+                        *value = next_value;
+                        next_value += 1;
+                    }
+                }
+            }
+            let raw = inst.raw;
+            after_branch = matches!(raw >> 26, 16..=19);
+            let rs = ((raw >> 21) & 31) as u8;
+            let ra = ((raw >> 16) & 31) as u8;
+            let offset = (raw & 0xFFFF) as i16;
+            if let Some(kind) = access_kind(raw >> 26) {
+                if !matches!(ra, 0 | 1 | 2 | 13) && offset >= 0 {
+                    let value = values[ra as usize];
+                    let (_, accesses) = pointers.entry(value).or_insert_with(|| {
+                        order.push(value);
+                        (ra, Vec::new())
+                    });
+                    accesses.push((offset as u32, kind, inst.address));
+                }
+            }
+            // mr rA,rS keeps the value; any other write makes a new one.
+            if raw >> 26 == 31 && (raw >> 1) & 0x3FF == 444 && (raw >> 11) & 31 == rs as u32 {
+                values[ra as usize] = values[rs as usize];
+                continue;
+            }
+            for r in 0..32u8 {
+                if writes_register(raw, r) {
+                    values[r as usize] = next_value;
+                    next_value += 1;
+                }
+            }
+        }
+
+        let mut detected = DetectedStructs::default();
+        for value in order {
+            let (register, accesses) = &pointers[&value];
+            let mut fields: Vec<FieldInfo> = Vec::new();
+            for &(offset, kind, _) in accesses {
+                match fields.iter_mut().find(|f| f.offset == offset) {
+                    Some(field) if kind.size() > field.kind.size() => field.kind = kind,
+                    Some(_) => {}
+                    None => fields.push(FieldInfo {
+                        name: format!("field_{offset:02X}"),
+                        offset,
+                        kind,
+                    }),
+                }
+            }
+            fields.sort_by_key(|f| f.offset);
+            let overlapping = fields
+                .windows(2)
+                .any(|w| w[0].offset + w[0].kind.size() > w[1].offset);
+            if fields.len() < MIN_FIELDS || overlapping {
+                continue;
+            }
+            let index = detected.structs.len();
+            for &(offset, _, at) in accesses {
+                if let Some(field) = fields.iter().position(|f| f.offset == offset) {
+                    detected.accesses.insert(at, (index, field));
+                }
+            }
+            let last = &fields[fields.len() - 1];
+            detected.structs.push(StructInfo {
+                name: format!("{prefix}_r{register}"),
+                size: last.offset + last.kind.size(),
+                fields,
+            });
+        }
+        detected
+    }
+}
+
+/// Field kind of a D-form load/store (lmw/stmw aside).
+fn access_kind(primary: u32) -> Option<FieldKind> {
+    match primary {
+        32 | 33 | 36 | 37 => Some(FieldKind::U32),
+        34 | 35 | 38 | 39 => Some(FieldKind::U8),
+        40..=45 => Some(FieldKind::U16),
+        48 | 49 | 52 | 53 => Some(FieldKind::F32),
+        50 | 51 | 54 | 55 => Some(FieldKind::F64),
+        _ => None,
+    }
+}
+
+/// Whether `raw` may write GPR `reg`, counting the registers a call clobbers.
+fn writes_register(raw: u32, reg: u8) -> bool {
+    let call = matches!(raw >> 26, 16 | 18 | 19) && raw & 1 != 0;
+    (call && (reg == 0 || (3..=12).contains(&reg))) || sda::writes_gpr(raw, reg)
+}
+
+/// Emits Rust for detected struct layouts.
+pub struct StructureGenerator;
+
+impl StructureGenerator {
+    /// All structs, one after another.
+    pub fn generate_all(structs: &[StructInfo]) -> String {
+        structs
+            .iter()
+            .map(Self::generate)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// All structs in a `structs` module, as codegen emits them into the body
+    /// of the function using them; callers name them `structs::{name}`.
+    pub fn generate_module(structs: &[StructInfo]) -> String {
+        let mut code = String::new();
+        let _ = writeln!(
+            code,
+            "// The layout structs are never built and a function calls only the"
+        );
+        let _ = writeln!(
+            code,
+            "// accessors it needs, so the rest of the module is dead by design."
+        );
+        let _ = writeln!(code, "#[allow(dead_code)]");
+        let _ = writeln!(code, "mod structs {{");
+        let _ = writeln!(code, "use super::*;\n");
+        code.push_str(&Self::generate_all(structs));
+        let _ = writeln!(code, "}}");
+        code
+    }
+
+    /// The documentation struct and accessor `impl` for `info`.
+    pub fn generate(info: &StructInfo) -> String {
+        let name = identifier(&info.name);
+        let mut code = String::new();
+
+        let _ = writeln!(
+            code,
+            "/// `{}` (0x{:X} bytes). Layout for reference only: guest memory is",
+            info.name, info.size
+        );
+        let _ = writeln!(
+            code,
+            "/// big-endian, so access fields through the associated functions."
+        );
+        let _ = writeln!(code, "#[repr(C)]");
+        let _ = writeln!(code, "pub struct {name} {{");
+        for field in &info.fields {
+            let _ = writeln!(
+                code,
+                "    pub {}: {}, // +0x{:02X}",
+                identifier(&field.name),
+                field.kind.rust_type(),
+                field.offset
+            );
+        }
+        let _ = writeln!(code, "}}\n");
+
+        let _ = writeln!(code, "impl {name} {{");
+        let _ = writeln!(code, "    pub const SIZE: u32 = 0x{:X};", info.size);
+        for field in &info.fields {
+            code.push('\n');
+            code.push_str(&Self::accessors(field));
+        }
+        let _ = writeln!(code, "}}");
+        code
+    }
+
+    fn accessors(field: &FieldInfo) -> String {
+        let ident = identifier(&field.name);
+        let ty = field.kind.rust_type();
+        let bits = field.kind.bits_type();
+        let addr = format!("base.wrapping_add(0x{:X})", field.offset);
+        let (load, store) = if field.kind.is_float() {
+            (
+                format!("Ok({ty}::from_bits(memory.read_{bits}({addr})?))"),
+                format!("memory.write_{bits}({addr}, value.to_bits())"),
+            )
+        } else {
+            (
+                format!("memory.read_{bits}({addr})"),
+                format!("memory.write_{bits}({addr}, value)"),
+            )
+        };
+
+        let mut code = String::new();
+        let _ = writeln!(
+            code,
+            "    pub fn get_{ident}(memory: &MemoryManager, base: u32) -> Result<{ty}> {{"
+        );
+        let _ = writeln!(code, "        {load}");
+        let _ = writeln!(code, "    }}\n");
+        let _ = writeln!(
+            code,
+            "    pub fn set_{ident}(memory: &mut MemoryManager, base: u32, value: {ty}) -> Result<()> {{"
+        );
+        let _ = writeln!(code, "        {store}");
+        let _ = writeln!(code, "    }}");
+        code
+    }
+}
+
+/// A Rust identifier for `name`: non-identifier characters become `_`, and a
+/// leading digit gets a `_` prefix.
+pub(crate) fn identifier(name: &str) -> String {
+    let ident: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{ident}")
+    } else {
+        ident
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recompiler::decoder::Instruction;
+
+    fn decode(words: &[u32]) -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, 0x8000_3000 + i as u32 * 4).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn pointers_are_followed_through_copies_but_not_calls() {
+        let detected = StructDetector::detect(
+            "s",
+            &decode(&[
+                0x7C7F_1B78, // mr r31,r3
+                0x8083_0000, // lwz r4,0(r3)
+                0xA0BF_0004, // lhz r5,4(r31)
+                0x4800_0101, // bl +0x100: r3 is no longer the argument
+                0xC03F_0008, // lfs f1,8(r31)
+                0x8083_000C, // lwz r4,12(r3)  another pointer
+                0x4E80_0020, // blr
+            ]),
+        );
+        assert_eq!(detected.structs.len(), 1);
+        let info = &detected.structs[0];
+        assert_eq!(info.name, "s_r3");
+        assert_eq!(info.size, 12);
+        let fields: Vec<(&str, u32, FieldKind)> = info
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.offset, f.kind))
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                ("field_00", 0, FieldKind::U32),
+                ("field_04", 4, FieldKind::U16),
+                ("field_08", 8, FieldKind::F32),
+            ]
+        );
+        assert_eq!(detected.field_at(0x8000_3008).unwrap().1.name, "field_04");
+        assert!(detected.field_at(0x8000_3014).is_none());
+    }
+
+    #[test]
+    fn three_field_struct_accessors_use_offsets_and_widths() {
+        let info = StructInfo {
+            name: "Actor".to_string(),
+            size: 0x10,
+            fields: vec![
+                FieldInfo {
+                    name: "flags".to_string(),
+                    offset: 0x0,
+                    kind: FieldKind::U8,
+                },
+                FieldInfo {
+                    name: "id".to_string(),
+                    offset: 0x2,
+                    kind: FieldKind::U16,
+                },
+                FieldInfo {
+                    name: "speed".to_string(),
+                    offset: 0xC,
+                    kind: FieldKind::F32,
+                },
+            ],
+        };
+        let code = StructureGenerator::generate(&info);
+
+        assert!(code.contains("#[repr(C)]\npub struct Actor {"), "{code}");
+        assert!(code.contains("pub const SIZE: u32 = 0x10;"), "{code}");
+        for expected in [
+            "pub fn get_flags(memory: &MemoryManager, base: u32) -> Result<u8>",
+            "memory.read_u8(base.wrapping_add(0x0))",
+            "memory.write_u8(base.wrapping_add(0x0), value)",
+            "pub fn get_id(memory: &MemoryManager, base: u32) -> Result<u16>",
+            "memory.read_u16(base.wrapping_add(0x2))",
+            "memory.write_u16(base.wrapping_add(0x2), value)",
+            "pub fn get_speed(memory: &MemoryManager, base: u32) -> Result<f32>",
+            "Ok(f32::from_bits(memory.read_u32(base.wrapping_add(0xC))?))",
+            "memory.write_u32(base.wrapping_add(0xC), value.to_bits())",
+        ] {
+            assert!(code.contains(expected), "missing `{expected}`:\n{code}");
+        }
+    }
+}
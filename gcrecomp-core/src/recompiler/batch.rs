@@ -0,0 +1,192 @@
+//! Batch instruction decoding
+//!
+//! Decodes a whole text section at once, producing exactly what calling
+//! [`Instruction::decode`] per word would. Words are processed in blocks of
+//! [`LANES`]: the byte swap and the primary opcode / RT / RA / immediate field
+//! extraction run as SIMD over the block, then each lane whose opcode is a
+//! plain D-form (`op RT, D(RA)` / `op RT, RA, SI` — the loads, stores and
+//! immediate arithmetic that make up most game code) is built straight from
+//! those fields. Everything else, including the opcode 31/63 extended forms,
+//! goes through the scalar decoder.
+//!
+//! The D-form table below has to match the scalar decoder's arms exactly; the
+//! `batch_decode_test` comparison over every opcode keeps it honest.
+
+use crate::recompiler::decoder::{DecodedInstruction, Instruction, InstructionType, Operand};
+use crate::recompiler::error::Result;
+use smallvec::SmallVec;
+use wide::u32x8;
+
+/// Words per SIMD block.
+pub const LANES: usize = 8;
+
+/// How the scalar decoder lays out a D-form opcode: its type, and whether the
+/// first operand is an FPR (`lfs`/`stfd`/...) rather than a GPR.
+#[derive(Clone, Copy)]
+struct DForm {
+    kind: InstructionType,
+    fpr: bool,
+}
+
+const fn gpr(kind: InstructionType) -> Option<DForm> {
+    Some(DForm { kind, fpr: false })
+}
+
+const fn fpr() -> Option<DForm> {
+    Some(DForm {
+        kind: InstructionType::FloatingPoint,
+        fpr: true,
+    })
+}
+
+/// Primary opcodes the scalar decoder turns into `[RT, RA, Immediate(low 16)]`.
+static DFORM: [Option<DForm>; 64] = {
+    use InstructionType::{Arithmetic, Load, Store};
+    let mut t: [Option<DForm>; 64] = [None; 64];
+    t[7] = gpr(Arithmetic); // mulli
+    t[8] = gpr(Arithmetic); // subfic
+    t[12] = gpr(Arithmetic); // addic
+    t[14] = gpr(Arithmetic); // addi
+    t[15] = gpr(Arithmetic); // addis
+    t[24] = gpr(Arithmetic); // ori
+    t[25] = gpr(Arithmetic); // oris
+    t[26] = gpr(Arithmetic); // xori
+    t[27] = gpr(Arithmetic); // xoris
+    t[28] = gpr(Arithmetic); // andi.
+    t[29] = gpr(Arithmetic); // andis.
+    t[32] = gpr(Load); // lwz
+    t[33] = gpr(Load); // lwzu
+    t[34] = gpr(Load); // lbz
+    t[35] = gpr(Load); // lbzu
+    t[36] = gpr(Store); // stw
+    t[37] = gpr(Store); // stwu
+    t[38] = gpr(Store); // stb
+    t[39] = gpr(Store); // stbu
+    t[40] = gpr(Load); // lhz
+    t[41] = gpr(Load); // lhzu
+    t[42] = gpr(Load); // lha
+    t[43] = gpr(Load); // lhau
+    t[44] = gpr(Store); // sth
+    t[45] = gpr(Store); // sthu
+    t[48] = fpr(); // lfs
+    t[49] = fpr(); // lfsu
+    t[50] = fpr(); // lfd
+    t[51] = fpr(); // lfdu
+    t[52] = fpr(); // stfs
+    t[53] = fpr(); // stfsu
+    t[54] = fpr(); // stfd
+    t[55] = fpr(); // stfdu
+    t
+};
+
+/// Field vectors for one block.
+struct Fields {
+    opcode: [u32; LANES],
+    rt: [u32; LANES],
+    ra: [u32; LANES],
+    imm: [u32; LANES],
+}
+
+#[inline]
+fn extract(words: [u32; LANES]) -> Fields {
+    let v = u32x8::from(words);
+    let five = u32x8::splat(0x1F);
+    Fields {
+        opcode: (v >> 26u32).to_array(),
+        rt: ((v >> 21u32) & five).to_array(),
+        ra: ((v >> 16u32) & five).to_array(),
+        imm: (v & u32x8::splat(0xFFFF)).to_array(),
+    }
+}
+
+/// Byte-swap a block of big-endian words.
+#[inline]
+fn load_be(bytes: &[u8]) -> [u32; LANES] {
+    let mut native = [0u32; LANES];
+    for (w, chunk) in native.iter_mut().zip(bytes.chunks_exact(4)) {
+        *w = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    if cfg!(target_endian = "big") {
+        return native;
+    }
+    // Per-lane bswap: rotate the bytes into place with shifts and masks.
+    let v = u32x8::from(native);
+    let byte = u32x8::splat(0xFF);
+    ((v >> 24u32) | ((v >> 8u32) & (byte << 8u32)) | ((v << 8u32) & (byte << 16u32)) | (v << 24u32))
+        .to_array()
+}
+
+#[inline]
+fn decode_block(
+    words: [u32; LANES],
+    count: usize,
+    address: u32,
+    out: &mut Vec<Result<DecodedInstruction>>,
+) {
+    let f = extract(words);
+    for (lane, &word) in words.iter().enumerate().take(count) {
+        let lane_address = address.wrapping_add(lane as u32 * 4);
+        let opcode = f.opcode[lane];
+        out.push(match DFORM[opcode as usize] {
+            Some(form) => {
+                let rt = f.rt[lane] as u8;
+                let first = if form.fpr {
+                    Operand::FpRegister(rt)
+                } else {
+                    Operand::Register(rt)
+                };
+                Ok(DecodedInstruction {
+                    instruction: Instruction {
+                        opcode,
+                        instruction_type: form.kind,
+                        operands: SmallVec::from_slice(&[
+                            first,
+                            Operand::Register(f.ra[lane] as u8),
+                            Operand::Immediate(f.imm[lane] as u16 as i16),
+                        ]),
+                    },
+                    raw: word,
+                    address: lane_address,
+                })
+            }
+            None => Instruction::decode(word, lane_address),
+        });
+    }
+}
+
+/// Decode `words`, the first at `address`. Element `i` is what
+/// `Instruction::decode(words[i], address + 4 * i)` returns.
+pub fn decode_words(words: &[u32], address: u32) -> Vec<Result<DecodedInstruction>> {
+    let mut out = Vec::with_capacity(words.len());
+    let mut chunks = words.chunks_exact(LANES);
+    let mut block_address = address;
+    for chunk in &mut chunks {
+        let block: [u32; LANES] = chunk.try_into().unwrap_or([0; LANES]);
+        decode_block(block, LANES, block_address, &mut out);
+        block_address = block_address.wrapping_add((LANES * 4) as u32);
+    }
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        let mut block = [0u32; LANES];
+        block[..rest.len()].copy_from_slice(rest);
+        decode_block(block, rest.len(), block_address, &mut out);
+    }
+    out
+}
+
+/// Decode a big-endian text section (trailing bytes short of a word are
+/// ignored, as with `chunks_exact(4)`).
+pub fn decode_section(data: &[u8], address: u32) -> Vec<Result<DecodedInstruction>> {
+    let mut words = Vec::with_capacity(data.len() / 4);
+    let mut blocks = data.chunks_exact(LANES * 4);
+    for block in &mut blocks {
+        words.extend_from_slice(&load_be(block));
+    }
+    words.extend(
+        blocks
+            .remainder()
+            .chunks_exact(4)
+            .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]])),
+    );
+    decode_words(&words, address)
+}
@@ -0,0 +1,204 @@
+//! Incremental Recompile Cache
+//!
+//! Most functions come out the same from one recompile to the next, so the
+//! pipeline can keep each function's generated Rust on disk and reuse it when
+//! nothing that went into it changed. Entries are content-addressed: the key
+//! is a SHA-256 over the function's instruction words, everything else the
+//! code generator looks at for it (name, optimizer settings, SDA bases, the
+//! bodies it inlines and the specialized copies it calls) and
+//! [`GENERATOR_VERSION`]. A changed function gets a new key and is generated
+//! again; the unchanged ones are read back.
+//!
+//! The directory also records the generator version it was written by. When
+//! that differs, every entry is dropped on open, so a new generator never
+//! reads code an old one produced. The pipeline uses the cache when
+//! `RecompileOptions::cache_dir` is set (`gcrecomp recompile --cache <dir>`).
+
+use crate::recompiler::error::{RecompileError, Result};
+use crate::recompiler::pipeline::Translation;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bump when the code generator's output changes for the same input; caches
+/// written by another version are cleared.
+pub const GENERATOR_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), "+codegen.1");
+
+/// File in the cache directory holding the version that wrote it.
+const VERSION_FILE: &str = "VERSION";
+
+/// Hashes what a function's generated code depends on into a [`CacheKey`].
+pub struct CacheKeyBuilder {
+    hasher: Sha256,
+}
+
+impl CacheKeyBuilder {
+    pub fn new() -> Self {
+        let mut builder = Self {
+            hasher: Sha256::new(),
+        };
+        builder.field(GENERATOR_VERSION);
+        builder
+    }
+
+    /// Add instruction words.
+    pub fn words(&mut self, words: impl IntoIterator<Item = u32>) -> &mut Self {
+        for word in words {
+            self.hasher.update(word.to_be_bytes());
+        }
+        self.hasher.update([0xFF]);
+        self
+    }
+
+    /// Add a setting or name, delimited so adjacent fields can't run together.
+    pub fn field(&mut self, value: &str) -> &mut Self {
+        self.hasher.update((value.len() as u64).to_be_bytes());
+        self.hasher.update(value.as_bytes());
+        self
+    }
+
+    pub fn finish(&self) -> CacheKey {
+        let mut hex = String::with_capacity(64);
+        for byte in self.hasher.clone().finalize() {
+            let _ = write!(hex, "{byte:02x}");
+        }
+        CacheKey(hex)
+    }
+}
+
+impl Default for CacheKeyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lowercase hex SHA-256 naming one cache entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey(String);
+
+impl CacheKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    translation: Translation,
+    code: String,
+}
+
+/// Generated functions on disk, one JSON file per [`CacheKey`].
+#[derive(Debug, Clone)]
+pub struct FunctionCache {
+    dir: PathBuf,
+}
+
+impl FunctionCache {
+    /// Open (creating) the cache in `dir`, clearing it if another generator
+    /// version wrote it.
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let version_path = dir.join(VERSION_FILE);
+        let version = fs::read_to_string(&version_path).unwrap_or_default();
+        if version.trim() != GENERATOR_VERSION {
+            let mut cleared = 0usize;
+            for entry in fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|e| e == "json") {
+                    fs::remove_file(&path)?;
+                    cleared += 1;
+                }
+            }
+            if cleared > 0 {
+                log::info!(
+                    "Cleared {cleared} cached functions from {} (generator {} -> {GENERATOR_VERSION})",
+                    dir.display(),
+                    if version.is_empty() { "unknown" } else { version.trim() }
+                );
+            }
+            fs::write(&version_path, format!("{GENERATOR_VERSION}\n"))?;
+        }
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The code stored under `key`. A missing or unreadable entry is a miss.
+    pub fn get(&self, key: &CacheKey) -> Option<(String, Translation)> {
+        let text = fs::read_to_string(self.path(key)).ok()?;
+        let entry: Entry = serde_json::from_str(&text).ok()?;
+        Some((entry.code, entry.translation))
+    }
+
+    /// Store `code` under `key`, replacing any entry there.
+    pub fn insert(&self, key: &CacheKey, code: &str, translation: Translation) -> Result<()> {
+        let entry = Entry {
+            translation,
+            code: code.to_string(),
+        };
+        let text = serde_json::to_string(&entry).map_err(|e| {
+            RecompileError::Io(format!("Failed to encode cache entry {}: {e}", key.0))
+        })?;
+        fs::write(self.path(key), text)?;
+        Ok(())
+    }
+
+    fn path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.json", key.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gcrecomp-cache-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn keys_follow_every_field() {
+        let key = |words: &[u32], name: &str| {
+            CacheKeyBuilder::new()
+                .words(words.iter().copied())
+                .field(name)
+                .finish()
+        };
+        assert_eq!(key(&[1, 2], "f"), key(&[1, 2], "f"));
+        assert_ne!(key(&[1, 2], "f"), key(&[1, 3], "f"));
+        assert_ne!(key(&[1, 2], "f"), key(&[1, 2], "g"));
+        assert_eq!(key(&[], "").as_str().len(), 64);
+    }
+
+    #[test]
+    fn another_generator_version_clears_the_cache() {
+        let dir = temp_dir("version");
+        let key = CacheKeyBuilder::new().words([0x4E80_0020]).finish();
+        let cache = FunctionCache::open(&dir).unwrap();
+        cache
+            .insert(&key, "fn f() {}", Translation::Partial)
+            .unwrap();
+        assert_eq!(
+            FunctionCache::open(&dir).unwrap().get(&key),
+            Some(("fn f() {}".to_string(), Translation::Partial))
+        );
+
+        fs::write(dir.join(VERSION_FILE), "0.0.0+codegen.0\n").unwrap();
+        let cache = FunctionCache::open(&dir).unwrap();
+        assert_eq!(cache.get(&key), None);
+        assert_eq!(
+            fs::read_to_string(dir.join(VERSION_FILE)).unwrap().trim(),
+            GENERATOR_VERSION
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,96 @@
+// Leaf-function inlining: which callees can be spliced into their call sites.
+//
+// A candidate is a small straight-line leaf: no branches before its final
+// `blr`, every instruction translatable, and no write to r1. With no branches
+// it makes no calls, so nothing recursive is ever a candidate. The spliced
+// body runs against the same `CpuContext` the call would have handed it, so
+// the caller's non-volatile registers survive exactly as they do across the
+// real call. A leaf that builds a frame (`stwu r1,-F(r1)` first, `addi
+// r1,r1,F` last) has its stack offsets rebased by -F instead, so r1 never
+// moves and every access still hits the slot it did in the callee.
+use crate::recompiler::decoder::{DecodedInstruction, Instruction, InstructionType, Operand};
+use std::collections::HashMap;
+
+/// Largest body (excluding the `blr`) that gets inlined.
+pub const INLINE_BUDGET: usize = 8;
+
+const BLR: u32 = 0x4E80_0020;
+/// `stwu r1,d(r1)` / `stw r1,d(r1)` / `addi r1,r1,d` without the displacement.
+const STWU_R1: u32 = 0x9421_0000;
+const STW_R1: u32 = 0x9021_0000;
+const ADDI_R1: u32 = 0x3821_0000;
+
+/// Callee address -> body (without the trailing `blr`).
+pub type InlineCandidates = HashMap<u32, Vec<DecodedInstruction>>;
+
+/// The body to splice for `instructions` (one whole function), or `None` if it
+/// isn't a candidate within `budget` instructions.
+pub fn inline_body(
+    instructions: &[DecodedInstruction],
+    budget: usize,
+) -> Option<Vec<DecodedInstruction>> {
+    let (last, body) = instructions.split_last()?;
+    if last.raw != BLR || body.len() > budget {
+        return None;
+    }
+    let body = flatten_frame(body)?;
+    let splicable = body.iter().all(|inst| {
+        !matches!(
+            inst.instruction.instruction_type,
+            InstructionType::Branch | InstructionType::Unknown
+        ) && !may_write_stack_pointer(inst.raw)
+    });
+    splicable.then_some(body)
+}
+
+/// `body` with its stack frame, if it has one, taken out: the `stwu` becomes a
+/// plain `stw` of the back chain, the closing `addi` goes, and each `d(r1)`
+/// in between becomes `d-F(r1)`, the same address with r1 left where the
+/// caller had it. `None` if r1 is used some other way in a framed body.
+fn flatten_frame(body: &[DecodedInstruction]) -> Option<Vec<DecodedInstruction>> {
+    let (Some(first), Some(last)) = (body.first(), body.last()) else {
+        return Some(body.to_vec());
+    };
+    let size = -(first.raw as u16 as i16 as i32);
+    if body.len() < 2 || first.raw & 0xFFFF_0000 != STWU_R1 || size <= 0 {
+        return Some(body.to_vec());
+    }
+    if last.raw != ADDI_R1 | size as u32 {
+        return None;
+    }
+    let rebase = |inst: &DecodedInstruction, raw: u32| -> Option<DecodedInstruction> {
+        let d = (raw as u16 as i16 as i32).checked_sub(size)?;
+        let d = i16::try_from(d).ok()?;
+        Instruction::decode(raw & 0xFFFF_0000 | d as u16 as u32, inst.address).ok()
+    };
+
+    let mut flat = vec![Instruction::decode(STW_R1 | (first.raw & 0xFFFF), first.address).ok()?];
+    for inst in &body[1..body.len() - 1] {
+        let ra = (inst.raw >> 16) & 0x1F;
+        match inst.raw >> 26 {
+            // addi, D-form loads and stores (integer, multiple, float) off r1.
+            14 | 32 | 34 | 36 | 38 | 40 | 42 | 44 | 46..=48 | 50 | 52 | 54 if ra == 1 => {
+                flat.push(rebase(inst, inst.raw)?);
+            }
+            _ if inst.instruction.operands.contains(&Operand::Register(1)) => return None,
+            _ => flat.push(inst.clone()),
+        }
+    }
+    Some(flat)
+}
+
+/// Conservative: true unless the instruction provably leaves r1 alone.
+fn may_write_stack_pointer(raw: u32) -> bool {
+    let rd = (raw >> 21) & 0x1F;
+    let ra = (raw >> 16) & 0x1F;
+    match raw >> 26 {
+        // addi, lwz/lbz/lhz/lha: only rD is written.
+        14 | 32 | 34 | 40 | 42 => rd == 1,
+        // lmw loads rD..r31.
+        46 => rd <= 1,
+        // lfs/lfd, stw/stb/sth/stfs/stfd and stmw: rA is only a base.
+        48 | 50 | 36 | 38 | 44 | 52 | 54 | 47 => false,
+        // Anything else may write rD or rA (update forms, logical ops).
+        _ => rd == 1 || ra == 1,
+    }
+}
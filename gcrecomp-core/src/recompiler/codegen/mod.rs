@@ -1,20 +1,54 @@
 // Rust code generator with optimizations
+pub mod inline;
 pub mod memory;
 pub mod register;
+pub mod sda;
+pub mod structure;
 
+use crate::recompiler::analysis::control_flow::{BasicBlock, ControlFlowAnalyzer};
+use crate::recompiler::analysis::structs::{
+    self, DetectedStructs, FieldKind, StructDetector, StructureGenerator,
+};
 use crate::recompiler::analysis::FunctionMetadata;
 use crate::recompiler::decoder::{DecodedInstruction, InstructionType, Operand};
-use anyhow::Result;
-use std::collections::{BTreeSet, HashMap};
-
+use crate::recompiler::enrich;
+use crate::recompiler::error::{RecompileError, Result};
+use crate::runtime::context;
+use inline::InlineCandidates;
+use sda::SdaBases;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use structure::{Layout, ScopeKind};
+
+/// Cloning is cheap: a clone shares the inlining and specialization tables,
+/// so one configured generator can be cloned for each function.
+#[derive(Clone)]
 pub struct CodeGenerator {
     indent_level: usize,
     _register_map: HashMap<u8, String>,
     _next_temp: usize,
     register_values: HashMap<u8, RegisterValue>,
     optimize: bool,
+    /// Fail on untranslatable instructions instead of emitting a comment.
+    strict: bool,
     function_calls: Vec<u32>,              // Track function call targets
     _basic_block_map: HashMap<u32, usize>, // Map addresses to basic block indices
+    /// Leaf bodies spliced in place of a `bl` to them (see `inline`).
+    inline_candidates: Arc<InlineCandidates>,
+    /// `bl` address -> the specialized copy it calls (see `specialize`).
+    specialized_calls: Arc<HashMap<u32, String>>,
+    /// Branches in the current function that are calls in tail position.
+    tail_sites: HashSet<u32>,
+    /// Known r13/r2 at function entry (see `sda`).
+    sda_bases: Option<SdaBases>,
+    /// Structs the current function dereferences (see `analysis::structs`).
+    structs: DetectedStructs,
+    /// Access whole-word struct fields through generated accessors.
+    struct_accessors: bool,
+    /// Nest reducible functions in labeled scopes (see `structure`).
+    structured: bool,
+    /// Run tail calls on the dispatcher's trampoline.
+    tail_calls: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -32,8 +66,17 @@ impl CodeGenerator {
             _next_temp: 0,
             register_values: HashMap::new(),
             optimize: true,
+            strict: false,
             function_calls: Vec::new(),
             _basic_block_map: HashMap::new(),
+            inline_candidates: Arc::default(),
+            specialized_calls: Arc::default(),
+            tail_sites: HashSet::new(),
+            sda_bases: None,
+            structs: DetectedStructs::default(),
+            struct_accessors: false,
+            structured: true,
+            tail_calls: true,
         }
     }
 
@@ -42,15 +85,107 @@ impl CodeGenerator {
         self
     }
 
+    pub fn optimizations(&self) -> bool {
+        self.optimize
+    }
+
+    /// In strict mode an instruction that can't be translated fails the whole
+    /// function with `UnsupportedInstruction`/`CodegenError`, so callers can
+    /// decide to skip or stub it. The default leaves an `// untranslated`
+    /// comment and carries on.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Treat r13/r2 as holding `bases` on entry to every function that doesn't
+    /// set them, so small-data globals fold to absolute addresses. Only sound
+    /// when the runtime boots with these values and nothing else changes them.
+    pub fn with_sda_bases(mut self, bases: Option<SdaBases>) -> Self {
+        self.sda_bases = bases;
+        self
+    }
+
+    pub fn sda_bases(&self) -> Option<SdaBases> {
+        self.sda_bases
+    }
+
+    /// Loads and stores of a detected struct field always carry a
+    /// `// Struct.field` comment. With accessors on, whole-word ones also
+    /// call the field's `get_`/`set_` function, and the function starts with
+    /// the structs' definitions.
+    pub fn with_struct_accessors(mut self, accessors: bool) -> Self {
+        self.struct_accessors = accessors;
+        self
+    }
+
+    pub fn struct_accessors(&self) -> bool {
+        self.struct_accessors
+    }
+
+    /// Emit reducible functions as nested labeled blocks and loops (the
+    /// default). Off, every function goes through the `__blk` state machine.
+    pub fn with_structured(mut self, structured: bool) -> Self {
+        self.structured = structured;
+        self
+    }
+
+    /// Hand calls in tail position to the dispatcher's trampoline (the
+    /// default, see `enrich::tail_call_sites`). Off, they are nested calls.
+    pub fn with_tail_calls(mut self, tail_calls: bool) -> Self {
+        self.tail_calls = tail_calls;
+        self
+    }
+
+    /// Callees to inline at their `bl` sites instead of dispatching.
+    pub fn set_inline_candidates(&mut self, candidates: InlineCandidates) {
+        self.inline_candidates = Arc::new(candidates);
+    }
+
+    /// `bl` sites to call a specialized copy at, by the copy's identifier
+    /// (see [`specialized_identifier`](Self::specialized_identifier)).
+    pub fn set_specialized_calls(&mut self, calls: HashMap<u32, String>) {
+        self.specialized_calls = Arc::new(calls);
+    }
+
+    /// Name of copy `index` of the function `metadata` describes.
+    pub fn specialized_identifier(&self, metadata: &FunctionMetadata, index: usize) -> String {
+        format!(
+            "{}_spec{index}",
+            self.function_identifier(&metadata.name, metadata.address)
+        )
+    }
+
     pub fn generate_function(
         &mut self,
         metadata: &FunctionMetadata,
         instructions: &[DecodedInstruction],
     ) -> Result<String> {
-        let mut code = String::new();
+        let signature = self.generate_function_signature(metadata)?;
+        self.generate_function_with_signature(signature, instructions)
+    }
 
-        // Generate function signature
-        code.push_str(&self.generate_function_signature(metadata)?);
+    /// A specialized copy of `metadata`'s function: `instructions` is its
+    /// body optimized for the constants at its call sites.
+    pub fn generate_specialized_function(
+        &mut self,
+        metadata: &FunctionMetadata,
+        index: usize,
+        instructions: &[DecodedInstruction],
+    ) -> Result<String> {
+        let signature = format!(
+            "pub fn {}(ctx: &mut CpuContext, memory: &mut MemoryManager) -> Result<Option<u32>>",
+            self.specialized_identifier(metadata, index)
+        );
+        self.generate_function_with_signature(signature, instructions)
+    }
+
+    fn generate_function_with_signature(
+        &mut self,
+        signature: String,
+        instructions: &[DecodedInstruction],
+    ) -> Result<String> {
+        let mut code = signature;
         code.push_str(" {\n");
 
         self.indent_level += 1;
@@ -64,19 +199,33 @@ impl CodeGenerator {
         Ok(code)
     }
 
+    /// Fallback for a function that couldn't be translated: same signature,
+    /// but the body hands the original code (in RAM via `load_image`) to the
+    /// runtime interpreter. `reason` is recorded as a comment.
+    pub fn generate_interpreted_function(
+        &self,
+        metadata: &FunctionMetadata,
+        reason: &RecompileError,
+    ) -> Result<String> {
+        let mut code = format!("// Interpreted at runtime: {}\n", reason);
+        code.push_str(&self.generate_function_signature(metadata)?);
+        code.push_str(" {\n");
+        code.push_str(&format!(
+            "    gcrecomp_core::runtime::trace_call(0x{:08X}u32);\n",
+            metadata.address
+        ));
+        code.push_str(&format!(
+            "    gcrecomp_core::runtime::interpreter::interpret_function(0x{:08X}u32, ctx, memory)\n",
+            metadata.address
+        ));
+        code.push_str("}\n");
+        Ok(code)
+    }
+
     fn generate_function_signature(&self, metadata: &FunctionMetadata) -> Result<String> {
         let mut sig = String::new();
 
-        // Function name - include address for uniqueness and dispatcher matching
-        let func_name = if metadata.name.is_empty() || metadata.name.starts_with("sub_") {
-            format!("func_0x{:08X}", metadata.address)
-        } else {
-            format!(
-                "{}_{:08X}",
-                self.sanitize_identifier(&metadata.name),
-                metadata.address
-            )
-        };
+        let func_name = self.function_identifier(&metadata.name, metadata.address);
 
         sig.push_str("pub fn ");
         sig.push_str(&func_name);
@@ -97,8 +246,9 @@ impl CodeGenerator {
     /// `let mut __blk = 0; loop { match __blk { 0 => {...}, ... } }`.
     /// Branches set `__blk` to the target block (intra-function) so loops and
     /// conditionals actually execute, instead of returning at the first branch.
-    /// An iteration guard bounds runaway loops so a recompiled spin-wait can't hang
-    /// the host. We intentionally skip dead-code elimination here — it would drop
+    /// Jumps back to an earlier block count against the loop budget
+    /// (`runtime::watchdog`), so a runaway loop fails with a timeout instead of
+    /// hanging the host. We intentionally skip dead-code elimination here — it would drop
     /// instructions and break the address→block mapping.
     fn generate_function_body(&mut self, instructions: &[DecodedInstruction]) -> Result<String> {
         if instructions.is_empty() {
@@ -106,45 +256,41 @@ impl CodeGenerator {
         }
 
         let func_start = instructions[0].address;
-        let func_end = instructions.last().unwrap().address.wrapping_add(4);
-
-        // 1. Leaders: function entry, branch targets (intra), and post-branch addresses.
-        let mut leaders: BTreeSet<u32> = BTreeSet::new();
-        leaders.insert(func_start);
-        for inst in instructions {
-            if !matches!(inst.instruction.instruction_type, InstructionType::Branch) {
-                continue;
-            }
-            let after = inst.address.wrapping_add(4);
-            if (func_start..func_end).contains(&after) {
-                leaders.insert(after);
-            }
-            if let Some(t) = Self::branch_target(inst) {
-                if (func_start..func_end).contains(&t) {
-                    leaders.insert(t);
-                }
+        self.tail_sites = if self.tail_calls {
+            enrich::tail_call_sites(instructions).into_iter().collect()
+        } else {
+            HashSet::new()
+        };
+        self.structs = StructDetector::detect(&format!("struct_{func_start:08X}"), instructions);
+        if let Some(bases) = self.sda_bases {
+            for (reg, base) in [(13, bases.sda), (2, bases.sda2)] {
+                let value = if instructions.iter().any(|i| sda::writes_gpr(i.raw, reg)) {
+                    RegisterValue::Unknown
+                } else {
+                    RegisterValue::Constant(base)
+                };
+                self.set_register_value(reg, value);
             }
         }
-        let leader_vec: Vec<u32> = leaders.iter().copied().collect();
-        let block_of: HashMap<u32, usize> = leader_vec
+
+        // 1. Basic blocks, split at the entry, branch targets and after branches.
+        let cfg = ControlFlowAnalyzer::build_cfg(instructions, func_start)
+            .map_err(|e| RecompileError::AnalysisError(e.to_string()))?;
+        let block_of: HashMap<u32, usize> = cfg
+            .nodes
             .iter()
             .enumerate()
-            .map(|(i, &a)| (a, i))
+            .map(|(i, b)| (b.start_address, i))
             .collect();
-        let n = leader_vec.len();
-
-        // 2. Partition instructions into blocks (largest leader <= address).
-        let mut blocks: Vec<Vec<&DecodedInstruction>> = vec![Vec::new(); n];
-        for inst in instructions {
-            let bi = leader_vec
-                .partition_point(|&l| l <= inst.address)
-                .saturating_sub(1);
-            blocks[bi].push(inst);
-        }
+        let n = cfg.nodes.len();
 
-        // 3. Emit the state machine.
+        // 2. Emit the blocks: as nested scopes when the graph is reducible,
+        // else as a state machine over `__blk`.
         let ind = self.indent();
         let mut code = String::new();
+        if self.struct_accessors && !self.structs.structs.is_empty() {
+            code.push_str(&StructureGenerator::generate_module(&self.structs.structs));
+        }
         // Optional call trace (env GCRECOMP_TRACE) to see the boot's actual path.
         code.push_str(&format!(
             "{ind}gcrecomp_core::runtime::trace_call(0x{:08X}u32);\n",
@@ -155,41 +301,34 @@ impl CodeGenerator {
         code.push_str(&format!(
             "{ind}if gcrecomp_core::runtime::out_of_budget() {{ return Ok(Some(ctx.get_register(3))); }}\n"
         ));
+        if cfg.edges.iter().any(|e| e.to <= e.from) {
+            code.push_str(&format!("{ind}let mut __steps: u64 = 0;\n"));
+        }
+
+        let edges: Vec<(usize, usize)> = cfg
+            .edges
+            .iter()
+            .map(|e| (e.from as usize, e.to as usize))
+            .collect();
+        if let Some(layout) = Layout::new(&edges).filter(|_| self.structured) {
+            // A jump the layout has no scope for (a `bcl` the CFG reads as a
+            // call) sends the whole function back to the state machine.
+            let saved = self.clone();
+            if let Some(body) = self.emit_structured(&cfg.nodes, &block_of, &layout)? {
+                code.push_str(&body);
+                return Ok(code);
+            }
+            *self = saved;
+        }
+
         code.push_str(&format!("{ind}let mut __blk: u32 = 0;\n"));
-        code.push_str(&format!("{ind}let mut __steps: u64 = 0;\n"));
         code.push_str(&format!("{ind}loop {{\n"));
-        // Loop guard: per-function hard cap, plus a cheap watchdog poll every 64K
-        // iterations so a spinning loop bails promptly once the deadline passes.
-        code.push_str(&format!(
-            "{ind}__steps += 1; if __steps > 8_000_000 || (__steps & 0xFFFF == 0 && gcrecomp_core::runtime::out_of_budget()) {{ return Ok(Some(ctx.get_register(3))); }}\n"
-        ));
         code.push_str(&format!("{ind}match __blk {{\n"));
-
-        for (bi, block) in blocks.iter().enumerate() {
+        for bi in 0..n {
             code.push_str(&format!("{ind}{bi}u32 => {{\n"));
-            let last = block.len().saturating_sub(1);
-            let mut terminated = false;
-            for (i, inst) in block.iter().enumerate() {
-                let is_branch =
-                    matches!(inst.instruction.instruction_type, InstructionType::Branch);
-                if i == last && is_branch {
-                    code.push_str(&self.emit_terminator(inst, bi, n, &block_of));
-                    terminated = true;
-                } else {
-                    match self.generate_instruction(inst) {
-                        Ok(c) => code.push_str(&c),
-                        Err(_) => {
-                            code.push_str(&format!("{ind}// untranslated 0x{:08X}\n", inst.raw))
-                        }
-                    }
-                }
-            }
-            if !terminated {
-                if bi + 1 < n {
-                    code.push_str(&format!("{ind}__blk = {}u32;\n", bi + 1));
-                } else {
-                    code.push_str(&format!("{ind}return Ok(Some(ctx.get_register(3)));\n"));
-                }
+            match self.emit_block(&cfg.nodes, bi, &block_of, None)? {
+                Some((block, _)) => code.push_str(&block),
+                None => unreachable!("state-machine jumps always resolve"),
             }
             code.push_str(&format!("{ind}}}\n"));
         }
@@ -201,43 +340,134 @@ impl CodeGenerator {
         Ok(code)
     }
 
-    /// Static intra-function branch target (relative `b`/`bc` only). `None` for
-    /// absolute branches and register branches (blr/bctr).
-    fn branch_target(inst: &DecodedInstruction) -> Option<u32> {
-        let raw = inst.raw;
-        if (raw >> 1) & 1 != 0 {
-            return None; // absolute (AA=1)
+    /// The blocks in address order inside the labeled blocks and loops of
+    /// `layout`, or `None` if a jump leaves no open scope.
+    fn emit_structured(
+        &mut self,
+        blocks: &[BasicBlock],
+        block_of: &HashMap<u32, usize>,
+        layout: &Layout,
+    ) -> Result<Option<String>> {
+        let ind = self.indent();
+        let mut code = String::new();
+        let mut reachable = true;
+        for bi in 0..blocks.len() {
+            for scope in layout.opening(bi) {
+                let label = scope.label();
+                match scope.kind {
+                    ScopeKind::Block => code.push_str(&format!("{ind}{label}: {{\n")),
+                    ScopeKind::Loop => code.push_str(&format!("{ind}{label}: loop {{\n")),
+                }
+            }
+            match self.emit_block(blocks, bi, block_of, Some(layout))? {
+                Some((block, falls)) => {
+                    code.push_str(&block);
+                    reachable = falls;
+                }
+                None => return Ok(None),
+            }
+            // Only code control can reach is emitted, so the generated crate
+            // stays free of `unreachable_code` warnings. A labeled block is
+            // always left by some `break`.
+            for scope in layout.closing(bi) {
+                if scope.kind == ScopeKind::Loop && reachable {
+                    code.push_str(&format!("{ind}break {};\n", scope.label()));
+                }
+                code.push_str(&format!("{ind}}}\n"));
+                reachable |= scope.kind == ScopeKind::Block;
+            }
         }
-        match raw >> 26 {
-            18 => {
-                let disp = ((raw & 0x03FF_FFFC) as i32) << 6 >> 6; // sign-extend 26-bit
-                Some(inst.address.wrapping_add(disp as u32))
+        if reachable {
+            code.push_str(&format!("{ind}Ok(Some(ctx.get_register(3)))\n"));
+        }
+        Ok(Some(code))
+    }
+
+    /// One block's statements, ending in its terminator, and whether control
+    /// can fall out of them. With a `layout` the block falls through to the
+    /// next one; without, it sets `__blk`.
+    fn emit_block(
+        &mut self,
+        blocks: &[BasicBlock],
+        bi: usize,
+        block_of: &HashMap<u32, usize>,
+        layout: Option<&Layout>,
+    ) -> Result<Option<(String, bool)>> {
+        let ind = self.indent();
+        let n = blocks.len();
+        let block = &blocks[bi].instructions;
+        let mut code = String::new();
+        // Debugger hook (see runtime::debug): breakpoints trap at block leaders.
+        code.push_str(&format!(
+            "{ind}gcrecomp_core::runtime::debug::check_breakpoint(0x{:08X}u32, ctx, memory);\n",
+            blocks[bi].start_address
+        ));
+        // Instruction clock (see runtime::clock): retire the block up front.
+        code.push_str(&format!(
+            "{ind}gcrecomp_core::runtime::clock::retire({}u32);\n",
+            retired_per_pass(block)
+        ));
+        let last = block.len().saturating_sub(1);
+        let mut terminated = false;
+        let mut falls = true;
+        for (i, inst) in block.iter().enumerate() {
+            let is_branch = matches!(inst.instruction.instruction_type, InstructionType::Branch);
+            if i == last && is_branch {
+                match self.emit_terminator(inst, bi, n, block_of, layout) {
+                    Some((c, f)) => {
+                        code.push_str(&c);
+                        falls = f;
+                    }
+                    None => return Ok(None),
+                }
+                terminated = true;
+            } else {
+                match self.generate_instruction(inst) {
+                    Ok(c) => code.push_str(&c),
+                    Err(e) if self.strict => return Err(e),
+                    Err(_) => code.push_str(&format!("{ind}// untranslated 0x{:08X}\n", inst.raw)),
+                }
             }
-            16 => {
-                let disp = ((raw & 0x0000_FFFC) as i32) << 16 >> 16; // sign-extend 16-bit
-                Some(inst.address.wrapping_add(disp as u32))
+        }
+        if !terminated && layout.is_none() {
+            if bi + 1 < n {
+                code.push_str(&format!("{ind}__blk = {}u32;\n", bi + 1));
+            } else {
+                code.push_str(&format!("{ind}return Ok(Some(ctx.get_register(3)));\n"));
             }
-            _ => None,
         }
+        Ok(Some((code, falls)))
     }
 
-    /// Emit the block terminator: set `__blk` to the next/target block, call+continue
+    /// Emit the block terminator: go to the next/target block, call+continue
     /// (bl), or return. `cur` is the current block index, `n` the block count.
+    /// Without a `layout` going to a block sets `__blk`; with one it falls
+    /// through, breaks or continues, and `None` means no scope was open. The
+    /// flag says whether control can reach the end of the emitted code.
     fn emit_terminator(
         &mut self,
         inst: &DecodedInstruction,
         cur: usize,
         n: usize,
         block_of: &HashMap<u32, usize>,
-    ) -> String {
+        layout: Option<&Layout>,
+    ) -> Option<(String, bool)> {
         let ind = self.indent();
         let raw = inst.raw;
         let primary = raw >> 26;
-        let next = if cur + 1 < n {
+        let next = if layout.is_some() {
+            String::new()
+        } else if cur + 1 < n {
             format!("__blk = {}u32;", cur + 1)
         } else {
             "return Ok(Some(ctx.get_register(3)));".to_string()
         };
+        // `next` after a statement, if there is anything to do.
+        let then_next = if next.is_empty() {
+            String::new()
+        } else {
+            format!(" {next}")
+        };
         let ret = "return Ok(Some(ctx.get_register(3)));".to_string();
         let call = |tgt: u32| {
             format!(
@@ -246,8 +476,34 @@ impl CodeGenerator {
                 tgt
             )
         };
+        // Tail position: hand the target to the dispatcher's trampoline.
+        let tail = |tgt: u32| format!("gcrecomp_core::runtime::tail_call(0x{tgt:08X}u32);");
+        let is_tail = self.tail_sites.contains(&inst.address);
+        // Intra-function jump; a back-edge also counts against the loop budget
+        // and bails out once the wall-clock watchdog fires.
+        let jump = |tb: usize, tgt: u32| {
+            let back_edge = format!(
+                "if gcrecomp_core::runtime::watchdog::back_edge(&mut __steps, 0x{tgt:08X}u32)? {{ {ret} }}"
+            );
+            let Some(layout) = layout else {
+                return Some(if tb <= cur {
+                    format!("{back_edge} __blk = {tb}u32;")
+                } else {
+                    format!("__blk = {tb}u32;")
+                });
+            };
+            if tb == cur + 1 {
+                return Some(String::new());
+            }
+            let scope = layout.jump(cur, tb)?;
+            Some(match scope.kind {
+                ScopeKind::Loop => format!("{back_edge} continue {};", scope.label()),
+                ScopeKind::Block => format!("break {};", scope.label()),
+            })
+        };
 
-        match primary {
+        let mut falls = true;
+        let code = match primary {
             18 => {
                 let aa = (raw >> 1) & 1;
                 let lk = raw & 1;
@@ -259,11 +515,40 @@ impl CodeGenerator {
                 };
                 if lk != 0 {
                     self.function_calls.push(target);
-                    format!("{ind}{} {next}\n", call(target))
+                    match self.inline_call(target, inst.address.wrapping_add(4)) {
+                        Some(body) if next.is_empty() => body,
+                        Some(body) => format!("{body}{ind}{next}\n"),
+                        // `bl` straight into `blr`.
+                        None if is_tail => {
+                            falls = false;
+                            format!(
+                            "{ind}ctx.lr = 0x{:08X}u32; {} {ret}\n",
+                                inst.address.wrapping_add(4),
+                                tail(target)
+                            )
+                        }
+                        None => match self.specialized_calls.get(&inst.address) {
+                            Some(copy) => format!(
+                                "{ind}ctx.lr = 0x{:08X}u32; if let Ok(Some(rv)) = {copy}(ctx, memory) {{ ctx.set_register(3, rv); }}{then_next}\n",
+                                inst.address.wrapping_add(4)
+                            ),
+                            None => format!("{ind}{}{then_next}\n", call(target)),
+                        },
+                    }
                 } else if let Some(&tb) = block_of.get(&target) {
-                    format!("{ind}__blk = {tb}u32;\n") // intra-function jump
+                    let goto = jump(tb, target)?;
+                    if goto.is_empty() {
+                        String::new()
+                    } else {
+                        falls = false;
+                        format!("{ind}{goto}\n")
+                    }
+                } else if is_tail {
+                    // Branch out of the function: a tail call.
+                    falls = false;
+                    format!("{ind}{} {ret}\n", tail(target))
                 } else {
-                    // Tail call out of the function, then return.
+                    falls = false;
                     format!("{ind}{} {ret}\n", call(target))
                 }
             }
@@ -299,13 +584,14 @@ impl CodeGenerator {
                 };
                 let taken = if aa == 0 {
                     match block_of.get(&target) {
-                        Some(&tb) => format!("__blk = {tb}u32;"),
+                        Some(&tb) => jump(tb, target)?,
                         None => ret.clone(),
                     }
                 } else {
                     ret.clone()
                 };
-                format!("{pre}{ind}if ({ctr_ok}) && ({cr_ok}) {{ {taken} }} else {{ {next} }}\n")
+                let cond = format!("({ctr_ok}) && ({cr_ok})");
+                format!("{pre}{}", Self::branch(&ind, &cond, &taken, &next))
             }
             19 => {
                 // Register-indirect branches: bclr (xo=16 — return via LR) and bctr
@@ -330,7 +616,7 @@ impl CodeGenerator {
                     // (jump tables) fall through to the dispatcher's unknown-addr path.
                     if lk != 0 {
                         format!(
-                            "ctx.lr = 0x{:08X}u32; if let Ok(Some(rv)) = call_function_by_address(ctx.ctr, ctx, memory) {{ ctx.set_register(3, rv); }} {next}",
+                            "ctx.lr = 0x{:08X}u32; if let Ok(Some(rv)) = call_function_by_address(ctx.ctr, ctx, memory) {{ ctx.set_register(3, rv); }}{then_next}",
                             inst.address.wrapping_add(4)
                         )
                     } else {
@@ -341,7 +627,7 @@ impl CodeGenerator {
                     // Common after `mtlr`. Treating this as a plain return (the old
                     // behavior) silently skipped these calls during boot.
                     format!(
-                        "{{ let __t = ctx.lr; ctx.lr = 0x{:08X}u32; if let Ok(Some(rv)) = call_function_by_address(__t, ctx, memory) {{ ctx.set_register(3, rv); }} }} {next}",
+                        "{{ let __t = ctx.lr; ctx.lr = 0x{:08X}u32; if let Ok(Some(rv)) = call_function_by_address(__t, ctx, memory) {{ ctx.set_register(3, rv); }} }}{then_next}",
                         inst.address.wrapping_add(4)
                     )
                 } else {
@@ -349,39 +635,49 @@ impl CodeGenerator {
                     ret.clone()
                 };
                 if bo & 0x10 != 0 {
+                    // Everything but a call returns.
+                    falls = lk != 0;
                     format!("{ind}{action}\n")
                 } else {
-                    format!("{ind}if {cond} {{ {action} }} else {{ {next} }}\n")
+                    Self::branch(&ind, &cond, &action, &next)
                 }
             }
-            _ => format!("{ind}{ret}\n"),
-        }
+            _ => {
+                falls = false;
+                format!("{ind}{ret}\n")
+            }
+        };
+        Some((code, falls))
     }
 
-    fn _build_basic_blocks<'a>(
-        &self,
-        instructions: &'a [DecodedInstruction],
-    ) -> Result<Vec<Vec<&'a DecodedInstruction>>> {
-        // Simple basic block construction: split at branches
-        let mut blocks = Vec::new();
-        let mut current_block = Vec::new();
-
-        for inst in instructions {
-            current_block.push(inst);
-
-            // If this is a branch, end the block
-            if matches!(inst.instruction.instruction_type, InstructionType::Branch) {
-                blocks.push(current_block);
-                current_block = Vec::new();
-            }
+    /// `if cond { taken } else { not_taken }`, leaving out empty arms.
+    fn branch(ind: &str, cond: &str, taken: &str, not_taken: &str) -> String {
+        match (taken.is_empty(), not_taken.is_empty()) {
+            (true, true) => String::new(),
+            (false, true) => format!("{ind}if {cond} {{ {taken} }}\n"),
+            (true, false) => format!("{ind}if !({cond}) {{ {not_taken} }}\n"),
+            (false, false) => format!("{ind}if {cond} {{ {taken} }} else {{ {not_taken} }}\n"),
         }
+    }
 
-        // Add remaining instructions as final block
-        if !current_block.is_empty() {
-            blocks.push(current_block);
+    /// The callee's body in place of `bl target`, or `None` to emit the call.
+    /// LR is still set, as the body may read it with `mflr`.
+    fn inline_call(&mut self, target: u32, return_addr: u32) -> Option<String> {
+        let body = self.inline_candidates.get(&target)?.clone();
+        let ind = self.indent();
+        let saved = self.register_values.clone();
+        let mut code =
+            format!("{ind}// inlined 0x{target:08X}\n{ind}ctx.lr = 0x{return_addr:08X}u32;\n");
+        for inst in &body {
+            match self.generate_instruction(inst) {
+                Ok(c) => code.push_str(&c),
+                Err(_) => {
+                    self.register_values = saved;
+                    return None;
+                }
+            }
         }
-
-        Ok(blocks)
+        Some(code)
     }
 
     fn generate_instruction(&mut self, inst: &DecodedInstruction) -> Result<String> {
@@ -425,6 +721,12 @@ impl CodeGenerator {
             InstructionType::Rotate => {
                 code.push_str(&self.generate_rotate(inst)?);
             }
+            InstructionType::Unknown if self.strict => {
+                return Err(RecompileError::UnsupportedInstruction {
+                    address: inst.address,
+                    opcode: inst.raw,
+                });
+            }
             _ => {
                 // Try to generate a generic instruction handler
                 code.push_str(&self.generate_generic(inst)?);
@@ -462,45 +764,62 @@ impl CodeGenerator {
         }
 
         if inst.instruction.operands.len() < 2 {
-            anyhow::bail!("Arithmetic instruction requires at least 2 operands");
+            return Err(codegen_error(
+                inst,
+                "Arithmetic instruction requires at least 2 operands",
+            ));
         }
 
         let rt_reg = match &inst.instruction.operands[0] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("First operand must be a register"),
+            _ => return Err(codegen_error(inst, "First operand must be a register")),
         };
 
         let ra_reg = match &inst.instruction.operands[1] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("Second operand must be a register"),
+            _ => return Err(codegen_error(inst, "Second operand must be a register")),
+        };
+
+        // D-form logical immediates (ori .. andis.) are decoded as `RS, RA, UI`:
+        // RA is the destination and RS the source.
+        let (rt_reg, ra_reg) = if (24..=29).contains(&inst.instruction.opcode) {
+            (ra_reg, rt_reg)
+        } else {
+            (rt_reg, ra_reg)
         };
 
         // Determine operation based on opcode and extended opcode.
         // Primary opcodes 12-15 are all add-immediate forms (addic/addic./addi/addis);
         // the immediate carries the operand, so they're all `+`. `.` forms set CR0.
         let (op, update_cr) = match inst.instruction.opcode {
-            7 => ("*", false),   // mulli
-            8 => ("rsb", false), // subfic: rt = simm - ra (reverse subtract)
-            12 => ("+", false),  // addic
-            13 => ("+", true),   // addic.
-            14 => ("+", false),  // addi
-            15 => ("+", false),  // addis
+            7 => ("*", false),       // mulli
+            8 => ("rsb", false),     // subfic: rt = simm - ra (reverse subtract)
+            12 => ("+", false),      // addic
+            13 => ("+", true),       // addic.
+            14 => ("+", false),      // addi
+            15 => ("+", false),      // addis
+            24 | 25 => ("|", false), // ori / oris
+            26 | 27 => ("^", false), // xori / xoris
+            28 | 29 => ("&", true),  // andi. / andis.
             31 => {
                 // Extended opcode - decode from instruction
                 let ext_opcode = (inst.raw >> 1) & 0x3FF;
                 match ext_opcode {
-                    266 | 10 => ("+", false),  // add / addc
-                    40 => ("rsb", false),      // subf: rt = rb - ra
-                    28 => ("&", false),        // and
-                    444 => ("|", false),       // or
-                    316 => ("^", false),       // xor
-                    235 | 75 => ("*", false),  // mullw / mulhw
-                    233 => ("*", false),       // mulhw (dup)
-                    459 | 491 => ("/", false), // divwu / divw
-                    104 => ("/", false),       // divw (legacy table)
-                    536 => (">>", false),      // srw
-                    24 => ("<<", false),       // slw
-                    792 => (">>", false),      // sraw
+                    // XO-form divides, with or without OE (divwo = 1003);
+                    // `.` forms set CR0.
+                    491 | 1003 => ("divw", inst.raw & 1 != 0),
+                    459 | 971 => ("divwu", inst.raw & 1 != 0),
+                    266 | 10 => ("+", false), // add / addc
+                    40 => ("rsb", false),     // subf: rt = rb - ra
+                    28 => ("&", false),       // and
+                    444 => ("|", false),      // or
+                    316 => ("^", false),      // xor
+                    235 | 75 => ("*", false), // mullw / mulhw
+                    233 => ("*", false),      // mulhw (dup)
+                    104 => ("/", false),      // divw (legacy table)
+                    536 => (">>", false),     // srw
+                    24 => ("<<", false),      // slw
+                    792 => (">>", false),     // sraw
                     _ => ("+", false),
                 }
             }
@@ -515,7 +834,7 @@ impl CodeGenerator {
                     (format!("ctx.get_register({})", r), reg_val)
                 }
                 Operand::Immediate(i) => {
-                    let val = *i as u32;
+                    let val = extend_immediate(inst.instruction.opcode, *i);
                     (format!("{}u32", val), Some(RegisterValue::Constant(val)))
                 }
                 Operand::Immediate32(i) => {
@@ -532,7 +851,15 @@ impl CodeGenerator {
         // arithmetic) and checked_div for division so we never emit code that
         // panics at runtime (rustc's unconditional_panic lint is a hard error).
         let ra_get = format!("ctx.get_register({})", ra_reg);
+        // divwo / divwuo record overflow in XER.
+        let oe = inst.raw & 0x400 != 0;
         let operation_code = match op {
+            "divw" | "divwu" if oe => format!(
+                "{{ let (q, ov) = gcrecomp_core::runtime::context::{op}({ra_get}, {rb_expr}); ctx.set_overflow(ov); q }}"
+            ),
+            "divw" | "divwu" => {
+                format!("gcrecomp_core::runtime::context::{op}({ra_get}, {rb_expr}).0")
+            }
             "<<" => format!("{}.wrapping_shl({})", ra_get, rb_expr),
             ">>" => format!("{}.wrapping_shr({})", ra_get, rb_expr),
             "/" => format!("{}.checked_div({}).unwrap_or(0)", ra_get, rb_expr),
@@ -544,9 +871,10 @@ impl CodeGenerator {
         };
 
         // Optimize: if both operands are constants, compute at compile time
+        // (not for divwo/divwuo, whose XER update has to happen at runtime).
         let ra_value = self.get_register_value(ra_reg);
-        if let (Some(RegisterValue::Constant(a)), Some(RegisterValue::Constant(b))) =
-            (ra_value, rb_value)
+        if let (Some(RegisterValue::Constant(a)), Some(RegisterValue::Constant(b)), false) =
+            (ra_value, rb_value, oe && op.starts_with("div"))
         {
             let result = match op {
                 "+" => a.wrapping_add(b),
@@ -554,6 +882,8 @@ impl CodeGenerator {
                 "rsb" => b.wrapping_sub(a),
                 "*" => a.wrapping_mul(b),
                 "/" => a.checked_div(b).unwrap_or(0),
+                "divw" => context::divw(a, b).0,
+                "divwu" => context::divwu(a, b).0,
                 "&" => a & b,
                 "|" => a | b,
                 "^" => a ^ b,
@@ -562,10 +892,22 @@ impl CodeGenerator {
                 _ => a,
             };
             code.push_str(&self.indent());
-            code.push_str(&format!(
-                "ctx.set_register({}, {}u32); // Optimized: constant folding\n",
-                rt_reg, result
-            ));
+            let sda_offset = (inst.instruction.opcode == 14)
+                .then(|| self.sda_bases.and_then(|b| b.base_of(ra_reg)))
+                .flatten()
+                .map(|base| result.wrapping_sub(base) as i32);
+            match sda_offset {
+                Some(offset) => code.push_str(&format!(
+                    "ctx.set_register({}, 0x{:08X}u32); // small-data global: {}\n",
+                    rt_reg,
+                    result,
+                    sda::describe(ra_reg, offset)
+                )),
+                None => code.push_str(&format!(
+                    "ctx.set_register({}, {}u32); // Optimized: constant folding\n",
+                    rt_reg, result
+                )),
+            }
             self.set_register_value(rt_reg, RegisterValue::Constant(result));
         } else {
             code.push_str(&self.indent());
@@ -601,17 +943,17 @@ impl CodeGenerator {
         let mut code = String::new();
 
         if inst.instruction.operands.len() < 3 {
-            anyhow::bail!("Load instruction requires 3 operands");
+            return Err(codegen_error(inst, "Load instruction requires 3 operands"));
         }
 
         let rt_reg = match &inst.instruction.operands[0] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("First operand must be a register"),
+            _ => return Err(codegen_error(inst, "First operand must be a register")),
         };
 
         let ra_reg = match &inst.instruction.operands[1] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("Second operand must be a register"),
+            _ => return Err(codegen_error(inst, "Second operand must be a register")),
         };
 
         let offset = match &inst.instruction.operands[2] {
@@ -628,10 +970,18 @@ impl CodeGenerator {
                 "let value = memory.read_u32(0x{:08X}u32).unwrap_or(0u32); // Optimized: constant address\n",
                 addr
             ));
+        } else if let Some((note, Some((ty, field)))) = self.struct_field(inst.address) {
+            code.push_str(&self.indent());
+            code.push_str(&format!(
+                "let value = {ty}::get_{field}(memory, ctx.get_register({ra_reg})).unwrap_or(0u32);{note}\n"
+            ));
         } else {
+            let note = self
+                .struct_field(inst.address)
+                .map_or(String::new(), |(note, _)| note);
             code.push_str(&self.indent());
             code.push_str(&format!(
-                "let addr = ctx.get_register({}) as u32 + {}i32 as u32;\n",
+                "let addr = ctx.get_register({}) as u32 + {}i32 as u32;{note}\n",
                 ra_reg, offset
             ));
             code.push_str(&self.indent());
@@ -649,17 +999,17 @@ impl CodeGenerator {
         let mut code = String::new();
 
         if inst.instruction.operands.len() < 3 {
-            anyhow::bail!("Store instruction requires 3 operands");
+            return Err(codegen_error(inst, "Store instruction requires 3 operands"));
         }
 
         let rs_reg = match &inst.instruction.operands[0] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("First operand must be a register"),
+            _ => return Err(codegen_error(inst, "First operand must be a register")),
         };
 
         let ra_reg = match &inst.instruction.operands[1] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("Second operand must be a register"),
+            _ => return Err(codegen_error(inst, "Second operand must be a register")),
         };
 
         let offset = match &inst.instruction.operands[2] {
@@ -683,10 +1033,18 @@ impl CodeGenerator {
                 "memory.write_u32(0x{:08X}u32, {}).unwrap_or(()); // Optimized: constant address\n",
                 addr, value_expr
             ));
+        } else if let Some((note, Some((ty, field)))) = self.struct_field(inst.address) {
+            code.push_str(&self.indent());
+            code.push_str(&format!(
+                "{ty}::set_{field}(memory, ctx.get_register({ra_reg}), {value_expr}).unwrap_or(());{note}\n"
+            ));
         } else {
+            let note = self
+                .struct_field(inst.address)
+                .map_or(String::new(), |(note, _)| note);
             code.push_str(&self.indent());
             code.push_str(&format!(
-                "let addr = ctx.get_register({}) as u32 + {}i32 as u32;\n",
+                "let addr = ctx.get_register({}) as u32 + {}i32 as u32;{note}\n",
                 ra_reg, offset
             ));
             code.push_str(&self.indent());
@@ -777,7 +1135,10 @@ impl CodeGenerator {
         let mut code = String::new();
 
         if inst.instruction.operands.len() < 2 {
-            anyhow::bail!("Compare instruction requires at least 2 operands");
+            return Err(codegen_error(
+                inst,
+                "Compare instruction requires at least 2 operands",
+            ));
         }
 
         let bf = match &inst.instruction.operands[0] {
@@ -787,7 +1148,15 @@ impl CodeGenerator {
 
         let ra_reg = match &inst.instruction.operands[1] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("Second operand must be a register"),
+            _ => return Err(codegen_error(inst, "Second operand must be a register")),
+        };
+
+        // Determine if unsigned comparison (cmplwi, cmplw). The signedness comes
+        // from the opcode: both operands are plain 32-bit register images.
+        let is_unsigned = match inst.instruction.opcode {
+            10 => true,                          // cmplwi
+            31 => (inst.raw >> 1) & 0x3FF == 32, // cmplw
+            _ => false,
         };
 
         // Handle different compare types (cmpwi, cmplwi, cmpw, cmplw)
@@ -797,8 +1166,12 @@ impl CodeGenerator {
                     format!("ctx.get_register({})", rb)
                 }
                 Operand::Immediate(i) => {
-                    let val = *i as i32;
-                    format!("{}i32", val)
+                    let val = extend_immediate(inst.instruction.opcode, *i);
+                    if is_unsigned {
+                        format!("{}u32", val)
+                    } else {
+                        format!("{}i32", val as i32)
+                    }
                 }
                 _ => "0i32".to_string(),
             }
@@ -806,9 +1179,6 @@ impl CodeGenerator {
             "0i32".to_string()
         };
 
-        // Determine if unsigned comparison (cmplwi, cmplw)
-        let is_unsigned = inst.instruction.opcode == 10; // cmplwi
-
         code.push_str(&self.indent());
         code.push_str(&format!(
             "let ra_val = ctx.get_register({}) as {};\n",
@@ -853,14 +1223,17 @@ impl CodeGenerator {
         let mut code = String::new();
 
         if inst.instruction.operands.is_empty() {
-            anyhow::bail!("Move instruction requires at least one operand");
+            return Err(codegen_error(
+                inst,
+                "Move instruction requires at least one operand",
+            ));
         }
 
         // Handle move from/to link register (mflr/mtlr)
         if inst.instruction.operands.len() == 1 {
             let reg = match &inst.instruction.operands[0] {
                 Operand::Register(r) => *r,
-                _ => anyhow::bail!("Move operand must be a register"),
+                _ => return Err(codegen_error(inst, "Move operand must be a register")),
             };
 
             // Check if this is mflr (move from link register) or mtlr (move to link register)
@@ -959,7 +1332,7 @@ impl CodeGenerator {
             // Move from/to condition register
             let reg = match &inst.instruction.operands[0] {
                 Operand::Register(r) => *r,
-                _ => anyhow::bail!("Operand must be register"),
+                _ => return Err(codegen_error(inst, "Operand must be register")),
             };
             code.push_str(&self.indent());
             code.push_str(&format!(
@@ -970,15 +1343,15 @@ impl CodeGenerator {
             // CR logical operations (crand, cror, etc.)
             let bt = match &inst.instruction.operands[0] {
                 Operand::Condition(c) => *c,
-                _ => anyhow::bail!("First operand must be condition"),
+                _ => return Err(codegen_error(inst, "First operand must be condition")),
             };
             let ba = match &inst.instruction.operands[1] {
                 Operand::Condition(c) => *c,
-                _ => anyhow::bail!("Second operand must be condition"),
+                _ => return Err(codegen_error(inst, "Second operand must be condition")),
             };
             let bb = match &inst.instruction.operands[2] {
                 Operand::Condition(c) => *c,
-                _ => anyhow::bail!("Third operand must be condition"),
+                _ => return Err(codegen_error(inst, "Third operand must be condition")),
             };
 
             code.push_str(&self.indent());
@@ -1041,16 +1414,19 @@ impl CodeGenerator {
         let mut code = String::new();
 
         if inst.instruction.operands.len() < 3 {
-            anyhow::bail!("Shift instruction requires at least 3 operands");
+            return Err(codegen_error(
+                inst,
+                "Shift instruction requires at least 3 operands",
+            ));
         }
 
         let rs = match &inst.instruction.operands[0] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("First operand must be register"),
+            _ => return Err(codegen_error(inst, "First operand must be register")),
         };
         let ra = match &inst.instruction.operands[1] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("Second operand must be register"),
+            _ => return Err(codegen_error(inst, "Second operand must be register")),
         };
         // Shift amount: either an immediate (masked to 5 bits) or a register value.
         // ponytail: always emit `<< (amount & 0x1F)` — masking avoids shift-overflow
@@ -1058,7 +1434,12 @@ impl CodeGenerator {
         let sh_expr = match &inst.instruction.operands[2] {
             Operand::ShiftAmount(s) => format!("{}u32", (*s as u32) & 0x1F),
             Operand::Register(r) => format!("(ctx.get_register({}) & 0x1F)", r),
-            _ => anyhow::bail!("Third operand must be shift amount or register"),
+            _ => {
+                return Err(codegen_error(
+                    inst,
+                    "Third operand must be shift amount or register",
+                ))
+            }
         };
 
         code.push_str(&self.indent());
@@ -1074,24 +1455,27 @@ impl CodeGenerator {
         let mut code = String::new();
 
         if inst.instruction.operands.len() < 4 {
-            anyhow::bail!("Rotate instruction requires 4 operands");
+            return Err(codegen_error(
+                inst,
+                "Rotate instruction requires 4 operands",
+            ));
         }
 
         let rs = match &inst.instruction.operands[0] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("First operand must be register"),
+            _ => return Err(codegen_error(inst, "First operand must be register")),
         };
         let ra = match &inst.instruction.operands[1] {
             Operand::Register(r) => *r,
-            _ => anyhow::bail!("Second operand must be register"),
+            _ => return Err(codegen_error(inst, "Second operand must be register")),
         };
         let sh = match &inst.instruction.operands[2] {
             Operand::ShiftAmount(s) => *s,
-            _ => anyhow::bail!("Third operand must be shift amount"),
+            _ => return Err(codegen_error(inst, "Third operand must be shift amount")),
         };
         let mask = match &inst.instruction.operands[3] {
             Operand::Mask(m) => *m,
-            _ => anyhow::bail!("Fourth operand must be mask"),
+            _ => return Err(codegen_error(inst, "Fourth operand must be mask")),
         };
 
         code.push_str(&self.indent());
@@ -1147,6 +1531,30 @@ impl CodeGenerator {
             }
         }
 
+        // icbi/dcbst (opcode 31, ext 982/54): let the interpreter's block cache
+        // know the line may hold new code. Writes already do; a loader that
+        // patched code through a path we don't see still flushes it.
+        if inst.instruction.opcode == 31 {
+            let ext = (inst.raw >> 1) & 0x3FF;
+            if ext == 982 || ext == 54 {
+                let ra = (inst.raw >> 16) & 0x1F;
+                let rb = (inst.raw >> 11) & 0x1F;
+                let ea = if ra == 0 {
+                    format!("ctx.get_register({})", rb)
+                } else {
+                    format!(
+                        "ctx.get_register({}).wrapping_add(ctx.get_register({}))",
+                        ra, rb
+                    )
+                };
+                return Ok(format!(
+                    "{}memory.invalidate_code(({}) & !31, 32);\n",
+                    self.indent(),
+                    ea
+                ));
+            }
+        }
+
         // Handle system instructions
         if !inst.instruction.operands.is_empty() {
             if let Operand::SpecialRegister(spr) = &inst.instruction.operands[0] {
@@ -1209,10 +1617,42 @@ impl CodeGenerator {
     }
 
     pub fn sanitize_identifier(&self, name: &str) -> String {
-        name.replace([' ', '-', '.'], "_")
+        let ident: String = name
+            .replace([' ', '-', '.'], "_")
             .chars()
             .filter(|c| c.is_alphanumeric() || *c == '_')
-            .collect()
+            .collect();
+        // Map symbols like `@1234` would otherwise start with a digit.
+        if ident.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("_{ident}")
+        } else {
+            ident
+        }
+    }
+
+    /// Rust name of the recompiled function at `address`. The address suffix
+    /// keeps names unique; functions, stubs and the dispatcher must all agree.
+    pub fn function_identifier(&self, name: &str, address: u32) -> String {
+        if name.is_empty() || name.starts_with("sub_") {
+            format!("func_0x{:08X}", address)
+        } else {
+            format!("{}_{:08X}", self.sanitize_identifier(name), address)
+        }
+    }
+
+    /// For a load/store of a detected struct field: its ` // Struct.field`
+    /// comment and, when accessors are on and the field is a whole word
+    /// (as wide as the raw access), the struct's path and field identifier.
+    fn struct_field(&self, address: u32) -> Option<(String, Option<(String, String)>)> {
+        let (info, field) = self.structs.field_at(address)?;
+        let note = format!(" // {}.{}", info.name, field.name);
+        let accessor = (self.struct_accessors && field.kind == FieldKind::U32).then(|| {
+            (
+                format!("structs::{}", structs::identifier(&info.name)),
+                structs::identifier(&field.name),
+            )
+        });
+        Some((note, accessor))
     }
 
     fn indent(&self) -> String {
@@ -1225,3 +1665,37 @@ impl Default for CodeGenerator {
         Self::new()
     }
 }
+
+/// A 16-bit D-form immediate as the 32-bit value its opcode operates on.
+/// The decoder stores every immediate as `i16`, so the extension has to come
+/// from the opcode: UI fields (cmplwi, ori/oris, xori/xoris, andi./andis.) are
+/// zero-extended, SI fields (addi, cmpwi, ...) sign-extended, and the shifted
+/// forms (addis, oris, xoris, andis.) move it to the upper half.
+pub fn extend_immediate(opcode: u32, imm: i16) -> u32 {
+    match opcode {
+        10 | 24 | 26 | 28 => imm as u16 as u32,
+        25 | 27 | 29 => (imm as u16 as u32) << 16,
+        15 => (imm as i32 as u32) << 16,
+        _ => imm as i32 as u32,
+    }
+}
+
+/// Guest instructions one pass through `block` stands for. Loop unrolling
+/// repeats a body with its addresses, so an unrolled block counts as
+/// `factor` passes of the original (each distinct address `factor` times)
+/// and the clock reads the same whether or not the loop was unrolled.
+fn retired_per_pass(block: &[DecodedInstruction]) -> usize {
+    let mut copies: HashMap<u32, usize> = HashMap::new();
+    for inst in block {
+        *copies.entry(inst.address).or_default() += 1;
+    }
+    copies.len() * copies.values().copied().max().unwrap_or(0)
+}
+
+#[cold]
+fn codegen_error(inst: &DecodedInstruction, message: &str) -> RecompileError {
+    RecompileError::CodegenError {
+        address: inst.address,
+        message: message.to_string(),
+    }
+}
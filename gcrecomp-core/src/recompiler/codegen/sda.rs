@@ -0,0 +1,66 @@
+// Small-data-area addressing: r13/r2-relative globals as constants.
+//
+// The EABI keeps small globals within a signed 16-bit reach of r13
+// (`_SDA_BASE_`) and small read-only data around r2 (`_SDA2_BASE_`), so
+// `addi rX, r13, off` / `lwz rX, off(r13)` name one fixed global. When the
+// bases the runtime boots with are known, codegen seeds r13/r2 as constants at
+// each function entry and the usual constant folding turns those into
+// absolute addresses. A function that writes r13 or r2 itself (the SDK's
+// `__init_registers`) gets no seed.
+use crate::recompiler::analysis::pointer::DEFAULT_SDA_BASE;
+
+/// r13 and r2 as the runtime sets them before the entry point runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SdaBases {
+    /// `_SDA_BASE_` (r13).
+    pub sda: u32,
+    /// `_SDA2_BASE_` (r2).
+    pub sda2: u32,
+}
+
+impl Default for SdaBases {
+    /// The bases the game binary's boot code sets.
+    fn default() -> Self {
+        Self {
+            sda: DEFAULT_SDA_BASE,
+            sda2: DEFAULT_SDA_BASE,
+        }
+    }
+}
+
+impl SdaBases {
+    /// The base `reg` holds, if it is r13 or r2.
+    pub fn base_of(&self, reg: u8) -> Option<u32> {
+        match reg {
+            13 => Some(self.sda),
+            2 => Some(self.sda2),
+            _ => None,
+        }
+    }
+}
+
+/// Whether the instruction word `raw` may write GPR `reg`. Errs towards yes
+/// for X-form (opcode 31) instructions.
+pub fn writes_gpr(raw: u32, reg: u8) -> bool {
+    let reg = reg as u32;
+    let rd = (raw >> 21) & 0x1F;
+    let ra = (raw >> 16) & 0x1F;
+    match raw >> 26 {
+        // mulli, subfic, addic(.), addi(s), lwz, lbz, lhz, lha
+        7 | 8 | 12..=15 | 32 | 34 | 40 | 42 => rd == reg,
+        // Load with update: rD and rA.
+        33 | 35 | 41 | 43 => rd == reg || ra == reg,
+        // lmw loads rD..r31.
+        46 => rd <= reg,
+        // Rotates, logical immediates, store/FP-load with update: rA.
+        20 | 21 | 23..=29 | 37 | 39 | 45 | 49 | 51 | 53 | 55 => ra == reg,
+        31 => rd == reg || ra == reg,
+        _ => false,
+    }
+}
+
+/// `r13 + 0x20` / `r2 - 0x7F00` for a generated comment.
+pub fn describe(reg: u8, offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!("r{reg} {sign} 0x{:X}", offset.unsigned_abs())
+}
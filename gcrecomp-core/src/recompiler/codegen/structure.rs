@@ -0,0 +1,157 @@
+// Structured control flow: nest a function's blocks in labeled Rust scopes.
+//
+// Blocks are emitted in address order. A forward jump past the next block
+// becomes `break '__b{t}` out of a labeled block that closes just before block
+// `t`; a back edge becomes `continue '__l{h}` in a labeled `loop` that opens at
+// block `h` and ends with `break` after its last source. Falling out of a
+// block falls into the next one, so if/else diamonds and while loops come out
+// as plain nested scopes. Scopes that partially overlap are widened until they
+// nest: a labeled block can start earlier and a loop can end later without
+// changing where their jumps land. A forward jump into the middle of a loop
+// can't be fixed that way (the graph is irreducible), and there is no layout.
+use std::collections::BTreeMap;
+
+/// Deepest nesting emitted; past it (long switch ladders) the state machine
+/// is smaller and easier on rustc.
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// `'__b{t}: { ... }`, left by jumping to block `t`.
+    Block,
+    /// `'__l{h}: loop { ... }`, re-entered by jumping to block `h`.
+    Loop,
+}
+
+/// A scope covering blocks `first..=last`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub first: usize,
+    pub last: usize,
+}
+
+impl Scope {
+    /// The scope's label: its exit block for a labeled block, its head for
+    /// a loop. Widening never moves either.
+    pub fn label(&self) -> String {
+        match self.kind {
+            ScopeKind::Block => format!("'__b{}", self.last + 1),
+            ScopeKind::Loop => format!("'__l{}", self.first),
+        }
+    }
+
+    fn crosses(&self, other: &Scope) -> bool {
+        self.first < other.first && other.first <= self.last && self.last < other.last
+    }
+}
+
+/// Properly nested scopes for one function's blocks.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    scopes: Vec<Scope>,
+}
+
+impl Layout {
+    /// The layout for the `(from, to)` block edges, or `None` when the graph
+    /// is irreducible or nests deeper than [`MAX_DEPTH`].
+    pub fn new(edges: &[(usize, usize)]) -> Option<Self> {
+        let mut exits: BTreeMap<usize, usize> = BTreeMap::new();
+        let mut loops: BTreeMap<usize, usize> = BTreeMap::new();
+        for &(from, to) in edges {
+            if to <= from {
+                let last = loops.entry(to).or_insert(from);
+                *last = (*last).max(from);
+            } else if to > from + 1 {
+                let first = exits.entry(to).or_insert(from);
+                *first = (*first).min(from);
+            }
+        }
+        let mut scopes: Vec<Scope> = exits
+            .into_iter()
+            .map(|(to, first)| Scope {
+                kind: ScopeKind::Block,
+                first,
+                last: to - 1,
+            })
+            .chain(loops.into_iter().map(|(head, last)| Scope {
+                kind: ScopeKind::Loop,
+                first: head,
+                last,
+            }))
+            .collect();
+
+        // Widen until no two scopes cross; every step only grows a scope.
+        loop {
+            let mut changed = false;
+            for i in 0..scopes.len() {
+                for j in 0..scopes.len() {
+                    let (a, b) = (scopes[i], scopes[j]);
+                    if !a.crosses(&b) {
+                        continue;
+                    }
+                    match (a.kind, b.kind) {
+                        (_, ScopeKind::Block) => scopes[j].first = a.first,
+                        (ScopeKind::Loop, ScopeKind::Loop) => scopes[i].last = b.last,
+                        (ScopeKind::Block, ScopeKind::Loop) => return None,
+                    }
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        let deepest = scopes
+            .iter()
+            .map(|s| {
+                scopes
+                    .iter()
+                    .filter(|o| o.first <= s.first && s.last <= o.last)
+                    .count()
+            })
+            .max()
+            .unwrap_or(0);
+        if deepest > MAX_DEPTH {
+            return None;
+        }
+        Some(Self { scopes })
+    }
+
+    /// Scopes to open before `block`, outermost first.
+    pub fn opening(&self, block: usize) -> Vec<Scope> {
+        let mut scopes: Vec<Scope> = self
+            .scopes
+            .iter()
+            .copied()
+            .filter(|s| s.first == block)
+            .collect();
+        scopes.sort_by_key(|s| (std::cmp::Reverse(s.last), s.kind == ScopeKind::Loop));
+        scopes
+    }
+
+    /// Scopes to close after `block`, innermost first.
+    pub fn closing(&self, block: usize) -> Vec<Scope> {
+        let mut scopes: Vec<Scope> = self
+            .scopes
+            .iter()
+            .copied()
+            .filter(|s| s.last == block)
+            .collect();
+        scopes.sort_by_key(|s| (std::cmp::Reverse(s.first), s.kind == ScopeKind::Block));
+        scopes
+    }
+
+    /// The scope a jump from block `from` to block `to` leaves or repeats,
+    /// if one is open there. A jump to the next block needs none.
+    pub fn jump(&self, from: usize, to: usize) -> Option<Scope> {
+        self.scopes.iter().copied().find(|s| {
+            s.first <= from
+                && from <= s.last
+                && match s.kind {
+                    ScopeKind::Block => s.last + 1 == to,
+                    ScopeKind::Loop => s.first == to,
+                }
+        })
+    }
+}
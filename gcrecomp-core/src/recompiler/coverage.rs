@@ -0,0 +1,129 @@
+//! Instruction coverage: how complete a recompile is.
+//!
+//! The pipeline records every function it generates, along with how that
+//! function came out (see [`Translation`]). The result is written next to the
+//! generated code as `coverage.json` and summarised in one log line. An
+//! instruction counts as translated when the decoder understood it (anything
+//! but `Unknown`), which matches the per-function numbers in
+//! [`enrich`](crate::recompiler::enrich).
+
+use crate::recompiler::decoder::{DecodedInstruction, InstructionType};
+use crate::recompiler::pipeline::Translation;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Whole-program instruction and function tallies.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstructionCoverage {
+    pub total_instructions: usize,
+    /// Instruction count per `InstructionType`, keyed by its name.
+    pub by_type: BTreeMap<String, usize>,
+    pub unknown_instructions: usize,
+    /// Functions containing at least one `Unknown` instruction.
+    pub functions_with_unknown: usize,
+    /// `Unknown` instructions per opcode: the primary opcode, plus the
+    /// extended opcode for the 4/19/31/59/63 groups (e.g. `"31/459"`).
+    pub unknown_opcodes: BTreeMap<String, usize>,
+    /// Functions fully translated to Rust.
+    pub recompiled_functions: usize,
+    /// Functions translated with some instructions left as comments.
+    pub partial_functions: usize,
+    /// Functions that run under the runtime interpreter.
+    pub interpreted_functions: usize,
+    /// Functions with no generated body (empty, or generation failed).
+    pub stubbed_functions: usize,
+}
+
+impl InstructionCoverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record one function. `translation` is `None` when it was stubbed.
+    pub fn record_function(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        translation: Option<Translation>,
+    ) {
+        let mut unknown = 0;
+        for inst in instructions {
+            let ty = inst.instruction.instruction_type;
+            *self.by_type.entry(format!("{:?}", ty)).or_insert(0) += 1;
+            if ty == InstructionType::Unknown {
+                unknown += 1;
+                *self
+                    .unknown_opcodes
+                    .entry(opcode_key(inst.raw))
+                    .or_insert(0) += 1;
+            }
+        }
+        self.total_instructions += instructions.len();
+        self.unknown_instructions += unknown;
+        if unknown > 0 {
+            self.functions_with_unknown += 1;
+        }
+        match translation {
+            Some(Translation::Recompiled) => self.recompiled_functions += 1,
+            Some(Translation::Partial) => self.partial_functions += 1,
+            Some(Translation::Interpreted) => self.interpreted_functions += 1,
+            None => self.stubbed_functions += 1,
+        }
+    }
+
+    /// Percentage of instructions translated, in `[0.0, 100.0]`.
+    pub fn translated_percent(&self) -> f64 {
+        if self.total_instructions == 0 {
+            return 0.0;
+        }
+        let translated = self.total_instructions - self.unknown_instructions;
+        translated as f64 * 100.0 / self.total_instructions as f64
+    }
+
+    /// The `n` most frequent unknown opcodes, most frequent first.
+    pub fn top_unknown_opcodes(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut top: Vec<(&str, usize)> = self
+            .unknown_opcodes
+            .iter()
+            .map(|(k, &v)| (k.as_str(), v))
+            .collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+
+    /// One-line summary for the log.
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{:.1}% of instructions translated, {} Unknown across {} functions",
+            self.translated_percent(),
+            self.unknown_instructions,
+            self.functions_with_unknown
+        );
+        let top = self.top_unknown_opcodes(3);
+        if !top.is_empty() {
+            let list: Vec<String> = top.iter().map(|(k, v)| format!("{k} ({v})")).collect();
+            line.push_str(&format!(" with top opcodes {}", list.join(", ")));
+        }
+        line.push_str(&format!(
+            "; functions: {} recompiled, {} partial, {} interpreted, {} stubbed",
+            self.recompiled_functions,
+            self.partial_functions,
+            self.interpreted_functions,
+            self.stubbed_functions
+        ));
+        line
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+}
+
+fn opcode_key(raw: u32) -> String {
+    let primary = raw >> 26;
+    match primary {
+        4 | 19 | 31 | 63 => format!("{}/{}", primary, (raw >> 1) & 0x3FF),
+        59 => format!("{}/{}", primary, (raw >> 1) & 0x1F),
+        _ => primary.to_string(),
+    }
+}
@@ -17,7 +17,7 @@
 //!
 //! Most PowerPC instructions have 3-4 operands, making `SmallVec<[Operand; 4]>` optimal.
 
-use anyhow::Result;
+use crate::recompiler::error::Result;
 use smallvec::SmallVec;
 
 /// PowerPC instruction representation with optimized memory layout.
@@ -26,7 +26,7 @@ use smallvec::SmallVec;
 /// - `opcode`: 6 bits (bits 26-31 of instruction word)
 /// - `instruction_type`: 1 byte (enum with `#[repr(u8)]`)
 /// - `operands`: SmallVec with inline capacity for 4 operands (most instructions have ≤4)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[repr(C)] // Ensure C-compatible layout for potential FFI
 pub struct Instruction {
     /// Primary opcode (6 bits, stored as u32 for alignment but only uses 6 bits)
@@ -109,7 +109,7 @@ pub enum Operand {
 /// - `instruction`: Contains opcode, type, and operands
 /// - `raw`: Original 32-bit instruction word
 /// - `address`: Memory address where this instruction is located (for function mapping)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DecodedInstruction {
     /// Decoded instruction structure
     pub instruction: Instruction,
@@ -120,6 +120,16 @@ pub struct DecodedInstruction {
     pub address: u32,
 }
 
+impl std::fmt::Display for DecodedInstruction {
+    /// Assembly text, e.g. `addi r3, r13, 32` (see `recompiler::disasm`).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&crate::recompiler::disasm::disassemble(
+            self.raw,
+            self.address,
+        ))
+    }
+}
+
 impl Instruction {
     /// Decode a 32-bit PowerPC instruction word into a structured representation.
     ///
@@ -2611,12 +2621,18 @@ impl Instruction {
                 InstructionType::System,
                 SmallVec::from_slice(&[Operand::Register(ra), Operand::Register(rb)]),
             )),
-            // Extended opcode 470: Instruction cache block invalidate (icbi)
-            // Format: icbi RA, RB
+            // Extended opcode 470: Data cache block invalidate (dcbi)
+            // Format: dcbi RA, RB
             470 => Ok((
                 InstructionType::System,
                 SmallVec::from_slice(&[Operand::Register(ra), Operand::Register(rb)]),
             )),
+            // Extended opcode 982: Instruction cache block invalidate (icbi)
+            // Format: icbi RA, RB
+            982 => Ok((
+                InstructionType::System,
+                SmallVec::from_slice(&[Operand::Register(ra), Operand::Register(rb)]),
+            )),
 
             // Memory synchronization instructions
             // Extended opcode 598: Synchronize (sync)
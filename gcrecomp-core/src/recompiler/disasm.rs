@@ -0,0 +1,419 @@
+//! PowerPC Disassembler
+//!
+//! Text for [`DecodedInstruction`]'s `Display`: Gekko mnemonics with the
+//! common simplified forms (`li`, `lis`, `mr`, `nop`, `mflr`, `mtctr`, `blr`,
+//! `beq`, `bdnz`, ...). Branch targets print as absolute addresses. Words
+//! outside the covered set print as `.word 0x........`.
+//!
+//! [`DecodedInstruction`]: crate::recompiler::decoder::DecodedInstruction
+
+/// `raw` at `address` as assembly.
+pub fn disassemble(raw: u32, address: u32) -> String {
+    Fields(raw)
+        .format(address)
+        .unwrap_or_else(|| format!(".word 0x{raw:08X}"))
+}
+
+const LOADS_STORES: [&str; 24] = [
+    "lwz", "lwzu", "lbz", "lbzu", "stw", "stwu", "stb", "stbu", "lhz", "lhzu", "lha", "lhau",
+    "sth", "sthu", "lmw", "stmw", "lfs", "lfsu", "lfd", "lfdu", "stfs", "stfsu", "stfd", "stfdu",
+];
+
+struct Fields(u32);
+
+impl Fields {
+    fn rd(&self) -> u32 {
+        (self.0 >> 21) & 0x1F
+    }
+    fn ra(&self) -> u32 {
+        (self.0 >> 16) & 0x1F
+    }
+    fn rb(&self) -> u32 {
+        (self.0 >> 11) & 0x1F
+    }
+    fn rc(&self) -> u32 {
+        (self.0 >> 6) & 0x1F
+    }
+    fn simm(&self) -> i32 {
+        (self.0 & 0xFFFF) as i16 as i32
+    }
+    fn uimm(&self) -> u32 {
+        self.0 & 0xFFFF
+    }
+    /// `.` for record forms.
+    fn dot(&self) -> &'static str {
+        if self.0 & 1 != 0 {
+            "."
+        } else {
+            ""
+        }
+    }
+
+    fn format(&self, address: u32) -> Option<String> {
+        let (rd, ra, rb) = (self.rd(), self.ra(), self.rb());
+        let (simm, uimm) = (self.simm(), self.uimm());
+        let text = match self.0 >> 26 {
+            7 => format!("mulli r{rd}, r{ra}, {simm}"),
+            8 => format!("subfic r{rd}, r{ra}, {simm}"),
+            10 => format!("cmplwi {}r{ra}, 0x{uimm:X}", crf(rd >> 2)),
+            11 => format!("cmpwi {}r{ra}, {simm}", crf(rd >> 2)),
+            12 => format!("addic r{rd}, r{ra}, {simm}"),
+            13 => format!("addic. r{rd}, r{ra}, {simm}"),
+            14 if ra == 0 => format!("li r{rd}, {simm}"),
+            14 => format!("addi r{rd}, r{ra}, {simm}"),
+            15 if ra == 0 => format!("lis r{rd}, 0x{uimm:X}"),
+            15 => format!("addis r{rd}, r{ra}, 0x{uimm:X}"),
+            16 => {
+                let target = if self.0 & 2 != 0 {
+                    (simm & !3) as u32
+                } else {
+                    address.wrapping_add((simm & !3) as u32)
+                };
+                let suffix = branch_suffix(self.0);
+                match condition(rd, ra) {
+                    Some((cond, field)) => format!("b{cond}{suffix} {field}0x{target:08X}"),
+                    None => format!("bc{suffix} {rd}, {ra}, 0x{target:08X}"),
+                }
+            }
+            17 => "sc".to_string(),
+            18 => {
+                let disp = ((self.0 & 0x03FF_FFFC) << 6) as i32 >> 6;
+                let target = if self.0 & 2 != 0 {
+                    disp as u32
+                } else {
+                    address.wrapping_add(disp as u32)
+                };
+                format!("b{} 0x{target:08X}", branch_suffix(self.0))
+            }
+            19 => self.format_19()?,
+            20 => format!(
+                "rlwimi{} r{ra}, r{rd}, {rb}, {}, {}",
+                self.dot(),
+                self.rc(),
+                (self.0 >> 1) & 0x1F
+            ),
+            21 => format!(
+                "rlwinm{} r{ra}, r{rd}, {rb}, {}, {}",
+                self.dot(),
+                self.rc(),
+                (self.0 >> 1) & 0x1F
+            ),
+            23 => format!(
+                "rlwnm{} r{ra}, r{rd}, r{rb}, {}, {}",
+                self.dot(),
+                self.rc(),
+                (self.0 >> 1) & 0x1F
+            ),
+            24 if self.0 == 0x6000_0000 => "nop".to_string(),
+            op @ 24..=29 => {
+                let name = ["ori", "oris", "xori", "xoris", "andi.", "andis."][op as usize - 24];
+                format!("{name} r{ra}, r{rd}, 0x{uimm:X}")
+            }
+            31 => self.format_31()?,
+            op @ 32..=55 => {
+                let name = LOADS_STORES[op as usize - 32];
+                let reg = if op >= 48 { 'f' } else { 'r' };
+                format!("{name} {reg}{rd}, {simm}(r{ra})")
+            }
+            op @ (56 | 57 | 60 | 61) => {
+                let name = ["psq_l", "psq_lu", "", "", "psq_st", "psq_stu"][op as usize - 56];
+                let offset = ((self.0 & 0xFFF) << 20) as i32 >> 20;
+                format!(
+                    "{name} f{rd}, {offset}(r{ra}), {}, {}",
+                    (self.0 >> 15) & 1,
+                    (self.0 >> 12) & 7
+                )
+            }
+            59 => self.format_float(true)?,
+            63 => self.format_float(false)?,
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    fn format_19(&self) -> Option<String> {
+        let (bo, bi) = (self.rd(), self.ra());
+        let text = match (self.0 >> 1) & 0x3FF {
+            0 => format!("mcrf cr{}, cr{}", bo >> 2, bi >> 2),
+            xo @ (16 | 528) => {
+                let reg = if xo == 16 { "lr" } else { "ctr" };
+                let link = if self.0 & 1 != 0 { "l" } else { "" };
+                match condition(bo, bi) {
+                    Some((cond, field)) if field.is_empty() => format!("b{cond}{reg}{link}"),
+                    Some((cond, field)) => {
+                        format!("b{cond}{reg}{link} {}", field.trim_end_matches(", "))
+                    }
+                    None => format!("bc{reg}{link} {bo}, {bi}"),
+                }
+            }
+            50 => "rfi".to_string(),
+            150 => "isync".to_string(),
+            xo @ (33 | 129 | 193 | 225 | 257 | 289 | 417 | 449) => {
+                let name = match xo {
+                    33 => "crnor",
+                    129 => "crandc",
+                    193 => "crxor",
+                    225 => "crnand",
+                    257 => "crand",
+                    289 => "creqv",
+                    417 => "crorc",
+                    _ => "cror",
+                };
+                format!("{name} {bo}, {bi}, {}", self.rb())
+            }
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    fn format_31(&self) -> Option<String> {
+        let (rd, ra, rb) = (self.rd(), self.ra(), self.rb());
+        let dot = self.dot();
+        // XO-form arithmetic: 9-bit opcode, OE in bit 10.
+        let arithmetic = match (self.0 >> 1) & 0x1FF {
+            266 => Some("add"),
+            10 => Some("addc"),
+            138 => Some("adde"),
+            40 => Some("subf"),
+            8 => Some("subfc"),
+            136 => Some("subfe"),
+            235 => Some("mullw"),
+            75 => Some("mulhw"),
+            11 => Some("mulhwu"),
+            491 => Some("divw"),
+            459 => Some("divwu"),
+            _ => None,
+        };
+        if let Some(name) = arithmetic {
+            let o = if self.0 & 0x400 != 0 { "o" } else { "" };
+            return Some(format!("{name}{o}{dot} r{rd}, r{ra}, r{rb}"));
+        }
+        let unary = match (self.0 >> 1) & 0x1FF {
+            104 => Some("neg"),
+            202 => Some("addze"),
+            234 => Some("addme"),
+            200 => Some("subfze"),
+            232 => Some("subfme"),
+            _ => None,
+        };
+        if let Some(name) = unary {
+            return Some(format!("{name}{dot} r{rd}, r{ra}"));
+        }
+
+        let text = match (self.0 >> 1) & 0x3FF {
+            0 => format!("cmpw {}r{ra}, r{rb}", crf(rd >> 2)),
+            32 => format!("cmplw {}r{ra}, r{rb}", crf(rd >> 2)),
+            444 if rd == rb => format!("mr{dot} r{ra}, r{rd}"),
+            124 if rd == rb => format!("not{dot} r{ra}, r{rd}"),
+            xo @ (24 | 28 | 60 | 124 | 284 | 316 | 412 | 444 | 476 | 536 | 792) => {
+                let name = match xo {
+                    24 => "slw",
+                    28 => "and",
+                    60 => "andc",
+                    124 => "nor",
+                    284 => "eqv",
+                    316 => "xor",
+                    412 => "orc",
+                    444 => "or",
+                    476 => "nand",
+                    536 => "srw",
+                    _ => "sraw",
+                };
+                format!("{name}{dot} r{ra}, r{rd}, r{rb}")
+            }
+            824 => format!("srawi{dot} r{ra}, r{rd}, {rb}"),
+            26 => format!("cntlzw{dot} r{ra}, r{rd}"),
+            922 => format!("extsh{dot} r{ra}, r{rd}"),
+            954 => format!("extsb{dot} r{ra}, r{rd}"),
+            xo @ (23 | 55 | 87 | 119 | 279 | 311 | 343 | 375 | 151 | 183 | 215 | 247 | 407
+            | 439 | 534 | 662 | 790 | 918 | 20) => {
+                let name = match xo {
+                    23 => "lwzx",
+                    55 => "lwzux",
+                    87 => "lbzx",
+                    119 => "lbzux",
+                    279 => "lhzx",
+                    311 => "lhzux",
+                    343 => "lhax",
+                    375 => "lhaux",
+                    151 => "stwx",
+                    183 => "stwux",
+                    215 => "stbx",
+                    247 => "stbux",
+                    407 => "sthx",
+                    439 => "sthux",
+                    534 => "lwbrx",
+                    662 => "stwbrx",
+                    790 => "lhbrx",
+                    918 => "sthbrx",
+                    _ => "lwarx",
+                };
+                format!("{name} r{rd}, r{ra}, r{rb}")
+            }
+            150 => format!("stwcx. r{rd}, r{ra}, r{rb}"),
+            xo @ (535 | 567 | 599 | 631 | 663 | 695 | 727 | 759 | 983) => {
+                let name = match xo {
+                    535 => "lfsx",
+                    567 => "lfsux",
+                    599 => "lfdx",
+                    631 => "lfdux",
+                    663 => "stfsx",
+                    695 => "stfsux",
+                    727 => "stfdx",
+                    759 => "stfdux",
+                    _ => "stfiwx",
+                };
+                format!("{name} f{rd}, r{ra}, r{rb}")
+            }
+            xo @ (339 | 467) => {
+                let spr = ra | (rb << 5);
+                let name = match spr {
+                    1 => Some("xer"),
+                    8 => Some("lr"),
+                    9 => Some("ctr"),
+                    _ => None,
+                };
+                match (xo, name) {
+                    (339, Some(name)) => format!("mf{name} r{rd}"),
+                    (_, Some(name)) => format!("mt{name} r{rd}"),
+                    (339, None) => format!("mfspr r{rd}, {spr}"),
+                    (_, None) => format!("mtspr {spr}, r{rd}"),
+                }
+            }
+            371 => format!("mftb r{rd}"),
+            19 => format!("mfcr r{rd}"),
+            144 => format!("mtcrf 0x{:02X}, r{rd}", (self.0 >> 12) & 0xFF),
+            83 => format!("mfmsr r{rd}"),
+            146 => format!("mtmsr r{rd}"),
+            595 => format!("mfsr r{rd}, {}", ra & 0xF),
+            210 => format!("mtsr {}, r{rd}", ra & 0xF),
+            xo @ (54 | 86 | 278 | 470 | 982 | 1014) => {
+                let name = match xo {
+                    54 => "dcbst",
+                    86 => "dcbf",
+                    278 => "dcbt",
+                    470 => "dcbi",
+                    982 => "icbi",
+                    _ => "dcbz",
+                };
+                format!("{name} r{ra}, r{rb}")
+            }
+            598 => "sync".to_string(),
+            854 => "eieio".to_string(),
+            _ => return None,
+        };
+        Some(text)
+    }
+
+    /// Opcode 59 (single) and 63 (double) arithmetic.
+    fn format_float(&self, single: bool) -> Option<String> {
+        let (fd, fa, fb, fc) = (self.rd(), self.ra(), self.rb(), self.rc());
+        let s = if single { "s" } else { "" };
+        let dot = self.dot();
+        let a_form = match (self.0 >> 1) & 0x1F {
+            18 => Some(("fdiv", format!("f{fd}, f{fa}, f{fb}"))),
+            20 => Some(("fsub", format!("f{fd}, f{fa}, f{fb}"))),
+            21 => Some(("fadd", format!("f{fd}, f{fa}, f{fb}"))),
+            25 => Some(("fmul", format!("f{fd}, f{fa}, f{fc}"))),
+            28 => Some(("fmsub", format!("f{fd}, f{fa}, f{fc}, f{fb}"))),
+            29 => Some(("fmadd", format!("f{fd}, f{fa}, f{fc}, f{fb}"))),
+            30 => Some(("fnmsub", format!("f{fd}, f{fa}, f{fc}, f{fb}"))),
+            31 => Some(("fnmadd", format!("f{fd}, f{fa}, f{fc}, f{fb}"))),
+            24 if single => Some(("fres", format!("f{fd}, f{fb}"))),
+            23 if !single => Some(("fsel", format!("f{fd}, f{fa}, f{fc}, f{fb}"))),
+            26 if !single => Some(("frsqrte", format!("f{fd}, f{fb}"))),
+            _ => None,
+        };
+        if let Some((name, operands)) = a_form {
+            return Some(format!("{name}{s}{dot} {operands}"));
+        }
+        if single {
+            return None;
+        }
+        let text = match (self.0 >> 1) & 0x3FF {
+            0 => format!("fcmpu cr{}, f{fa}, f{fb}", fd >> 2),
+            32 => format!("fcmpo cr{}, f{fa}, f{fb}", fd >> 2),
+            12 => format!("frsp{dot} f{fd}, f{fb}"),
+            14 => format!("fctiw{dot} f{fd}, f{fb}"),
+            15 => format!("fctiwz{dot} f{fd}, f{fb}"),
+            40 => format!("fneg{dot} f{fd}, f{fb}"),
+            72 => format!("fmr{dot} f{fd}, f{fb}"),
+            136 => format!("fnabs{dot} f{fd}, f{fb}"),
+            264 => format!("fabs{dot} f{fd}, f{fb}"),
+            583 => format!("mffs{dot} f{fd}"),
+            711 => format!("mtfsf{dot} 0x{:02X}, f{fb}", (self.0 >> 17) & 0xFF),
+            _ => return None,
+        };
+        Some(text)
+    }
+}
+
+/// `cr3, ` for a non-zero CR field; cr0 is implied.
+fn crf(field: u32) -> String {
+    if field == 0 {
+        String::new()
+    } else {
+        format!("cr{field}, ")
+    }
+}
+
+/// `l`/`a`/`la` from LK and AA.
+fn branch_suffix(raw: u32) -> &'static str {
+    match raw & 3 {
+        0 => "",
+        1 => "l",
+        2 => "a",
+        _ => "la",
+    }
+}
+
+/// Simplified condition for BO/BI: (`eq`, `cr1, `), or `None` for forms
+/// without one.
+fn condition(bo: u32, bi: u32) -> Option<(&'static str, String)> {
+    match bo & 0x14 {
+        0x14 => Some(("", String::new())),
+        0x10 => Some((if bo & 2 != 0 { "dz" } else { "dnz" }, String::new())),
+        0x04 => {
+            let names = if bo & 8 != 0 {
+                ["lt", "gt", "eq", "so"]
+            } else {
+                ["ge", "le", "ne", "ns"]
+            };
+            Some((names[(bi & 3) as usize], crf(bi >> 2)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_forms_use_simplified_mnemonics() {
+        let at = 0x8000_3000;
+        for (raw, text) in [
+            (0x3860_0001, "li r3, 1"),
+            (0x3C60_8040, "lis r3, 0x8040"),
+            (0x386D_0020, "addi r3, r13, 32"),
+            (0x7C83_2378, "mr r3, r4"),
+            (0x6000_0000, "nop"),
+            (0x7C08_02A6, "mflr r0"),
+            (0x9421_FFF0, "stwu r1, -16(r1)"),
+            (0xC023_0004, "lfs f1, 4(r3)"),
+            (0x4E80_0020, "blr"),
+            (0x4E80_0421, "bctrl"),
+            (0x4182_000C, "beq 0x8000300C"),
+            (0x4099_FFF8, "ble cr6, 0x80002FF8"),
+            (0x4200_FFFC, "bdnz 0x80002FFC"),
+            (0x4800_0011, "bl 0x80003010"),
+            (0x2C03_0000, "cmpwi r3, 0"),
+            (0x7C63_2214, "add r3, r3, r4"),
+            (0x5463_103A, "rlwinm r3, r3, 2, 0, 29"),
+            (0xEC21_102A, "fadds f1, f1, f2"),
+            (0x0000_0000, ".word 0x00000000"),
+        ] {
+            assert_eq!(disassemble(raw, at), text, "0x{raw:08X}");
+        }
+    }
+}
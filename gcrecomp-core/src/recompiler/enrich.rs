@@ -9,12 +9,15 @@
 //!   a *leaf* (calls nothing — so codegen can skip link-register save/restore).
 //! - **shape**: whether it ends in a return (`blr`) and whether it contains a
 //!   backward branch (a loop).
+//! - **tail calls**: calls in tail position, which codegen turns into
+//!   trampoline hops instead of nested Rust calls.
 //! - **coverage**: how many of its instructions the decoder actually understood
 //!   (non-`Unknown`), i.e. how much of it becomes real translated code vs. a
 //!   commented-out stub. This is the honest "how much did we recompile" number.
 
 use crate::recompiler::decoder::{DecodedInstruction, InstructionType};
 use crate::recompiler::ghidra::FunctionInfo;
+use serde::{Deserialize, Serialize};
 
 /// Derived facts about one function. This is the "info added to a function".
 #[derive(Debug, Clone, Default)]
@@ -25,12 +28,16 @@ pub struct FunctionFacts {
     pub byte_size: u32,
     /// Addresses called via `bl` (deduplicated, in first-seen order).
     pub call_targets: Vec<u32>,
+    /// `(site, target)` for every relative `bl`, in address order.
+    pub call_sites: Vec<(u32, u32)>,
     /// True if the function never calls another function.
     pub is_leaf: bool,
     /// True if it ends in a `blr` (proper return).
     pub returns: bool,
     /// True if it contains a backward branch (a loop).
     pub has_loop: bool,
+    /// Addresses of calls in tail position (see [`tail_call_sites`]).
+    pub tail_calls: Vec<u32>,
     /// Instructions the decoder understood (non-`Unknown`).
     pub translated: usize,
     /// `translated / instruction_count` in `[0.0, 1.0]`.
@@ -76,6 +83,7 @@ pub fn analyze_function(func: &FunctionInfo, instructions: &[DecodedInstruction]
             facts.is_leaf = false;
             if (raw & 2) == 0 {
                 let target = inst.address.wrapping_add(sign_extend_li(raw) as u32);
+                facts.call_sites.push((inst.address, target));
                 if !facts.call_targets.contains(&target) {
                     facts.call_targets.push(target);
                 }
@@ -100,6 +108,8 @@ pub fn analyze_function(func: &FunctionInfo, instructions: &[DecodedInstruction]
         }
     }
 
+    facts.tail_calls = tail_call_sites(body.iter().copied());
+
     facts.coverage = if facts.instruction_count == 0 {
         0.0
     } else {
@@ -108,6 +118,40 @@ pub fn analyze_function(func: &FunctionInfo, instructions: &[DecodedInstruction]
     facts
 }
 
+/// Calls in tail position within one function's instructions (in address
+/// order): a relative `b` leaving the function, or a `bl` whose next
+/// instruction is a plain `blr`. Returns the addresses of the branches.
+pub fn tail_call_sites<'a>(
+    instructions: impl IntoIterator<Item = &'a DecodedInstruction>,
+) -> Vec<u32> {
+    let body: Vec<&DecodedInstruction> = instructions.into_iter().collect();
+    let (Some(first), Some(last)) = (body.first(), body.last()) else {
+        return Vec::new();
+    };
+    let (start, end) = (first.address, last.address.wrapping_add(4));
+    let mut sites = Vec::new();
+    for (i, inst) in body.iter().enumerate() {
+        let raw = inst.raw;
+        if raw >> 26 != 18 {
+            continue;
+        }
+        let tail = if raw & 1 == 0 {
+            let target = if raw & 2 != 0 {
+                sign_extend_li(raw) as u32
+            } else {
+                inst.address.wrapping_add(sign_extend_li(raw) as u32)
+            };
+            !(start..end).contains(&target)
+        } else {
+            body.get(i + 1).is_some_and(|next| next.raw == BLR)
+        };
+        if tail {
+            sites.push(inst.address);
+        }
+    }
+    sites
+}
+
 /// Enrich every discovered function. This is the "function that adds info to the
 /// functions": one `FunctionFacts` per `FunctionInfo`.
 pub fn enrich_functions(
@@ -121,7 +165,7 @@ pub fn enrich_functions(
 }
 
 /// Whole-program coverage rollup over the enriched functions.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CoverageReport {
     pub functions: usize,
     pub leaf_functions: usize,
@@ -191,6 +235,7 @@ mod tests {
         assert!(!f.is_leaf, "has a bl -> not a leaf");
         assert!(f.returns, "ends in blr");
         assert_eq!(f.call_targets, vec![0x100 + 4 + 0x10], "bl target resolved");
+        assert_eq!(f.tail_calls, vec![0x104], "bl straight into blr");
         assert_eq!(f.instruction_count, 3);
         assert!(f.coverage > 0.0 && f.coverage <= 1.0);
     }
@@ -1,76 +1,69 @@
-//! Enhanced Error Handling
+//! Recompiler Error Types
 //!
-//! This module provides comprehensive error types for the recompiler using `thiserror`.
-//! All errors are zero-cost (no heap allocation) and provide detailed error messages.
+//! The decoder, code generator, validator and pipeline return [`RecompileError`]
+//! so library consumers can react to specific failures (skip a function that
+//! hits an unsupported instruction, retry analysis with another Ghidra backend)
+//! instead of string-matching `anyhow` messages. `anyhow` stays at the binary
+//! boundaries (CLI, game), where `?` converts these automatically.
 //!
 //! # Error Categories
-//! - **Parsing errors**: DOL file parsing, instruction decoding
-//! - **Analysis errors**: Control flow, data flow, type inference
-//! - **Code generation errors**: Rust code generation failures
-//! - **Memory errors**: Invalid memory accesses
-//! - **Validation errors**: Generated code validation failures
+//! - **Parsing errors**: DOL file parsing
+//! - **Decoding errors**: malformed instruction words
+//! - **Analysis errors**: Ghidra, control flow, data flow
+//! - **Code generation errors**: per-instruction translation failures
+//! - **Validation errors**: generated code sanity checks
 
 use thiserror::Error;
 
+/// Result alias for the recompiler's public APIs.
+pub type Result<T> = std::result::Result<T, RecompileError>;
+
 /// Recompiler error types.
-///
-/// Uses `thiserror` for zero-cost error handling with detailed error messages.
-/// All error variants are marked with `#[cold]` to help the optimizer place
-/// error handling code in cold paths.
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
-pub enum RecompilerError {
-    /// DOL file parsing error.
-    ///
-    /// Occurs when the DOL file format is invalid or cannot be parsed.
-    #[error("DOL parsing error: {0}")]
-    DolParseError(String),
+pub enum RecompileError {
+    /// The DOL (or other input file) is malformed.
+    #[error("Parse error: {0}")]
+    ParseError(String),
 
-    /// Instruction decoding error.
-    ///
-    /// Occurs when a PowerPC instruction cannot be decoded (invalid opcode, malformed format).
+    /// An instruction word could not be decoded.
     #[error("Instruction decode error: {0}")]
-    InstructionDecodeError(String),
+    DecodeError(String),
+
+    /// An instruction decoded but has no translation. Only raised when the
+    /// code generator runs in strict mode; otherwise it becomes a comment.
+    /// `opcode` is the full instruction word.
+    #[error("Unsupported instruction 0x{opcode:08X} at 0x{address:08X}")]
+    UnsupportedInstruction { address: u32, opcode: u32 },
 
-    /// Code generation error.
-    ///
-    /// Occurs when Rust code generation fails (invalid IR, unsupported instruction, etc.).
-    #[error("Code generation error: {0}")]
-    CodeGenError(String),
+    /// Translating the instruction at `address` failed (e.g. unexpected operands).
+    #[error("Code generation error at 0x{address:08X}: {message}")]
+    CodegenError { address: u32, message: String },
 
-    /// Ghidra analysis error.
-    ///
-    /// Occurs when Ghidra analysis fails (Ghidra not found, analysis script error, etc.).
+    /// Ghidra analysis failed (Ghidra not found, script error, bad output).
     #[error("Ghidra analysis error: {0}")]
     GhidraError(String),
 
-    /// Memory access error.
-    ///
-    /// Occurs when accessing invalid memory addresses (out of bounds, unmapped region).
-    #[error("Memory access error: address 0x{0:08X}")]
-    MemoryError(u32),
+    /// Control/data flow analysis failed.
+    #[error("Analysis error: {0}")]
+    AnalysisError(String),
 
-    /// Invalid register error.
-    ///
-    /// Occurs when using an invalid register number (PowerPC has 32 GPRs, r0-r31).
-    #[error("Invalid register: {0} (must be 0-31)")]
-    InvalidRegister(u8),
+    /// Generated code failed validation. `line` is 1-based, or 0 when the
+    /// problem isn't tied to one line.
+    #[error("Validation error at line {line}: {message}")]
+    ValidationError { line: usize, message: String },
 
-    /// Optimization error.
-    ///
-    /// Occurs when an optimization pass fails (invalid CFG, data flow analysis error, etc.).
-    #[error("Optimization error: {0}")]
-    OptimizationError(String),
+    /// A pipeline stage ran before the stage that produces its input.
+    #[error("Pipeline stage out of order: {0}")]
+    MissingStage(&'static str),
 
-    /// Validation error.
-    ///
-    /// Occurs when generated Rust code validation fails (syntax error, type error, etc.).
-    #[error("Validation error: {0}")]
-    ValidationError(String),
+    /// Reading input or writing output failed.
+    #[error("I/O error: {0}")]
+    Io(String),
 }
 
-impl From<std::io::Error> for RecompilerError {
+impl From<std::io::Error> for RecompileError {
     #[cold] // Error paths are cold
     fn from(err: std::io::Error) -> Self {
-        RecompilerError::DolParseError(format!("IO error: {}", err))
+        RecompileError::Io(err.to_string())
     }
 }
@@ -0,0 +1,345 @@
+//! Function ID Database
+//!
+//! Names SDK and runtime-library functions (`memcpy`, `OSReport`, `sqrtf`) by
+//! their code alone, without Ghidra's FID pipeline. A signature is the
+//! function's instruction words as they appear in some known build; a
+//! discovered function matches when it has the same length and every word
+//! agrees outside the bits the linker rewrites:
+//!
+//! - the target of a `bl` (calls are relocated, local branches aren't),
+//! - the immediate of `lis` and of the `addi`/`ori`/load/store that consumes
+//!   the register `lis` set (`@ha`/`@l` pairs),
+//! - displacements off r2/r13 (small-data `@sda21` relocations).
+//!
+//! The confidence of a match is the share of signature bits actually
+//! compared, scaled down for signatures too short to be distinctive.
+//!
+//! The database is JSON, an array of signatures:
+//!
+//! ```json
+//! [{"name": "memcpy", "words": ["7C041840", "41800028", "..."],
+//!   "return_type": "void*",
+//!   "parameters": [{"name": "dst", "type": "void*"}]}]
+//! ```
+
+use crate::recompiler::decoder::DecodedInstruction;
+use crate::recompiler::ghidra::{FunctionInfo, ParameterInfo};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Matches below this confidence leave the function's name alone.
+pub const DEFAULT_MIN_CONFIDENCE: f32 = 0.8;
+
+/// Signatures shorter than this many words get proportionally less confidence.
+const DISTINCTIVE_WORDS: usize = 8;
+
+/// One known function.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FidSignature {
+    pub name: String,
+    #[serde(deserialize_with = "hex_words")]
+    pub words: Vec<u32>,
+    #[serde(default)]
+    pub return_type: Option<String>,
+    #[serde(default)]
+    pub parameters: Vec<ParameterInfo>,
+}
+
+/// A function renamed from the database.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FidMatch {
+    pub address: u32,
+    pub name: String,
+    /// 0.0-1.0, see the module docs.
+    pub confidence: f32,
+}
+
+/// Signatures, bucketed by length since only equal lengths can match.
+#[derive(Debug, Clone, Default)]
+pub struct FidDatabase {
+    by_len: HashMap<usize, Vec<(FidSignature, Vec<u32>)>>,
+}
+
+impl FidDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let signatures: Vec<FidSignature> =
+            serde_json::from_str(text).context("Function ID database must be a JSON array")?;
+        let mut db = Self::new();
+        for signature in signatures {
+            db.insert(signature);
+        }
+        Ok(db)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read function ID database: {}", path.display()))?;
+        Self::from_json(&text)
+    }
+
+    pub fn insert(&mut self, signature: FidSignature) {
+        let mask = relocation_mask(&signature.words);
+        self.by_len
+            .entry(signature.words.len())
+            .or_default()
+            .push((signature, mask));
+    }
+
+    pub fn len(&self) -> usize {
+        self.by_len.values().map(Vec::len).sum()
+    }
+
+    pub fn signatures(&self) -> impl Iterator<Item = &FidSignature> {
+        self.by_len.values().flatten().map(|(sig, _)| sig)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_len.is_empty()
+    }
+
+    /// The best signature for a function's words, with its confidence.
+    pub fn best_match(&self, words: &[u32]) -> Option<(&FidSignature, f32)> {
+        self.by_len
+            .get(&words.len())?
+            .iter()
+            .filter(|(sig, mask)| {
+                sig.words
+                    .iter()
+                    .zip(mask)
+                    .zip(words)
+                    .all(|((&s, &m), &w)| s & m == w & m)
+            })
+            .map(|(sig, mask)| (sig, confidence(mask)))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Rename (and retype) every function matching a signature with at least
+    /// `min_confidence`. `instructions` is the decoded program the functions
+    /// were discovered in.
+    pub fn apply(
+        &self,
+        functions: &mut [FunctionInfo],
+        instructions: &[DecodedInstruction],
+        min_confidence: f32,
+    ) -> Vec<FidMatch> {
+        let words: HashMap<u32, u32> = instructions.iter().map(|i| (i.address, i.raw)).collect();
+        let mut matches = Vec::new();
+        for function in functions.iter_mut() {
+            let body: Option<Vec<u32>> = (0..function.size / 4)
+                .map(|i| words.get(&function.address.wrapping_add(i * 4)).copied())
+                .collect();
+            let Some((signature, confidence)) = body.and_then(|body| self.best_match(&body)) else {
+                continue;
+            };
+            if confidence < min_confidence {
+                continue;
+            }
+            function.name = signature.name.clone();
+            if signature.return_type.is_some() {
+                function.return_type = signature.return_type.clone();
+            }
+            if !signature.parameters.is_empty() {
+                function.parameters = signature.parameters.clone();
+            }
+            matches.push(FidMatch {
+                address: function.address,
+                name: signature.name.clone(),
+                confidence,
+            });
+        }
+        matches
+    }
+}
+
+/// Per-word mask of the bits that must match; relocated fields are cleared.
+pub fn relocation_mask(words: &[u32]) -> Vec<u32> {
+    // Registers holding an `@ha` half from `lis`.
+    let mut high_half = [false; 32];
+    words
+        .iter()
+        .map(|&word| {
+            let primary = word >> 26;
+            let rd = ((word >> 21) & 0x1F) as usize;
+            let ra = ((word >> 16) & 0x1F) as usize;
+            let mask = match primary {
+                // bl: LI is the call target.
+                18 if word & 1 != 0 => 0xFC00_0003,
+                // lis rD,@ha
+                15 if ra == 0 => {
+                    high_half[rd] = true;
+                    return 0xFFFF_0000;
+                }
+                // addi / loads / stores off a `lis` register or the SDA bases.
+                14 | 32..=55 if high_half[ra] || ra == 2 || ra == 13 => 0xFFFF_0000,
+                // ori rA,rS,@l: the source register is in the rD slot.
+                24 if high_half[rd] => 0xFFFF_0000,
+                _ => 0xFFFF_FFFF,
+            };
+            // A write to the register ends its `@ha` pairing. Over-clearing
+            // only makes the match stricter.
+            match primary {
+                // Compares, branches, stores and FP loads leave GPRs alone.
+                10 | 11 | 16..=19 | 36 | 38 | 44 | 47 | 48..=52 | 54 => {}
+                // Arithmetic immediates and loads write rD.
+                7 | 8 | 12..=15 | 32 | 34 | 40 | 42 | 46 => high_half[rd] = false,
+                // Rotates, logical immediates and update-form stores write rA.
+                20..=29 | 37 | 39 | 45 | 53 | 55 => high_half[ra] = false,
+                _ => {
+                    high_half[rd] = false;
+                    high_half[ra] = false;
+                }
+            }
+            mask
+        })
+        .collect()
+}
+
+/// Compared bits over all bits, scaled for short signatures.
+fn confidence(mask: &[u32]) -> f32 {
+    if mask.is_empty() {
+        return 0.0;
+    }
+    let compared: u32 = mask.iter().map(|m| m.count_ones()).sum();
+    let coverage = compared as f32 / (mask.len() * 32) as f32;
+    coverage * (mask.len().min(DISTINCTIVE_WORDS) as f32 / DISTINCTIVE_WORDS as f32)
+}
+
+fn hex_words<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<u32>, D::Error> {
+    let words: Vec<String> = Vec::deserialize(deserializer)?;
+    words
+        .iter()
+        .map(|w| {
+            let digits = w.trim_start_matches("0x").trim_start_matches("0X");
+            u32::from_str_radix(digits, 16)
+                .map_err(|_| serde::de::Error::custom(format!("bad instruction word {w:?}")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recompiler::decoder::Instruction;
+
+    /// MSL `memcpy`: byte copy with a backwards path for overlapping ranges.
+    const MEMCPY: [u32; 19] = [
+        0x7C04_1840, // cmplw r4,r3
+        0x4180_0028, // blt backwards
+        0x3884_FFFF, // subi r4,r4,1
+        0x38C3_FFFF, // subi r6,r3,1
+        0x38A5_0001, // addi r5,r5,1
+        0x4800_000C, // b 1f
+        0x8C04_0001, // 0: lbzu r0,1(r4)
+        0x9C06_0001, // stbu r0,1(r6)
+        0x34A5_FFFF, // 1: subic. r5,r5,1
+        0x4082_FFF4, // bne 0b
+        0x4E80_0020, // blr
+        0x7C84_2A14, // backwards: add r4,r4,r5
+        0x7CC3_2A14, // add r6,r3,r5
+        0x38A5_0001, // addi r5,r5,1
+        0x4800_000C, // b 1f
+        0x8C04_FFFF, // 0: lbzu r0,-1(r4)
+        0x9C06_FFFF, // stbu r0,-1(r6)
+        0x34A5_FFFF, // 1: subic. r5,r5,1
+        0x4082_FFF4, // bne 0b
+    ];
+
+    fn function(address: u32, words: &[u32]) -> (FunctionInfo, Vec<DecodedInstruction>) {
+        let info = FunctionInfo {
+            address,
+            name: format!("sub_{address:08x}"),
+            size: words.len() as u32 * 4,
+            calling_convention: "default".to_string(),
+            parameters: vec![],
+            return_type: None,
+            local_variables: vec![],
+            basic_blocks: vec![],
+        };
+        let instrs = words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, address + i as u32 * 4).unwrap())
+            .collect();
+        (info, instrs)
+    }
+
+    fn json(name: &str, words: &[u32]) -> String {
+        let words: Vec<String> = words.iter().map(|w| format!("\"{w:08X}\"")).collect();
+        format!(
+            r#"[{{"name": "{name}", "words": [{}], "return_type": "void*",
+                 "parameters": [{{"name": "dst", "type": "void*"}},
+                                {{"name": "src", "type": "const void*"}},
+                                {{"name": "n", "type": "size_t"}}]}}]"#,
+            words.join(", ")
+        )
+    }
+
+    #[test]
+    fn memcpy_is_renamed_with_high_confidence() {
+        let db = FidDatabase::from_json(&json("memcpy", &MEMCPY)).unwrap();
+        let (info, instrs) = function(0x8000_5000, &MEMCPY);
+        let mut functions = vec![info];
+
+        let matches = db.apply(&mut functions, &instrs, DEFAULT_MIN_CONFIDENCE);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].name, "memcpy");
+        assert!(matches[0].confidence > 0.95, "{:?}", matches[0]);
+        assert_eq!(functions[0].name, "memcpy");
+        assert_eq!(functions[0].return_type.as_deref(), Some("void*"));
+        assert_eq!(functions[0].parameters.len(), 3);
+
+        // One changed opcode is a different function.
+        let mut other = MEMCPY;
+        other[7] = 0x9806_0001; // stb instead of stbu
+        let (info, instrs) = function(0x8000_5000, &other);
+        let mut functions = vec![info];
+        assert!(db
+            .apply(&mut functions, &instrs, DEFAULT_MIN_CONFIDENCE)
+            .is_empty());
+        assert_eq!(functions[0].name, "sub_80005000");
+    }
+
+    #[test]
+    fn relocated_fields_are_ignored() {
+        // lis r3,0x8030 ; addi r3,r3,0x1234 ; lwz r4,-0x7F00(r13) ; bl OSReport ; blr
+        let reference = [
+            0x3C60_8030,
+            0x3863_1234,
+            0x808D_8100,
+            0x4800_1001,
+            0x4E80_0020,
+        ];
+        let relinked = [
+            0x3C60_8031,
+            0x3863_ABCD,
+            0x808D_8200,
+            0x4BFF_F001,
+            0x4E80_0020,
+        ];
+        assert_eq!(
+            relocation_mask(&reference),
+            vec![
+                0xFFFF_0000,
+                0xFFFF_0000,
+                0xFFFF_0000,
+                0xFC00_0003,
+                0xFFFF_FFFF
+            ]
+        );
+
+        let db = FidDatabase::from_json(&json("report_init", &reference)).unwrap();
+        let (sig, confidence) = db.best_match(&relinked).unwrap();
+        assert_eq!(sig.name, "report_init");
+        // Short and mostly relocated: found, but below the renaming bar.
+        assert!(confidence < DEFAULT_MIN_CONFIDENCE, "{confidence}");
+
+        // A local branch is not relocated.
+        assert_eq!(relocation_mask(&[0x4800_0010]), vec![0xFFFF_FFFF]);
+    }
+}
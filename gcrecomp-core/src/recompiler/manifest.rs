@@ -0,0 +1,333 @@
+//! Batch Recompilation Manifests
+//!
+//! A manifest lists DOLs to recompile in one run, each with its own options:
+//!
+//! ```toml
+//! jobs = 4                # DOLs recompiled at once (default 1)
+//!
+//! [[entries]]
+//! dol = "zelda/main.dol"
+//! output_dir = "out/zelda"  # default: <manifest dir>/<dol stem>
+//! opt_level = "aggressive"  # default: the caller's level
+//! symbols = "zelda/main.map"
+//! fidb = "sdk.json"
+//! native_bsim = true
+//! ```
+//!
+//! The same structure works as JSON (`{"jobs": 4, "entries": [...]}`) for
+//! manifests ending in `.json`. Relative paths are taken from the manifest's
+//! directory. [`BatchManifest::run`] keeps going past a DOL that fails and
+//! records why in its [`BatchSummary`], whose entries are in manifest order
+//! however many ran in parallel.
+
+use crate::recompiler::analysis::similarity::SimilarityIndex;
+use crate::recompiler::fidb::FidDatabase;
+use crate::recompiler::optimizer::{OptLevel, Optimizer};
+use crate::recompiler::parser::DolFile;
+use crate::recompiler::pipeline::{RecompilationPipeline, RecompileOptions};
+use crate::recompiler::symbols::SymbolMap;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// One DOL and its options.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchEntry {
+    pub dol: PathBuf,
+    #[serde(default)]
+    pub output_dir: Option<PathBuf>,
+    /// `none`, `basic` or `aggressive`.
+    #[serde(default)]
+    pub opt_level: Option<String>,
+    #[serde(default)]
+    pub symbols: Option<PathBuf>,
+    #[serde(default)]
+    pub fidb: Option<PathBuf>,
+    #[serde(default)]
+    pub native_bsim: bool,
+    /// Not supported yet; such entries fail.
+    #[serde(default)]
+    pub hierarchical: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchManifest {
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+    pub entries: Vec<BatchEntry>,
+}
+
+fn default_jobs() -> usize {
+    1
+}
+
+/// How one entry went.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchResult {
+    pub dol: PathBuf,
+    /// The generated `recompiled.rs`, when the entry succeeded.
+    pub output: Option<PathBuf>,
+    pub error: Option<String>,
+    pub functions: usize,
+    pub failed_functions: usize,
+    /// Percentage of instructions translated.
+    pub coverage: f64,
+}
+
+impl BatchResult {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Every entry's result, in manifest order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    pub entries: Vec<BatchResult>,
+}
+
+impl BatchSummary {
+    pub fn succeeded(&self) -> usize {
+        self.entries.iter().filter(|e| e.succeeded()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.entries.len() - self.succeeded()
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write batch summary: {}", path.display()))
+    }
+}
+
+impl BatchManifest {
+    /// Read a TOML or (by extension) JSON manifest, resolving its paths.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read manifest: {}", path.display()))?;
+        let json = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+        let mut manifest = if json {
+            Self::from_json(&text)
+        } else {
+            Self::from_toml(&text)
+        }
+        .with_context(|| format!("Invalid manifest: {}", path.display()))?;
+        manifest.resolve(path.parent().unwrap_or(Path::new("")));
+        Ok(manifest)
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        Self::from_value(serde_json::from_str(text)?)
+    }
+
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let doc: toml_edit::DocumentMut = text.parse()?;
+        Self::from_value(table_to_json(doc.as_table())?)
+    }
+
+    fn from_value(value: Value) -> Result<Self> {
+        let manifest: Self = serde_json::from_value(value)?;
+        if manifest.jobs == 0 {
+            bail!("jobs must be at least 1");
+        }
+        Ok(manifest)
+    }
+
+    /// Make relative paths relative to `base`, and fill in output dirs.
+    pub fn resolve(&mut self, base: &Path) {
+        let join = |p: &mut PathBuf| {
+            if p.is_relative() {
+                *p = base.join(&*p);
+            }
+        };
+        for entry in &mut self.entries {
+            join(&mut entry.dol);
+            let output = entry.output_dir.get_or_insert_with(|| {
+                PathBuf::from(
+                    entry
+                        .dol
+                        .file_stem()
+                        .unwrap_or(entry.dol.as_os_str())
+                        .to_os_string(),
+                )
+            });
+            join(output);
+            entry.symbols.iter_mut().for_each(join);
+            entry.fidb.iter_mut().for_each(join);
+        }
+    }
+
+    /// Recompile every entry, `jobs` (or the manifest's `jobs`) at a time.
+    /// Entries without an opt level use `default_level`.
+    pub fn run(&self, jobs: Option<usize>, default_level: OptLevel) -> BatchSummary {
+        let jobs = jobs
+            .unwrap_or(self.jobs)
+            .clamp(1, self.entries.len().max(1));
+        // Share the cores between the DOLs running at once.
+        let codegen_jobs = (crate::recompiler::pipeline::default_jobs() / jobs).max(1);
+        let next = AtomicUsize::new(0);
+        let results: Mutex<Vec<Option<BatchResult>>> = Mutex::new(vec![None; self.entries.len()]);
+        std::thread::scope(|scope| {
+            for _ in 0..jobs {
+                scope.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(entry) = self.entries.get(i) else {
+                        break;
+                    };
+                    let result = run_entry(entry, default_level, codegen_jobs);
+                    results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+                });
+            }
+        });
+        let entries = results
+            .into_inner()
+            .unwrap_or_else(|e| e.into_inner())
+            .into_iter()
+            .flatten()
+            .collect();
+        BatchSummary { entries }
+    }
+}
+
+fn run_entry(entry: &BatchEntry, default_level: OptLevel, codegen_jobs: usize) -> BatchResult {
+    let mut result = BatchResult {
+        dol: entry.dol.clone(),
+        output: None,
+        error: None,
+        functions: 0,
+        failed_functions: 0,
+        coverage: 0.0,
+    };
+    // A pipeline panic fails this entry, not the batch.
+    let outcome = std::panic::catch_unwind(|| recompile_entry(entry, default_level, codegen_jobs))
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("recompiler panicked")));
+    match outcome {
+        Ok((output, summary)) => {
+            log::info!(
+                "{}: {} functions, {:.1}% translated",
+                entry.dol.display(),
+                summary.stats.total_functions,
+                summary.coverage.translated_percent()
+            );
+            result.output = Some(output);
+            result.functions = summary.stats.total_functions;
+            result.failed_functions = summary.stats.failed_functions;
+            result.coverage = summary.coverage.translated_percent();
+        }
+        Err(e) => {
+            log::warn!("{}: {e:#}", entry.dol.display());
+            result.error = Some(format!("{e:#}"));
+        }
+    }
+    result
+}
+
+fn recompile_entry(
+    entry: &BatchEntry,
+    default_level: OptLevel,
+    codegen_jobs: usize,
+) -> Result<(PathBuf, crate::recompiler::pipeline::RecompileSummary)> {
+    if entry.hierarchical {
+        bail!("hierarchical recompilation is not supported");
+    }
+    let level = match &entry.opt_level {
+        Some(level) => level.parse().map_err(anyhow::Error::msg)?,
+        None => default_level,
+    };
+    let symbols = match &entry.symbols {
+        Some(path) => {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read symbol map: {}", path.display()))?;
+            Some(SymbolMap::parse(&text))
+        }
+        None => None,
+    };
+    let fidb = entry.fidb.as_deref().map(FidDatabase::load).transpose()?;
+    let similarity = match &fidb {
+        Some(db) if entry.native_bsim => Some(SimilarityIndex::from_database(db)),
+        _ => None,
+    };
+
+    let data = std::fs::read(&entry.dol)
+        .with_context(|| format!("Failed to read DOL file: {}", entry.dol.display()))?;
+    let dol =
+        DolFile::parse(&data, &entry.dol.to_string_lossy()).context("Failed to parse DOL file")?;
+    let dir = entry
+        .output_dir
+        .as_deref()
+        .context("entry has no output directory")?;
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create output directory: {}", dir.display()))?;
+    let output = dir.join("recompiled.rs");
+
+    let options = RecompileOptions {
+        symbols,
+        fidb,
+        similarity,
+        optimizer: Optimizer::with_level(level),
+        jobs: Some(codegen_jobs),
+        ..Default::default()
+    };
+    let summary = RecompilationPipeline::recompile_with_options(
+        &dol,
+        output.to_str().context("Invalid output path")?,
+        &options,
+    )
+    .context("Recompilation pipeline failed")?;
+    Ok((output, summary))
+}
+
+/// A TOML table (with nested tables and arrays of tables) as JSON.
+fn table_to_json(table: &toml_edit::Table) -> Result<Value> {
+    let mut map = serde_json::Map::new();
+    for (key, item) in table.iter() {
+        map.insert(key.to_string(), item_to_json(key, item)?);
+    }
+    Ok(Value::Object(map))
+}
+
+fn item_to_json(key: &str, item: &toml_edit::Item) -> Result<Value> {
+    Ok(match item {
+        toml_edit::Item::Table(table) => table_to_json(table)?,
+        toml_edit::Item::ArrayOfTables(tables) => Value::Array(
+            tables
+                .iter()
+                .map(table_to_json)
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        toml_edit::Item::Value(value) => value_to_json(key, value)?,
+        toml_edit::Item::None => Value::Null,
+    })
+}
+
+fn value_to_json(key: &str, value: &toml_edit::Value) -> Result<Value> {
+    Ok(match value {
+        toml_edit::Value::String(s) => Value::String(s.value().clone()),
+        toml_edit::Value::Integer(i) => Value::from(*i.value()),
+        toml_edit::Value::Float(f) => Value::from(*f.value()),
+        toml_edit::Value::Boolean(b) => Value::Bool(*b.value()),
+        toml_edit::Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|v| value_to_json(key, v))
+                .collect::<Result<Vec<_>>>()?,
+        ),
+        toml_edit::Value::InlineTable(table) => {
+            let mut map = serde_json::Map::new();
+            for (k, v) in table.iter() {
+                map.insert(k.to_string(), value_to_json(k, v)?);
+            }
+            Value::Object(map)
+        }
+        toml_edit::Value::Datetime(_) => bail!("{key}: dates are not supported"),
+    })
+}
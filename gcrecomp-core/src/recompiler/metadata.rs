@@ -0,0 +1,51 @@
+//! Machine-Readable Analysis Output
+//!
+//! `gcrecomp analyze --json` writes an [`AnalysisMetadata`]: the discovered
+//! functions, the call graph between them, the functions the entry point
+//! never reaches and the coverage estimate. External tools key off
+//! `schema_version`; bump [`SCHEMA_VERSION`] whenever a field is renamed,
+//! removed or changes meaning (adding a field does not need a bump).
+
+use crate::recompiler::analysis::inter_procedural::{CallGraph, InterProceduralAnalyzer};
+use crate::recompiler::enrich::{CoverageReport, FunctionFacts};
+use crate::recompiler::ghidra::FunctionInfo;
+use serde::{Deserialize, Serialize};
+
+/// Version of the JSON layout below.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisMetadata {
+    pub schema_version: u32,
+    pub entry_point: u32,
+    pub functions: Vec<FunctionInfo>,
+    /// Node indices follow `functions`.
+    pub call_graph: CallGraph,
+    /// Addresses of functions no call chain from the entry point reaches.
+    pub unreachable_functions: Vec<u32>,
+    pub coverage: CoverageReport,
+    /// `coverage.translated_instructions / coverage.total_instructions`.
+    pub instruction_coverage: f32,
+}
+
+impl AnalysisMetadata {
+    /// `facts` must be `enrich_functions(&functions, ..)`.
+    pub fn new(entry_point: u32, functions: Vec<FunctionInfo>, facts: &[FunctionFacts]) -> Self {
+        let call_graph = InterProceduralAnalyzer::call_graph_from_facts(facts, entry_point);
+        let unreachable_functions =
+            InterProceduralAnalyzer::find_unreachable_functions(&call_graph)
+                .into_iter()
+                .map(|idx| call_graph.nodes[idx].address)
+                .collect();
+        let coverage = CoverageReport::from_facts(facts);
+        Self {
+            schema_version: SCHEMA_VERSION,
+            entry_point,
+            functions,
+            call_graph,
+            unreachable_functions,
+            instruction_coverage: coverage.instruction_coverage(),
+            coverage,
+        }
+    }
+}
@@ -1,10 +1,21 @@
 pub mod analysis;
+pub mod batch;
+pub mod cache;
 pub mod codegen;
+pub mod coverage;
 pub mod decoder;
+pub mod disasm;
 pub mod enrich;
 pub mod error;
+pub mod fidb;
 pub mod ghidra;
+pub mod manifest;
+pub mod metadata;
 pub mod optimizer;
 pub mod parser;
 pub mod pipeline;
+pub mod profile;
+pub mod report;
+pub mod specialize;
+pub mod symbols;
 pub mod validator;
@@ -2,45 +2,242 @@
 //!
 //! This module provides optimization passes for decoded PowerPC instructions.
 //! These optimizations improve code quality and reduce generated code size.
+//! The pipeline runs them per function, before code generation, as configured
+//! by [`RecompileOptions::optimizer`](crate::recompiler::pipeline::RecompileOptions).
 //!
 //! # Optimization Passes
 //! - **Constant Folding**: Evaluate constant expressions at compile time
 //! - **Dead Code Elimination**: Remove unused instructions
-//! - **Constant Propagation**: Track li/addi constant loads through register chains
+//! - **Constant Propagation**: Track li/addi constant loads through register chains,
+//!   rewriting `add` with a known operand to `addi`
+//! - **Redundant Load Elimination**: Replace a load from a location an earlier
+//!   load in the same block already read with a register copy, when pointer
+//!   analysis shows no store in between can touch it
+//! - **Loop-Invariant Code Motion**: Move register computations whose inputs a
+//!   loop never changes to just before the loop
+//! - **Loop Unrolling**: Repeat the body of a branch-free counted loop whose trip
+//!   count is a small constant (`mtctr`/`bdnz`, or a register stepped by `addi`
+//!   and compared against an immediate), so fewer back edges are taken
 //! - **Function-level DCE**: Remove unreachable functions using call graph analysis
+//!
+//! The instruction passes work on a straight-line view of the function, so
+//! they treat branches and branch targets as barriers: constants are forgotten
+//! and every register is assumed live there. Only instructions known to write
+//! a single GPR and nothing else (no CR0, XER or memory) are ever removed.
+//! Unrolled copies keep their original addresses, so they land in the loop's
+//! block in code generation and branch targets don't move; hoisted
+//! instructions take the address of the instruction they follow.
 
-use crate::recompiler::decoder::{DecodedInstruction, InstructionType, Operand};
+use crate::recompiler::analysis::pointer::{
+    MemoryAccess, MemoryMap, MemoryRegion, PointerAnalyzer,
+};
+use crate::recompiler::codegen::sda;
+use crate::recompiler::decoder::{DecodedInstruction, Instruction, InstructionType, Operand};
 use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Preset pass selections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptLevel {
+    /// No instruction-level passes.
+    None,
+    /// Constant folding only.
+    Basic,
+    /// Every pass.
+    Aggressive,
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(OptLevel::None),
+            "basic" => Ok(OptLevel::Basic),
+            "aggressive" => Ok(OptLevel::Aggressive),
+            other => Err(format!(
+                "unknown optimization level '{other}' (expected none, basic or aggressive)"
+            )),
+        }
+    }
+}
+
+/// Counts accumulated by [`Optimizer::optimize`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptimizerStats {
+    /// `addi` instructions rewritten to `li` of a known value.
+    pub folded_constants: usize,
+    /// Instructions removed by dead code elimination.
+    pub eliminated_instructions: usize,
+    /// Loops whose body was repeated by loop unrolling.
+    pub unrolled_loops: usize,
+    /// Instructions moved out of loops by loop-invariant code motion.
+    pub hoisted_instructions: usize,
+    /// Loads replaced with a copy of a register by redundant load elimination.
+    pub eliminated_loads: usize,
+}
+
+impl std::ops::AddAssign for OptimizerStats {
+    fn add_assign(&mut self, other: Self) {
+        self.folded_constants += other.folded_constants;
+        self.eliminated_instructions += other.eliminated_instructions;
+        self.unrolled_loops += other.unrolled_loops;
+        self.hoisted_instructions += other.hoisted_instructions;
+        self.eliminated_loads += other.eliminated_loads;
+    }
+}
 
 /// Optimizer for PowerPC instructions.
 ///
 /// Applies various optimization passes to improve code quality.
+#[derive(Debug, Clone)]
 pub struct Optimizer {
     /// Enable constant folding optimization
     constant_folding: bool,
     /// Enable dead code elimination
     dead_code_elimination: bool,
+    /// Enable loop-invariant code motion
+    loop_invariant_motion: bool,
+    /// Enable loop unrolling
+    loop_unrolling: bool,
+    /// Enable redundant load elimination
+    load_elimination: bool,
+    /// Sections the pointer analysis classifies addresses against.
+    memory_map: Option<Arc<MemoryMap>>,
+    /// Most copies of a loop body after unrolling.
+    unroll_factor: usize,
+    stats: OptimizerStats,
 }
 
+/// Default for [`Optimizer::set_unroll_factor`].
+pub const DEFAULT_UNROLL_FACTOR: usize = 4;
+/// Loops running more times than this are left alone.
+const MAX_UNROLL_TRIPS: u32 = 64;
+/// Instructions an unrolled body may grow to.
+const MAX_UNROLLED_LEN: usize = 64;
+/// `blr`.
+const BLR: u32 = 0x4E80_0020;
+
 impl Optimizer {
+    /// Names accepted by [`set_pass`](Self::set_pass).
+    pub const PASSES: &'static [&'static str] = &[
+        "constant_folding",
+        "dead_code_elimination",
+        "loop_invariant_motion",
+        "loop_unrolling",
+        "redundant_load_elimination",
+    ];
+
     /// Create a new optimizer with all optimizations enabled.
     pub fn new() -> Self {
-        Self {
-            constant_folding: true,
-            dead_code_elimination: true,
+        Self::with_level(OptLevel::Aggressive)
+    }
+
+    pub fn with_level(level: OptLevel) -> Self {
+        let mut optimizer = Self {
+            constant_folding: false,
+            dead_code_elimination: false,
+            loop_invariant_motion: false,
+            loop_unrolling: false,
+            load_elimination: false,
+            memory_map: None,
+            unroll_factor: DEFAULT_UNROLL_FACTOR,
+            stats: OptimizerStats::default(),
+        };
+        optimizer.set_level(level);
+        optimizer
+    }
+
+    /// Switch to a preset pass selection, keeping the accumulated stats.
+    pub fn set_level(&mut self, level: OptLevel) {
+        self.constant_folding = level != OptLevel::None;
+        self.dead_code_elimination = level == OptLevel::Aggressive;
+        self.loop_invariant_motion = level == OptLevel::Aggressive;
+        self.loop_unrolling = level == OptLevel::Aggressive;
+        self.load_elimination = level == OptLevel::Aggressive;
+    }
+
+    /// The DOL's layout, so redundant load elimination can tell globals in
+    /// its sections from stack slots. Without one only stack and small-data
+    /// accesses are told apart.
+    pub fn set_memory_map(&mut self, map: MemoryMap) {
+        self.memory_map = Some(Arc::new(map));
+    }
+
+    /// Most copies of a loop body loop unrolling makes; below 2, none.
+    pub fn set_unroll_factor(&mut self, factor: usize) {
+        self.unroll_factor = factor;
+    }
+
+    /// Enable or disable one pass by name. Returns false for an unknown name.
+    pub fn set_pass(&mut self, name: &str, enabled: bool) -> bool {
+        match name {
+            "constant_folding" => self.constant_folding = enabled,
+            "dead_code_elimination" => self.dead_code_elimination = enabled,
+            "loop_invariant_motion" => self.loop_invariant_motion = enabled,
+            "loop_unrolling" => self.loop_unrolling = enabled,
+            "redundant_load_elimination" => self.load_elimination = enabled,
+            _ => return false,
         }
+        true
+    }
+
+    pub fn pass_enabled(&self, name: &str) -> Option<bool> {
+        match name {
+            "constant_folding" => Some(self.constant_folding),
+            "dead_code_elimination" => Some(self.dead_code_elimination),
+            "loop_invariant_motion" => Some(self.loop_invariant_motion),
+            "loop_unrolling" => Some(self.loop_unrolling),
+            "redundant_load_elimination" => Some(self.load_elimination),
+            _ => None,
+        }
+    }
+
+    /// Counts accumulated over every `optimize` call so far.
+    pub fn stats(&self) -> OptimizerStats {
+        self.stats
+    }
+
+    /// Return the counts so far and start again from zero.
+    pub fn take_stats(&mut self) -> OptimizerStats {
+        std::mem::take(&mut self.stats)
     }
 
     /// Optimize a sequence of instructions.
-    pub fn optimize(&self, instructions: &[DecodedInstruction]) -> Vec<DecodedInstruction> {
+    pub fn optimize(&mut self, instructions: &[DecodedInstruction]) -> Vec<DecodedInstruction> {
+        self.optimize_with_constants(instructions, &[])
+    }
+
+    /// Optimize a function entered with the `(register, value)` pairs in
+    /// `entry` already known, as a specialized copy is (see `specialize`).
+    pub fn optimize_with_constants(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        entry: &[(u8, u32)],
+    ) -> Vec<DecodedInstruction> {
         let mut optimized: Vec<DecodedInstruction> = instructions.to_vec();
+        let labels = branch_targets(instructions);
 
         if self.constant_folding {
-            optimized = self.fold_constants(&optimized);
+            optimized = self.fold_constants(&optimized, &labels, entry);
+        }
+
+        // Before the loop passes, while every instruction has its own address.
+        if self.load_elimination {
+            optimized = self.eliminate_redundant_loads(&optimized, &labels);
+        }
+
+        if self.loop_invariant_motion {
+            optimized = self.hoist_invariants(&optimized, &labels);
+        }
+
+        if self.loop_unrolling {
+            optimized = self.unroll_loops(&optimized, &labels);
         }
 
         if self.dead_code_elimination {
-            optimized = self.eliminate_dead_code(&optimized);
+            optimized = self.eliminate_dead_code(&optimized, &labels);
         }
 
         optimized
@@ -48,64 +245,114 @@ impl Optimizer {
 
     /// Constant folding and propagation pass.
     ///
-    /// Tracks constant values loaded by `li` (opcode 14 with rA=0) and `addi`
-    /// patterns, propagating them through register chains.
-    fn fold_constants(&self, instructions: &[DecodedInstruction]) -> Vec<DecodedInstruction> {
+    /// Tracks constant values loaded by `li` (opcode 14 with rA=0) and `lis`,
+    /// propagating them through `addi` chains. An `addi` whose base is a known
+    /// constant and whose result fits a 16-bit immediate becomes `li`; an `add`
+    /// with one such operand becomes `addi`, or `li` with both.
+    fn fold_constants(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        labels: &HashSet<u32>,
+        entry: &[(u8, u32)],
+    ) -> Vec<DecodedInstruction> {
         let mut result: Vec<DecodedInstruction> = Vec::with_capacity(instructions.len());
-        let mut constants: HashMap<u8, u32> = HashMap::new();
+        let mut constants: HashMap<u8, u32> = entry.iter().copied().collect();
 
         for inst in instructions.iter() {
-            // Track li/addi constant loads (opcode 14 = addi; li is addi rD,0,imm)
-            if inst.instruction.opcode == 14 && inst.instruction.operands.len() >= 3 {
-                if let (Operand::Register(rd), Operand::Register(ra), Operand::Immediate(imm)) = (
-                    &inst.instruction.operands[0],
-                    &inst.instruction.operands[1],
-                    &inst.instruction.operands[2],
-                ) {
-                    if *ra == 0 {
-                        // li rD, imm — rD = sign_extend(imm)
-                        constants.insert(*rd, *imm as i32 as u32);
-                    } else if let Some(&base) = constants.get(ra) {
-                        // addi rD, rA, imm where rA is known constant
-                        constants.insert(*rd, base.wrapping_add(*imm as i32 as u32));
-                    } else {
-                        constants.remove(rd);
-                    }
-                }
+            // Control flow can join here from elsewhere: nothing is known.
+            if labels.contains(&inst.address) {
+                constants.clear();
             }
-            // Track lis (opcode 15 = addis; lis is addis rD,0,imm)
-            else if inst.instruction.opcode == 15 && inst.instruction.operands.len() >= 3 {
-                if let (Operand::Register(rd), Operand::Register(ra), Operand::Immediate(imm)) = (
-                    &inst.instruction.operands[0],
-                    &inst.instruction.operands[1],
-                    &inst.instruction.operands[2],
-                ) {
-                    if *ra == 0 {
-                        constants.insert(*rd, (*imm as u32) << 16);
+            let mut inst = inst.clone();
+            let raw = inst.raw;
+            let (rd, ra) = (((raw >> 21) & 31) as u8, ((raw >> 16) & 31) as u8);
+            let imm = raw as u16 as i16;
+
+            match raw >> 26 {
+                // addi / li
+                14 => {
+                    let value = if ra == 0 {
+                        Some(imm as i32 as u32)
                     } else {
-                        constants.remove(rd);
+                        constants
+                            .get(&ra)
+                            .map(|&base| base.wrapping_add(imm as i32 as u32))
+                    };
+                    match value {
+                        Some(v) => {
+                            if ra != 0 && v as i32 == v as i16 as i32 {
+                                fold_to_li(&mut inst, rd, v as i16);
+                                self.stats.folded_constants += 1;
+                            }
+                            constants.insert(rd, v);
+                        }
+                        None => {
+                            constants.remove(&rd);
+                        }
                     }
                 }
-            }
-            // Invalidate register on any other write
-            else if let Some(Operand::Register(rd)) = inst.instruction.operands.first() {
-                if matches!(
-                    inst.instruction.instruction_type,
-                    InstructionType::Arithmetic
-                        | InstructionType::Load
-                        | InstructionType::Move
-                        | InstructionType::Shift
-                        | InstructionType::Rotate
-                ) {
-                    constants.remove(rd);
+                // addis / lis
+                15 if ra == 0 => {
+                    constants.insert(rd, (imm as u16 as u32) << 16);
                 }
+                // add (OE=0, Rc=0)
+                31 if raw & 0x7FF == 266 << 1 => {
+                    let rb = ((raw >> 11) & 31) as u8;
+                    let (a, b) = (constants.get(&ra).copied(), constants.get(&rb).copied());
+                    let fits = |v: u32| v as i32 == v as i16 as i32;
+                    match (a, b) {
+                        (Some(a), Some(b)) => {
+                            let v = a.wrapping_add(b);
+                            if fits(v) {
+                                fold_to_addi(&mut inst, rd, 0, v as i16); // li
+                                self.stats.folded_constants += 1;
+                            }
+                            constants.insert(rd, v);
+                        }
+                        // addi reads r0 as zero, so the register operand can't be r0.
+                        (None, Some(k)) if fits(k) && ra != 0 => {
+                            fold_to_addi(&mut inst, rd, ra, k as i16);
+                            self.stats.folded_constants += 1;
+                            constants.remove(&rd);
+                        }
+                        (Some(k), None) if fits(k) && rb != 0 => {
+                            fold_to_addi(&mut inst, rd, rb, k as i16);
+                            self.stats.folded_constants += 1;
+                            constants.remove(&rd);
+                        }
+                        _ => {
+                            constants.remove(&rd);
+                        }
+                    }
+                }
+                _ => match pure_def(raw) {
+                    Some((dest, _)) => {
+                        constants.remove(&dest);
+                    }
+                    // lmw writes rD..r31; unknown instructions could write anything.
+                    None if raw >> 26 == 46
+                        || inst.instruction.instruction_type == InstructionType::Unknown =>
+                    {
+                        constants.clear()
+                    }
+                    None => {
+                        for op in &inst.instruction.operands {
+                            if let Operand::Register(r) = op {
+                                constants.remove(r);
+                            }
+                        }
+                    }
+                },
             }
             // Branches invalidate all tracked constants (control flow merge)
-            if matches!(inst.instruction.instruction_type, InstructionType::Branch) {
+            if matches!(
+                inst.instruction.instruction_type,
+                InstructionType::Branch | InstructionType::System
+            ) {
                 constants.clear();
             }
 
-            result.push(inst.clone());
+            result.push(inst);
         }
 
         result
@@ -113,58 +360,360 @@ impl Optimizer {
 
     /// Dead code elimination pass.
     ///
-    /// Removes instructions that write to registers never subsequently read.
-    fn eliminate_dead_code(&self, instructions: &[DecodedInstruction]) -> Vec<DecodedInstruction> {
-        let mut result: Vec<DecodedInstruction> = Vec::with_capacity(instructions.len());
-
-        // Collect used registers in a backward pass
-        let mut used_after: HashSet<u8> = HashSet::new();
-        // Assume all registers may be live at function exit
-        for r in 0..32u8 {
-            used_after.insert(r);
-        }
-
+    /// Removes side-effect-free instructions whose result is overwritten before
+    /// it is read. Branch targets are kept so block boundaries don't move.
+    fn eliminate_dead_code(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        labels: &HashSet<u32>,
+    ) -> Vec<DecodedInstruction> {
+        const ALL_LIVE: u32 = u32::MAX;
+        // Bit r set = GPR r may be read later. Everything is live at exit.
+        let mut live: u32 = ALL_LIVE;
         let mut keep = vec![true; instructions.len()];
 
         for (i, inst) in instructions.iter().enumerate().rev() {
-            // Branch/system/store instructions always kept (side effects)
             if matches!(
                 inst.instruction.instruction_type,
-                InstructionType::Branch
-                    | InstructionType::System
-                    | InstructionType::Store
-                    | InstructionType::Compare
+                InstructionType::Branch | InstructionType::System | InstructionType::Unknown
             ) {
-                // Mark all source registers as used
-                for op in &inst.instruction.operands {
-                    if let Operand::Register(r) = op {
-                        used_after.insert(*r);
+                live = ALL_LIVE;
+                continue;
+            }
+            match pure_def(inst.raw) {
+                Some((dest, sources)) => {
+                    if live & (1 << dest) == 0 && !labels.contains(&inst.address) {
+                        keep[i] = false;
+                        continue;
+                    }
+                    live &= !(1 << dest);
+                    for r in sources.into_iter().flatten() {
+                        live |= 1 << r;
+                    }
+                }
+                None => {
+                    // Unclear which operands it writes: kill nothing, read everything named.
+                    for op in &inst.instruction.operands {
+                        if let Operand::Register(r) = op {
+                            live |= 1 << r;
+                        }
+                    }
+                    if inst.raw >> 26 == 47 {
+                        live = ALL_LIVE; // stmw reads rS..r31
                     }
                 }
-                continue;
             }
+        }
 
-            // For instructions that write a register: check if it's read later
-            if let Some(Operand::Register(rd)) = inst.instruction.operands.first() {
-                if !used_after.contains(rd) {
-                    keep[i] = false;
+        let mut result: Vec<DecodedInstruction> = Vec::with_capacity(instructions.len());
+        for (i, inst) in instructions.iter().enumerate() {
+            if keep[i] {
+                result.push(inst.clone());
+            } else {
+                self.stats.eliminated_instructions += 1;
+            }
+        }
+
+        result
+    }
+
+    /// Redundant load elimination pass.
+    ///
+    /// Within each block, remembers which register holds the value of each
+    /// `lwz`/`lbz`/`lhz`/`lha` whose target [`PointerAnalyzer`] knows. A later
+    /// load of the same width from the same target becomes `ori rD,rX,0`
+    /// while rX still holds it and no store in between may alias it (see
+    /// [`MemoryAccess::may_alias`]). A store or anything else that may write
+    /// memory with no known target forgets everything. Hardware registers and
+    /// unclassified addresses are never reused, as they may read differently
+    /// each time.
+    fn eliminate_redundant_loads(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        labels: &HashSet<u32>,
+    ) -> Vec<DecodedInstruction> {
+        let map = match &self.memory_map {
+            Some(map) => Arc::clone(map),
+            None => Arc::new(MemoryMap::without_sections()),
+        };
+        let analysis = PointerAnalyzer::analyze(instructions, &map);
+        let mut result: Vec<DecodedInstruction> = instructions.to_vec();
+        // (load opcode, what it read, register holding the value)
+        let mut available: Vec<(u32, MemoryAccess, u8)> = Vec::new();
+
+        for inst in &mut result {
+            if labels.contains(&inst.address) || is_barrier(inst) {
+                available.clear();
+            }
+            let raw = inst.raw;
+            let primary = raw >> 26;
+            let access = analysis.access_at(inst.address);
+            let rd = ((raw >> 21) & 31) as u8;
+            if let (32 | 34 | 40 | 42, Some(access)) = (primary, access) {
+                if !matches!(
+                    access.region,
+                    MemoryRegion::Hardware | MemoryRegion::Unknown
+                ) {
+                    let held = available
+                        .iter()
+                        .find(|(op, earlier, _)| *op == primary && earlier.target == access.target)
+                        .map(|&(_, _, reg)| reg);
+                    if let Some(reg) = held {
+                        copy_register(inst, rd, reg);
+                        self.stats.eliminated_loads += 1;
+                    }
+                    available.retain(|&(_, _, reg)| reg != rd);
+                    available.push((primary, *access, rd));
                     continue;
                 }
-                // Remove destination from used set, add sources
-                used_after.remove(rd);
             }
+            if let Some(store) = access.filter(|a| a.store) {
+                available.retain(|(_, earlier, _)| !store.may_alias(earlier));
+            } else if may_write_memory(inst) {
+                available.clear();
+            }
+            available.retain(|&(_, _, reg)| !sda::writes_gpr(raw, reg));
+        }
 
-            for op in inst.instruction.operands.iter().skip(1) {
-                if let Operand::Register(r) = op {
-                    used_after.insert(*r);
+        result
+    }
+
+    /// Loop unrolling pass.
+    ///
+    /// Finds loops made of one straight-line block that branches back to its
+    /// own start, entered only by falling into it, and running a constant
+    /// number of times:
+    ///
+    /// - `li rX,N ; mtctr rX ; L: body ; bdnz L`: the body is repeated `f`
+    ///   times and `li rX,N` becomes `li rX,N/f`. rX must not be read again
+    ///   (see [`dead_after`]), since it now holds the smaller count.
+    /// - `li rC,V ; L: body ; addi rC,rC,S ; cmpwi rC,K ; b<cond> L`: the body
+    ///   and its `addi` are repeated `f` times ahead of one compare, so rC
+    ///   and CR0 end up exactly as before.
+    ///
+    /// `f` is the largest factor up to `unroll_factor` that divides the trip
+    /// count and keeps the body within `MAX_UNROLLED_LEN`. The copies run in
+    /// the original order, so loads and stores in the body are fine; the body
+    /// just can't touch the counter, CTR or the CR. Copies keep their
+    /// addresses, which is how codegen retires `f` original passes per trip.
+    fn unroll_loops(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        labels: &HashSet<u32>,
+    ) -> Vec<DecodedInstruction> {
+        let branches_to = branch_counts(instructions);
+        let mut plans: Vec<Unroll> = Vec::new();
+        for j in 0..instructions.len() {
+            if let Some(plan) = self.plan_unroll(instructions, labels, &branches_to, j) {
+                plans.push(plan);
+            }
+        }
+        if plans.is_empty() {
+            return instructions.to_vec();
+        }
+
+        let mut result: Vec<DecodedInstruction> = Vec::with_capacity(instructions.len());
+        let mut plans = plans.into_iter().peekable();
+        let mut i = 0;
+        while i < instructions.len() {
+            let Some(plan) = plans.peek() else {
+                result.extend_from_slice(&instructions[i..]);
+                break;
+            };
+            if let Some((li, count)) = plan.count {
+                if i == li {
+                    let mut inst = instructions[i].clone();
+                    let rd = ((inst.raw >> 21) & 31) as u8;
+                    fold_to_li(&mut inst, rd, count as i16);
+                    result.push(inst);
+                    i += 1;
+                    continue;
+                }
+            }
+            if i == plan.head {
+                for _ in 0..plan.factor {
+                    result.extend_from_slice(&instructions[plan.head..plan.end]);
                 }
+                i = plan.end;
+                plans.next();
+                self.stats.unrolled_loops += 1;
+                continue;
             }
+            result.push(instructions[i].clone());
+            i += 1;
         }
 
-        for (i, inst) in instructions.iter().enumerate() {
-            if keep[i] {
-                result.push(inst.clone());
+        result
+    }
+
+    /// How to unroll the loop closed by the branch at `j`, if it can be.
+    fn plan_unroll(
+        &self,
+        instructions: &[DecodedInstruction],
+        labels: &HashSet<u32>,
+        branches_to: &HashMap<u32, usize>,
+        j: usize,
+    ) -> Option<Unroll> {
+        let head = single_block_loop(instructions, labels, branches_to, j)?;
+        let branch = &instructions[j];
+        let bo = (branch.raw >> 21) & 31;
+        let bi = (branch.raw >> 16) & 31;
+        let (end, trips, count_reg) = if bo & 0b10110 == 0b10000 {
+            // bdnz: CTR counts down from N.
+            let mut found = None;
+            for (k, inst) in instructions[..head].iter().enumerate().rev() {
+                if labels.contains(&inst.address) {
+                    break;
+                }
+                if let Some(reg) = mtctr_source(inst.raw) {
+                    found = Some((k, reg));
+                    break;
+                }
+                if is_barrier(inst) {
+                    break;
+                }
+            }
+            let (mtctr, reg) = found?;
+            let (li, n) = constant_before(instructions, labels, mtctr, reg)?;
+            if instructions[mtctr + 1..j].iter().any(|i| mentions(i, reg))
+                || !dead_after(&instructions[j + 1..], reg)
+            {
+                return None;
+            }
+            (j, n, Some((li, reg)))
+        } else if bo & 0b10100 == 0b00100 && bi < 3 && j >= head + 2 {
+            // A register stepped by `addi` and compared with an immediate.
+            let (step, cmp) = (instructions[j - 2].raw, instructions[j - 1].raw);
+            let reg = ((cmp >> 16) & 31) as u8;
+            let signed = match cmp >> 26 {
+                11 => true,
+                10 => false,
+                _ => return None,
+            };
+            let imm = cmp as u16;
+            if (cmp >> 21) & 31 != 0 // cr0, 32-bit compare
+                || step >> 26 != 14
+                || reg == 0
+                || (step >> 21) & 31 != reg as u32
+                || (step >> 16) & 31 != reg as u32
+                || step as u16 == 0
+            {
+                return None;
             }
+            if instructions[head..j - 2]
+                .iter()
+                .any(|i| sda::writes_gpr(i.raw, reg))
+            {
+                return None;
+            }
+            let (_, start) = constant_before(instructions, labels, head, reg)?;
+            let trips = counted_trips(start, step as i16, imm, signed, bo, bi)?;
+            (j - 1, trips, None)
+        } else {
+            return None;
+        };
+
+        let body = &instructions[head..end];
+        if !body
+            .iter()
+            .all(|i| replicable(i, count_reg.map(|(_, r)| r)))
+        {
+            return None;
+        }
+        if trips == 0 || trips > MAX_UNROLL_TRIPS {
+            return None;
+        }
+        let factor = (2..=self.unroll_factor)
+            .rev()
+            .find(|&f| trips as usize % f == 0 && body.len() * f <= MAX_UNROLLED_LEN)?;
+        Some(Unroll {
+            head,
+            end,
+            factor,
+            count: count_reg.map(|(li, _)| (li, trips / factor as u32)),
+        })
+    }
+
+    /// Loop-invariant code motion pass.
+    ///
+    /// In each loop found by [`single_block_loop`] that something falls into,
+    /// moves pure single-GPR definitions (see [`pure_def`]) to just before the
+    /// loop when their sources are never written in the loop, or only by an
+    /// instruction already moved. The destination must be written once in the
+    /// loop and not read before that write, so the first iteration sees the
+    /// same values; the body always runs at least once, so the value left
+    /// after the loop is the same too.
+    fn hoist_invariants(
+        &mut self,
+        instructions: &[DecodedInstruction],
+        labels: &HashSet<u32>,
+    ) -> Vec<DecodedInstruction> {
+        let branches_to = branch_counts(instructions);
+        let mut result: Vec<DecodedInstruction> = instructions.to_vec();
+
+        // Moving instructions within [head - 1, j] leaves every index outside it alone.
+        for j in 0..result.len() {
+            let Some(head) = single_block_loop(&result, labels, &branches_to, j) else {
+                continue;
+            };
+            // The preheader's last instruction must fall into the loop.
+            if head == 0 || result[head - 1].instruction.instruction_type == InstructionType::Branch
+            {
+                continue;
+            }
+            let body = &result[head..j];
+            if body
+                .iter()
+                .any(|i| i.instruction.instruction_type == InstructionType::Unknown)
+            {
+                continue;
+            }
+            let mut writes = [0usize; 32];
+            for inst in body {
+                for (reg, count) in writes.iter_mut().enumerate() {
+                    if sda::writes_gpr(inst.raw, reg as u8) {
+                        *count += 1;
+                    }
+                }
+            }
+
+            let mut hoisted: Vec<usize> = Vec::new();
+            let mut hoisted_regs: u32 = 0;
+            // Registers named by the instructions left in the loop so far.
+            let mut named: u32 = 0;
+            for (k, inst) in body.iter().enumerate() {
+                if let Some((dest, sources)) = pure_def(inst.raw) {
+                    let invariant = sources
+                        .into_iter()
+                        .flatten()
+                        .all(|r| writes[r as usize] == 0 || hoisted_regs & 1 << r != 0);
+                    if invariant && writes[dest as usize] == 1 && named & 1 << dest == 0 {
+                        hoisted.push(k);
+                        hoisted_regs |= 1 << dest;
+                        continue;
+                    }
+                }
+                named |= registers_named(inst);
+            }
+            if hoisted.is_empty() {
+                continue;
+            }
+
+            let preheader = result[head - 1].address;
+            let (moved, kept): (Vec<_>, Vec<_>) = result[head..j]
+                .iter()
+                .cloned()
+                .enumerate()
+                .partition(|(k, _)| hoisted.contains(k));
+            let reordered = moved
+                .into_iter()
+                .map(|(_, mut inst)| {
+                    inst.address = preheader;
+                    inst
+                })
+                .chain(kept.into_iter().map(|(_, inst)| inst));
+            result.splice(head..j, reordered);
+            self.stats.hoisted_instructions += hoisted.len();
         }
 
         result
@@ -202,3 +751,547 @@ impl Default for Optimizer {
         Self::new()
     }
 }
+
+/// Relative `b`/`bc` targets.
+pub(crate) fn branch_targets(instructions: &[DecodedInstruction]) -> HashSet<u32> {
+    instructions.iter().filter_map(relative_target).collect()
+}
+
+fn relative_target(inst: &DecodedInstruction) -> Option<u32> {
+    if inst.raw & 2 != 0 {
+        return None; // AA=1
+    }
+    let disp = match inst.raw >> 26 {
+        18 => ((inst.raw & 0x03FF_FFFC) as i32) << 6 >> 6,
+        16 => (inst.raw & 0xFFFC) as u16 as i16 as i32,
+        _ => return None,
+    };
+    Some(inst.address.wrapping_add(disp as u32))
+}
+
+fn branch_counts(instructions: &[DecodedInstruction]) -> HashMap<u32, usize> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for inst in instructions {
+        if let Some(target) = relative_target(inst) {
+            *counts.entry(target).or_default() += 1;
+        }
+    }
+    counts
+}
+
+/// The index of the first instruction of the loop closed by the `bc` at `j`,
+/// if the loop is one straight-line block: entered only by falling into it
+/// (its start is the target of no other branch), with no branch or branch
+/// target between its start and `j`. The start is the first instruction at
+/// or after the target address, as code generation splits blocks.
+fn single_block_loop(
+    instructions: &[DecodedInstruction],
+    labels: &HashSet<u32>,
+    branches_to: &HashMap<u32, usize>,
+    j: usize,
+) -> Option<usize> {
+    let branch = &instructions[j];
+    // bc, relative, no link, back to an earlier instruction.
+    if branch.raw >> 26 != 16 || branch.raw & 3 != 0 {
+        return None;
+    }
+    let target = relative_target(branch).filter(|&t| t < branch.address)?;
+    let head = instructions[..j].iter().position(|i| i.address >= target)?;
+    if branches_to.get(&target) != Some(&1)
+        || instructions[head + 1..=j]
+            .iter()
+            .any(|i| labels.contains(&i.address))
+        || instructions[head..j]
+            .iter()
+            .any(|i| i.instruction.instruction_type == InstructionType::Branch)
+    {
+        return None;
+    }
+    Some(head)
+}
+
+/// Bit r set for each GPR `inst` may read or write; see [`mentions`].
+fn registers_named(inst: &DecodedInstruction) -> u32 {
+    (0..32u8)
+        .filter(|&r| mentions(inst, r))
+        .fold(0, |mask, r| mask | 1 << r)
+}
+
+/// A loop [`Optimizer::unroll_loops`] repeats.
+struct Unroll {
+    /// First instruction of the body.
+    head: usize,
+    /// One past the last instruction repeated.
+    end: usize,
+    factor: usize,
+    /// For CTR loops: the `li` loading the count, and its new value.
+    count: Option<(usize, u32)>,
+}
+
+/// `mtctr rS`: rS.
+fn mtctr_source(raw: u32) -> Option<u8> {
+    (raw & 0xFC1F_FFFE == 0x7C09_03A6).then_some(((raw >> 21) & 31) as u8)
+}
+
+/// Whether `inst` may store to memory (or is unknown). Loads, compares and
+/// register arithmetic don't.
+fn may_write_memory(inst: &DecodedInstruction) -> bool {
+    let raw = inst.raw;
+    match raw >> 26 {
+        7 | 8 | 10..=15 | 20..=29 | 59 | 63 => false,
+        32..=35 | 40..=43 | 46 | 48..=51 => false,
+        31 => {
+            let xo = (raw >> 1) & 0x3FF;
+            // cmp, cmpl, mfcr, mtcrf, mfspr, mtspr, mftb and the indexed
+            // integer loads.
+            let no_store = matches!(
+                xo,
+                0 | 32
+                    | 19
+                    | 144
+                    | 339
+                    | 467
+                    | 371
+                    | 23
+                    | 55
+                    | 87
+                    | 119
+                    | 279
+                    | 311
+                    | 343
+                    | 375
+                    | 534
+                    | 790
+            );
+            !no_store && pure_def(raw & !1).is_none()
+        }
+        _ => true,
+    }
+}
+
+fn is_barrier(inst: &DecodedInstruction) -> bool {
+    matches!(
+        inst.instruction.instruction_type,
+        InstructionType::Branch | InstructionType::System
+    )
+}
+
+/// Whether `inst` may read or write `reg`, as far as its operands tell.
+fn mentions(inst: &DecodedInstruction, reg: u8) -> bool {
+    // lmw/stmw cover rD..r31; unknown instructions could touch anything.
+    matches!(inst.raw >> 26, 46 | 47)
+        || inst.instruction.instruction_type == InstructionType::Unknown
+        || inst.instruction.operands.contains(&Operand::Register(reg))
+}
+
+/// The value `li reg,N` gives `reg` just before `instructions[at]`, if that
+/// `li` is the last thing to mention `reg` and every instruction between
+/// runs whenever it does. Returns the `li`'s index and N.
+fn constant_before(
+    instructions: &[DecodedInstruction],
+    labels: &HashSet<u32>,
+    at: usize,
+    reg: u8,
+) -> Option<(usize, u32)> {
+    for k in (0..at).rev() {
+        let inst = &instructions[k];
+        if inst.raw >> 26 == 14 && (inst.raw >> 16) & 31 == 0 && (inst.raw >> 21) & 31 == reg as u32
+        {
+            return Some((k, inst.raw as u16 as i16 as i32 as u32));
+        }
+        if mentions(inst, reg) || labels.contains(&inst.address) || is_barrier(inst) {
+            return None;
+        }
+    }
+    None
+}
+
+/// Whether `reg` is overwritten before it is read on the straight-line path
+/// through `instructions`. At `blr` the volatile registers that carry no
+/// return value (r0, r5-r12) are dead as well, as the ABI lets callers assume.
+fn dead_after(instructions: &[DecodedInstruction], reg: u8) -> bool {
+    for inst in instructions {
+        if inst.raw == BLR {
+            return matches!(reg, 0 | 5..=12);
+        }
+        if let Some((dest, sources)) = pure_def(inst.raw) {
+            if !sources.contains(&Some(reg)) && dest == reg {
+                return true;
+            }
+        }
+        if mentions(inst, reg) || is_barrier(inst) {
+            return false;
+        }
+    }
+    false
+}
+
+/// Whether a loop body instruction can be repeated as is: no control flow,
+/// nothing reading or writing SPRs or CR bits the loop's test uses, and
+/// (for CTR loops) nothing touching the count register.
+fn replicable(inst: &DecodedInstruction, count_reg: Option<u8>) -> bool {
+    if matches!(
+        inst.instruction.instruction_type,
+        InstructionType::Branch | InstructionType::System | InstructionType::Unknown
+    ) || matches!(inst.raw >> 26, 19 | 46 | 47)
+    {
+        return false;
+    }
+    // mfcr, mtcrf, mfspr, mtspr
+    if inst.raw >> 26 == 31 && matches!((inst.raw >> 1) & 0x3FF, 19 | 144 | 339 | 467) {
+        return false;
+    }
+    match count_reg {
+        Some(reg) => !mentions(inst, reg),
+        None => true,
+    }
+}
+
+/// Times a `addi rC,rC,step ; cmp(l)wi rC,imm ; bc bo,bi` loop body runs,
+/// starting from rC = `start`, or `None` past `MAX_UNROLL_TRIPS`.
+fn counted_trips(start: u32, step: i16, imm: u16, signed: bool, bo: u32, bi: u32) -> Option<u32> {
+    let mut value = start;
+    for trips in 1..=MAX_UNROLL_TRIPS {
+        value = value.wrapping_add(step as i32 as u32);
+        let ordering = if signed {
+            (value as i32).cmp(&(imm as i16 as i32))
+        } else {
+            value.cmp(&(imm as u32))
+        };
+        let bit = match bi {
+            0 => ordering.is_lt(),
+            1 => ordering.is_gt(),
+            _ => ordering.is_eq(),
+        };
+        // BO bit 0b01000: branch when the CR bit is set.
+        if bit != (bo & 0b01000 != 0) {
+            return Some(trips);
+        }
+    }
+    None
+}
+
+fn fold_to_li(inst: &mut DecodedInstruction, rd: u8, value: i16) {
+    inst.raw = (inst.raw & 0xFFE0_0000) | value as u16 as u32;
+    inst.instruction.operands = [
+        Operand::Register(rd),
+        Operand::Register(0),
+        Operand::Immediate(value),
+    ]
+    .into_iter()
+    .collect();
+}
+
+/// Rewrite `inst` to `ori rd,rs,0`.
+fn copy_register(inst: &mut DecodedInstruction, rd: u8, rs: u8) {
+    let raw = 24 << 26 | (rs as u32) << 21 | (rd as u32) << 16;
+    if let Ok(ori) = Instruction::decode(raw, inst.address) {
+        *inst = ori;
+    }
+}
+
+fn fold_to_addi(inst: &mut DecodedInstruction, rd: u8, ra: u8, value: i16) {
+    let raw = 14 << 26 | (rd as u32) << 21 | (ra as u32) << 16 | value as u16 as u32;
+    if let Ok(addi) = Instruction::decode(raw, inst.address) {
+        *inst = addi;
+    }
+}
+
+/// For instructions whose only effect is writing one GPR: `(dest, sources)`.
+/// Record forms (Rc=1), overflow-enable forms and anything touching XER,
+/// memory or SPRs return `None`.
+pub(crate) fn pure_def(raw: u32) -> Option<(u8, [Option<u8>; 2])> {
+    let rd = ((raw >> 21) & 31) as u8;
+    let ra = ((raw >> 16) & 31) as u8;
+    let rb = ((raw >> 11) & 31) as u8;
+    let rc = raw & 1 != 0;
+    match raw >> 26 {
+        14 | 15 => Some((rd, [(ra != 0).then_some(ra), None])),
+        7 => Some((rd, [Some(ra), None])),
+        // ori, oris, xori, xoris: rA = rS op imm
+        24..=27 => Some((ra, [Some(rd), None])),
+        21 if !rc => Some((ra, [Some(rd), None])),
+        23 if !rc => Some((ra, [Some(rd), Some(rb)])),
+        31 if !rc => {
+            let xo = (raw >> 1) & 0x3FF;
+            match xo {
+                // add, subf, mullw (OE=0)
+                266 | 40 | 235 => Some((rd, [Some(ra), Some(rb)])),
+                104 => Some((rd, [Some(ra), None])), // neg
+                // and, or, xor, nor, andc, slw, srw, eqv, nand, orc: rA = rS op rB
+                28 | 444 | 316 | 124 | 60 | 24 | 536 | 284 | 476 | 412 => {
+                    Some((ra, [Some(rd), Some(rb)]))
+                }
+                // extsh, extsb, cntlzw
+                922 | 954 | 26 => Some((ra, [Some(rd), None])),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recompiler::decoder::Instruction;
+    use crate::recompiler::parser::{DolFile, Section};
+
+    fn decode(words: &[u32]) -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, 0x8000_3000 + i as u32 * 4).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn folds_addi_chains_into_li() {
+        // li r3,0x10 ; addi r4,r3,0x20 ; blr
+        let mut opt = Optimizer::with_level(OptLevel::Basic);
+        let out = opt.optimize(&decode(&[0x3860_0010, 0x3883_0020, 0x4E80_0020]));
+        assert_eq!(out[1].raw, 0x3880_0030); // li r4,0x30
+        assert_eq!(opt.stats().folded_constants, 1);
+    }
+
+    #[test]
+    fn folds_add_with_a_known_operand() {
+        // li r4,5 ; add r3,r3,r4 ; add r5,r4,r4 ; add r6,r0,r4 ; blr
+        let words = [
+            0x3880_0005,
+            0x7C63_2214,
+            0x7CA4_2214,
+            0x7CC0_2214,
+            0x4E80_0020,
+        ];
+        let mut opt = Optimizer::with_level(OptLevel::Basic);
+        let out = opt.optimize(&decode(&words));
+        assert_eq!(out[1].raw, 0x3863_0005); // addi r3,r3,5
+        assert_eq!(out[2].raw, 0x38A0_000A); // li r5,10
+        assert_eq!(out[3].raw, 0x7CC0_2214); // r0 can't be addi's base
+        assert_eq!(opt.stats().folded_constants, 2);
+
+        // The same add, with r4 known on entry instead.
+        let out = opt.optimize_with_constants(&decode(&words[1..]), &[(4, 7)]);
+        assert_eq!(out[0].raw, 0x3863_0007);
+    }
+
+    fn unrolling_only() -> Optimizer {
+        let mut opt = Optimizer::with_level(OptLevel::None);
+        opt.set_pass("loop_unrolling", true);
+        opt
+    }
+
+    fn raws(instructions: &[DecodedInstruction]) -> Vec<u32> {
+        instructions.iter().map(|i| i.raw).collect()
+    }
+
+    #[test]
+    fn bdnz_loops_with_a_constant_count_are_unrolled() {
+        // li r0,4 ; mtctr r0 ; L: addi r3,r3,1 ; addi r4,r4,2 ; bdnz L ; blr
+        let (add3, add4, bdnz, blr) = (0x3863_0001, 0x3884_0002, 0x4200_FFF8, 0x4E80_0020);
+        let words = [0x3800_0004, 0x7C09_03A6, add3, add4, bdnz, blr];
+        let mut opt = unrolling_only();
+        let out = opt.optimize(&decode(&words));
+        let body = [add3, add4].repeat(4);
+        let expected = [&[0x3800_0001, 0x7C09_03A6][..], &body, &[bdnz, blr]].concat();
+        assert_eq!(raws(&out), expected); // li r0,1: one pass of four copies
+        assert!(out[2..10].iter().all(|i| i.address < out[10].address));
+        assert_eq!(opt.stats().unrolled_loops, 1);
+
+        opt.set_unroll_factor(2);
+        let out = opt.optimize(&decode(&words));
+        assert_eq!(out[0].raw, 0x3800_0002);
+        assert_eq!(out.len(), words.len() + 2);
+    }
+
+    #[test]
+    fn counted_register_loops_are_unrolled_around_one_compare() {
+        // li r5,0 ; L: stbx r3,r4,r5 ; addi r5,r5,1 ; cmpwi r5,8 ; blt L ; blr
+        let (store, step) = (0x7C64_29AE, 0x38A5_0001);
+        let words = [
+            0x38A0_0000,
+            store,
+            step,
+            0x2C05_0008,
+            0x4180_FFF4,
+            0x4E80_0020,
+        ];
+        let mut opt = unrolling_only();
+        let out = opt.optimize(&decode(&words));
+        let body = [store, step].repeat(4);
+        let expected = [&[0x38A0_0000][..], &body, &words[3..]].concat();
+        assert_eq!(raws(&out), expected);
+    }
+
+    #[test]
+    fn loops_without_a_known_count_are_left_alone() {
+        // mtctr r3 ; L: addi r3,r3,1 ; addi r4,r4,2 ; bdnz L ; blr
+        let ctr = [
+            0x7C69_03A6,
+            0x3863_0001,
+            0x3884_0002,
+            0x4200_FFF8,
+            0x4E80_0020,
+        ];
+        // mr r5,r7 ; L: stbx r3,r4,r5 ; addi r5,r5,1 ; cmpwi r5,8 ; blt L ; blr
+        let counter = [
+            0x7CE5_3B78,
+            0x7C64_29AE,
+            0x38A5_0001,
+            0x2C05_0008,
+            0x4180_FFF4,
+            0x4E80_0020,
+        ];
+        // li r3,4 ; mtctr r3 ; L: ... ; blr returns r3, so its count can't change.
+        let live = [
+            0x3860_0004,
+            0x7C69_03A6,
+            0x3884_0002,
+            0x4200_FFFC,
+            0x4E80_0020,
+        ];
+        let mut opt = unrolling_only();
+        for words in [&ctr[..], &counter, &live] {
+            assert_eq!(raws(&opt.optimize(&decode(words))), words);
+        }
+        assert_eq!(opt.stats().unrolled_loops, 0);
+    }
+
+    #[test]
+    fn invariant_computations_move_out_of_loops() {
+        // li r0,4 ; mtctr r0 ; L: addi r5,r6,16 ; addi r3,r3,1 ; add r4,r4,r5 ; bdnz L ; blr
+        let (invariant, variant) = (0x38A6_0010, 0x3863_0001);
+        let words = [
+            0x3800_0004,
+            0x7C09_03A6,
+            invariant,
+            variant,
+            0x7C84_2A14,
+            0x4200_FFF4,
+            0x4E80_0020,
+        ];
+        let mut opt = Optimizer::with_level(OptLevel::None);
+        opt.set_pass("loop_invariant_motion", true);
+        let out = opt.optimize(&decode(&words));
+        assert_eq!(raws(&out), words);
+        // `addi r5,r6,16` now sits with `mtctr`, ahead of the loop's block.
+        assert_eq!(out[2].address, 0x8000_3004);
+        let looped: Vec<u32> = out
+            .iter()
+            .filter(|i| i.address >= 0x8000_3008)
+            .map(|i| i.raw)
+            .collect();
+        assert_eq!(looped, vec![variant, 0x7C84_2A14, 0x4200_FFF4, 0x4E80_0020]);
+        assert_eq!(opt.stats().hoisted_instructions, 1);
+
+        // Read before it is written, r5 carries over from the last iteration.
+        let words = [
+            words[0],
+            words[1],
+            0x7C84_2A14,
+            invariant,
+            variant,
+            0x4200_FFF4,
+        ];
+        let out = opt.optimize(&decode(&words));
+        assert!(out.iter().zip(decode(&words)).all(|(a, b)| a == &b));
+        assert_eq!(opt.stats().hoisted_instructions, 1);
+    }
+
+    fn load_elimination_only() -> Optimizer {
+        let mut opt = Optimizer::with_level(OptLevel::None);
+        opt.set_pass("redundant_load_elimination", true);
+        opt
+    }
+
+    #[test]
+    fn loads_after_non_aliasing_stores_become_copies() {
+        let words = [
+            0x806D_9000, // lwz r3,-0x7000(r13)
+            0x90A1_0008, // stw r5,8(r1)        stack slot
+            0x90CD_9004, // stw r6,-0x6FFC(r13) the next global
+            0x808D_9000, // lwz r4,-0x7000(r13)
+            0x4E80_0020, // blr
+        ];
+        let mut opt = load_elimination_only();
+        let out = opt.optimize(&decode(&words));
+        assert_eq!(out[3].raw, 0x6064_0000); // ori r4,r3,0
+        assert_eq!(opt.stats().eliminated_loads, 1);
+
+        // A global in .data against a stack slot needs the DOL's sections.
+        let words = [
+            0x3CE0_8020, // lis r7,0x8020
+            0x8067_0010, // lwz r3,0x10(r7)
+            0x90A1_0008, // stw r5,8(r1)
+            0x8087_0010, // lwz r4,0x10(r7)
+            0x4E80_0020, // blr
+        ];
+        let out = load_elimination_only().optimize(&decode(&words));
+        assert_eq!(out[3].raw, 0x8087_0010, "unknown address, kept");
+        let dol = DolFile {
+            text_sections: vec![],
+            data_sections: vec![Section {
+                offset: 0x100,
+                address: 0x8020_0000,
+                size: 0x1000,
+                data: vec![],
+                executable: false,
+            }],
+            bss_address: 0,
+            bss_size: 0,
+            entry_point: 0x8000_3000,
+            path: "test.dol".to_string(),
+        };
+        let mut opt = load_elimination_only();
+        opt.set_memory_map(MemoryMap::from_dol(&dol, 0x8040_0000, 0x8040_0000));
+        let out = opt.optimize(&decode(&words));
+        assert_eq!(out[3].raw, 0x6064_0000);
+    }
+
+    #[test]
+    fn stores_that_may_alias_keep_the_load() {
+        let load = 0x806D_9000; // lwz r3,-0x7000(r13)
+        let reload = 0x808D_9000; // lwz r4,-0x7000(r13)
+        for store in [
+            0xB0CD_9002, // sth r6,-0x6FFE(r13): the loaded word's low half
+            0x90A6_0000, // stw r5,0(r6): unknown base
+        ] {
+            let mut opt = load_elimination_only();
+            let out = opt.optimize(&decode(&[load, store, reload, 0x4E80_0020]));
+            assert_eq!(out[2].raw, reload, "{store:08X}");
+            assert_eq!(opt.stats().eliminated_loads, 0);
+        }
+        // Nor across a write to the register holding the value.
+        let out = load_elimination_only().optimize(&decode(&[
+            load,
+            0x3863_0001, // addi r3,r3,1
+            reload,
+            0x4E80_0020,
+        ]));
+        assert_eq!(out[2].raw, reload);
+    }
+
+    #[test]
+    fn dead_stores_to_registers_are_removed_but_not_across_branches() {
+        // li r3,1 (dead) ; li r3,2 ; beq +8 ; li r4,5 ; li r4,6 (branch target) ; blr
+        let words = [
+            0x3860_0001,
+            0x3860_0002,
+            0x4182_0008,
+            0x3880_0005,
+            0x3880_0006,
+            0x4E80_0020,
+        ];
+        let mut opt = Optimizer::new();
+        let out = opt.optimize(&decode(&words));
+        let kept: Vec<u32> = out.iter().map(|i| i.raw).collect();
+        // `li r4,5` is dead on the fall-through path too, but `li r4,6` is a
+        // branch target and stays.
+        assert_eq!(
+            kept,
+            vec![0x3860_0002, 0x4182_0008, 0x3880_0006, 0x4E80_0020]
+        );
+        assert_eq!(opt.stats().eliminated_instructions, 2);
+    }
+}
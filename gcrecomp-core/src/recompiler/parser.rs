@@ -10,12 +10,16 @@
 //! - **BSS section**: Uninitialized data section (address and size)
 //! - **Entry point**: Program entry point address
 //!
+//! REL modules loaded at runtime are handled by [`rel`].
+//!
 //! # Memory Optimizations
 //! - Uses const generics for fixed-size arrays (text/data section arrays)
 //! - Pre-allocates vectors with known capacity
 //! - Efficient byte reading with explicit buffer management
 
-use anyhow::{Context, Result};
+pub mod rel;
+
+use crate::recompiler::error::{RecompileError, Result};
 use std::io::{Cursor, Read};
 
 /// DOL file structure.
@@ -83,11 +87,11 @@ impl DolFile {
     pub fn parse(data: &[u8], path: &str) -> Result<Self> {
         const MIN_DOL_SIZE: usize = 0x100usize;
         if data.len() < MIN_DOL_SIZE {
-            anyhow::bail!(
+            return Err(RecompileError::ParseError(format!(
                 "DOL file too small: {} bytes (minimum {} bytes)",
                 data.len(),
                 MIN_DOL_SIZE
-            );
+            )));
         }
 
         let mut cursor: Cursor<&[u8]> = Cursor::new(data);
@@ -149,12 +153,10 @@ impl DolFile {
                 let size: usize = text_sizes[i] as usize;
 
                 if offset.wrapping_add(size) > data.len() {
-                    anyhow::bail!(
+                    return Err(RecompileError::ParseError(format!(
                         "Text section {} extends beyond file: offset {}, size {}",
-                        i,
-                        offset,
-                        size
-                    );
+                        i, offset, size
+                    )));
                 }
 
                 let section_data: Vec<u8> = data[offset..offset.wrapping_add(size)].to_vec();
@@ -176,12 +178,10 @@ impl DolFile {
                 let size: usize = data_sizes[i] as usize;
 
                 if offset.wrapping_add(size) > data.len() {
-                    anyhow::bail!(
+                    return Err(RecompileError::ParseError(format!(
                         "Data section {} extends beyond file: offset {}, size {}",
-                        i,
-                        offset,
-                        size
-                    );
+                        i, offset, size
+                    )));
                 }
 
                 let section_data: Vec<u8> = data[offset..offset.wrapping_add(size)].to_vec();
@@ -235,8 +235,8 @@ impl DolFile {
 fn read_u32_be(cursor: &mut Cursor<&[u8]>) -> Result<u32> {
     const U32_SIZE: usize = 4usize;
     let mut buf: [u8; U32_SIZE] = [0u8; U32_SIZE];
-    cursor
-        .read_exact(&mut buf)
-        .context("Failed to read u32 from DOL file")?;
+    cursor.read_exact(&mut buf).map_err(|e| {
+        RecompileError::ParseError(format!("Failed to read u32 from DOL file: {}", e))
+    })?;
     Ok(u32::from_be_bytes(buf))
 }
@@ -0,0 +1,410 @@
+//! REL (Relocatable Module) Parser
+//!
+//! Games load much of their code at runtime from REL modules (`OSLink`). A
+//! module carries its own section table plus, per imported module, a list of
+//! relocations to patch once it is placed in memory.
+//!
+//! # REL File Format
+//! - **Header** (0x40 bytes in version 1, 0x48 in 2, 0x4C in 3): module id,
+//!   section table offset/count, relocation and import table offsets, BSS
+//!   size, and prolog/epilog/unresolved entry points as (section, offset).
+//! - **Section table**: `(offset, size)` per section; bit 0 of the offset marks
+//!   executable sections, an offset of 0 with a size is BSS.
+//! - **Import table**: `(module id, relocation list offset)` per module this
+//!   one references; module 0 is the main DOL.
+//! - **Relocations**: 8-byte entries `(delta u16, type u8, section u8,
+//!   addend u32)`, walked with a cursor: `R_DOLPHIN_SECTION` selects the
+//!   section to patch, `delta` advances within it.
+//!
+//! Against the DOL, the addend is the absolute target address; against the
+//! module itself, it is an offset into the target section. [`RelFile::link`]
+//! lays the sections out at a load base the way `OSLink` does (in place, at
+//! `base + file offset`), applies both kinds, and [`RelFile::to_dol`] hands the
+//! result to the pipeline in the same shape as a DOL.
+
+use super::{DolFile, Section};
+use crate::recompiler::error::{RecompileError, Result};
+
+/// Module id of the main DOL in import tables.
+pub const MAIN_MODULE: u32 = 0;
+
+/// `R_DOLPHIN_*` control entries in the relocation stream.
+const R_DOLPHIN_NOP: u8 = 201;
+const R_DOLPHIN_SECTION: u8 = 202;
+const R_DOLPHIN_END: u8 = 203;
+
+/// PowerPC ELF relocation types RELs use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    Addr32,
+    Addr24,
+    Addr16,
+    Addr16Lo,
+    Addr16Hi,
+    Addr16Ha,
+    Addr14,
+    Rel24,
+    Rel14,
+    Rel32,
+}
+
+impl RelocationKind {
+    fn from_u8(value: u8) -> Option<Self> {
+        use RelocationKind::*;
+        Some(match value {
+            1 => Addr32,
+            2 => Addr24,
+            3 => Addr16,
+            4 => Addr16Lo,
+            5 => Addr16Hi,
+            6 => Addr16Ha,
+            7..=9 => Addr14,
+            10 => Rel24,
+            11..=13 => Rel14,
+            26 => Rel32,
+            _ => return None,
+        })
+    }
+}
+
+/// One section of the module.
+#[derive(Debug, Clone)]
+pub struct RelSection {
+    /// File offset (0 for BSS and empty sections).
+    pub offset: u32,
+    pub size: u32,
+    pub executable: bool,
+    /// Contents; empty for BSS.
+    pub data: Vec<u8>,
+    /// Load address, set by [`RelFile::link`].
+    pub address: u32,
+}
+
+impl RelSection {
+    pub fn is_bss(&self) -> bool {
+        self.offset == 0 && self.size != 0
+    }
+}
+
+/// One relocation, with its position resolved from the delta stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Relocation {
+    /// Section being patched.
+    pub section: u8,
+    /// Byte offset within that section.
+    pub offset: u32,
+    pub kind: RelocationKind,
+    /// Section of the target (for self-relocations).
+    pub target_section: u8,
+    pub addend: u32,
+}
+
+/// Relocations against one imported module.
+#[derive(Debug, Clone)]
+pub struct RelImport {
+    pub module_id: u32,
+    pub relocations: Vec<Relocation>,
+}
+
+/// What [`RelFile::link`] patched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkReport {
+    pub applied: usize,
+    /// Relocations against modules other than the DOL and this one.
+    pub unresolved: usize,
+}
+
+/// A parsed REL module.
+#[derive(Debug, Clone)]
+pub struct RelFile {
+    pub id: u32,
+    pub version: u32,
+    pub sections: Vec<RelSection>,
+    pub bss_size: u32,
+    pub imports: Vec<RelImport>,
+    /// Entry points as (section, offset); section 0 means none.
+    pub prolog: (u8, u32),
+    pub epilog: (u8, u32),
+    pub unresolved: (u8, u32),
+    /// File path (for reference)
+    pub path: String,
+}
+
+impl RelFile {
+    /// Parse a REL file from byte data.
+    pub fn parse(data: &[u8], path: &str) -> Result<Self> {
+        const HEADER_SIZE: usize = 0x40;
+        if data.len() < HEADER_SIZE {
+            return Err(parse_error(format!(
+                "REL file too small: {} bytes (minimum {} bytes)",
+                data.len(),
+                HEADER_SIZE
+            )));
+        }
+
+        let id = be32(data, 0x00)?;
+        let num_sections = be32(data, 0x0C)?;
+        let section_table = be32(data, 0x10)?;
+        let version = be32(data, 0x1C)?;
+        let bss_size = be32(data, 0x20)?;
+        let imp_offset = be32(data, 0x28)?;
+        let imp_size = be32(data, 0x2C)?;
+        let entry_point = |section: usize, offset: usize| -> Result<(u8, u32)> {
+            Ok((data[section], be32(data, offset)?))
+        };
+        let prolog = entry_point(0x30, 0x34)?;
+        let epilog = entry_point(0x31, 0x38)?;
+        let unresolved = entry_point(0x32, 0x3C)?;
+
+        let mut sections = Vec::with_capacity(num_sections as usize);
+        for i in 0..num_sections {
+            let entry = section_table as usize + i as usize * 8;
+            let raw_offset = be32(data, entry)?;
+            let size = be32(data, entry + 4)?;
+            let offset = raw_offset & !1;
+            let contents = if offset != 0 {
+                let start = offset as usize;
+                let end = start + size as usize;
+                data.get(start..end)
+                    .ok_or_else(|| {
+                        parse_error(format!(
+                            "REL section {} extends beyond file: offset {}, size {}",
+                            i, offset, size
+                        ))
+                    })?
+                    .to_vec()
+            } else {
+                Vec::new()
+            };
+            sections.push(RelSection {
+                offset,
+                size,
+                executable: raw_offset & 1 != 0,
+                data: contents,
+                address: 0,
+            });
+        }
+
+        let mut imports = Vec::new();
+        for i in 0..imp_size / 8 {
+            let entry = imp_offset as usize + i as usize * 8;
+            let module_id = be32(data, entry)?;
+            let list = be32(data, entry + 4)?;
+            imports.push(RelImport {
+                module_id,
+                relocations: parse_relocations(data, list as usize)?,
+            });
+        }
+
+        Ok(Self {
+            id,
+            version,
+            sections,
+            bss_size,
+            imports,
+            prolog,
+            epilog,
+            unresolved,
+            path: path.to_string(),
+        })
+    }
+
+    /// Place the module at `base` (sections in place at `base + file offset`,
+    /// BSS at `bss_base`) and apply its relocations against the DOL and
+    /// itself.
+    pub fn link(&mut self, base: u32, bss_base: u32) -> Result<LinkReport> {
+        for section in &mut self.sections {
+            section.address = if section.is_bss() {
+                bss_base
+            } else if section.offset != 0 {
+                base.wrapping_add(section.offset)
+            } else {
+                0
+            };
+        }
+
+        let mut report = LinkReport::default();
+        let imports = std::mem::take(&mut self.imports);
+        let result = self.apply_imports(&imports, &mut report);
+        self.imports = imports;
+        result.map(|()| report)
+    }
+
+    fn apply_imports(&mut self, imports: &[RelImport], report: &mut LinkReport) -> Result<()> {
+        for import in imports {
+            let external = import.module_id == MAIN_MODULE;
+            if !external && import.module_id != self.id {
+                report.unresolved += import.relocations.len();
+                continue;
+            }
+            for reloc in &import.relocations {
+                let target = if external {
+                    reloc.addend
+                } else {
+                    self.section_address(reloc.target_section)?
+                        .wrapping_add(reloc.addend)
+                };
+                self.patch(reloc, target)?;
+                report.applied += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Address of an entry point, once linked.
+    pub fn entry_address(&self, (section, offset): (u8, u32)) -> Option<u32> {
+        if section == 0 {
+            return None;
+        }
+        let section = self.sections.get(section as usize)?;
+        Some(section.address.wrapping_add(offset))
+    }
+
+    /// The linked module as the pipeline's DOL view; the entry point is the
+    /// prolog.
+    pub fn to_dol(&self) -> DolFile {
+        let mut dol = DolFile {
+            text_sections: Vec::new(),
+            data_sections: Vec::new(),
+            bss_address: 0,
+            bss_size: 0,
+            entry_point: self.entry_address(self.prolog).unwrap_or(0),
+            path: self.path.clone(),
+        };
+        for section in &self.sections {
+            if section.is_bss() {
+                dol.bss_address = section.address;
+                dol.bss_size = section.size;
+                continue;
+            }
+            if section.data.is_empty() {
+                continue;
+            }
+            let view = Section {
+                offset: section.offset,
+                address: section.address,
+                size: section.size,
+                data: section.data.clone(),
+                executable: section.executable,
+            };
+            if section.executable {
+                dol.text_sections.push(view);
+            } else {
+                dol.data_sections.push(view);
+            }
+        }
+        dol
+    }
+
+    fn section_address(&self, index: u8) -> Result<u32> {
+        self.sections
+            .get(index as usize)
+            .map(|s| s.address)
+            .ok_or_else(|| parse_error(format!("REL relocation targets missing section {index}")))
+    }
+
+    fn patch(&mut self, reloc: &Relocation, target: u32) -> Result<()> {
+        let section = self
+            .sections
+            .get_mut(reloc.section as usize)
+            .filter(|s| !s.data.is_empty())
+            .ok_or_else(|| {
+                parse_error(format!(
+                    "REL relocation patches section {} without data",
+                    reloc.section
+                ))
+            })?;
+        let place = section.address.wrapping_add(reloc.offset);
+        let at = reloc.offset as usize;
+        let bytes = section.data.get_mut(at..).unwrap_or_default();
+
+        let half = |value: u32| -> [u8; 2] { (value as u16).to_be_bytes() };
+        let word = |bytes: &[u8]| -> Option<u32> {
+            Some(u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?))
+        };
+        let out_of_range = || {
+            parse_error(format!(
+                "REL relocation at section {} offset 0x{:X} is out of range",
+                reloc.section, reloc.offset
+            ))
+        };
+        let merge = |bytes: &mut [u8], mask: u32, value: u32| -> Result<()> {
+            let old = word(bytes).ok_or_else(out_of_range)?;
+            bytes[..4].copy_from_slice(&((old & !mask) | (value & mask)).to_be_bytes());
+            Ok(())
+        };
+
+        use RelocationKind::*;
+        match reloc.kind {
+            Addr32 => merge(bytes, 0xFFFF_FFFF, target)?,
+            Rel32 => merge(bytes, 0xFFFF_FFFF, target.wrapping_sub(place))?,
+            Addr24 => merge(bytes, 0x03FF_FFFC, target)?,
+            Rel24 => merge(bytes, 0x03FF_FFFC, target.wrapping_sub(place))?,
+            Addr14 => merge(bytes, 0x0000_FFFC, target)?,
+            Rel14 => merge(bytes, 0x0000_FFFC, target.wrapping_sub(place))?,
+            Addr16 | Addr16Lo | Addr16Hi | Addr16Ha => {
+                let value = match reloc.kind {
+                    Addr16Hi => target >> 16,
+                    Addr16Ha => target.wrapping_add(0x8000) >> 16,
+                    _ => target,
+                };
+                bytes
+                    .get_mut(..2)
+                    .ok_or_else(out_of_range)?
+                    .copy_from_slice(&half(value));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Walk one module's relocation stream starting at `offset`.
+fn parse_relocations(data: &[u8], mut offset: usize) -> Result<Vec<Relocation>> {
+    let mut relocations = Vec::new();
+    let mut section = 0u8;
+    let mut position = 0u32;
+    loop {
+        let entry = data.get(offset..offset + 8).ok_or_else(|| {
+            parse_error(format!(
+                "REL relocation list runs past end of file at 0x{offset:X}"
+            ))
+        })?;
+        let delta = u16::from_be_bytes([entry[0], entry[1]]) as u32;
+        let kind = entry[2];
+        let target_section = entry[3];
+        let addend = u32::from_be_bytes([entry[4], entry[5], entry[6], entry[7]]);
+        offset += 8;
+        position = position.wrapping_add(delta);
+
+        match kind {
+            R_DOLPHIN_END => break,
+            R_DOLPHIN_SECTION => {
+                section = target_section;
+                position = 0;
+            }
+            R_DOLPHIN_NOP | 0 => {}
+            _ => match RelocationKind::from_u8(kind) {
+                Some(kind) => relocations.push(Relocation {
+                    section,
+                    offset: position,
+                    kind,
+                    target_section,
+                    addend,
+                }),
+                None => log::warn!("Skipping unsupported REL relocation type {kind}"),
+            },
+        }
+    }
+    Ok(relocations)
+}
+
+fn be32(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| parse_error(format!("REL read past end of file at 0x{offset:X}")))
+}
+
+#[cold]
+fn parse_error(message: String) -> RecompileError {
+    RecompileError::ParseError(message)
+}
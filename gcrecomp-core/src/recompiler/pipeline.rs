@@ -9,7 +9,8 @@
 //! 3. **Control Flow Analysis**: Build control flow graph (CFG)
 //! 4. **Data Flow Analysis**: Build def-use chains and perform live variable analysis
 //! 5. **Type Inference**: Recover type information for registers and variables
-//! 6. **Code Generation**: Generate Rust code from analyzed instructions
+//! 6. **Code Generation**: Generate Rust code from analyzed instructions, after
+//!    picking leaves to inline and calls to specialize for constant arguments
 //! 7. **Validation**: Validate generated Rust code
 //! 8. **Output**: Write generated code to file
 //!
@@ -21,12 +22,29 @@
 
 use crate::recompiler::analysis::control_flow::ControlFlowAnalyzer;
 use crate::recompiler::analysis::data_flow::DataFlowAnalyzer;
+use crate::recompiler::analysis::pointer::MemoryMap;
+use crate::recompiler::analysis::similarity::{SimilarityIndex, DEFAULT_MIN_SIMILARITY};
+use crate::recompiler::cache::{CacheKey, CacheKeyBuilder, FunctionCache};
+use crate::recompiler::codegen::inline::{self, InlineCandidates, INLINE_BUDGET};
+use crate::recompiler::codegen::sda::SdaBases;
 use crate::recompiler::codegen::CodeGenerator;
+use crate::recompiler::coverage::InstructionCoverage;
 use crate::recompiler::decoder::DecodedInstruction;
-use crate::recompiler::ghidra::GhidraAnalysis;
+use crate::recompiler::enrich::FunctionFacts;
+use crate::recompiler::error::{RecompileError, Result};
+use crate::recompiler::fidb::{FidDatabase, DEFAULT_MIN_CONFIDENCE};
+use crate::recompiler::ghidra::{FunctionInfo, GhidraAnalysis};
+use crate::recompiler::optimizer::{OptLevel, Optimizer, OptimizerStats};
 use crate::recompiler::parser::DolFile;
+use crate::recompiler::profile::HotProfile;
+use crate::recompiler::report::ReportWriter;
+use crate::recompiler::specialize::{self, Specialization};
+use crate::recompiler::symbols::SymbolMap;
 use crate::recompiler::validator::CodeValidator;
-use anyhow::Result;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Recompilation pipeline orchestrator.
 ///
@@ -43,6 +61,7 @@ pub struct PipelineContext {
     pub cfg: Option<crate::recompiler::analysis::control_flow::ControlFlowGraph>,
     pub rust_code: Option<String>,
     pub stats: PipelineStats,
+    pub coverage: InstructionCoverage,
 }
 
 /// Statistics collected during pipeline execution.
@@ -52,6 +71,154 @@ pub struct PipelineStats {
     pub successful_functions: usize,
     pub failed_functions: usize,
     pub total_instructions: usize,
+    /// Functions that run under the runtime interpreter (counted as successful).
+    #[serde(default)]
+    pub interpreted_functions: usize,
+    /// Instructions handed to the code generator, after optimization.
+    #[serde(default)]
+    pub generated_instructions: usize,
+    /// Functions left as stubs because `RecompileOptions::only` didn't pick them.
+    #[serde(default)]
+    pub skipped_functions: usize,
+    /// Functions read back from `RecompileOptions::cache_dir` unchanged.
+    #[serde(default)]
+    pub cache_hits: usize,
+    /// Functions generated because the cache had nothing for them.
+    #[serde(default)]
+    pub cache_misses: usize,
+}
+
+/// How [`RecompilationPipeline::generate_function_code`] produced a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Translation {
+    /// Fully translated to Rust.
+    Recompiled,
+    /// Delegates to `runtime::interpreter` at runtime.
+    Interpreted,
+    /// Translated, but some instructions were left as `// untranslated` comments.
+    Partial,
+}
+
+/// One function's code generation, before it's added to the output.
+struct GeneratedFunction {
+    instructions: Vec<DecodedInstruction>,
+    /// Instructions left after optimization.
+    generated_instructions: usize,
+    stats: OptimizerStats,
+    /// `None` when the function has no instructions.
+    code: Option<Result<(String, Translation)>>,
+    /// Where `code` goes in the cache, and whether it came from there.
+    cached: Option<(CacheKey, bool)>,
+}
+
+/// A code generation progress step, reported where it's logged: function
+/// `done` of `total` is being generated.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct FunctionProgress {
+    pub done: usize,
+    pub total: usize,
+    pub function: String,
+}
+
+/// Receives [`FunctionProgress`] during [`RecompilationPipeline::recompile_with_options`],
+/// from whichever codegen thread gets there.
+#[derive(Clone)]
+pub struct ProgressSink(Arc<dyn Fn(FunctionProgress) + Send + Sync>);
+
+impl ProgressSink {
+    pub fn new(report: impl Fn(FunctionProgress) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(report))
+    }
+
+    pub fn report(&self, progress: FunctionProgress) {
+        (self.0)(progress)
+    }
+}
+
+impl std::fmt::Debug for ProgressSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressSink")
+    }
+}
+
+/// Codegen threads when [`RecompileOptions::jobs`] isn't set: one per core.
+pub fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Options for [`RecompilationPipeline::recompile_with_options`].
+#[derive(Debug, Clone)]
+pub struct RecompileOptions {
+    /// Name functions from this map where it has them.
+    pub symbols: Option<SymbolMap>,
+    /// Name library functions by their code. A symbol map, when given, wins.
+    pub fidb: Option<FidDatabase>,
+    /// Suggest names for functions still unnamed after `fidb` by structural
+    /// similarity to these references.
+    pub similarity: Option<SimilarityIndex>,
+    /// Let the code generator fold constants and skip redundant work.
+    pub optimize: bool,
+    /// Instruction-level passes run on each function before code generation.
+    pub optimizer: Optimizer,
+    /// Runtime profile: when set, hot functions are optimized aggressively and
+    /// the rest lightly, overriding `optimizer`'s pass selection.
+    pub profile: Option<HotProfile>,
+    /// r13/r2 the runtime boots with. When set, small-data-area addressing
+    /// is resolved to absolute global addresses at compile time.
+    pub sda_bases: Option<SdaBases>,
+    /// Read and write whole-word fields of the structs each function is seen
+    /// to dereference through generated accessors, not raw memory calls.
+    /// Field-name comments are emitted either way.
+    pub struct_accessors: bool,
+    /// Write a Markdown report (disassembly beside generated Rust, per
+    /// function) into this directory.
+    pub report_dir: Option<PathBuf>,
+    /// Threads generating functions at once (default: [`default_jobs`]). The
+    /// output is the same for any count.
+    pub jobs: Option<usize>,
+    /// Generate only the functions starting at these addresses and their
+    /// direct callees; everything else becomes a stub.
+    pub only: Option<Vec<u32>>,
+    /// Keep each function's generated Rust in this directory and reuse it
+    /// while the function and its codegen inputs are unchanged.
+    pub cache_dir: Option<PathBuf>,
+    /// Told about each progress step the pipeline logs.
+    pub progress: Option<ProgressSink>,
+}
+
+impl RecompileOptions {
+    /// The level the profile picks for the function at `address`, if any.
+    pub fn opt_level_for(&self, address: u32) -> Option<OptLevel> {
+        self.profile.as_ref().map(|p| p.level_for(address))
+    }
+}
+
+impl Default for RecompileOptions {
+    fn default() -> Self {
+        Self {
+            symbols: None,
+            fidb: None,
+            similarity: None,
+            optimize: true,
+            optimizer: Optimizer::with_level(OptLevel::None),
+            profile: None,
+            sda_bases: None,
+            struct_accessors: false,
+            report_dir: None,
+            jobs: None,
+            only: None,
+            cache_dir: None,
+            progress: None,
+        }
+    }
+}
+
+/// What a whole-DOL recompile produced.
+#[derive(Debug, Clone, Default)]
+pub struct RecompileSummary {
+    pub stats: PipelineStats,
+    pub coverage: InstructionCoverage,
+    pub optimizer: OptimizerStats,
 }
 
 impl PipelineContext {
@@ -91,6 +258,33 @@ impl RecompilationPipeline {
     /// ```
     #[inline(never)] // Large function - don't inline
     pub fn recompile(dol_file: &DolFile, output_path: &str) -> Result<()> {
+        Self::recompile_with_symbols(dol_file, output_path, None)
+    }
+
+    /// [`recompile`](Self::recompile), naming functions from a symbol map
+    /// (`.map`) where it has them. The discovered function list is written next
+    /// to the output as `symbols.map` either way.
+    #[inline(never)]
+    pub fn recompile_with_symbols(
+        dol_file: &DolFile,
+        output_path: &str,
+        symbols: Option<&SymbolMap>,
+    ) -> Result<()> {
+        let options = RecompileOptions {
+            symbols: symbols.cloned(),
+            ..Default::default()
+        };
+        Self::recompile_with_options(dol_file, output_path, &options).map(|_| ())
+    }
+
+    /// [`recompile`](Self::recompile) with explicit options, returning the
+    /// function counts and instruction coverage.
+    #[inline(never)]
+    pub fn recompile_with_options(
+        dol_file: &DolFile,
+        output_path: &str,
+        options: &RecompileOptions,
+    ) -> Result<RecompileSummary> {
         log::info!("Starting recompilation pipeline...");
 
         // Step 1: Decode instructions
@@ -101,7 +295,7 @@ impl RecompilationPipeline {
         // otherwise fall back to a naive scan of the decoded instructions so the
         // pipeline runs end-to-end with no external tool. ponytail: naive linear
         // sweep (split on `blr`), bounded; swap in Ghidra reachability for accuracy.
-        let ghidra_analysis: GhidraAnalysis = if std::env::var("GHIDRA_INSTALL_DIR").is_ok() {
+        let mut ghidra_analysis: GhidraAnalysis = if std::env::var("GHIDRA_INSTALL_DIR").is_ok() {
             log::info!("Step 2: Running Ghidra analysis (GHIDRA_INSTALL_DIR set)...");
             GhidraAnalysis::analyze(
                 &dol_file.path,
@@ -116,6 +310,56 @@ impl RecompilationPipeline {
             Self::naive_function_discovery(dol_file.entry_point, &instructions)
         };
 
+        if let Some(db) = &options.fidb {
+            let matches = db.apply(
+                &mut ghidra_analysis.functions,
+                &instructions,
+                DEFAULT_MIN_CONFIDENCE,
+            );
+            for m in &matches {
+                log::debug!(
+                    "FID: 0x{:08X} is {} ({:.0}%)",
+                    m.address,
+                    m.name,
+                    m.confidence * 100.0
+                );
+            }
+            log::info!(
+                "Function ID database: named {} of {} functions",
+                matches.len(),
+                ghidra_analysis.functions.len()
+            );
+        }
+
+        if let Some(index) = &options.similarity {
+            let suggestions = index.apply(
+                &mut ghidra_analysis.functions,
+                &instructions,
+                DEFAULT_MIN_SIMILARITY,
+            );
+            for s in &suggestions {
+                log::debug!(
+                    "Similarity: 0x{:08X} looks like {} ({:.2})",
+                    s.address,
+                    s.name,
+                    s.similarity
+                );
+            }
+            log::info!(
+                "Structural similarity: suggested names for {} functions",
+                suggestions.len()
+            );
+        }
+
+        if let Some(map) = &options.symbols {
+            let named = map.apply(&mut ghidra_analysis.functions);
+            log::info!(
+                "Symbol map: named {} of {} functions",
+                named,
+                ghidra_analysis.functions.len()
+            );
+        }
+
         // Step 2b: Enrich functions with derived facts and report coverage.
         let facts =
             crate::recompiler::enrich::enrich_functions(&ghidra_analysis.functions, &instructions);
@@ -130,9 +374,23 @@ impl RecompilationPipeline {
             report.total_instructions,
         );
 
+        let selected = match &options.only {
+            Some(addresses) => {
+                let selected = Self::select_functions(addresses, &facts)?;
+                log::info!(
+                    "Generating {} of {} functions (--only and their direct callees)",
+                    selected.len(),
+                    facts.len()
+                );
+                Some(selected)
+            }
+            None => None,
+        };
+
         // Step 3: Control flow analysis
         log::info!("Step 3: Building control flow graph...");
-        let cfg = ControlFlowAnalyzer::build_cfg(&instructions, 0u32)?;
+        let cfg = ControlFlowAnalyzer::build_cfg(&instructions, 0u32)
+            .map_err(|e| RecompileError::AnalysisError(e.to_string()))?;
 
         // Step 4: Data flow analysis
         log::info!("Step 4: Performing data flow analysis...");
@@ -145,7 +403,28 @@ impl RecompilationPipeline {
 
         // Step 6: Code generation
         log::info!("Step 6: Generating Rust code...");
-        let mut codegen: CodeGenerator = CodeGenerator::new();
+        let mut codegen: CodeGenerator = CodeGenerator::new()
+            .with_strict(true)
+            .with_optimizations(options.optimize)
+            .with_sda_bases(options.sda_bases)
+            .with_struct_accessors(options.struct_accessors);
+        let mut specializations = Vec::new();
+        let mut inline_candidates = InlineCandidates::new();
+        if options.optimize {
+            let candidates =
+                Self::analyze_inlining_candidates(&ghidra_analysis.functions, &instructions);
+            log::info!(
+                "Inlining {} leaf functions at their call sites",
+                candidates.len()
+            );
+            specializations = Self::analyze_specializations(
+                &ghidra_analysis.functions,
+                &instructions,
+                &candidates.keys().copied().collect(),
+            );
+            inline_candidates = candidates.clone();
+            codegen.set_inline_candidates(candidates);
+        }
 
         // Pre-allocate string buffer with estimated capacity
         // Estimate: ~1000 bytes per function on average
@@ -165,11 +444,86 @@ impl RecompilationPipeline {
             dol_file.entry_point
         ));
 
+        // Specialized copies go first; call sites only target the ones that
+        // generated.
+        let mut specialized_calls: HashMap<u32, String> = HashMap::new();
+        for spec in &specializations {
+            let Some(func) = ghidra_analysis
+                .functions
+                .iter()
+                .find(|f| f.address == spec.callee)
+            else {
+                continue;
+            };
+            let metadata = Self::function_metadata(func);
+            match codegen
+                .clone()
+                .generate_specialized_function(&metadata, spec.index, &spec.body)
+            {
+                Ok(code) => {
+                    rust_code.push_str(&format!(
+                        "// 0x{:08X} specialized for {}\n",
+                        spec.callee,
+                        spec.arguments
+                            .iter()
+                            .map(|(r, v)| format!("r{r} = 0x{v:X}"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                    rust_code.push_str(&code);
+                    rust_code.push('\n');
+                    let identifier = codegen.specialized_identifier(&metadata, spec.index);
+                    for &site in &spec.call_sites {
+                        specialized_calls.insert(site, identifier.clone());
+                    }
+                }
+                Err(e) => log::warn!(
+                    "Failed to specialize function {} at 0x{:08X}: {}",
+                    func.name,
+                    func.address,
+                    e
+                ),
+            }
+        }
+        if !specializations.is_empty() {
+            log::info!(
+                "Specialized {} call sites into {} copies",
+                specialized_calls.len(),
+                specializations.len()
+            );
+        }
+        codegen.set_specialized_calls(specialized_calls.clone());
+
         let total_functions: usize = ghidra_analysis.functions.len();
         let mut successful_functions: usize = 0usize;
         let mut failed_functions: usize = 0usize;
+        let mut skipped_functions: usize = 0usize;
+        let mut interpreted: Vec<u32> = Vec::new();
+        let mut coverage = InstructionCoverage::new();
+        let mut optimizer_stats = options.optimizer.stats();
+        let mut generated_instructions: usize = 0;
+        let mut report = match &options.report_dir {
+            Some(dir) => Some(ReportWriter::create(dir)?),
+            None => None,
+        };
+        let cache = match &options.cache_dir {
+            Some(dir) => Some(FunctionCache::open(dir)?),
+            None => None,
+        };
+        let mut cache_hits: usize = 0;
+        let mut cache_misses: usize = 0;
+        let call_sites: HashMap<u32, &[(u32, u32)]> = facts
+            .iter()
+            .map(|f| (f.address, f.call_sites.as_slice()))
+            .collect();
 
-        for (idx, func) in ghidra_analysis.functions.iter().enumerate() {
+        let skipped = |address: u32| selected.as_ref().is_some_and(|s| !s.contains(&address));
+        let sda = options.sda_bases.unwrap_or_default();
+        let mut function_optimizer = options.optimizer.clone();
+        function_optimizer.set_memory_map(MemoryMap::from_dol(dol_file, sda.sda, sda.sda2));
+        // Each function gets its own code generator and optimizer, so the
+        // output is the same whichever order (or thread) generates it in.
+        let generate = |idx: usize, func: &FunctionInfo| {
             // Progress reporting
             if idx % 10 == 0 || idx == total_functions - 1 {
                 log::info!(
@@ -179,60 +533,152 @@ impl RecompilationPipeline {
                     ((idx + 1) * 100) / total_functions.max(1),
                     func.name
                 );
+                if let Some(progress) = &options.progress {
+                    progress.report(FunctionProgress {
+                        done: idx + 1,
+                        total: total_functions,
+                        function: func.name.clone(),
+                    });
+                }
             }
-
             // Get instructions for this function using address-based mapping
-            let func_instructions: Vec<DecodedInstruction> =
-                Self::map_instructions_to_function(func, &instructions);
+            let instructions = Self::map_instructions_to_function(func, &instructions);
+            let mut optimizer = function_optimizer.clone();
+            optimizer.take_stats();
+            if let Some(level) = options.opt_level_for(func.address) {
+                optimizer.set_level(level);
+            }
+            let wanted = !skipped(func.address);
+            let optimized = if wanted {
+                optimizer.optimize(&instructions)
+            } else {
+                Vec::new()
+            };
+            let metadata = Self::function_metadata(func);
+            let mut cached = None;
+            let code = (wanted && !instructions.is_empty()).then(|| {
+                if let Some(cache) = &cache {
+                    // Everything the generator reads for this function.
+                    let mut key = CacheKeyBuilder::new();
+                    key.words(instructions.iter().map(|i| i.raw))
+                        .field(&format!("{metadata:?}"))
+                        .field(&format!("{:?} {optimizer:?}", options.optimize))
+                        .field(&format!("{:?}", options.sda_bases))
+                        .field(&format!("{:?}", options.struct_accessors));
+                    for &(site, target) in call_sites.get(&func.address).copied().unwrap_or(&[]) {
+                        if let Some(body) = inline_candidates.get(&target) {
+                            key.field(&format!("inline 0x{target:08X}"))
+                                .words(body.iter().map(|i| i.raw));
+                        }
+                        if let Some(identifier) = specialized_calls.get(&site) {
+                            key.field(&format!("0x{site:08X} calls {identifier}"));
+                        }
+                    }
+                    let key = key.finish();
+                    if let Some(hit) = cache.get(&key) {
+                        cached = Some((key, true));
+                        return Ok(hit);
+                    }
+                    cached = Some((key, false));
+                }
+                let mut codegen = codegen.clone();
+                Self::generate_function_code(&mut codegen, &metadata, &optimized)
+            });
+            GeneratedFunction {
+                instructions,
+                generated_instructions: optimized.len(),
+                stats: optimizer.take_stats(),
+                code,
+                cached,
+            }
+        };
+        let jobs = options.jobs.unwrap_or_else(default_jobs).max(1);
+        log::info!("Generating {total_functions} functions on {jobs} threads");
+        let generated: Vec<GeneratedFunction> = if jobs == 1 {
+            ghidra_analysis
+                .functions
+                .iter()
+                .enumerate()
+                .map(|(idx, func)| generate(idx, func))
+                .collect()
+        } else {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .map_err(|e| RecompileError::Io(format!("Failed to start codegen threads: {e}")))?
+                .install(|| {
+                    ghidra_analysis
+                        .functions
+                        .par_iter()
+                        .enumerate()
+                        .map(|(idx, func)| generate(idx, func))
+                        .collect()
+                })
+        };
 
-            if func_instructions.is_empty() {
+        // Collected in address order: the rest runs as if generated in turn.
+        for (func, generated) in ghidra_analysis.functions.iter().zip(generated) {
+            let func_instructions = generated.instructions;
+            optimizer_stats += generated.stats;
+            if skipped(func.address) {
+                skipped_functions += 1;
+                coverage.record_function(&func_instructions, None);
+                let stub = Self::stub_function(&codegen, func, "not selected by --only");
+                if let Some(report) = &mut report {
+                    report.write_function(
+                        &func.name,
+                        func.address,
+                        &func_instructions,
+                        &stub,
+                        None,
+                    )?;
+                }
+                rust_code.push_str(&stub);
+                continue;
+            }
+            let Some(code) = generated.code else {
                 log::warn!(
                     "Function {} at 0x{:08X} has no instructions, skipping",
                     func.name,
                     func.address
                 );
                 failed_functions += 1;
+                coverage.record_function(&func_instructions, None);
                 continue;
-            }
-
-            // Generate function code
-            let func_metadata = crate::recompiler::analysis::FunctionMetadata {
-                address: func.address,
-                name: func.name.clone(),
-                size: func.size,
-                calling_convention: func.calling_convention.clone(),
-                parameters: func
-                    .parameters
-                    .iter()
-                    .map(|p| crate::recompiler::analysis::ParameterInfo {
-                        name: p.name.clone(),
-                        type_info: crate::recompiler::analysis::TypeInfo::Unknown,
-                        register: None,
-                        stack_offset: p.offset.unwrap_or(0),
-                    })
-                    .collect(),
-                return_type: None,
-                local_variables: func
-                    .local_variables
-                    .iter()
-                    .map(|v| crate::recompiler::analysis::VariableInfo {
-                        name: v.name.clone(),
-                        type_info: crate::recompiler::analysis::TypeInfo::Unknown,
-                        stack_offset: v.offset,
-                        scope_start: 0u32,
-                        scope_end: 0u32,
-                    })
-                    .collect(),
-                basic_blocks: vec![],
             };
-
-            match codegen.generate_function(&func_metadata, &func_instructions) {
-                Ok(func_code) => {
+            generated_instructions += generated.generated_instructions;
+
+            match code {
+                Ok((func_code, translation)) => {
+                    match (&cache, &generated.cached) {
+                        (Some(_), Some((_, true))) => cache_hits += 1,
+                        (Some(cache), Some((key, false))) => {
+                            cache.insert(key, &func_code, translation)?;
+                            cache_misses += 1;
+                        }
+                        _ => {}
+                    }
+                    if let Some(report) = &mut report {
+                        report.write_function(
+                            &func.name,
+                            func.address,
+                            &func_instructions,
+                            &func_code,
+                            Some(translation),
+                        )?;
+                    }
                     rust_code.push_str(&func_code);
                     rust_code.push('\n');
                     successful_functions += 1;
+                    coverage.record_function(&func_instructions, Some(translation));
+                    if translation == Translation::Interpreted {
+                        interpreted.push(func.address);
+                    }
                 }
                 Err(e) => {
+                    if generated.cached.is_some() {
+                        cache_misses += 1;
+                    }
                     log::warn!(
                         "Failed to generate code for function {} at 0x{:08X}: {}",
                         func.name,
@@ -240,54 +686,75 @@ impl RecompilationPipeline {
                         e
                     );
                     failed_functions += 1;
+                    coverage.record_function(&func_instructions, None);
                     // Generate a stub function instead
-                    rust_code.push_str(&format!(
-                        "// Stub for function {} at 0x{:08X} (generation failed: {})\n",
-                        func.name, func.address, e
-                    ));
-                    rust_code.push_str(&format!(
-                        "pub fn {}_0x{:08X}(_ctx: &mut CpuContext, _memory: &mut MemoryManager) -> Result<Option<u32>> {{\n",
-                        codegen.sanitize_identifier(&func.name),
-                        func.address
-                    ));
-                    rust_code
-                        .push_str("    log::warn!(\"Function stub called - not implemented\");\n");
-                    rust_code.push_str("    Ok(None)\n");
-                    rust_code.push_str("}\n\n");
+                    let stub =
+                        Self::stub_function(&codegen, func, &format!("generation failed: {e}"));
+                    if let Some(report) = &mut report {
+                        report.write_function(
+                            &func.name,
+                            func.address,
+                            &func_instructions,
+                            &stub,
+                            None,
+                        )?;
+                    }
+                    rust_code.push_str(&stub);
                 }
             }
         }
 
         log::info!(
-            "Code generation complete: {} successful, {} failed out of {} total functions",
+            "Code generation complete: {} successful ({} interpreted), {} failed, {} skipped out of {} total functions",
             successful_functions,
+            interpreted.len(),
             failed_functions,
+            skipped_functions,
             total_functions
         );
+        log::info!("Coverage: {}", coverage.summary());
+        if let Some(cache) = &cache {
+            log::info!(
+                "Function cache: {} hits, {} misses in {}",
+                cache_hits,
+                cache_misses,
+                cache.dir().display()
+            );
+        }
+        if let Some(report) = report {
+            let index = report.finish()?;
+            log::info!("Report written to {}", index.display());
+        }
+        rust_code.push_str(&Self::interpreted_registry(&interpreted));
 
         // Add function dispatcher at the end
         rust_code.push_str("\n/// Function dispatcher - calls recompiled functions by address\n");
         rust_code
             .push_str("/// This is generated automatically to handle indirect function calls\n");
+        rust_code.push_str(
+            "/// Tail calls run in a loop here rather than nesting (see `runtime::trampoline`)\n",
+        );
         rust_code.push_str("pub fn call_function_by_address(\n");
         rust_code.push_str("    address: u32,\n");
         rust_code.push_str("    ctx: &mut CpuContext,\n");
         rust_code.push_str("    memory: &mut MemoryManager,\n");
         rust_code.push_str(") -> Result<Option<u32>> {\n");
+        rust_code.push_str(
+            "    gcrecomp_core::runtime::trampoline(address, ctx, memory, dispatch_function)\n",
+        );
+        rust_code.push_str("}\n\n");
+        rust_code.push_str(Self::hot_reload_exports());
+        rust_code.push_str("fn dispatch_function(\n");
+        rust_code.push_str("    address: u32,\n");
+        rust_code.push_str("    ctx: &mut CpuContext,\n");
+        rust_code.push_str("    memory: &mut MemoryManager,\n");
+        rust_code.push_str(") -> Result<Option<u32>> {\n");
         rust_code.push_str("    // Static function address mapping\n");
         rust_code.push_str("    match address {\n");
 
         // Add function address mappings
         for func in ghidra_analysis.functions.iter() {
-            let func_name = if func.name.is_empty() || func.name.starts_with("sub_") {
-                format!("func_0x{:08X}", func.address)
-            } else {
-                format!(
-                    "{}_{:08X}",
-                    codegen.sanitize_identifier(&func.name),
-                    func.address
-                )
-            };
+            let func_name = codegen.function_identifier(&func.name, func.address);
             rust_code.push_str(&format!(
                 "        0x{:08X}u32 => {}(ctx, memory),\n",
                 func.address, func_name
@@ -309,6 +776,9 @@ impl RecompilationPipeline {
             .push_str("pub static GAME_IMAGE: &[u8] = include_bytes!(\"game_image.bin\");\n\n");
         rust_code.push_str("/// Load the DOL's sections into RAM at their virtual addresses.\n");
         rust_code.push_str("pub fn load_image(memory: &mut MemoryManager) {\n");
+        rust_code.push_str(
+            "    gcrecomp_core::runtime::interpreter::set_call_dispatcher(call_function_by_address);\n",
+        );
         rust_code.push_str("    let img = GAME_IMAGE;\n");
         rust_code.push_str("    let mut p = 0usize;\n");
         rust_code.push_str("    while p + 8 <= img.len() {\n");
@@ -352,8 +822,44 @@ impl RecompilationPipeline {
             image.len()
         );
 
+        let map_path = std::path::Path::new(output_path).with_file_name("symbols.map");
+        std::fs::write(
+            &map_path,
+            crate::recompiler::symbols::export_map(&ghidra_analysis.functions),
+        )?;
+        log::info!("Wrote symbol map: {}", map_path.display());
+
+        let coverage_path = std::path::Path::new(output_path).with_file_name("coverage.json");
+        std::fs::write(&coverage_path, coverage.to_json())?;
+        log::info!("Wrote coverage report: {}", coverage_path.display());
+
+        let stats = PipelineStats {
+            total_functions,
+            successful_functions,
+            failed_functions,
+            total_instructions: instructions.len(),
+            interpreted_functions: interpreted.len(),
+            generated_instructions,
+            skipped_functions,
+            cache_hits,
+            cache_misses,
+        };
+        let optimizer = optimizer_stats;
+        log::info!(
+            "Optimizer: {} constants folded, {} instructions eliminated, {} loads reused, {} hoisted, {} loops unrolled",
+            optimizer.folded_constants,
+            optimizer.eliminated_instructions,
+            optimizer.eliminated_loads,
+            optimizer.hoisted_instructions,
+            optimizer.unrolled_loops
+        );
+
         log::info!("Recompilation complete!");
-        Ok(())
+        Ok(RecompileSummary {
+            stats,
+            coverage,
+            optimizer,
+        })
     }
 
     /// Analyze a DOL without generating code or needing any external tool:
@@ -373,6 +879,21 @@ impl RecompilationPipeline {
         Ok((facts, report))
     }
 
+    /// `analyze`, packaged for tools: the discovered functions, their call
+    /// graph, unreachable functions and coverage (`analyze --json`).
+    pub fn analyze_metadata(
+        dol_file: &DolFile,
+    ) -> Result<crate::recompiler::metadata::AnalysisMetadata> {
+        let instructions = Self::decode_all_instructions(dol_file)?;
+        let analysis = Self::naive_function_discovery(dol_file.entry_point, &instructions);
+        let facts = crate::recompiler::enrich::enrich_functions(&analysis.functions, &instructions);
+        Ok(crate::recompiler::metadata::AnalysisMetadata::new(
+            dol_file.entry_point,
+            analysis.functions,
+            &facts,
+        ))
+    }
+
     // --- Discrete stage methods for Lua orchestration ---
 
     /// Stage: Load a DOL file into the pipeline context.
@@ -390,20 +911,34 @@ impl RecompilationPipeline {
         let dol = ctx
             .dol_file
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No DOL file loaded"))?;
+            .ok_or(RecompileError::MissingStage("No DOL file loaded"))?;
         let analysis =
-            GhidraAnalysis::analyze(&dol.path, crate::recompiler::ghidra::GhidraBackend::ReOxide)?;
+            GhidraAnalysis::analyze(&dol.path, crate::recompiler::ghidra::GhidraBackend::ReOxide)
+                .map_err(|e| RecompileError::GhidraError(format!("{:#}", e)))?;
         ctx.ghidra_analysis = Some(analysis);
         Ok(())
     }
 
+    /// Stage: Name analyzed functions from a `.map` file.
+    pub fn stage_load_symbols(ctx: &mut PipelineContext, path: &str) -> Result<()> {
+        log::info!("Stage: Loading symbol map: {}", path);
+        let analysis = ctx
+            .ghidra_analysis
+            .as_mut()
+            .ok_or(RecompileError::MissingStage("No Ghidra analysis"))?;
+        let map = SymbolMap::parse(&std::fs::read_to_string(path)?);
+        let named = map.apply(&mut analysis.functions);
+        log::info!("Symbol map: named {} functions", named);
+        Ok(())
+    }
+
     /// Stage: Decode PowerPC instructions from the DOL.
     pub fn stage_decode(ctx: &mut PipelineContext) -> Result<()> {
         log::info!("Stage: Decoding instructions...");
         let dol = ctx
             .dol_file
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No DOL file loaded"))?;
+            .ok_or(RecompileError::MissingStage("No DOL file loaded"))?;
         let instructions = Self::decode_all_instructions(dol)?;
         ctx.stats.total_instructions = instructions.len();
         ctx.instructions = Some(instructions);
@@ -416,8 +951,9 @@ impl RecompilationPipeline {
         let instructions = ctx
             .instructions
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No instructions decoded"))?;
-        let cfg = ControlFlowAnalyzer::build_cfg(instructions, 0u32)?;
+            .ok_or(RecompileError::MissingStage("No instructions decoded"))?;
+        let cfg = ControlFlowAnalyzer::build_cfg(instructions, 0u32)
+            .map_err(|e| RecompileError::AnalysisError(e.to_string()))?;
         ctx.cfg = Some(cfg);
         Ok(())
     }
@@ -428,11 +964,11 @@ impl RecompilationPipeline {
         let instructions = ctx
             .instructions
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No instructions decoded"))?;
+            .ok_or(RecompileError::MissingStage("No instructions decoded"))?;
         let cfg = ctx
             .cfg
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No CFG built"))?;
+            .ok_or(RecompileError::MissingStage("No CFG built"))?;
         let _def_use_chains = DataFlowAnalyzer::build_def_use_chains(instructions);
         let _live_analysis = DataFlowAnalyzer::live_variable_analysis(cfg);
         Ok(())
@@ -450,12 +986,12 @@ impl RecompilationPipeline {
         let ghidra_analysis = ctx
             .ghidra_analysis
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No Ghidra analysis"))?;
+            .ok_or(RecompileError::MissingStage("No Ghidra analysis"))?;
         let instructions = ctx
             .instructions
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No instructions decoded"))?;
-        let mut codegen = CodeGenerator::new();
+            .ok_or(RecompileError::MissingStage("No instructions decoded"))?;
+        let mut codegen = CodeGenerator::new().with_strict(true);
 
         let estimated_capacity = ghidra_analysis.functions.len() * 1000;
         let mut rust_code = String::with_capacity(estimated_capacity);
@@ -469,78 +1005,54 @@ impl RecompilationPipeline {
         let total_functions = ghidra_analysis.functions.len();
         let mut successful = 0usize;
         let mut failed = 0usize;
+        let mut interpreted = Vec::new();
+        let mut coverage = InstructionCoverage::new();
 
         for func in ghidra_analysis.functions.iter() {
             let func_instructions = Self::map_instructions_to_function(func, instructions);
 
             if func_instructions.is_empty() {
                 failed += 1;
+                coverage.record_function(&func_instructions, None);
                 continue;
             }
 
-            let func_metadata = crate::recompiler::analysis::FunctionMetadata {
-                address: func.address,
-                name: func.name.clone(),
-                size: func.size,
-                calling_convention: func.calling_convention.clone(),
-                parameters: func
-                    .parameters
-                    .iter()
-                    .map(|p| crate::recompiler::analysis::ParameterInfo {
-                        name: p.name.clone(),
-                        type_info: crate::recompiler::analysis::TypeInfo::Unknown,
-                        register: None,
-                        stack_offset: p.offset.unwrap_or(0),
-                    })
-                    .collect(),
-                return_type: None,
-                local_variables: func
-                    .local_variables
-                    .iter()
-                    .map(|v| crate::recompiler::analysis::VariableInfo {
-                        name: v.name.clone(),
-                        type_info: crate::recompiler::analysis::TypeInfo::Unknown,
-                        stack_offset: v.offset,
-                        scope_start: 0,
-                        scope_end: 0,
-                    })
-                    .collect(),
-                basic_blocks: vec![],
-            };
+            let func_metadata = Self::function_metadata(func);
 
-            match codegen.generate_function(&func_metadata, &func_instructions) {
-                Ok(func_code) => {
+            match Self::generate_function_code(&mut codegen, &func_metadata, &func_instructions) {
+                Ok((func_code, translation)) => {
                     rust_code.push_str(&func_code);
                     rust_code.push('\n');
                     successful += 1;
+                    coverage.record_function(&func_instructions, Some(translation));
+                    if translation == Translation::Interpreted {
+                        interpreted.push(func.address);
+                    }
                 }
                 Err(e) => {
                     failed += 1;
+                    coverage.record_function(&func_instructions, None);
                     rust_code.push_str(&format!(
                         "// Stub for {} at 0x{:08X} (generation failed: {})\n",
                         func.name, func.address, e
                     ));
                     rust_code.push_str(&format!(
-                        "pub fn {}_0x{:08X}(_ctx: &mut CpuContext, _memory: &mut MemoryManager) -> Result<Option<u32>> {{\n",
-                        codegen.sanitize_identifier(&func.name), func.address
+                        "pub fn {}(_ctx: &mut CpuContext, _memory: &mut MemoryManager) -> Result<Option<u32>> {{\n",
+                        codegen.function_identifier(&func.name, func.address)
                     ));
                     rust_code.push_str("    Ok(None)\n}\n\n");
                 }
             }
         }
 
+        rust_code.push_str(&Self::interpreted_registry(&interpreted));
+
         // Function dispatcher
-        rust_code.push_str("\npub fn call_function_by_address(\n    address: u32,\n    ctx: &mut CpuContext,\n    memory: &mut MemoryManager,\n) -> Result<Option<u32>> {\n    match address {\n");
+        rust_code.push_str("\npub fn call_function_by_address(\n    address: u32,\n    ctx: &mut CpuContext,\n    memory: &mut MemoryManager,\n) -> Result<Option<u32>> {\n    gcrecomp_core::runtime::trampoline(address, ctx, memory, dispatch_function)\n}\n\n");
+        rust_code.push_str(Self::hot_reload_exports());
+        rust_code.push_str("fn dispatch_function(\n    address: u32,\n    ctx: &mut CpuContext,\n    memory: &mut MemoryManager,\n) -> Result<Option<u32>> {\n    match address {\n");
         for func in ghidra_analysis.functions.iter() {
-            let func_name = if func.name.is_empty() || func.name.starts_with("sub_") {
-                format!("func_0x{:08X}", func.address)
-            } else {
-                format!(
-                    "{}_{:08X}",
-                    codegen.sanitize_identifier(&func.name),
-                    func.address
-                )
-            };
+            let func_name = codegen.function_identifier(&func.name, func.address);
             rust_code.push_str(&format!(
                 "        0x{:08X}u32 => {}(ctx, memory),\n",
                 func.address, func_name
@@ -551,6 +1063,9 @@ impl RecompilationPipeline {
         ctx.stats.total_functions = total_functions;
         ctx.stats.successful_functions = successful;
         ctx.stats.failed_functions = failed;
+        ctx.stats.interpreted_functions = interpreted.len();
+        log::info!("Coverage: {}", coverage.summary());
+        ctx.coverage = coverage;
         ctx.rust_code = Some(rust_code);
         Ok(())
     }
@@ -561,19 +1076,21 @@ impl RecompilationPipeline {
         let code = ctx
             .rust_code
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No code generated"))?;
+            .ok_or(RecompileError::MissingStage("No code generated"))?;
         CodeValidator::validate_rust_code(code)?;
         Ok(())
     }
 
-    /// Stage: Write output to file.
+    /// Stage: Write output to file, with `coverage.json` next to it.
     pub fn stage_write_output(ctx: &mut PipelineContext, output_path: &str) -> Result<()> {
         log::info!("Stage: Writing output to {}...", output_path);
         let code = ctx
             .rust_code
             .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("No code generated"))?;
+            .ok_or(RecompileError::MissingStage("No code generated"))?;
         std::fs::write(output_path, code)?;
+        let coverage_path = std::path::Path::new(output_path).with_file_name("coverage.json");
+        std::fs::write(coverage_path, ctx.coverage.to_json())?;
         Ok(())
     }
 
@@ -606,6 +1123,117 @@ impl RecompilationPipeline {
         Ok(())
     }
 
+    /// Generate one function with `codegen` (which should be strict). A
+    /// function the generator can't fully translate is handed to the runtime
+    /// interpreter instead when it understands every instruction; otherwise it
+    /// is translated leniently, leaving the failures as comments.
+    pub fn generate_function_code(
+        codegen: &mut CodeGenerator,
+        metadata: &crate::recompiler::analysis::FunctionMetadata,
+        instructions: &[DecodedInstruction],
+    ) -> Result<(String, Translation)> {
+        let reason = match codegen.generate_function(metadata, instructions) {
+            Ok(code) => return Ok((code, Translation::Recompiled)),
+            Err(e) => e,
+        };
+        if instructions
+            .iter()
+            .all(|i| crate::runtime::interpreter::supports(i.raw))
+        {
+            log::info!(
+                "Function {} at 0x{:08X} will be interpreted: {}",
+                metadata.name,
+                metadata.address,
+                reason
+            );
+            let code = codegen.generate_interpreted_function(metadata, &reason)?;
+            return Ok((code, Translation::Interpreted));
+        }
+        let code = CodeGenerator::new()
+            .with_optimizations(codegen.optimizations())
+            .with_sda_bases(codegen.sda_bases())
+            .with_struct_accessors(codegen.struct_accessors())
+            .generate_function(metadata, instructions)?;
+        Ok((code, Translation::Partial))
+    }
+
+    /// C entry points for loading the generated crate as a cdylib (see
+    /// `runtime::hot_reload`).
+    fn hot_reload_exports() -> &'static str {
+        concat!(
+            "/// Hot-reload entry points (`runtime::hot_reload`).\n",
+            "#[no_mangle]\n",
+            "pub extern \"C\" fn gcrecomp_abi_version() -> u32 {\n",
+            "    gcrecomp_core::runtime::hot_reload::ABI_VERSION\n",
+            "}\n\n",
+            "/// # Safety\n///\n/// Called by `runtime::hot_reload::dispatch` with live pointers.\n",
+            "#[no_mangle]\n",
+            "pub unsafe extern \"C\" fn gcrecomp_dispatch(\n",
+            "    address: u32,\n",
+            "    ctx: *mut CpuContext,\n",
+            "    memory: *mut MemoryManager,\n",
+            "    ret: *mut u32,\n",
+            ") -> i32 {\n",
+            "    gcrecomp_core::runtime::hot_reload::export(address, ctx, memory, ret, call_function_by_address)\n",
+            "}\n\n",
+            "/// # Safety\n///\n/// Called by `runtime::hot_reload::HotReloader` with the host's logger.\n",
+            "#[no_mangle]\n",
+            "pub unsafe extern \"C\" fn gcrecomp_set_logger(logger: *const std::ffi::c_void, max_level: usize) {\n",
+            "    gcrecomp_core::runtime::hot_reload::install_logger(logger, max_level)\n",
+            "}\n\n",
+        )
+    }
+
+    /// `INTERPRETED_FUNCTIONS`: addresses whose generated body delegates to
+    /// the runtime interpreter, sorted.
+    fn interpreted_registry(addresses: &[u32]) -> String {
+        let mut sorted = addresses.to_vec();
+        sorted.sort_unstable();
+        let mut out =
+            String::from("/// Functions that run under the runtime interpreter.\npub static INTERPRETED_FUNCTIONS: &[u32] = &[\n");
+        for address in sorted {
+            out.push_str(&format!("    0x{:08X},\n", address));
+        }
+        out.push_str("];\n\n");
+        out
+    }
+
+    /// Codegen metadata for a discovered function. Parameter and local types
+    /// aren't recovered yet, so they're all `Unknown`.
+    pub fn function_metadata(
+        func: &crate::recompiler::ghidra::FunctionInfo,
+    ) -> crate::recompiler::analysis::FunctionMetadata {
+        crate::recompiler::analysis::FunctionMetadata {
+            address: func.address,
+            name: func.name.clone(),
+            size: func.size,
+            calling_convention: func.calling_convention.clone(),
+            parameters: func
+                .parameters
+                .iter()
+                .map(|p| crate::recompiler::analysis::ParameterInfo {
+                    name: p.name.clone(),
+                    type_info: crate::recompiler::analysis::TypeInfo::Unknown,
+                    register: None,
+                    stack_offset: p.offset.unwrap_or(0),
+                })
+                .collect(),
+            return_type: None,
+            local_variables: func
+                .local_variables
+                .iter()
+                .map(|v| crate::recompiler::analysis::VariableInfo {
+                    name: v.name.clone(),
+                    type_info: crate::recompiler::analysis::TypeInfo::Unknown,
+                    stack_offset: v.offset,
+                    scope_start: 0,
+                    scope_end: 0,
+                })
+                .collect(),
+            basic_blocks: vec![],
+        }
+    }
+
     /// Decode all instructions from a DOL file.
     ///
     /// # Algorithm
@@ -635,24 +1263,14 @@ impl RecompilationPipeline {
         // Pre-allocate vector with estimated capacity
         let mut instructions: Vec<DecodedInstruction> = Vec::with_capacity(estimated_count);
 
-        // Decode instructions from text sections (executable sections)
+        // Decode instructions from text sections (executable sections), a
+        // SIMD block at a time; undecodable words are skipped.
         for section in dol_file.text_sections.iter() {
-            let data: &[u8] = &section.data;
-            let section_address: u32 = section.address;
-
-            // Decode each 4-byte instruction chunk
-            for (chunk_index, chunk) in data.chunks_exact(4usize).enumerate() {
-                let word: u32 = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
-                // Calculate instruction address: section base + offset
-                let instruction_address: u32 =
-                    section_address.wrapping_add((chunk_index * 4usize) as u32);
-
-                if let Ok(decoded) =
-                    crate::recompiler::decoder::Instruction::decode(word, instruction_address)
-                {
-                    instructions.push(decoded);
-                }
-            }
+            instructions.extend(
+                crate::recompiler::batch::decode_section(&section.data, section.address)
+                    .into_iter()
+                    .flatten(),
+            );
         }
 
         Ok(instructions)
@@ -676,6 +1294,78 @@ impl RecompilationPipeline {
     /// # Returns
     /// `Vec<DecodedInstruction>` - Instructions belonging to this function
     #[inline] // May be called frequently
+    /// Small straight-line leaves (at most [`INLINE_BUDGET`] instructions before
+    /// the `blr`) that codegen splices into their callers.
+    pub fn analyze_inlining_candidates(
+        functions: &[FunctionInfo],
+        instructions: &[DecodedInstruction],
+    ) -> InlineCandidates {
+        let max_size = (INLINE_BUDGET as u32 + 1) * 4;
+        let by_address: HashMap<u32, &DecodedInstruction> =
+            instructions.iter().map(|i| (i.address, i)).collect();
+        functions
+            .iter()
+            .filter(|f| f.size != 0 && f.size <= max_size)
+            .filter_map(|f| {
+                let body: Option<Vec<DecodedInstruction>> = (f.address..f.address + f.size)
+                    .step_by(4)
+                    .map(|a| by_address.get(&a).map(|&i| i.clone()))
+                    .collect();
+                inline::inline_body(&body?, INLINE_BUDGET).map(|b| (f.address, b))
+            })
+            .collect()
+    }
+
+    /// The functions `--only` generates: those starting at `addresses` and
+    /// their direct callees. Fails if an address doesn't start a function.
+    pub fn select_functions(addresses: &[u32], facts: &[FunctionFacts]) -> Result<HashSet<u32>> {
+        let mut call_graph: HashMap<u32, Vec<u32>> = HashMap::new();
+        for &address in addresses {
+            let f = facts.iter().find(|f| f.address == address).ok_or_else(|| {
+                RecompileError::AnalysisError(format!("no function starts at 0x{address:08X}"))
+            })?;
+            // Only the requested functions' edges: callees' callees stay out.
+            call_graph.insert(address, f.call_targets.clone());
+        }
+        Ok(Optimizer::reachable_functions(addresses, &call_graph))
+    }
+
+    /// A function that logs and returns when called, for one not generated.
+    fn stub_function(codegen: &CodeGenerator, func: &FunctionInfo, reason: &str) -> String {
+        let mut stub = format!(
+            "// Stub for function {} at 0x{:08X} ({reason})\n",
+            func.name, func.address
+        );
+        stub.push_str(&format!(
+            "pub fn {}(_ctx: &mut CpuContext, _memory: &mut MemoryManager) -> Result<Option<u32>> {{\n",
+            codegen.function_identifier(&func.name, func.address)
+        ));
+        stub.push_str("    log::warn!(\"Function stub called - not implemented\");\n");
+        stub.push_str("    Ok(None)\n");
+        stub.push_str("}\n\n");
+        stub
+    }
+
+    /// Copies of small functions specialized for the constant arguments their
+    /// callers pass (see [`specialize`](crate::recompiler::specialize)).
+    /// Callees in `skip` are left alone.
+    pub fn analyze_specializations(
+        functions: &[FunctionInfo],
+        instructions: &[DecodedInstruction],
+        skip: &HashSet<u32>,
+    ) -> Vec<Specialization> {
+        let bodies: Vec<(u32, Vec<DecodedInstruction>)> = functions
+            .iter()
+            .map(|f| {
+                (
+                    f.address,
+                    Self::map_instructions_to_function(f, instructions),
+                )
+            })
+            .collect();
+        specialize::find_specializations(&bodies, skip)
+    }
+
     fn map_instructions_to_function(
         func: &crate::recompiler::ghidra::FunctionInfo,
         instructions: &[DecodedInstruction],
@@ -848,4 +1538,31 @@ mod tests {
             "no blr -> one function spanning all 3"
         );
     }
+
+    #[test]
+    fn profile_selects_aggressive_for_hot_function_only() {
+        let profile =
+            HotProfile::from_json("[[2147495936, 90000], [2147496192, 12], [2147496960, 3]]")
+                .unwrap();
+        let options = RecompileOptions {
+            profile: Some(profile),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.opt_level_for(0x8000_3000),
+            Some(OptLevel::Aggressive)
+        );
+        assert_eq!(options.opt_level_for(0x8000_3100), Some(OptLevel::Basic));
+        // Not in the profile at all: never ran, so cold.
+        assert_eq!(options.opt_level_for(0x8000_5000), Some(OptLevel::Basic));
+        assert_eq!(RecompileOptions::default().opt_level_for(0x8000_3000), None);
+
+        // The pipeline's optimizer follows: DCE only on the hot function.
+        let mut optimizer = options.optimizer.clone();
+        optimizer.set_level(options.opt_level_for(0x8000_3000).unwrap());
+        assert_eq!(optimizer.pass_enabled("dead_code_elimination"), Some(true));
+        optimizer.set_level(options.opt_level_for(0x8000_3100).unwrap());
+        assert_eq!(optimizer.pass_enabled("dead_code_elimination"), Some(false));
+        assert_eq!(optimizer.pass_enabled("constant_folding"), Some(true));
+    }
 }
@@ -0,0 +1,72 @@
+//! Profile-Guided Optimization Tiers
+//!
+//! A runtime profile (`PerformanceMonitor::hot_functions`, written by the game
+//! with `GCRECOMP_PROFILE`) is a JSON array of `[address, weight]` pairs, the
+//! weight being call count. The recompiler reads it back with
+//! `--profile-guided` and spends its optimization effort where the time goes:
+//! the hottest functions that together account for [`HOT_FRACTION`] of the
+//! total weight get [`OptLevel::Aggressive`], everything else
+//! [`OptLevel::Basic`].
+
+use crate::recompiler::optimizer::OptLevel;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Share of the profiled weight covered by the hot set.
+pub const HOT_FRACTION: f64 = 0.8;
+
+/// Functions marked hot by a runtime profile.
+#[derive(Debug, Clone, Default)]
+pub struct HotProfile {
+    hot: HashSet<u32>,
+}
+
+impl HotProfile {
+    /// Pick the hot set from `(address, weight)` samples in any order.
+    pub fn from_hot_functions(samples: &[(u32, u64)]) -> Self {
+        let mut sorted: Vec<(u32, u64)> = samples.iter().copied().filter(|&(_, w)| w > 0).collect();
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let total: u64 = sorted.iter().map(|&(_, w)| w).sum();
+
+        let mut hot = HashSet::new();
+        let mut covered = 0u64;
+        for (address, weight) in sorted {
+            if covered as f64 >= total as f64 * HOT_FRACTION {
+                break;
+            }
+            hot.insert(address);
+            covered += weight;
+        }
+        Self { hot }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let samples: Vec<(u32, u64)> = serde_json::from_str(text)
+            .context("Profile must be a JSON array of [address, weight]")?;
+        Ok(Self::from_hot_functions(&samples))
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read profile: {}", path.display()))?;
+        Self::from_json(&text)
+    }
+
+    pub fn is_hot(&self, address: u32) -> bool {
+        self.hot.contains(&address)
+    }
+
+    pub fn hot_count(&self) -> usize {
+        self.hot.len()
+    }
+
+    /// Optimization level for the function at `address`.
+    pub fn level_for(&self, address: u32) -> OptLevel {
+        if self.is_hot(address) {
+            OptLevel::Aggressive
+        } else {
+            OptLevel::Basic
+        }
+    }
+}
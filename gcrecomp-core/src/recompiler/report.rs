@@ -0,0 +1,225 @@
+//! Recompilation Report
+//!
+//! A browsable Markdown report for reviewing a recompile: one page per
+//! function with its original disassembly, the Rust generated for it and how
+//! it came out (fully translated, partial, interpreted or stubbed), plus an
+//! `index.md` linking every page. The pipeline writes it when
+//! `RecompileOptions::report_dir` is set (`gcrecomp recompile --report <dir>`).
+//!
+//! Reports read back with [`read_report`] can be compared with
+//! [`ReportDiff`] (`gcrecomp diff --old <dir> --new <dir>`) to see what a
+//! decoder or codegen change did across a whole game.
+
+use crate::recompiler::decoder::DecodedInstruction;
+use crate::recompiler::error::{RecompileError, Result};
+use crate::recompiler::pipeline::Translation;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Report label for how a function was generated; `None` is a stub.
+pub fn status(translation: Option<Translation>) -> &'static str {
+    match translation {
+        Some(Translation::Recompiled) => "fully translated",
+        Some(Translation::Partial) => "partial",
+        Some(Translation::Interpreted) => "interpreted",
+        None => "stubbed",
+    }
+}
+
+struct IndexEntry {
+    file: String,
+    name: String,
+    address: u32,
+    status: &'static str,
+    instructions: usize,
+}
+
+/// Writes function pages into a directory, then the index.
+pub struct ReportWriter {
+    dir: PathBuf,
+    entries: Vec<IndexEntry>,
+}
+
+impl ReportWriter {
+    /// Start a report in `dir`, creating it if needed.
+    pub fn create(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            entries: Vec::new(),
+        })
+    }
+
+    /// Write the page for one function and return its path.
+    pub fn write_function(
+        &mut self,
+        name: &str,
+        address: u32,
+        instructions: &[DecodedInstruction],
+        rust: &str,
+        translation: Option<Translation>,
+    ) -> Result<PathBuf> {
+        let status = status(translation);
+        let mut page = format!("# {name}\n\n");
+        let _ = writeln!(
+            page,
+            "`0x{address:08X}` · {status} · {} instructions\n",
+            instructions.len()
+        );
+        page.push_str("[Index](index.md)\n\n## Disassembly\n\n```asm\n");
+        for inst in instructions {
+            let _ = writeln!(page, "{:08X}  {:08X}  {inst}", inst.address, inst.raw);
+        }
+        page.push_str("```\n\n## Generated Rust\n\n```rust\n");
+        page.push_str(rust.trim_end());
+        page.push_str("\n```\n");
+
+        let file = format!("{address:08X}.md");
+        let path = self.dir.join(&file);
+        fs::write(&path, page)?;
+        self.entries.push(IndexEntry {
+            file,
+            name: name.to_string(),
+            address,
+            status,
+            instructions: instructions.len(),
+        });
+        Ok(path)
+    }
+
+    /// Write `index.md` (functions in address order, with status counts) and
+    /// return its path.
+    pub fn finish(mut self) -> Result<PathBuf> {
+        self.entries.sort_by_key(|e| e.address);
+        let mut index = String::from("# Recompilation report\n\n");
+        for label in ["fully translated", "partial", "interpreted", "stubbed"] {
+            let count = self.entries.iter().filter(|e| e.status == label).count();
+            let _ = writeln!(index, "- {label}: {count}");
+        }
+        index.push_str("\n| Address | Function | Status | Instructions |\n");
+        index.push_str("|---|---|---|---|\n");
+        for e in &self.entries {
+            let _ = writeln!(
+                index,
+                "| `0x{:08X}` | [{}]({}) | {} | {} |",
+                e.address, e.name, e.file, e.status, e.instructions
+            );
+        }
+        let path = self.dir.join("index.md");
+        fs::write(&path, index)?;
+        Ok(path)
+    }
+}
+
+/// A function page read back from a report directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionPage {
+    pub name: String,
+    pub address: u32,
+    /// One of the [`status`] labels.
+    pub status: String,
+    /// Length of the generated Rust, in bytes.
+    pub rust_len: usize,
+}
+
+impl FunctionPage {
+    pub fn is_stub(&self) -> bool {
+        self.status == status(None)
+    }
+
+    /// Parse a page [`ReportWriter::write_function`] wrote.
+    pub fn parse(page: &str) -> Option<Self> {
+        let mut lines = page.lines();
+        let name = lines.next()?.strip_prefix("# ")?.to_string();
+        let header = lines.find(|l| !l.is_empty())?;
+        let mut fields = header.split(" · ");
+        let address = fields.next()?.trim_matches('`').strip_prefix("0x")?;
+        let address = u32::from_str_radix(address, 16).ok()?;
+        let status = fields.next()?.to_string();
+        let (_, rest) = page.split_once("## Generated Rust\n\n```rust\n")?;
+        let rust = rest.strip_suffix("\n```\n").unwrap_or(rest);
+        Some(Self {
+            name,
+            address,
+            status,
+            rust_len: rust.len(),
+        })
+    }
+}
+
+/// Every function page in the report at `dir`, by address.
+pub fn read_report(dir: &Path) -> Result<BTreeMap<u32, FunctionPage>> {
+    let mut pages = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_page = path.extension().is_some_and(|e| e == "md")
+            && path.file_stem().is_some_and(|s| s != "index");
+        if !is_page {
+            continue;
+        }
+        let page = FunctionPage::parse(&fs::read_to_string(&path)?).ok_or_else(|| {
+            RecompileError::ParseError(format!("{} is not a report page", path.display()))
+        })?;
+        pages.insert(page.address, page);
+    }
+    Ok(pages)
+}
+
+/// How one report's functions differ from another's. Functions whose status
+/// flipped between stubbed and generated aren't also listed as resized.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportDiff {
+    /// Stubbed before, generated now.
+    pub newly_succeeded: Vec<FunctionPage>,
+    /// Generated before, stubbed now.
+    pub newly_stubbed: Vec<FunctionPage>,
+    /// `(old, new)` pages whose generated Rust changed length.
+    pub resized: Vec<(FunctionPage, FunctionPage)>,
+    /// Only in the new report.
+    pub added: Vec<FunctionPage>,
+    /// Only in the old report.
+    pub removed: Vec<FunctionPage>,
+    /// In both, same status and length.
+    pub unchanged: usize,
+}
+
+impl ReportDiff {
+    pub fn compare(old: &BTreeMap<u32, FunctionPage>, new: &BTreeMap<u32, FunctionPage>) -> Self {
+        let mut diff = Self::default();
+        for (address, before) in old {
+            let Some(after) = new.get(address) else {
+                diff.removed.push(before.clone());
+                continue;
+            };
+            match (before.is_stub(), after.is_stub()) {
+                (true, false) => diff.newly_succeeded.push(after.clone()),
+                (false, true) => diff.newly_stubbed.push(after.clone()),
+                _ if before.rust_len != after.rust_len => {
+                    diff.resized.push((before.clone(), after.clone()))
+                }
+                _ => diff.unchanged += 1,
+            }
+        }
+        diff.added = new
+            .iter()
+            .filter(|(address, _)| !old.contains_key(address))
+            .map(|(_, page)| page.clone())
+            .collect();
+        diff
+    }
+
+    /// One line of counts.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} newly succeeded, {} newly stubbed, {} changed size, {} added, {} removed, {} unchanged",
+            self.newly_succeeded.len(),
+            self.newly_stubbed.len(),
+            self.resized.len(),
+            self.added.len(),
+            self.removed.len(),
+            self.unchanged
+        )
+    }
+}
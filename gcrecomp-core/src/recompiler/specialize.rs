@@ -0,0 +1,252 @@
+//! Call-Site Specialization
+//!
+//! A small function called with constant arguments gets a copy compiled for
+//! those constants: the callee's instructions are re-optimized with the
+//! argument registers known on entry, so constant folding and DCE can do
+//! what they can't in the general version, and the `bl` that passed the
+//! constants calls the copy directly instead of going through the dispatcher.
+//!
+//! Arguments are found on a straight-line view of the caller, like the
+//! optimizer's passes: `li`/`lis`/`ori`/`addi` into r3-r10 are tracked, and
+//! branch targets, other branches and anything writing a register in a way
+//! the tracker doesn't understand forget what was known. Only registers the
+//! callee names are specialized on, and a copy that folds nothing is dropped.
+
+use crate::recompiler::decoder::{DecodedInstruction, InstructionType, Operand};
+use crate::recompiler::optimizer::{branch_targets, pure_def, OptLevel, Optimizer};
+use std::collections::{HashMap, HashSet};
+
+/// Largest callee (instructions, including the `blr`) that gets specialized.
+pub const SPECIALIZE_BUDGET: usize = 32;
+
+/// Most specialized copies made of any one function.
+pub const MAX_SPECIALIZATIONS: usize = 4;
+
+/// Argument registers (r3-r10).
+const ARGUMENTS: std::ops::RangeInclusive<u8> = 3..=10;
+
+/// One specialized copy of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Specialization {
+    pub callee: u32,
+    /// Which copy of `callee` this is, numbered from 0.
+    pub index: usize,
+    /// `(register, value)` known on entry, by register.
+    pub arguments: Vec<(u8, u32)>,
+    /// The callee re-optimized for `arguments`.
+    pub body: Vec<DecodedInstruction>,
+    /// `bl` addresses that call this copy.
+    pub call_sites: Vec<u32>,
+}
+
+/// `(call site, callee, constant arguments)` for every `bl` in `caller` that
+/// passes at least one constant in r3-r10.
+pub fn constant_calls(caller: &[DecodedInstruction]) -> Vec<(u32, u32, Vec<(u8, u32)>)> {
+    let labels = branch_targets(caller);
+    let mut constants: HashMap<u8, u32> = HashMap::new();
+    let mut calls = Vec::new();
+    for inst in caller {
+        if labels.contains(&inst.address) {
+            constants.clear();
+        }
+        let raw = inst.raw;
+        let (rd, ra) = (((raw >> 21) & 31) as u8, ((raw >> 16) & 31) as u8);
+        let imm = raw as u16;
+        match raw >> 26 {
+            // bl (relative)
+            18 if raw & 3 == 1 => {
+                let disp = ((raw & 0x03FF_FFFC) as i32) << 6 >> 6;
+                let mut arguments: Vec<(u8, u32)> = constants
+                    .iter()
+                    .filter(|(r, _)| ARGUMENTS.contains(r))
+                    .map(|(&r, &v)| (r, v))
+                    .collect();
+                if !arguments.is_empty() {
+                    arguments.sort_unstable();
+                    calls.push((
+                        inst.address,
+                        inst.address.wrapping_add(disp as u32),
+                        arguments,
+                    ));
+                }
+            }
+            // addi / li
+            14 => {
+                let value = if ra == 0 {
+                    Some(imm as i16 as i32 as u32)
+                } else {
+                    constants
+                        .get(&ra)
+                        .map(|&base| base.wrapping_add(imm as i16 as i32 as u32))
+                };
+                match value {
+                    Some(v) => constants.insert(rd, v),
+                    None => constants.remove(&rd),
+                };
+            }
+            // lis
+            15 if ra == 0 => {
+                constants.insert(rd, (imm as u32) << 16);
+            }
+            // ori rA,rS,imm
+            24 => match constants.get(&rd) {
+                Some(&v) => {
+                    constants.insert(ra, v | imm as u32);
+                }
+                None => {
+                    constants.remove(&ra);
+                }
+            },
+            _ => match pure_def(raw) {
+                Some((dest, _)) => {
+                    constants.remove(&dest);
+                }
+                None => constants.clear(),
+            },
+        }
+        if inst.instruction.instruction_type == InstructionType::Branch {
+            constants.clear();
+        }
+    }
+    calls
+}
+
+/// Specialized copies for the constant calls between `functions` (address
+/// and instructions, in a fixed order so copies are numbered the same every
+/// run). Calls to callees in `skip` (e.g. ones inlined anyway), to callees
+/// larger than [`SPECIALIZE_BUDGET`] and from tail position are left alone.
+pub fn find_specializations(
+    functions: &[(u32, Vec<DecodedInstruction>)],
+    skip: &HashSet<u32>,
+) -> Vec<Specialization> {
+    let bodies: HashMap<u32, &[DecodedInstruction]> = functions
+        .iter()
+        .filter(|(address, body)| {
+            !skip.contains(address) && !body.is_empty() && body.len() <= SPECIALIZE_BUDGET
+        })
+        .map(|(address, body)| (*address, body.as_slice()))
+        .collect();
+    let mut specializations: Vec<Specialization> = Vec::new();
+    // Callee -> argument sets tried and found not to fold anything.
+    let mut fruitless: HashSet<(u32, Vec<(u8, u32)>)> = HashSet::new();
+    let mut optimizer = Optimizer::with_level(OptLevel::Aggressive);
+
+    for (_, caller) in functions {
+        let tail_sites: HashSet<u32> = crate::recompiler::enrich::tail_call_sites(caller)
+            .into_iter()
+            .collect();
+        for (site, callee, arguments) in constant_calls(caller) {
+            let Some(body) = bodies.get(&callee) else {
+                continue;
+            };
+            if tail_sites.contains(&site) {
+                continue;
+            }
+            let arguments: Vec<(u8, u32)> = arguments
+                .into_iter()
+                .filter(|&(r, _)| reads_register(body, r))
+                .collect();
+            if arguments.is_empty() || fruitless.contains(&(callee, arguments.clone())) {
+                continue;
+            }
+            if let Some(existing) = specializations
+                .iter_mut()
+                .find(|s| s.callee == callee && s.arguments == arguments)
+            {
+                existing.call_sites.push(site);
+                continue;
+            }
+            let index = specializations
+                .iter()
+                .filter(|s| s.callee == callee)
+                .count();
+            if index >= MAX_SPECIALIZATIONS {
+                continue;
+            }
+            let before = optimizer.stats();
+            let specialized = optimizer.optimize_with_constants(body, &arguments);
+            let after = optimizer.stats();
+            if after.folded_constants == before.folded_constants {
+                fruitless.insert((callee, arguments));
+                continue;
+            }
+            specializations.push(Specialization {
+                callee,
+                index,
+                arguments,
+                body: specialized,
+                call_sites: vec![site],
+            });
+        }
+    }
+    specializations
+}
+
+/// Whether any instruction of `body` names GPR `reg`.
+fn reads_register(body: &[DecodedInstruction], reg: u8) -> bool {
+    body.iter().any(|inst| {
+        inst.instruction
+            .operands
+            .iter()
+            .any(|op| matches!(op, Operand::Register(r) if *r == reg))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recompiler::decoder::Instruction;
+
+    fn decode(words: &[u32], base: u32) -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, base + i as u32 * 4).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn constant_arguments_are_tracked_to_the_call() {
+        // lis r4,0x8000 ; ori r4,r4,0x10 ; li r5,3 ; mr r5,r31 ; bl +0x100 ; bl +0x100
+        let caller = decode(
+            &[
+                0x3C80_8000,
+                0x6084_0010,
+                0x38A0_0003,
+                0x7FE5_FB78,
+                0x4800_0101,
+                0x4800_0101,
+            ],
+            0x8000_3000,
+        );
+        let calls = constant_calls(&caller);
+        assert_eq!(
+            calls,
+            vec![(0x8000_3010, 0x8000_3110, vec![(4, 0x8000_0010)])]
+        );
+    }
+
+    #[test]
+    fn copies_are_bounded_and_shared() {
+        // add: add r3,r3,r4 ; blr
+        let add = decode(&[0x7C63_2214, 0x4E80_0020], 0x8000_3100);
+        // Six calls: K = 0..=4 and K = 0 again.
+        let mut words = Vec::new();
+        for k in [0u32, 1, 2, 3, 4, 0] {
+            words.push(0x3880_0000 | k); // li r4,K
+            let site = 0x8000_3000 + words.len() as u32 * 4;
+            words.push(0x4800_0001 | (0x8000_3100 - site)); // bl add
+        }
+        words.extend([0x6000_0000, 0x4E80_0020]); // nop ; blr: no tail call
+        let caller = decode(&words, 0x8000_3000);
+        let functions = vec![(0x8000_3000, caller), (0x8000_3100, add)];
+
+        let specs = find_specializations(&functions, &HashSet::new());
+        assert_eq!(specs.len(), MAX_SPECIALIZATIONS);
+        assert_eq!(specs[0].arguments, vec![(4, 0)]);
+        assert_eq!(specs[0].call_sites, vec![0x8000_3004, 0x8000_302C]);
+        assert_eq!(specs[3].index, 3);
+
+        assert!(find_specializations(&functions, &HashSet::from([0x8000_3100])).is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+//! Symbol maps: import/export of GameCube `.map` files.
+//!
+//! Community symbol maps name the functions that naive discovery only knows as
+//! `sub_ADDR`. Two layouts are common and both are accepted, one symbol per line
+//! under a `.text section layout` style header:
+//!
+//! ```text
+//! CodeWarrior:  00000000 000034 80003100  4 __start   os.a os.o
+//!               00000000 000034 80003100 00000200  4 __start   os.a os.o
+//! Dolphin:      80003100 00000034 80003100 0 __start
+//! ```
+//!
+//! i.e. section offset, size, virtual address, an optional file offset,
+//! alignment, then the name. Anything else (headers, memory-map tables,
+//! `UNUSED` entries) is skipped. The exporter writes the Dolphin layout, which
+//! both Dolphin and this parser read back.
+
+use crate::recompiler::ghidra::FunctionInfo;
+use std::collections::BTreeMap;
+
+/// One named symbol from a map.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapSymbol {
+    pub name: String,
+    pub size: u32,
+    /// Declared in an executable section (`.init`/`.text`).
+    pub is_code: bool,
+}
+
+/// Address → symbol table.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolMap {
+    symbols: BTreeMap<u32, MapSymbol>,
+}
+
+impl SymbolMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a CodeWarrior or Dolphin `.map`. When the same address appears
+    /// twice the first name wins (later ones are usually aliases).
+    pub fn parse(text: &str) -> Self {
+        let mut map = Self::new();
+        // Maps without section headers (hand-written ones) are all code.
+        let mut in_code = true;
+        for line in text.lines() {
+            let trimmed = line.trim();
+            if let Some(section) = trimmed.strip_suffix("section layout") {
+                in_code = matches!(section.trim(), ".init" | ".text");
+                continue;
+            }
+            if let Some((address, size, name)) = parse_symbol_line(trimmed) {
+                map.symbols.entry(address).or_insert(MapSymbol {
+                    name: name.to_string(),
+                    size,
+                    is_code: in_code,
+                });
+            }
+        }
+        map
+    }
+
+    pub fn insert(&mut self, address: u32, symbol: MapSymbol) {
+        self.symbols.insert(address, symbol);
+    }
+
+    pub fn get(&self, address: u32) -> Option<&MapSymbol> {
+        self.symbols.get(&address)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &MapSymbol)> {
+        self.symbols.iter().map(|(&a, s)| (a, s))
+    }
+
+    /// Rename discovered functions from the map, which is trusted over
+    /// discovery: an exact address match takes the map's name, and a code
+    /// symbol that starts inside a discovered function splits it there.
+    /// `functions` stays sorted by address. Returns how many functions now
+    /// carry a map name.
+    pub fn apply(&self, functions: &mut Vec<FunctionInfo>) -> usize {
+        functions.sort_by_key(|f| f.address);
+        let mut named = 0;
+        for (address, symbol) in self.iter() {
+            let idx = functions.partition_point(|f| f.address <= address);
+            let Some(containing) = idx.checked_sub(1).map(|i| &mut functions[i]) else {
+                continue;
+            };
+            if containing.address == address {
+                containing.name = symbol.name.clone();
+                named += 1;
+                continue;
+            }
+            let end = containing.address.wrapping_add(containing.size);
+            if !symbol.is_code || address >= end {
+                continue;
+            }
+            // Map symbol lands mid-function: the discovered boundary is wrong.
+            containing.size = address - containing.address;
+            let mut split = containing.clone();
+            split.address = address;
+            split.name = symbol.name.clone();
+            split.size = end - address;
+            split.basic_blocks.clear();
+            functions.insert(idx, split);
+            named += 1;
+        }
+        named
+    }
+}
+
+/// `(address, size, name)` from one symbol line, or `None` for anything else.
+fn parse_symbol_line(line: &str) -> Option<(u32, u32, &str)> {
+    let mut tokens = line.split_whitespace();
+    let _offset = hex(tokens.next()?)?;
+    let size = hex(tokens.next()?)?;
+    let address = hex(tokens.next()?)?;
+    let mut next = tokens.next()?;
+    // Newer CodeWarrior maps carry an 8-digit file offset before the alignment.
+    if next.len() == 8 {
+        hex(next)?;
+        next = tokens.next()?;
+    }
+    next.parse::<u32>().ok()?; // alignment
+    let name = tokens.next()?;
+    if address < 0x8000_0000 {
+        return None;
+    }
+    Some((address, size, name))
+}
+
+fn hex(token: &str) -> Option<u32> {
+    u32::from_str_radix(token, 16).ok()
+}
+
+/// Write `functions` as a Dolphin-layout `.map`.
+pub fn export_map(functions: &[FunctionInfo]) -> String {
+    let mut out = String::from(".text section layout\n");
+    for f in functions {
+        out.push_str(&format!(
+            "{:08x} {:08x} {:08x} 0 {}\n",
+            f.address, f.size, f.address, f.name
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn func(address: u32, size: u32) -> FunctionInfo {
+        FunctionInfo {
+            address,
+            name: format!("sub_{:08x}", address),
+            size,
+            calling_convention: "default".to_string(),
+            parameters: vec![],
+            return_type: None,
+            local_variables: vec![],
+            basic_blocks: vec![],
+        }
+    }
+
+    #[test]
+    fn parses_codewarrior_and_dolphin_lines() {
+        let map = SymbolMap::parse(
+            ".init section layout\n\
+             \x20 Starting        Virtual\n\
+             \x20 address  Size   address\n\
+             \x20 -----------------------\n\
+             \x20 00000000 000034 80003100  4 __start \tos.a os.o\n\
+             \x20 UNUSED   000010 ........ __unused os.a os.o\n\
+             .text section layout\n\
+             \x20 00000000 000120 80005000 00000300  4 OSInit \tos.a OSInit.o\n\
+             80006000 00000040 80006000 0 OSReport\n\
+             .data section layout\n\
+             \x20 00000000 000100 80200000  8 gStrings \tmain.o\n",
+        );
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get(0x8000_3100).unwrap().name, "__start");
+        assert_eq!(map.get(0x8000_5000).unwrap().size, 0x120);
+        assert_eq!(map.get(0x8000_6000).unwrap().name, "OSReport");
+        assert!(!map.get(0x8020_0000).unwrap().is_code);
+    }
+
+    #[test]
+    fn map_names_win_and_split_overlapping_functions() {
+        let map = SymbolMap::parse(
+            "80003000 00000010 80003000 0 main\n\
+             80003010 00000010 80003010 0 helper\n",
+        );
+        // Discovery merged both into one function
+        let mut functions = vec![func(0x8000_3000, 0x20)];
+        assert_eq!(map.apply(&mut functions), 2);
+        assert_eq!(functions.len(), 2);
+        assert_eq!(
+            (functions[0].name.as_str(), functions[0].size),
+            ("main", 0x10)
+        );
+        assert_eq!(
+            (
+                functions[1].name.as_str(),
+                functions[1].address,
+                functions[1].size
+            ),
+            ("helper", 0x8000_3010, 0x10)
+        );
+
+        let exported = export_map(&functions);
+        let reparsed = SymbolMap::parse(&exported);
+        assert_eq!(reparsed.get(0x8000_3010).unwrap().name, "helper");
+        assert_eq!(reparsed.get(0x8000_3010).unwrap().size, 0x10);
+    }
+}
@@ -8,8 +8,7 @@
 //! - **Type validation**: Type correctness (would use rustc or syn crate in full implementation)
 //! - **Semantic validation**: Semantic correctness (would use rustc in full implementation)
 
-use crate::recompiler::error::RecompilerError;
-use anyhow::Result;
+use crate::recompiler::error::{RecompileError, Result};
 
 /// Code validator for generated Rust code.
 pub struct CodeValidator;
@@ -45,46 +44,30 @@ impl CodeValidator {
 
         // Check for basic syntax issues
         if !code.contains("fn ") {
-            return Err(RecompilerError::ValidationError(
-                "Generated code must contain at least one function definition".to_string(),
-            )
-            .into());
+            return Err(RecompileError::ValidationError {
+                line: 0,
+                message: "Generated code must contain at least one function definition".to_string(),
+            });
         }
 
-        // Check balanced braces
+        // Check balanced braces, parentheses and brackets
         let open_braces: usize = code.matches('{').count();
-        let close_braces: usize = code.matches('}').count();
-        if open_braces != close_braces {
-            return Err(RecompilerError::ValidationError(
-                format!(
-                    "Unbalanced braces in generated code: {} open, {} close. This indicates a syntax error in code generation.",
-                    open_braces, close_braces
-                )
-            ).into());
-        }
-
-        // Check balanced parentheses
         let open_parens: usize = code.matches('(').count();
-        let close_parens: usize = code.matches(')').count();
-        if open_parens != close_parens {
-            return Err(RecompilerError::ValidationError(
-                format!(
-                    "Unbalanced parentheses in generated code: {} open, {} close. This indicates a syntax error in code generation.",
-                    open_parens, close_parens
-                )
-            ).into());
-        }
-
-        // Check balanced brackets
-        let open_brackets: usize = code.matches('[').count();
-        let close_brackets: usize = code.matches(']').count();
-        if open_brackets != close_brackets {
-            return Err(RecompilerError::ValidationError(
-                format!(
-                    "Unbalanced brackets in generated code: {} open, {} close. This indicates a syntax error in code generation.",
-                    open_brackets, close_brackets
-                )
-            ).into());
+        for (open, close, what) in [
+            ('{', '}', "braces"),
+            ('(', ')', "parentheses"),
+            ('[', ']', "brackets"),
+        ] {
+            let (opened, closed) = (code.matches(open).count(), code.matches(close).count());
+            if opened != closed {
+                return Err(RecompileError::ValidationError {
+                    line: unbalanced_line(code, open, close).unwrap_or(0),
+                    message: format!(
+                        "Unbalanced {} in generated code: {} open, {} close. This indicates a syntax error in code generation.",
+                        what, opened, closed
+                    ),
+                });
+            }
         }
 
         // Check for common syntax errors
@@ -135,3 +118,19 @@ impl CodeValidator {
         Self::validate_rust_code(function_code)
     }
 }
+
+/// 1-based line of the first unmatched `close`, or of the innermost `open`
+/// still unclosed at the end; `None` when balanced.
+fn unbalanced_line(code: &str, open: char, close: char) -> Option<usize> {
+    let mut unclosed: Vec<usize> = Vec::new();
+    for (i, line) in code.lines().enumerate() {
+        for c in line.chars() {
+            if c == open {
+                unclosed.push(i + 1);
+            } else if c == close && unclosed.pop().is_none() {
+                return Some(i + 1);
+            }
+        }
+    }
+    unclosed.last().copied()
+}
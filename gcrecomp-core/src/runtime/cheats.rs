@@ -0,0 +1,272 @@
+//! Cheat codes (Gecko / Action Replay RAM writes)
+//!
+//! Supports the constant-write code types shared by Gecko and decrypted Action
+//! Replay codes, which are encoded identically:
+//!
+//! | Code              | Effect                                                  |
+//! |-------------------|---------------------------------------------------------|
+//! | `00XXXXXX NNNN00YY` | write byte `YY` to `0x80XXXXXX`, repeated `NNNN+1` times |
+//! | `02XXXXXX NNNNYYYY` | write halfword `YYYY`, repeated `NNNN+1` times           |
+//! | `04XXXXXX YYYYYYYY` | write word `YYYYYYYY`                                    |
+//!
+//! `01`/`03`/`05` are the same with address bit 24 set. All writes are
+//! re-applied every frame, before the CPU runs, so the game can't overwrite them
+//! for long. Encrypted AR codes (`XXXX-XXXX-XXXXX`) must be decrypted first.
+
+use crate::runtime::memory::MemoryManager;
+use anyhow::{Context, Result};
+
+/// Base of main RAM; code addresses are offsets from it.
+const RAM_BASE: u32 = 0x8000_0000;
+/// End of main RAM (24MB).
+const RAM_END: u32 = 0x8180_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatOp {
+    Write8 {
+        address: u32,
+        value: u8,
+        count: u16,
+    },
+    Write16 {
+        address: u32,
+        value: u16,
+        count: u16,
+    },
+    Write32 {
+        address: u32,
+        value: u32,
+    },
+}
+
+impl CheatOp {
+    /// Decode one `XXXXXXXX YYYYYYYY` code line.
+    pub fn decode(addr_word: u32, value_word: u32) -> Result<Self> {
+        let code_type = (addr_word >> 24) & 0xFE;
+        let address = RAM_BASE + (addr_word & 0x01FF_FFFF);
+        let count = (value_word >> 16) as u16;
+
+        let (op, len) = match code_type {
+            0x00 => (
+                CheatOp::Write8 {
+                    address,
+                    value: value_word as u8,
+                    count,
+                },
+                count as u32 + 1,
+            ),
+            0x02 => (
+                CheatOp::Write16 {
+                    address,
+                    value: value_word as u16,
+                    count,
+                },
+                (count as u32 + 1) * 2,
+            ),
+            0x04 => (
+                CheatOp::Write32 {
+                    address,
+                    value: value_word,
+                },
+                4,
+            ),
+            other => anyhow::bail!("Unsupported cheat code type 0x{:02X}", other),
+        };
+
+        if address.checked_add(len).map_or(true, |end| end > RAM_END) {
+            anyhow::bail!(
+                "Cheat write 0x{:08X}+{} is outside main RAM (0x{:08X}-0x{:08X})",
+                address,
+                len,
+                RAM_BASE,
+                RAM_END
+            );
+        }
+        Ok(op)
+    }
+
+    pub fn apply(&self, memory: &mut MemoryManager) -> Result<()> {
+        match *self {
+            CheatOp::Write8 {
+                address,
+                value,
+                count,
+            } => {
+                for i in 0..=count as u32 {
+                    memory.write_u8(address + i, value)?;
+                }
+            }
+            CheatOp::Write16 {
+                address,
+                value,
+                count,
+            } => {
+                for i in 0..=count as u32 {
+                    memory.write_u16(address + i * 2, value)?;
+                }
+            }
+            CheatOp::Write32 { address, value } => memory.write_u32(address, value)?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheatCode {
+    pub name: String,
+    pub enabled: bool,
+    pub ops: Vec<CheatOp>,
+}
+
+impl CheatCode {
+    /// Parse whitespace-separated `XXXXXXXX YYYYYYYY` pairs.
+    pub fn parse(name: &str, text: &str) -> Result<Self> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() || words.len() % 2 != 0 {
+            anyhow::bail!("Cheat '{}' must be pairs of 8-digit hex words", name);
+        }
+        let ops = words
+            .chunks(2)
+            .map(|pair| {
+                let a = parse_hex_word(pair[0])?;
+                let v = parse_hex_word(pair[1])?;
+                CheatOp::decode(a, v)
+            })
+            .collect::<Result<Vec<_>>>()
+            .with_context(|| format!("Invalid cheat '{}'", name))?;
+        Ok(Self {
+            name: name.to_string(),
+            enabled: true,
+            ops,
+        })
+    }
+}
+
+fn parse_hex_word(s: &str) -> Result<u32> {
+    if s.len() != 8 {
+        anyhow::bail!("Expected 8 hex digits, got '{}'", s);
+    }
+    u32::from_str_radix(s, 16).with_context(|| format!("Invalid hex word '{}'", s))
+}
+
+/// Set of cheat codes applied to RAM once per frame.
+#[derive(Debug, Clone, Default)]
+pub struct CheatEngine {
+    codes: Vec<CheatCode>,
+}
+
+impl CheatEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, code: CheatCode) {
+        self.codes.push(code);
+    }
+
+    /// Parse a Dolphin-style code list: `$Name` starts a code, following lines
+    /// hold its code words. `[Section]` headers and `*` comments are ignored.
+    pub fn load_list(&mut self, text: &str) -> Result<usize> {
+        let mut parsed = 0;
+        let mut current: Option<(String, String)> = None;
+        let mut flush = |current: &mut Option<(String, String)>, codes: &mut Vec<CheatCode>| {
+            if let Some((name, body)) = current.take() {
+                codes.push(CheatCode::parse(&name, &body)?);
+                parsed += 1;
+            }
+            Ok::<(), anyhow::Error>(())
+        };
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('*') || line.starts_with('[') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('$') {
+                flush(&mut current, &mut self.codes)?;
+                current = Some((name.trim().to_string(), String::new()));
+            } else if let Some((_, body)) = current.as_mut() {
+                body.push(' ');
+                body.push_str(line);
+            }
+        }
+        flush(&mut current, &mut self.codes)?;
+        Ok(parsed)
+    }
+
+    pub fn codes(&self) -> &[CheatCode] {
+        &self.codes
+    }
+
+    /// Enable or disable a code by name. Returns false if no such code exists.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        let mut found = false;
+        for code in self.codes.iter_mut().filter(|c| c.name == name) {
+            code.enabled = enabled;
+            found = true;
+        }
+        found
+    }
+
+    /// Apply every enabled code. Call once per frame before running the CPU.
+    pub fn apply(&self, memory: &mut MemoryManager) -> Result<()> {
+        for code in self.codes.iter().filter(|c| c.enabled) {
+            for op in &code.ops {
+                op.apply(memory)
+                    .with_context(|| format!("Applying cheat '{}'", code.name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_write_applies_each_frame_until_disabled() {
+        let mut engine = CheatEngine::new();
+        engine.add(CheatCode::parse("Infinite lives", "04123450 00000063").unwrap());
+
+        let mut memory = MemoryManager::new();
+        engine.apply(&mut memory).unwrap();
+        assert_eq!(memory.read_u32(0x8012_3450).unwrap(), 0x63);
+
+        // Game overwrites it; next frame the cheat puts it back
+        memory.write_u32(0x8012_3450, 1).unwrap();
+        engine.apply(&mut memory).unwrap();
+        assert_eq!(memory.read_u32(0x8012_3450).unwrap(), 0x63);
+
+        assert!(engine.set_enabled("Infinite lives", false));
+        memory.write_u32(0x8012_3450, 1).unwrap();
+        engine.apply(&mut memory).unwrap();
+        assert_eq!(memory.read_u32(0x8012_3450).unwrap(), 1);
+    }
+
+    #[test]
+    fn list_parsing_and_repeat_writes() {
+        let mut engine = CheatEngine::new();
+        let n = engine
+            .load_list(
+                "[Gecko]\n$Max rupees\n02100000 0001FFFF\n* two halfwords\n$Flags\n00100010 000200AA\n",
+            )
+            .unwrap();
+        assert_eq!(n, 2);
+
+        let mut memory = MemoryManager::new();
+        engine.apply(&mut memory).unwrap();
+        assert_eq!(memory.read_u32(0x8010_0000).unwrap(), 0xFFFF_FFFF);
+        assert_eq!(
+            memory.read_bytes(0x8010_0010, 4).unwrap(),
+            [0xAA, 0xAA, 0xAA, 0]
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_unknown_types() {
+        // 0x81800000 is past the end of main RAM
+        assert!(CheatCode::parse("bad", "05800000 00000001").is_err());
+        assert!(CheatCode::parse("ptr", "48000000 80001000").is_err());
+        assert!(CheatCode::parse("odd", "04000000").is_err());
+    }
+}
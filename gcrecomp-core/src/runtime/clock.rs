@@ -0,0 +1,121 @@
+// Retired-instruction counter: the guest's own notion of elapsed work
+//
+// Generated code retires each basic block's instruction count at its leader
+// and the interpreter retires one instruction per step, so `instructions()`
+// depends only on what the game executed, never on host speed. The
+// instruction clock (`OsTimer::instruction_clock`, `[runtime] time_source =
+// "instructions"`) derives the timebase from it, which makes loops that
+// busy-wait on `OSGetTime` finish after the same number of instructions on
+// every run.
+//
+// On an instruction clock `FieldClock` also decides when a VI field ends: every
+// `instructions_per_field` retired instructions, the number that spans one
+// field of timebase. The clock ratio (`set_clock_ratio`, `[runtime]
+// cpu_clock_percent`) over- or underclocks the CPU against VI: at 2.0 twice as
+// many instructions run per field, and each advances the timebase half as far.
+
+use crate::runtime::sdk::timer::OsTimer;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+static RETIRED: AtomicU64 = AtomicU64::new(0);
+
+/// Accepted clock ratios. Far below 1.0 games miss every deadline; far above
+/// it they run entire frames of logic per field.
+pub const CLOCK_RATIO_RANGE: RangeInclusive<f64> = 0.25..=4.0;
+
+/// The clock ratio in thousandths.
+static RATIO_PERMILLE: AtomicU32 = AtomicU32::new(1000);
+
+/// Count `n` executed instructions. Guest code runs on one thread, so a plain
+/// load/store (no locked add) is enough and keeps the per-block cost low.
+#[inline]
+pub fn retire(n: u32) {
+    RETIRED.store(
+        RETIRED.load(Ordering::Relaxed).wrapping_add(u64::from(n)),
+        Ordering::Relaxed,
+    );
+}
+
+/// Instructions retired since startup.
+#[inline]
+pub fn instructions() -> u64 {
+    RETIRED.load(Ordering::Relaxed)
+}
+
+/// Run the CPU at `ratio` times its speed relative to VI, clamped to
+/// `CLOCK_RATIO_RANGE`. Returns the ratio applied. Anything but 1.0 trades
+/// timing accuracy for smoothness or for a slower game to study.
+pub fn set_clock_ratio(ratio: f64) -> f64 {
+    let ratio = if ratio.is_finite() {
+        ratio.clamp(*CLOCK_RATIO_RANGE.start(), *CLOCK_RATIO_RANGE.end())
+    } else {
+        1.0
+    };
+    let permille = (ratio * 1000.0).round() as u32;
+    RATIO_PERMILLE.store(permille, Ordering::Relaxed);
+    if permille != 1000 {
+        log::warn!("CPU clock at {ratio:.2}x: timing no longer matches the hardware");
+    }
+    clock_ratio()
+}
+
+pub fn clock_ratio() -> f64 {
+    f64::from(clock_ratio_permille()) / 1000.0
+}
+
+/// The clock ratio in thousandths, for integer timebase arithmetic.
+pub fn clock_ratio_permille() -> u32 {
+    RATIO_PERMILLE.load(Ordering::Relaxed)
+}
+
+/// Instructions in one VI field at `field_rate` Hz, when each advances the
+/// timebase `ticks_per_instruction` at a ratio of 1.0.
+pub fn instructions_per_field(field_rate: f64, ticks_per_instruction: u64) -> u64 {
+    let ticks = OsTimer::TIMEBASE_FREQ as f64 / field_rate;
+    let at_ratio = ticks / ticks_per_instruction.max(1) as f64 * clock_ratio();
+    (at_ratio.round() as u64).max(1)
+}
+
+/// Splits retired instructions into VI fields.
+#[derive(Debug, Clone)]
+pub struct FieldClock {
+    field_rate: f64,
+    ticks_per_instruction: u64,
+    /// `instructions()` at which the current field ends.
+    next: u64,
+}
+
+impl FieldClock {
+    /// A clock whose first field starts now.
+    pub fn new(field_rate: f64, ticks_per_instruction: u64) -> Self {
+        let mut clock = Self {
+            field_rate,
+            ticks_per_instruction,
+            next: 0,
+        };
+        clock.next = instructions() + clock.instructions_per_field();
+        clock
+    }
+
+    /// Follow a video mode change from the next field on.
+    pub fn set_field_rate(&mut self, field_rate: f64) {
+        self.field_rate = field_rate;
+    }
+
+    /// The length of the next field; follows `set_clock_ratio`.
+    pub fn instructions_per_field(&self) -> u64 {
+        instructions_per_field(self.field_rate, self.ticks_per_instruction)
+    }
+
+    /// Fields that ended since the last call.
+    pub fn fields_due(&mut self) -> u32 {
+        let now = instructions();
+        let mut due = 0;
+        while now >= self.next {
+            self.next += self.instructions_per_field();
+            due += 1;
+        }
+        due
+    }
+}
@@ -0,0 +1,315 @@
+// Differential testing against Dolphin
+//
+// Dolphin's debugger scripting can log the CPU state at every instruction of a
+// run. Given such a log and an instruction trace of the same code on our side
+// (`runtime::trace`), `align` walks both in lockstep and reports the first
+// instruction where they disagree, with the instructions leading up to it.
+//
+// Log format, one line per executed instruction, state *before* it executes
+// (as a code breakpoint sees it):
+//
+// ```text
+// # comment
+// PC=80003000 r3=0000000A r4=00000001 f1=1.5 LR=80001234 CTR=00000000 CR=20000000
+// MEM 80004000=0000002A
+// ```
+//
+// Registers are hex (a `0x` prefix is optional); FPRs are decimal as Dolphin
+// prints them, or raw bits with `0x`. `XER` and `FPSCR` are accepted too.
+// Only the registers a line lists are checked. `MEM addr=value` lines are word
+// checks against memory after the run. FPRs compare within `FLOAT_TOLERANCE`,
+// since Dolphin rounds them when printing.
+//
+// `compare_memory` checks two memories byte for byte over any number of
+// regions, for harnesses that have the expected memory as a whole rather
+// than as `MEM` lines.
+
+use crate::recompiler::disasm::disassemble;
+use crate::runtime::memory::MemoryManager;
+use crate::runtime::trace::{RegisterState, TraceEntry};
+use anyhow::{bail, Context, Result};
+use std::fmt;
+
+/// Relative tolerance for FPR comparisons (absolute below 1.0).
+pub const FLOAT_TOLERANCE: f64 = 1e-6;
+
+/// Instructions of context shown before a divergence.
+const CONTEXT: usize = 5;
+
+/// One logged instruction: its address and the registers it saw.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoggedStep {
+    pub pc: u32,
+    pub gpr: [Option<u32>; 32],
+    pub fpr: [Option<f64>; 32],
+    pub lr: Option<u32>,
+    pub ctr: Option<u32>,
+    pub cr: Option<u32>,
+    pub xer: Option<u32>,
+    pub fpscr: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DolphinLog {
+    pub steps: Vec<LoggedStep>,
+    /// `(address, word)` memory checks for the end of the run.
+    pub memory: Vec<(u32, u32)>,
+}
+
+impl DolphinLog {
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut log = DolphinLog::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = if let Some(rest) = line.strip_prefix("MEM ") {
+                parse_memory(rest).map(|m| log.memory.push(m))
+            } else {
+                parse_step(line).map(|s| log.steps.push(s))
+            };
+            parsed.with_context(|| format!("line {}: {line:?}", n + 1))?;
+        }
+        Ok(log)
+    }
+}
+
+fn parse_hex(value: &str) -> Result<u32> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u32::from_str_radix(digits, 16).with_context(|| format!("{value:?} is not hex"))
+}
+
+fn parse_memory(rest: &str) -> Result<(u32, u32)> {
+    let Some((addr, value)) = rest.trim().split_once('=') else {
+        bail!("expected MEM address=value");
+    };
+    Ok((parse_hex(addr.trim())?, parse_hex(value.trim())?))
+}
+
+fn parse_step(line: &str) -> Result<LoggedStep> {
+    let mut step = LoggedStep::default();
+    let mut pc = None;
+    for token in line.split_whitespace() {
+        let Some((key, value)) = token.split_once('=') else {
+            bail!("expected key=value, got {token:?}");
+        };
+        let key = key.to_ascii_lowercase();
+        match key.as_str() {
+            "pc" => pc = Some(parse_hex(value)?),
+            "lr" => step.lr = Some(parse_hex(value)?),
+            "ctr" => step.ctr = Some(parse_hex(value)?),
+            "cr" => step.cr = Some(parse_hex(value)?),
+            "xer" => step.xer = Some(parse_hex(value)?),
+            "fpscr" => step.fpscr = Some(parse_hex(value)?),
+            _ => {
+                let index = |prefix: char| {
+                    key.strip_prefix(prefix)
+                        .and_then(|n| n.parse::<usize>().ok())
+                        .filter(|&n| n < 32)
+                };
+                if let Some(r) = index('r') {
+                    step.gpr[r] = Some(parse_hex(value)?);
+                } else if let Some(f) = index('f') {
+                    step.fpr[f] = Some(match value.strip_prefix("0x") {
+                        Some(bits) => f64::from_bits(
+                            u64::from_str_radix(bits, 16)
+                                .with_context(|| format!("{value:?} is not hex"))?,
+                        ),
+                        None => value
+                            .parse()
+                            .with_context(|| format!("{value:?} is not a number"))?,
+                    });
+                } else {
+                    bail!("unknown register {key:?}");
+                }
+            }
+        }
+    }
+    step.pc = pc.context("no PC")?;
+    Ok(step)
+}
+
+fn floats_match(expected: f64, actual: f64) -> bool {
+    if expected.is_nan() || actual.is_nan() {
+        return expected.is_nan() && actual.is_nan();
+    }
+    expected == actual || (expected - actual).abs() <= FLOAT_TOLERANCE * expected.abs().max(1.0)
+}
+
+/// Differences between a logged state and ours, as `r3: expected 0x..., got 0x...`.
+/// Empty when every register the log lists matches.
+pub fn compare_execution_results(expected: &LoggedStep, actual: &RegisterState) -> Vec<String> {
+    let mut diffs = Vec::new();
+    let mut word = |name: String, expected: Option<u32>, actual: u32| {
+        if let Some(e) = expected.filter(|&e| e != actual) {
+            diffs.push(format!("{name}: expected 0x{e:08X}, got 0x{actual:08X}"));
+        }
+    };
+    for r in 0..32 {
+        word(format!("r{r}"), expected.gpr[r], actual.gpr[r]);
+    }
+    word("lr".into(), expected.lr, actual.lr);
+    word("ctr".into(), expected.ctr, actual.ctr);
+    word("cr".into(), expected.cr, actual.cr);
+    word("xer".into(), expected.xer, actual.xer);
+    word("fpscr".into(), expected.fpscr, actual.fpscr);
+    for f in 0..32 {
+        let ours = f64::from_bits(actual.fpr[f]);
+        if let Some(e) = expected.fpr[f].filter(|&e| !floats_match(e, ours)) {
+            diffs.push(format!("f{f}: expected {e}, got {ours}"));
+        }
+    }
+    diffs
+}
+
+/// Byte differences between `expected` and `actual` over each `(address,
+/// len)` region, in region order, as `[0x...]: expected 0x.., got 0x..`. A
+/// byte either side can't read is reported as unmapped on that side.
+pub fn compare_memory(
+    expected: &MemoryManager,
+    actual: &MemoryManager,
+    regions: &[(u32, usize)],
+) -> Vec<String> {
+    let mut diffs = Vec::new();
+    for &(start, len) in regions {
+        for offset in 0..len {
+            let address = start.wrapping_add(offset as u32);
+            let byte = |memory: &MemoryManager| {
+                memory
+                    .read_u8(address)
+                    .map_or_else(|_| "unmapped".to_string(), |b| format!("0x{b:02X}"))
+            };
+            let (e, a) = (byte(expected), byte(actual));
+            if e != a {
+                diffs.push(format!("[0x{address:08X}]: expected {e}, got {a}"));
+            }
+        }
+    }
+    diffs
+}
+
+/// `compare_memory` over one region.
+pub fn compare_memory_region(
+    expected: &MemoryManager,
+    actual: &MemoryManager,
+    address: u32,
+    len: usize,
+) -> Vec<String> {
+    compare_memory(expected, actual, &[(address, len)])
+}
+
+/// Where the two runs first disagree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Index of the instruction in both logs; `None` for the end-of-run
+    /// memory checks.
+    pub step: Option<usize>,
+    pub pc: u32,
+    pub differences: Vec<String>,
+    /// The instructions before it, disassembled, oldest first.
+    pub context: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.step {
+            Some(step) => writeln!(
+                f,
+                "Diverged from Dolphin at step {step} (0x{:08X}):",
+                self.pc
+            )?,
+            None => writeln!(f, "Memory differs from Dolphin after the run:")?,
+        }
+        for diff in &self.differences {
+            writeln!(f, "  {diff}")?;
+        }
+        if !self.context.is_empty() {
+            writeln!(f, "Preceded by:")?;
+            for line in &self.context {
+                writeln!(f, "  {line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Walk `log` and `trace` in lockstep. `initial` is our register state before
+/// the first traced instruction (what the log's first line describes).
+/// `memory`, if given, is checked against the log's `MEM` lines afterwards.
+pub fn align(
+    log: &DolphinLog,
+    initial: &RegisterState,
+    trace: &[TraceEntry],
+    memory: Option<&MemoryManager>,
+) -> std::result::Result<(), Divergence> {
+    let context = |upto: usize| -> Vec<String> {
+        trace[upto.saturating_sub(CONTEXT)..upto]
+            .iter()
+            .map(|e| format!("{:08X}  {}", e.address, disassemble(e.raw, e.address)))
+            .collect()
+    };
+    for (i, step) in log.steps.iter().enumerate() {
+        let Some(entry) = trace.get(i) else {
+            return Err(Divergence {
+                step: Some(i),
+                pc: step.pc,
+                differences: vec![format!(
+                    "Dolphin ran {} instructions, we stopped after {}",
+                    log.steps.len(),
+                    trace.len()
+                )],
+                context: context(trace.len()),
+            });
+        };
+        let mut differences = Vec::new();
+        if entry.address != step.pc {
+            differences.push(format!(
+                "pc: expected 0x{:08X}, got 0x{:08X}",
+                step.pc, entry.address
+            ));
+        }
+        let before = if i == 0 {
+            initial
+        } else {
+            &trace[i - 1].registers
+        };
+        differences.extend(compare_execution_results(step, before));
+        if !differences.is_empty() {
+            return Err(Divergence {
+                step: Some(i),
+                pc: step.pc,
+                differences,
+                context: context(i),
+            });
+        }
+    }
+
+    if let Some(memory) = memory {
+        let differences: Vec<String> = log
+            .memory
+            .iter()
+            .filter_map(|&(addr, expected)| {
+                let actual = memory.read_u32(addr).ok();
+                (actual != Some(expected)).then(|| match actual {
+                    Some(a) => format!("[0x{addr:08X}]: expected 0x{expected:08X}, got 0x{a:08X}"),
+                    None => format!("[0x{addr:08X}]: expected 0x{expected:08X}, unmapped"),
+                })
+            })
+            .collect();
+        if !differences.is_empty() {
+            return Err(Divergence {
+                step: None,
+                pc: trace.last().map_or(0, |e| e.address),
+                differences,
+                context: context(trace.len()),
+            });
+        }
+    }
+    Ok(())
+}
@@ -1,5 +1,13 @@
 // CPU context
+
+/// XER summary overflow (sticky), overflow and carry bits.
+pub const XER_SO: u32 = 0x8000_0000;
+pub const XER_OV: u32 = 0x4000_0000;
+pub const XER_CA: u32 = 0x2000_0000;
+
+/// `repr(C)` so hot-reloaded libraries (`runtime::hot_reload`) share the layout.
 #[derive(Debug, Clone)]
+#[repr(C)]
 pub struct CpuContext {
     pub gpr: [u32; 32], // General Purpose Registers (r0-r31)
     pub pc: u32,        // Program Counter
@@ -69,6 +77,35 @@ impl CpuContext {
             self.fpr[reg as usize] = value;
         }
     }
+
+    /// Record an `o`-form result: OV is set or cleared, SO only ever set.
+    pub fn set_overflow(&mut self, overflow: bool) {
+        if overflow {
+            self.xer |= XER_OV | XER_SO;
+        } else {
+            self.xer &= !XER_OV;
+        }
+    }
+}
+
+/// `divw`: signed `a / b` and whether it overflowed. Division by zero and
+/// `0x80000000 / -1` are undefined on PowerPC and don't trap; Gekko leaves -1
+/// for a negative dividend and 0 otherwise.
+pub fn divw(a: u32, b: u32) -> (u32, bool) {
+    match (a as i32).checked_div(b as i32) {
+        Some(q) => (q as u32, false),
+        None if (a as i32) < 0 => (u32::MAX, true),
+        None => (0, true),
+    }
+}
+
+/// `divwu`: unsigned `a / b` and whether it overflowed (division by zero,
+/// which leaves 0).
+pub fn divwu(a: u32, b: u32) -> (u32, bool) {
+    match a.checked_div(b) {
+        Some(q) => (q, false),
+        None => (0, true),
+    }
 }
 
 impl Default for CpuContext {
@@ -0,0 +1,119 @@
+// Debugger hooks for recompiled code: software breakpoints and single-step
+//
+// Generated code calls `check_breakpoint` at the start of every basic block, so
+// breakpoints trap at block granularity: an address that isn't a block leader
+// never fires, and a single step runs to the next block. The check is a single
+// relaxed atomic load unless a debugger has armed something.
+pub mod gdbstub;
+
+use crate::runtime::context::CpuContext;
+use crate::runtime::memory::MemoryManager;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Why execution stopped and handed control to the debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint,
+    Step,
+    /// User interrupt (Ctrl-C in gdb) or initial attach.
+    Interrupt,
+}
+
+/// Called on the recompiled thread while it is halted. Returns once the
+/// debugger resumes; set `set_stepping(true)` before returning to stop again at
+/// the next block. Returning false uninstalls the handler (debugger detached).
+pub type TrapHandler =
+    Box<dyn FnMut(StopReason, &mut CpuContext, &mut MemoryManager) -> bool + Send>;
+
+/// Fast-path gate: true when any breakpoint is set or stepping is armed.
+static ARMED: AtomicBool = AtomicBool::new(false);
+static STEPPING: AtomicBool = AtomicBool::new(false);
+static BREAKPOINTS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+static HANDLER: Mutex<Option<TrapHandler>> = Mutex::new(None);
+
+fn rearm(breakpoints: &BTreeSet<u32>) {
+    ARMED.store(
+        !breakpoints.is_empty() || STEPPING.load(Ordering::Relaxed),
+        Ordering::Relaxed,
+    );
+}
+
+fn breakpoints() -> std::sync::MutexGuard<'static, BTreeSet<u32>> {
+    BREAKPOINTS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns false if a breakpoint was already set at `addr`.
+pub fn add_breakpoint(addr: u32) -> bool {
+    let mut bps = breakpoints();
+    let added = bps.insert(addr);
+    rearm(&bps);
+    added
+}
+
+/// Returns false if no breakpoint was set at `addr`.
+pub fn remove_breakpoint(addr: u32) -> bool {
+    let mut bps = breakpoints();
+    let removed = bps.remove(&addr);
+    rearm(&bps);
+    removed
+}
+
+pub fn clear_breakpoints() {
+    let mut bps = breakpoints();
+    bps.clear();
+    rearm(&bps);
+}
+
+pub fn has_breakpoint(addr: u32) -> bool {
+    breakpoints().contains(&addr)
+}
+
+/// Stop at the next block boundary regardless of breakpoints.
+pub fn set_stepping(stepping: bool) {
+    STEPPING.store(stepping, Ordering::Relaxed);
+    rearm(&breakpoints());
+}
+
+/// Install the handler run when a breakpoint or step traps. Without one, traps
+/// are ignored.
+pub fn set_trap_handler(handler: Option<TrapHandler>) {
+    *HANDLER.lock().unwrap_or_else(|e| e.into_inner()) = handler;
+}
+
+/// Called by generated code at each block leader `addr`.
+#[inline]
+pub fn check_breakpoint(addr: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) {
+    if ARMED.load(Ordering::Relaxed) {
+        trap_slow(addr, ctx, memory);
+    }
+}
+
+#[cold]
+fn trap_slow(addr: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) {
+    let reason = if STEPPING.swap(false, Ordering::Relaxed) {
+        StopReason::Step
+    } else if has_breakpoint(addr) {
+        StopReason::Breakpoint
+    } else {
+        return;
+    };
+    rearm(&breakpoints());
+    ctx.pc = addr;
+    halt(reason, ctx, memory);
+}
+
+/// Hand control to the installed handler, e.g. for the initial attach.
+pub fn halt(reason: StopReason, ctx: &mut CpuContext, memory: &mut MemoryManager) {
+    // Take the handler out so it can touch breakpoints/stepping (or install a
+    // replacement) without deadlocking, then put it back if it stays attached.
+    let taken = HANDLER.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(mut handler) = taken {
+        let keep = handler(reason, ctx, memory);
+        let mut slot = HANDLER.lock().unwrap_or_else(|e| e.into_inner());
+        if keep && slot.is_none() {
+            *slot = Some(handler);
+        }
+    }
+}
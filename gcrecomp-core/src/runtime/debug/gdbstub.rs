@@ -0,0 +1,484 @@
+// GDB remote serial protocol stub over TCP
+//
+// Attach with `gdb -ex 'set arch powerpc:750' -ex 'target remote host:port'`.
+// The register file follows GDB's 32-bit PowerPC numbering: r0-r31, f0-f31,
+// then pc, msr, cr, lr, ctr, xer, fpscr. All values are big-endian on the wire,
+// matching the GameCube.
+//
+// The stub is synchronous: whoever halts (a trap in recompiled code, or the
+// frame loop on Ctrl-C) runs `GdbServer::session` on its own thread until gdb
+// continues or steps.
+use super::{StopReason, TrapHandler};
+use crate::runtime::context::CpuContext;
+use crate::runtime::memory::MemoryManager;
+use anyhow::Result;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+
+/// GDB register numbers past the GPR/FPR banks.
+const REG_PC: usize = 64;
+const REG_MSR: usize = 65;
+const REG_CR: usize = 66;
+const REG_LR: usize = 67;
+const REG_CTR: usize = 68;
+const REG_XER: usize = 69;
+const REG_FPSCR: usize = 70;
+const NUM_REGS: usize = 71;
+
+/// SIGTRAP, reported for every stop.
+const SIGTRAP: u8 = 5;
+
+/// How execution continues after a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    Step,
+    /// gdb detached or killed the session; breakpoints are cleared.
+    Detach,
+}
+
+/// Result of handling one packet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Send this payload back (an empty payload means "unsupported").
+    Reply(String),
+    /// Resume execution; the stop reply is sent at the next halt.
+    Resume(Resume),
+}
+
+/// Packet-level protocol state, independent of the transport.
+#[derive(Debug, Default)]
+pub struct GdbStub {
+    /// `QStartNoAckMode` negotiated: no `+`/`-` acknowledgements.
+    no_ack: bool,
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle_packet(
+        &mut self,
+        packet: &str,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+    ) -> Action {
+        let reply = |s: &str| Action::Reply(s.to_string());
+        let (cmd, args) = match packet.char_indices().nth(1) {
+            Some((i, _)) => packet.split_at(i),
+            None => (packet, ""),
+        };
+        match cmd {
+            "?" => Action::Reply(stop_reply(StopReason::Interrupt)),
+            "g" => Action::Reply((0..NUM_REGS).map(|n| read_register(ctx, n)).collect()),
+            "G" => {
+                let mut rest = args;
+                for n in 0..NUM_REGS {
+                    let width = register_width(n) * 2;
+                    if rest.len() < width || write_register(ctx, n, &rest[..width]).is_none() {
+                        return reply("E01");
+                    }
+                    rest = &rest[width..];
+                }
+                reply("OK")
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(n) if n < NUM_REGS => Action::Reply(read_register(ctx, n)),
+                _ => reply("E01"),
+            },
+            "P" => {
+                let ok = args.split_once('=').and_then(|(n, v)| {
+                    let n = usize::from_str_radix(n, 16)
+                        .ok()
+                        .filter(|&n| n < NUM_REGS)?;
+                    write_register(ctx, n, v)
+                });
+                reply(if ok.is_some() { "OK" } else { "E01" })
+            }
+            "m" => match parse_addr_len(args) {
+                Some((addr, len)) => match memory.read_bytes(addr, len) {
+                    Ok(bytes) => Action::Reply(to_hex(&bytes)),
+                    Err(_) => reply("E14"),
+                },
+                None => reply("E01"),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_addr_len(range)?;
+                    let bytes = from_hex(data).filter(|b| b.len() == len)?;
+                    Some((addr, bytes))
+                });
+                match parsed {
+                    Some((addr, bytes)) => match memory.write_bytes(addr, &bytes) {
+                        Ok(()) => reply("OK"),
+                        Err(_) => reply("E14"),
+                    },
+                    None => reply("E01"),
+                }
+            }
+            "Z" | "z" => {
+                // Only software breakpoints (type 0); gdb falls back on "".
+                let Some(addr) = args
+                    .strip_prefix("0,")
+                    .and_then(|a| a.split(',').next())
+                    .and_then(|a| u32::from_str_radix(a, 16).ok())
+                else {
+                    return reply("");
+                };
+                if cmd == "Z" {
+                    super::add_breakpoint(addr);
+                } else {
+                    super::remove_breakpoint(addr);
+                }
+                reply("OK")
+            }
+            "c" => Action::Resume(Resume::Continue),
+            "s" => Action::Resume(Resume::Step),
+            "D" | "k" => Action::Resume(Resume::Detach),
+            "H" | "T" => reply("OK"),
+            _ => self.handle_query(packet),
+        }
+    }
+
+    fn handle_query(&mut self, packet: &str) -> Action {
+        let reply = match packet {
+            p if p.starts_with("qSupported") => "PacketSize=4000;QStartNoAckMode+;swbreak+",
+            "QStartNoAckMode" => {
+                self.no_ack = true;
+                "OK"
+            }
+            "qAttached" => "1",
+            "qC" => "QC1",
+            "qfThreadInfo" => "m1",
+            "qsThreadInfo" => "l",
+            "qOffsets" => "Text=0;Data=0;Bss=0",
+            _ => "",
+        };
+        Action::Reply(reply.to_string())
+    }
+}
+
+fn stop_reply(_reason: StopReason) -> String {
+    // gdb only distinguishes signals here; every stop is a SIGTRAP.
+    format!("S{:02x}", SIGTRAP)
+}
+
+fn register_width(n: usize) -> usize {
+    if (32..64).contains(&n) {
+        8
+    } else {
+        4
+    }
+}
+
+fn read_register(ctx: &CpuContext, n: usize) -> String {
+    match n {
+        0..=31 => to_hex(&ctx.gpr[n].to_be_bytes()),
+        32..=63 => to_hex(&ctx.fpr[n - 32].to_bits().to_be_bytes()),
+        _ => {
+            let v = match n {
+                REG_PC => ctx.pc,
+                REG_MSR => ctx.msr,
+                REG_CR => ctx.cr,
+                REG_LR => ctx.lr,
+                REG_CTR => ctx.ctr,
+                REG_XER => ctx.xer,
+                REG_FPSCR => ctx.fpscr,
+                _ => 0,
+            };
+            to_hex(&v.to_be_bytes())
+        }
+    }
+}
+
+fn write_register(ctx: &mut CpuContext, n: usize, hex: &str) -> Option<()> {
+    let bytes = from_hex(hex).filter(|b| b.len() == register_width(n))?;
+    if (32..64).contains(&n) {
+        ctx.fpr[n - 32] = f64::from_bits(u64::from_be_bytes(bytes.try_into().ok()?));
+        return Some(());
+    }
+    let v = u32::from_be_bytes(bytes.try_into().ok()?);
+    match n {
+        0..=31 => ctx.gpr[n] = v,
+        REG_PC => ctx.pc = v,
+        REG_MSR => ctx.msr = v,
+        REG_CR => ctx.cr = v,
+        REG_LR => ctx.lr = v,
+        REG_CTR => ctx.ctr = v,
+        REG_XER => ctx.xer = v,
+        REG_FPSCR => ctx.fpscr = v,
+        _ => return None,
+    }
+    Some(())
+}
+
+fn parse_addr_len(s: &str) -> Option<(u32, usize)> {
+    let (addr, len) = s.split_once(',')?;
+    Some((
+        u32::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 != 0 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Frame a payload as `$payload#checksum`.
+pub fn encode_packet(payload: &str) -> String {
+    let sum = payload.bytes().fold(0u8, |a, b| a.wrapping_add(b));
+    format!("${payload}#{sum:02x}")
+}
+
+/// Pull the next complete `$...#xx` packet out of `buf`, dropping acks and
+/// noise before it. Returns the payload and whether its checksum matched.
+pub fn decode_packet(buf: &mut Vec<u8>) -> Option<(String, bool)> {
+    let start = buf.iter().position(|&b| b == b'$')?;
+    let hash = start + buf[start..].iter().position(|&b| b == b'#')?;
+    if buf.len() < hash + 3 {
+        return None;
+    }
+    let payload = String::from_utf8_lossy(&buf[start + 1..hash]).into_owned();
+    let expected = std::str::from_utf8(&buf[hash + 1..hash + 3])
+        .ok()
+        .and_then(|s| u8::from_str_radix(s, 16).ok());
+    let sum = payload.bytes().fold(0u8, |a, b| a.wrapping_add(b));
+    buf.drain(..hash + 3);
+    Some((payload, expected == Some(sum)))
+}
+
+/// A connected gdb client.
+pub struct GdbServer {
+    stream: TcpStream,
+    stub: GdbStub,
+    buf: Vec<u8>,
+    /// gdb is waiting on a `c`/`s`; the next halt answers it with a stop reply.
+    resumed: bool,
+}
+
+impl GdbServer {
+    /// Block until gdb connects on `addr`.
+    pub fn accept(addr: impl ToSocketAddrs) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        log::info!("Waiting for gdb on {}", listener.local_addr()?);
+        let (stream, peer) = listener.accept()?;
+        log::info!("gdb connected from {peer}");
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            stub: GdbStub::new(),
+            buf: Vec::new(),
+            resumed: false,
+        })
+    }
+
+    fn send(&mut self, payload: &str) -> Result<()> {
+        self.stream.write_all(encode_packet(payload).as_bytes())?;
+        Ok(())
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        let mut chunk = [0u8; 4096];
+        let n = self.stream.read(&mut chunk)?;
+        if n == 0 {
+            anyhow::bail!("gdb disconnected");
+        }
+        self.buf.extend_from_slice(&chunk[..n]);
+        Ok(())
+    }
+
+    /// Non-blocking check for gdb's Ctrl-C (a raw 0x03 byte) while running.
+    pub fn poll_interrupt(&mut self) -> Result<bool> {
+        self.stream.set_nonblocking(true)?;
+        let mut chunk = [0u8; 256];
+        let read = self.stream.read(&mut chunk);
+        self.stream.set_nonblocking(false)?;
+        match read {
+            Ok(0) => anyhow::bail!("gdb disconnected"),
+            Ok(n) => {
+                self.buf.extend_from_slice(&chunk[..n]);
+                Ok(self.take_interrupt())
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(self.take_interrupt()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn take_interrupt(&mut self) -> bool {
+        let before = self.buf.len();
+        self.buf.retain(|&b| b != 0x03);
+        self.buf.len() != before
+    }
+
+    /// Serve packets while halted, until gdb continues, steps or detaches.
+    pub fn session(
+        &mut self,
+        reason: StopReason,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+    ) -> Result<Resume> {
+        if self.resumed {
+            self.send(&stop_reply(reason))?;
+            self.resumed = false;
+        }
+        loop {
+            let Some((packet, valid)) = decode_packet(&mut self.buf) else {
+                self.fill()?;
+                continue;
+            };
+            if !self.stub.no_ack {
+                self.stream.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if !valid {
+                continue;
+            }
+            match self.stub.handle_packet(&packet, ctx, memory) {
+                Action::Reply(payload) => self.send(&payload)?,
+                Action::Resume(Resume::Detach) => {
+                    // `k` expects no reply; `D` wants an OK.
+                    if packet.starts_with('D') {
+                        self.send("OK")?;
+                    }
+                    super::clear_breakpoints();
+                    super::set_stepping(false);
+                    return Ok(Resume::Detach);
+                }
+                Action::Resume(resume) => {
+                    self.resumed = true;
+                    return Ok(resume);
+                }
+            }
+        }
+    }
+}
+
+/// Route recompiled-code traps into `server`. The handler uninstalls itself
+/// when gdb detaches or the connection drops.
+pub fn install(server: Arc<Mutex<GdbServer>>) {
+    let handler: TrapHandler = Box::new(move |reason, ctx, memory| {
+        let mut server = server.lock().unwrap_or_else(|e| e.into_inner());
+        match server.session(reason, ctx, memory) {
+            Ok(Resume::Continue) => true,
+            Ok(Resume::Step) => {
+                super::set_stepping(true);
+                true
+            }
+            Ok(Resume::Detach) => false,
+            Err(e) => {
+                log::warn!("gdb session ended: {e}");
+                super::clear_breakpoints();
+                false
+            }
+        }
+    });
+    super::set_trap_handler(Some(handler));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(action: Action) -> String {
+        match action {
+            Action::Reply(s) => s,
+            other => panic!("expected reply, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn register_memory_and_breakpoint_packets() {
+        let mut stub = GdbStub::new();
+        let mut ctx = CpuContext::new();
+        let mut memory = MemoryManager::new();
+        ctx.gpr[1] = 0x817F_FF00;
+        ctx.gpr[3] = 0xDEAD_BEEF;
+        ctx.fpr[0] = 1.0;
+        ctx.pc = 0x8000_3100;
+        ctx.lr = 0x8000_2000;
+        memory.write_u32(0x8000_1000, 0x3860_0001).unwrap();
+
+        let regs = reply(stub.handle_packet("g", &mut ctx, &mut memory));
+        assert_eq!(regs.len(), (32 * 4 + 32 * 8 + 7 * 4) * 2);
+        assert_eq!(&regs[8..16], "817fff00");
+        assert_eq!(&regs[24..32], "deadbeef");
+        // f0 follows the 32 GPRs
+        assert_eq!(&regs[256..272], "3ff0000000000000");
+        // pc, then msr, cr, lr
+        assert_eq!(&regs[768..776], "80003100");
+        assert_eq!(&regs[792..800], "80002000");
+
+        assert_eq!(
+            reply(stub.handle_packet("m80001000,4", &mut ctx, &mut memory)),
+            "38600001"
+        );
+        assert_eq!(
+            reply(stub.handle_packet("m00000010,4", &mut ctx, &mut memory)),
+            "E14"
+        );
+
+        assert_eq!(
+            reply(stub.handle_packet("Z0,80001234,4", &mut ctx, &mut memory)),
+            "OK"
+        );
+        assert!(crate::runtime::debug::has_breakpoint(0x8000_1234));
+        assert_eq!(
+            reply(stub.handle_packet("z0,80001234,4", &mut ctx, &mut memory)),
+            "OK"
+        );
+        assert!(!crate::runtime::debug::has_breakpoint(0x8000_1234));
+        // Hardware watchpoints aren't supported
+        assert_eq!(
+            reply(stub.handle_packet("Z2,80001234,4", &mut ctx, &mut memory)),
+            ""
+        );
+    }
+
+    #[test]
+    fn register_and_memory_writes() {
+        let mut stub = GdbStub::new();
+        let mut ctx = CpuContext::new();
+        let mut memory = MemoryManager::new();
+
+        assert_eq!(
+            reply(stub.handle_packet("P40=80004000", &mut ctx, &mut memory)),
+            "OK"
+        );
+        assert_eq!(ctx.pc, 0x8000_4000);
+        assert_eq!(
+            reply(stub.handle_packet("M80002000,2:abcd", &mut ctx, &mut memory)),
+            "OK"
+        );
+        assert_eq!(memory.read_u16(0x8000_2000).unwrap(), 0xABCD);
+        assert_eq!(
+            stub.handle_packet("s", &mut ctx, &mut memory),
+            Action::Resume(Resume::Step)
+        );
+    }
+
+    #[test]
+    fn packet_framing() {
+        assert_eq!(encode_packet("OK"), "$OK#9a");
+        // Leading ack, a good packet, then one with a corrupt checksum
+        let mut buf = b"+$m80000000,4#55$g#00".to_vec();
+        assert_eq!(
+            decode_packet(&mut buf),
+            Some(("m80000000,4".to_string(), true))
+        );
+        assert_eq!(decode_packet(&mut buf), Some(("g".to_string(), false)));
+        assert!(buf.is_empty());
+        let mut partial = b"$g#6".to_vec();
+        assert_eq!(decode_packet(&mut partial), None);
+    }
+}
@@ -0,0 +1,288 @@
+// Crash reports
+//
+// When the game dies, `generate_crash_bundle` writes one zip a user can attach
+// to a bug report:
+//
+// ```text
+// error.txt      what failed
+// trace.txt      the last instructions executed (the watchdog's trace ring)
+// context.txt    every register
+// callstack.txt  the guest calls active at the failure, outermost first
+// gx.txt         the GX state summary, when the host has a renderer
+// mods.txt       loaded mods and their versions
+// config.txt     each setting and where it came from
+// version.txt    gcrecomp version and host platform
+// ```
+//
+// The call stack comes from `FunctionCallLogger`, which follows `trampoline`
+// while enabled. Absolute paths in every entry (config sources, texture packs,
+// error messages) are cut down to their file name by `redact_paths`, so a
+// bundle doesn't give away the user's directory layout or account name.
+
+use crate::config::LoadedConfig;
+use crate::recompiler::disasm::disassemble;
+use crate::runtime::context::CpuContext;
+use crate::runtime::memory::MemoryManager;
+use crate::runtime::watchdog;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CALL_LOGGING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static STACK: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    static FAILED_STACK: RefCell<Option<Vec<u32>>> = const { RefCell::new(None) };
+}
+
+/// The guest call stack, tracked through `trampoline` on each thread. Off by
+/// default, as it costs a push and a pop per call. A tail call replaces its
+/// caller's frame, as it does on the hardware.
+pub struct FunctionCallLogger;
+
+impl FunctionCallLogger {
+    pub fn enable() {
+        CALL_LOGGING.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disable() {
+        CALL_LOGGING.store(false, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_enabled() -> bool {
+        CALL_LOGGING.load(Ordering::Relaxed)
+    }
+
+    /// This thread's calls in progress, outermost first.
+    pub fn stack() -> Vec<u32> {
+        STACK.with(|s| s.borrow().clone())
+    }
+
+    /// The stack as it was when a call first failed on this thread (the
+    /// innermost failing call last), if one has.
+    pub fn failure_stack() -> Option<Vec<u32>> {
+        FAILED_STACK.with(|s| s.borrow().clone())
+    }
+
+    /// Forget this thread's stack and recorded failure.
+    pub fn reset() {
+        STACK.with(|s| s.borrow_mut().clear());
+        FAILED_STACK.with(|s| *s.borrow_mut() = None);
+    }
+
+    pub(crate) fn enter(address: u32) {
+        STACK.with(|s| s.borrow_mut().push(address));
+    }
+
+    pub(crate) fn leave(failed: bool) {
+        STACK.with(|s| {
+            let mut stack = s.borrow_mut();
+            if failed {
+                FAILED_STACK.with(|f| {
+                    f.borrow_mut().get_or_insert_with(|| stack.clone());
+                });
+            }
+            stack.pop();
+        });
+    }
+}
+
+/// A mod the game was running with (a Lua script, a texture pack).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedMod {
+    pub name: String,
+    pub version: String,
+}
+
+impl LoadedMod {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+        }
+    }
+}
+
+/// What the host knows about a crash. The trace and call stack are read from
+/// the current thread when the bundle is written.
+#[derive(Default)]
+pub struct CrashReport<'a> {
+    pub error: String,
+    pub context: Option<&'a CpuContext>,
+    /// Lets the trace show instructions, not just addresses.
+    pub memory: Option<&'a MemoryManager>,
+    /// `GxState::summary` of the renderer.
+    pub gx_summary: Option<String>,
+    pub mods: Vec<LoadedMod>,
+    pub config: Option<&'a LoadedConfig>,
+}
+
+impl CrashReport<'_> {
+    /// The bundle's `(file name, contents)` entries, paths redacted.
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let mut entries = vec![
+            ("error.txt", format!("{}\n", self.error)),
+            ("trace.txt", self.trace()),
+            (
+                "context.txt",
+                self.context
+                    .map(format_context)
+                    .unwrap_or_else(|| "no CPU context\n".to_string()),
+            ),
+            ("callstack.txt", self.call_stack()),
+            (
+                "gx.txt",
+                self.gx_summary
+                    .clone()
+                    .unwrap_or_else(|| "no renderer\n".to_string()),
+            ),
+            ("mods.txt", self.mod_list()),
+            (
+                "config.txt",
+                self.config
+                    .map(LoadedConfig::describe)
+                    .unwrap_or_else(|| "no config loaded\n".to_string()),
+            ),
+            (
+                "version.txt",
+                format!(
+                    "gcrecomp {}\n{}-{}\n",
+                    env!("CARGO_PKG_VERSION"),
+                    std::env::consts::OS,
+                    std::env::consts::ARCH
+                ),
+            ),
+        ];
+        for (_, text) in &mut entries {
+            *text = redact_paths(text);
+        }
+        entries
+    }
+
+    fn trace(&self) -> String {
+        let pcs = watchdog::recent_pcs();
+        if pcs.is_empty() {
+            return "no instructions traced\n".to_string();
+        }
+        let mut out = String::new();
+        for pc in pcs {
+            match self.memory.and_then(|m| m.read_u32(pc).ok()) {
+                Some(raw) => {
+                    let _ = writeln!(out, "0x{pc:08X}  {raw:08X}  {}", disassemble(raw, pc));
+                }
+                None => {
+                    let _ = writeln!(out, "0x{pc:08X}");
+                }
+            }
+        }
+        out
+    }
+
+    fn call_stack(&self) -> String {
+        let (stack, note) = match FunctionCallLogger::failure_stack() {
+            Some(stack) => (stack, "at the first failed call"),
+            None if FunctionCallLogger::is_enabled() => {
+                (FunctionCallLogger::stack(), "when the report was made")
+            }
+            None => return "call logging was off\n".to_string(),
+        };
+        let mut out = format!("# {note}, outermost first\n");
+        for (depth, address) in stack.iter().enumerate() {
+            let _ = writeln!(out, "#{depth} 0x{address:08X}");
+        }
+        out
+    }
+
+    fn mod_list(&self) -> String {
+        if self.mods.is_empty() {
+            return "no mods loaded\n".to_string();
+        }
+        self.mods
+            .iter()
+            .map(|m| format!("{} {}\n", m.name, m.version))
+            .collect()
+    }
+}
+
+fn format_context(ctx: &CpuContext) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "pc  0x{:08X}  lr  0x{:08X}  ctr 0x{:08X}",
+        ctx.pc, ctx.lr, ctx.ctr
+    );
+    let _ = writeln!(
+        out,
+        "cr  0x{:08X}  xer 0x{:08X}  msr 0x{:08X}  fpscr 0x{:08X}",
+        ctx.cr, ctx.xer, ctx.msr, ctx.fpscr
+    );
+    for (i, value) in ctx.gpr.iter().enumerate() {
+        let _ = writeln!(out, "r{i:<2} 0x{value:08X}");
+    }
+    for (i, value) in ctx.fpr.iter().enumerate() {
+        let _ = writeln!(out, "f{i:<2} {value:e}");
+    }
+    out
+}
+
+/// Replace every absolute path in `text` (`/home/me/x`, `C:\Users\me\x`,
+/// `\\server\share\x`) with `<redacted>/` and its last component.
+pub fn redact_paths(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let starts_token =
+            i == 0 || chars[i - 1].is_whitespace() || "\"'=([,`".contains(chars[i - 1]);
+        let next = |n: usize| chars.get(i + n).copied();
+        let is_path = starts_token
+            && match chars[i] {
+                '/' => next(1).is_some_and(|c| !c.is_whitespace() && c != '/'),
+                '\\' => next(1) == Some('\\'),
+                c if c.is_ascii_alphabetic() => {
+                    next(1) == Some(':') && matches!(next(2), Some('\\' | '/'))
+                }
+                _ => false,
+            };
+        if !is_path {
+            out.push(chars[i]);
+            i += 1;
+            continue;
+        }
+        let end = (i..chars.len())
+            .find(|&j| chars[j].is_whitespace() || "\"'`),]".contains(chars[j]))
+            .unwrap_or(chars.len());
+        let path: String = chars[i..end].iter().collect();
+        let name = path
+            .rsplit(['/', '\\'])
+            .find(|part| !part.is_empty())
+            .unwrap_or("");
+        out.push_str("<redacted>/");
+        out.push_str(name);
+        i = end;
+    }
+    out
+}
+
+/// Write `report` to `path` as a zip (see the top of this file).
+#[cfg(feature = "native")]
+pub fn generate_crash_bundle(path: &std::path::Path, report: &CrashReport) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use std::io::Write as _;
+
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let file =
+        std::fs::File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, text) in report.entries() {
+        zip.start_file(name, options)?;
+        zip.write_all(text.as_bytes())?;
+    }
+    zip.finish()
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
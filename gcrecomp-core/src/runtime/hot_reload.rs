@@ -0,0 +1,276 @@
+// Hot-reloading recompiled code from a dynamic library
+//
+// Rebuilding the game after every codegen change is slow. Instead the
+// generated `recompiled` crate can be built as a `cdylib` (`build_library`)
+// and loaded into a running game: `HotReloader` opens it, checks its ABI
+// version and installs `dispatch` as the call dispatcher, which forwards to
+// the library's `call_function_by_address`. `poll` loads the library again
+// whenever the file changes. `CpuContext` and memory stay with the host and are
+// passed in by pointer, so the game's state carries over a swap.
+//
+// The library exports three C symbols, emitted by the pipeline next to
+// `call_function_by_address`:
+//
+// ```text
+// gcrecomp_abi_version() -> u32
+// gcrecomp_dispatch(address, *mut CpuContext, *mut MemoryManager, *mut u32) -> i32
+// gcrecomp_set_logger(*const &'static dyn log::Log, max_level: usize)
+// ```
+//
+// The library's `log` has no logger of its own, so the host hands it its
+// logger on load; libraries without `gcrecomp_set_logger` log nowhere.
+//
+// `gcrecomp_dispatch` returns one of the `STATUS_*` codes, writing r3 through
+// the last pointer for `STATUS_VALUE`. `MemoryManager` crosses as an opaque
+// Rust type, so host and library must be built from the same gcrecomp-core;
+// bump `ABI_VERSION` when `CpuContext`, `MemoryManager` or this protocol
+// changes. The library has its own copy of gcrecomp-core's globals (stop flag,
+// interpreter dispatcher, clock). Replaced libraries are never unloaded, since
+// one of their functions may still be on the stack.
+
+use crate::runtime::context::CpuContext;
+use crate::runtime::interpreter::CallFn;
+use crate::runtime::memory::MemoryManager;
+
+/// Version of the host/library protocol above.
+pub const ABI_VERSION: u32 = 2;
+
+/// `gcrecomp_dispatch` results.
+pub const STATUS_NONE: i32 = 0;
+pub const STATUS_VALUE: i32 = 1;
+pub const STATUS_ERROR: i32 = -1;
+
+/// Library side of `gcrecomp_dispatch`: run `call` and encode its result.
+/// Errors and panics are logged here, since neither can cross the C ABI.
+///
+/// # Safety
+///
+/// `ctx`, `memory` and `ret` must be valid and not aliased for the call, as
+/// `dispatch` passes them.
+pub unsafe fn export(
+    address: u32,
+    ctx: *mut CpuContext,
+    memory: *mut MemoryManager,
+    ret: *mut u32,
+    call: CallFn,
+) -> i32 {
+    let (ctx, memory) = (&mut *ctx, &mut *memory);
+    let result =
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| call(address, ctx, memory)));
+    match result {
+        Ok(Ok(None)) => STATUS_NONE,
+        Ok(Ok(Some(value))) => {
+            *ret = value;
+            STATUS_VALUE
+        }
+        Ok(Err(e)) => {
+            log::error!("hot-reloaded 0x{address:08X}: {e:#}");
+            STATUS_ERROR
+        }
+        Err(_) => {
+            log::error!("hot-reloaded 0x{address:08X} panicked");
+            STATUS_ERROR
+        }
+    }
+}
+
+/// Library side of `gcrecomp_set_logger`: route this library's `log` records
+/// to the host's logger. `max_level` is a `log::LevelFilter` as `usize`.
+///
+/// # Safety
+///
+/// `logger` must point to a `&'static dyn log::Log` from a host built with the
+/// same `log`, as `HotReloader::load` passes it.
+pub unsafe fn install_logger(logger: *const std::ffi::c_void, max_level: usize) {
+    const LEVELS: [log::LevelFilter; 6] = [
+        log::LevelFilter::Off,
+        log::LevelFilter::Error,
+        log::LevelFilter::Warn,
+        log::LevelFilter::Info,
+        log::LevelFilter::Debug,
+        log::LevelFilter::Trace,
+    ];
+    let logger = *(logger as *const &'static dyn log::Log);
+    // Only fails when this copy of the library was already given one.
+    let _ = log::set_logger(logger);
+    log::set_max_level(LEVELS[max_level.min(LEVELS.len() - 1)]);
+}
+
+#[cfg(feature = "native")]
+pub use host::{build_library, dispatch, HotReloader};
+
+#[cfg(feature = "native")]
+mod host {
+    use super::*;
+    use crate::runtime::interpreter;
+    use anyhow::{bail, Context, Result};
+    use libloading::Library;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+    use std::sync::RwLock;
+    use std::time::SystemTime;
+
+    type DispatchFn =
+        unsafe extern "C" fn(u32, *mut CpuContext, *mut MemoryManager, *mut u32) -> i32;
+    type SetLoggerFn = unsafe extern "C" fn(*const std::ffi::c_void, usize);
+
+    static CURRENT: RwLock<Option<DispatchFn>> = RwLock::new(None);
+
+    /// Call dispatcher forwarding to the loaded library.
+    pub fn dispatch(
+        address: u32,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+    ) -> Result<Option<u32>> {
+        let current = *CURRENT.read().unwrap_or_else(|e| e.into_inner());
+        let Some(call) = current else {
+            bail!("no hot-reloaded library loaded (calling 0x{address:08X})");
+        };
+        let mut ret = 0;
+        // SAFETY: the pointers come from live exclusive references.
+        match unsafe { call(address, ctx, memory, &mut ret) } {
+            STATUS_NONE => Ok(None),
+            STATUS_VALUE => Ok(Some(ret)),
+            _ => bail!("0x{address:08X} failed in the hot-reloaded library"),
+        }
+    }
+
+    /// Watches a recompiled cdylib and swaps it in when it changes.
+    pub struct HotReloader {
+        library: PathBuf,
+        modified: Option<SystemTime>,
+        loaded: Vec<Library>,
+    }
+
+    impl HotReloader {
+        pub fn new(library: impl Into<PathBuf>) -> Self {
+            Self {
+                library: library.into(),
+                modified: None,
+                loaded: Vec::new(),
+            }
+        }
+
+        /// Libraries loaded so far.
+        pub fn generation(&self) -> usize {
+            self.loaded.len()
+        }
+
+        /// Load the library and make it the call dispatcher.
+        pub fn load(&mut self) -> Result<()> {
+            let modified = std::fs::metadata(&self.library)
+                .and_then(|m| m.modified())
+                .with_context(|| format!("reading {}", self.library.display()))?;
+            // Open a copy: builds overwrite the original in place, and the
+            // system loader hands back the already-open library for a path.
+            let name = self
+                .library
+                .file_name()
+                .context("library has no file name")?;
+            let copy = std::env::temp_dir().join(format!(
+                "gcrecomp_hot_{}_{}_{}",
+                std::process::id(),
+                self.loaded.len(),
+                name.to_string_lossy()
+            ));
+            std::fs::copy(&self.library, &copy)
+                .with_context(|| format!("copying {}", self.library.display()))?;
+            // SAFETY: loading runs the library's initializers; it is a
+            // recompiled crate built by us, checked for the ABI below.
+            let library = unsafe { Library::new(&copy) }
+                .with_context(|| format!("loading {}", self.library.display()));
+            let _ = std::fs::remove_file(&copy);
+            let library = library?;
+
+            // SAFETY: the symbol types are the protocol in the header comment.
+            let version =
+                unsafe { library.get::<unsafe extern "C" fn() -> u32>(b"gcrecomp_abi_version\0") }
+                    .context("not a hot-reloadable library (no gcrecomp_abi_version)")?;
+            let version = unsafe { version() };
+            if version != ABI_VERSION {
+                bail!(
+                    "{} has ABI version {version}, expected {ABI_VERSION}; rebuild it against this gcrecomp-core",
+                    self.library.display()
+                );
+            }
+            let call = *unsafe { library.get::<DispatchFn>(b"gcrecomp_dispatch\0") }
+                .context("not a hot-reloadable library (no gcrecomp_dispatch)")?;
+            if let Ok(set_logger) = unsafe { library.get::<SetLoggerFn>(b"gcrecomp_set_logger\0") }
+            {
+                let logger: &'static dyn log::Log = log::logger();
+                let logger = &logger as *const &'static dyn log::Log;
+                unsafe { set_logger(logger.cast(), log::max_level() as usize) };
+            }
+
+            *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(call);
+            interpreter::set_call_dispatcher(dispatch);
+            self.loaded.push(library);
+            self.modified = Some(modified);
+            log::info!(
+                "Hot-loaded {} (generation {})",
+                self.library.display(),
+                self.loaded.len()
+            );
+            Ok(())
+        }
+
+        /// Load the library again if it changed since the last load. Returns
+        /// true if it was swapped. A missing file (mid-build) is not an error.
+        pub fn poll(&mut self) -> Result<bool> {
+            let Ok(modified) = std::fs::metadata(&self.library).and_then(|m| m.modified()) else {
+                return Ok(false);
+            };
+            if self.modified == Some(modified) {
+                return Ok(false);
+            }
+            self.load()?;
+            Ok(true)
+        }
+    }
+
+    /// Build the crate at `crate_dir` as a cdylib (into `target/hot-reload`
+    /// under it) and return the library's path.
+    pub fn build_library(crate_dir: &Path, release: bool) -> Result<PathBuf> {
+        let manifest = crate_dir.join("Cargo.toml");
+        let text = std::fs::read_to_string(&manifest)
+            .with_context(|| format!("reading {}", manifest.display()))?;
+        let doc: toml_edit::DocumentMut = text
+            .parse()
+            .with_context(|| format!("parsing {}", manifest.display()))?;
+        let name = doc
+            .get("lib")
+            .and_then(|lib| lib.get("name"))
+            .or_else(|| doc.get("package").and_then(|p| p.get("name")))
+            .and_then(|n| n.as_str())
+            .context("Cargo.toml has no package name")?
+            .replace('-', "_");
+
+        let target_dir = crate_dir.join("target").join("hot-reload");
+        let mut cargo = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+        cargo
+            .args([
+                "rustc",
+                "--lib",
+                "--crate-type",
+                "cdylib",
+                "--manifest-path",
+            ])
+            .arg(&manifest)
+            .arg("--target-dir")
+            .arg(&target_dir);
+        if release {
+            cargo.arg("--release");
+        }
+        let status = cargo.status().context("running cargo")?;
+        if !status.success() {
+            bail!("building {} failed ({status})", crate_dir.display());
+        }
+        Ok(target_dir
+            .join(if release { "release" } else { "debug" })
+            .join(format!(
+                "{}{name}{}",
+                std::env::consts::DLL_PREFIX,
+                std::env::consts::DLL_SUFFIX
+            )))
+    }
+}
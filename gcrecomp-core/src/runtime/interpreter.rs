@@ -0,0 +1,784 @@
+// PowerPC interpreter: runtime fallback for functions the recompiler can't translate
+//
+// Runs a function straight out of emulated RAM (the DOL image is loaded there by
+// `load_image`), one instruction at a time, on the same `CpuContext` and
+// `MemoryManager` as recompiled code. Calls (`bl`, `bctrl`, `blrl`) go through
+// the registered dispatcher, so interpreted and recompiled functions can call
+// each other freely; plain branches, including ones that leave the function, are
+// simply followed. Covers the integer ISA: arithmetic, logic, rotates/shifts,
+// compares, CR logic, loads/stores and LR/CTR/XER/CR/MSR moves. Anything else
+// (floating point, paired singles) is an error; `supports` lets the pipeline
+// check a function before relying on this.
+//
+// CR follows `CpuContext`'s layout (field n in bits 4n..4n+3, LT as the field's
+// top bit), matching the code generator, so CR values survive crossing between
+// interpreted and recompiled code.
+//
+// Code runs through a block cache: a block (straight-line code up to and
+// including the first branch) is read once and compiled into one closure per
+// instruction, with its fields already extracted; common forms get their own
+// closure, the rest go through `step`. Blocks are cached per thread, keyed on
+// their start address, for one `MemoryManager` at a time. The pages they were
+// read from are watched (`MemoryManager::watch_code`), and blocks on a page
+// written since are dropped before the next lookup, so self-modifying code
+// and code loaded over old code is read again. `icbi` and `dcbst` drop the
+// blocks on their line's page too, and `icbi` and `isync` end a block, so the
+// instruction after either is looked up again with those drops applied.
+
+use crate::runtime::context::{self, CpuContext, XER_CA, XER_SO};
+use crate::runtime::memory::MemoryManager;
+use crate::runtime::{clock, trace, watchdog};
+use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::RwLock;
+
+/// Signature shared with the generated `call_function_by_address`.
+pub type CallFn = fn(u32, &mut CpuContext, &mut MemoryManager) -> Result<Option<u32>>;
+
+static DISPATCHER: RwLock<Option<CallFn>> = RwLock::new(None);
+
+/// LR value meaning "return to the host caller". Never a real code address.
+const RETURN_ADDR: u32 = 0xFFFF_FFFC;
+
+/// Route calls made by interpreted code. The generated `load_image` registers
+/// `call_function_by_address`; without a dispatcher callees are interpreted too.
+pub fn set_call_dispatcher(dispatcher: CallFn) {
+    *DISPATCHER.write().unwrap_or_else(|e| e.into_inner()) = Some(dispatcher);
+}
+
+/// Interpret the function at `address` until it returns, like a call to its
+/// recompiled counterpart. Returns r3.
+pub fn interpret_function(
+    address: u32,
+    ctx: &mut CpuContext,
+    memory: &mut MemoryManager,
+) -> Result<Option<u32>> {
+    let caller_lr = ctx.lr;
+    ctx.lr = RETURN_ADDR;
+    let mut pc = address;
+    let mut back_edges: u64 = 0;
+    'run: while pc != RETURN_ADDR {
+        let block = block_at(pc, memory)?;
+        for op in &block.ops {
+            ctx.pc = op.pc;
+            watchdog::record_pc(op.pc);
+            let next = (op.exec)(ctx, memory)?;
+            clock::retire(1);
+            trace::record(op.pc, op.word, ctx);
+            if next == op.pc.wrapping_add(4) {
+                pc = next;
+                continue;
+            }
+            if next <= op.pc && next != RETURN_ADDR && watchdog::back_edge(&mut back_edges, next)? {
+                break 'run;
+            }
+            pc = next;
+            continue 'run;
+        }
+    }
+    ctx.lr = caller_lr;
+    Ok(Some(ctx.gpr[3]))
+}
+
+/// Longest block compiled in one go.
+const MAX_BLOCK_LEN: usize = 64;
+
+type OpFn = Box<dyn Fn(&mut CpuContext, &mut MemoryManager) -> Result<u32>>;
+
+/// One compiled instruction: runs it and returns the next PC.
+struct Op {
+    pc: u32,
+    word: u32,
+    exec: OpFn,
+}
+
+struct Block {
+    start: u32,
+    ops: Vec<Op>,
+}
+
+impl Block {
+    fn end(&self) -> u32 {
+        self.start.wrapping_add(self.ops.len() as u32 * 4)
+    }
+}
+
+/// Counters for this thread's block cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Blocks dropped because their code was written.
+    pub invalidated: u64,
+    /// Blocks currently cached.
+    pub blocks: usize,
+}
+
+#[derive(Default)]
+struct BlockCache {
+    memory: Option<u64>,
+    blocks: HashMap<u32, Rc<Block>>,
+    stats: BlockCacheStats,
+}
+
+thread_local! {
+    static BLOCKS: RefCell<BlockCache> = RefCell::new(BlockCache::default());
+}
+
+/// This thread's block cache counters.
+pub fn block_cache_stats() -> BlockCacheStats {
+    BLOCKS.with(|c| {
+        let cache = c.borrow();
+        BlockCacheStats {
+            blocks: cache.blocks.len(),
+            ..cache.stats
+        }
+    })
+}
+
+/// Empty this thread's block cache and reset its counters.
+pub fn clear_block_cache() {
+    BLOCKS.with(|c| *c.borrow_mut() = BlockCache::default());
+}
+
+/// The compiled block starting at `pc`, from the cache or freshly read.
+fn block_at(pc: u32, memory: &mut MemoryManager) -> Result<Rc<Block>> {
+    let dirty = memory.take_dirty_code();
+    let cached = BLOCKS.with(|c| {
+        let mut cache = c.borrow_mut();
+        if cache.memory != Some(memory.id()) {
+            cache.blocks.clear();
+            cache.memory = Some(memory.id());
+        }
+        for page in dirty {
+            let page_end = page.wrapping_add(crate::runtime::memory::CODE_PAGE_SIZE);
+            let before = cache.blocks.len();
+            cache
+                .blocks
+                .retain(|_, b| b.end() <= page || b.start >= page_end);
+            cache.stats.invalidated += (before - cache.blocks.len()) as u64;
+        }
+        let block = cache.blocks.get(&pc).cloned();
+        if block.is_some() {
+            cache.stats.hits += 1;
+        } else {
+            cache.stats.misses += 1;
+        }
+        block
+    });
+    if let Some(block) = cached {
+        return Ok(block);
+    }
+    let block = Rc::new(compile_block(pc, memory)?);
+    memory.watch_code(pc, block.ops.len() * 4);
+    BLOCKS.with(|c| c.borrow_mut().blocks.insert(pc, block.clone()));
+    Ok(block)
+}
+
+/// Read and compile instructions from `start` through the first branch.
+fn compile_block(start: u32, memory: &MemoryManager) -> Result<Block> {
+    let mut ops = Vec::new();
+    let mut pc = start;
+    while ops.len() < MAX_BLOCK_LEN {
+        let word = match memory.read_u32(pc) {
+            Ok(word) => word,
+            // The first word must read; later ones fault when reached.
+            Err(e) if ops.is_empty() => return Err(e),
+            Err(_) => break,
+        };
+        ops.push(Op {
+            pc,
+            word,
+            exec: compile(word, pc),
+        });
+        // bc, sc, b, and opcode 19 (bclr/bcctr/rfi/isync) end the block, as
+        // does icbi, so code it invalidates is read again right after it.
+        if matches!(word >> 26, 16..=19) || word & 0xFC00_07FE == 0x7C00_07AC {
+            break;
+        }
+        pc = pc.wrapping_add(4);
+    }
+    Ok(Block { start, ops })
+}
+
+/// A closure running `word` at `pc`, specialised for the common forms.
+fn compile(word: u32, pc: u32) -> OpFn {
+    let next = pc.wrapping_add(4);
+    let rd = ((word >> 21) & 31) as usize;
+    let ra = ((word >> 16) & 31) as usize;
+    let simm = word as u16 as i16 as i32 as u32;
+    let uimm = word & 0xFFFF;
+    match word >> 26 {
+        14 if ra == 0 => Box::new(move |ctx, _| {
+            ctx.gpr[rd] = simm;
+            Ok(next)
+        }),
+        14 => Box::new(move |ctx, _| {
+            ctx.gpr[rd] = ctx.gpr[ra].wrapping_add(simm);
+            Ok(next)
+        }),
+        15 if ra == 0 => Box::new(move |ctx, _| {
+            ctx.gpr[rd] = uimm << 16;
+            Ok(next)
+        }),
+        24 => Box::new(move |ctx, _| {
+            ctx.gpr[ra] = ctx.gpr[rd] | uimm;
+            Ok(next)
+        }),
+        11 => {
+            let crf = (rd >> 2) as u8;
+            Box::new(move |ctx, _| {
+                compare(ctx, crf, (ctx.gpr[ra] as i32).cmp(&(simm as i32)));
+                Ok(next)
+            })
+        }
+        32 if ra != 0 => Box::new(move |ctx, memory| {
+            ctx.gpr[rd] = memory.read_u32(ctx.gpr[ra].wrapping_add(simm))?;
+            Ok(next)
+        }),
+        36 if ra != 0 => Box::new(move |ctx, memory| {
+            memory.write_u32(ctx.gpr[ra].wrapping_add(simm), ctx.gpr[rd])?;
+            Ok(next)
+        }),
+        _ => Box::new(move |ctx, memory| step(word, pc, ctx, memory)),
+    }
+}
+
+/// Call `target` the way interpreted code's `bl` does: through the registered
+/// dispatcher, or interpreted when there isn't one.
+pub fn call_function(
+    target: u32,
+    ctx: &mut CpuContext,
+    memory: &mut MemoryManager,
+) -> Result<Option<u32>> {
+    let dispatcher = *DISPATCHER.read().unwrap_or_else(|e| e.into_inner());
+    match dispatcher {
+        Some(dispatch) => dispatch(target, ctx, memory),
+        None => interpret_function(target, ctx, memory),
+    }
+}
+
+fn call(target: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) -> Result<()> {
+    if let Some(rv) = call_function(target, ctx, memory)? {
+        ctx.gpr[3] = rv;
+    }
+    Ok(())
+}
+
+/// Primary opcodes `step` implements (31 and 19 are refined below).
+const PRIMARY: &[u32] = &[
+    7, 8, 10, 11, 12, 13, 14, 15, 16, 18, 19, 20, 21, 23, 24, 25, 26, 27, 28, 29, 31, 32, 33, 34,
+    35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47,
+];
+/// Opcode 19 extended opcodes: mcrf, bclr, CR logic, isync, bcctr.
+const EXT19: &[u32] = &[0, 16, 33, 129, 150, 193, 225, 257, 289, 417, 449, 528];
+/// Opcode 31 XO-form arithmetic (9-bit extended opcode, OE masked off).
+const EXT31_ARITH: &[u32] = &[
+    8, 10, 11, 40, 75, 104, 136, 138, 200, 202, 234, 235, 266, 459, 491,
+];
+/// Opcode 31 everything else (10-bit extended opcode).
+const EXT31: &[u32] = &[
+    0, 19, 23, 24, 26, 28, 32, 54, 55, 60, 83, 86, 87, 119, 124, 144, 146, 151, 183, 215, 246, 247,
+    278, 279, 284, 311, 316, 339, 343, 375, 407, 412, 439, 444, 467, 470, 476, 534, 536, 598, 662,
+    790, 792, 824, 854, 918, 922, 954, 982, 1014,
+];
+
+/// True if the interpreter can execute `word`.
+pub fn supports(word: u32) -> bool {
+    let xo = (word >> 1) & 0x3FF;
+    match word >> 26 {
+        19 => EXT19.contains(&xo),
+        31 => EXT31_ARITH.contains(&(xo & 0x1FF)) || EXT31.contains(&xo),
+        op => PRIMARY.contains(&op),
+    }
+}
+
+fn unsupported(word: u32, pc: u32) -> anyhow::Error {
+    anyhow::anyhow!("Interpreter: unsupported instruction 0x{word:08X} at 0x{pc:08X}")
+}
+
+fn cr_bit(ctx: &CpuContext, bit: u32) -> bool {
+    (ctx.get_cr_field((bit / 4) as u8) >> (3 - bit % 4)) & 1 != 0
+}
+
+fn set_cr_bit(ctx: &mut CpuContext, bit: u32, value: bool) {
+    let field = (bit / 4) as u8;
+    let mask = 1 << (3 - bit % 4);
+    let old = ctx.get_cr_field(field);
+    ctx.set_cr_field(field, if value { old | mask } else { old & !mask });
+}
+
+fn compare(ctx: &mut CpuContext, crf: u8, ord: std::cmp::Ordering) {
+    let so = (ctx.xer & XER_SO != 0) as u8;
+    let flags = match ord {
+        std::cmp::Ordering::Less => 0x8,
+        std::cmp::Ordering::Greater => 0x4,
+        std::cmp::Ordering::Equal => 0x2,
+    };
+    ctx.set_cr_field(crf, flags | so);
+}
+
+fn set_cr0(ctx: &mut CpuContext, value: u32) {
+    compare(ctx, 0, (value as i32).cmp(&0));
+}
+
+fn set_ca(ctx: &mut CpuContext, carry: bool) {
+    if carry {
+        ctx.xer |= XER_CA;
+    } else {
+        ctx.xer &= !XER_CA;
+    }
+}
+
+fn ca(ctx: &CpuContext) -> u64 {
+    (ctx.xer & XER_CA != 0) as u64
+}
+
+/// `a + b + c` with the carry out of bit 31.
+fn add_carry(a: u32, b: u32, c: u64) -> (u32, bool) {
+    let sum = a as u64 + b as u64 + c;
+    (sum as u32, sum > u32::MAX as u64)
+}
+
+fn rotate_mask(mb: u32, me: u32) -> u32 {
+    let m = (u32::MAX >> mb) ^ (u32::MAX >> me >> 1);
+    if mb <= me {
+        m
+    } else {
+        !m
+    }
+}
+
+/// Branch condition for `bc`/`bclr`/`bcctr`; decrements CTR when BO asks.
+fn branch_taken(ctx: &mut CpuContext, bo: u32, bi: u32, uses_ctr: bool) -> bool {
+    let ctr_ok = if uses_ctr && bo & 0x04 == 0 {
+        ctx.ctr = ctx.ctr.wrapping_sub(1);
+        (ctx.ctr != 0) != (bo & 0x02 != 0)
+    } else {
+        true
+    };
+    let cond_ok = bo & 0x10 != 0 || cr_bit(ctx, bi) == (bo & 0x08 != 0);
+    ctr_ok && cond_ok
+}
+
+/// Execute one instruction at `pc`; returns the next PC.
+fn step(word: u32, pc: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) -> Result<u32> {
+    let next = pc.wrapping_add(4);
+    let rd = ((word >> 21) & 31) as usize;
+    let ra = ((word >> 16) & 31) as usize;
+    let rb = ((word >> 11) & 31) as usize;
+    let simm = word as u16 as i16 as i32 as u32;
+    let uimm = word & 0xFFFF;
+    let ra0 = if ra == 0 { 0 } else { ctx.gpr[ra] };
+    let rc = word & 1 != 0;
+    let lk = word & 1 != 0;
+
+    match word >> 26 {
+        7 => ctx.gpr[rd] = (ctx.gpr[ra] as i32).wrapping_mul(simm as i32) as u32,
+        8 => {
+            let (r, c) = add_carry(!ctx.gpr[ra], simm, 1);
+            ctx.gpr[rd] = r;
+            set_ca(ctx, c);
+        }
+        10 => compare(ctx, (rd >> 2) as u8, ctx.gpr[ra].cmp(&uimm)),
+        11 => compare(
+            ctx,
+            (rd >> 2) as u8,
+            (ctx.gpr[ra] as i32).cmp(&(simm as i32)),
+        ),
+        op @ (12 | 13) => {
+            let (r, c) = add_carry(ctx.gpr[ra], simm, 0);
+            ctx.gpr[rd] = r;
+            set_ca(ctx, c);
+            if op == 13 {
+                set_cr0(ctx, r);
+            }
+        }
+        14 => ctx.gpr[rd] = ra0.wrapping_add(simm),
+        15 => ctx.gpr[rd] = ra0.wrapping_add(uimm << 16),
+        16 => {
+            let bo = (word >> 21) & 31;
+            let bi = (word >> 16) & 31;
+            if branch_taken(ctx, bo, bi, true) {
+                let bd = (word & 0xFFFC) as u16 as i16 as i32 as u32;
+                let target = if word & 2 != 0 {
+                    bd
+                } else {
+                    pc.wrapping_add(bd)
+                };
+                if lk {
+                    ctx.lr = next;
+                    call(target, ctx, memory)?;
+                    return Ok(next);
+                }
+                return Ok(target);
+            }
+        }
+        18 => {
+            let disp = (((word & 0x03FF_FFFC) as i32) << 6 >> 6) as u32;
+            let target = if word & 2 != 0 {
+                disp
+            } else {
+                pc.wrapping_add(disp)
+            };
+            if lk {
+                ctx.lr = next;
+                call(target, ctx, memory)?;
+                return Ok(next);
+            }
+            return Ok(target);
+        }
+        19 => return step_ext19(word, pc, ctx, memory),
+        20 => {
+            let (sh, mb, me) = (rb as u32, (word >> 6) & 31, (word >> 1) & 31);
+            let m = rotate_mask(mb, me);
+            let r = (ctx.gpr[rd].rotate_left(sh) & m) | (ctx.gpr[ra] & !m);
+            ctx.gpr[ra] = r;
+            if rc {
+                set_cr0(ctx, r);
+            }
+        }
+        op @ (21 | 23) => {
+            let sh = if op == 21 {
+                rb as u32
+            } else {
+                ctx.gpr[rb] & 31
+            };
+            let r = ctx.gpr[rd].rotate_left(sh) & rotate_mask((word >> 6) & 31, (word >> 1) & 31);
+            ctx.gpr[ra] = r;
+            if rc {
+                set_cr0(ctx, r);
+            }
+        }
+        24 => ctx.gpr[ra] = ctx.gpr[rd] | uimm,
+        25 => ctx.gpr[ra] = ctx.gpr[rd] | (uimm << 16),
+        26 => ctx.gpr[ra] = ctx.gpr[rd] ^ uimm,
+        27 => ctx.gpr[ra] = ctx.gpr[rd] ^ (uimm << 16),
+        op @ (28 | 29) => {
+            let imm = if op == 28 { uimm } else { uimm << 16 };
+            let r = ctx.gpr[rd] & imm;
+            ctx.gpr[ra] = r;
+            set_cr0(ctx, r);
+        }
+        31 => step_ext31(word, pc, ctx, memory)?,
+        op @ 32..=45 => {
+            // D-form loads/stores; odd opcodes are the update forms.
+            let update = op % 2 == 1;
+            let ea = if update { ctx.gpr[ra] } else { ra0 }.wrapping_add(simm);
+            load_store(op, rd, ea, ctx, memory)?;
+            if update {
+                ctx.gpr[ra] = ea;
+            }
+        }
+        46 => {
+            let mut ea = ra0.wrapping_add(simm);
+            for r in rd..32 {
+                ctx.gpr[r] = memory.read_u32(ea)?;
+                ea = ea.wrapping_add(4);
+            }
+        }
+        47 => {
+            let mut ea = ra0.wrapping_add(simm);
+            for r in rd..32 {
+                memory.write_u32(ea, ctx.gpr[r])?;
+                ea = ea.wrapping_add(4);
+            }
+        }
+        _ => return Err(unsupported(word, pc)),
+    }
+    Ok(next)
+}
+
+/// Shared by D-form (`op` 32-45) and the X-form indexed variants.
+fn load_store(
+    op: u32,
+    rd: usize,
+    ea: u32,
+    ctx: &mut CpuContext,
+    memory: &mut MemoryManager,
+) -> Result<()> {
+    match op {
+        32 | 33 => ctx.gpr[rd] = memory.read_u32(ea)?,
+        34 | 35 => ctx.gpr[rd] = memory.read_u8(ea)? as u32,
+        36 | 37 => memory.write_u32(ea, ctx.gpr[rd])?,
+        38 | 39 => memory.write_u8(ea, ctx.gpr[rd] as u8)?,
+        40 | 41 => ctx.gpr[rd] = memory.read_u16(ea)? as u32,
+        42 | 43 => ctx.gpr[rd] = memory.read_u16(ea)? as i16 as i32 as u32,
+        44 | 45 => memory.write_u16(ea, ctx.gpr[rd] as u16)?,
+        _ => unreachable!("not a load/store opcode: {op}"),
+    }
+    Ok(())
+}
+
+fn step_ext19(word: u32, pc: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) -> Result<u32> {
+    let next = pc.wrapping_add(4);
+    let bo = (word >> 21) & 31;
+    let (ba, bb) = ((word >> 16) & 31, (word >> 11) & 31);
+    let lk = word & 1 != 0;
+    let cr_op = |f: fn(bool, bool) -> bool, ctx: &mut CpuContext| {
+        let v = f(cr_bit(ctx, ba), cr_bit(ctx, bb));
+        set_cr_bit(ctx, bo, v);
+    };
+    match (word >> 1) & 0x3FF {
+        0 => {
+            let v = ctx.get_cr_field((ba >> 2) as u8);
+            ctx.set_cr_field((bo >> 2) as u8, v);
+        }
+        xo @ (16 | 528) => {
+            let is_lr = xo == 16;
+            if !branch_taken(ctx, bo, ba, is_lr) {
+                return Ok(next);
+            }
+            let target = if is_lr { ctx.lr } else { ctx.ctr } & !3;
+            if lk {
+                ctx.lr = next;
+                call(target, ctx, memory)?;
+                return Ok(next);
+            }
+            return Ok(target);
+        }
+        33 => cr_op(|a, b| !(a || b), ctx),
+        129 => cr_op(|a, b| a && !b, ctx),
+        150 => {}
+        193 => cr_op(|a, b| a != b, ctx),
+        225 => cr_op(|a, b| !(a && b), ctx),
+        257 => cr_op(|a, b| a && b, ctx),
+        289 => cr_op(|a, b| a == b, ctx),
+        417 => cr_op(|a, b| a || !b, ctx),
+        449 => cr_op(|a, b| a || b, ctx),
+        _ => return Err(unsupported(word, pc)),
+    }
+    Ok(next)
+}
+
+fn step_ext31(word: u32, pc: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) -> Result<()> {
+    let rd = ((word >> 21) & 31) as usize;
+    let ra = ((word >> 16) & 31) as usize;
+    let rb = ((word >> 11) & 31) as usize;
+    let ra0 = if ra == 0 { 0 } else { ctx.gpr[ra] };
+    let rc = word & 1 != 0;
+    let xo = (word >> 1) & 0x3FF;
+
+    if EXT31_ARITH.contains(&(xo & 0x1FF)) {
+        // XO-form: rD = f(rA, rB). OE (overflow recording) is only modelled
+        // for the divides.
+        let (a, b) = (ctx.gpr[ra], ctx.gpr[rb]);
+        let oe = word & 0x400 != 0;
+        let r = match xo & 0x1FF {
+            266 => a.wrapping_add(b),
+            40 => b.wrapping_sub(a),
+            104 => a.wrapping_neg(),
+            235 => a.wrapping_mul(b),
+            75 => ((a as i32 as i64 * b as i32 as i64) >> 32) as u32,
+            11 => ((a as u64 * b as u64) >> 32) as u32,
+            491 | 459 => {
+                let (q, overflow) = if xo & 0x1FF == 491 {
+                    context::divw(a, b)
+                } else {
+                    context::divwu(a, b)
+                };
+                if oe {
+                    ctx.set_overflow(overflow);
+                }
+                q
+            }
+            carrying => {
+                let (r, c) = match carrying {
+                    10 => add_carry(a, b, 0),
+                    138 => add_carry(a, b, ca(ctx)),
+                    202 => add_carry(a, 0, ca(ctx)),
+                    234 => add_carry(a, u32::MAX, ca(ctx)),
+                    8 => add_carry(!a, b, 1),
+                    136 => add_carry(!a, b, ca(ctx)),
+                    _ => add_carry(!a, 0, ca(ctx)), // 200: subfze
+                };
+                set_ca(ctx, c);
+                r
+            }
+        };
+        ctx.gpr[rd] = r;
+        if rc {
+            set_cr0(ctx, r);
+        }
+        return Ok(());
+    }
+
+    // X-form logic/shift ops write rA from rS (the rd field).
+    let s = ctx.gpr[rd];
+    let logic = |ctx: &mut CpuContext, r: u32| {
+        ctx.gpr[ra] = r;
+        if rc {
+            set_cr0(ctx, r);
+        }
+    };
+    let spr = ((word >> 16) & 0x1F) | (((word >> 11) & 0x1F) << 5);
+    match xo {
+        0 => compare(
+            ctx,
+            (rd >> 2) as u8,
+            (ctx.gpr[ra] as i32).cmp(&(ctx.gpr[rb] as i32)),
+        ),
+        32 => compare(ctx, (rd >> 2) as u8, ctx.gpr[ra].cmp(&ctx.gpr[rb])),
+        19 => ctx.gpr[rd] = ctx.cr,
+        144 => {
+            let crm = (word >> 12) & 0xFF;
+            let mask = (0..8)
+                .filter(|f| crm & (0x80 >> f) != 0)
+                .fold(0u32, |m, f| m | (0xF << (4 * f)));
+            ctx.cr = (ctx.cr & !mask) | (s & mask);
+        }
+        83 => ctx.gpr[rd] = ctx.msr,
+        146 => ctx.msr = s,
+        339 => {
+            ctx.gpr[rd] = match spr {
+                1 => ctx.xer,
+                8 => ctx.lr,
+                9 => ctx.ctr,
+                _ => 0, // HID/GQR/etc. aren't modelled
+            }
+        }
+        467 => match spr {
+            1 => ctx.xer = s,
+            8 => ctx.lr = s,
+            9 => ctx.ctr = s,
+            _ => {}
+        },
+        28 => logic(ctx, s & ctx.gpr[rb]),
+        60 => logic(ctx, s & !ctx.gpr[rb]),
+        124 => logic(ctx, !(s | ctx.gpr[rb])),
+        284 => logic(ctx, !(s ^ ctx.gpr[rb])),
+        316 => logic(ctx, s ^ ctx.gpr[rb]),
+        412 => logic(ctx, s | !ctx.gpr[rb]),
+        444 => logic(ctx, s | ctx.gpr[rb]),
+        476 => logic(ctx, !(s & ctx.gpr[rb])),
+        24 | 536 => {
+            let sh = ctx.gpr[rb] & 0x3F;
+            let r = match (sh > 31, xo == 24) {
+                (true, _) => 0,
+                (false, true) => s << sh,
+                (false, false) => s >> sh,
+            };
+            logic(ctx, r);
+        }
+        792 | 824 => {
+            let sh = if xo == 824 {
+                rb as u32
+            } else {
+                ctx.gpr[rb] & 0x3F
+            };
+            let neg = (s as i32) < 0;
+            let (r, carry) = if sh > 31 {
+                (if neg { u32::MAX } else { 0 }, neg)
+            } else {
+                let lost = sh > 0 && s & (u32::MAX >> (32 - sh)) != 0;
+                (((s as i32) >> sh) as u32, neg && lost)
+            };
+            set_ca(ctx, carry);
+            logic(ctx, r);
+        }
+        26 => logic(ctx, s.leading_zeros()),
+        922 => logic(ctx, s as i16 as i32 as u32),
+        954 => logic(ctx, s as i8 as i32 as u32),
+        // Indexed loads/stores map onto the D-form opcode table.
+        23 | 55 | 87 | 119 | 151 | 183 | 215 | 247 | 279 | 311 | 343 | 375 | 407 | 439 => {
+            let update = xo & 0x20 != 0;
+            let op = match xo & !0x20 {
+                23 => 32,
+                87 => 34,
+                151 => 36,
+                215 => 38,
+                279 => 40,
+                343 => 42,
+                _ => 44, // 407: sthx
+            };
+            let ea = if update { ctx.gpr[ra] } else { ra0 }.wrapping_add(ctx.gpr[rb]);
+            load_store(op, rd, ea, ctx, memory)?;
+            if update {
+                ctx.gpr[ra] = ea;
+            }
+        }
+        534 => ctx.gpr[rd] = memory.read_u32(ra0.wrapping_add(ctx.gpr[rb]))?.swap_bytes(),
+        662 => memory.write_u32(ra0.wrapping_add(ctx.gpr[rb]), s.swap_bytes())?,
+        790 => ctx.gpr[rd] = memory.read_u16(ra0.wrapping_add(ctx.gpr[rb]))?.swap_bytes() as u32,
+        918 => memory.write_u16(ra0.wrapping_add(ctx.gpr[rb]), (s as u16).swap_bytes())?,
+        1014 => {
+            let ea = ra0.wrapping_add(ctx.gpr[rb]) & !31;
+            memory.write_bytes(ea, &[0u8; 32])?;
+        }
+        // icbi/dcbst: drop cached blocks on the line's page. Writes do that
+        // already; this covers code that only flushes.
+        54 | 982 => {
+            let ea = ra0.wrapping_add(ctx.gpr[rb]) & !31;
+            memory.invalidate_code(ea, 32);
+        }
+        // Other cache maintenance and barriers: no-ops here.
+        86 | 246 | 278 | 470 | 598 | 854 => {}
+        _ => return Err(unsupported(word, pc)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(words: &[u32], ctx: &mut CpuContext, memory: &mut MemoryManager) -> Option<u32> {
+        for (i, &w) in words.iter().enumerate() {
+            memory.write_u32(0x8000_4000 + i as u32 * 4, w).unwrap();
+        }
+        interpret_function(0x8000_4000, ctx, memory).unwrap()
+    }
+
+    #[test]
+    fn counted_loop_with_loads_and_stores() {
+        let mut ctx = CpuContext::new();
+        let mut memory = MemoryManager::new();
+        ctx.lr = 0x8000_1234;
+        // Sum the 4 words at 0x80005000 into r3 and store it after them:
+        //   li r3,0 ; lis r4,0x8000 ; ori r4,r4,0x4FFC ; li r5,4 ; mtctr r5
+        //   loop: lwzu r6,4(r4) ; add r3,r3,r6 ; bdnz loop
+        //   stw r3,4(r4) ; blr
+        let words = [
+            0x3860_0000,
+            0x3C80_8000,
+            0x6084_4FFC,
+            0x38A0_0004,
+            0x7CA9_03A6,
+            0x84C4_0004,
+            0x7C63_3214,
+            0x4200_FFF8,
+            0x9064_0004,
+            0x4E80_0020,
+        ];
+        for (i, v) in [1u32, 2, 3, 4].iter().enumerate() {
+            memory.write_u32(0x8000_5000 + i as u32 * 4, *v).unwrap();
+        }
+        assert_eq!(run(&words, &mut ctx, &mut memory), Some(10));
+        assert_eq!(memory.read_u32(0x8000_5010).unwrap(), 10);
+        assert_eq!(ctx.ctr, 0);
+        // Caller's LR survives the call
+        assert_eq!(ctx.lr, 0x8000_1234);
+    }
+
+    #[test]
+    fn compare_branch_and_rotate() {
+        let mut ctx = CpuContext::new();
+        let mut memory = MemoryManager::new();
+        ctx.gpr[3] = 0xFFFF_FFF0; // -16
+                                  // srawi r4,r3,2 ; cmpwi r4,0 ; bge skip ; rlwinm r3,r3,8,24,31 ; skip: blr
+        let words = [
+            0x7C64_1670,
+            0x2C04_0000,
+            0x4080_0008,
+            0x5463_463E,
+            0x4E80_0020,
+        ];
+        assert_eq!(run(&words, &mut ctx, &mut memory), Some(0xFF));
+        assert_eq!(ctx.gpr[4], 0xFFFF_FFFC);
+        assert_eq!(ctx.get_cr_field(0), 0x8); // LT
+        assert!(supports(0x7C64_1670));
+        assert!(!supports(0xFC20_1024)); // fdiv: floating point isn't interpreted
+    }
+}
@@ -19,7 +19,60 @@
 //! GameCube uses physical addresses directly. Main RAM is mapped at 0x80000000,
 //! so we subtract this base address to get the RAM offset.
 
+use crate::runtime::rng::MemoryFill;
 use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Granularity of code-write tracking (`watch_code` / `take_dirty_code`).
+pub const CODE_PAGE_SIZE: u32 = 4096;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// An access to an unmapped address: the error behind a DSI exception.
+///
+/// Returned (wrapped in `anyhow::Error`) by the word accessors when the
+/// address is neither RAM nor I/O space; the OS exception path downcasts to it
+/// to find the faulting address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryFault {
+    pub address: u32,
+    pub write: bool,
+}
+
+impl std::fmt::Display for MemoryFault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let access = if self.write { "write" } else { "read" };
+        write!(
+            f,
+            "Invalid memory address 0x{:08X} ({access})",
+            self.address
+        )
+    }
+}
+
+impl std::error::Error for MemoryFault {}
+
+/// Hardware register handlers behind the I/O window (0xCC000000-0xCC00FFFF).
+///
+/// Installed with [`MemoryManager::set_mmio`]. Every 8/16/32-bit access to the
+/// window goes through it: a read it answers returns that value instead of the
+/// register's last written contents, and every write is stored as usual and
+/// then passed on. `size` is the access width in bytes; a 64-bit store
+/// (`stfd`) is passed on as two 32-bit writes, high word first.
+pub trait MmioBus: Send + Sync {
+    fn read(&self, address: u32, size: u8) -> Option<u32>;
+    fn write(&self, address: u32, size: u8, value: u32);
+}
+
+impl std::fmt::Debug for dyn MmioBus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MmioBus")
+    }
+}
+
+const IO_BASE: u32 = 0xCC00_0000;
+const IO_END: u32 = 0xCC00_FFFF;
 
 /// Memory manager for GameCube memory operations.
 ///
@@ -35,6 +88,17 @@ pub struct MemoryManager {
     ram: Vec<u8>,
     /// I/O registers (hardware register space: 0xCC000000-0xCC00FFFF)
     io_regs: Vec<u8>,
+    /// Emulated hardware behind the I/O registers, if attached.
+    mmio: Option<Arc<dyn MmioBus>>,
+    /// Bitset of RAM pages holding code someone cached (`watch_code`).
+    code_pages: Vec<u64>,
+    /// Bitset of RAM pages code has ever been fetched from. Unlike
+    /// `code_pages`, a write doesn't clear these.
+    exec_pages: Vec<u64>,
+    /// Watched pages written since, as page indices.
+    dirty_code: Vec<u32>,
+    /// Distinguishes this memory in caches keyed by guest address.
+    id: u64,
 }
 
 impl MemoryManager {
@@ -52,12 +116,122 @@ impl MemoryManager {
         // 24MB RAM model
         const RAM_SIZE: usize = 24usize * 1024usize * 1024usize; // 24MB
         const IO_SIZE: usize = 0x10000usize; // 64KB I/O register space
+        const PAGES: usize = RAM_SIZE / CODE_PAGE_SIZE as usize;
         Self {
             ram: vec![0u8; RAM_SIZE],
             io_regs: vec![0u8; IO_SIZE],
+            mmio: None,
+            code_pages: vec![0; PAGES.div_ceil(64)],
+            exec_pages: vec![0; PAGES.div_ceil(64)],
+            dirty_code: Vec::new(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
         }
     }
 
+    /// Unique per `MemoryManager` in this process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// RAM pages under `[address, address + len)`, if it starts in RAM.
+    fn ram_pages(&self, address: u32, len: usize) -> Option<std::ops::Range<usize>> {
+        let start = self.translate_address(address)?;
+        let end = start.saturating_add(len.max(1)).min(self.ram.len());
+        Some(start / CODE_PAGE_SIZE as usize..end.div_ceil(CODE_PAGE_SIZE as usize))
+    }
+
+    /// Start watching the RAM pages under `[address, address + len)` for
+    /// writes, e.g. because code was decoded from them. Each page written
+    /// afterwards is reported once by `take_dirty_code`. The pages are also
+    /// marked executable for good.
+    pub fn watch_code(&mut self, address: u32, len: usize) {
+        let Some(pages) = self.ram_pages(address, len) else {
+            return;
+        };
+        for page in pages {
+            self.code_pages[page / 64] |= 1 << (page % 64);
+            self.exec_pages[page / 64] |= 1 << (page % 64);
+        }
+    }
+
+    /// Whether code has been fetched from the page holding `address`.
+    pub fn is_executable(&self, address: u32) -> bool {
+        self.ram_pages(address, 1).is_some_and(|pages| {
+            let page = pages.start;
+            self.exec_pages[page / 64] & (1 << (page % 64)) != 0
+        })
+    }
+
+    /// Report the watched pages under `[address, address + len)` as dirty,
+    /// as if they had been written: what `icbi` asks of an instruction cache.
+    pub fn invalidate_code(&mut self, address: u32, len: usize) {
+        if let Some(pages) = self.ram_pages(address, len) {
+            let start = pages.start * CODE_PAGE_SIZE as usize;
+            let len = (pages.end - pages.start) * CODE_PAGE_SIZE as usize;
+            self.note_write(start, len);
+        }
+    }
+
+    /// Cached-address (0x8xxxxxxx) start of every watched page written since
+    /// the last call. Those pages are no longer watched.
+    pub fn take_dirty_code(&mut self) -> Vec<u32> {
+        std::mem::take(&mut self.dirty_code)
+            .into_iter()
+            .map(|page| 0x8000_0000 + page * CODE_PAGE_SIZE)
+            .collect()
+    }
+
+    /// Record a write of `len` bytes at RAM offset `offset` to watched pages.
+    #[inline(always)]
+    fn note_write(&mut self, offset: usize, len: usize) {
+        let first = offset / CODE_PAGE_SIZE as usize;
+        let last = (offset + len.max(1) - 1) / CODE_PAGE_SIZE as usize;
+        for page in first..=last {
+            let Some(bits) = self.code_pages.get_mut(page / 64) else {
+                break;
+            };
+            let bit = 1u64 << (page % 64);
+            if *bits & bit != 0 {
+                *bits &= !bit;
+                self.dirty_code.push(page as u32);
+            }
+        }
+    }
+
+    /// Route hardware register accesses to `bus` (`None` detaches it, leaving
+    /// the registers plain storage). The `*_io_*` accessors below always
+    /// bypass it, so handlers can update register contents themselves.
+    pub fn set_mmio(&mut self, bus: Option<Arc<dyn MmioBus>>) {
+        self.mmio = bus;
+    }
+
+    #[inline(always)]
+    fn mmio_read(&self, address: u32, size: u8) -> Option<u32> {
+        match &self.mmio {
+            Some(bus) if (IO_BASE..=IO_END).contains(&address) => bus.read(address, size),
+            _ => None,
+        }
+    }
+
+    #[inline(always)]
+    fn mmio_write(&self, address: u32, size: u8, value: u32) {
+        if let Some(bus) = &self.mmio {
+            if (IO_BASE..=IO_END).contains(&address) {
+                bus.write(address, size, value);
+            }
+        }
+    }
+
+    /// Create a memory manager whose RAM starts out as `fill` instead of zeros.
+    /// I/O registers still start at zero, as after a hardware reset.
+    pub fn with_fill(fill: MemoryFill) -> Self {
+        let mut memory = Self::new();
+        if fill != MemoryFill::Zero {
+            fill.apply(&mut memory.ram);
+        }
+        memory
+    }
+
     /// Translate a virtual address to a physical RAM offset.
     ///
     /// # Algorithm
@@ -102,8 +276,9 @@ impl MemoryManager {
     }
 
     #[inline(always)]
-    fn region_mut(&mut self, address: u32) -> Option<(&mut [u8], usize)> {
+    fn region_mut(&mut self, address: u32, len: usize) -> Option<(&mut [u8], usize)> {
         if let Some(off) = self.translate_address(address) {
+            self.note_write(off, len);
             Some((&mut self.ram, off))
         } else if (0xCC000000..=0xCC00FFFF).contains(&address) {
             Some((&mut self.io_regs, (address - 0xCC000000) as usize))
@@ -187,7 +362,13 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn read_u8(&self, address: u32) -> Result<u8> {
-        let (buf, off) = self.region(address).context("Invalid memory address")?;
+        if let Some(value) = self.mmio_read(address, 1) {
+            return Ok(value as u8);
+        }
+        let (buf, off) = self.region(address).ok_or(MemoryFault {
+            address,
+            write: false,
+        })?;
         buf.get(off).copied().context("Memory read out of bounds")
     }
 
@@ -208,7 +389,13 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn read_u16(&self, address: u32) -> Result<u16> {
-        let (buf, off) = self.region(address).context("Invalid memory address")?;
+        if let Some(value) = self.mmio_read(address, 2) {
+            return Ok(value as u16);
+        }
+        let (buf, off) = self.region(address).ok_or(MemoryFault {
+            address,
+            write: false,
+        })?;
         if off + 2 > buf.len() {
             anyhow::bail!("Memory read out of bounds");
         }
@@ -232,7 +419,13 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn read_u32(&self, address: u32) -> Result<u32> {
-        let (buf, off) = self.region(address).context("Invalid memory address")?;
+        if let Some(value) = self.mmio_read(address, 4) {
+            return Ok(value);
+        }
+        let (buf, off) = self.region(address).ok_or(MemoryFault {
+            address,
+            write: false,
+        })?;
         if off + 4 > buf.len() {
             anyhow::bail!("Memory read out of bounds");
         }
@@ -261,7 +454,10 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn read_u64(&self, address: u32) -> Result<u64> {
-        let (buf, off) = self.region(address).context("Invalid memory address")?;
+        let (buf, off) = self.region(address).ok_or(MemoryFault {
+            address,
+            write: false,
+        })?;
         if off + 8 > buf.len() {
             anyhow::bail!("Memory read out of bounds");
         }
@@ -288,8 +484,12 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn write_u8(&mut self, address: u32, value: u8) -> Result<()> {
-        let (buf, off) = self.region_mut(address).context("Invalid memory address")?;
+        let (buf, off) = self.region_mut(address, 1).ok_or(MemoryFault {
+            address,
+            write: true,
+        })?;
         *buf.get_mut(off).context("Memory write out of bounds")? = value;
+        self.mmio_write(address, 1, value as u32);
         Ok(())
     }
 
@@ -311,11 +511,15 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn write_u16(&mut self, address: u32, value: u16) -> Result<()> {
-        let (buf, off) = self.region_mut(address).context("Invalid memory address")?;
+        let (buf, off) = self.region_mut(address, 2).ok_or(MemoryFault {
+            address,
+            write: true,
+        })?;
         if off + 2 > buf.len() {
             anyhow::bail!("Memory write out of bounds");
         }
         buf[off..off + 2].copy_from_slice(&value.to_be_bytes());
+        self.mmio_write(address, 2, value as u32);
         Ok(())
     }
 
@@ -337,11 +541,15 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn write_u32(&mut self, address: u32, value: u32) -> Result<()> {
-        let (buf, off) = self.region_mut(address).context("Invalid memory address")?;
+        let (buf, off) = self.region_mut(address, 4).ok_or(MemoryFault {
+            address,
+            write: true,
+        })?;
         if off + 4 > buf.len() {
             anyhow::bail!("Memory write out of bounds");
         }
         buf[off..off + 4].copy_from_slice(&value.to_be_bytes());
+        self.mmio_write(address, 4, value);
         Ok(())
     }
 
@@ -363,11 +571,16 @@ impl MemoryManager {
     /// ```
     #[inline] // Hot path - may be inlined
     pub fn write_u64(&mut self, address: u32, value: u64) -> Result<()> {
-        let (buf, off) = self.region_mut(address).context("Invalid memory address")?;
+        let (buf, off) = self.region_mut(address, 8).ok_or(MemoryFault {
+            address,
+            write: true,
+        })?;
         if off + 8 > buf.len() {
             anyhow::bail!("Memory write out of bounds");
         }
         buf[off..off + 8].copy_from_slice(&value.to_be_bytes());
+        self.mmio_write(address, 4, (value >> 32) as u32);
+        self.mmio_write(address.wrapping_add(4), 4, value as u32);
         Ok(())
     }
 
@@ -422,6 +635,7 @@ impl MemoryManager {
         if offset.wrapping_add(data.len()) > self.ram.len() {
             anyhow::bail!("Memory write out of bounds");
         }
+        self.note_write(offset, data.len());
         self.ram[offset..offset.wrapping_add(data.len())].copy_from_slice(data);
         Ok(())
     }
@@ -480,6 +694,7 @@ impl MemoryManager {
         {
             anyhow::bail!("Bulk copy out of bounds");
         }
+        self.note_write(dest_offset, len);
 
         // Always use temporary buffer to avoid borrow checker issues with overlapping slices
         let temp: Vec<u8> = self.ram[src_offset..src_offset.wrapping_add(len)].to_vec();
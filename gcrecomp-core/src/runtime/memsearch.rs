@@ -0,0 +1,209 @@
+// Memory search for cheat discovery
+//
+// The classic loop: search MEM1 for the value the game shows (lives, rupees),
+// let it change in game, search again for the new value or for everything
+// that changed, and repeat until a handful of addresses are left. Each
+// `filter` call snapshots MEM1 and keeps the candidates whose value in the
+// new snapshot passes; `Changed`/`Unchanged` compare against the snapshot
+// before it. Values are big-endian and aligned to their width. Until the
+// first filter every aligned address is a candidate, without listing them.
+
+use crate::runtime::memory::MemoryManager;
+use std::fmt;
+use std::str::FromStr;
+
+/// Cached-address base of MEM1.
+const MEM1_BASE: u32 = 0x8000_0000;
+
+/// How many bytes a candidate value spans, and how to read them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueWidth {
+    U8,
+    U16,
+    #[default]
+    U32,
+    F32,
+}
+
+impl ValueWidth {
+    pub const ALL: [ValueWidth; 4] = [
+        ValueWidth::U8,
+        ValueWidth::U16,
+        ValueWidth::U32,
+        ValueWidth::F32,
+    ];
+
+    pub fn bytes(self) -> usize {
+        match self {
+            ValueWidth::U8 => 1,
+            ValueWidth::U16 => 2,
+            ValueWidth::U32 | ValueWidth::F32 => 4,
+        }
+    }
+
+    /// The value at `offset` in `ram`, if it fits.
+    fn read(self, ram: &[u8], offset: usize) -> Option<f64> {
+        let bytes = ram.get(offset..offset + self.bytes())?;
+        Some(match self {
+            ValueWidth::U8 => f64::from(bytes[0]),
+            ValueWidth::U16 => f64::from(u16::from_be_bytes([bytes[0], bytes[1]])),
+            ValueWidth::U32 => f64::from(u32::from_be_bytes(bytes.try_into().ok()?)),
+            ValueWidth::F32 => f64::from(f32::from_be_bytes(bytes.try_into().ok()?)),
+        })
+    }
+}
+
+impl fmt::Display for ValueWidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValueWidth::U8 => "u8",
+            ValueWidth::U16 => "u16",
+            ValueWidth::U32 => "u32",
+            ValueWidth::F32 => "f32",
+        })
+    }
+}
+
+impl FromStr for ValueWidth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "u8" => Ok(ValueWidth::U8),
+            "u16" => Ok(ValueWidth::U16),
+            "u32" => Ok(ValueWidth::U32),
+            "f32" => Ok(ValueWidth::F32),
+            other => Err(format!(
+                "unknown value width '{other}' (expected u8, u16, u32 or f32)"
+            )),
+        }
+    }
+}
+
+/// What a candidate's new value must satisfy to stay a candidate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchFilter {
+    Exact(f64),
+    GreaterThan(f64),
+    LessThan(f64),
+    /// Differs from the previous snapshot.
+    Changed,
+    /// Same as in the previous snapshot.
+    Unchanged,
+}
+
+impl SearchFilter {
+    /// Parse a filter by name: `exact`, `greater`, `less` (which need
+    /// `value`), `changed` or `unchanged`.
+    pub fn parse(kind: &str, value: Option<f64>) -> Result<Self, String> {
+        let need = || value.ok_or_else(|| format!("'{kind}' needs a value"));
+        match kind.trim().to_ascii_lowercase().as_str() {
+            "exact" | "eq" => Ok(SearchFilter::Exact(need()?)),
+            "greater" | "gt" => Ok(SearchFilter::GreaterThan(need()?)),
+            "less" | "lt" => Ok(SearchFilter::LessThan(need()?)),
+            "changed" => Ok(SearchFilter::Changed),
+            "unchanged" => Ok(SearchFilter::Unchanged),
+            other => Err(format!(
+                "unknown filter '{other}' (expected exact, greater, less, changed or unchanged)"
+            )),
+        }
+    }
+
+    fn keeps(self, previous: Option<f64>, current: f64) -> bool {
+        match self {
+            SearchFilter::Exact(v) => current == v,
+            SearchFilter::GreaterThan(v) => current > v,
+            SearchFilter::LessThan(v) => current < v,
+            // A NaN that stays NaN counts as unchanged.
+            SearchFilter::Changed => previous.is_some_and(|p| !same(p, current)),
+            SearchFilter::Unchanged => previous.is_some_and(|p| same(p, current)),
+        }
+    }
+}
+
+fn same(a: f64, b: f64) -> bool {
+    a == b || (a.is_nan() && b.is_nan())
+}
+
+/// One search session over MEM1.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySearch {
+    width: ValueWidth,
+    /// MEM1 as of the last snapshot.
+    snapshot: Option<Vec<u8>>,
+    /// `None` until the first filter: every aligned address.
+    candidates: Option<Vec<u32>>,
+}
+
+impl MemorySearch {
+    pub fn new(width: ValueWidth) -> Self {
+        Self {
+            width,
+            ..Self::default()
+        }
+    }
+
+    pub fn width(&self) -> ValueWidth {
+        self.width
+    }
+
+    /// Start over: snapshot MEM1 with every address a candidate again.
+    pub fn reset(&mut self, memory: &MemoryManager) {
+        self.snapshot = Some(memory.ram_slice().to_vec());
+        self.candidates = None;
+    }
+
+    /// Start over at another value width.
+    pub fn set_width(&mut self, width: ValueWidth, memory: &MemoryManager) {
+        self.width = width;
+        self.reset(memory);
+    }
+
+    /// Snapshot MEM1 and keep the candidates `filter` accepts. Returns how
+    /// many are left. Without an earlier snapshot `Changed` and `Unchanged`
+    /// keep nothing.
+    pub fn filter(&mut self, memory: &MemoryManager, filter: SearchFilter) -> usize {
+        let ram = memory.ram_slice();
+        let width = self.width;
+        let previous = self.snapshot.as_deref();
+        let keep = |address: u32| {
+            let offset = (address - MEM1_BASE) as usize;
+            width.read(ram, offset).is_some_and(|current| {
+                let before = previous.and_then(|p| width.read(p, offset));
+                filter.keeps(before, current)
+            })
+        };
+        let kept: Vec<u32> = match self.candidates.take() {
+            Some(candidates) => candidates.into_iter().filter(|&a| keep(a)).collect(),
+            None => (0..ram.len())
+                .step_by(width.bytes())
+                .map(|offset| MEM1_BASE + offset as u32)
+                .filter(|&a| keep(a))
+                .collect(),
+        };
+        self.candidates = Some(kept);
+        self.snapshot = Some(ram.to_vec());
+        self.count()
+    }
+
+    /// The addresses left, ascending; empty before the first filter.
+    pub fn candidates(&self) -> &[u32] {
+        self.candidates.as_deref().unwrap_or(&[])
+    }
+
+    /// How many addresses are left, counting every aligned one before the
+    /// first filter.
+    pub fn count(&self) -> usize {
+        match (&self.candidates, &self.snapshot) {
+            (Some(candidates), _) => candidates.len(),
+            (None, Some(snapshot)) => snapshot.len().div_ceil(self.width.bytes()),
+            (None, None) => 0,
+        }
+    }
+
+    /// `address`'s value in the last snapshot.
+    pub fn value_at(&self, address: u32) -> Option<f64> {
+        let offset = address.checked_sub(MEM1_BASE)? as usize;
+        self.width.read(self.snapshot.as_deref()?, offset)
+    }
+}
@@ -1,9 +1,23 @@
 pub mod calling;
+pub mod cheats;
+pub mod clock;
+pub mod comparison;
 pub mod context;
+pub mod debug;
+pub mod diagnostics;
+pub mod hot_reload;
+pub mod interpreter;
 pub mod memory;
+pub mod memsearch;
+pub mod regression;
+pub mod rng;
 pub mod sdk;
+pub mod trace;
+pub mod watchdog;
 
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 /// Wall-clock "stop" flag for recompiled code. The recompiled entry may spin on
 /// hardware/SDK state we don't fully emulate; a watchdog thread sets this after a
@@ -26,6 +40,151 @@ pub fn arm_watchdog(secs: u64) {
     });
 }
 
+/// How the game asked to leave: `OSResetSystem` / return to the IPL menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownRequest {
+    /// Reboot the game (`OS_RESET_RESTART` / `OS_RESET_HOTRESET`); the host
+    /// reloads the image and runs the entry again. `code` is the reset code the
+    /// game reads back with `OSGetResetCode` after the reboot.
+    Restart { code: u32, hot: bool },
+    /// Back to the IPL menu (`forceMenu`); there is no menu, so the host exits.
+    ReturnToMenu,
+    /// `OS_RESET_SHUTDOWN`: power off.
+    PowerOff,
+}
+
+static SHUTDOWN: Mutex<Option<ShutdownRequest>> = Mutex::new(None);
+
+/// Ask the host to stop emulation. Also trips the stop flag so recompiled code
+/// unwinds back to the host at its next budget check.
+pub fn request_shutdown(request: ShutdownRequest) {
+    *SHUTDOWN.lock().unwrap_or_else(|e| e.into_inner()) = Some(request);
+    STOP.store(true, Ordering::Relaxed);
+}
+
+/// The pending shutdown request, if the game asked for one.
+pub fn shutdown_requested() -> Option<ShutdownRequest> {
+    *SHUTDOWN.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Take the pending request, clearing it (the host is handling it).
+pub fn take_shutdown_request() -> Option<ShutdownRequest> {
+    SHUTDOWN.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+// --- Tail calls ---
+//
+// A call in tail position (`b` out of the function, or `bl` straight into
+// `blr`) doesn't nest: the generated function records its target with
+// `tail_call` and returns, and the dispatcher's `trampoline` loop runs the
+// target next. Host stack depth stays bounded however long the chain.
+thread_local! {
+    static TAIL_CALL: std::cell::Cell<Option<u32>> = const { std::cell::Cell::new(None) };
+}
+
+/// Called by generated code just before returning from a tail call.
+#[inline]
+pub fn tail_call(target: u32) {
+    TAIL_CALL.with(|t| t.set(Some(target)));
+}
+
+/// Run `address` through `dispatch`, then every tail call it chains to.
+/// After a tail call the result follows the old nested-call convention: the
+/// callee's return value if it produced one, otherwise r3 as it left it.
+pub fn trampoline(
+    address: u32,
+    ctx: &mut context::CpuContext,
+    memory: &mut memory::MemoryManager,
+    dispatch: interpreter::CallFn,
+) -> anyhow::Result<Option<u32>> {
+    let catch = CATCH_PANICS.load(Ordering::Relaxed);
+    let log_calls = diagnostics::FunctionCallLogger::is_enabled();
+    let run = |address: u32, ctx: &mut context::CpuContext, memory: &mut memory::MemoryManager| {
+        if log_calls {
+            diagnostics::FunctionCallLogger::enter(address);
+        }
+        let result = if catch {
+            dispatch_catching_panics(address, ctx, memory, dispatch)
+        } else {
+            dispatch(address, ctx, memory)
+        };
+        if log_calls {
+            diagnostics::FunctionCallLogger::leave(result.is_err());
+        }
+        result
+    };
+    let mut result = run(address, ctx, memory);
+    while let Some(next) = TAIL_CALL.with(|t| t.take()) {
+        result = match run(next, ctx, memory) {
+            Ok(Some(rv)) => Ok(Some(rv)),
+            Err(e) if is_panic(&e) => return Err(e),
+            Ok(None) | Err(_) => Ok(Some(ctx.get_register(3))),
+        };
+    }
+    result
+}
+
+// --- Opt-in panic containment ---
+//
+// A panic in generated code (an overflow or division check, a bug in an SDK
+// handler) would unwind through every recompiled frame and take down the
+// runtime thread. With `set_catch_panics(true)` each dispatch runs under
+// `catch_unwind` and a panic becomes `ExecutionError::Panic` for the function
+// that raised it. Generated callers don't propagate errors from their callees,
+// so the first panic is also kept for the host to pick up with `take_panic`.
+// Off by default: `catch_unwind` around every call isn't free.
+static CATCH_PANICS: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static LAST_PANIC: std::cell::RefCell<Option<watchdog::ExecutionError>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Turn panics in dispatched functions into errors (`[runtime] catch_panics`).
+pub fn set_catch_panics(enabled: bool) {
+    CATCH_PANICS.store(enabled, Ordering::Relaxed);
+}
+
+/// The first panic caught on this thread since the last call, if any.
+pub fn take_panic() -> Option<watchdog::ExecutionError> {
+    LAST_PANIC.with(|p| p.borrow_mut().take())
+}
+
+fn dispatch_catching_panics(
+    address: u32,
+    ctx: &mut context::CpuContext,
+    memory: &mut memory::MemoryManager,
+    dispatch: interpreter::CallFn,
+) -> anyhow::Result<Option<u32>> {
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        dispatch(address, ctx, memory)
+    }));
+    result.unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        let error = watchdog::ExecutionError::Panic {
+            address,
+            message,
+            recent: watchdog::recent_pcs(),
+        };
+        log::error!("{error}");
+        LAST_PANIC.with(|p| {
+            p.borrow_mut().get_or_insert_with(|| error.clone());
+        });
+        Err(error.into())
+    })
+}
+
+fn is_panic(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<watchdog::ExecutionError>(),
+        Some(watchdog::ExecutionError::Panic { .. })
+    )
+}
+
 // --- Optional function-call trace (for debugging where boot diverges) ---
 use std::sync::atomic::AtomicU64;
 static TRACE: AtomicBool = AtomicBool::new(false);
@@ -36,9 +195,32 @@ pub fn enable_trace() {
     TRACE.store(true, Ordering::Relaxed);
 }
 
-/// Called at the top of every generated function. No-op unless tracing is on.
+// --- Optional per-function call counts (input to `--profile-guided`) ---
+static PROFILE: AtomicBool = AtomicBool::new(false);
+static CALL_COUNTS: Mutex<BTreeMap<u32, u64>> = Mutex::new(BTreeMap::new());
+
+/// Start counting function entries.
+pub fn enable_call_profile() {
+    PROFILE.store(true, Ordering::Relaxed);
+}
+
+/// Drain the call counts gathered so far as `(address, calls)`.
+pub fn take_call_counts() -> Vec<(u32, u64)> {
+    let mut counts = CALL_COUNTS.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::take(&mut *counts).into_iter().collect()
+}
+
+/// Called at the top of every generated function. No-op unless tracing or
+/// call profiling is on.
 #[inline]
 pub fn trace_call(addr: u32) {
+    if PROFILE.load(Ordering::Relaxed) {
+        *CALL_COUNTS
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(addr)
+            .or_insert(0) += 1;
+    }
     if !TRACE.load(Ordering::Relaxed) {
         return;
     }
@@ -0,0 +1,527 @@
+// Per-function regression tests from captured calls
+//
+// `RegressionTestCase::capture` runs one call and records what went in and
+// what came out: the registers on entry and on return, plus the memory ranges
+// the caller asked to snapshot. `FunctionTestHarness` replays a case through
+// a call function (the interpreter, or the generated
+// `call_function_by_address`) and reports every register or byte that comes
+// out different. Memory is checked over every range the case names: a range
+// recorded on entry but missing from the expected memory must come out
+// unchanged, and expected bytes the replay can't read are reported rather
+// than failing the run. `generate_test_module` writes a set of cases out as a Rust
+// file of `#[test]`s, one per case, so a recompiled function's behaviour is
+// locked in once it is known to be right.
+//
+// Rather than writing expected states by hand, `FunctionTestHarness::record`
+// runs a list of `InitialState`s and keeps whatever comes out as the golden:
+// the final registers and every byte of RAM the call changed. Registers whose
+// value depends on the caller rather than the function (`VOLATILE_SLOTS`) can
+// be masked so replays ignore them. Cases round-trip through JSON with
+// `save_to_file` and `load_from_file`.
+//
+// `run_all` replays a whole suite and can skip cases it has already run: a
+// `ResultCache` keeps each outcome under a hash of the entire case (address,
+// registers, memory and expectations), so editing a case or reusing a name
+// never returns a stale result. The cache is tagged with the executor it was
+// filled by (a build id, say) and forgets everything when that changes.
+
+use crate::runtime::context::CpuContext;
+use crate::runtime::interpreter::{interpret_function, CallFn};
+use crate::runtime::memory::MemoryManager;
+use crate::runtime::trace::RegisterState;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+/// Slots of registers left to the caller's circumstances: LR (the return
+/// address) and CTR.
+pub const VOLATILE_SLOTS: [usize; 2] = [64, 65];
+
+/// Changed runs of RAM closer than this are recorded as one region.
+const MERGE_GAP: usize = 8;
+
+/// Bytes at a guest address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryRegion {
+    pub address: u32,
+    pub bytes: Vec<u8>,
+}
+
+impl MemoryRegion {
+    pub fn new(address: u32, bytes: Vec<u8>) -> Self {
+        Self { address, bytes }
+    }
+}
+
+/// One recorded call of a function.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegressionTestCase {
+    /// Function name, used to name the generated test.
+    pub name: String,
+    pub address: u32,
+    /// Registers on entry.
+    pub input: RegisterState,
+    /// Memory before the call, including the function's code if it is to be
+    /// interpreted.
+    pub input_memory: Vec<MemoryRegion>,
+    /// Registers on return.
+    pub expected: RegisterState,
+    /// The same ranges as `input_memory`, after the call. Input ranges left
+    /// out are expected unchanged.
+    pub expected_memory: Vec<MemoryRegion>,
+    /// Register slots not compared on replay.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub masked: Vec<usize>,
+}
+
+/// Where `FunctionTestHarness::record` starts a call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InitialState {
+    pub name: String,
+    pub address: u32,
+    pub registers: RegisterState,
+    /// Memory before the call, on top of zeroed RAM.
+    pub memory: Vec<MemoryRegion>,
+}
+
+impl RegressionTestCase {
+    /// Call `address` through `call` with the current `ctx` and `memory`,
+    /// snapshotting the `(address, len)` ranges before and after.
+    pub fn capture(
+        name: &str,
+        address: u32,
+        call: CallFn,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+        ranges: &[(u32, usize)],
+    ) -> Result<Self> {
+        let snapshot = |memory: &MemoryManager| -> Result<Vec<MemoryRegion>> {
+            ranges
+                .iter()
+                .map(|&(addr, len)| Ok(MemoryRegion::new(addr, memory.read_bytes(addr, len)?)))
+                .collect()
+        };
+        let input = RegisterState::from_context(ctx);
+        let input_memory = snapshot(memory)?;
+        call(address, ctx, memory)?;
+        Ok(Self {
+            name: name.to_string(),
+            address,
+            input,
+            input_memory,
+            expected: RegisterState::from_context(ctx),
+            expected_memory: snapshot(memory)?,
+            masked: Vec::new(),
+        })
+    }
+}
+
+/// SHA-256 (hex) of everything in `case`; any edit changes it.
+pub fn case_hash(case: &RegressionTestCase) -> String {
+    use sha2::{Digest, Sha256};
+    let json = serde_json::to_vec(case).unwrap_or_default();
+    Sha256::digest(json)
+        .iter()
+        .fold(String::with_capacity(64), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
+
+/// Outcomes of earlier replays, by `case_hash`, for one executor.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ResultCache {
+    executor: String,
+    /// Differences found (empty for a pass).
+    results: HashMap<String, Vec<String>>,
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+impl ResultCache {
+    /// A cache that lives as long as this value.
+    pub fn in_memory(executor: &str) -> Self {
+        Self {
+            executor: executor.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// The cache saved at `path`, or an empty one if there is none or it was
+    /// filled by another executor. `save` writes it back there.
+    pub fn open(path: &Path, executor: &str) -> Result<Self> {
+        let mut cache = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str::<Self>(&text)
+                .ok()
+                .filter(|cache| cache.executor == executor)
+                .unwrap_or_else(|| Self::in_memory(executor)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::in_memory(executor),
+            Err(e) => return Err(e).with_context(|| format!("reading {}", path.display())),
+        };
+        cache.path = Some(path.to_path_buf());
+        Ok(cache)
+    }
+
+    /// Write to the path given to `open`; nothing for an in-memory cache.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        std::fs::write(path, serde_json::to_string(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.results.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.results.is_empty()
+    }
+}
+
+/// One case's outcome from `run_all`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseResult {
+    pub name: String,
+    pub address: u32,
+    /// As `FunctionTestHarness::run` reports them; a failed call is one
+    /// `call failed: ...` entry.
+    pub diffs: Vec<String>,
+    /// Taken from the cache rather than replayed.
+    pub cached: bool,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+/// Replays `RegressionTestCase`s against a call function.
+#[derive(Debug, Clone, Copy)]
+pub struct FunctionTestHarness {
+    call: CallFn,
+}
+
+impl FunctionTestHarness {
+    pub fn new(call: CallFn) -> Self {
+        Self { call }
+    }
+
+    /// Run cases through the interpreter.
+    pub fn interpreted() -> Self {
+        Self::new(interpret_function)
+    }
+
+    /// Replay `case` on a fresh context and memory. Returns the differences
+    /// from what was recorded (`r3: expected 0x..., got 0x...`); empty when
+    /// the call behaves the same.
+    pub fn run(&self, case: &RegressionTestCase) -> Result<Vec<String>> {
+        let mut memory = MemoryManager::new();
+        for region in &case.input_memory {
+            memory.write_bytes(region.address, &region.bytes)?;
+        }
+        let mut ctx = CpuContext::new();
+        case.input.apply_to(&mut ctx);
+        (self.call)(case.address, &mut ctx, &mut memory)?;
+
+        let actual = RegisterState::from_context(&ctx);
+        let mut diffs: Vec<String> = case
+            .expected
+            .changed_since(&actual)
+            .into_iter()
+            .filter(|slot| !case.masked.contains(slot))
+            .map(|slot| {
+                format!(
+                    "{}: expected 0x{:08X}, got 0x{:08X}",
+                    RegisterState::slot_name(slot),
+                    case.expected.slot(slot),
+                    actual.slot(slot)
+                )
+            })
+            .collect();
+        diffs.extend(memory_diffs(case, &memory));
+        Ok(diffs)
+    }
+
+    /// Run each of `states` and record the outcome as a case: the registers
+    /// on return and, as `expected_memory`, the bytes of RAM the call changed.
+    /// With `mask_volatile`, `VOLATILE_SLOTS` are left out of the comparison.
+    pub fn record(
+        &self,
+        states: &[InitialState],
+        mask_volatile: bool,
+    ) -> Result<Vec<RegressionTestCase>> {
+        states
+            .iter()
+            .map(|state| {
+                let mut memory = MemoryManager::new();
+                for region in &state.memory {
+                    memory.write_bytes(region.address, &region.bytes)?;
+                }
+                let before = memory.ram_slice().to_vec();
+                let mut ctx = CpuContext::new();
+                state.registers.apply_to(&mut ctx);
+                (self.call)(state.address, &mut ctx, &mut memory).with_context(|| {
+                    format!("recording {} (0x{:08X})", state.name, state.address)
+                })?;
+                Ok(RegressionTestCase {
+                    name: state.name.clone(),
+                    address: state.address,
+                    input: state.registers,
+                    input_memory: state.memory.clone(),
+                    expected: RegisterState::from_context(&ctx),
+                    expected_memory: changed_regions(&before, memory.ram_slice()),
+                    masked: if mask_volatile {
+                        VOLATILE_SLOTS.to_vec()
+                    } else {
+                        Vec::new()
+                    },
+                })
+            })
+            .collect()
+    }
+
+    /// Replay every case, in order. With a `cache`, cases whose hash it
+    /// already holds are not replayed, and the rest are added to it. Calls
+    /// that fail outright are reported but never cached.
+    pub fn run_all(
+        &self,
+        cases: &[RegressionTestCase],
+        mut cache: Option<&mut ResultCache>,
+    ) -> Vec<CaseResult> {
+        cases
+            .iter()
+            .map(|case| {
+                let hash = cache.as_ref().map(|_| case_hash(case));
+                let cached = match (&cache, &hash) {
+                    (Some(cache), Some(hash)) => cache.results.get(hash).cloned(),
+                    _ => None,
+                };
+                let (diffs, from_cache) = match cached {
+                    Some(diffs) => (diffs, true),
+                    None => match self.run(case) {
+                        Ok(diffs) => {
+                            if let (Some(cache), Some(hash)) = (cache.as_mut(), hash) {
+                                cache.results.insert(hash, diffs.clone());
+                            }
+                            (diffs, false)
+                        }
+                        Err(e) => (vec![format!("call failed: {e:#}")], false),
+                    },
+                };
+                CaseResult {
+                    name: case.name.clone(),
+                    address: case.address,
+                    diffs,
+                    cached: from_cache,
+                }
+            })
+            .collect()
+    }
+
+    /// `run`, failing with the differences if there are any.
+    pub fn check(&self, case: &RegressionTestCase) -> Result<()> {
+        let diffs = self.run(case)?;
+        if !diffs.is_empty() {
+            bail!(
+                "{} (0x{:08X}) regressed:\n  {}",
+                case.name,
+                case.address,
+                diffs.join("\n  ")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// The runs of `after` that differ from `before` (both RAM from 0x80000000),
+/// with their new contents.
+fn changed_regions(before: &[u8], after: &[u8]) -> Vec<MemoryRegion> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (i, _) in before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .filter(|(_, (b, a))| b != a)
+    {
+        match runs.last_mut() {
+            Some((_, end)) if i - *end <= MERGE_GAP => *end = i + 1,
+            _ => runs.push((i, i + 1)),
+        }
+    }
+    runs.into_iter()
+        .map(|(start, end)| {
+            MemoryRegion::new(0x8000_0000 + start as u32, after[start..end].to_vec())
+        })
+        .collect()
+}
+
+/// Write `cases` as the JSON `load_from_file` reads.
+pub fn save_to_file(path: &Path, cases: &[RegressionTestCase]) -> Result<()> {
+    let json = serde_json::to_string_pretty(cases)?;
+    std::fs::write(path, json).with_context(|| format!("writing {}", path.display()))
+}
+
+/// Cases saved by `save_to_file`.
+pub fn load_from_file(path: &Path) -> Result<Vec<RegressionTestCase>> {
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Byte differences between `memory` after a replay and what `case` expects,
+/// by address, over the union of its input and expected ranges. Expected
+/// bytes win where the two overlap; the rest of the input is expected as it
+/// went in.
+fn memory_diffs(case: &RegressionTestCase, memory: &MemoryManager) -> Vec<String> {
+    let mut expected: BTreeMap<u32, u8> = BTreeMap::new();
+    for region in case.input_memory.iter().chain(&case.expected_memory) {
+        for (i, &byte) in region.bytes.iter().enumerate() {
+            expected.insert(region.address.wrapping_add(i as u32), byte);
+        }
+    }
+    expected
+        .into_iter()
+        .filter_map(|(address, e)| match memory.read_u8(address) {
+            Ok(g) if g == e => None,
+            Ok(g) => Some(format!(
+                "[0x{address:08X}]: expected 0x{e:02X}, got 0x{g:02X}"
+            )),
+            Err(_) => Some(format!("[0x{address:08X}]: expected 0x{e:02X}, unmapped")),
+        })
+        .collect()
+}
+
+/// Rust source for a test module with one `#[test]` per case. `call` is the
+/// path of the `CallFn` the tests go through, e.g.
+/// `gcrecomp_core::runtime::interpreter::interpret_function` or the
+/// recompiled crate's `call_function_by_address`. Tests are named
+/// `test_<function>`, with a `_<n>` suffix when a function has several cases.
+pub fn generate_test_module(cases: &[RegressionTestCase], call: &str) -> String {
+    let mut out = String::new();
+    out.push_str("// Generated from captured calls by `generate_test_module`; do not edit.\n\n");
+    out.push_str("use gcrecomp_core::runtime::regression::{\n");
+    out.push_str("    FunctionTestHarness, MemoryRegion, RegressionTestCase,\n};\n");
+    out.push_str("use gcrecomp_core::runtime::trace::RegisterState;\n\n");
+    let _ = writeln!(out, "fn harness() -> FunctionTestHarness {{");
+    let _ = writeln!(out, "    FunctionTestHarness::new({call})\n}}");
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let total = |ident: &str| {
+        cases
+            .iter()
+            .filter(|c| test_ident(&c.name) == ident)
+            .count()
+    };
+    for case in cases {
+        let ident = test_ident(&case.name);
+        let n = seen.entry(ident.clone()).or_default();
+        let test_name = if total(&ident) > 1 {
+            format!("test_{ident}_{n}")
+        } else {
+            format!("test_{ident}")
+        };
+        *n += 1;
+
+        let _ = writeln!(out, "\n#[test]\nfn {test_name}() {{");
+        let _ = writeln!(out, "    // {} at 0x{:08X}", case.name, case.address);
+        let _ = writeln!(out, "    // Input: {}", describe(&case.input));
+        for region in &case.input_memory {
+            let _ = writeln!(
+                out,
+                "    // Memory: 0x{:08X}..0x{:08X}",
+                region.address,
+                region.address.wrapping_add(region.bytes.len() as u32)
+            );
+        }
+        let _ = writeln!(out, "    let case = RegressionTestCase {{");
+        let _ = writeln!(out, "        name: {:?}.to_string(),", case.name);
+        let _ = writeln!(out, "        address: 0x{:08X},", case.address);
+        let _ = writeln!(out, "        input: {},", registers_literal(&case.input));
+        let _ = writeln!(
+            out,
+            "        input_memory: {},",
+            regions_literal(&case.input_memory)
+        );
+        let _ = writeln!(
+            out,
+            "        expected: {},",
+            registers_literal(&case.expected)
+        );
+        let _ = writeln!(
+            out,
+            "        expected_memory: {},",
+            regions_literal(&case.expected_memory)
+        );
+        let _ = writeln!(out, "        masked: vec!{:?},", case.masked);
+        out.push_str("    };\n");
+        out.push_str("    harness().check(&case).unwrap();\n}\n");
+    }
+    out
+}
+
+/// A function name as a lowercase identifier fragment.
+fn test_ident(name: &str) -> String {
+    let ident: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if ident.is_empty() {
+        "function".to_string()
+    } else {
+        ident
+    }
+}
+
+/// Non-zero registers, `r3=0x0000000A lr=0x80001234`.
+fn describe(state: &RegisterState) -> String {
+    let nonzero = RegisterState::default().changed_since(state);
+    if nonzero.is_empty() {
+        return "all registers zero".to_string();
+    }
+    nonzero
+        .into_iter()
+        .map(|slot| {
+            format!(
+                "{}=0x{:08X}",
+                RegisterState::slot_name(slot),
+                state.slot(slot)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn registers_literal(state: &RegisterState) -> String {
+    let slots: Vec<String> = RegisterState::default()
+        .changed_since(state)
+        .into_iter()
+        .map(|slot| format!("({slot}, 0x{:X})", state.slot(slot)))
+        .collect();
+    format!("RegisterState::from_slots(&[{}])", slots.join(", "))
+}
+
+fn regions_literal(regions: &[MemoryRegion]) -> String {
+    let regions: Vec<String> = regions
+        .iter()
+        .map(|r| {
+            let bytes: Vec<String> = r.bytes.iter().map(|b| format!("0x{b:02X}")).collect();
+            format!(
+                "MemoryRegion::new(0x{:08X}, vec![{}])",
+                r.address,
+                bytes.join(", ")
+            )
+        })
+        .collect();
+    format!("vec![{}]", regions.join(", "))
+}
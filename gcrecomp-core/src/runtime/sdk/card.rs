@@ -0,0 +1,752 @@
+//! Memory card emulation (`CARD*` API and the EXI memory-card device).
+//!
+//! A card image is a flat array of 8 KiB blocks in the standard layout:
+//!
+//! ```text
+//! block 0      header (card size, encoding)
+//! blocks 1-2   directory, two copies (127 64-byte entries each)
+//! blocks 3-4   block allocation table (BAT), two copies
+//! blocks 5..   file data, chained through the BAT
+//! ```
+//!
+//! The directory and BAT are double-buffered: the copy with the higher update
+//! counter (and a valid checksum) is current, and every change is written to
+//! the other copy. Images are backed by a host `.raw` (the whole card) or
+//! `.gci` (a single save, imported into a blank card and exported back).
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::runtime::memory::MemoryManager;
+
+pub const BLOCK_SIZE: usize = 0x2000;
+/// Granularity the SDK requires for `CARDRead` / `CARDWrite` offsets.
+pub const CARD_READ_SIZE: usize = 0x200;
+/// EXI write-page size.
+pub const PAGE_SIZE: usize = 0x80;
+pub const DIR_ENTRY_SIZE: usize = 0x40;
+pub const MAX_FILES: usize = 127;
+pub const MAX_FILENAME: usize = 32;
+/// First block holding file data.
+pub const FIRST_DATA_BLOCK: u16 = 5;
+/// Card sizes in Mbit (59, 251 and 1019 usable blocks).
+pub const MBIT_4: u16 = 4;
+pub const MBIT_16: u16 = 16;
+pub const MBIT_64: u16 = 64;
+
+const HEADER_SIZE_MBIT: usize = 0x22;
+const HEADER_ENCODING: usize = 0x24;
+const HEADER_CHECKSUM: usize = 0x1FC;
+const DIR_UPDATE: usize = 0x1FFA;
+const DIR_CHECKSUM: usize = 0x1FFC;
+const BAT_CHECKSUM: usize = 0x0;
+const BAT_UPDATE: usize = 0x4;
+const BAT_FREE: usize = 0x6;
+const BAT_LAST_ALLOC: usize = 0x8;
+const BAT_MAP: usize = 0xA;
+const BAT_LAST: u16 = 0xFFFF;
+
+/// Directory entry fields.
+const ENT_GAMECODE: usize = 0x00;
+const ENT_MAKER: usize = 0x04;
+const ENT_FILENAME: usize = 0x08;
+const ENT_FIRST_BLOCK: usize = 0x36;
+const ENT_BLOCK_COUNT: usize = 0x38;
+
+/// `CARD_RESULT_*` failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardError {
+    WrongDevice,
+    NoCard,
+    NoFile,
+    IoError,
+    Broken,
+    Exist,
+    /// No free directory entry.
+    NoEnt,
+    /// Not enough free blocks ("card full").
+    InsSpace,
+    NoPerm,
+    /// Access past the end of the file.
+    Limit,
+    NameTooLong,
+}
+
+impl CardError {
+    /// The `CARD_RESULT_*` value returned to the game.
+    pub fn code(self) -> i32 {
+        match self {
+            CardError::WrongDevice => -2,
+            CardError::NoCard => -3,
+            CardError::NoFile => -4,
+            CardError::IoError => -5,
+            CardError::Broken => -6,
+            CardError::Exist => -7,
+            CardError::NoEnt => -8,
+            CardError::InsSpace => -9,
+            CardError::NoPerm => -10,
+            CardError::Limit => -11,
+            CardError::NameTooLong => -12,
+        }
+    }
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            CardError::WrongDevice => "not a memory card",
+            CardError::NoCard => "no card inserted",
+            CardError::NoFile => "file not found",
+            CardError::IoError => "I/O error",
+            CardError::Broken => "card image is corrupt",
+            CardError::Exist => "file already exists",
+            CardError::NoEnt => "directory is full",
+            CardError::InsSpace => "not enough free blocks",
+            CardError::NoPerm => "permission denied",
+            CardError::Limit => "access outside the file",
+            CardError::NameTooLong => "file name too long",
+        };
+        write!(f, "{} (CARD_RESULT {})", text, self.code())
+    }
+}
+
+impl std::error::Error for CardError {}
+
+/// Where a card image is persisted.
+#[derive(Debug, Clone)]
+enum Backing {
+    Raw(PathBuf),
+    /// A single save; the first file on the card is exported back to it.
+    Gci(PathBuf),
+}
+
+/// One memory card image.
+#[derive(Debug, Clone)]
+pub struct MemoryCard {
+    image: Vec<u8>,
+    backing: Option<Backing>,
+    /// Index (1 or 2) of the current directory copy.
+    dir_block: usize,
+    /// Index (3 or 4) of the current BAT copy.
+    bat_block: usize,
+    /// EXI status register.
+    exi_status: u8,
+}
+
+impl MemoryCard {
+    /// A blank, formatted card of `size_mbit` (4, 16 or 64) not backed by a file.
+    pub fn format(size_mbit: u16) -> Self {
+        let blocks = size_mbit as usize * 16;
+        let mut image = vec![0xFF; blocks * BLOCK_SIZE];
+        let header = &mut image[..BLOCK_SIZE];
+        header[..0x200].fill(0);
+        header[HEADER_SIZE_MBIT..HEADER_SIZE_MBIT + 2].copy_from_slice(&size_mbit.to_be_bytes());
+        header[HEADER_ENCODING..HEADER_ENCODING + 2].copy_from_slice(&0u16.to_be_bytes());
+        let (sum, inv) = checksum(&header[..HEADER_CHECKSUM]);
+        put_u16(header, HEADER_CHECKSUM, sum);
+        put_u16(header, HEADER_CHECKSUM + 2, inv);
+
+        let mut card = Self {
+            image,
+            backing: None,
+            dir_block: 1,
+            bat_block: 3,
+            exi_status: 0x41, // ready, unlocked
+        };
+        for block in [1, 2] {
+            let dir = card.block_mut(block);
+            dir.fill(0xFF);
+            put_u16(dir, DIR_UPDATE, 0);
+            seal_dir(dir);
+        }
+        for block in [3, 4] {
+            let bat = card.block_mut(block);
+            bat.fill(0);
+            put_u16(bat, BAT_FREE, (blocks - FIRST_DATA_BLOCK as usize) as u16);
+            put_u16(bat, BAT_LAST_ALLOC, FIRST_DATA_BLOCK - 1);
+            seal_bat(bat);
+        }
+        card
+    }
+
+    /// Parse a whole-card image, picking the current directory and BAT copies.
+    pub fn from_raw(image: Vec<u8>) -> Result<Self, CardError> {
+        if image.len() % BLOCK_SIZE != 0
+            || image.len() < (FIRST_DATA_BLOCK as usize + 1) * BLOCK_SIZE
+        {
+            return Err(CardError::Broken);
+        }
+        let mut card = Self {
+            image,
+            backing: None,
+            dir_block: 1,
+            bat_block: 3,
+            exi_status: 0x41,
+        };
+        card.dir_block = card.current_copy(1, DIR_UPDATE, dir_valid)?;
+        card.bat_block = card.current_copy(3, BAT_UPDATE, bat_valid)?;
+        Ok(card)
+    }
+
+    /// Load the card at `path`: a `.gci` is imported into a blank 16 Mbit card,
+    /// anything else is a raw image. A missing file gives a blank card that is
+    /// created on the first `flush`.
+    pub fn open(path: &Path) -> Result<Self, CardError> {
+        let is_gci = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("gci"));
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => Some(bytes),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::warn!("Memory card {}: {}", path.display(), e);
+                return Err(CardError::IoError);
+            }
+        };
+        let mut card = match (bytes, is_gci) {
+            (Some(bytes), true) => {
+                let mut card = Self::format(MBIT_16);
+                card.import_gci(&bytes)?;
+                card
+            }
+            (Some(bytes), false) => Self::from_raw(bytes)?,
+            (None, _) => Self::format(MBIT_16),
+        };
+        card.backing = Some(if is_gci {
+            Backing::Gci(path.to_path_buf())
+        } else {
+            Backing::Raw(path.to_path_buf())
+        });
+        Ok(card)
+    }
+
+    /// Write the card back to its host file, if it has one.
+    pub fn flush(&self) -> Result<(), CardError> {
+        let (path, bytes) = match &self.backing {
+            None => return Ok(()),
+            Some(Backing::Raw(path)) => (path, self.image.clone()),
+            Some(Backing::Gci(path)) => match self.files().next() {
+                Some(file_no) => (path, self.export_gci(file_no)?),
+                None => return Ok(()),
+            },
+        };
+        std::fs::write(path, bytes).map_err(|e| {
+            log::warn!("Memory card {}: {}", path.display(), e);
+            CardError::IoError
+        })
+    }
+
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// Total blocks, including the five system blocks.
+    pub fn blocks(&self) -> u16 {
+        (self.image.len() / BLOCK_SIZE) as u16
+    }
+
+    pub fn free_blocks(&self) -> u16 {
+        get_u16(self.block(self.bat_block), BAT_FREE)
+    }
+
+    /// Numbers of the used directory entries.
+    pub fn files(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..MAX_FILES as u16).filter(|&n| !entry_is_free(self.entry(n)))
+    }
+
+    /// The file named `name` owned by `game`/`maker`.
+    pub fn find(&self, game: [u8; 4], maker: [u8; 2], name: &str) -> Option<u16> {
+        self.files().find(|&n| {
+            let e = self.entry(n);
+            e[ENT_GAMECODE..ENT_GAMECODE + 4] == game
+                && e[ENT_MAKER..ENT_MAKER + 2] == maker
+                && entry_name(e) == name.as_bytes()
+        })
+    }
+
+    /// File size in bytes.
+    pub fn file_len(&self, file_no: u16) -> Result<usize, CardError> {
+        let e = self.used_entry(file_no)?;
+        Ok(get_u16(e, ENT_BLOCK_COUNT) as usize * BLOCK_SIZE)
+    }
+
+    /// Create a file of `size` bytes (rounded up to whole blocks).
+    pub fn create(
+        &mut self,
+        game: [u8; 4],
+        maker: [u8; 2],
+        name: &str,
+        size: usize,
+    ) -> Result<u16, CardError> {
+        if name.len() > MAX_FILENAME {
+            return Err(CardError::NameTooLong);
+        }
+        if self.find(game, maker, name).is_some() {
+            return Err(CardError::Exist);
+        }
+        let mut entry = [0xFFu8; DIR_ENTRY_SIZE];
+        entry[ENT_GAMECODE..ENT_GAMECODE + 4].copy_from_slice(&game);
+        entry[ENT_MAKER..ENT_MAKER + 2].copy_from_slice(&maker);
+        let filename = &mut entry[ENT_FILENAME..ENT_FILENAME + MAX_FILENAME];
+        filename.fill(0);
+        filename[..name.len()].copy_from_slice(name.as_bytes());
+        let blocks = size.div_ceil(BLOCK_SIZE).max(1) as u16;
+        put_u16(&mut entry, ENT_BLOCK_COUNT, blocks);
+        self.add_entry(entry)
+    }
+
+    /// Read `len` bytes at `offset` from a file.
+    pub fn read(&self, file_no: u16, offset: usize, len: usize) -> Result<Vec<u8>, CardError> {
+        let mut out = Vec::with_capacity(len);
+        self.for_each_span(file_no, offset, len, |card, at, n| {
+            out.extend_from_slice(&card.image[at..at + n]);
+        })?;
+        Ok(out)
+    }
+
+    /// Write `data` at `offset` into a file.
+    pub fn write(&mut self, file_no: u16, offset: usize, data: &[u8]) -> Result<(), CardError> {
+        let mut spans = Vec::new();
+        self.for_each_span(file_no, offset, data.len(), |_, at, n| spans.push((at, n)))?;
+        let mut src = 0;
+        for (at, n) in spans {
+            self.image[at..at + n].copy_from_slice(&data[src..src + n]);
+            src += n;
+        }
+        Ok(())
+    }
+
+    /// Import a `.gci` (a 64-byte directory entry followed by the file's
+    /// blocks) as a new file.
+    pub fn import_gci(&mut self, gci: &[u8]) -> Result<u16, CardError> {
+        if gci.len() < DIR_ENTRY_SIZE {
+            return Err(CardError::Broken);
+        }
+        let mut entry = [0u8; DIR_ENTRY_SIZE];
+        entry.copy_from_slice(&gci[..DIR_ENTRY_SIZE]);
+        let data = &gci[DIR_ENTRY_SIZE..];
+        if data.len() != get_u16(&entry, ENT_BLOCK_COUNT) as usize * BLOCK_SIZE {
+            return Err(CardError::Broken);
+        }
+        let game: [u8; 4] = entry[ENT_GAMECODE..ENT_GAMECODE + 4].try_into().unwrap();
+        let maker: [u8; 2] = entry[ENT_MAKER..ENT_MAKER + 2].try_into().unwrap();
+        let name = String::from_utf8_lossy(entry_name(&entry)).into_owned();
+        if self.find(game, maker, &name).is_some() {
+            return Err(CardError::Exist);
+        }
+        let file_no = self.add_entry(entry)?;
+        self.write(file_no, 0, data)?;
+        Ok(file_no)
+    }
+
+    /// A file as a `.gci`.
+    pub fn export_gci(&self, file_no: u16) -> Result<Vec<u8>, CardError> {
+        let mut out = self.used_entry(file_no)?.to_vec();
+        out.extend(self.read(file_no, 0, self.file_len(file_no)?)?);
+        Ok(out)
+    }
+
+    // -- EXI device --------------------------------------------------------
+
+    /// One EXI transfer: `cmd` is the command phase, `data` the data phase
+    /// (filled for reads, consumed for writes).
+    pub fn exi_transfer(&mut self, cmd: &[u8], data: &mut [u8]) {
+        let Some(&op) = cmd.first() else {
+            return;
+        };
+        let addr = exi_address(cmd);
+        match op {
+            // Device ID: card size in Mbit
+            0x00 => {
+                let id = (self.blocks() as u32 / 16).to_be_bytes();
+                for (d, s) in data.iter_mut().zip(id) {
+                    *d = s;
+                }
+            }
+            0x83 => data.fill(self.exi_status),
+            0x89 => self.exi_status &= !0x18,
+            // Read array
+            0x52 => {
+                let start = addr % self.image.len();
+                for (i, d) in data.iter_mut().enumerate() {
+                    *d = self.image[(start + i) % self.image.len()];
+                }
+            }
+            // Erase sector
+            0xF1 => {
+                let start = addr / BLOCK_SIZE * BLOCK_SIZE % self.image.len();
+                self.image[start..start + BLOCK_SIZE].fill(0xFF);
+                self.after_raw_write();
+            }
+            // Write page
+            0xF2 => {
+                let page = addr / PAGE_SIZE * PAGE_SIZE % self.image.len();
+                let n = data.len().min(PAGE_SIZE);
+                self.image[page..page + n].copy_from_slice(&data[..n]);
+                self.after_raw_write();
+            }
+            // Erase card
+            0xF4 => {
+                self.image.fill(0xFF);
+            }
+            other => log::debug!("EXI memory card: unhandled command 0x{:02X}", other),
+        }
+    }
+
+    // -- Internals ---------------------------------------------------------
+
+    fn block(&self, n: usize) -> &[u8] {
+        &self.image[n * BLOCK_SIZE..(n + 1) * BLOCK_SIZE]
+    }
+
+    fn block_mut(&mut self, n: usize) -> &mut [u8] {
+        &mut self.image[n * BLOCK_SIZE..(n + 1) * BLOCK_SIZE]
+    }
+
+    fn entry(&self, file_no: u16) -> &[u8] {
+        let at = file_no as usize * DIR_ENTRY_SIZE;
+        &self.block(self.dir_block)[at..at + DIR_ENTRY_SIZE]
+    }
+
+    fn used_entry(&self, file_no: u16) -> Result<&[u8], CardError> {
+        if file_no as usize >= MAX_FILES || entry_is_free(self.entry(file_no)) {
+            return Err(CardError::NoFile);
+        }
+        Ok(self.entry(file_no))
+    }
+
+    /// Of the two copies starting at `first`, the valid one with the higher
+    /// update counter.
+    fn current_copy(
+        &self,
+        first: usize,
+        update_at: usize,
+        valid: impl Fn(&[u8]) -> bool,
+    ) -> Result<usize, CardError> {
+        let candidates = [first, first + 1]
+            .into_iter()
+            .filter(|&b| valid(self.block(b)));
+        candidates
+            .max_by_key(|&b| get_u16(self.block(b), update_at))
+            .ok_or(CardError::Broken)
+    }
+
+    fn bat_next(&self, block: u16) -> u16 {
+        let at = BAT_MAP + (block - FIRST_DATA_BLOCK) as usize * 2;
+        get_u16(self.block(self.bat_block), at)
+    }
+
+    /// Allocate `count` blocks and a directory slot for `entry`, committing
+    /// new directory and BAT copies.
+    fn add_entry(&mut self, mut entry: [u8; DIR_ENTRY_SIZE]) -> Result<u16, CardError> {
+        let file_no = (0..MAX_FILES as u16)
+            .find(|&n| entry_is_free(self.entry(n)))
+            .ok_or(CardError::NoEnt)?;
+        let count = get_u16(&entry, ENT_BLOCK_COUNT);
+        if count > self.free_blocks() {
+            return Err(CardError::InsSpace);
+        }
+
+        // New BAT: chain `count` free blocks after the last allocation.
+        let mut bat = self.block(self.bat_block).to_vec();
+        let total = self.blocks();
+        let mut last = get_u16(&bat, BAT_LAST_ALLOC);
+        let mut chain = Vec::with_capacity(count as usize);
+        for _ in 0..total {
+            if chain.len() == count as usize {
+                break;
+            }
+            last = if last + 1 >= total {
+                FIRST_DATA_BLOCK
+            } else {
+                last + 1
+            };
+            if get_u16(&bat, BAT_MAP + (last - FIRST_DATA_BLOCK) as usize * 2) == 0 {
+                chain.push(last);
+            }
+        }
+        if chain.len() != count as usize {
+            return Err(CardError::InsSpace);
+        }
+        for (i, &b) in chain.iter().enumerate() {
+            let next = chain.get(i + 1).copied().unwrap_or(BAT_LAST);
+            put_u16(
+                &mut bat,
+                BAT_MAP + (b - FIRST_DATA_BLOCK) as usize * 2,
+                next,
+            );
+        }
+        let free = get_u16(&bat, BAT_FREE) - count;
+        put_u16(&mut bat, BAT_FREE, free);
+        put_u16(&mut bat, BAT_LAST_ALLOC, last);
+        let update = get_u16(&bat, BAT_UPDATE).wrapping_add(1);
+        put_u16(&mut bat, BAT_UPDATE, update);
+        seal_bat(&mut bat);
+
+        // New directory.
+        put_u16(&mut entry, ENT_FIRST_BLOCK, chain[0]);
+        let mut dir = self.block(self.dir_block).to_vec();
+        let at = file_no as usize * DIR_ENTRY_SIZE;
+        dir[at..at + DIR_ENTRY_SIZE].copy_from_slice(&entry);
+        let update = get_u16(&dir, DIR_UPDATE).wrapping_add(1);
+        put_u16(&mut dir, DIR_UPDATE, update);
+        seal_dir(&mut dir);
+
+        // Write to the older copies, which then become current.
+        let (new_bat, new_dir) = (7 - self.bat_block, 3 - self.dir_block);
+        self.block_mut(new_bat).copy_from_slice(&bat);
+        self.block_mut(new_dir).copy_from_slice(&dir);
+        self.bat_block = new_bat;
+        self.dir_block = new_dir;
+        Ok(file_no)
+    }
+
+    /// Visit the image ranges covering `len` bytes at `offset` in a file.
+    fn for_each_span(
+        &self,
+        file_no: u16,
+        offset: usize,
+        len: usize,
+        mut f: impl FnMut(&Self, usize, usize),
+    ) -> Result<(), CardError> {
+        let entry = self.used_entry(file_no)?;
+        if offset + len > self.file_len(file_no)? {
+            return Err(CardError::Limit);
+        }
+        let mut block = get_u16(entry, ENT_FIRST_BLOCK);
+        let mut pos = 0;
+        let end = offset + len;
+        while pos < end {
+            if block < FIRST_DATA_BLOCK || block >= self.blocks() {
+                return Err(CardError::Broken);
+            }
+            let (lo, hi) = (pos.max(offset), (pos + BLOCK_SIZE).min(end));
+            if lo < hi {
+                f(self, block as usize * BLOCK_SIZE + (lo - pos), hi - lo);
+            }
+            pos += BLOCK_SIZE;
+            if pos < end {
+                block = self.bat_next(block);
+            }
+        }
+        Ok(())
+    }
+
+    /// Raw EXI writes may replace the directory or BAT; re-pick the copies.
+    fn after_raw_write(&mut self) {
+        if let Ok(b) = self.current_copy(1, DIR_UPDATE, dir_valid) {
+            self.dir_block = b;
+        }
+        if let Ok(b) = self.current_copy(3, BAT_UPDATE, bat_valid) {
+            self.bat_block = b;
+        }
+    }
+}
+
+/// The two memory card slots and the CARD API state.
+#[derive(Debug, Default)]
+pub struct CardSystem {
+    slots: [Option<MemoryCard>; 2],
+    mounted: [bool; 2],
+    /// Owner of the files this game opens and creates (from the disc header).
+    game_code: [u8; 4],
+    maker_code: [u8; 2],
+}
+
+impl CardSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the game and maker codes files are created under and looked up by.
+    pub fn set_game(&mut self, game_code: [u8; 4], maker_code: [u8; 2]) {
+        self.game_code = game_code;
+        self.maker_code = maker_code;
+    }
+
+    /// Insert a card into slot `chan` (0 = A, 1 = B).
+    pub fn insert(&mut self, chan: usize, card: MemoryCard) {
+        if let Some(slot) = self.slots.get_mut(chan) {
+            *slot = Some(card);
+            self.mounted[chan] = false;
+        }
+    }
+
+    /// Remove the card from slot `chan`.
+    pub fn eject(&mut self, chan: usize) -> Option<MemoryCard> {
+        if let Some(m) = self.mounted.get_mut(chan) {
+            *m = false;
+        }
+        self.slots.get_mut(chan)?.take()
+    }
+
+    pub fn card(&self, chan: usize) -> Option<&MemoryCard> {
+        self.slots.get(chan)?.as_ref()
+    }
+
+    /// `CARDProbe`: is there a card in the slot?
+    pub fn probe(&self, chan: usize) -> bool {
+        self.card(chan).is_some()
+    }
+
+    /// `CARDMount`.
+    pub fn mount(&mut self, chan: usize) -> Result<(), CardError> {
+        if !self.probe(chan) {
+            return Err(CardError::NoCard);
+        }
+        self.mounted[chan] = true;
+        Ok(())
+    }
+
+    /// `CARDUnmount`.
+    pub fn unmount(&mut self, chan: usize) {
+        if let Some(m) = self.mounted.get_mut(chan) {
+            *m = false;
+        }
+    }
+
+    /// `CARDOpen`: the file number of `name`.
+    pub fn open(&self, chan: usize, name: &str) -> Result<u16, CardError> {
+        let card = self.mounted_card(chan)?;
+        card.find(self.game_code, self.maker_code, name)
+            .ok_or(CardError::NoFile)
+    }
+
+    /// `CARDCreate`: a new `size`-byte file, persisted immediately.
+    pub fn create(&mut self, chan: usize, name: &str, size: usize) -> Result<u16, CardError> {
+        let (game, maker) = (self.game_code, self.maker_code);
+        let card = self.mounted_card_mut(chan)?;
+        let file_no = card.create(game, maker, name, size)?;
+        card.flush()?;
+        Ok(file_no)
+    }
+
+    /// `CARDRead`.
+    pub fn read(
+        &self,
+        chan: usize,
+        file_no: u16,
+        offset: usize,
+        len: usize,
+    ) -> Result<Vec<u8>, CardError> {
+        self.mounted_card(chan)?.read(file_no, offset, len)
+    }
+
+    /// `CARDWrite`, persisted immediately.
+    pub fn write(
+        &mut self,
+        chan: usize,
+        file_no: u16,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<(), CardError> {
+        let card = self.mounted_card_mut(chan)?;
+        card.write(file_no, offset, data)?;
+        card.flush()
+    }
+
+    /// One EXI transfer to the card in `chan`. Returns `false` when the slot
+    /// is empty (reads then float high, 0xFF).
+    pub fn exi_transfer(&mut self, chan: usize, cmd: &[u8], data: &mut [u8]) -> bool {
+        match self.slots.get_mut(chan).and_then(|s| s.as_mut()) {
+            Some(card) => {
+                card.exi_transfer(cmd, data);
+                true
+            }
+            None => {
+                data.fill(0xFF);
+                false
+            }
+        }
+    }
+
+    fn mounted_card(&self, chan: usize) -> Result<&MemoryCard, CardError> {
+        match (self.card(chan), self.mounted.get(chan)) {
+            (Some(card), Some(true)) => Ok(card),
+            _ => Err(CardError::NoCard),
+        }
+    }
+
+    fn mounted_card_mut(&mut self, chan: usize) -> Result<&mut MemoryCard, CardError> {
+        if self.mounted.get(chan) != Some(&true) {
+            return Err(CardError::NoCard);
+        }
+        self.slots[chan].as_mut().ok_or(CardError::NoCard)
+    }
+}
+
+/// Read the game and maker codes from the disc header in low memory.
+pub fn disc_codes(memory: &MemoryManager) -> ([u8; 4], [u8; 2]) {
+    let bytes = memory
+        .read_bytes(0x8000_0000, 6)
+        .unwrap_or_else(|_| vec![0; 6]);
+    (
+        [bytes[0], bytes[1], bytes[2], bytes[3]],
+        [bytes[4], bytes[5]],
+    )
+}
+
+/// Byte address from a read/erase/write command
+/// (`op, a[23:17], a[16:9], a[8:7], a[6:0]`).
+fn exi_address(cmd: &[u8]) -> usize {
+    if cmd.len() < 5 {
+        return 0;
+    }
+    ((cmd[1] as usize & 0x7F) << 17)
+        | ((cmd[2] as usize) << 9)
+        | ((cmd[3] as usize & 3) << 7)
+        | (cmd[4] as usize & 0x7F)
+}
+
+fn entry_is_free(entry: &[u8]) -> bool {
+    entry[ENT_GAMECODE..ENT_GAMECODE + 4] == [0xFF; 4]
+}
+
+fn entry_name(entry: &[u8]) -> &[u8] {
+    let name = &entry[ENT_FILENAME..ENT_FILENAME + MAX_FILENAME];
+    let len = name.iter().position(|&b| b == 0).unwrap_or(MAX_FILENAME);
+    &name[..len]
+}
+
+fn get_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([data[at], data[at + 1]])
+}
+
+fn put_u16(data: &mut [u8], at: usize, value: u16) {
+    data[at..at + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+/// The card's additive checksum and inverse checksum over 16-bit words.
+fn checksum(data: &[u8]) -> (u16, u16) {
+    let (mut sum, mut inv) = (0u16, 0u16);
+    for word in data.chunks_exact(2) {
+        let w = u16::from_be_bytes([word[0], word[1]]);
+        sum = sum.wrapping_add(w);
+        inv = inv.wrapping_add(w ^ 0xFFFF);
+    }
+    // 0xFFFF would read as erased flash
+    (
+        if sum == 0xFFFF { 0 } else { sum },
+        if inv == 0xFFFF { 0 } else { inv },
+    )
+}
+
+fn seal_dir(dir: &mut [u8]) {
+    let (sum, inv) = checksum(&dir[..DIR_CHECKSUM]);
+    put_u16(dir, DIR_CHECKSUM, sum);
+    put_u16(dir, DIR_CHECKSUM + 2, inv);
+}
+
+fn seal_bat(bat: &mut [u8]) {
+    let (sum, inv) = checksum(&bat[BAT_UPDATE..]);
+    put_u16(bat, BAT_CHECKSUM, sum);
+    put_u16(bat, BAT_CHECKSUM + 2, inv);
+}
+
+fn dir_valid(dir: &[u8]) -> bool {
+    checksum(&dir[..DIR_CHECKSUM]) == (get_u16(dir, DIR_CHECKSUM), get_u16(dir, DIR_CHECKSUM + 2))
+}
+
+fn bat_valid(bat: &[u8]) -> bool {
+    checksum(&bat[BAT_UPDATE..]) == (get_u16(bat, BAT_CHECKSUM), get_u16(bat, BAT_CHECKSUM + 2))
+}
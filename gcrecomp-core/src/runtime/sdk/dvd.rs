@@ -1,21 +1,46 @@
 //! Virtual DVD filesystem for GameCube disc asset access.
 //!
-//! Parses a GCFS archive (built by `disc_fs::build_archive`) and provides
-//! `DVDOpen` / `DVDRead` / `DVDClose` / `DVDGetLength` emulation so
-//! recompiled games can load assets at runtime.
+//! Parses a GCFS archive (built by `disc_fs::build_archive` from a disc
+//! image, or `build_archive` / `pack_directory` here from extracted files)
+//! and provides `DVDOpen` / `DVDRead` / `DVDClose` / `DVDGetLength`
+//! emulation so recompiled games can load assets at runtime.
 
 use std::collections::HashMap;
+use std::path::Path;
 
+use super::yaz0;
 use crate::runtime::memory::MemoryManager;
 
+/// How an entry's data is stored. Version 1 archives are all zstd; version 2
+/// records the codec per entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Codec {
+    Stored = 0,
+    Zstd = 1,
+    Yaz0 = 2,
+}
+
+impl Codec {
+    fn from_u8(v: u8) -> Option<Self> {
+        match v {
+            0 => Some(Codec::Stored),
+            1 => Some(Codec::Zstd),
+            2 => Some(Codec::Yaz0),
+            _ => None,
+        }
+    }
+}
+
 /// Table-of-contents entry parsed from the GCFS archive.
 struct TocEntry {
     /// Byte offset of compressed data within the archive.
     data_offset: usize,
-    /// Size of the zstd-compressed data.
+    /// Size of the data as stored.
     compressed_size: usize,
     /// Size after decompression.
     decompressed_size: usize,
+    codec: Codec,
 }
 
 /// State for a currently open file handle.
@@ -48,6 +73,10 @@ impl VirtualFilesystem {
     /// [8..12]  file_count u32
     /// [12..20] toc_offset u64
     /// ```
+    ///
+    /// Each TOC entry is `path_len u16 | path | data_offset u64 |
+    /// compressed_size u64 | decompressed_size u64`, followed in version 2 by
+    /// a `Codec` byte.
     pub fn new(archive: &'static [u8]) -> Result<Self, String> {
         if archive.is_empty() {
             return Ok(Self {
@@ -67,7 +96,10 @@ impl VirtualFilesystem {
             return Err("Invalid GCFS magic.".to_string());
         }
 
-        let _version = u32::from_le_bytes([archive[4], archive[5], archive[6], archive[7]]);
+        let version = u32::from_le_bytes([archive[4], archive[5], archive[6], archive[7]]);
+        if !(1..=VERSION).contains(&version) {
+            return Err(format!("Unsupported GCFS version {}.", version));
+        }
         let file_count =
             u32::from_le_bytes([archive[8], archive[9], archive[10], archive[11]]) as usize;
         let toc_offset = u64::from_le_bytes([
@@ -114,6 +146,16 @@ impl VirtualFilesystem {
             pos += 8;
             let decompressed_size = read_u64_le(archive, pos) as usize;
             pos += 8;
+            let codec = if version == 1 {
+                Codec::Zstd
+            } else {
+                let raw = *archive
+                    .get(pos)
+                    .ok_or_else(|| "GCFS TOC truncated (codec).".to_string())?;
+                pos += 1;
+                Codec::from_u8(raw)
+                    .ok_or_else(|| format!("GCFS entry '{}' has unknown codec {}.", path, raw))?
+            };
 
             toc.insert(
                 path,
@@ -121,6 +163,7 @@ impl VirtualFilesystem {
                     data_offset,
                     compressed_size,
                     decompressed_size,
+                    codec,
                 },
             );
         }
@@ -224,8 +267,12 @@ impl VirtualFilesystem {
             }
 
             let compressed = &self.archive[toc_entry.data_offset..compressed_end];
-            let decompressed = zstd::decode_all(compressed)
-                .map_err(|e| format!("DVDRead: zstd decompression failed for '{}': {}", path, e))?;
+            let decompressed = match toc_entry.codec {
+                Codec::Stored => Ok(compressed.to_vec()),
+                Codec::Zstd => decompress(compressed).map_err(|e| format!("zstd: {}", e)),
+                Codec::Yaz0 => yaz0::decompress(compressed),
+            }
+            .map_err(|e| format!("DVDRead: decompression failed for '{}': {}", path, e))?;
 
             log::debug!(
                 "DVDRead: decompressed '{}' ({} -> {} bytes)",
@@ -252,6 +299,79 @@ impl VirtualFilesystem {
     }
 }
 
+/// Archive version written by `build_archive`.
+const VERSION: u32 = 2;
+
+/// Build a version 2 GCFS archive from `(path, data)` pairs, every entry
+/// stored with `codec`. Paths are relative with `/` separators, as
+/// `dvd_open` looks them up.
+pub fn build_archive(files: &[(String, Vec<u8>)], codec: Codec) -> Result<Vec<u8>, String> {
+    let mut archive = Vec::new();
+    archive.extend_from_slice(b"GCFS");
+    archive.extend_from_slice(&VERSION.to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&0u64.to_le_bytes()); // toc_offset, patched below
+
+    let mut toc = Vec::new();
+    for (path, data) in files {
+        let stored = match codec {
+            Codec::Stored => data.clone(),
+            Codec::Zstd => compress(data)
+                .map_err(|e| format!("zstd compression failed for '{}': {}", path, e))?,
+            Codec::Yaz0 => yaz0::compress(data),
+        };
+        let path_len =
+            u16::try_from(path.len()).map_err(|_| format!("GCFS path too long: '{}'", path))?;
+        toc.extend_from_slice(&path_len.to_le_bytes());
+        toc.extend_from_slice(path.as_bytes());
+        toc.extend_from_slice(&(archive.len() as u64).to_le_bytes());
+        toc.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+        toc.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        toc.push(codec as u8);
+        archive.extend_from_slice(&stored);
+    }
+
+    let toc_offset = archive.len() as u64;
+    archive[12..20].copy_from_slice(&toc_offset.to_le_bytes());
+    archive.extend_from_slice(&toc);
+    Ok(archive)
+}
+
+/// Archive every file under `dir`, keeping the directory structure in the
+/// entry paths (sorted, so the same tree always packs to the same bytes).
+/// Returns the archive and the number of files in it.
+pub fn pack_directory(dir: &Path, codec: Codec) -> Result<(Vec<u8>, usize), String> {
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok((build_archive(&files, codec)?, files.len()))
+}
+
+fn collect_files(root: &Path, dir: &Path, out: &mut Vec<(String, Vec<u8>)>) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Reading {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Reading {}: {}", dir.display(), e))?
+            .path();
+        if path.is_dir() {
+            collect_files(root, &path, out)?;
+            continue;
+        }
+        let relative = path
+            .strip_prefix(root)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let data =
+            std::fs::read(&path).map_err(|e| format!("Reading {}: {}", path.display(), e))?;
+        out.push((relative, data));
+    }
+    Ok(())
+}
+
 fn read_u64_le(data: &[u8], offset: usize) -> u64 {
     u64::from_le_bytes([
         data[offset],
@@ -264,3 +384,27 @@ fn read_u64_le(data: &[u8], offset: usize) -> u64 {
         data[offset + 7],
     ])
 }
+
+#[cfg(feature = "native")]
+fn decompress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    zstd::decode_all(data)
+}
+
+#[cfg(feature = "native")]
+fn compress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    zstd::encode_all(data, 3)
+}
+
+/// zstd is native code; builds without it can't read or write zstd entries.
+#[cfg(not(feature = "native"))]
+fn decompress(_data: &[u8]) -> std::io::Result<Vec<u8>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "built without the `native` feature",
+    ))
+}
+
+#[cfg(not(feature = "native"))]
+fn compress(_data: &[u8]) -> std::io::Result<Vec<u8>> {
+    decompress(&[])
+}
@@ -0,0 +1,169 @@
+//! OS exception vectors (`OSSetErrorHandler`).
+//!
+//! Recompiled code never takes a real exception: a load from an unmapped
+//! address comes back as a `MemoryFault` error instead. The table here stands
+//! in for `__OSErrorTable` so that error can still reach the handler the game
+//! registered, with the arguments the SDK passes:
+//! `handler(error, context, dsisr, dar)`.
+
+use crate::runtime::context::CpuContext;
+use crate::runtime::interpreter::CallFn;
+use crate::runtime::memory::{MemoryFault, MemoryManager};
+use anyhow::Result;
+use log::warn;
+
+/// `OSError` numbers, in vector order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OsError {
+    SystemReset = 0,
+    MachineCheck = 1,
+    Dsi = 2,
+    Isi = 3,
+    ExternalInterrupt = 4,
+    Alignment = 5,
+    Program = 6,
+    FloatingPointUnavailable = 7,
+    Decrementer = 8,
+    SystemCall = 9,
+    Trace = 10,
+    PerformanceMonitor = 11,
+    Breakpoint = 12,
+    SystemInterrupt = 13,
+    ThermalInterrupt = 14,
+    /// Memory protection (`OSProtectRange`), raised by the MI.
+    Protection = 15,
+    /// Floating-point exception reported through the program vector.
+    FloatingPoint = 16,
+}
+
+/// Number of `OSError` slots in the table.
+pub const OS_ERROR_MAX: usize = 17;
+
+/// DSISR bit 6: the faulting access was a store.
+pub const DSISR_STORE: u32 = 1 << 25;
+/// DSISR bit 1: no translation for the effective address.
+pub const DSISR_NO_TRANSLATION: u32 = 1 << 30;
+
+/// Low-memory pointer to the current `OSContext` (`OS_CURRENT_CONTEXT`).
+const OS_CURRENT_CONTEXT: u32 = 0x8000_00D4;
+
+impl OsError {
+    pub fn from_u32(value: u32) -> Option<Self> {
+        use OsError::*;
+        Some(match value {
+            0 => SystemReset,
+            1 => MachineCheck,
+            2 => Dsi,
+            3 => Isi,
+            4 => ExternalInterrupt,
+            5 => Alignment,
+            6 => Program,
+            7 => FloatingPointUnavailable,
+            8 => Decrementer,
+            9 => SystemCall,
+            10 => Trace,
+            11 => PerformanceMonitor,
+            12 => Breakpoint,
+            13 => SystemInterrupt,
+            14 => ThermalInterrupt,
+            15 => Protection,
+            16 => FloatingPoint,
+            _ => return None,
+        })
+    }
+}
+
+/// The state a fault was raised with, as the SPRs would have held it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub error: OsError,
+    /// SRR0: the PC of the faulting instruction.
+    pub srr0: u32,
+    pub dar: u32,
+    pub dsisr: u32,
+}
+
+/// Handlers registered per `OSError` (GC function addresses).
+#[derive(Debug, Default)]
+pub struct ExceptionTable {
+    handlers: [Option<u32>; OS_ERROR_MAX],
+    last_fault: Option<Fault>,
+}
+
+impl ExceptionTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Install `handler` for `error` (0 removes it); returns the previous one,
+    /// as `OSSetErrorHandler` does.
+    pub fn set_handler(&mut self, error: OsError, handler: u32) -> Option<u32> {
+        let slot = &mut self.handlers[error as usize];
+        std::mem::replace(slot, (handler != 0).then_some(handler))
+    }
+
+    pub fn handler(&self, error: OsError) -> Option<u32> {
+        self.handlers[error as usize]
+    }
+
+    /// The most recent fault raised, handled or not.
+    pub fn last_fault(&self) -> Option<Fault> {
+        self.last_fault
+    }
+
+    /// Raise `error` at the current PC. With a handler registered, its
+    /// arguments are loaded into r3-r6 and `call` runs it; returns whether a
+    /// handler ran.
+    pub fn raise(
+        &mut self,
+        error: OsError,
+        dar: u32,
+        dsisr: u32,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+        call: CallFn,
+    ) -> Result<bool> {
+        let fault = Fault {
+            error,
+            srr0: ctx.pc,
+            dar,
+            dsisr,
+        };
+        self.last_fault = Some(fault);
+        let Some(handler) = self.handler(error) else {
+            warn!(
+                "Unhandled {:?} at 0x{:08X} (DAR 0x{:08X}, DSISR 0x{:08X})",
+                error, fault.srr0, dar, dsisr
+            );
+            return Ok(false);
+        };
+
+        let context = memory.read_u32(OS_CURRENT_CONTEXT).unwrap_or(0);
+        ctx.set_register(3, error as u32);
+        ctx.set_register(4, context);
+        ctx.set_register(5, dsisr);
+        ctx.set_register(6, dar);
+        call(handler, ctx, memory)?;
+        Ok(true)
+    }
+
+    /// Route a failed memory access to the DSI handler. Errors that aren't a
+    /// `MemoryFault` return `Ok(false)` untouched.
+    pub fn dispatch_memory_fault(
+        &mut self,
+        err: &anyhow::Error,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+        call: CallFn,
+    ) -> Result<bool> {
+        let Some(fault) = err.downcast_ref::<MemoryFault>().copied() else {
+            return Ok(false);
+        };
+        let mut dsisr = DSISR_NO_TRANSLATION;
+        if fault.write {
+            dsisr |= DSISR_STORE;
+        }
+        self.raise(OsError::Dsi, fault.address, dsisr, ctx, memory, call)
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 /// Arena allocator matching the GameCube OS memory model.
 ///
 /// The GameCube arena sits between the end of the loaded DOL and the top of MEM1.
@@ -101,6 +103,238 @@ impl Default for ArenaAllocator {
     }
 }
 
+/// Alignment and granularity of heap blocks (the SDK's 32-byte cells).
+const HEAP_ALIGN: u32 = 32;
+
+/// One heap from `OSCreateHeap`: first-fit over a sorted free list. Block
+/// bookkeeping lives on the host, not in emulated memory.
+struct Heap {
+    /// `(address, size)` of free ranges, sorted by address, coalesced.
+    free: Vec<(u32, u32)>,
+    /// Allocated block address -> rounded size.
+    used: BTreeMap<u32, u32>,
+}
+
+impl Heap {
+    fn new(start: u32, end: u32) -> Self {
+        let start = start.next_multiple_of(HEAP_ALIGN);
+        let end = end & !(HEAP_ALIGN - 1);
+        Self {
+            free: (end > start)
+                .then_some((start, end - start))
+                .into_iter()
+                .collect(),
+            used: BTreeMap::new(),
+        }
+    }
+
+    fn alloc(&mut self, size: u32) -> Option<u32> {
+        let size = size.max(1).checked_next_multiple_of(HEAP_ALIGN)?;
+        let i = self.free.iter().position(|&(_, len)| len >= size)?;
+        let (addr, len) = self.free[i];
+        if len == size {
+            self.free.remove(i);
+        } else {
+            self.free[i] = (addr + size, len - size);
+        }
+        self.used.insert(addr, size);
+        Some(addr)
+    }
+
+    fn free(&mut self, addr: u32) -> bool {
+        let Some(size) = self.used.remove(&addr) else {
+            return false;
+        };
+        let i = self.free.partition_point(|&(a, _)| a < addr);
+        self.free.insert(i, (addr, size));
+        // Merge with the following range, then the preceding one.
+        if i + 1 < self.free.len() && addr + size == self.free[i + 1].0 {
+            self.free[i].1 += self.free.remove(i + 1).1;
+        }
+        if i > 0 && self.free[i - 1].0 + self.free[i - 1].1 == addr {
+            self.free[i - 1].1 += self.free.remove(i).1;
+        }
+        true
+    }
+}
+
+/// A live allocation seen by the tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Allocation {
+    pub heap: i32,
+    pub address: u32,
+    /// Bytes the game asked for (before rounding).
+    pub size: u32,
+    /// Call site of the `OSAllocFromHeap`.
+    pub pc: u32,
+}
+
+/// Allocations never freed, from [`HeapManager::heap_report`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeapReport {
+    /// Outstanding allocations by address.
+    pub leaks: Vec<Allocation>,
+}
+
+impl HeapReport {
+    pub fn leaked_bytes(&self) -> u64 {
+        self.leaks.iter().map(|a| u64::from(a.size)).sum()
+    }
+}
+
+impl std::fmt::Display for HeapReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} outstanding allocation(s), {} bytes",
+            self.leaks.len(),
+            self.leaked_bytes()
+        )?;
+        for a in &self.leaks {
+            writeln!(
+                f,
+                "  heap {} 0x{:08X} {:>8} bytes  allocated at 0x{:08X}",
+                a.heap, a.address, a.size, a.pc
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The SDK heaps (`OSInitAlloc`, `OSCreateHeap`, `OSAllocFromHeap`, ...),
+/// with optional allocation tracking for leak hunting.
+pub struct HeapManager {
+    heaps: Vec<Option<Heap>>,
+    current: i32,
+    /// Outstanding allocations by address; `None` while tracking is off, so
+    /// the alloc/free path only pays for a branch.
+    tracker: Option<BTreeMap<u32, Allocation>>,
+}
+
+impl HeapManager {
+    pub fn new() -> Self {
+        Self {
+            heaps: Vec::new(),
+            current: -1,
+            tracker: None,
+        }
+    }
+
+    /// `OSInitAlloc`: drop every heap and make room for `max_heaps`.
+    pub fn init(&mut self, max_heaps: u32) {
+        self.heaps = Vec::with_capacity(max_heaps as usize);
+        self.current = -1;
+        if let Some(tracker) = self.tracker.as_mut() {
+            tracker.clear();
+        }
+    }
+
+    /// `OSCreateHeap`: a heap over `start..end`; returns its handle.
+    pub fn create(&mut self, start: u32, end: u32) -> i32 {
+        let heap = Some(Heap::new(start, end));
+        match self.heaps.iter().position(Option::is_none) {
+            Some(slot) => {
+                self.heaps[slot] = heap;
+                slot as i32
+            }
+            None => {
+                self.heaps.push(heap);
+                self.heaps.len() as i32 - 1
+            }
+        }
+    }
+
+    /// `OSDestroyHeap`. Its blocks stop counting as leaks.
+    pub fn destroy(&mut self, heap: i32) {
+        if let Some(slot) = self.slot(heap) {
+            *slot = None;
+        }
+        if let Some(tracker) = self.tracker.as_mut() {
+            tracker.retain(|_, a| a.heap != heap);
+        }
+    }
+
+    /// `OSSetCurrentHeap`; returns the previous current heap.
+    pub fn set_current(&mut self, heap: i32) -> i32 {
+        std::mem::replace(&mut self.current, heap)
+    }
+
+    pub fn current(&self) -> i32 {
+        self.current
+    }
+
+    /// `OSAllocFromHeap` called from `pc`; 0 when the heap is full or invalid.
+    pub fn alloc(&mut self, heap: i32, size: u32, pc: u32) -> u32 {
+        let Some(addr) = self.slot(heap).and_then(|h| h.as_mut()?.alloc(size)) else {
+            log::warn!("OSAllocFromHeap({heap}, {size}) failed (called from 0x{pc:08X})");
+            return 0;
+        };
+        if let Some(tracker) = self.tracker.as_mut() {
+            tracker.insert(
+                addr,
+                Allocation {
+                    heap,
+                    address: addr,
+                    size,
+                    pc,
+                },
+            );
+        }
+        addr
+    }
+
+    /// `OSFreeToHeap`; false if `addr` isn't a live block of `heap`.
+    pub fn free(&mut self, heap: i32, addr: u32) -> bool {
+        let freed = self
+            .slot(heap)
+            .and_then(|h| h.as_mut())
+            .is_some_and(|h| h.free(addr));
+        if !freed {
+            log::warn!("OSFreeToHeap({heap}, 0x{addr:08X}): not an allocated block");
+            return false;
+        }
+        if let Some(tracker) = self.tracker.as_mut() {
+            tracker.remove(&addr);
+        }
+        true
+    }
+
+    /// Start or stop recording allocations. Blocks allocated while tracking
+    /// was off are never reported.
+    pub fn set_tracking(&mut self, enabled: bool) {
+        if enabled != self.tracker.is_some() {
+            self.tracker = enabled.then(BTreeMap::new);
+        }
+    }
+
+    pub fn tracking(&self) -> bool {
+        self.tracker.is_some()
+    }
+
+    /// Tracked allocations not yet freed.
+    pub fn heap_report(&self) -> HeapReport {
+        HeapReport {
+            leaks: self
+                .tracker
+                .iter()
+                .flat_map(|t| t.values().copied())
+                .collect(),
+        }
+    }
+
+    fn slot(&mut self, heap: i32) -> Option<&mut Option<Heap>> {
+        usize::try_from(heap)
+            .ok()
+            .and_then(|i| self.heaps.get_mut(i))
+    }
+}
+
+impl Default for HeapManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,4 +362,41 @@ mod tests {
         let addr = arena.alloc_lo(512, 32);
         assert_eq!(addr, 0); // Should fail
     }
+
+    #[test]
+    fn test_heap_report_lists_unfreed_blocks() {
+        let mut heaps = HeapManager::new();
+        heaps.init(4);
+        let heap = heaps.create(0x8050_0000, 0x8060_0000);
+        heaps.set_tracking(true);
+
+        let a = heaps.alloc(heap, 100, 0x8000_1000);
+        let b = heaps.alloc(heap, 64, 0x8000_2000);
+        let c = heaps.alloc(heap, 7, 0x8000_3000);
+        assert!(a != 0 && b != 0 && c != 0);
+        assert!(heaps.free(heap, b));
+        assert!(!heaps.free(heap, b)); // double free
+
+        let report = heaps.heap_report();
+        let leaks: Vec<_> = report
+            .leaks
+            .iter()
+            .map(|l| (l.address, l.size, l.pc))
+            .collect();
+        assert_eq!(leaks, [(a, 100, 0x8000_1000), (c, 7, 0x8000_3000)]);
+        assert_eq!(report.leaked_bytes(), 107);
+    }
+
+    #[test]
+    fn test_heap_reuses_and_coalesces_freed_blocks() {
+        let mut heaps = HeapManager::new();
+        let heap = heaps.create(0x8050_0000, 0x8050_0100); // 256 bytes
+        let a = heaps.alloc(heap, 128, 0);
+        let b = heaps.alloc(heap, 128, 0);
+        assert_eq!(heaps.alloc(heap, 1, 0), 0); // full
+        heaps.free(heap, a);
+        heaps.free(heap, b);
+        assert_eq!(heaps.alloc(heap, 256, 0), a);
+        assert!(heaps.heap_report().leaks.is_empty()); // tracking off
+    }
 }
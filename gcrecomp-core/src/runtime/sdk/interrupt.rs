@@ -1,8 +1,25 @@
+use crate::runtime::context::CpuContext;
+use crate::runtime::interpreter::CallFn;
+use crate::runtime::memory::MemoryManager;
+use anyhow::Result;
+
+/// `__OSInterrupt` numbers for the sources the runtime raises.
+/// AI DMA buffer drained (reported through the DSP interface).
+pub const OS_INTERRUPT_DSP_AI: u8 = 5;
+/// AI streaming sample counter reached its trigger.
+pub const OS_INTERRUPT_AI_AI: u8 = 8;
+/// VI retrace.
+pub const OS_INTERRUPT_PI_VI: u8 = 24;
+
+/// Low-memory pointer to the current `OSContext` (`OS_CURRENT_CONTEXT`).
+const OS_CURRENT_CONTEXT: u32 = 0x8000_00D4;
+
 /// GameCube interrupt system emulation.
 ///
 /// The GameCube has 32 interrupt sources managed through a mask register.
 /// In a static recompiler context, most interrupts are simulated (VI retrace,
 /// AI DMA complete, etc.) rather than triggered by real hardware.
+#[derive(serde::Serialize, serde::Deserialize)]
 pub struct InterruptSystem {
     master_enable: bool,
     mask: u32,
@@ -79,6 +96,40 @@ impl InterruptSystem {
         }
     }
 
+    /// Whether `irq` has been raised and not yet acknowledged.
+    pub fn is_pending(&self, irq: u8) -> bool {
+        (irq as usize) < 32 && self.pending & (1 << irq) != 0
+    }
+
+    /// Run the handler of every pending, unmasked interrupt, lowest number
+    /// first, acknowledging each. Handlers get `(interrupt, context)` in
+    /// r3/r4, as `__OSInterruptHandler`s do, and run through `call`. Nothing
+    /// runs while interrupts are disabled. Returns how many handlers ran.
+    pub fn dispatch(
+        &mut self,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+        call: CallFn,
+    ) -> Result<usize> {
+        if !self.master_enable {
+            return Ok(0);
+        }
+        let mut ran = 0;
+        while self.get_pending_masked() != 0 {
+            let irq = self.get_pending_masked().trailing_zeros() as u8;
+            self.acknowledge(irq);
+            let Some(handler) = self.handlers[irq as usize] else {
+                continue;
+            };
+            let context = memory.read_u32(OS_CURRENT_CONTEXT).unwrap_or(0);
+            ctx.set_register(3, irq as u32);
+            ctx.set_register(4, context);
+            call(handler, ctx, memory)?;
+            ran += 1;
+        }
+        Ok(ran)
+    }
+
     /// Get pending interrupts masked by the enable mask.
     pub fn get_pending_masked(&self) -> u32 {
         self.pending & self.mask
@@ -1,11 +1,21 @@
+pub mod card;
 pub mod dvd;
+pub mod exception;
 pub mod heap;
 pub mod interrupt;
 pub mod os;
+pub mod printf;
+pub mod region;
+pub mod sram;
 pub mod timer;
+pub mod yaz0;
 
+pub use card::{CardError, CardSystem, MemoryCard};
 pub use dvd::VirtualFilesystem;
-pub use heap::ArenaAllocator;
+pub use exception::{ExceptionTable, OsError};
+pub use heap::{ArenaAllocator, HeapManager, HeapReport};
 pub use interrupt::InterruptSystem;
 pub use os::*;
-pub use timer::OsTimer;
+pub use region::{Language, Region};
+pub use sram::Sram;
+pub use timer::{OsTimer, TimeSource};
@@ -1,31 +1,101 @@
 use log::{info, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
 
+use super::card::{self, CardError, CardSystem};
 use super::dvd::VirtualFilesystem;
-use super::heap::ArenaAllocator;
+use super::exception::{ExceptionTable, OsError};
+use super::heap::{ArenaAllocator, HeapManager};
 use super::interrupt::InterruptSystem;
+use super::printf::{self, VarArgs};
+use super::region::{self, Language, Region};
+use super::sram::Sram;
 use super::timer::OsTimer;
 use crate::runtime::context::CpuContext;
 use crate::runtime::memory::MemoryManager;
+use crate::runtime::rng::SeededRng;
+use crate::runtime::{request_shutdown, ShutdownRequest};
 
 /// Full OS state for the recompiled GameCube runtime.
 pub struct OsState {
     pub arena: ArenaAllocator,
+    /// Heaps carved out of the arena by `OSCreateHeap`.
+    pub heaps: HeapManager,
     pub timer: OsTimer,
     pub interrupts: InterruptSystem,
     pub console_type: u32,
+    /// Console region; see `sdk::region`.
+    pub region: Region,
+    /// Console settings (language, sound, video flags).
+    pub sram: Sram,
     pub initialized: bool,
     pub dvd: Option<VirtualFilesystem>,
+    /// Memory card slots A and B.
+    pub card: CardSystem,
+    /// Handlers from `OSSetErrorHandler`.
+    pub exceptions: ExceptionTable,
+    /// Where `OSReport` lines go besides the log, e.g. an in-game console.
+    pub console: Option<Arc<ReportConsole>>,
+    /// `OSReport` output since the last newline.
+    pending_report: String,
 }
 
 impl OsState {
     pub fn new() -> Self {
         Self {
             arena: ArenaAllocator::new(),
+            heaps: HeapManager::new(),
             timer: OsTimer::new(),
             interrupts: InterruptSystem::new(),
             console_type: 0x10000006, // Retail GameCube (HW2)
+            region: Region::default(),
+            sram: Sram::default(),
             initialized: false,
             dvd: None,
+            card: CardSystem::new(),
+            exceptions: ExceptionTable::new(),
+            console: None,
+            pending_report: String::new(),
+        }
+    }
+
+    /// Print `text` from `OSReport`/`printf`. Games build lines from several
+    /// calls, so only complete lines are logged.
+    pub fn report(&mut self, text: &str) {
+        self.pending_report.push_str(text);
+        while let Some(end) = self.pending_report.find('\n') {
+            let line: String = self.pending_report.drain(..=end).collect();
+            let line = line.trim_end_matches(['\r', '\n']);
+            os_report(line);
+            if let Some(console) = &self.console {
+                console.push(line.to_string());
+            }
+        }
+    }
+
+    /// OS state for a reproducible run: the timebase runs on a virtual clock
+    /// whose boot value comes from `rng`.
+    pub fn seeded(rng: &mut SeededRng) -> Self {
+        Self {
+            timer: OsTimer::virtual_clock(u64::from(rng.next_u32())),
+            ..Self::new()
+        }
+    }
+
+    /// Switch to `region`: the TV format the OS reads from low memory and
+    /// the video mode in SRAM. The rest of SRAM is the user's.
+    pub fn set_region(&mut self, region: Region, memory: &mut MemoryManager) {
+        info!("Region: {region} ({} Hz)", region.field_rate());
+        self.region = region;
+        self.sram.set_video_mode(region.tv_mode());
+        let _ = memory.write_u32(region::OS_TV_MODE, region.tv_mode());
+    }
+
+    /// Change a setting in SRAM and write it back to its host file.
+    pub fn update_sram(&mut self, change: impl FnOnce(&mut Sram)) {
+        change(&mut self.sram);
+        if let Err(e) = self.sram.flush() {
+            warn!("{:#}", e);
         }
     }
 
@@ -69,8 +139,10 @@ pub fn os_init(os: &mut OsState, memory: &mut MemoryManager) {
     let _ = memory.write_u32(0x800000FC, 486_000_000);
     // 0x80000028: Memory size (24 MB)
     let _ = memory.write_u32(0x80000028, 24 * 1024 * 1024);
-    // 0x800000CC: Console type
-    let _ = memory.write_u32(0x800000CC, os.console_type);
+    // 0x8000002C: Console type
+    let _ = memory.write_u32(0x8000002C, os.console_type);
+    // 0x800000CC: TV format
+    let _ = memory.write_u32(region::OS_TV_MODE, os.region.tv_mode());
 
     info!("OSInit complete: arena ready, timer started");
 }
@@ -80,12 +152,78 @@ pub fn os_report(message: &str) {
     info!("OSReport: {}", message);
 }
 
+/// The most recent `OSReport` lines, shared with whatever displays them.
+#[derive(Debug)]
+pub struct ReportConsole {
+    lines: Mutex<VecDeque<String>>,
+    capacity: usize,
+}
+
+impl ReportConsole {
+    /// Keep at most `capacity` lines, dropping the oldest.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// Oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        let lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        lines.iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
 /// OSFatal - Fatal error handler.
 pub fn os_fatal(message: &str) {
     warn!("OSFatal: {}", message);
     std::process::exit(1);
 }
 
+/// `OSResetSystem` reset kinds.
+pub const OS_RESET_RESTART: u32 = 0;
+pub const OS_RESET_HOTRESET: u32 = 1;
+pub const OS_RESET_SHUTDOWN: u32 = 2;
+
+/// OSResetSystem - Reset, power off, or return to the menu.
+/// Quiesces the OS (interrupts off, memory cards flushed and unmounted) and
+/// asks the host to stop; recompiled code unwinds at its next budget check.
+pub fn os_reset_system(os: &mut OsState, reset: u32, reset_code: u32, force_menu: bool) {
+    let request = if force_menu {
+        ShutdownRequest::ReturnToMenu
+    } else if reset == OS_RESET_SHUTDOWN {
+        ShutdownRequest::PowerOff
+    } else {
+        ShutdownRequest::Restart {
+            code: reset_code,
+            hot: reset == OS_RESET_HOTRESET,
+        }
+    };
+    info!("OSResetSystem: {:?}", request);
+
+    os.interrupts.disable_all();
+    for chan in 0..2 {
+        if let Some(Err(e)) = os.card.card(chan).map(|c| c.flush()) {
+            warn!("Memory card {} flush on reset failed: {}", chan, e);
+        }
+        os.card.unmount(chan);
+    }
+    request_shutdown(request);
+}
+
 /// OSGetConsoleType - Returns console hardware revision.
 pub fn os_get_console_type(os: &OsState) -> u32 {
     os.console_type
@@ -145,10 +283,17 @@ pub fn dispatch_sdk_call(
             os_init(os, memory);
             true
         }
-        "OSReport" => {
-            let addr = ctx.get_register(3);
-            let msg = read_c_string(memory, addr);
-            os_report(&msg);
+        "OSReport" | "printf" => {
+            let fmt = read_c_string(memory, ctx.get_register(3));
+            let text = printf::format(&fmt, &mut VarArgs::from_registers(ctx, memory, 4));
+            os.report(&text);
+            true
+        }
+        "OSVReport" | "vprintf" => {
+            let fmt = read_c_string(memory, ctx.get_register(3));
+            let mut args = VarArgs::from_va_list(memory, ctx.get_register(4));
+            let text = printf::format(&fmt, &mut args);
+            os.report(&text);
             true
         }
         "OSFatal" => {
@@ -157,11 +302,71 @@ pub fn dispatch_sdk_call(
             os_fatal(&msg);
             true
         }
+        "OSResetSystem" => {
+            let reset = ctx.get_register(3);
+            let code = ctx.get_register(4);
+            let force_menu = ctx.get_register(5) != 0;
+            os_reset_system(os, reset, code, force_menu);
+            true
+        }
+        "OSReturnToMenu" => {
+            os_reset_system(os, OS_RESET_RESTART, 0, true);
+            true
+        }
+        "OSSetErrorHandler" => {
+            let old = match OsError::from_u32(ctx.get_register(3)) {
+                Some(error) => os.exceptions.set_handler(error, ctx.get_register(4)),
+                None => None,
+            };
+            ctx.set_register(3, old.unwrap_or(0));
+            true
+        }
         "OSGetConsoleType" => {
             let val = os_get_console_type(os);
             ctx.set_register(3, val);
             true
         }
+        "OSGetFontEncode" => {
+            ctx.set_register(3, os.region.font_encode());
+            true
+        }
+        "OSGetLanguage" => {
+            ctx.set_register(3, os.sram.language as u32);
+            true
+        }
+        "OSSetLanguage" => {
+            if let Some(language) = Language::from_u8(ctx.get_register(3) as u8) {
+                os.update_sram(|sram| sram.language = language);
+            }
+            true
+        }
+        "OSGetSoundMode" => {
+            ctx.set_register(3, os.sram.sound_mode());
+            true
+        }
+        "OSSetSoundMode" => {
+            let stereo = ctx.get_register(3) != 0;
+            os.update_sram(|sram| sram.set_stereo(stereo));
+            true
+        }
+        "OSGetProgressiveMode" => {
+            ctx.set_register(3, os.sram.progressive() as u32);
+            true
+        }
+        "OSSetProgressiveMode" => {
+            let on = ctx.get_register(3) != 0;
+            os.update_sram(|sram| sram.set_progressive(on));
+            true
+        }
+        "OSGetEuRgb60Mode" => {
+            ctx.set_register(3, os.sram.eurgb60() as u32);
+            true
+        }
+        "OSSetEuRgb60Mode" => {
+            let on = ctx.get_register(3) != 0;
+            os.update_sram(|sram| sram.set_eurgb60(on));
+            true
+        }
         "OSDisableInterrupts" => {
             let prev = os_disable_interrupts(os);
             ctx.set_register(3, prev);
@@ -186,6 +391,43 @@ pub fn dispatch_sdk_call(
             ctx.set_register(3, addr);
             true
         }
+        "OSInitAlloc" => {
+            // Returns the arena start past the heap descriptor table (12 bytes
+            // per heap), as the SDK does.
+            let start = ctx.get_register(3);
+            let max_heaps = ctx.get_register(5);
+            os.heaps.init(max_heaps);
+            let table_end = start.wrapping_add(max_heaps.wrapping_mul(12));
+            ctx.set_register(3, table_end.wrapping_add(31) & !31);
+            true
+        }
+        "OSCreateHeap" => {
+            let heap = os.heaps.create(ctx.get_register(3), ctx.get_register(4));
+            ctx.set_register(3, heap as u32);
+            true
+        }
+        "OSDestroyHeap" => {
+            os.heaps.destroy(ctx.get_register(3) as i32);
+            true
+        }
+        "OSSetCurrentHeap" => {
+            let prev = os.heaps.set_current(ctx.get_register(3) as i32);
+            ctx.set_register(3, prev as u32);
+            true
+        }
+        "OSAllocFromHeap" => {
+            let heap = ctx.get_register(3) as i32;
+            let size = ctx.get_register(4);
+            // LR points past the `bl`; report the call itself.
+            let addr = os.heaps.alloc(heap, size, ctx.lr.wrapping_sub(4));
+            ctx.set_register(3, addr);
+            true
+        }
+        "OSFreeToHeap" => {
+            os.heaps
+                .free(ctx.get_register(3) as i32, ctx.get_register(4));
+            true
+        }
         "OSGetArenaLo" => {
             ctx.set_register(3, os_get_arena_lo(os));
             true
@@ -284,10 +526,143 @@ pub fn dispatch_sdk_call(
             true
         }
 
+        // CARD API. Results are CARD_RESULT_* codes (0 = ready).
+        "CARDInit" => {
+            let (game, maker) = card::disc_codes(memory);
+            os.card.set_game(game, maker);
+            true
+        }
+        "CARDProbe" => {
+            let chan = ctx.get_register(3) as usize;
+            ctx.set_register(3, os.card.probe(chan) as u32);
+            true
+        }
+        "CARDProbeEx" => {
+            // r3 = chan, r4 = s32* memSize (Mbit), r5 = s32* sectorSize
+            let chan = ctx.get_register(3) as usize;
+            let result = match os.card.card(chan) {
+                Some(c) => {
+                    let mem_size = ctx.get_register(4);
+                    let sector_size = ctx.get_register(5);
+                    if mem_size != 0 {
+                        let _ = memory.write_u32(mem_size, c.blocks() as u32 / 16);
+                    }
+                    if sector_size != 0 {
+                        let _ = memory.write_u32(sector_size, card::BLOCK_SIZE as u32);
+                    }
+                    Ok(())
+                }
+                None => Err(CardError::NoCard),
+            };
+            ctx.set_register(3, card_result(result));
+            true
+        }
+        "CARDMount" => {
+            // r3 = chan, r4 = work area, r5 = detach callback
+            let chan = ctx.get_register(3) as usize;
+            let (game, maker) = card::disc_codes(memory);
+            os.card.set_game(game, maker);
+            let result = os.card.mount(chan);
+            ctx.set_register(3, card_result(result));
+            true
+        }
+        "CARDUnmount" => {
+            let chan = ctx.get_register(3) as usize;
+            os.card.unmount(chan);
+            ctx.set_register(3, 0);
+            true
+        }
+        "CARDOpen" => {
+            // r3 = chan, r4 = file name, r5 = CARDFileInfo*
+            let chan = ctx.get_register(3);
+            let name = read_c_string(memory, ctx.get_register(4));
+            let info = ctx.get_register(5);
+            let result = os.card.open(chan as usize, &name).map(|file_no| {
+                write_card_file_info(memory, info, chan, file_no);
+            });
+            ctx.set_register(3, card_result(result));
+            true
+        }
+        "CARDCreate" => {
+            // r3 = chan, r4 = file name, r5 = size, r6 = CARDFileInfo*
+            let chan = ctx.get_register(3);
+            let name = read_c_string(memory, ctx.get_register(4));
+            let size = ctx.get_register(5) as usize;
+            let info = ctx.get_register(6);
+            let result = os.card.create(chan as usize, &name, size).map(|file_no| {
+                write_card_file_info(memory, info, chan, file_no);
+            });
+            ctx.set_register(3, card_result(result));
+            true
+        }
+        "CARDClose" => {
+            let info = ctx.get_register(3);
+            let _ = memory.write_u32(info, u32::MAX); // chan = -1
+            ctx.set_register(3, 0);
+            true
+        }
+        "CARDRead" => {
+            // r3 = CARDFileInfo*, r4 = buffer, r5 = length, r6 = offset
+            let info = ctx.get_register(3);
+            let (buf, len, offset) = (
+                ctx.get_register(4),
+                ctx.get_register(5),
+                ctx.get_register(6),
+            );
+            let chan = memory.read_u32(info).unwrap_or(u32::MAX) as usize;
+            let file_no = memory.read_u32(info + 4).unwrap_or(u32::MAX) as u16;
+            let result = os
+                .card
+                .read(chan, file_no, offset as usize, len as usize)
+                .and_then(|data| {
+                    memory
+                        .write_bytes(buf, &data)
+                        .map_err(|_| CardError::IoError)
+                });
+            ctx.set_register(3, card_result(result));
+            true
+        }
+        "CARDWrite" => {
+            // r3 = CARDFileInfo*, r4 = buffer, r5 = length, r6 = offset
+            let info = ctx.get_register(3);
+            let (buf, len, offset) = (
+                ctx.get_register(4),
+                ctx.get_register(5),
+                ctx.get_register(6),
+            );
+            let chan = memory.read_u32(info).unwrap_or(u32::MAX) as usize;
+            let file_no = memory.read_u32(info + 4).unwrap_or(u32::MAX) as u16;
+            let result = memory
+                .read_bytes(buf, len as usize)
+                .map_err(|_| CardError::IoError)
+                .and_then(|data| os.card.write(chan, file_no, offset as usize, &data));
+            ctx.set_register(3, card_result(result));
+            true
+        }
+
         _ => false,
     }
 }
 
+/// A CARD call's return value in r3: 0 or a negative `CARD_RESULT_*`.
+fn card_result(result: Result<(), CardError>) -> u32 {
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            warn!("CARD: {}", e);
+            e.code() as u32
+        }
+    }
+}
+
+/// Fill a `CARDFileInfo` (chan @0, fileNo @4, offset @8, length @0xC).
+fn write_card_file_info(memory: &mut MemoryManager, info: u32, chan: u32, file_no: u16) {
+    let _ = memory.write_u32(info, chan);
+    let _ = memory.write_u32(info + 4, file_no as u32);
+    let _ = memory.write_u32(info + 8, 0);
+    let _ = memory.write_u32(info + 0xC, 0);
+}
+
 /// Read a null-terminated C string from memory at the given GC address.
 pub fn read_c_string(memory: &MemoryManager, addr: u32) -> String {
     let mut result = Vec::new();
@@ -0,0 +1,477 @@
+// C `printf` formatting for `OSReport` and friends, with arguments fetched
+// the way the PowerPC EABI passes varargs.
+//
+// Integer and pointer arguments come from r3-r10, doubles from f1-f8; once
+// those run out the rest are in the caller's parameter area at `r1 + 8`. A
+// 64-bit integer takes an odd/even register pair (r3:r4, r5:r6, ...) or an
+// 8-byte aligned stack slot. `v*printf` variants get the same view through a
+// `va_list` in memory instead.
+use crate::runtime::context::CpuContext;
+use crate::runtime::memory::MemoryManager;
+use crate::runtime::sdk::os::read_c_string;
+
+/// Argument registers of each kind.
+const ARG_REGS: u8 = 8;
+
+/// The arguments after the format string.
+pub struct VarArgs<'a> {
+    memory: &'a MemoryManager,
+    source: Source<'a>,
+    /// Argument GPRs (from r3) and FPRs (from f1) already used.
+    gpr: u8,
+    fpr: u8,
+    /// Next stack argument.
+    overflow: u32,
+}
+
+enum Source<'a> {
+    Registers(&'a CpuContext),
+    /// A `va_list`'s register save area: r3-r10, then f1-f8.
+    SaveArea(u32),
+}
+
+impl<'a> VarArgs<'a> {
+    /// Arguments of a variadic call in progress, the first one in `first_gpr`
+    /// (`OSReport(fmt, ...)` has its format in r3, so 4).
+    pub fn from_registers(ctx: &'a CpuContext, memory: &'a MemoryManager, first_gpr: u8) -> Self {
+        Self {
+            memory,
+            source: Source::Registers(ctx),
+            gpr: first_gpr.saturating_sub(3).min(ARG_REGS),
+            fpr: 0,
+            overflow: ctx.get_register(1).wrapping_add(8),
+        }
+    }
+
+    /// Arguments described by the EABI `va_list` at `va_list`:
+    /// `{ u8 gpr; u8 fpr; u16 pad; void *overflow_arg_area; void *reg_save_area; }`.
+    pub fn from_va_list(memory: &'a MemoryManager, va_list: u32) -> Self {
+        let byte = |off| memory.read_u8(va_list.wrapping_add(off)).unwrap_or(0);
+        let word = |off| memory.read_u32(va_list.wrapping_add(off)).unwrap_or(0);
+        Self {
+            memory,
+            source: Source::SaveArea(word(8)),
+            gpr: byte(0).min(ARG_REGS),
+            fpr: byte(1).min(ARG_REGS),
+            overflow: word(4),
+        }
+    }
+
+    fn gpr_value(&self, index: u8) -> u32 {
+        match self.source {
+            Source::Registers(ctx) => ctx.get_register(3 + index),
+            Source::SaveArea(area) => self
+                .memory
+                .read_u32(area.wrapping_add(4 * index as u32))
+                .unwrap_or(0),
+        }
+    }
+
+    fn stack_u32(&mut self) -> u32 {
+        let value = self.memory.read_u32(self.overflow).unwrap_or(0);
+        self.overflow = self.overflow.wrapping_add(4);
+        value
+    }
+
+    fn stack_u64(&mut self) -> u64 {
+        self.overflow = self.overflow.wrapping_add(7) & !7;
+        let value = self.memory.read_u64(self.overflow).unwrap_or(0);
+        self.overflow = self.overflow.wrapping_add(8);
+        value
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        if self.gpr < ARG_REGS {
+            self.gpr += 1;
+            self.gpr_value(self.gpr - 1)
+        } else {
+            self.stack_u32()
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // Pairs start on an odd register: r3, r5, r7, r9.
+        self.gpr += self.gpr & 1;
+        if self.gpr < ARG_REGS - 1 {
+            self.gpr += 2;
+            let hi = self.gpr_value(self.gpr - 2) as u64;
+            let lo = self.gpr_value(self.gpr - 1) as u64;
+            (hi << 32) | lo
+        } else {
+            self.gpr = ARG_REGS;
+            self.stack_u64()
+        }
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        if self.fpr < ARG_REGS {
+            self.fpr += 1;
+            let index = self.fpr - 1;
+            match self.source {
+                Source::Registers(ctx) => ctx.fpr[1 + index as usize],
+                Source::SaveArea(area) => f64::from_bits(
+                    self.memory
+                        .read_u64(area.wrapping_add(32 + 8 * index as u32))
+                        .unwrap_or(0),
+                ),
+            }
+        } else {
+            f64::from_bits(self.stack_u64())
+        }
+    }
+}
+
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+/// Format `fmt` like the SDK's `vprintf`. Unknown conversions are copied
+/// through as written.
+pub fn format(fmt: &str, args: &mut VarArgs) -> String {
+    let mut out = String::with_capacity(fmt.len());
+    let mut chars = fmt.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let start = out.len();
+        out.push('%');
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alt = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            out.push(flag);
+            chars.next();
+        }
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            let width = args.next_u32() as i32;
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            spec.width = take_number(&mut chars, &mut out);
+        }
+        if chars.peek() == Some(&'.') {
+            out.push('.');
+            chars.next();
+            spec.precision = Some(if chars.peek() == Some(&'*') {
+                chars.next();
+                (args.next_u32() as i32).max(0) as usize
+            } else {
+                take_number(&mut chars, &mut out)
+            });
+        }
+        let mut length = String::new();
+        while let Some(&len) = chars.peek().filter(|c| "hlqjLzt".contains(**c)) {
+            length.push(len);
+            out.push(len);
+            chars.next();
+        }
+        // `l` alone is 32 bits on the GameCube; `L` only matters for floats,
+        // where long double is double anyway.
+        let bits = match length.as_str() {
+            "ll" | "q" | "j" => 64,
+            "hh" => 8,
+            "h" => 16,
+            _ => 32,
+        };
+        let Some(conv) = chars.next() else {
+            break;
+        };
+        let text = match conv {
+            'd' | 'i' => {
+                let value = if bits == 64 {
+                    args.next_u64() as i64
+                } else {
+                    match bits {
+                        8 => args.next_u32() as i8 as i64,
+                        16 => args.next_u32() as i16 as i64,
+                        _ => args.next_u32() as i32 as i64,
+                    }
+                };
+                let sign = if value < 0 {
+                    "-"
+                } else if spec.plus {
+                    "+"
+                } else if spec.space {
+                    " "
+                } else {
+                    ""
+                };
+                integer(&spec, sign, "", value.unsigned_abs().to_string())
+            }
+            'u' | 'x' | 'X' | 'o' => {
+                let value = if bits == 64 {
+                    args.next_u64()
+                } else {
+                    match bits {
+                        8 => args.next_u32() as u8 as u64,
+                        16 => args.next_u32() as u16 as u64,
+                        _ => args.next_u32() as u64,
+                    }
+                };
+                let (digits, prefix) = match conv {
+                    'x' => (format!("{value:x}"), "0x"),
+                    'X' => (format!("{value:X}"), "0X"),
+                    'o' => (format!("{value:o}"), "0"),
+                    _ => (value.to_string(), ""),
+                };
+                let prefix = if spec.alt && value != 0 { prefix } else { "" };
+                integer(&spec, "", prefix, digits)
+            }
+            'p' => {
+                let value = args.next_u32();
+                pad(&spec, "", "0x", format!("{value:08x}"))
+            }
+            'c' => pad(
+                &Spec {
+                    zero: false,
+                    ..spec
+                },
+                "",
+                "",
+                char::from(args.next_u32() as u8).to_string(),
+            ),
+            's' => {
+                let ptr = args.next_u32();
+                let mut s = if ptr == 0 {
+                    "(null)".to_string()
+                } else {
+                    read_c_string(args.memory, ptr)
+                };
+                if let Some(max) = spec.precision {
+                    s = s.chars().take(max).collect();
+                }
+                pad(
+                    &Spec {
+                        zero: false,
+                        ..spec
+                    },
+                    "",
+                    "",
+                    s,
+                )
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => float(&spec, conv, args.next_f64()),
+            'n' => {
+                args.next_u32();
+                String::new()
+            }
+            '%' => "%".to_string(),
+            other => {
+                out.push(other);
+                continue;
+            }
+        };
+        out.truncate(start);
+        out.push_str(&text);
+    }
+    out
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>, out: &mut String) -> usize {
+    let mut n = 0usize;
+    while let Some(d) = chars.peek().and_then(|c| c.to_digit(10)) {
+        out.push(chars.next().unwrap_or('0'));
+        n = n.saturating_mul(10).saturating_add(d as usize);
+    }
+    n
+}
+
+/// Apply integer precision (minimum digits), then width.
+fn integer(spec: &Spec, sign: &str, prefix: &str, mut digits: String) -> String {
+    match spec.precision {
+        Some(0) if digits == "0" => digits.clear(),
+        Some(min) if digits.len() < min => digits.insert_str(0, &"0".repeat(min - digits.len())),
+        _ => {}
+    }
+    let spec = Spec {
+        // A precision turns off zero padding for integers.
+        zero: spec.zero && spec.precision.is_none(),
+        ..*spec
+    };
+    pad(&spec, sign, prefix, digits)
+}
+
+/// Pad `sign + prefix + body` to the field width.
+fn pad(spec: &Spec, sign: &str, prefix: &str, body: String) -> String {
+    let len = sign.len() + prefix.len() + body.chars().count();
+    let fill = spec.width.saturating_sub(len);
+    if spec.left {
+        format!("{sign}{prefix}{body}{}", " ".repeat(fill))
+    } else if spec.zero {
+        format!("{sign}{prefix}{}{body}", "0".repeat(fill))
+    } else {
+        format!("{}{sign}{prefix}{body}", " ".repeat(fill))
+    }
+}
+
+fn float(spec: &Spec, conv: char, value: f64) -> String {
+    let upper = conv.is_ascii_uppercase();
+    let sign = if value.is_sign_negative() && !value.is_nan() {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    };
+    let value = value.abs();
+    if !value.is_finite() {
+        let body = if value.is_nan() { "nan" } else { "inf" };
+        let body = if upper {
+            body.to_uppercase()
+        } else {
+            body.to_string()
+        };
+        return pad(
+            &Spec {
+                zero: false,
+                ..*spec
+            },
+            sign,
+            "",
+            body,
+        );
+    }
+    let precision = spec.precision.unwrap_or(6);
+    let body = match conv.to_ascii_lowercase() {
+        'f' => format!("{value:.precision$}"),
+        'e' => exponential(value, precision),
+        _ => {
+            let p = precision.max(1);
+            let exp = exponent_of(value, p - 1);
+            let mut s = if exp < -4 || exp >= p as i32 {
+                exponential(value, p - 1)
+            } else {
+                format!("{value:.*}", (p as i32 - 1 - exp) as usize)
+            };
+            if !spec.alt {
+                s = strip_fraction_zeros(&s);
+            }
+            s
+        }
+    };
+    let body = if upper { body.to_uppercase() } else { body };
+    pad(spec, sign, "", body)
+}
+
+/// Decimal exponent of `value` once rounded to `precision` fraction digits
+/// in `%e` form.
+fn exponent_of(value: f64, precision: usize) -> i32 {
+    let s = format!("{value:.precision$e}");
+    s.rsplit('e')
+        .next()
+        .and_then(|e| e.parse().ok())
+        .unwrap_or(0)
+}
+
+/// `%e`: `1.500000e+02`, at least two exponent digits.
+fn exponential(value: f64, precision: usize) -> String {
+    let s = format!("{value:.precision$e}");
+    let (mantissa, exp) = s.split_once('e').unwrap_or((&s, "0"));
+    let exp: i32 = exp.parse().unwrap_or(0);
+    let sign = if exp < 0 { '-' } else { '+' };
+    format!("{mantissa}e{sign}{:02}", exp.unsigned_abs())
+}
+
+/// `%g` drops trailing fraction zeros (and a bare point), keeping any exponent.
+fn strip_fraction_zeros(s: &str) -> String {
+    let (mantissa, exp) = match s.find('e') {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        mantissa
+    };
+    format!("{mantissa}{exp}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printf(fmt: &str, ctx: &CpuContext, memory: &MemoryManager) -> String {
+        format(fmt, &mut VarArgs::from_registers(ctx, memory, 4))
+    }
+
+    #[test]
+    fn integers_strings_and_padding() {
+        let mut memory = MemoryManager::new();
+        for (i, b) in b"disc\0".iter().enumerate() {
+            memory.write_u8(0x8000_4000 + i as u32, *b).unwrap();
+        }
+        let mut ctx = CpuContext::new();
+        ctx.set_register(4, (-7i32) as u32);
+        ctx.set_register(5, 0xBEEF);
+        ctx.set_register(6, 0x8000_4000);
+        ctx.set_register(7, 0);
+        assert_eq!(
+            printf("[%4d] %#06x %-6s| %s %%", &ctx, &memory),
+            "[  -7] 0xbeef disc  | (null) %"
+        );
+        assert_eq!(printf("%05.3d|%hhu", &ctx, &memory), " -007|239");
+    }
+
+    #[test]
+    fn long_long_uses_aligned_pair_and_stack_overflows() {
+        let mut memory = MemoryManager::new();
+        let mut ctx = CpuContext::new();
+        ctx.set_register(1, 0x8010_0000);
+        ctx.set_register(4, 1);
+        // r5:r6 holds the 64-bit value, skipping nothing (r5 is odd).
+        ctx.set_register(5, 0x0000_0001);
+        ctx.set_register(6, 0x0000_0000);
+        for r in 7..=10 {
+            ctx.set_register(r, r as u32);
+        }
+        memory.write_u32(0x8010_0008, 11).unwrap(); // first stack argument
+        assert_eq!(
+            printf("%d %lld %d %d %d %d %d", &ctx, &memory),
+            "1 4294967296 7 8 9 10 11"
+        );
+    }
+
+    #[test]
+    fn doubles_come_from_fprs() {
+        let memory = MemoryManager::new();
+        let mut ctx = CpuContext::new();
+        ctx.fpr[1] = 1.23456;
+        ctx.fpr[2] = 150.0;
+        ctx.fpr[3] = 0.0001;
+        ctx.fpr[4] = -2.5;
+        assert_eq!(
+            printf("%.2f %e %g %G", &ctx, &memory),
+            "1.23 1.500000e+02 0.0001 -2.5"
+        );
+    }
+
+    #[test]
+    fn va_list_reads_the_save_area() {
+        let mut memory = MemoryManager::new();
+        let (list, save) = (0x8000_5000, 0x8000_5100);
+        memory.write_u8(list, 2).unwrap(); // r3, r4 used
+        memory.write_u32(list + 8, save).unwrap();
+        memory.write_u32(save + 8, 99).unwrap(); // r5
+        memory.write_u64(save + 32, 0.5f64.to_bits()).unwrap(); // f1
+        let mut args = VarArgs::from_va_list(&memory, list);
+        assert_eq!(format("%d %.1f", &mut args), "99 0.5");
+    }
+}
@@ -0,0 +1,165 @@
+// Console region (NTSC-U / PAL / NTSC-J)
+//
+// The region decides the video standard the OS boots in (60 Hz NTSC vs 50 Hz
+// PAL), the font encoding (Shift-JIS on Japanese consoles) and the defaults
+// of a fresh SRAM settings block (see `sdk::sram`). It is detected from
+// the last letter of the disc's game code (`GALE` is NTSC-U, `GALP` PAL,
+// `GALJ` NTSC-J) and can be forced with `[runtime] region`.
+
+use log::warn;
+use std::fmt;
+use std::str::FromStr;
+
+use super::card;
+use crate::runtime::memory::MemoryManager;
+
+/// Low-memory word the OS reads the TV format from (`VI_NTSC`, `VI_PAL`, ...).
+pub const OS_TV_MODE: u32 = 0x8000_00CC;
+
+/// `VI_NTSC` / `VI_PAL` in `OS_TV_MODE`.
+pub const VI_NTSC: u32 = 0;
+pub const VI_PAL: u32 = 1;
+
+/// `OS_FONT_ENCODE_ANSI` / `OS_FONT_ENCODE_SJIS` from `OSGetFontEncode`.
+pub const OS_FONT_ENCODE_ANSI: u32 = 0;
+pub const OS_FONT_ENCODE_SJIS: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Region {
+    #[default]
+    NtscU,
+    Pal,
+    NtscJ,
+}
+
+/// `OSGetLanguage` values (the SRAM language byte).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English = 0,
+    German = 1,
+    French = 2,
+    Spanish = 3,
+    Italian = 4,
+    Dutch = 5,
+}
+
+impl Region {
+    /// Region from a game code's country letter.
+    pub fn from_game_code(code: [u8; 4]) -> Option<Self> {
+        match code[3] {
+            b'E' => Some(Region::NtscU),
+            b'J' => Some(Region::NtscJ),
+            // Europe, and the per-country PAL releases.
+            b'P' | b'D' | b'F' | b'S' | b'I' | b'H' | b'U' | b'X' | b'Y' => Some(Region::Pal),
+            _ => None,
+        }
+    }
+
+    /// Region of the disc header in low memory, if its game code has a known
+    /// country letter.
+    pub fn detect(memory: &MemoryManager) -> Option<Self> {
+        Self::from_game_code(card::disc_codes(memory).0)
+    }
+
+    /// The region to run as: the override if there is one (warning when it
+    /// contradicts the disc), else the detected one, else NTSC-U.
+    pub fn resolve(detected: Option<Region>, override_region: Option<Region>) -> Self {
+        match (detected, override_region) {
+            (Some(disc), Some(forced)) if disc != forced => {
+                warn!("Disc is {disc} but region is forced to {forced}; the game may misbehave");
+                forced
+            }
+            (_, Some(forced)) => forced,
+            (Some(disc), None) => disc,
+            (None, None) => Region::default(),
+        }
+    }
+
+    /// VI field rate the OS boots in.
+    pub fn field_rate(self) -> f64 {
+        match self {
+            Region::Pal => 50.0,
+            Region::NtscU | Region::NtscJ => 59.94,
+        }
+    }
+
+    /// `OS_TV_MODE` value.
+    pub fn tv_mode(self) -> u32 {
+        match self {
+            Region::Pal => VI_PAL,
+            Region::NtscU | Region::NtscJ => VI_NTSC,
+        }
+    }
+
+    /// `OSGetFontEncode` value.
+    pub fn font_encode(self) -> u32 {
+        match self {
+            Region::NtscJ => OS_FONT_ENCODE_SJIS,
+            Region::NtscU | Region::Pal => OS_FONT_ENCODE_ANSI,
+        }
+    }
+
+    /// Language of a console with cleared SRAM. Every region defaults to
+    /// English; Japanese consoles are told apart by the font encoding.
+    pub fn default_language(self) -> Language {
+        Language::English
+    }
+}
+
+impl Language {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Language::English,
+            1 => Language::German,
+            2 => Language::French,
+            3 => Language::Spanish,
+            4 => Language::Italian,
+            5 => Language::Dutch,
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "english" | "en" => Ok(Language::English),
+            "german" | "de" => Ok(Language::German),
+            "french" | "fr" => Ok(Language::French),
+            "spanish" | "es" => Ok(Language::Spanish),
+            "italian" | "it" => Ok(Language::Italian),
+            "dutch" | "nl" => Ok(Language::Dutch),
+            other => Err(format!(
+                "unknown language {other:?}; expected english, german, french, spanish, italian or dutch"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Region::NtscU => "ntsc-u",
+            Region::Pal => "pal",
+            Region::NtscJ => "ntsc-j",
+        })
+    }
+}
+
+impl FromStr for Region {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ntsc-u" | "ntsc_u" | "usa" | "us" => Ok(Region::NtscU),
+            "pal" | "eur" | "europe" => Ok(Region::Pal),
+            "ntsc-j" | "ntsc_j" | "jpn" | "japan" => Ok(Region::NtscJ),
+            other => Err(format!(
+                "unknown region {other:?}; expected auto, ntsc-u, pal or ntsc-j"
+            )),
+        }
+    }
+}
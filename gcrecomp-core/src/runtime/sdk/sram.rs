@@ -0,0 +1,194 @@
+//! Battery-backed SRAM: the console settings block (`OSSram`).
+//!
+//! The IPL writes the user's settings here and the SDK's `OSGet*`/`OSSet*`
+//! settings calls read and change them:
+//!
+//! ```text
+//! 0x00  u16  checkSum      sum of the u16 words from 0x0C to the end
+//! 0x02  u16  checkSumInv   sum of their complements
+//! 0x04  u32  ead0, ead1    (unused by games)
+//! 0x0C  u32  counterBias   RTC offset
+//! 0x10  s8   displayOffsetH
+//! 0x11  u8   ntd           wireless ID
+//! 0x12  u8   language      OS_LANG_*
+//! 0x13  u8   flags         video mode, stereo, EuRGB60, progressive
+//! ```
+//!
+//! A block is backed by a host file like a memory card: it is loaded with
+//! [`Sram::open`] and every settings change is written back.
+
+use anyhow::{bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use super::region::{Language, Region};
+
+/// Size of `OSSram`.
+pub const SRAM_SIZE: usize = 20;
+
+/// `flags` bits.
+pub const SRAM_FLAG_VIDEO_MODE: u8 = 0x03;
+pub const SRAM_FLAG_STEREO: u8 = 0x04;
+/// Set once the IPL's first-boot setup ran.
+pub const SRAM_FLAG_OOBE_DONE: u8 = 0x08;
+/// Always set by the IPL.
+pub const SRAM_FLAG_RESERVED: u8 = 0x20;
+pub const SRAM_FLAG_EURGB60: u8 = 0x40;
+pub const SRAM_FLAG_PROGRESSIVE: u8 = 0x80;
+
+/// `OSGetSoundMode` values.
+pub const OS_SOUND_MODE_MONO: u32 = 0;
+pub const OS_SOUND_MODE_STEREO: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sram {
+    pub counter_bias: u32,
+    pub display_offset_h: i8,
+    pub ntd: u8,
+    pub language: Language,
+    pub flags: u8,
+    /// Host file changes are written to.
+    backing: Option<PathBuf>,
+}
+
+impl Default for Sram {
+    fn default() -> Self {
+        Self::for_region(Region::default())
+    }
+}
+
+impl Sram {
+    /// Settings of a console from `region` after its first-boot setup:
+    /// its default language, stereo, interlaced.
+    pub fn for_region(region: Region) -> Self {
+        Self {
+            counter_bias: 0,
+            display_offset_h: 0,
+            ntd: 0,
+            language: region.default_language(),
+            flags: SRAM_FLAG_RESERVED
+                | SRAM_FLAG_OOBE_DONE
+                | SRAM_FLAG_STEREO
+                | region.tv_mode() as u8,
+            backing: None,
+        }
+    }
+
+    /// Load the block at `path`, backed by it from now on. A missing file
+    /// gives `region`'s defaults, created on the first change.
+    pub fn open(path: &Path, region: Region) -> Result<Self> {
+        let mut sram = match std::fs::read(path) {
+            Ok(bytes) => {
+                Self::from_bytes(&bytes).with_context(|| format!("SRAM {}", path.display()))?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::for_region(region),
+            Err(e) => return Err(e).with_context(|| format!("SRAM {}", path.display())),
+        };
+        sram.backing = Some(path.to_path_buf());
+        Ok(sram)
+    }
+
+    /// Write the block to its host file, if it has one.
+    pub fn flush(&self) -> Result<()> {
+        if let Some(path) = &self.backing {
+            std::fs::write(path, self.to_bytes())
+                .with_context(|| format!("SRAM {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// The `OSSram` image, checksummed.
+    pub fn to_bytes(&self) -> [u8; SRAM_SIZE] {
+        let mut bytes = [0u8; SRAM_SIZE];
+        bytes[0x0C..0x10].copy_from_slice(&self.counter_bias.to_be_bytes());
+        bytes[0x10] = self.display_offset_h as u8;
+        bytes[0x11] = self.ntd;
+        bytes[0x12] = self.language as u8;
+        bytes[0x13] = self.flags;
+        let (sum, inv) = checksum(&bytes);
+        bytes[0..2].copy_from_slice(&sum.to_be_bytes());
+        bytes[2..4].copy_from_slice(&inv.to_be_bytes());
+        bytes
+    }
+
+    /// Parse an `OSSram` image, rejecting one whose checksum doesn't match
+    /// (the SDK then falls back to defaults and the IPL asks for setup).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < SRAM_SIZE {
+            bail!("{} bytes, expected {}", bytes.len(), SRAM_SIZE);
+        }
+        let bytes = &bytes[..SRAM_SIZE];
+        let stored = (
+            u16::from_be_bytes([bytes[0], bytes[1]]),
+            u16::from_be_bytes([bytes[2], bytes[3]]),
+        );
+        if stored != checksum(bytes) {
+            bail!("checksum mismatch");
+        }
+        Ok(Self {
+            counter_bias: u32::from_be_bytes([bytes[0x0C], bytes[0x0D], bytes[0x0E], bytes[0x0F]]),
+            display_offset_h: bytes[0x10] as i8,
+            ntd: bytes[0x11],
+            language: Language::from_u8(bytes[0x12]).unwrap_or_default(),
+            flags: bytes[0x13],
+            backing: None,
+        })
+    }
+
+    /// `OSGetSoundMode`.
+    pub fn sound_mode(&self) -> u32 {
+        if self.flags & SRAM_FLAG_STEREO != 0 {
+            OS_SOUND_MODE_STEREO
+        } else {
+            OS_SOUND_MODE_MONO
+        }
+    }
+
+    /// `OSGetProgressiveMode`.
+    pub fn progressive(&self) -> bool {
+        self.flags & SRAM_FLAG_PROGRESSIVE != 0
+    }
+
+    /// `OSGetEuRgb60Mode`.
+    pub fn eurgb60(&self) -> bool {
+        self.flags & SRAM_FLAG_EURGB60 != 0
+    }
+
+    /// `OSGetVideoMode`: `VI_NTSC`, `VI_PAL` or `VI_MPAL`.
+    pub fn video_mode(&self) -> u32 {
+        u32::from(self.flags & SRAM_FLAG_VIDEO_MODE)
+    }
+
+    pub fn set_stereo(&mut self, stereo: bool) {
+        self.set_flag(SRAM_FLAG_STEREO, stereo);
+    }
+
+    pub fn set_progressive(&mut self, on: bool) {
+        self.set_flag(SRAM_FLAG_PROGRESSIVE, on);
+    }
+
+    pub fn set_eurgb60(&mut self, on: bool) {
+        self.set_flag(SRAM_FLAG_EURGB60, on);
+    }
+
+    pub fn set_video_mode(&mut self, mode: u32) {
+        self.flags = (self.flags & !SRAM_FLAG_VIDEO_MODE) | (mode as u8 & SRAM_FLAG_VIDEO_MODE);
+    }
+
+    fn set_flag(&mut self, flag: u8, on: bool) {
+        if on {
+            self.flags |= flag;
+        } else {
+            self.flags &= !flag;
+        }
+    }
+}
+
+/// `(checkSum, checkSumInv)` over the u16 words from `counterBias` on.
+pub fn checksum(bytes: &[u8]) -> (u16, u16) {
+    bytes[0x0C..SRAM_SIZE]
+        .chunks_exact(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]))
+        .fold((0u16, 0u16), |(sum, inv), w| {
+            (sum.wrapping_add(w), inv.wrapping_add(!w))
+        })
+}
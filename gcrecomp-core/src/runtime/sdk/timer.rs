@@ -1,5 +1,10 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 
+use crate::runtime::clock;
+
 /// GameCube timer emulation.
 ///
 /// The GameCube timebase runs at 1/4 of the bus clock:
@@ -8,24 +13,90 @@ use std::time::Instant;
 ///
 /// `OSGetTick()` returns the lower 32 bits of the timebase counter.
 /// `OSGetTime()` returns the full 64-bit timebase counter.
+///
+/// By default the timebase follows the host clock. A [`OsTimer::virtual_clock`]
+/// timer instead advances a fixed [`OsTimer::VIRTUAL_STEP`] per read, so runs
+/// that poll the time see the same values every time. An
+/// [`OsTimer::instruction_clock`] timer advances with the instructions the game
+/// retires (see `runtime::clock`), so time is a pure function of executed code.
 pub struct OsTimer {
     start: Instant,
+    source: Source,
+}
+
+enum Source {
+    Host,
+    /// `(boot value, current value)`.
+    PerRead(u64, AtomicU64),
+    Instructions {
+        boot: u64,
+        /// `clock::instructions()` at the last reset.
+        retired_at_reset: u64,
+        ticks_per_instruction: u64,
+        /// `clock::clock_ratio_permille()` when the timer was made.
+        ratio_permille: u32,
+    },
+}
+
+/// Where the timebase comes from (`[runtime] time_source`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeSource {
+    /// The host clock (or, on seeded runs, a fixed step per read).
+    #[default]
+    Wall,
+    /// A fixed number of ticks per retired instruction.
+    Instructions,
 }
 
 impl OsTimer {
     /// Timebase frequency: 40.5 MHz (bus clock / 4).
-    const TIMEBASE_FREQ: u64 = 40_500_000;
+    pub const TIMEBASE_FREQ: u64 = 40_500_000;
     /// Bus clock frequency: 162 MHz.
     pub const BUS_CLOCK: u64 = 162_000_000;
 
+    /// Virtual timebase ticks per read: 10 µs, so polling loops still finish.
+    pub const VIRTUAL_STEP: u64 = Self::TIMEBASE_FREQ / 100_000;
+
     pub fn new() -> Self {
         Self {
             start: Instant::now(),
+            source: Source::Host,
+        }
+    }
+
+    /// A timer on a virtual clock starting at `boot_ticks`, independent of
+    /// host timing.
+    pub fn virtual_clock(boot_ticks: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            source: Source::PerRead(boot_ticks, AtomicU64::new(boot_ticks)),
+        }
+    }
+
+    /// A timer starting at `boot_ticks` that advances `ticks_per_instruction`
+    /// for every instruction retired from now (or the next `reset`) on,
+    /// divided by the clock ratio in effect now (see `clock::set_clock_ratio`).
+    pub fn instruction_clock(boot_ticks: u64, ticks_per_instruction: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            source: Source::Instructions {
+                boot: boot_ticks,
+                retired_at_reset: clock::instructions(),
+                ticks_per_instruction,
+                ratio_permille: clock::clock_ratio_permille(),
+            },
         }
     }
 
     pub fn reset(&mut self) {
         self.start = Instant::now();
+        match &mut self.source {
+            Source::Host => {}
+            Source::PerRead(boot, ticks) => ticks.store(*boot, Ordering::Relaxed),
+            Source::Instructions {
+                retired_at_reset, ..
+            } => *retired_at_reset = clock::instructions(),
+        }
     }
 
     /// Get the lower 32 bits of the timebase counter (OSGetTick).
@@ -35,6 +106,23 @@ impl OsTimer {
 
     /// Get the full 64-bit timebase counter (OSGetTime).
     pub fn get_time(&self) -> u64 {
+        match &self.source {
+            Source::Host => {}
+            Source::PerRead(_, ticks) => {
+                return ticks.fetch_add(Self::VIRTUAL_STEP, Ordering::Relaxed)
+            }
+            Source::Instructions {
+                boot,
+                retired_at_reset,
+                ticks_per_instruction,
+                ratio_permille,
+            } => {
+                let retired = clock::instructions().wrapping_sub(*retired_at_reset);
+                let ticks = u128::from(retired) * u128::from(*ticks_per_instruction) * 1000
+                    / u128::from(*ratio_permille);
+                return boot.wrapping_add(ticks as u64);
+            }
+        }
         let elapsed = self.start.elapsed();
         let nanos = elapsed.as_nanos() as u64;
         // Convert nanoseconds to timebase ticks: ticks = nanos * freq / 1_000_000_000
@@ -62,3 +150,26 @@ impl Default for OsTimer {
         Self::new()
     }
 }
+
+impl fmt::Display for TimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeSource::Wall => "wall",
+            TimeSource::Instructions => "instructions",
+        })
+    }
+}
+
+impl FromStr for TimeSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "wall" | "host" => Ok(TimeSource::Wall),
+            "instructions" | "instruction" => Ok(TimeSource::Instructions),
+            other => Err(format!(
+                "unknown time source {other:?}; expected wall or instructions"
+            )),
+        }
+    }
+}
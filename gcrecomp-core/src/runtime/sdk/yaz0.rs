@@ -0,0 +1,178 @@
+//! Yaz0, Nintendo's LZ77 variant used for `.szs` and other packed files.
+//!
+//! Stream layout: `"Yaz0"`, the decompressed size (u32 big-endian), eight
+//! reserved bytes, then groups of one code byte followed by eight chunks,
+//! most significant bit first. A set bit is one literal byte; a clear bit a
+//! back-reference `NR RR` (length N + 2, distance R + 1), or `0R RR NN` for
+//! lengths 0x12-0x111 (length NN + 0x12).
+
+const MAGIC: &[u8; 4] = b"Yaz0";
+const HEADER_LEN: usize = 16;
+const WINDOW: usize = 0x1000;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 0x111;
+/// Candidates tried per position; bounds the compressor's time on long runs.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+/// True if `data` starts with a Yaz0 header.
+pub fn is_yaz0(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data.starts_with(MAGIC)
+}
+
+/// Compress `data` (greedy matching over the 4 KiB window).
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + data.len() + data.len() / 8 + 1);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(&[0; 8]);
+
+    let mut chains = Chains {
+        head: vec![usize::MAX; 1 << HASH_BITS],
+        prev: vec![usize::MAX; data.len()],
+    };
+
+    let mut pos = 0;
+    while pos < data.len() {
+        let code_at = out.len();
+        out.push(0);
+        for bit in 0..8 {
+            if pos >= data.len() {
+                break;
+            }
+            let (length, distance) = chains.longest_match(data, pos);
+            if length >= MIN_MATCH {
+                let d = distance - 1;
+                if length >= 0x12 {
+                    out.extend_from_slice(&[(d >> 8) as u8, d as u8, (length - 0x12) as u8]);
+                } else {
+                    out.extend_from_slice(&[((length - 2) << 4 | d >> 8) as u8, d as u8]);
+                }
+                for p in pos..pos + length {
+                    chains.insert(data, p);
+                }
+                pos += length;
+            } else {
+                out[code_at] |= 0x80 >> bit;
+                out.push(data[pos]);
+                chains.insert(data, pos);
+                pos += 1;
+            }
+        }
+    }
+    out
+}
+
+/// Hash chains over 3-byte prefixes: `head` is the latest position per
+/// hash, `prev` links each position to the previous one with the same hash.
+struct Chains {
+    head: Vec<usize>,
+    prev: Vec<usize>,
+}
+
+impl Chains {
+    fn hash(data: &[u8], p: usize) -> usize {
+        ((data[p] as usize) << 10 ^ (data[p + 1] as usize) << 5 ^ data[p + 2] as usize)
+            & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, data: &[u8], p: usize) {
+        if p + MIN_MATCH <= data.len() {
+            let h = Self::hash(data, p);
+            self.prev[p] = self.head[h];
+            self.head[h] = p;
+        }
+    }
+
+    /// Longest earlier match for `pos` within the window: (length, distance).
+    fn longest_match(&self, data: &[u8], pos: usize) -> (usize, usize) {
+        if pos + MIN_MATCH > data.len() {
+            return (0, 0);
+        }
+        let limit = MAX_MATCH.min(data.len() - pos);
+        let (mut best, mut distance) = (0, 0);
+        let mut candidate = self.head[Self::hash(data, pos)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || pos - candidate > WINDOW {
+                break;
+            }
+            let length = (0..limit)
+                .take_while(|&i| data[candidate + i] == data[pos + i])
+                .count();
+            if length > best {
+                (best, distance) = (length, pos - candidate);
+                if length == limit {
+                    break;
+                }
+            }
+            candidate = self.prev[candidate];
+        }
+        (best, distance)
+    }
+}
+
+/// Decompress a Yaz0 stream.
+pub fn decompress(src: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_yaz0(src) {
+        return Err("Not a Yaz0 stream.".to_string());
+    }
+    let size = u32::from_be_bytes([src[4], src[5], src[6], src[7]]) as usize;
+    let mut out = Vec::with_capacity(size);
+    let mut pos = HEADER_LEN;
+    let byte = |pos: &mut usize| {
+        let b = src.get(*pos).copied();
+        *pos += 1;
+        b.ok_or_else(|| "Yaz0 stream truncated.".to_string())
+    };
+
+    while out.len() < size {
+        let code = byte(&mut pos)?;
+        for bit in 0..8 {
+            if out.len() >= size {
+                break;
+            }
+            if code & (0x80 >> bit) != 0 {
+                out.push(byte(&mut pos)?);
+                continue;
+            }
+            let b0 = byte(&mut pos)? as usize;
+            let b1 = byte(&mut pos)? as usize;
+            let distance = ((b0 & 0xF) << 8 | b1) + 1;
+            let length = match b0 >> 4 {
+                0 => byte(&mut pos)? as usize + 0x12,
+                n => n + 2,
+            };
+            if distance > out.len() {
+                return Err(format!(
+                    "Yaz0 back-reference {} bytes before the start of the output.",
+                    distance
+                ));
+            }
+            for _ in 0..length.min(size - out.len()) {
+                out.push(out[out.len() - distance]);
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrips_and_compresses_repetition() {
+        let mut data = b"GameCube ".repeat(100);
+        data.extend((0..=255u8).cycle().take(3000));
+        data.extend([0u8; 500]);
+        let packed = compress(&data);
+        assert!(is_yaz0(&packed));
+        assert!(packed.len() < data.len() / 2, "{} bytes", packed.len());
+        assert_eq!(decompress(&packed).unwrap(), data);
+
+        for small in [&b""[..], b"a", b"ab", b"abcabcabc"] {
+            assert_eq!(decompress(&compress(small)).unwrap(), small);
+        }
+        assert!(decompress(b"Yaz0\0\0\0\x10\0\0\0\0\0\0\0\0").is_err());
+    }
+}
@@ -0,0 +1,322 @@
+// Instruction traces: every interpreted instruction with the registers after it
+//
+// `start` arms a global tracer and the interpreter records into it after each
+// instruction; `stop` hands the trace back for export. Real runs produce
+// millions of entries, so the default export is a compact binary stream that
+// stores, per instruction, only the registers that changed since the previous
+// one (`GCTR` format below). `TraceFormat::Json` keeps the pretty JSON export
+// for small traces that are read by hand. `RuntimeTracer::read_from_file`
+// loads either and reconstructs full register states.
+//
+// Compact format (big-endian):
+//
+// ```text
+// "GCTR"  u32 version  u64 entry count
+// per entry: u32 address  u32 raw  u8 n  n × (u8 slot, value)
+// ```
+//
+// Slots are `RegisterState::slot` indices; FPR slots carry a u64 bit pattern,
+// all others a u32. The first entry is a delta against all-zero registers.
+
+use crate::runtime::context::CpuContext;
+use anyhow::{bail, ensure, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+const MAGIC: &[u8; 4] = b"GCTR";
+const VERSION: u32 = 1;
+
+/// Register snapshot. FPRs are kept as bit patterns so NaNs compare exactly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct RegisterState {
+    pub gpr: [u32; 32],
+    pub fpr: [u64; 32],
+    pub lr: u32,
+    pub ctr: u32,
+    pub cr: u32,
+    pub xer: u32,
+    pub fpscr: u32,
+    pub msr: u32,
+}
+
+impl RegisterState {
+    /// Slots: r0-r31, f0-f31, then lr, ctr, cr, xer, fpscr, msr.
+    pub const SLOTS: usize = 70;
+
+    pub fn from_context(ctx: &CpuContext) -> Self {
+        Self {
+            gpr: ctx.gpr,
+            fpr: ctx.fpr.map(f64::to_bits),
+            lr: ctx.lr,
+            ctr: ctx.ctr,
+            cr: ctx.cr,
+            xer: ctx.xer,
+            fpscr: ctx.fpscr,
+            msr: ctx.msr,
+        }
+    }
+
+    /// Load these registers into `ctx` (PC is left alone).
+    pub fn apply_to(&self, ctx: &mut CpuContext) {
+        ctx.gpr = self.gpr;
+        ctx.fpr = self.fpr.map(f64::from_bits);
+        ctx.lr = self.lr;
+        ctx.ctr = self.ctr;
+        ctx.cr = self.cr;
+        ctx.xer = self.xer;
+        ctx.fpscr = self.fpscr;
+        ctx.msr = self.msr;
+    }
+
+    /// All-zero registers except the given `(slot, value)` pairs.
+    pub fn from_slots(slots: &[(usize, u64)]) -> Self {
+        let mut state = Self::default();
+        for &(slot, value) in slots {
+            state.set_slot(slot, value);
+        }
+        state
+    }
+
+    pub fn slot(&self, slot: usize) -> u64 {
+        match slot {
+            0..=31 => self.gpr[slot].into(),
+            32..=63 => self.fpr[slot - 32],
+            64 => self.lr.into(),
+            65 => self.ctr.into(),
+            66 => self.cr.into(),
+            67 => self.xer.into(),
+            68 => self.fpscr.into(),
+            69 => self.msr.into(),
+            _ => 0,
+        }
+    }
+
+    fn set_slot(&mut self, slot: usize, value: u64) {
+        let word = value as u32;
+        match slot {
+            0..=31 => self.gpr[slot] = word,
+            32..=63 => self.fpr[slot - 32] = value,
+            64 => self.lr = word,
+            65 => self.ctr = word,
+            66 => self.cr = word,
+            67 => self.xer = word,
+            68 => self.fpscr = word,
+            69 => self.msr = word,
+            _ => {}
+        }
+    }
+
+    /// `r3`, `f1`, `lr`, ... for a slot.
+    pub fn slot_name(slot: usize) -> String {
+        match slot {
+            0..=31 => format!("r{slot}"),
+            32..=63 => format!("f{}", slot - 32),
+            _ => ["lr", "ctr", "cr", "xer", "fpscr", "msr"]
+                .get(slot - 64)
+                .unwrap_or(&"?")
+                .to_string(),
+        }
+    }
+
+    /// Slots whose value differs from `prev`.
+    pub fn changed_since(&self, prev: &RegisterState) -> Vec<usize> {
+        (0..Self::SLOTS)
+            .filter(|&s| self.slot(s) != prev.slot(s))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    pub address: u32,
+    pub raw: u32,
+    /// Registers after the instruction executed.
+    pub registers: RegisterState,
+}
+
+/// How `RuntimeTracer::export_to_files` writes a trace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceFormat {
+    /// Delta-encoded binary (`trace.gctrace`).
+    #[default]
+    Compact,
+    /// Pretty JSON with full register states (`trace.json`); small traces only.
+    Json,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RuntimeTracer {
+    entries: Vec<TraceEntry>,
+}
+
+impl RuntimeTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, address: u32, raw: u32, ctx: &CpuContext) {
+        self.entries.push(TraceEntry {
+            address,
+            raw,
+            registers: RegisterState::from_context(ctx),
+        });
+    }
+
+    pub fn entries(&self) -> &[TraceEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Write the trace into `dir` (created if missing). Returns the file.
+    pub fn export_to_files(&self, dir: &Path, format: TraceFormat) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        let path = dir.join(match format {
+            TraceFormat::Compact => "trace.gctrace",
+            TraceFormat::Json => "trace.json",
+        });
+        let file =
+            std::fs::File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+        let mut out = BufWriter::new(file);
+        match format {
+            TraceFormat::Compact => self.write_compact(&mut out)?,
+            TraceFormat::Json => serde_json::to_writer_pretty(&mut out, &self.entries)?,
+        }
+        out.flush()?;
+        Ok(path)
+    }
+
+    /// Load a trace written by `export_to_files`, in either format.
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        let file =
+            std::fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+        let mut input = BufReader::new(file);
+        let tracer = if input.fill_buf()?.starts_with(MAGIC) {
+            Self::read_compact(&mut input)
+        } else {
+            serde_json::from_reader(input)
+                .map(|entries| Self { entries })
+                .map_err(Into::into)
+        };
+        tracer.with_context(|| format!("reading trace {}", path.display()))
+    }
+
+    /// The compact encoding of the trace.
+    pub fn write_compact(&self, out: &mut impl Write) -> Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_be_bytes())?;
+        out.write_all(&(self.entries.len() as u64).to_be_bytes())?;
+        let mut prev = RegisterState::default();
+        for entry in &self.entries {
+            out.write_all(&entry.address.to_be_bytes())?;
+            out.write_all(&entry.raw.to_be_bytes())?;
+            let changed = entry.registers.changed_since(&prev);
+            out.write_all(&[changed.len() as u8])?;
+            for slot in changed {
+                out.write_all(&[slot as u8])?;
+                let value = entry.registers.slot(slot);
+                if is_wide(slot) {
+                    out.write_all(&value.to_be_bytes())?;
+                } else {
+                    out.write_all(&(value as u32).to_be_bytes())?;
+                }
+            }
+            prev = entry.registers;
+        }
+        Ok(())
+    }
+
+    /// Decode a compact trace, replaying the deltas into full states.
+    pub fn read_compact(input: &mut impl Read) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        ensure!(magic == *MAGIC, "not a compact trace");
+        Self::read_compact_body(input)
+    }
+
+    fn read_compact_body(input: &mut impl Read) -> Result<Self> {
+        let version = u32::from_be_bytes(read_array(input)?);
+        ensure!(
+            version == VERSION,
+            "trace version {version} (expected {VERSION})"
+        );
+        let count = u64::from_be_bytes(read_array(input)?);
+        let mut entries = Vec::with_capacity(count.min(1 << 20) as usize);
+        let mut registers = RegisterState::default();
+        for index in 0..count {
+            let address = u32::from_be_bytes(read_array(input)?);
+            let raw = u32::from_be_bytes(read_array(input)?);
+            let [changed] = read_array(input)?;
+            for _ in 0..changed {
+                let [slot] = read_array(input)?;
+                let slot = slot as usize;
+                if slot >= RegisterState::SLOTS {
+                    bail!("entry {index}: register slot {slot} out of range");
+                }
+                let value = if is_wide(slot) {
+                    u64::from_be_bytes(read_array(input)?)
+                } else {
+                    u32::from_be_bytes(read_array(input)?).into()
+                };
+                registers.set_slot(slot, value);
+            }
+            entries.push(TraceEntry {
+                address,
+                raw,
+                registers,
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+fn is_wide(slot: usize) -> bool {
+    (32..64).contains(&slot)
+}
+
+fn read_array<const N: usize>(input: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    input
+        .read_exact(&mut bytes)
+        .context("trace ends mid-entry")?;
+    Ok(bytes)
+}
+
+/// Fast-path gate for `record`: true while a global trace is running.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static TRACER: Mutex<Option<RuntimeTracer>> = Mutex::new(None);
+
+fn tracer() -> std::sync::MutexGuard<'static, Option<RuntimeTracer>> {
+    TRACER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start recording interpreted instructions into a fresh trace.
+pub fn start() {
+    *tracer() = Some(RuntimeTracer::new());
+    ACTIVE.store(true, Ordering::Relaxed);
+}
+
+/// Stop recording and return what was recorded.
+pub fn stop() -> Option<RuntimeTracer> {
+    ACTIVE.store(false, Ordering::Relaxed);
+    tracer().take()
+}
+
+/// Record one executed instruction if a trace is running.
+#[inline]
+pub fn record(address: u32, raw: u32, ctx: &CpuContext) {
+    if ACTIVE.load(Ordering::Relaxed) {
+        if let Some(t) = tracer().as_mut() {
+            t.record(address, raw, ctx);
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Loop budget for recompiled and interpreted code
+//
+// A mistranslated loop condition would otherwise spin forever. Both execution
+// paths count loop back-edges per call (`back_edge`): generated code at every
+// intra-function jump to an earlier block, the interpreter at every backward
+// branch. Past the budget the call fails with `ExecutionError::Timeout`,
+// carrying the loop's PC and the most recent addresses from the trace ring.
+//
+// The ring holds every instruction the interpreter executed and every loop
+// header recompiled code jumped back to; recompiled straight-line code doesn't
+// record anything, so its hot path stays free of bookkeeping.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Default back-edges allowed per call (`[runtime] loop_budget`).
+pub const DEFAULT_LOOP_BUDGET: u64 = 8_000_000;
+
+/// Addresses kept in the trace ring.
+pub const TRACE_RING_LEN: usize = 32;
+
+/// 0 = unlimited.
+static LOOP_BUDGET: AtomicU64 = AtomicU64::new(DEFAULT_LOOP_BUDGET);
+
+thread_local! {
+    static RING: RefCell<VecDeque<u32>> = RefCell::new(VecDeque::with_capacity(TRACE_RING_LEN));
+}
+
+/// Runtime failures of recompiled/interpreted code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionError {
+    /// A loop ran past the budget. `recent` is oldest first.
+    Timeout {
+        pc: u32,
+        steps: u64,
+        recent: Vec<u32>,
+    },
+    /// The function at `address` panicked (only caught with
+    /// `runtime::set_catch_panics`). `recent` is oldest first.
+    Panic {
+        address: u32,
+        message: String,
+        recent: Vec<u32>,
+    },
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecutionError::Timeout { pc, steps, recent } => {
+                write!(
+                    f,
+                    "Loop budget exceeded at 0x{pc:08X} after {steps} iterations; recent:"
+                )?;
+                for addr in recent {
+                    write!(f, " {addr:08X}")?;
+                }
+                Ok(())
+            }
+            ExecutionError::Panic {
+                address,
+                message,
+                recent,
+            } => {
+                write!(f, "Function 0x{address:08X} panicked: {message}; recent:")?;
+                for addr in recent {
+                    write!(f, " {addr:08X}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExecutionError {}
+
+/// Set the per-call back-edge budget; `None` disables the check.
+pub fn set_loop_budget(budget: Option<u64>) {
+    LOOP_BUDGET.store(budget.unwrap_or(0), Ordering::Relaxed);
+}
+
+pub fn loop_budget() -> Option<u64> {
+    match LOOP_BUDGET.load(Ordering::Relaxed) {
+        0 => None,
+        n => Some(n),
+    }
+}
+
+/// Push `pc` into this thread's trace ring.
+#[inline]
+pub fn record_pc(pc: u32) {
+    RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        if ring.len() == TRACE_RING_LEN {
+            ring.pop_front();
+        }
+        ring.push_back(pc);
+    });
+}
+
+/// The trace ring, oldest first.
+pub fn recent_pcs() -> Vec<u32> {
+    RING.with(|ring| ring.borrow().iter().copied().collect())
+}
+
+/// Count one back-edge to `pc`. Errors once `steps` passes the budget;
+/// returns true when the wall-clock watchdog (`out_of_budget`) says to bail.
+#[inline]
+pub fn back_edge(steps: &mut u64, pc: u32) -> Result<bool, ExecutionError> {
+    *steps += 1;
+    record_pc(pc);
+    if let Some(budget) = loop_budget() {
+        if *steps > budget {
+            return Err(ExecutionError::Timeout {
+                pc,
+                steps: *steps,
+                recent: recent_pcs(),
+            });
+        }
+    }
+    Ok(*steps & 0xFFFF == 0 && super::out_of_budget())
+}
@@ -0,0 +1,143 @@
+// Browser bindings (`wasm` feature)
+//
+// A small machine for demos: load a function's words into RAM, run it on the
+// interpreter and inspect the registers afterwards, or recompile it to Rust
+// source. Only the decoder, code generator, interpreter, `CpuContext` and the
+// Vec-backed `MemoryManager` are involved, so this builds for
+// wasm32-unknown-unknown with `--no-default-features --features wasm` (no
+// zstd, and none of the runtime crate's wgpu/winit/audio).
+//
+// There is one machine per thread; every export works on it. Errors come back
+// as strings, which wasm-bindgen throws as JS exceptions.
+
+use crate::recompiler::analysis::FunctionMetadata;
+use crate::recompiler::codegen::CodeGenerator;
+use crate::recompiler::decoder::Instruction;
+use crate::runtime::context::CpuContext;
+use crate::runtime::interpreter;
+use crate::runtime::memory::MemoryManager;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+#[derive(Default)]
+struct Machine {
+    ctx: CpuContext,
+    memory: MemoryManager,
+}
+
+thread_local! {
+    static MACHINE: RefCell<Machine> = RefCell::new(Machine::default());
+}
+
+fn with_machine<T>(f: impl FnOnce(&mut Machine) -> T) -> T {
+    MACHINE.with(|m| f(&mut m.borrow_mut()))
+}
+
+/// Reset the machine: zeroed registers and RAM.
+#[wasm_bindgen]
+pub fn init() {
+    with_machine(|m| *m = Machine::default());
+}
+
+/// Write a function's instruction words to RAM at `address`.
+#[wasm_bindgen]
+pub fn load_function(address: u32, words: &[u32]) -> Result<(), String> {
+    with_machine(|m| {
+        for (i, &word) in words.iter().enumerate() {
+            m.memory
+                .write_u32(address.wrapping_add(i as u32 * 4), word)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    })
+}
+
+/// Interpret the function at `address` until it returns. Returns r3.
+#[wasm_bindgen]
+pub fn run(address: u32) -> Result<u32, String> {
+    with_machine(|m| {
+        interpreter::interpret_function(address, &mut m.ctx, &mut m.memory)
+            .map(|r3| r3.unwrap_or(m.ctx.gpr[3]))
+            .map_err(|e| format!("{e:#}"))
+    })
+}
+
+#[wasm_bindgen]
+pub fn get_register(reg: u8) -> u32 {
+    with_machine(|m| m.ctx.get_register(reg))
+}
+
+#[wasm_bindgen]
+pub fn set_register(reg: u8, value: u32) {
+    with_machine(|m| m.ctx.set_register(reg, value));
+}
+
+#[wasm_bindgen]
+pub fn get_cr() -> u32 {
+    with_machine(|m| m.ctx.cr)
+}
+
+#[wasm_bindgen]
+pub fn get_lr() -> u32 {
+    with_machine(|m| m.ctx.lr)
+}
+
+#[wasm_bindgen]
+pub fn get_ctr() -> u32 {
+    with_machine(|m| m.ctx.ctr)
+}
+
+#[wasm_bindgen]
+pub fn get_xer() -> u32 {
+    with_machine(|m| m.ctx.xer)
+}
+
+/// The Rust the recompiler generates for `words` placed at `address`.
+#[wasm_bindgen]
+pub fn recompile(address: u32, words: &[u32]) -> Result<String, String> {
+    let instructions = words
+        .iter()
+        .enumerate()
+        .map(|(i, &word)| Instruction::decode(word, address.wrapping_add(i as u32 * 4)))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let metadata = FunctionMetadata {
+        address,
+        name: format!("func_{address:08X}"),
+        size: words.len() as u32 * 4,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    CodeGenerator::new()
+        .generate_function(&metadata, &instructions)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_a_sample_function() {
+        // r3 = r3 * 3 + 1
+        let words = [
+            0x1C63_0003, // mulli r3, r3, 3
+            0x3863_0001, // addi  r3, r3, 1
+            0x4E80_0020, // blr
+        ];
+        init();
+        load_function(0x8000_3100, &words).unwrap();
+        set_register(3, 7);
+        assert_eq!(run(0x8000_3100), Ok(22));
+        assert_eq!(get_register(3), 22);
+
+        let rust = recompile(0x8000_3100, &words).unwrap();
+        assert!(rust.contains("fn func_80003100"), "{rust}");
+
+        init();
+        assert_eq!(get_register(3), 0);
+    }
+}
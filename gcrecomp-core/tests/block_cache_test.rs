@@ -0,0 +1,72 @@
+//! The interpreter's block cache: repeat runs hit it, code writes invalidate it
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::{
+    block_cache_stats, clear_block_cache, interpret_function,
+};
+use gcrecomp_core::runtime::memory::MemoryManager;
+
+const CODE: u32 = 0x8000_4000;
+
+#[test]
+fn repeat_runs_hit_and_code_writes_invalidate() {
+    clear_block_cache();
+    let mut memory = MemoryManager::new();
+    // li r3,1 ; addi r3,r3,2 ; blr
+    memory.write_u32(CODE, 0x3860_0001).unwrap();
+    memory.write_u32(CODE + 4, 0x3863_0002).unwrap();
+    memory.write_u32(CODE + 8, 0x4E80_0020).unwrap();
+    let mut ctx = CpuContext::new();
+
+    assert_eq!(
+        interpret_function(CODE, &mut ctx, &mut memory).unwrap(),
+        Some(3)
+    );
+    let first = block_cache_stats();
+    assert_eq!((first.hits, first.misses, first.blocks), (0, 1, 1));
+
+    assert_eq!(
+        interpret_function(CODE, &mut ctx, &mut memory).unwrap(),
+        Some(3)
+    );
+    let second = block_cache_stats();
+    assert_eq!((second.hits, second.misses), (1, 1));
+
+    // Data writes elsewhere leave the block alone.
+    memory.write_u32(0x8001_0000, 0xDEAD_BEEF).unwrap();
+    interpret_function(CODE, &mut ctx, &mut memory).unwrap();
+    assert_eq!(block_cache_stats().hits, 2);
+
+    // Patch `li r3,1` into `li r3,10`: the block is dropped and read again.
+    memory.write_u32(CODE, 0x3860_000A).unwrap();
+    assert_eq!(
+        interpret_function(CODE, &mut ctx, &mut memory).unwrap(),
+        Some(12)
+    );
+    let patched = block_cache_stats();
+    assert_eq!(patched.invalidated, 1);
+    assert_eq!((patched.hits, patched.misses), (2, 2));
+
+    // The recompiled block is cached again.
+    assert_eq!(
+        interpret_function(CODE, &mut ctx, &mut memory).unwrap(),
+        Some(12)
+    );
+    assert_eq!(block_cache_stats().hits, 3);
+}
+
+#[test]
+fn another_memory_does_not_reuse_blocks() {
+    clear_block_cache();
+    let mut a = MemoryManager::new();
+    a.write_u32(CODE, 0x3860_0001).unwrap(); // li r3,1
+    a.write_u32(CODE + 4, 0x4E80_0020).unwrap();
+    let mut b = MemoryManager::new();
+    b.write_u32(CODE, 0x3860_0002).unwrap(); // li r3,2
+    b.write_u32(CODE + 4, 0x4E80_0020).unwrap();
+    let mut ctx = CpuContext::new();
+
+    assert_eq!(interpret_function(CODE, &mut ctx, &mut a).unwrap(), Some(1));
+    assert_eq!(interpret_function(CODE, &mut ctx, &mut b).unwrap(), Some(2));
+    assert_eq!(block_cache_stats().hits, 0);
+}
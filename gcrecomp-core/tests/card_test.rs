@@ -0,0 +1,102 @@
+//! Memory card images and the CARD API
+
+use gcrecomp_core::runtime::sdk::card::{self, CardError, CardSystem, MemoryCard, BLOCK_SIZE};
+use std::path::{Path, PathBuf};
+
+const GAME: [u8; 4] = *b"GTST";
+const MAKER: [u8; 2] = *b"01";
+
+fn temp_card(name: &str, ext: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_card_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(format!("card.{ext}"))
+}
+
+fn mounted(path: &Path) -> CardSystem {
+    let mut cards = CardSystem::new();
+    cards.set_game(GAME, MAKER);
+    cards.insert(0, MemoryCard::open(path).unwrap());
+    cards.mount(0).unwrap();
+    cards
+}
+
+#[test]
+fn test_write_remount_read_back() {
+    let path = temp_card("roundtrip", "raw");
+    let block: Vec<u8> = (0..BLOCK_SIZE).map(|i| (i * 7 % 251) as u8).collect();
+
+    let mut cards = mounted(&path);
+    let file_no = cards.create(0, "save01", 2 * BLOCK_SIZE).unwrap();
+    cards.write(0, file_no, BLOCK_SIZE, &block).unwrap();
+    drop(cards);
+
+    // A fresh mount of the file on disk sees the same save.
+    let cards = mounted(&path);
+    let file_no = cards.open(0, "save01").unwrap();
+    assert_eq!(
+        cards.read(0, file_no, BLOCK_SIZE, BLOCK_SIZE).unwrap(),
+        block
+    );
+    assert_eq!(
+        cards.card(0).unwrap().file_len(file_no).unwrap(),
+        2 * BLOCK_SIZE
+    );
+    assert_eq!(cards.open(0, "missing"), Err(CardError::NoFile));
+
+    // 16 Mbit card: 251 data blocks, two of them now used.
+    assert_eq!(cards.card(0).unwrap().free_blocks(), 249);
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
+
+#[test]
+fn test_gci_backing_exports_the_save() {
+    let path = temp_card("gci", "gci");
+    let mut cards = mounted(&path);
+    let file_no = cards.create(0, "only", BLOCK_SIZE).unwrap();
+    cards.write(0, file_no, 0, &[0xAB; 0x200]).unwrap();
+
+    let gci = std::fs::read(&path).unwrap();
+    assert_eq!(gci.len(), card::DIR_ENTRY_SIZE + BLOCK_SIZE);
+    assert_eq!(&gci[..4], b"GTST");
+
+    let cards = mounted(&path);
+    let file_no = cards.open(0, "only").unwrap();
+    assert_eq!(cards.read(0, file_no, 0, 0x200).unwrap(), vec![0xAB; 0x200]);
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
+
+#[test]
+fn test_no_card_and_card_full() {
+    let mut cards = CardSystem::new();
+    assert_eq!(cards.mount(1), Err(CardError::NoCard));
+    assert_eq!(CardError::NoCard.code(), -3);
+
+    // 4 Mbit card: 59 data blocks.
+    cards.set_game(GAME, MAKER);
+    cards.insert(0, MemoryCard::format(card::MBIT_4));
+    cards.mount(0).unwrap();
+    assert_eq!(cards.card(0).unwrap().free_blocks(), 59);
+    cards.create(0, "big", 50 * BLOCK_SIZE).unwrap();
+    assert_eq!(
+        cards.create(0, "bigger", 10 * BLOCK_SIZE),
+        Err(CardError::InsSpace)
+    );
+    assert_eq!(CardError::InsSpace.code(), -9);
+    assert_eq!(cards.create(0, "big", BLOCK_SIZE), Err(CardError::Exist));
+
+    // EXI: an empty slot floats high; a read command returns image bytes.
+    let mut data = [0u8; 4];
+    assert!(!cards.exi_transfer(1, &[0x52, 0, 0, 0, 0], &mut data));
+    assert_eq!(data, [0xFF; 4]);
+    let addr = 0x22u32; // header: card size in Mbit
+    let cmd = [
+        0x52,
+        (addr >> 17) as u8 & 0x7F,
+        (addr >> 9) as u8,
+        (addr >> 7) as u8 & 3,
+        addr as u8 & 0x7F,
+    ];
+    let mut size = [0u8; 2];
+    assert!(cards.exi_transfer(0, &cmd, &mut size));
+    assert_eq!(u16::from_be_bytes(size), card::MBIT_4);
+}
@@ -0,0 +1,107 @@
+//! CPU clock ratio: instructions per VI field scale with it, timebase per field doesn't
+
+use gcrecomp_core::config::Config;
+use gcrecomp_core::runtime::clock::{self, set_clock_ratio, FieldClock, CLOCK_RATIO_RANGE};
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::{interpret_function, set_call_dispatcher};
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::sdk::timer::OsTimer;
+use std::sync::Mutex;
+
+const POLL: u32 = 0x8000_1000;
+const GAME: u32 = 0x8000_3000;
+/// 40.5 MHz / 60 Hz / 375 = 1800 instructions per field at 1.0x, a multiple
+/// of the game loop's 4 instructions, so every field is the same length.
+const TICKS_PER_INSTRUCTION: u64 = 375;
+
+static FIELDS: Mutex<Option<FieldClock>> = Mutex::new(None);
+/// `clock::instructions()` at each retrace callback.
+static RETRACES: Mutex<Vec<u64>> = Mutex::new(Vec::new());
+/// The clock ratio and instruction count are global; tests using them take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// The host's per-call check: a field that ended runs the retrace callback.
+fn dispatch(
+    address: u32,
+    ctx: &mut CpuContext,
+    _memory: &mut MemoryManager,
+) -> anyhow::Result<Option<u32>> {
+    assert_eq!(address, POLL);
+    if FIELDS.lock().unwrap().as_mut().unwrap().fields_due() > 0 {
+        RETRACES.lock().unwrap().push(clock::instructions());
+    }
+    Ok(Some(ctx.gpr[3]))
+}
+
+/// Instructions between consecutive retrace callbacks at `ratio`.
+fn instructions_between_retraces(ratio: f64) -> Vec<u64> {
+    let words = [
+        0x7FE8_02A6, // mflr r31
+        0x3BC0_0000, // li r30, 0
+        0x4BFF_DFF9, // loop: bl POLL
+        0x3BDE_0001, // addi r30, r30, 1
+        0x2C1E_0FA0, // cmpwi r30, 4000
+        0x4180_FFF4, // blt loop
+        0x7FE8_03A6, // mtlr r31
+        0x4E80_0020, // blr
+    ];
+    let mut memory = MemoryManager::new();
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(GAME + i as u32 * 4, *w).unwrap();
+    }
+    assert_eq!(set_clock_ratio(ratio), ratio);
+    *FIELDS.lock().unwrap() = Some(FieldClock::new(60.0, TICKS_PER_INSTRUCTION));
+    RETRACES.lock().unwrap().clear();
+
+    interpret_function(GAME, &mut CpuContext::new(), &mut memory).unwrap();
+    let retraces = RETRACES.lock().unwrap();
+    retraces.windows(2).map(|w| w[1] - w[0]).collect()
+}
+
+#[test]
+fn doubling_the_ratio_doubles_instructions_per_field() {
+    let _serial = SERIAL.lock().unwrap();
+    set_call_dispatcher(dispatch);
+
+    let normal = instructions_between_retraces(1.0);
+    let fast = instructions_between_retraces(2.0);
+    let slow = instructions_between_retraces(0.5);
+    set_clock_ratio(1.0);
+
+    assert!(normal.len() >= 4, "{normal:?}");
+    assert!(normal.iter().all(|&n| n == 1800), "{normal:?}");
+    assert!(fast.len() >= 2, "{fast:?}");
+    assert!(fast.iter().all(|&n| n == 3600), "{fast:?}");
+    assert!(slow.iter().all(|&n| n == 900), "{slow:?}");
+}
+
+#[test]
+fn timebase_per_field_is_unchanged_and_ratio_is_clamped() {
+    let _serial = SERIAL.lock().unwrap();
+    for ratio in [1.0, 2.0, 0.5] {
+        set_clock_ratio(ratio);
+        let timer = OsTimer::instruction_clock(0, TICKS_PER_INSTRUCTION);
+        let per_field = FieldClock::new(60.0, TICKS_PER_INSTRUCTION).instructions_per_field();
+        clock::retire(per_field as u32);
+        assert_eq!(timer.get_time(), OsTimer::TIMEBASE_FREQ / 60, "at {ratio}x");
+    }
+
+    assert_eq!(set_clock_ratio(100.0), *CLOCK_RATIO_RANGE.end());
+    assert_eq!(set_clock_ratio(0.0), *CLOCK_RATIO_RANGE.start());
+    assert_eq!(set_clock_ratio(f64::NAN), 1.0);
+}
+
+#[test]
+fn config_sets_the_clock_ratio() {
+    let env = |var: &str| (var == "GCRECOMP_RUNTIME_CPU_CLOCK_PERCENT").then(|| "150".to_string());
+    let loaded = Config::load_from(None, env).unwrap();
+    assert_eq!(loaded.config.clock_ratio(), 1.5);
+    assert_eq!(Config::default().clock_ratio(), 1.0);
+
+    let env = |var: &str| (var == "GCRECOMP_RUNTIME_CPU_CLOCK_PERCENT").then(|| "1000".to_string());
+    let err = Config::load_from(None, env).unwrap_err();
+    assert!(
+        err.to_string().contains("runtime.cpu_clock_percent = 1000"),
+        "{err}"
+    );
+}
@@ -0,0 +1,119 @@
+//! Instruction-count time source: OSGetTime waits are deterministic
+
+use gcrecomp_core::config::Config;
+use gcrecomp_core::recompiler::analysis::FunctionMetadata;
+use gcrecomp_core::recompiler::codegen::CodeGenerator;
+use gcrecomp_core::recompiler::decoder::Instruction;
+use gcrecomp_core::runtime::clock;
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::{interpret_function, set_call_dispatcher};
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::sdk::timer::{OsTimer, TimeSource};
+use std::sync::Mutex;
+
+const OS_GET_TIME: u32 = 0x8000_1000;
+const WAIT: u32 = 0x8000_3000;
+
+static TIMER: Mutex<Option<OsTimer>> = Mutex::new(None);
+
+/// `OSGetTime` as an HLE call; nothing else is called.
+fn dispatch(
+    address: u32,
+    ctx: &mut CpuContext,
+    _memory: &mut MemoryManager,
+) -> anyhow::Result<Option<u32>> {
+    assert_eq!(address, OS_GET_TIME);
+    let time = TIMER.lock().unwrap().as_ref().unwrap().get_time();
+    ctx.set_register(4, time as u32);
+    Ok(Some((time >> 32) as u32))
+}
+
+/// Spin until 1000 ticks have passed since entry. Returns the elapsed ticks.
+fn busy_wait_on_time() -> (u64, u32) {
+    let words = [
+        0x7FE8_02A6, // mflr r31
+        0x4BFF_DFFD, // bl OSGetTime
+        0x7C9E_2378, // mr r30, r4
+        0x4BFF_DFF5, // loop: bl OSGetTime
+        0x7CBE_2050, // subf r5, r30, r4
+        0x2805_03E8, // cmplwi r5, 1000
+        0x4180_FFF4, // blt loop
+        0x7FE8_03A6, // mtlr r31
+        0x7CA3_2B78, // mr r3, r5
+        0x4E80_0020, // blr
+    ];
+    let mut memory = MemoryManager::new();
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(WAIT + i as u32 * 4, *w).unwrap();
+    }
+    *TIMER.lock().unwrap() = Some(OsTimer::instruction_clock(0x1234_5678, 1));
+
+    let before = clock::instructions();
+    let elapsed = interpret_function(WAIT, &mut CpuContext::new(), &mut memory)
+        .unwrap()
+        .unwrap();
+    (clock::instructions() - before, elapsed)
+}
+
+#[test]
+fn busy_wait_on_instruction_clock_is_deterministic() {
+    set_call_dispatcher(dispatch);
+
+    let (first_count, first_elapsed) = busy_wait_on_time();
+    let (second_count, second_elapsed) = busy_wait_on_time();
+    assert!(first_elapsed >= 1000, "waited only {first_elapsed} ticks");
+    assert_eq!(first_count, second_count, "same instructions every run");
+    assert_eq!(first_elapsed, second_elapsed);
+    // 4 instructions per iteration at 1 tick each, plus the setup.
+    assert!(first_count < 1100, "{first_count} instructions");
+
+    // Time is retired instructions times the configured ticks.
+    let timer = OsTimer::instruction_clock(0, 2);
+    let start = timer.get_time();
+    clock::retire(10);
+    assert_eq!(timer.get_time() - start, 20);
+}
+
+#[test]
+fn generated_blocks_retire_their_length() {
+    // addi r3,r3,1 ; cmpwi r3,10 ; blt -8 ; blr
+    let words = [0x3863_0001, 0x2C03_000A, 0x4180_FFF8, 0x4E80_0020];
+    let instrs: Vec<_> = words
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| Instruction::decode(w, 0x8000_4000 + i as u32 * 4).unwrap())
+        .collect();
+    let md = FunctionMetadata {
+        address: 0x8000_4000,
+        name: "count".to_string(),
+        size: 16,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    let code = CodeGenerator::new()
+        .generate_function(&md, &instrs)
+        .unwrap();
+    assert!(code.contains("clock::retire(3u32)"), "{code}");
+    assert!(code.contains("clock::retire(1u32)"), "{code}");
+}
+
+#[test]
+fn config_selects_the_instruction_clock() {
+    let env =
+        |var: &str| (var == "GCRECOMP_RUNTIME_TIME_SOURCE").then(|| "instructions".to_string());
+    let loaded = Config::load_from(None, env).unwrap();
+    assert_eq!(loaded.config.time_source(), TimeSource::Instructions);
+    assert_eq!(Config::default().time_source(), TimeSource::Wall);
+
+    let env =
+        |var: &str| (var == "GCRECOMP_RUNTIME_TICKS_PER_INSTRUCTION").then(|| "0".to_string());
+    let err = Config::load_from(None, env).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("runtime.ticks_per_instruction = 0"),
+        "{err}"
+    );
+}
@@ -1,9 +1,21 @@
 //! Unit tests for code generation
 
 use gcrecomp_core::recompiler::analysis::FunctionMetadata;
-use gcrecomp_core::recompiler::codegen::CodeGenerator;
-use gcrecomp_core::recompiler::decoder::{DecodedInstruction, Instruction, InstructionType};
+use gcrecomp_core::recompiler::codegen::sda::SdaBases;
+use gcrecomp_core::recompiler::codegen::{extend_immediate, CodeGenerator};
+use gcrecomp_core::recompiler::decoder::{
+    DecodedInstruction, Instruction, InstructionType, Operand,
+};
+use gcrecomp_core::recompiler::enrich;
+use gcrecomp_core::recompiler::error::RecompileError;
+use gcrecomp_core::recompiler::optimizer::{OptLevel, Optimizer};
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
 use smallvec::SmallVec;
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 fn _create_test_instruction(opcode: u32, inst_type: InstructionType) -> DecodedInstruction {
     DecodedInstruction {
@@ -65,34 +77,52 @@ fn test_generate_function_empty() {
 
 /// Generate a full function from raw PowerPC instruction words.
 fn gen(words: &[u32]) -> String {
-    let mut cg = CodeGenerator::new();
-    let instrs: Vec<DecodedInstruction> = words
+    gen_with(CodeGenerator::new(), words)
+}
+
+fn gen_with(cg: CodeGenerator, words: &[u32]) -> String {
+    gen_decoded(cg, &decode(words))
+}
+
+fn decode(words: &[u32]) -> Vec<DecodedInstruction> {
+    words
         .iter()
         .enumerate()
         .map(|(i, &w)| Instruction::decode(w, 0x8000_3000 + (i as u32) * 4).unwrap())
-        .collect();
+        .collect()
+}
+
+fn gen_decoded(mut cg: CodeGenerator, instrs: &[DecodedInstruction]) -> String {
     let md = FunctionMetadata {
         address: 0x8000_3000,
         name: "f".to_string(),
-        size: (words.len() * 4) as u32,
+        size: instrs
+            .iter()
+            .map(|i| i.address + 4 - 0x8000_3000)
+            .max()
+            .unwrap_or(0),
         calling_convention: "default".to_string(),
         parameters: vec![],
         return_type: None,
         local_variables: vec![],
         basic_blocks: vec![],
     };
-    cg.generate_function(&md, &instrs).unwrap()
+    cg.generate_function(&md, instrs).unwrap()
 }
 
 #[test]
 fn test_bl_generates_real_call() {
     // bl +0x10 ; blr  — opcode 18 with LK set must dispatch a real call, not a stub.
-    let code = gen(&[0x4800_0011, 0x4E80_0020]);
+    let words = [0x4800_0011, 0x4E80_0020];
+    let code = gen_with(CodeGenerator::new().with_tail_calls(false), &words);
     assert!(
         code.contains("call_function_by_address"),
         "bl must emit a dispatcher call:\n{code}"
     );
     assert!(!code.contains("untranslated"), "no stubs:\n{code}");
+    // By default `bl` straight into `blr` is a tail call.
+    let code = gen(&words);
+    assert!(code.contains("tail_call(0x80003010u32)"), "{code}");
 }
 
 #[test]
@@ -134,11 +164,311 @@ fn test_bctr_dispatches_via_ctr() {
 fn test_state_machine_has_loop_and_match() {
     // A backward conditional branch should produce a block state machine.
     // addi r3,r3,1 ; bdnz back ; blr
-    let code = gen(&[0x3863_0001, 0x4200_FFFC, 0x4E80_0020]);
+    let code = gen_with(
+        CodeGenerator::new().with_structured(false),
+        &[0x3863_0001, 0x4200_FFFC, 0x4E80_0020],
+    );
+    assert!(code.contains("loop {"), "function body is a loop:\n{code}");
+    assert!(code.contains("match __blk"), "block dispatch:\n{code}");
+}
+
+#[test]
+fn test_irreducible_graph_falls_back_to_state_machine() {
+    // A jump into the middle of a loop can't be nested, so the blocks go
+    // through a state machine.
+    let code = gen(&[
+        0x2C03_0000, // cmpwi r3,0
+        0x4182_000C, // beq 0x80003010 (into the loop)
+        0x3863_0001, // addi r3,r3,1 (loop head)
+        0x3884_0001, // addi r4,r4,1
+        0x38A5_0001, // addi r5,r5,1
+        0x4200_FFF4, // bdnz 0x80003008
+        0x4E80_0020, // blr
+    ]);
     assert!(code.contains("loop {"), "function body is a loop:\n{code}");
     assert!(code.contains("match __blk"), "block dispatch:\n{code}");
 }
 
+/// `--extern` for the newest build of `krate` next to this test.
+fn rlib(deps: &Path, krate: &str) -> String {
+    let prefix = format!("lib{krate}-");
+    let path = std::fs::read_dir(deps)
+        .unwrap_or_else(|e| panic!("reading {}: {e}", deps.display()))
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy())
+                .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(".rlib"))
+        })
+        .max_by_key(|p| p.metadata().and_then(|m| m.modified()).ok())
+        .unwrap_or_else(|| panic!("no {prefix}*.rlib in {}", deps.display()));
+    format!("{krate}={}", path.display())
+}
+
+/// Compile `code` (one generated function named `f_80003000`) into a program
+/// and return its r3 for each input r3. Unreachable code is an error, as the
+/// generated crate should have none.
+fn run_compiled(code: &str, inputs: &[u32]) -> Vec<u32> {
+    static PROGRAMS: AtomicUsize = AtomicUsize::new(0);
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let dir = std::env::temp_dir().join(format!("gcrecomp_structured_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join(format!("f{}", PROGRAMS.fetch_add(1, Ordering::Relaxed)));
+    let source = program.with_extension("rs");
+    std::fs::write(
+        &source,
+        format!(
+            r#"
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use anyhow::Result;
+
+fn call_function_by_address(
+    _address: u32,
+    _ctx: &mut CpuContext,
+    _memory: &mut MemoryManager,
+) -> Result<Option<u32>> {{
+    Ok(None)
+}}
+
+{code}
+fn main() {{
+    for arg in std::env::args().skip(1) {{
+        let mut ctx = CpuContext::new();
+        let mut memory = MemoryManager::new();
+        ctx.gpr[3] = arg.parse().unwrap();
+        println!("{{}}", f_80003000(&mut ctx, &mut memory).unwrap().unwrap());
+    }}
+}}
+"#
+        ),
+    )
+    .unwrap();
+    let output = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into()))
+        .args([
+            "--edition",
+            "2021",
+            "-A",
+            "warnings",
+            "-D",
+            "unreachable_code",
+            "-o",
+        ])
+        .arg(&program)
+        .arg(&source)
+        .arg("--extern")
+        .arg(rlib(deps, "gcrecomp_core"))
+        .arg("--extern")
+        .arg(rlib(deps, "anyhow"))
+        .arg("-L")
+        .arg(format!("dependency={}", deps.display()))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}\n{code}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = Command::new(&program)
+        .args(inputs.iter().map(u32::to_string))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| l.parse().unwrap())
+        .collect()
+}
+
+/// r3 after interpreting `words` from 0x80003000 with each input r3.
+fn run_interpreted(words: &[u32], inputs: &[u32]) -> Vec<u32> {
+    inputs
+        .iter()
+        .map(|&r3| {
+            let mut ctx = CpuContext::new();
+            let mut memory = MemoryManager::new();
+            for (i, w) in words.iter().enumerate() {
+                memory.write_u32(0x8000_3000 + i as u32 * 4, *w).unwrap();
+            }
+            ctx.gpr[3] = r3;
+            interpret_function(0x8000_3000, &mut ctx, &mut memory).unwrap();
+            ctx.gpr[3]
+        })
+        .collect()
+}
+
+#[test]
+fn test_if_else_diamond_is_structured() {
+    let words = [
+        0x2C03_0000, // cmpwi r3,0
+        0x4080_000C, // bge 0x80003010
+        0x2063_0000, // subfic r3,r3,0
+        0x4800_0008, // b 0x80003014
+        0x3863_0064, // addi r3,r3,100
+        0x3863_0001, // addi r3,r3,1
+        0x4E80_0020, // blr
+    ];
+    let code = gen(&words);
+    assert!(!code.contains("__blk"), "no state machine:\n{code}");
+    assert!(
+        code.contains("break '__b"),
+        "arms leave labeled blocks:\n{code}"
+    );
+    let inputs = [5, (-7i32) as u32, 0];
+    let results = run_compiled(&code, &inputs);
+    assert_eq!(results, [106, 8, 101]);
+    assert_eq!(results, run_interpreted(&words, &inputs));
+}
+
+#[test]
+fn test_while_loop_is_structured() {
+    let words = [
+        0x3883_0000, // addi r4,r3,0
+        0x2C03_0000, // cmpwi r3,0 (loop head)
+        0x4182_0010, // beq 0x80003018
+        0x3884_0002, // addi r4,r4,2
+        0x3863_FFFF, // addi r3,r3,-1
+        0x4BFF_FFF0, // b 0x80003004
+        0x3864_0000, // addi r3,r4,0
+        0x4E80_0020, // blr
+    ];
+    let code = gen(&words);
+    assert!(!code.contains("__blk"), "no state machine:\n{code}");
+    assert!(code.contains("'__l1: loop {"), "{code}");
+    assert!(code.contains("continue '__l1;"), "{code}");
+    // The loop's last block continues, so nothing follows it.
+    assert!(!code.contains("break '__l1"), "{code}");
+    assert!(
+        !code.contains(")))\n    Ok(Some("),
+        "no tail after the final return:\n{code}"
+    );
+    let inputs = [0, 1, 7];
+    let results = run_compiled(&code, &inputs);
+    assert_eq!(results, [0, 3, 21]);
+    assert_eq!(results, run_interpreted(&words, &inputs));
+}
+
+#[test]
+fn test_struct_field_accesses_are_annotated() {
+    // A pointer in r3 read at +0, +4 and +8: a three-field struct.
+    let words = [
+        0x8083_0000, // lwz r4,0(r3)
+        0xA0A3_0004, // lhz r5,4(r3)
+        0x9083_0008, // stw r4,8(r3)
+        0x4E80_0020, // blr
+    ];
+    let code = gen(&words);
+    for field in ["field_00", "field_04", "field_08"] {
+        assert!(
+            code.contains(&format!("i32 as u32; // struct_80003000_r3.{field}\n")),
+            "{field}:\n{code}"
+        );
+    }
+    assert!(
+        !code.contains("struct_80003000_r3::"),
+        "raw access by default:\n{code}"
+    );
+
+    // An unrelated single access stays unannotated.
+    let code = gen(&[0x8083_0000, 0x4E80_0020]);
+    assert!(!code.contains("struct_"), "{code}");
+}
+
+#[test]
+fn test_struct_accessors_compile_and_round_trip() {
+    let words = [
+        0x9063_0000, // stw r3,0(r3)
+        0x8083_0000, // lwz r4,0(r3)
+        0x9083_0008, // stw r4,8(r3)
+        0x8063_0008, // lwz r3,8(r3)
+        0x4E80_0020, // blr
+    ];
+    let code = gen_with(CodeGenerator::new().with_struct_accessors(true), &words);
+    assert!(code.contains("pub struct struct_80003000_r3 {"), "{code}");
+    // Only the module is exempt from dead-code warnings, not each item.
+    assert_eq!(code.matches("#[allow(dead_code)]").count(), 1, "{code}");
+    assert!(
+        code.contains("#[allow(dead_code)]\nmod structs {"),
+        "{code}"
+    );
+    assert!(
+        code.contains(
+            "structs::struct_80003000_r3::set_field_08(memory, ctx.get_register(3), ctx.get_register(4))"
+        ),
+        "{code}"
+    );
+    assert!(
+        code.contains("structs::struct_80003000_r3::get_field_00(memory, ctx.get_register(3))"),
+        "{code}"
+    );
+    let inputs = [0x8000_1000, 0x8000_2000];
+    let results = run_compiled(&code, &inputs);
+    assert_eq!(results, inputs);
+    assert_eq!(results, run_interpreted(&words, &inputs));
+}
+
+#[test]
+fn test_back_edges_count_against_loop_budget() {
+    // addi r3,r3,1 ; bdnz back ; blr — the bdnz jumps back to 0x80003000.
+    let code = gen(&[0x3863_0001, 0x4200_FFFC, 0x4E80_0020]);
+    assert!(
+        code.contains("watchdog::back_edge(&mut __steps, 0x80003000u32)?"),
+        "back-edge must be budgeted:\n{code}"
+    );
+    // Straight-line code has no counter at all.
+    let code = gen(&[0x3863_0001, 0x4E80_0020]);
+    assert!(!code.contains("__steps"), "{code}");
+}
+
+#[test]
+fn test_sda_relative_addi_folds_to_global_address() {
+    let bases = SdaBases {
+        sda: 0x8040_0000,
+        sda2: 0x8041_0000,
+    };
+    // addi r3,r13,0x20 ; addi r4,r2,-0x10 ; blr
+    let words = [0x386D_0020, 0x3882_FFF0, 0x4E80_0020];
+    let code = gen_with(CodeGenerator::new().with_sda_bases(Some(bases)), &words);
+    assert!(
+        code.contains("ctx.set_register(3, 0x80400020u32); // small-data global: r13 + 0x20"),
+        "{code}"
+    );
+    assert!(
+        code.contains("ctx.set_register(4, 0x8040FFF0u32); // small-data global: r2 - 0x10"),
+        "{code}"
+    );
+
+    // Without configured bases r13 stays a runtime value.
+    let code = gen(&words);
+    assert!(code.contains("ctx.get_register(13).wrapping_add"), "{code}");
+
+    // A function that sets r13 itself (lis r13,0x8050 ; addi r3,r13,0x20) is
+    // not seeded.
+    let code = gen_with(
+        CodeGenerator::new().with_sda_bases(Some(bases)),
+        &[0x3DA0_8050, 0x386D_0020, 0x4E80_0020],
+    );
+    assert!(!code.contains("small-data global"), "{code}");
+}
+
+#[test]
+fn test_blocks_have_breakpoint_hooks() {
+    // addi r3,r3,1 ; bdnz back ; blr — the loop body and the exit are leaders.
+    let code = gen(&[0x3863_0001, 0x4200_FFFC, 0x4E80_0020]);
+    for leader in ["0x80003000u32", "0x80003008u32"] {
+        assert!(
+            code.contains(&format!("debug::check_breakpoint({leader}, ctx, memory)")),
+            "missing breakpoint hook at {leader}:\n{code}"
+        );
+    }
+}
+
 #[test]
 fn test_cntlzw_translates() {
     // cntlzw r0, r3 ; blr — was mistranslated as an add, hanging the boot.
@@ -164,6 +494,18 @@ fn test_mtctr_sets_ctr() {
     );
 }
 
+#[test]
+fn test_icbi_invalidates_code() {
+    // icbi r3,r4 ; blr — 982 was missing (470, dcbi, was decoded as icbi).
+    let code = gen(&[0x7C03_27AC, 0x4E80_0020]);
+    assert!(
+        code.contains(
+            "memory.invalidate_code((ctx.get_register(3).wrapping_add(ctx.get_register(4))) & !31, 32)"
+        ),
+        "icbi must invalidate the line:\n{code}"
+    );
+}
+
 #[test]
 fn test_sanitize_identifier() {
     let codegen = CodeGenerator::new();
@@ -185,3 +527,510 @@ fn test_sanitize_identifier() {
         "test_function"
     );
 }
+
+#[test]
+fn test_strict_mode_reports_unsupported_instruction() {
+    // li r3,0 ; <primary opcode 5, undefined on Gekko> ; blr
+    let words = [0x3860_0000u32, 0x1400_0000, 0x4E80_0020];
+    let instrs: Vec<DecodedInstruction> = words
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| Instruction::decode(w, 0x8000_3000 + (i as u32) * 4).unwrap())
+        .collect();
+    let md = FunctionMetadata {
+        address: 0x8000_3000,
+        name: "f".to_string(),
+        size: 12,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+
+    let err = CodeGenerator::new()
+        .with_strict(true)
+        .generate_function(&md, &instrs)
+        .unwrap_err();
+    match err {
+        RecompileError::UnsupportedInstruction { address, opcode } => {
+            assert_eq!(address, 0x8000_3004);
+            assert_eq!(opcode, 0x1400_0000);
+        }
+        other => panic!("expected UnsupportedInstruction, got {other:?}"),
+    }
+
+    // The default stays lenient and comments the instruction out.
+    let code = CodeGenerator::new()
+        .generate_function(&md, &instrs)
+        .unwrap();
+    assert!(code.contains("untranslated 0x14000000"), "{code}");
+}
+
+#[test]
+fn test_leaf_with_a_stack_frame_is_inlined_with_rebased_offsets() {
+    use gcrecomp_core::recompiler::codegen::inline::{inline_body, INLINE_BUDGET};
+
+    let decode = |words: &[u32], base: u32| -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, base + (i as u32) * 4).unwrap())
+            .collect()
+    };
+    // stwu r1,-16(r1) ; stw r3,8(r1) ; lwz r4,8(r1) ; addi r3,r4,1 ; addi r1,r1,16 ; blr
+    let framed = decode(
+        &[
+            0x9421_FFF0,
+            0x9061_0008,
+            0x8081_0008,
+            0x3864_0001,
+            0x3821_0010,
+            0x4E80_0020,
+        ],
+        0x8000_3020,
+    );
+    let body = inline_body(&framed, INLINE_BUDGET).unwrap();
+    let raws: Vec<u32> = body.iter().map(|i| i.raw).collect();
+    // stw r1,-16(r1) ; stw r3,-8(r1) ; lwz r4,-8(r1) ; addi r3,r4,1: r1 never moves.
+    assert_eq!(
+        raws,
+        vec![0x9021_FFF0, 0x9061_FFF8, 0x8081_FFF8, 0x3864_0001]
+    );
+
+    // The frame's size has to match on the way out.
+    let mut unbalanced = framed.clone();
+    unbalanced[4] = Instruction::decode(0x3821_0020, 0x8000_3030).unwrap();
+    assert!(inline_body(&unbalanced, INLINE_BUDGET).is_none());
+    // r1 as a value (mr r5,r1) would be off by the frame size.
+    let mut escapes = framed.clone();
+    escapes[3] = Instruction::decode(0x7C25_0B78, 0x8000_302C).unwrap();
+    assert!(inline_body(&escapes, INLINE_BUDGET).is_none());
+    // A function calling itself has a branch, so it is never a candidate.
+    let recursive = decode(&[0x4800_0001, 0x4E80_0020], 0x8000_3040);
+    assert!(inline_body(&recursive, INLINE_BUDGET).is_none());
+
+    let md = FunctionMetadata {
+        address: 0x8000_3000,
+        name: "caller".to_string(),
+        size: 8,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    // caller: bl framed ; blr
+    let caller = decode(&[0x4800_0021, 0x4E80_0020], 0x8000_3000);
+    let mut codegen = CodeGenerator::new();
+    codegen.set_inline_candidates([(0x8000_3020, body)].into_iter().collect());
+    let code = codegen.generate_function(&md, &caller).unwrap();
+    assert!(code.contains("// inlined 0x80003020"), "{code}");
+    assert!(
+        !code.contains("call_function_by_address(0x80003020u32"),
+        "{code}"
+    );
+}
+
+#[test]
+fn test_leaf_adder_is_inlined_into_caller() {
+    use gcrecomp_core::recompiler::ghidra::FunctionInfo;
+    use gcrecomp_core::recompiler::pipeline::RecompilationPipeline;
+
+    let decode = |words: &[u32], base: u32| -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, base + (i as u32) * 4).unwrap())
+            .collect()
+    };
+    let func = |address: u32, size: u32| FunctionInfo {
+        address,
+        name: format!("sub_{address:08x}"),
+        size,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    // caller: li r3,1 ; li r4,2 ; bl adder ; bl 0x80003100 ; blr
+    let caller = decode(
+        &[
+            0x3860_0001,
+            0x3880_0002,
+            0x4800_0019,
+            0x4800_00F5,
+            0x4E80_0020,
+        ],
+        0x8000_3000,
+    );
+    // adder: add r3,r3,r4 ; blr
+    let adder = decode(&[0x7C63_2214, 0x4E80_0020], 0x8000_3020);
+    // Too big for the budget: eight nops (ori r0,r0,0) and two adds.
+    let mut big_words = vec![0x6000_0000; 8];
+    big_words.extend([0x7C63_2214, 0x7C63_2214, 0x4E80_0020]);
+    let big = decode(&big_words, 0x8000_3100);
+
+    let all: Vec<DecodedInstruction> = [caller.clone(), adder, big].concat();
+    let candidates = RecompilationPipeline::analyze_inlining_candidates(
+        &[
+            func(0x8000_3000, 20),
+            func(0x8000_3020, 8),
+            func(0x8000_3100, 44),
+        ],
+        &all,
+    );
+    assert!(candidates.contains_key(&0x8000_3020));
+    assert!(!candidates.contains_key(&0x8000_3100));
+    // The caller calls out, so it isn't a leaf.
+    assert!(!candidates.contains_key(&0x8000_3000));
+
+    let md = FunctionMetadata {
+        address: 0x8000_3000,
+        name: "caller".to_string(),
+        size: 20,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    let mut codegen = CodeGenerator::new();
+    codegen.set_inline_candidates(candidates);
+    let code = codegen.generate_function(&md, &caller).unwrap();
+    assert!(
+        !code.contains("call_function_by_address(0x80003020u32"),
+        "{code}"
+    );
+    assert!(code.contains("// inlined 0x80003020"), "{code}");
+    // Non-candidates are still dispatched (this one from tail position).
+    assert!(code.contains("tail_call(0x80003100u32)"), "{code}");
+}
+
+/// Mirrors what codegen emits for `is_even` / `is_odd` below: each decrements
+/// r3 and tail-calls the other.
+fn even_odd(
+    address: u32,
+    ctx: &mut CpuContext,
+    _memory: &mut MemoryManager,
+) -> anyhow::Result<Option<u32>> {
+    let n = ctx.get_register(3);
+    let is_even = address == 0x8000_3000;
+    if n == 0 {
+        ctx.set_register(3, is_even as u32);
+        return Ok(Some(ctx.get_register(3)));
+    }
+    ctx.set_register(3, n - 1);
+    gcrecomp_core::runtime::tail_call(if is_even { 0x8000_3020 } else { 0x8000_3000 });
+    Ok(Some(ctx.get_register(3)))
+}
+
+#[test]
+fn test_mutual_recursion_tail_calls_run_on_trampoline() {
+    let decode = |words: &[u32], base: u32| -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, base + (i as u32) * 4).unwrap())
+            .collect()
+    };
+    let md = |address: u32, name: &str| FunctionMetadata {
+        address,
+        name: name.to_string(),
+        size: 24,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    // is_even: cmpwi r3,0 ; bne +0xC ; li r3,1 ; blr ; addi r3,r3,-1 ; b is_odd
+    let even = decode(
+        &[
+            0x2C03_0000,
+            0x4082_000C,
+            0x3860_0001,
+            0x4E80_0020,
+            0x3863_FFFF,
+            0x4800_000C,
+        ],
+        0x8000_3000,
+    );
+    // is_odd: same with li r3,0, ending in b is_even
+    let odd = decode(
+        &[
+            0x2C03_0000,
+            0x4082_000C,
+            0x3860_0000,
+            0x4E80_0020,
+            0x3863_FFFF,
+            0x4BFF_FFCC,
+        ],
+        0x8000_3020,
+    );
+    assert_eq!(enrich::tail_call_sites(&even), vec![0x8000_3014]);
+    assert_eq!(enrich::tail_call_sites(&odd), vec![0x8000_3034]);
+
+    let mut cg = CodeGenerator::new();
+    for (md, body, target) in [
+        (md(0x8000_3000, "is_even"), &even, "0x80003020u32"),
+        (md(0x8000_3020, "is_odd"), &odd, "0x80003000u32"),
+    ] {
+        let code = cg.generate_function(&md, body).unwrap();
+        assert!(code.contains(&format!("tail_call({target})")), "{code}");
+        assert!(!code.contains("call_function_by_address"), "{code}");
+    }
+
+    // A million hops: far past what nested Rust calls fit in a test thread's
+    // stack, constant depth on the trampoline.
+    let mut ctx = CpuContext::new();
+    let mut memory = MemoryManager::new();
+    ctx.set_register(3, 1_000_001);
+    let result =
+        gcrecomp_core::runtime::trampoline(0x8000_3000, &mut ctx, &mut memory, even_odd).unwrap();
+    assert_eq!(result, Some(0)); // 1_000_001 is odd
+}
+
+#[test]
+fn test_divides_never_emit_a_panicking_division() {
+    // divwo r3,r4,r5 ; divwu r7,r4,r5 ; stw r3,0(r6) ; stw r7,4(r6) ; blr
+    let xo = |word: u32| DecodedInstruction {
+        instruction: Instruction {
+            opcode: 31,
+            instruction_type: InstructionType::Arithmetic,
+            operands: SmallVec::from_slice(&[
+                Operand::Register(((word >> 21) & 31) as u8),
+                Operand::Register(((word >> 16) & 31) as u8),
+                Operand::Register(((word >> 11) & 31) as u8),
+            ]),
+        },
+        address: 0,
+        raw: word,
+    };
+    let mut instrs = vec![xo(0x7C64_2FD6), xo(0x7CE4_2B96)];
+    instrs.extend(
+        [0x9066_0000, 0x90E6_0004, 0x4E80_0020]
+            .iter()
+            .map(|&w| Instruction::decode(w, 0).unwrap()),
+    );
+    for (i, inst) in instrs.iter_mut().enumerate() {
+        inst.address = 0x8000_3000 + i as u32 * 4;
+    }
+    let md = FunctionMetadata {
+        address: 0x8000_3000,
+        name: "f".to_string(),
+        size: 20,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    let code = CodeGenerator::new()
+        .generate_function(&md, &instrs)
+        .unwrap();
+    assert!(
+        code.contains(
+            "context::divw(ctx.get_register(4), ctx.get_register(5)); ctx.set_overflow(ov)"
+        ),
+        "divwo is signed and records overflow:\n{code}"
+    );
+    assert!(
+        code.contains("context::divwu(ctx.get_register(4), ctx.get_register(5)).0"),
+        "divwu is unsigned and leaves XER alone:\n{code}"
+    );
+    assert!(!code.contains(" / "), "no raw division:\n{code}");
+}
+
+#[test]
+fn test_immediates_extend_by_opcode_signedness() {
+    // 0x8000 as UI is 32768; as SI it is -32768.
+    assert_eq!(extend_immediate(10, 0x8000u16 as i16), 0x0000_8000); // cmplwi
+    assert_eq!(extend_immediate(11, 0x8000u16 as i16), 0xFFFF_8000); // cmpwi
+    assert_eq!(extend_immediate(14, 0x8000u16 as i16), 0xFFFF_8000); // addi
+    assert_eq!(extend_immediate(15, 0x8000u16 as i16), 0x8000_0000); // addis
+    assert_eq!(extend_immediate(24, 0x8000u16 as i16), 0x0000_8000); // ori
+    assert_eq!(extend_immediate(28, 0xFFFFu16 as i16), 0x0000_FFFF); // andi.
+    assert_eq!(extend_immediate(29, 0xFFFFu16 as i16), 0xFFFF_0000); // andis.
+
+    // A register holding 1 compared against 0x8000: cmplwi says less
+    // (1 < 32768) and cmpwi says greater (1 > -32768).
+    let mut instrs: Vec<_> = [
+        0x2803_8000, // cmplwi cr0, r3, 0x8000
+        0x2C83_8000, // cmpwi  cr1, r3, 0x8000
+        0x6064_8000, // ori    r4, r3, 0x8000
+        0x7C03_2040, // cmplw  cr0, r3, r4
+        0x4E80_0020, // blr
+    ]
+    .iter()
+    .map(|&w| Instruction::decode(w, 0).unwrap())
+    .collect();
+    for (i, inst) in instrs.iter_mut().enumerate() {
+        inst.address = 0x8000_4000 + i as u32 * 4;
+    }
+    let md = FunctionMetadata {
+        address: 0x8000_4000,
+        name: "f".to_string(),
+        size: 20,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    let code = CodeGenerator::new()
+        .generate_function(&md, &instrs)
+        .unwrap();
+    assert!(
+        code.contains("let rb_val = 32768u32 as u32;"),
+        "cmplwi zero-extends its immediate:\n{code}"
+    );
+    assert!(
+        code.contains("let rb_val = -32768i32 as i32;"),
+        "cmpwi sign-extends its immediate:\n{code}"
+    );
+    assert!(
+        code.contains("ctx.set_register(4, ctx.get_register(3) | 32768u32);"),
+        "ori writes RA from RS with a zero-extended immediate:\n{code}"
+    );
+    assert!(
+        code.contains("let rb_val = ctx.get_register(4) as u32;"),
+        "cmplw compares unsigned:\n{code}"
+    );
+
+    // The comparisons the emitted code performs.
+    let r3 = 1u32;
+    assert!(r3 < extend_immediate(10, 0x8000u16 as i16));
+    assert!(r3 as i32 > extend_immediate(11, 0x8000u16 as i16) as i32);
+    // And 0x8000 itself is greater than 0 unsigned but negative signed.
+    assert!(extend_immediate(10, 0x8000u16 as i16) > 0);
+    assert!((extend_immediate(11, 0x8000u16 as i16) as i32) < 0);
+}
+
+#[test]
+fn test_constant_argument_call_is_specialized() {
+    use gcrecomp_core::recompiler::ghidra::FunctionInfo;
+    use gcrecomp_core::recompiler::pipeline::RecompilationPipeline;
+    use std::collections::{HashMap, HashSet};
+
+    let decode = |words: &[u32], base: u32| -> Vec<DecodedInstruction> {
+        words
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, base + (i as u32) * 4).unwrap())
+            .collect()
+    };
+    let func = |address: u32, size: u32| FunctionInfo {
+        address,
+        name: format!("sub_{address:08x}"),
+        size,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    // caller: li r4,5 ; bl add ; mr r4,r31 ; bl add ; nop ; blr
+    let caller = decode(
+        &[
+            0x3880_0005,
+            0x4800_00FD,
+            0x7FE4_FB78,
+            0x4800_00F5,
+            0x6000_0000,
+            0x4E80_0020,
+        ],
+        0x8000_3000,
+    );
+    // add: add r3,r3,r4 ; blr
+    let add = decode(&[0x7C63_2214, 0x4E80_0020], 0x8000_3100);
+    let functions = [func(0x8000_3000, 24), func(0x8000_3100, 8)];
+    let all: Vec<DecodedInstruction> = [caller.clone(), add].concat();
+
+    let specs = RecompilationPipeline::analyze_specializations(&functions, &all, &HashSet::new());
+    assert_eq!(specs.len(), 1);
+    let spec = &specs[0];
+    assert_eq!((spec.callee, spec.index), (0x8000_3100, 0));
+    assert_eq!(spec.arguments, vec![(4, 5)]);
+    assert_eq!(spec.call_sites, vec![0x8000_3004]);
+    // add r3,r3,r4 became addi r3,r3,5.
+    assert_eq!(spec.body[0].raw, 0x3863_0005);
+
+    let mut codegen = CodeGenerator::new();
+    let md = |address: u32, size: u32| FunctionMetadata {
+        address,
+        name: format!("sub_{address:08x}"),
+        size,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    let copy = codegen
+        .generate_specialized_function(&md(0x8000_3100, 8), 0, &spec.body)
+        .unwrap();
+    assert!(copy.starts_with("pub fn func_0x80003100_spec0("), "{copy}");
+    assert!(
+        copy.contains("get_register(3).wrapping_add(5u32)"),
+        "{copy}"
+    );
+    assert!(!copy.contains("get_register(4)"), "{copy}");
+
+    codegen.set_specialized_calls(HashMap::from([(
+        0x8000_3004,
+        codegen.specialized_identifier(&md(0x8000_3100, 8), 0),
+    )]));
+    let code = codegen
+        .generate_function(&md(0x8000_3000, 24), &caller)
+        .unwrap();
+    assert!(
+        code.contains("func_0x80003100_spec0(ctx, memory)"),
+        "{code}"
+    );
+    // The call with an unknown r4 still goes through the dispatcher.
+    assert!(
+        code.contains("call_function_by_address(0x80003100u32"),
+        "{code}"
+    );
+}
+
+/// The `clock::retire` counts in `code`, one per block in order.
+fn retire_counts(code: &str) -> Vec<u64> {
+    code.match_indices("clock::retire(")
+        .map(|(at, call)| {
+            let digits = &code[at + call.len()..];
+            digits[..digits.find('u').unwrap()].parse().unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_unrolled_loop_retires_as_many_instructions() {
+    // li r0,8 ; mtctr r0 ; L: addi r3,r3,1 ; addi r4,r4,2 ; bdnz L ; blr
+    let words = [
+        0x3800_0008,
+        0x7C09_03A6,
+        0x3863_0001,
+        0x3884_0002,
+        0x4200_FFF8,
+        0x4E80_0020,
+    ];
+    let mut unrolled = Optimizer::with_level(OptLevel::None);
+    unrolled.set_pass("loop_unrolling", true);
+    let instructions = unrolled.optimize(&decode(&words));
+    assert_eq!(unrolled.stats().unrolled_loops, 1);
+    // li r0,2: the loop now runs twice.
+    assert_eq!(instructions[0].raw, 0x3800_0002);
+
+    // Entry block once, the loop block per trip, the return once.
+    let cycles = |code: &str, trips: u64| match retire_counts(code)[..] {
+        [entry, body, exit] => entry + body * trips + exit,
+        ref counts => panic!("{counts:?}\n{code}"),
+    };
+    let plain = gen(&words);
+    let code = gen_decoded(CodeGenerator::new(), &instructions);
+    assert_eq!(retire_counts(&code)[1], 12, "{code}");
+    assert_eq!(cycles(&code, 2), cycles(&plain, 8));
+}
@@ -0,0 +1,110 @@
+//! Differential harness: our interpreter against Dolphin register logs
+//!
+//! Runs a function on the interpreter with tracing on and aligns the trace
+//! with a Dolphin log of the same function (`runtime::comparison`). The logs
+//! next to this file are synthetic but in the format Dolphin's debugger
+//! scripting writes.
+
+use gcrecomp_core::runtime::comparison::{
+    align, compare_execution_results, compare_memory, compare_memory_region, Divergence,
+    DolphinLog, FLOAT_TOLERANCE,
+};
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::trace::{self, RegisterState};
+
+const ENTRY: u32 = 0x8000_3000;
+
+/// Interpret the sample function and compare the run against `log`.
+fn run_against(log: &str) -> Result<(), Divergence> {
+    let words = [
+        0x3860_0005, // li r3, 5
+        0x3880_0007, // li r4, 7
+        0x7CA3_2214, // add r5, r3, r4
+        0x90A6_0000, // stw r5, 0(r6)
+        0x4E80_0020, // blr
+    ];
+    let mut memory = MemoryManager::new();
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(ENTRY + i as u32 * 4, *w).unwrap();
+    }
+    let mut ctx = CpuContext::new();
+    ctx.gpr[6] = 0x8000_4000;
+    // Dolphin prints 0.300000; the tolerance has to absorb the rounding.
+    ctx.fpr[1] = 0.1 + 0.2;
+    let initial = RegisterState::from_context(&ctx);
+
+    trace::start();
+    interpret_function(ENTRY, &mut ctx, &mut memory).unwrap();
+    let tracer = trace::stop().expect("trace was running");
+
+    let log = DolphinLog::parse(log).unwrap();
+    align(&log, &initial, tracer.entries(), Some(&memory))
+}
+
+#[test]
+fn matching_log_has_no_divergence() {
+    if let Err(divergence) = run_against(include_str!("matching.log")) {
+        panic!("{divergence}");
+    }
+}
+
+#[test]
+fn diverging_log_reports_the_first_mismatch_with_context() {
+    let divergence = run_against(include_str!("diverging.log")).unwrap_err();
+    assert_eq!(divergence.step, Some(3));
+    assert_eq!(divergence.pc, 0x8000_300C);
+    assert_eq!(
+        divergence.differences,
+        ["r5: expected 0x0000000D, got 0x0000000C"]
+    );
+    assert_eq!(divergence.context.len(), 3);
+    assert!(
+        divergence.context[2].starts_with("80003008"),
+        "{divergence}"
+    );
+    assert!(divergence.context[2].contains("add"), "{divergence}");
+}
+
+#[test]
+fn floats_outside_the_tolerance_diverge() {
+    let off = 0.3 + 10.0 * FLOAT_TOLERANCE;
+    let log = format!("PC=80003000 f1={off}\n");
+    let divergence = run_against(&log).unwrap_err();
+    assert_eq!(divergence.step, Some(0));
+    assert!(divergence.differences[0].starts_with("f1:"), "{divergence}");
+}
+
+#[test]
+fn xer_and_fpscr_are_compared() {
+    let log = DolphinLog::parse("PC=80003000 XER=20000000 FPSCR=0x00000004\n").unwrap();
+    let mut state = RegisterState {
+        xer: 0x2000_0000,
+        fpscr: 4,
+        ..Default::default()
+    };
+    assert!(compare_execution_results(&log.steps[0], &state).is_empty());
+    state.fpscr = 0;
+    assert_eq!(
+        compare_execution_results(&log.steps[0], &state),
+        ["fpscr: expected 0x00000004, got 0x00000000"]
+    );
+}
+
+#[test]
+fn every_memory_region_is_compared() {
+    let mut expected = MemoryManager::new();
+    let mut actual = MemoryManager::new();
+    for memory in [&mut expected, &mut actual] {
+        memory.write_u32(0x8000_4000, 0x1234_5678).unwrap();
+        memory.write_u32(0x8010_0000, 0xCAFE_0000).unwrap();
+    }
+    actual.write_u8(0x8010_0003, 0x01).unwrap();
+    let regions = [(0x8000_4000, 4), (0x8010_0000, 4)];
+    assert_eq!(
+        compare_memory(&expected, &actual, &regions),
+        ["[0x80100003]: expected 0x00, got 0x01"]
+    );
+    assert!(compare_memory_region(&expected, &actual, 0x8000_4000, 4).is_empty());
+}
@@ -0,0 +1,89 @@
+//! Config file loading and GCRECOMP_* overrides
+
+use gcrecomp_core::config::{Config, ValueSource};
+use std::path::PathBuf;
+
+fn write_config(name: &str, text: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_config_{}_{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    std::fs::write(&path, text).unwrap();
+    path
+}
+
+#[test]
+fn test_env_overrides_file_value() {
+    let path = write_config(
+        "override",
+        "[audio]\nlatency_ms = 60\n\n[graphics]\nbackend = \"vulkan\"\n",
+    );
+    let env = |var: &str| (var == "GCRECOMP_AUDIO_LATENCY_MS").then(|| "120".to_string());
+    let loaded = Config::load_from(Some(&path), env).unwrap();
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+
+    assert_eq!(loaded.config.audio.latency_ms, 120);
+    assert_eq!(
+        loaded.source("audio.latency_ms"),
+        &ValueSource::Env("GCRECOMP_AUDIO_LATENCY_MS".to_string())
+    );
+    assert_eq!(loaded.config.graphics.backend, "vulkan");
+    assert_eq!(loaded.source("graphics.backend"), &ValueSource::File(path));
+    assert_eq!(loaded.source("input.profile"), &ValueSource::Default);
+    assert!(loaded
+        .describe()
+        .contains("audio.latency_ms = 120 (GCRECOMP_AUDIO_LATENCY_MS)"));
+}
+
+#[test]
+fn test_out_of_range_audio_latency_is_rejected() {
+    let path = write_config("range", "[audio]\nlatency_ms = 2000\n");
+    let err = Config::load_from(Some(&path), |_| None).unwrap_err();
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+
+    let msg = err.to_string();
+    assert!(
+        msg.contains("audio.latency_ms = 2000")
+            && msg.contains(&path.display().to_string())
+            && msg.contains("out of range; expected 5-500 ms"),
+        "{msg}"
+    );
+
+    let env = |var: &str| (var == "GCRECOMP_AUDIO_LATENCY_MS").then(|| "1".to_string());
+    let err = Config::load_from(None, env).unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("audio.latency_ms = 1 (from GCRECOMP_AUDIO_LATENCY_MS) is out of range"),
+        "{err}"
+    );
+}
+
+#[test]
+fn test_save_updates_file_in_place_and_reloads() {
+    let path = write_config(
+        "save",
+        "# my settings\n[audio]\nlatency_ms = 60 # tuned for bluetooth\n\n[custom]\nkept = true\n",
+    );
+    let mut config = Config::load_from(Some(&path), |_| None).unwrap().config;
+    config.audio.master_volume = 35;
+    config.graphics.resolution_scale = 3;
+    config.audio.latency_ms = 80;
+    config.save(&path).unwrap();
+
+    let text = std::fs::read_to_string(&path).unwrap();
+    assert!(text.starts_with("# my settings\n"), "{text}");
+    assert!(
+        text.contains("latency_ms = 80 # tuned for bluetooth"),
+        "{text}"
+    );
+    assert!(text.contains("master_volume = 35"), "{text}");
+    assert!(text.contains("kept = true"), "{text}");
+    // Defaults that weren't in the file stay out of it.
+    assert!(!text.contains("backend"), "{text}");
+
+    let reloaded = Config::load_from(Some(&path), |_| None).unwrap();
+    assert_eq!(reloaded.config, config);
+
+    config.audio.master_volume = 150;
+    assert!(config.save(&path).is_err());
+    std::fs::remove_dir_all(path.parent().unwrap()).ok();
+}
@@ -0,0 +1,70 @@
+//! coverage.json over a small hand-built DOL
+
+use gcrecomp_core::recompiler::coverage::InstructionCoverage;
+use gcrecomp_core::recompiler::parser::DolFile;
+use gcrecomp_core::recompiler::pipeline::RecompilationPipeline;
+
+mod common;
+use common::build_dol;
+
+const BLR: u32 = 0x4E80_0020;
+
+#[test]
+fn test_coverage_matches_instruction_mix() {
+    let words = [
+        // Fully translatable: li r3,1 ; stw r3,8(r1) ; lwz r4,8(r1) ; cmpwi r3,0 ; blr
+        0x3860_0001,
+        0x9061_0008,
+        0x8081_0008,
+        0x2C03_0000,
+        BLR,
+        // divwu has no translation but the interpreter runs it:
+        // li r3,100 ; li r4,7 ; divwu r3,r3,r4 ; blr
+        0x3860_0064,
+        0x3880_0007,
+        0x7C63_2396,
+        BLR,
+        // Opcode 5 is neither translatable nor interpretable: partial translation.
+        0x1400_0000,
+        BLR,
+    ];
+    let dir = std::env::temp_dir().join(format!("gcrecomp_coverage_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dol_path = dir.join("test.dol");
+    let data = build_dol(&words);
+    std::fs::write(&dol_path, &data).unwrap();
+    let dol = DolFile::parse(&data, dol_path.to_str().unwrap()).unwrap();
+
+    let output = dir.join("recompiled.rs");
+    RecompilationPipeline::recompile(&dol, output.to_str().unwrap()).unwrap();
+
+    let json = std::fs::read_to_string(dir.join("coverage.json")).unwrap();
+    let coverage: InstructionCoverage = serde_json::from_str(&json).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert_eq!(coverage.total_instructions, 11);
+    assert_eq!(coverage.unknown_instructions, 2);
+    assert_eq!(coverage.functions_with_unknown, 2);
+    let count = |ty: &str| coverage.by_type.get(ty).copied().unwrap_or(0);
+    assert_eq!(count("Arithmetic"), 3);
+    assert_eq!(count("Store"), 1);
+    assert_eq!(count("Load"), 1);
+    assert_eq!(count("Compare"), 1);
+    assert_eq!(count("Branch"), 3);
+    assert_eq!(count("Unknown"), 2);
+    assert_eq!(
+        coverage.top_unknown_opcodes(5),
+        vec![("31/459", 1), ("5", 1)]
+    );
+    assert_eq!(coverage.recompiled_functions, 1);
+    assert_eq!(coverage.interpreted_functions, 1);
+    assert_eq!(coverage.partial_functions, 1);
+    assert_eq!(coverage.stubbed_functions, 0);
+    assert!(
+        coverage
+            .summary()
+            .starts_with("81.8% of instructions translated, 2 Unknown across 2 functions"),
+        "{}",
+        coverage.summary()
+    );
+}
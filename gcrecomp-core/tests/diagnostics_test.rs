@@ -0,0 +1,121 @@
+//! Crash bundles carry the trace, registers, call stack and mods, paths redacted
+
+use gcrecomp_core::config::Config;
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::diagnostics::{
+    generate_crash_bundle, redact_paths, CrashReport, FunctionCallLogger, LoadedMod,
+};
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::trampoline;
+use std::io::Read;
+
+const CODE: u32 = 0x8000_3000;
+const OUTER: u32 = 0x8000_5000;
+const INNER: u32 = 0x8000_6000;
+
+fn dispatch(
+    address: u32,
+    ctx: &mut CpuContext,
+    memory: &mut MemoryManager,
+) -> anyhow::Result<Option<u32>> {
+    match address {
+        OUTER => trampoline(INNER, ctx, memory, dispatch),
+        INNER => anyhow::bail!("invalid read at 0x{:08X}", ctx.gpr[4]),
+        _ => interpret_function(address, ctx, memory),
+    }
+}
+
+fn read_entry(archive: &mut zip::ZipArchive<std::fs::File>, name: &str) -> String {
+    let mut text = String::new();
+    archive
+        .by_name(name)
+        .unwrap()
+        .read_to_string(&mut text)
+        .unwrap();
+    text
+}
+
+#[test]
+fn crash_bundle_contains_seeded_state() {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_crash_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let config_path = dir.join("config.toml");
+    std::fs::write(&config_path, "[audio]\nmaster_volume = 80\n").unwrap();
+    let config = Config::load_from(Some(&config_path), |_| None).unwrap();
+
+    // Run a little code so the trace ring has instructions in it.
+    let mut memory = MemoryManager::new();
+    memory.write_u32(CODE, 0x3880_1234).unwrap(); // li r4, 0x1234
+    memory.write_u32(CODE + 4, 0x4E80_0020).unwrap(); // blr
+    let mut ctx = CpuContext::new();
+    interpret_function(CODE, &mut ctx, &mut memory).unwrap();
+
+    FunctionCallLogger::reset();
+    FunctionCallLogger::enable();
+    let error = trampoline(OUTER, &mut ctx, &mut memory, dispatch).unwrap_err();
+    FunctionCallLogger::disable();
+    assert!(FunctionCallLogger::stack().is_empty());
+    assert_eq!(
+        FunctionCallLogger::failure_stack(),
+        Some(vec![OUTER, INNER])
+    );
+
+    let report = CrashReport {
+        error: format!("{error:#} (loading {})", dir.join("game.iso").display()),
+        context: Some(&ctx),
+        memory: Some(&memory),
+        gx_summary: Some("tev stages: 1\n".to_string()),
+        mods: vec![
+            LoadedMod::new("speedrun-timer", "1.2.0"),
+            LoadedMod::new("hd-textures", "0.3"),
+        ],
+        config: Some(&config),
+    };
+    let bundle = dir.join("reports").join("crash.zip");
+    generate_crash_bundle(&bundle, &report).unwrap();
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(&bundle).unwrap()).unwrap();
+    let trace = read_entry(&mut archive, "trace.txt");
+    assert!(trace.contains("0x80003000  38801234"), "{trace}");
+    assert!(trace.contains("0x80003004  4E800020"), "{trace}");
+
+    let context = read_entry(&mut archive, "context.txt");
+    assert!(context.contains("r4  0x00001234"), "{context}");
+
+    let stack = read_entry(&mut archive, "callstack.txt");
+    assert!(stack.contains("#0 0x80005000\n#1 0x80006000"), "{stack}");
+
+    let mods = read_entry(&mut archive, "mods.txt");
+    assert_eq!(mods, "speedrun-timer 1.2.0\nhd-textures 0.3\n");
+
+    let error = read_entry(&mut archive, "error.txt");
+    assert!(error.contains("invalid read at 0x00001234"), "{error}");
+    let config = read_entry(&mut archive, "config.txt");
+    assert!(config.contains("audio.master_volume = 80"), "{config}");
+    let dir_text = dir.display().to_string();
+    for name in ["error.txt", "config.txt"] {
+        let text = read_entry(&mut archive, name);
+        assert!(!text.contains(&dir_text), "{name} leaks a path: {text}");
+        assert!(text.contains("<redacted>/"), "{name}: {text}");
+    }
+    for name in ["gx.txt", "version.txt"] {
+        archive.by_name(name).unwrap();
+    }
+
+    FunctionCallLogger::reset();
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn redact_paths_keeps_file_names() {
+    assert_eq!(
+        redact_paths("reading /home/alice/games/zelda.iso failed"),
+        "reading <redacted>/zelda.iso failed"
+    );
+    assert_eq!(
+        redact_paths(r#"pack = "C:\Users\bob\packs\hd" (D:/cfg/config.toml)"#),
+        r#"pack = "<redacted>/hd" (<redacted>/config.toml)"#
+    );
+    assert_eq!(redact_paths("ratio 3/4 and a / b"), "ratio 3/4 and a / b");
+}
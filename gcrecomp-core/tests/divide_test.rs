@@ -0,0 +1,57 @@
+//! Integer divides follow PowerPC: no trap on zero or overflow, XER[OV]/SO
+//! set by the `o` forms
+
+use gcrecomp_core::runtime::context::{self, CpuContext, XER_OV, XER_SO};
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+
+const DIVW: u32 = 0x7C64_2BD6; // divw r3,r4,r5
+const DIVWO: u32 = 0x7C64_2FD6; // divwo r3,r4,r5
+const DIVWU: u32 = 0x7C64_2B96; // divwu r3,r4,r5
+const DIVWUO: u32 = 0x7C64_2F96; // divwuo r3,r4,r5
+const BLR: u32 = 0x4E80_0020;
+
+/// Run `word ; blr` with r4 = `a`, r5 = `b`; returns (r3, XER).
+fn run(word: u32, a: u32, b: u32) -> (u32, u32) {
+    let mut memory = MemoryManager::new();
+    memory.write_u32(0x8000_3000, word).unwrap();
+    memory.write_u32(0x8000_3004, BLR).unwrap();
+    let mut ctx = CpuContext::new();
+    ctx.set_register(4, a);
+    ctx.set_register(5, b);
+    interpret_function(0x8000_3000, &mut ctx, &mut memory).unwrap();
+    (ctx.get_register(3), ctx.xer)
+}
+
+#[test]
+fn test_divw_by_zero() {
+    assert_eq!(context::divw(7, 0), (0, true));
+    assert_eq!(context::divw(-7i32 as u32, 0), (u32::MAX, true));
+    assert_eq!(run(DIVW, 7, 0), (0, 0));
+    assert_eq!(run(DIVWO, -7i32 as u32, 0), (u32::MAX, XER_OV | XER_SO));
+}
+
+#[test]
+fn test_divwu_by_zero() {
+    assert_eq!(context::divwu(0xFFFF_FFFF, 0), (0, true));
+    assert_eq!(run(DIVWU, 0xFFFF_FFFF, 0), (0, 0));
+    assert_eq!(run(DIVWUO, 0xFFFF_FFFF, 0), (0, XER_OV | XER_SO));
+    // Unsigned, unlike divw.
+    assert_eq!(run(DIVWU, 0xFFFF_FFFE, 2), (0x7FFF_FFFF, 0));
+}
+
+#[test]
+fn test_int_min_over_minus_one_overflows() {
+    assert_eq!(context::divw(0x8000_0000, u32::MAX), (u32::MAX, true));
+    assert_eq!(run(DIVW, 0x8000_0000, u32::MAX), (u32::MAX, 0));
+    assert_eq!(
+        run(DIVWO, 0x8000_0000, u32::MAX),
+        (u32::MAX, XER_OV | XER_SO)
+    );
+    // A clean divwo clears OV but SO stays sticky.
+    let mut ctx = CpuContext::new();
+    ctx.set_overflow(true);
+    ctx.set_overflow(false);
+    assert_eq!(ctx.xer, XER_SO);
+    assert_eq!(run(DIVWO, -9i32 as u32, 3), (-3i32 as u32, 0));
+}
@@ -0,0 +1,118 @@
+//! Hot-loading a recompiled dylib and calling through the swapped dispatcher
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::hot_reload::{dispatch, HotReloader, ABI_VERSION};
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Stand-in for a recompiled crate: its one function adds `ADD` to r3 and
+/// returns it. `CpuContext` is `repr(C)` with the GPRs first.
+const STUB: &str = r#"
+#[no_mangle]
+pub extern "C" fn gcrecomp_abi_version() -> u32 {
+    VERSION
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn gcrecomp_dispatch(
+    _address: u32,
+    ctx: *mut u32,
+    _memory: *mut u8,
+    ret: *mut u32,
+) -> i32 {
+    let r3 = ctx.add(3);
+    *r3 = (*r3).wrapping_add(ADD);
+    *ret = *r3;
+    1
+}
+"#;
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_hot_reload_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Compile the stub into `dir`'s library, replacing any previous build.
+fn build_stub(dir: &Path, version: u32, add: u32) -> PathBuf {
+    let source = dir.join(format!("stub_{version}_{add}.rs"));
+    std::fs::write(
+        &source,
+        STUB.replace("VERSION", &version.to_string())
+            .replace("ADD", &add.to_string()),
+    )
+    .unwrap();
+    let library = dir.join(format!(
+        "{}recompiled_stub{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ));
+    let staged = dir.join("staged.lib");
+    let status = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into()))
+        .args(["--crate-type", "cdylib", "--edition", "2021", "-o"])
+        .arg(&staged)
+        .arg(&source)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::rename(&staged, &library).unwrap();
+    library
+}
+
+#[test]
+fn swapped_library_serves_calls_and_keeps_state() {
+    let dir = temp_dir();
+    let library = build_stub(&dir, ABI_VERSION, 1);
+    let mut reloader = HotReloader::new(&library);
+    let mut ctx = CpuContext::new();
+    let mut memory = MemoryManager::new();
+    assert!(dispatch(0x8000_3100, &mut ctx, &mut memory).is_err());
+
+    assert!(reloader.poll().unwrap());
+    assert!(!reloader.poll().unwrap());
+    ctx.gpr[3] = 10;
+    assert_eq!(
+        dispatch(0x8000_3100, &mut ctx, &mut memory).unwrap(),
+        Some(11)
+    );
+
+    // Interpreted code calling out goes through the library too.
+    let words = [
+        0x7C08_02A6, // mflr r0
+        0x4800_00FD, // bl 0x80003100
+        0x7C08_03A6, // mtlr r0
+        0x3863_0005, // addi r3, r3, 5
+        0x4E80_0020, // blr
+    ];
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(0x8000_3000 + i as u32 * 4, *w).unwrap();
+    }
+    assert_eq!(
+        interpret_function(0x8000_3000, &mut ctx, &mut memory).unwrap(),
+        Some(17)
+    );
+
+    // Rebuild with new behaviour: the next call lands in the new library,
+    // on the same context.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    build_stub(&dir, ABI_VERSION, 100);
+    assert!(reloader.poll().unwrap());
+    assert_eq!(reloader.generation(), 2);
+    assert_eq!(
+        dispatch(0x8000_3100, &mut ctx, &mut memory).unwrap(),
+        Some(117)
+    );
+
+    // A library built against another ABI is refused; the old one stays.
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    build_stub(&dir, ABI_VERSION + 1, 1000);
+    let err = reloader.poll().unwrap_err().to_string();
+    assert!(err.contains("ABI version"), "{err}");
+    assert_eq!(
+        dispatch(0x8000_3100, &mut ctx, &mut memory).unwrap(),
+        Some(217)
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,70 @@
+//! Untranslatable functions falling back to the runtime interpreter
+
+use gcrecomp_core::recompiler::codegen::CodeGenerator;
+use gcrecomp_core::recompiler::decoder::{DecodedInstruction, Instruction};
+use gcrecomp_core::recompiler::ghidra::FunctionInfo;
+use gcrecomp_core::recompiler::pipeline::{RecompilationPipeline, Translation};
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+
+fn function(address: u32, words: &[u32]) -> (FunctionInfo, Vec<DecodedInstruction>) {
+    let info = FunctionInfo {
+        address,
+        name: format!("sub_{:08x}", address),
+        size: words.len() as u32 * 4,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    };
+    let instrs = words
+        .iter()
+        .enumerate()
+        .map(|(i, &w)| Instruction::decode(w, address + i as u32 * 4).unwrap())
+        .collect();
+    (info, instrs)
+}
+
+#[test]
+fn test_unsupported_function_delegates_to_interpreter() {
+    // li r3,100 ; li r4,7 ; divwu r3,r3,r4 ; blr
+    // divwu has no translation in the code generator.
+    let words = [0x3860_0064, 0x3880_0007, 0x7C63_2396, 0x4E80_0020];
+    let (func, instrs) = function(0x8000_3000, &words);
+    let metadata = RecompilationPipeline::function_metadata(&func);
+
+    let mut codegen = CodeGenerator::new().with_strict(true);
+    let (code, translation) =
+        RecompilationPipeline::generate_function_code(&mut codegen, &metadata, &instrs).unwrap();
+    assert_eq!(translation, Translation::Interpreted);
+    assert!(code.contains("pub fn func_0x80003000("), "{code}");
+    assert!(
+        code.contains("runtime::interpreter::interpret_function(0x80003000u32, ctx, memory)"),
+        "{code}"
+    );
+
+    // What the generated body does at runtime, with the image loaded in RAM.
+    let mut memory = MemoryManager::new();
+    for (i, &w) in words.iter().enumerate() {
+        memory.write_u32(0x8000_3000 + i as u32 * 4, w).unwrap();
+    }
+    let mut ctx = CpuContext::new();
+    assert_eq!(
+        interpret_function(0x8000_3000, &mut ctx, &mut memory).unwrap(),
+        Some(14)
+    );
+}
+
+#[test]
+fn test_translatable_function_is_recompiled() {
+    // li r3,0 ; blr
+    let (func, instrs) = function(0x8000_3100, &[0x3860_0000, 0x4E80_0020]);
+    let metadata = RecompilationPipeline::function_metadata(&func);
+    let mut codegen = CodeGenerator::new().with_strict(true);
+    let (code, translation) =
+        RecompilationPipeline::generate_function_code(&mut codegen, &metadata, &instrs).unwrap();
+    assert_eq!(translation, Translation::Recompiled);
+    assert!(!code.contains("interpret_function"), "{code}");
+}
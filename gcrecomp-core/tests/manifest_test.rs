@@ -0,0 +1,84 @@
+//! Batch manifests: a bad DOL is reported without stopping the others
+
+use gcrecomp_core::recompiler::manifest::{BatchManifest, BatchSummary};
+use gcrecomp_core::recompiler::optimizer::OptLevel;
+
+mod common;
+use common::build_dol;
+
+#[test]
+fn batch_continues_past_a_malformed_dol() {
+    let dir = std::env::temp_dir().join(format!("gcrecomp_batch_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    // li r3,1 ; blr
+    std::fs::write(dir.join("good.dol"), build_dol(&[0x3860_0001, 0x4E80_0020])).unwrap();
+    std::fs::write(dir.join("broken.dol"), b"not a dol").unwrap();
+    let manifest_path = dir.join("batch.toml");
+    std::fs::write(
+        &manifest_path,
+        r#"
+jobs = 2
+
+[[entries]]
+dol = "good.dol"
+opt_level = "aggressive"
+
+[[entries]]
+dol = "broken.dol"
+output_dir = "out/broken"
+"#,
+    )
+    .unwrap();
+
+    let manifest = BatchManifest::load(&manifest_path).unwrap();
+    assert_eq!(manifest.jobs, 2);
+    assert_eq!(manifest.entries[0].output_dir, Some(dir.join("good")));
+    assert_eq!(manifest.entries[1].output_dir, Some(dir.join("out/broken")));
+
+    let summary = manifest.run(None, OptLevel::Basic);
+    assert_eq!(summary.entries.len(), 2);
+    let good = &summary.entries[0];
+    assert!(good.succeeded(), "{:?}", good.error);
+    assert_eq!(good.dol, dir.join("good.dol"));
+    assert_eq!(good.functions, 1);
+    assert!(good.coverage > 99.0);
+    assert!(dir.join("good/recompiled.rs").is_file());
+
+    let broken = &summary.entries[1];
+    assert_eq!(broken.dol, dir.join("broken.dol"));
+    assert!(broken.error.as_deref().unwrap().contains("parse DOL"));
+    assert_eq!((summary.succeeded(), summary.failed()), (1, 1));
+
+    // The same manifest run serially gives the same summary.
+    let serial = manifest.run(Some(1), OptLevel::Basic);
+    assert_eq!(serial, summary);
+
+    let summary_path = dir.join("summary.json");
+    summary.write(&summary_path).unwrap();
+    let read: BatchSummary =
+        serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+    assert_eq!(read, summary);
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn json_manifest_matches_toml() {
+    let toml = BatchManifest::from_toml(
+        "[[entries]]\ndol = \"a.dol\"\nfidb = \"sdk.json\"\nhierarchical = true\n",
+    )
+    .unwrap();
+    let json = BatchManifest::from_json(
+        r#"{"entries": [{"dol": "a.dol", "fidb": "sdk.json", "hierarchical": true}]}"#,
+    )
+    .unwrap();
+    assert_eq!(toml, json);
+    assert_eq!(toml.jobs, 1);
+    assert!(BatchManifest::from_toml("[[entries]]\ndol = \"a.dol\"\ntypo = 1\n").is_err());
+
+    let summary = toml.run(None, OptLevel::None);
+    assert!(summary.entries[0]
+        .error
+        .as_deref()
+        .unwrap()
+        .contains("hierarchical"));
+}
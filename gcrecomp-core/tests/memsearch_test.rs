@@ -0,0 +1,62 @@
+//! Memory search: narrowing candidates across snapshots
+
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::memsearch::{MemorySearch, SearchFilter, ValueWidth};
+
+#[test]
+fn unchanged_keeps_the_value_and_drops_the_mutated_address() {
+    let mut memory = MemoryManager::new();
+    memory.write_u32(0x8012_3450, 0x0BAD_F00D).unwrap(); // lives
+    memory.write_u32(0x8040_0000, 0x0BAD_F00D).unwrap(); // unrelated copy
+
+    let mut search = MemorySearch::new(ValueWidth::U32);
+    search.reset(&memory);
+    assert_eq!(search.count(), 24 * 1024 * 1024 / 4);
+    assert_eq!(
+        search.filter(&memory, SearchFilter::Exact(f64::from(0x0BAD_F00Du32))),
+        2
+    );
+    assert_eq!(search.candidates(), [0x8012_3450, 0x8040_0000]);
+
+    memory.write_u32(0x8040_0000, 7).unwrap();
+    assert_eq!(search.filter(&memory, SearchFilter::Unchanged), 1);
+    assert_eq!(search.candidates(), [0x8012_3450]);
+    assert_eq!(
+        search.value_at(0x8012_3450),
+        Some(f64::from(0x0BAD_F00Du32))
+    );
+
+    // It changes in game; only it is left either way.
+    memory.write_u32(0x8012_3450, 2).unwrap();
+    assert_eq!(search.filter(&memory, SearchFilter::Changed), 1);
+    assert_eq!(search.filter(&memory, SearchFilter::LessThan(3.0)), 1);
+    assert_eq!(search.filter(&memory, SearchFilter::GreaterThan(2.0)), 0);
+}
+
+#[test]
+fn narrower_and_float_widths() {
+    let mut memory = MemoryManager::new();
+    memory.write_u16(0x8000_1002, 300).unwrap();
+    memory.write_u8(0x8000_2001, 250).unwrap();
+    memory.write_u32(0x8000_3000, 1.5f32.to_bits()).unwrap();
+
+    let mut search = MemorySearch::new(ValueWidth::U16);
+    assert_eq!(search.filter(&memory, SearchFilter::Exact(300.0)), 1);
+    assert_eq!(search.candidates(), [0x8000_1002]);
+
+    search.set_width(ValueWidth::U8, &memory);
+    search.filter(&memory, SearchFilter::GreaterThan(200.0));
+    assert_eq!(search.candidates(), [0x8000_2001]);
+
+    search.set_width(ValueWidth::F32, &memory);
+    search.filter(&memory, SearchFilter::Exact(1.5));
+    assert_eq!(search.candidates(), [0x8000_3000]);
+
+    assert_eq!(
+        SearchFilter::parse("gt", Some(1.0)),
+        Ok(SearchFilter::GreaterThan(1.0))
+    );
+    assert!(SearchFilter::parse("exact", None).is_err());
+    assert_eq!("F32".parse(), Ok(ValueWidth::F32));
+    assert!("u64".parse::<ValueWidth>().is_err());
+}
@@ -0,0 +1,149 @@
+//! OS reset entry points and exception vectors
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::memory::{MemoryFault, MemoryManager};
+use gcrecomp_core::runtime::sdk::exception::{OsError, DSISR_STORE};
+use gcrecomp_core::runtime::sdk::os::{
+    dispatch_sdk_call, read_c_string, OsState, ReportConsole, OS_RESET_RESTART,
+};
+use gcrecomp_core::runtime::sdk::printf::{self, VarArgs};
+use gcrecomp_core::runtime::{self, ShutdownRequest};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+const DSI_HANDLER: u32 = 0x8000_4000;
+
+/// Arguments the fake handler saw: (error, dsisr, dar).
+static HANDLER_ERROR: AtomicU32 = AtomicU32::new(u32::MAX);
+static HANDLER_DSISR: AtomicU32 = AtomicU32::new(0);
+static HANDLER_DAR: AtomicU32 = AtomicU32::new(0);
+
+fn call(
+    addr: u32,
+    ctx: &mut CpuContext,
+    _memory: &mut MemoryManager,
+) -> anyhow::Result<Option<u32>> {
+    assert_eq!(addr, DSI_HANDLER);
+    HANDLER_ERROR.store(ctx.get_register(3), Ordering::SeqCst);
+    HANDLER_DSISR.store(ctx.get_register(5), Ordering::SeqCst);
+    HANDLER_DAR.store(ctx.get_register(6), Ordering::SeqCst);
+    Ok(None)
+}
+
+#[test]
+fn test_os_reset_system_requests_shutdown() {
+    let mut ctx = CpuContext::new();
+    let mut memory = MemoryManager::new();
+    let mut os = OsState::new();
+    assert_eq!(runtime::shutdown_requested(), None);
+
+    ctx.set_register(3, OS_RESET_RESTART);
+    ctx.set_register(4, 0x1234);
+    ctx.set_register(5, 0);
+    assert!(dispatch_sdk_call(
+        "OSResetSystem",
+        &mut ctx,
+        &mut memory,
+        &mut os
+    ));
+
+    assert_eq!(
+        runtime::shutdown_requested(),
+        Some(ShutdownRequest::Restart {
+            code: 0x1234,
+            hot: false
+        })
+    );
+    // Recompiled code sees the stop flag and unwinds.
+    assert!(runtime::out_of_budget());
+    assert!(!os.interrupts.enabled());
+    assert!(runtime::take_shutdown_request().is_some());
+    assert_eq!(runtime::shutdown_requested(), None);
+}
+
+#[test]
+fn test_dsi_on_bad_address_runs_registered_handler() {
+    let mut ctx = CpuContext::new();
+    let mut memory = MemoryManager::new();
+    let mut os = OsState::new();
+
+    ctx.set_register(3, OsError::Dsi as u32);
+    ctx.set_register(4, DSI_HANDLER);
+    assert!(dispatch_sdk_call(
+        "OSSetErrorHandler",
+        &mut ctx,
+        &mut memory,
+        &mut os
+    ));
+    assert_eq!(ctx.get_register(3), 0); // no previous handler
+
+    // A store to unmapped space faults.
+    let err = memory.write_u32(0x9000_0000, 1).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<MemoryFault>(),
+        Some(&MemoryFault {
+            address: 0x9000_0000,
+            write: true
+        })
+    );
+    ctx.pc = 0x8000_3100;
+    let handled = os
+        .exceptions
+        .dispatch_memory_fault(&err, &mut ctx, &mut memory, call)
+        .unwrap();
+    assert!(handled);
+    assert_eq!(HANDLER_ERROR.load(Ordering::SeqCst), OsError::Dsi as u32);
+    assert_eq!(HANDLER_DAR.load(Ordering::SeqCst), 0x9000_0000);
+    assert_ne!(HANDLER_DSISR.load(Ordering::SeqCst) & DSISR_STORE, 0);
+    assert_eq!(os.exceptions.last_fault().unwrap().srr0, 0x8000_3100);
+
+    // Other errors are not faults.
+    let other = anyhow::anyhow!("Memory read out of bounds");
+    assert!(!os
+        .exceptions
+        .dispatch_memory_fault(&other, &mut ctx, &mut memory, call)
+        .unwrap());
+}
+
+#[test]
+fn test_os_report_formats_printf_arguments() {
+    let mut ctx = CpuContext::new();
+    let mut memory = MemoryManager::new();
+    let mut os = OsState::new();
+    let console = Arc::new(ReportConsole::new(16));
+    os.console = Some(console.clone());
+
+    let write_str = |memory: &mut MemoryManager, addr: u32, s: &str| {
+        for (i, b) in s.bytes().chain([0]).enumerate() {
+            memory.write_u8(addr + i as u32, b).unwrap();
+        }
+    };
+    write_str(&mut memory, 0x8000_5000, "val=%d");
+    ctx.set_register(3, 0x8000_5000);
+    ctx.set_register(4, 42);
+    let fmt = read_c_string(&memory, 0x8000_5000);
+    assert_eq!(
+        printf::format(&fmt, &mut VarArgs::from_registers(&ctx, &memory, 4)),
+        "val=42"
+    );
+
+    // A line is printed once its newline arrives, possibly from a later call.
+    assert!(dispatch_sdk_call(
+        "OSReport",
+        &mut ctx,
+        &mut memory,
+        &mut os
+    ));
+    assert!(console.lines().is_empty());
+    write_str(&mut memory, 0x8000_5000, " %s (%.1f)\n");
+    write_str(&mut memory, 0x8000_5100, "ok");
+    ctx.set_register(4, 0x8000_5100);
+    ctx.fpr[1] = 1.5;
+    assert!(dispatch_sdk_call(
+        "OSReport",
+        &mut ctx,
+        &mut memory,
+        &mut os
+    ));
+    assert_eq!(console.lines(), ["val=42 ok (1.5)"]);
+}
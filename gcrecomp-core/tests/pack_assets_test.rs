@@ -0,0 +1,54 @@
+//! GCFS archives packed from a directory read back through the DVD filesystem
+
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::sdk::dvd::{pack_directory, Codec};
+use gcrecomp_core::runtime::sdk::VirtualFilesystem;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("gcrecomp_pack_{}_{}", name, std::process::id()))
+}
+
+fn read_file(vfs: &mut VirtualFilesystem, path: &str) -> Vec<u8> {
+    let handle = vfs.dvd_open(path);
+    assert_ne!(handle, 0, "{path} not found");
+    let length = vfs.dvd_get_length(handle);
+    let mut memory = MemoryManager::new();
+    let read = vfs
+        .dvd_read(handle, &mut memory, 0x8010_0000, length, 0)
+        .unwrap();
+    assert_eq!(read, length);
+    assert!(vfs.dvd_close(handle));
+    memory.read_bytes(0x8010_0000, length as usize).unwrap()
+}
+
+#[test]
+fn packed_directory_roundtrips_through_the_vfs() {
+    let dir = temp_dir("tree");
+    std::fs::create_dir_all(dir.join("audio/bgm")).unwrap();
+    let banner = b"BNR1".repeat(200);
+    let stream: Vec<u8> = (0..5000u32).map(|i| (i * 7 % 251) as u8).collect();
+    std::fs::write(dir.join("opening.bnr"), &banner).unwrap();
+    std::fs::write(dir.join("audio/bgm/title.adp"), &stream).unwrap();
+    std::fs::write(dir.join("audio/empty.bin"), b"").unwrap();
+
+    for codec in [Codec::Stored, Codec::Yaz0] {
+        let (archive, files) = pack_directory(&dir, codec).unwrap();
+        assert_eq!(files, 3);
+        let mut vfs = VirtualFilesystem::new(Box::leak(archive.into_boxed_slice())).unwrap();
+        assert_eq!(read_file(&mut vfs, "/opening.bnr"), banner, "{codec:?}");
+        assert_eq!(
+            read_file(&mut vfs, "audio/bgm/title.adp"),
+            stream,
+            "{codec:?}"
+        );
+        assert_eq!(read_file(&mut vfs, "audio/empty.bin"), b"");
+        assert_eq!(vfs.dvd_open("title.adp"), 0);
+    }
+
+    // Yaz0 actually shrinks the repetitive banner.
+    let (stored, _) = pack_directory(&dir, Codec::Stored).unwrap();
+    let (packed, _) = pack_directory(&dir, Codec::Yaz0).unwrap();
+    assert!(packed.len() < stored.len());
+    std::fs::remove_dir_all(&dir).ok();
+}
@@ -0,0 +1,80 @@
+//! Panics in dispatched functions becoming errors (`set_catch_panics`)
+//!
+//! The switch is process-wide, so everything that changes it lives in one test.
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::watchdog::{self, ExecutionError};
+use gcrecomp_core::runtime::{self as rt, trampoline};
+
+const DIVIDE: u32 = 0x8000_5000;
+const CALLER: u32 = 0x8000_5100;
+
+/// A stand-in for generated code: `divwu r3,r4,r5` translated without a
+/// zero check, and a caller that ignores its callee's error like generated
+/// callers do.
+fn dispatch(
+    address: u32,
+    ctx: &mut CpuContext,
+    memory: &mut MemoryManager,
+) -> anyhow::Result<Option<u32>> {
+    match address {
+        DIVIDE => {
+            watchdog::record_pc(DIVIDE);
+            let q = ctx.get_register(4) / ctx.get_register(5);
+            ctx.set_register(3, q);
+            Ok(Some(q))
+        }
+        CALLER => {
+            let _ = trampoline(DIVIDE, ctx, memory, dispatch);
+            Ok(Some(7))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[test]
+fn test_panicking_function_returns_error_with_its_address() {
+    let mut memory = MemoryManager::new();
+    let mut ctx = CpuContext::new();
+    ctx.set_register(4, 10);
+    ctx.set_register(5, 0);
+
+    rt::set_catch_panics(true);
+    let err = trampoline(DIVIDE, &mut ctx, &mut memory, dispatch).unwrap_err();
+    match err.downcast_ref::<ExecutionError>() {
+        Some(ExecutionError::Panic {
+            address,
+            message,
+            recent,
+        }) => {
+            assert_eq!(*address, DIVIDE);
+            assert!(message.contains("divide by zero"), "{message}");
+            assert_eq!(recent.last(), Some(&DIVIDE));
+        }
+        other => panic!("expected a panic error, got {other:?}"),
+    }
+    assert!(err.to_string().contains("0x80005000 panicked"), "{err}");
+    let _ = rt::take_panic();
+
+    // The caller carries on; the host still learns where it went wrong.
+    assert_eq!(
+        trampoline(CALLER, &mut ctx, &mut memory, dispatch).unwrap(),
+        Some(7)
+    );
+    assert!(matches!(
+        rt::take_panic(),
+        Some(ExecutionError::Panic {
+            address: DIVIDE,
+            ..
+        })
+    ));
+    assert_eq!(rt::take_panic(), None);
+
+    // Off (the default), the panic unwinds as before.
+    rt::set_catch_panics(false);
+    let unwound = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        trampoline(DIVIDE, &mut ctx, &mut memory, dispatch)
+    }));
+    assert!(unwound.is_err());
+}
@@ -0,0 +1,50 @@
+//! `RecompileOptions::jobs`: generating functions on several threads gives the same output
+
+use gcrecomp_core::recompiler::parser::DolFile;
+use gcrecomp_core::recompiler::pipeline::{RecompilationPipeline, RecompileOptions};
+
+mod common;
+use common::build_dol;
+
+#[test]
+fn parallel_output_matches_sequential() {
+    // 64 five-instruction functions; each loads a constant, calls the next
+    // with it and adds: li r4,i ; bl next ; add r3,r3,r4 ; nop ; blr
+    const FUNCTIONS: u32 = 64;
+    let mut words = Vec::new();
+    for i in 0..FUNCTIONS {
+        words.push(0x3880_0000 | i);
+        words.push(if i + 1 < FUNCTIONS {
+            0x4800_0011
+        } else {
+            0x6000_0000
+        });
+        words.extend([0x7C63_2214, 0x6000_0000, 0x4E80_0020]);
+    }
+    let dol = DolFile::parse(&build_dol(&words), "test.dol").unwrap();
+    let dir = std::env::temp_dir().join(format!("gcrecomp_parallel_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let recompile = |jobs: usize| {
+        let out = dir.join(format!("recompiled_{jobs}.rs"));
+        let options = RecompileOptions {
+            jobs: Some(jobs),
+            ..Default::default()
+        };
+        let summary =
+            RecompilationPipeline::recompile_with_options(&dol, out.to_str().unwrap(), &options)
+                .unwrap();
+        (std::fs::read_to_string(&out).unwrap(), summary)
+    };
+    let (sequential, sequential_summary) = recompile(1);
+    let (parallel, parallel_summary) = recompile(4);
+    std::fs::remove_dir_all(&dir).ok();
+
+    assert!(sequential_summary.stats.successful_functions >= FUNCTIONS as usize);
+    assert_eq!(
+        parallel_summary.stats.successful_functions,
+        sequential_summary.stats.successful_functions
+    );
+    assert_eq!(parallel_summary.optimizer, sequential_summary.optimizer);
+    assert!(parallel == sequential, "parallel output differs");
+}
@@ -0,0 +1,69 @@
+//! Region detection from the disc header and the OS defaults it selects
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::sdk::os::{dispatch_sdk_call, os_init, OsState};
+use gcrecomp_core::runtime::sdk::region::{
+    Language, Region, OS_FONT_ENCODE_ANSI, OS_FONT_ENCODE_SJIS, OS_TV_MODE, VI_NTSC, VI_PAL,
+};
+
+/// Boot with `game_id` in the disc header, as `game` does.
+fn boot(game_id: &[u8; 6], forced: Option<Region>) -> (MemoryManager, OsState) {
+    let mut memory = MemoryManager::new();
+    let mut os = OsState::new();
+    os_init(&mut os, &mut memory);
+    memory.write_bytes(0x8000_0000, game_id).unwrap();
+    let region = Region::resolve(Region::detect(&memory), forced);
+    os.set_region(region, &mut memory);
+    (memory, os)
+}
+
+fn call(name: &str, memory: &mut MemoryManager, os: &mut OsState) -> u32 {
+    let mut ctx = CpuContext::new();
+    assert!(dispatch_sdk_call(name, &mut ctx, memory, os));
+    ctx.get_register(3)
+}
+
+#[test]
+fn test_pal_header_boots_at_50hz_with_pal_defaults() {
+    let (mut memory, mut os) = boot(b"GALP01", None);
+    assert_eq!(os.region, Region::Pal);
+    assert_eq!(os.region.field_rate(), 50.0);
+    assert_eq!(memory.read_u32(OS_TV_MODE).unwrap(), VI_PAL);
+    assert_eq!(os.sram.language, Language::English);
+    assert_eq!(call("OSGetLanguage", &mut memory, &mut os), 0);
+    assert_eq!(
+        call("OSGetFontEncode", &mut memory, &mut os),
+        OS_FONT_ENCODE_ANSI
+    );
+}
+
+#[test]
+fn test_ntsc_headers_boot_at_60hz() {
+    let (memory, os) = boot(b"GALE01", None);
+    assert_eq!(os.region, Region::NtscU);
+    assert!((os.region.field_rate() - 59.94).abs() < 1e-9);
+    assert_eq!(memory.read_u32(OS_TV_MODE).unwrap(), VI_NTSC);
+
+    let (mut memory, mut os) = boot(b"GALJ01", None);
+    assert_eq!(os.region, Region::NtscJ);
+    assert_eq!(
+        call("OSGetFontEncode", &mut memory, &mut os),
+        OS_FONT_ENCODE_SJIS
+    );
+}
+
+#[test]
+fn test_region_override_wins_over_the_disc() {
+    let (memory, os) = boot(b"GALE01", Some(Region::Pal));
+    assert_eq!(os.region, Region::Pal);
+    assert_eq!(memory.read_u32(OS_TV_MODE).unwrap(), VI_PAL);
+
+    // Unknown country letter and no override: NTSC-U.
+    assert_eq!(
+        Region::resolve(Region::detect(&MemoryManager::new()), None),
+        Region::NtscU
+    );
+    assert_eq!("PAL".parse::<Region>(), Ok(Region::Pal));
+    assert!("secam".parse::<Region>().is_err());
+}
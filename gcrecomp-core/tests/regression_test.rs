@@ -0,0 +1,223 @@
+//! Captured calls replay through the harness and generate valid test modules
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::regression::{
+    generate_test_module, load_from_file, save_to_file, FunctionTestHarness, InitialState,
+    MemoryRegion, RegressionTestCase, ResultCache, VOLATILE_SLOTS,
+};
+use gcrecomp_core::runtime::trace::RegisterState;
+
+const CODE: u32 = 0x8000_3000;
+const DATA: u32 = 0x8000_4000;
+
+/// Capture `r3 = r3 + r4; *(u32*)r5 = r3` with the given inputs.
+fn capture(a: u32, b: u32) -> RegressionTestCase {
+    let words = [
+        0x7C63_2214, // add r3, r3, r4
+        0x9065_0000, // stw r3, 0(r5)
+        0x4E80_0020, // blr
+    ];
+    let mut memory = MemoryManager::new();
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(CODE + i as u32 * 4, *w).unwrap();
+    }
+    let mut ctx = CpuContext::new();
+    ctx.gpr[3] = a;
+    ctx.gpr[4] = b;
+    ctx.gpr[5] = DATA;
+    RegressionTestCase::capture(
+        "Math::Add",
+        CODE,
+        interpret_function,
+        &mut ctx,
+        &mut memory,
+        &[(CODE, words.len() * 4), (DATA, 4)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn captured_case_replays_and_detects_changes() {
+    let case = capture(5, 7);
+    assert_eq!(case.expected.gpr[3], 12);
+    assert_eq!(case.expected_memory[1].bytes, [0, 0, 0, 12]);
+
+    let harness = FunctionTestHarness::interpreted();
+    harness.check(&case).unwrap();
+
+    let mut wrong = case.clone();
+    wrong.expected.gpr[3] = 13;
+    wrong.expected_memory[1].bytes[3] = 13;
+    let diffs = harness.run(&wrong).unwrap();
+    assert_eq!(
+        diffs,
+        [
+            "r3: expected 0x0000000D, got 0x0000000C",
+            "[0x80004003]: expected 0x0D, got 0x0C",
+        ]
+    );
+    assert!(harness.check(&wrong).is_err());
+}
+
+#[test]
+fn memory_ranges_on_one_side_only_are_still_checked() {
+    let harness = FunctionTestHarness::interpreted();
+
+    // Recorded on entry but not after: the store into it is unexpected.
+    let mut input_only = capture(5, 7);
+    input_only.expected_memory.truncate(1);
+    assert_eq!(
+        harness.run(&input_only).unwrap(),
+        ["[0x80004003]: expected 0x00, got 0x0C"]
+    );
+
+    // Expected after but not recorded on entry: replayed from fresh memory,
+    // so the right result passes and a wrong byte is caught.
+    let mut expected_only = capture(5, 7);
+    expected_only.input_memory.truncate(1);
+    harness.check(&expected_only).unwrap();
+    expected_only.expected_memory[1].bytes[2] = 1;
+    assert_eq!(
+        harness.run(&expected_only).unwrap(),
+        ["[0x80004002]: expected 0x01, got 0x00"]
+    );
+
+    // Outside RAM: reported, not an error.
+    let mut unmapped = capture(5, 7);
+    unmapped
+        .expected_memory
+        .push(MemoryRegion::new(0x9000_0000, vec![0xAB]));
+    assert_eq!(
+        harness.run(&unmapped).unwrap(),
+        ["[0x90000000]: expected 0xAB, unmapped"]
+    );
+}
+
+#[test]
+fn recorded_cases_replay_and_round_trip() {
+    let code = [
+        0x7C63_2214u32, // add r3, r3, r4
+        0x9865_0001,    // stb r3, 1(r5)
+        0x4E80_0020,    // blr
+    ];
+    let code = MemoryRegion::new(CODE, code.iter().flat_map(|w| w.to_be_bytes()).collect());
+    let states: Vec<InitialState> = [(5, 7), (0x20, 0x21)]
+        .into_iter()
+        .map(|(a, b)| InitialState {
+            name: "Math::AddByte".to_string(),
+            address: CODE,
+            registers: RegisterState::from_slots(&[
+                (3, a),
+                (4, b),
+                (5, DATA as u64),
+                (64, 0x8000_1234),
+            ]),
+            memory: vec![code.clone()],
+        })
+        .collect();
+
+    let harness = FunctionTestHarness::interpreted();
+    let cases = harness.record(&states, true).unwrap();
+    assert_eq!(cases[0].expected.gpr[3], 12);
+    assert_eq!(
+        cases[0].expected_memory,
+        [MemoryRegion::new(DATA + 1, vec![12])]
+    );
+    assert_eq!(
+        cases[1].expected_memory,
+        [MemoryRegion::new(DATA + 1, vec![0x41])]
+    );
+    assert_eq!(cases[0].masked, VOLATILE_SLOTS);
+    for case in &cases {
+        harness.check(case).unwrap();
+    }
+
+    // Masked registers don't count; everything else still does.
+    let mut moved = cases[0].clone();
+    moved.expected.lr = 0x8000_5678;
+    harness.check(&moved).unwrap();
+    moved.expected_memory[0].bytes[0] = 13;
+    assert_eq!(
+        harness.run(&moved).unwrap(),
+        ["[0x80004001]: expected 0x0D, got 0x0C"]
+    );
+    assert!(harness.record(&states, false).unwrap()[0].masked.is_empty());
+
+    let path = std::env::temp_dir().join(format!("gcrecomp_recorded_{}.json", std::process::id()));
+    save_to_file(&path, &cases).unwrap();
+    let loaded = load_from_file(&path).unwrap();
+    assert_eq!(loaded, cases);
+    for case in &loaded {
+        harness.check(case).unwrap();
+    }
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn cached_results_follow_the_whole_case() {
+    let harness = FunctionTestHarness::interpreted();
+    let path = std::env::temp_dir().join(format!("gcrecomp_results_{}.json", std::process::id()));
+    std::fs::remove_file(&path).ok();
+    let mut cases = vec![capture(5, 7), capture(1, 2)];
+
+    let mut cache = ResultCache::open(&path, "interpreter").unwrap();
+    let first = harness.run_all(&cases, Some(&mut cache));
+    assert!(first.iter().all(|r| r.passed() && !r.cached));
+    // Same name, different inputs: two entries.
+    assert_eq!(cache.len(), 2);
+    cache.save().unwrap();
+
+    let mut cache = ResultCache::open(&path, "interpreter").unwrap();
+    let again = harness.run_all(&cases, Some(&mut cache));
+    assert!(again.iter().all(|r| r.passed() && r.cached));
+
+    // Editing the code in the case's input memory moves the store; the cached
+    // pass must not hide it.
+    cases[0].input_memory[0].bytes[7] = 4; // stw r3, 4(r5)
+    let edited = harness.run_all(&cases, Some(&mut cache));
+    assert!(!edited[0].cached);
+    assert_eq!(
+        edited[0].diffs,
+        [
+            "[0x80003007]: expected 0x00, got 0x04",
+            "[0x80004003]: expected 0x0C, got 0x00",
+        ]
+    );
+    assert!(edited[1].cached && edited[1].passed());
+
+    // Another executor starts from nothing.
+    let fresh = ResultCache::open(&path, "recompiled").unwrap();
+    assert!(fresh.is_empty());
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn generated_module_is_valid_rust() {
+    let cases = [capture(5, 7), capture(1, 2)];
+    let source = generate_test_module(
+        &cases,
+        "gcrecomp_core::runtime::interpreter::interpret_function",
+    );
+    let file = syn::parse_file(&source).unwrap_or_else(|e| panic!("{e}\n{source}"));
+
+    let tests: Vec<String> = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            syn::Item::Fn(f) if f.attrs.iter().any(|a| a.path().is_ident("test")) => {
+                Some(f.sig.ident.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(tests, ["test_math__add_0", "test_math__add_1"]);
+    assert!(
+        source.contains("// Input: r3=0x00000005 r4=0x00000007 r5=0x80004000"),
+        "{source}"
+    );
+    assert!(source.contains(
+        "FunctionTestHarness::new(gcrecomp_core::runtime::interpreter::interpret_function)"
+    ));
+}
@@ -0,0 +1,68 @@
+//! REL module parsing and relocation
+
+use gcrecomp_core::recompiler::parser::rel::{RelFile, RelocationKind, MAIN_MODULE};
+
+fn put(buf: &mut Vec<u8>, offset: usize, value: u32) {
+    if buf.len() < offset + 4 {
+        buf.resize(offset + 4, 0);
+    }
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+/// Version 1 module 7: one executable section holding `bl 0 ; blr`, with a
+/// REL24 relocation pointing the `bl` at `0x80003100` in the main DOL.
+fn minimal_rel() -> Vec<u8> {
+    let mut rel = Vec::new();
+    put(&mut rel, 0x00, 7); // id
+    put(&mut rel, 0x0C, 2); // sections: null + .text
+    put(&mut rel, 0x10, 0x40); // section table
+    put(&mut rel, 0x1C, 1); // version
+    put(&mut rel, 0x24, 0x60); // relocation data
+    put(&mut rel, 0x28, 0x58); // import table
+    put(&mut rel, 0x2C, 8); // one import
+    put(&mut rel, 0x30, 0x0100_0000); // prolog in section 1
+    put(&mut rel, 0x34, 0); // at offset 0
+
+    put(&mut rel, 0x40, 0); // section 0: null
+    put(&mut rel, 0x44, 0);
+    put(&mut rel, 0x48, 0x50 | 1); // section 1: executable at 0x50
+    put(&mut rel, 0x4C, 8);
+    put(&mut rel, 0x50, 0x4800_0001); // bl 0
+    put(&mut rel, 0x54, 0x4E80_0020); // blr
+
+    put(&mut rel, 0x58, MAIN_MODULE);
+    put(&mut rel, 0x5C, 0x60);
+    // R_DOLPHIN_SECTION 1; R_PPC_REL24 at +0 -> 0x80003100; R_DOLPHIN_END
+    put(&mut rel, 0x60, 0x0000_CA01);
+    put(&mut rel, 0x64, 0);
+    put(&mut rel, 0x68, 0x0000_0A00);
+    put(&mut rel, 0x6C, 0x8000_3100);
+    put(&mut rel, 0x70, 0x0000_CB00);
+    put(&mut rel, 0x74, 0);
+    rel
+}
+
+#[test]
+fn test_rel24_against_main_dol_is_patched() {
+    let mut rel = RelFile::parse(&minimal_rel(), "test.rel").unwrap();
+    assert_eq!(rel.id, 7);
+    assert_eq!(rel.sections.len(), 2);
+    assert!(rel.sections[1].executable);
+    assert_eq!(rel.imports.len(), 1);
+    assert_eq!(rel.imports[0].relocations[0].kind, RelocationKind::Rel24);
+
+    let report = rel.link(0x8050_0000, 0x8060_0000).unwrap();
+    assert_eq!(report.applied, 1);
+    assert_eq!(report.unresolved, 0);
+
+    // bl at 0x80500050 now reaches 0x80003100: LI = 0x80003100 - 0x80500050.
+    let dol = rel.to_dol();
+    assert_eq!(dol.entry_point, 0x8050_0050);
+    assert_eq!(dol.text_sections.len(), 1);
+    let text = &dol.text_sections[0];
+    assert_eq!(text.address, 0x8050_0050);
+    let patched = u32::from_be_bytes(text.data[0..4].try_into().unwrap());
+    assert_eq!(patched, 0x4BB0_30B1);
+    let li = ((patched & 0x03FF_FFFC) as i32) << 6 >> 6;
+    assert_eq!(text.address.wrapping_add(li as u32), 0x8000_3100);
+}
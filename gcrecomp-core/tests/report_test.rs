@@ -0,0 +1,74 @@
+//! --report pages over a one-function DOL
+
+use gcrecomp_core::recompiler::parser::DolFile;
+use gcrecomp_core::recompiler::pipeline::{RecompilationPipeline, RecompileOptions};
+
+mod common;
+use common::build_dol;
+
+#[test]
+fn test_report_page_has_disassembly_and_rust() {
+    // li r3,1 ; addi r3,r3,2 ; blr
+    let data = build_dol(&[0x3860_0001, 0x3863_0002, 0x4E80_0020]);
+    let dir = std::env::temp_dir().join(format!("gcrecomp_report_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dol = DolFile::parse(&data, "test.dol").unwrap();
+    let options = RecompileOptions {
+        report_dir: Some(dir.join("report")),
+        ..Default::default()
+    };
+    RecompilationPipeline::recompile_with_options(
+        &dol,
+        dir.join("recompiled.rs").to_str().unwrap(),
+        &options,
+    )
+    .unwrap();
+
+    let page = std::fs::read_to_string(dir.join("report/80003000.md")).unwrap();
+    let index = std::fs::read_to_string(dir.join("report/index.md")).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+
+    for expected in [
+        "80003000  38600001  li r3, 1",
+        "addi r3, r3, 2",
+        "blr",
+        "pub fn func_0x80003000(",
+        "fully translated",
+    ] {
+        assert!(page.contains(expected), "missing `{expected}`:\n{page}");
+    }
+    assert!(
+        index.contains("[sub_80003000](80003000.md) | fully translated | 3 |"),
+        "{index}"
+    );
+}
+
+#[test]
+fn test_report_reads_back() {
+    use gcrecomp_core::recompiler::decoder::Instruction;
+    use gcrecomp_core::recompiler::pipeline::Translation;
+    use gcrecomp_core::recompiler::report::{read_report, ReportWriter};
+
+    let dir = std::env::temp_dir().join(format!("gcrecomp_report_read_{}", std::process::id()));
+    let blr = [Instruction::decode(0x4E80_0020, 0x8000_3000).unwrap()];
+    let mut report = ReportWriter::create(&dir).unwrap();
+    report
+        .write_function(
+            "OSInit",
+            0x8000_3000,
+            &blr,
+            "pub fn OSInit() {}\n",
+            Some(Translation::Interpreted),
+        )
+        .unwrap();
+    report.finish().unwrap();
+
+    let pages = read_report(&dir).unwrap();
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(pages.len(), 1);
+    let page = &pages[&0x8000_3000];
+    assert_eq!(page.name, "OSInit");
+    assert_eq!(page.status, "interpreted");
+    assert_eq!(page.rust_len, "pub fn OSInit() {}".len());
+    assert!(!page.is_stub());
+}
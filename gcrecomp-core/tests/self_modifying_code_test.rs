@@ -0,0 +1,71 @@
+//! Self-modifying code: a patched instruction runs after `icbi`, not the cached one
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::{
+    block_cache_stats, clear_block_cache, interpret_function,
+};
+use gcrecomp_core::runtime::memory::MemoryManager;
+
+const CODE: u32 = 0x8000_4000;
+
+#[test]
+fn patched_instruction_runs_after_icbi() {
+    clear_block_cache();
+    let mut memory = MemoryManager::new();
+    let words = [
+        0x3CA0_3860, // lis r5,0x3860
+        0x60A5_0007, // ori r5,r5,7        ; r5 = li r3,7
+        0x3CC0_8000, // lis r6,0x8000
+        0x60C6_4018, // ori r6,r6,0x4018   ; r6 = the `li r3,1` below
+        0x90A6_0000, // stw r5,0(r6)
+        0x7C00_37AC, // icbi 0,r6
+        0x3860_0001, // li r3,1
+        0x4E80_0020, // blr
+    ];
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(CODE + i as u32 * 4, *w).unwrap();
+    }
+    assert!(!memory.is_executable(CODE));
+    let mut ctx = CpuContext::new();
+
+    // The patch lands in the block being run; icbi makes it take effect.
+    assert_eq!(
+        interpret_function(CODE, &mut ctx, &mut memory).unwrap(),
+        Some(7)
+    );
+    assert!(memory.is_executable(CODE));
+    assert!(!memory.is_executable(0x8010_0000));
+    let stats = block_cache_stats();
+    assert_eq!(stats.invalidated, 1);
+    assert_eq!(memory.read_u32(CODE + 0x18).unwrap(), 0x3860_0007);
+
+    // Each run rewrites the same word, so every run reads its code again.
+    assert_eq!(
+        interpret_function(CODE, &mut ctx, &mut memory).unwrap(),
+        Some(7)
+    );
+    assert!(block_cache_stats().invalidated > stats.invalidated);
+}
+
+#[test]
+fn invalidate_code_drops_cached_blocks() {
+    clear_block_cache();
+    let mut memory = MemoryManager::new();
+    memory.write_u32(CODE, 0x3860_0001).unwrap(); // li r3,1
+    memory.write_u32(CODE + 4, 0x4E80_0020).unwrap(); // blr
+    let mut ctx = CpuContext::new();
+    interpret_function(CODE, &mut ctx, &mut memory).unwrap();
+    interpret_function(CODE, &mut ctx, &mut memory).unwrap();
+    assert_eq!(block_cache_stats().hits, 1);
+
+    // Flushing a line on another page leaves the block cached.
+    memory.invalidate_code(0x8010_0000, 32);
+    interpret_function(CODE, &mut ctx, &mut memory).unwrap();
+    assert_eq!(block_cache_stats().hits, 2);
+
+    // Flushing its own line drops it, as icbi would.
+    memory.invalidate_code(CODE, 32);
+    interpret_function(CODE, &mut ctx, &mut memory).unwrap();
+    let stats = block_cache_stats();
+    assert_eq!((stats.hits, stats.misses, stats.invalidated), (2, 2, 1));
+}
@@ -0,0 +1,62 @@
+//! SRAM settings block: config overrides, the settings calls and the checksum
+
+use gcrecomp_core::config::Config;
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::sdk::os::{dispatch_sdk_call, OsState};
+use gcrecomp_core::runtime::sdk::region::{Language, Region};
+use gcrecomp_core::runtime::sdk::sram::{self, Sram, OS_SOUND_MODE_STEREO};
+
+fn call(name: &str, arg: u32, os: &mut OsState) -> u32 {
+    let mut ctx = CpuContext::new();
+    let mut memory = MemoryManager::new();
+    ctx.set_register(3, arg);
+    assert!(dispatch_sdk_call(name, &mut ctx, &mut memory, os));
+    ctx.get_register(3)
+}
+
+#[test]
+fn test_configured_settings_reach_the_sdk_and_checksum() {
+    let env = |var: &str| match var {
+        "GCRECOMP_SYSTEM_LANGUAGE" => Some("german".to_string()),
+        "GCRECOMP_SYSTEM_PROGRESSIVE" => Some("true".to_string()),
+        _ => None,
+    };
+    let loaded = Config::load_from(None, env).unwrap();
+    let mut os = OsState::new();
+    os.sram = Sram::for_region(Region::Pal);
+    loaded.apply_system(&mut os.sram);
+
+    assert_eq!(call("OSGetLanguage", 0, &mut os), Language::German as u32);
+    assert_eq!(call("OSGetProgressiveMode", 0, &mut os), 1);
+    // Unset keys keep the SRAM's value.
+    assert_eq!(call("OSGetSoundMode", 0, &mut os), OS_SOUND_MODE_STEREO);
+
+    let bytes = os.sram.to_bytes();
+    let (sum, inv) = sram::checksum(&bytes);
+    assert_eq!(u16::from_be_bytes([bytes[0], bytes[1]]), sum);
+    assert_eq!(u16::from_be_bytes([bytes[2], bytes[3]]), inv);
+    let parsed = Sram::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed.language, Language::German);
+    assert!(parsed.progressive());
+
+    let mut corrupt = bytes;
+    corrupt[0x12] ^= 1;
+    assert!(Sram::from_bytes(&corrupt).is_err());
+}
+
+#[test]
+fn test_settings_calls_persist_to_the_sram_file() {
+    let path = std::env::temp_dir().join(format!("gcrecomp_sram_{}.bin", std::process::id()));
+    std::fs::remove_file(&path).ok();
+
+    let mut os = OsState::new();
+    os.sram = Sram::open(&path, Region::NtscU).unwrap();
+    call("OSSetLanguage", Language::French as u32, &mut os);
+    call("OSSetSoundMode", 0, &mut os);
+
+    let reloaded = Sram::open(&path, Region::NtscU).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(reloaded.language, Language::French);
+    assert_eq!(reloaded.sound_mode(), 0);
+}
@@ -0,0 +1,60 @@
+//! Symbol map names flowing into generated code
+
+use gcrecomp_core::recompiler::codegen::CodeGenerator;
+use gcrecomp_core::recompiler::decoder::Instruction;
+use gcrecomp_core::recompiler::ghidra::FunctionInfo;
+use gcrecomp_core::recompiler::pipeline::RecompilationPipeline;
+use gcrecomp_core::recompiler::symbols::SymbolMap;
+
+const SAMPLE_MAP: &str = "\
+.text section layout
+  Starting        Virtual
+  address  Size   address
+  -----------------------
+  00000000 000008 80003000  4 OSInit \tos.a OSInit.o
+  00000008 000008 80003008  4 @1234 \tmain.o
+";
+
+fn discovered(address: u32) -> FunctionInfo {
+    FunctionInfo {
+        address,
+        name: format!("sub_{:08x}", address),
+        size: 8,
+        calling_convention: "default".to_string(),
+        parameters: vec![],
+        return_type: None,
+        local_variables: vec![],
+        basic_blocks: vec![],
+    }
+}
+
+#[test]
+fn test_map_names_become_function_identifiers() {
+    let map = SymbolMap::parse(SAMPLE_MAP);
+    assert_eq!(map.len(), 2);
+
+    let mut functions = vec![discovered(0x8000_3000), discovered(0x8000_3008)];
+    assert_eq!(map.apply(&mut functions), 2);
+
+    let mut codegen = CodeGenerator::new();
+    let mut code = String::new();
+    for func in &functions {
+        // li r3,0 ; blr
+        let instrs = [0x3860_0000u32, 0x4E80_0020]
+            .iter()
+            .enumerate()
+            .map(|(i, &w)| Instruction::decode(w, func.address + i as u32 * 4).unwrap())
+            .collect::<Vec<_>>();
+        let metadata = RecompilationPipeline::function_metadata(func);
+        code.push_str(&codegen.generate_function(&metadata, &instrs).unwrap());
+    }
+
+    assert!(code.contains("pub fn OSInit_80003000("), "{code}");
+    // `@1234` sanitizes to a valid identifier
+    assert!(code.contains("pub fn _1234_80003008("), "{code}");
+    assert!(!code.contains("func_0x"), "{code}");
+    assert_eq!(
+        codegen.function_identifier(&functions[1].name, functions[1].address),
+        "_1234_80003008"
+    );
+}
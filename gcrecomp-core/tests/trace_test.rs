@@ -0,0 +1,93 @@
+//! Instruction traces: compact export round-trips register states exactly
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::trace::{self, RegisterState, RuntimeTracer, TraceFormat};
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("gcrecomp_trace_{}_{}", name, std::process::id()))
+}
+
+#[test]
+fn interpreted_run_roundtrips_through_both_formats() {
+    // r3 = sum of 1..=10 with a ctr loop.
+    let words = [
+        0x3860_0000, // li r3, 0
+        0x3880_000A, // li r4, 10
+        0x7C89_03A6, // mtctr r4
+        0x7C63_2214, // add r3, r3, r4
+        0x3884_FFFF, // addi r4, r4, -1
+        0x4200_FFF8, // bdnz -8
+        0x4E80_0020, // blr
+    ];
+    let mut memory = MemoryManager::new();
+    for (i, w) in words.iter().enumerate() {
+        memory.write_u32(0x8000_3000 + i as u32 * 4, *w).unwrap();
+    }
+    let mut ctx = CpuContext::new();
+
+    trace::start();
+    assert_eq!(
+        interpret_function(0x8000_3000, &mut ctx, &mut memory).unwrap(),
+        Some(55)
+    );
+    let original = trace::stop().expect("trace was running");
+    assert_eq!(original.len(), 3 + 3 * 10 + 1);
+    assert_eq!(original.entries()[0].registers.gpr[3], 0);
+    assert_eq!(original.entries().last().unwrap().registers.gpr[3], 55);
+
+    let dir = temp_dir("run");
+    let compact = original
+        .export_to_files(&dir, TraceFormat::Compact)
+        .unwrap();
+    let json = original.export_to_files(&dir, TraceFormat::Json).unwrap();
+    assert_eq!(RuntimeTracer::read_from_file(&compact).unwrap(), original);
+    assert_eq!(RuntimeTracer::read_from_file(&json).unwrap(), original);
+
+    let compact_size = std::fs::metadata(&compact).unwrap().len();
+    let json_size = std::fs::metadata(&json).unwrap().len();
+    assert!(
+        compact_size * 50 < json_size,
+        "compact {compact_size} bytes vs JSON {json_size}"
+    );
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn every_register_kind_survives_the_delta_encoding() {
+    let mut ctx = CpuContext::new();
+    let mut tracer = RuntimeTracer::new();
+    ctx.gpr[31] = 0xFFFF_FFFF;
+    ctx.fpr[1] = f64::NAN;
+    ctx.fpr[2] = -0.0;
+    tracer.record(0x8000_0000, 0x6000_0000, &ctx);
+    ctx.lr = 0x8000_1234;
+    ctx.cr = 0x2000_0000;
+    ctx.xer = 0x2000_0000;
+    ctx.fpscr = 0x0000_0004;
+    ctx.msr = 0x0000_8000;
+    ctx.ctr = 7;
+    tracer.record(0x8000_0004, 0x6000_0000, &ctx);
+    // Back to zero: a change to 0 must be stored too.
+    ctx.gpr[31] = 0;
+    ctx.fpr[1] = 0.0;
+    tracer.record(0x8000_0008, 0x6000_0000, &ctx);
+
+    let mut bytes = Vec::new();
+    tracer.write_compact(&mut bytes).unwrap();
+    let read = RuntimeTracer::read_compact(&mut bytes.as_slice()).unwrap();
+    assert_eq!(read, tracer);
+    assert_eq!(
+        read.entries()[1].registers,
+        RegisterState::from_context(&{
+            let mut c = ctx.clone();
+            c.gpr[31] = 0xFFFF_FFFF;
+            c.fpr[1] = f64::NAN;
+            c
+        })
+    );
+
+    // Truncated input is an error, not a short trace.
+    assert!(RuntimeTracer::read_compact(&mut &bytes[..bytes.len() - 1]).is_err());
+}
@@ -0,0 +1,56 @@
+//! The `wasm` feature's subset: no native-only crates, and it runs code.
+//!
+//! Built natively with `--no-default-features --features wasm` into its own
+//! target directory (the workspace one is busy with this test run).
+
+use std::process::Command;
+
+fn cargo(args: &[&str]) -> std::process::Output {
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+    let target = concat!(env!("CARGO_MANIFEST_DIR"), "/../target/wasm-subset");
+    Command::new(env!("CARGO"))
+        .args(args)
+        .args(["--manifest-path", manifest])
+        .args([
+            "-p",
+            "gcrecomp-core",
+            "--no-default-features",
+            "--features",
+            "wasm",
+        ])
+        .env("CARGO_TARGET_DIR", target)
+        .output()
+        .expect("running cargo")
+}
+
+#[test]
+fn wasm_subset_excludes_native_crates() {
+    let out = cargo(&["tree", "-e", "normal", "--prefix", "none"]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let tree = String::from_utf8_lossy(&out.stdout);
+    let crates: Vec<&str> = tree.lines().filter_map(|l| l.split(' ').next()).collect();
+
+    assert!(crates.contains(&"wasm-bindgen"), "{tree}");
+    for native in ["zstd", "dirs", "which", "wgpu", "winit", "cpal", "minifb"] {
+        assert!(
+            !crates.contains(&native),
+            "{native} in the wasm subset:\n{tree}"
+        );
+    }
+}
+
+#[test]
+fn wasm_subset_builds_and_runs_a_function() {
+    let out = cargo(&["test", "--lib", "wasm::"]);
+    assert!(
+        out.status.success(),
+        "{}\n{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8_lossy(&out.stdout).contains("runs_a_sample_function ... ok"));
+}
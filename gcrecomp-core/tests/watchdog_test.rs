@@ -0,0 +1,54 @@
+//! Loop budget tripping on runaway loops
+//!
+//! The budget is process-wide, so everything that changes it lives in one test.
+
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::interpret_function;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::watchdog::{self, ExecutionError};
+
+fn load(memory: &mut MemoryManager, address: u32, words: &[u32]) {
+    for (i, &w) in words.iter().enumerate() {
+        memory.write_u32(address + i as u32 * 4, w).unwrap();
+    }
+}
+
+#[test]
+fn test_infinite_loop_trips_watchdog_with_offending_pc() {
+    let mut memory = MemoryManager::new();
+    // li r3,1 ; b .  — never returns.
+    load(&mut memory, 0x8000_3000, &[0x3860_0001, 0x4800_0000]);
+    // li r3,100 ; mtctr r3 ; bdnz . ; blr  — 99 back-edges, then returns.
+    load(
+        &mut memory,
+        0x8000_3100,
+        &[0x3860_0064, 0x7C69_03A6, 0x4200_0000, 0x4E80_0020],
+    );
+
+    watchdog::set_loop_budget(Some(1000));
+    let mut ctx = CpuContext::new();
+    let err = interpret_function(0x8000_3000, &mut ctx, &mut memory).unwrap_err();
+    match err.downcast_ref::<ExecutionError>() {
+        Some(ExecutionError::Timeout { pc, steps, recent }) => {
+            assert_eq!(*pc, 0x8000_3004);
+            assert_eq!(*steps, 1001);
+            assert_eq!(recent.last(), Some(&0x8000_3004));
+            assert!(recent.len() <= watchdog::TRACE_RING_LEN);
+        }
+        _ => panic!("expected a timeout, got {err:#}"),
+    }
+    assert!(err.to_string().contains("0x80003004"), "{err}");
+
+    // A finite loop under the budget runs to completion...
+    let mut ctx = CpuContext::new();
+    assert!(interpret_function(0x8000_3100, &mut ctx, &mut memory).is_ok());
+
+    // ...and over it only trips while the check is enabled.
+    watchdog::set_loop_budget(Some(50));
+    let mut ctx = CpuContext::new();
+    assert!(interpret_function(0x8000_3100, &mut ctx, &mut memory).is_err());
+    watchdog::set_loop_budget(None);
+    let mut ctx = CpuContext::new();
+    assert!(interpret_function(0x8000_3100, &mut ctx, &mut memory).is_ok());
+    assert_eq!(ctx.ctr, 0);
+}
@@ -1,111 +1,139 @@
-use mlua::{Lua, Table, UserData, UserDataMethods};
-use std::sync::{Arc, Mutex};
+use mlua::{Lua, Table, UserData, UserDataFields, UserDataMethods, UserDataRef};
+use std::sync::{Arc, Mutex, MutexGuard};
 
+use gcrecomp_core::recompiler::decoder::{Instruction, Operand};
 use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter;
 
+use super::memory::LuaMemoryManager;
 use crate::error::IntoAnyhow;
 
 pub struct LuaCpuContext {
     pub inner: Arc<Mutex<CpuContext>>,
 }
 
+impl LuaCpuContext {
+    fn lock(&self) -> mlua::Result<MutexGuard<'_, CpuContext>> {
+        self.inner
+            .lock()
+            .map_err(|e| mlua::Error::external(e.to_string()))
+    }
+}
+
+/// Check a register/field index from Lua against `count`, so a typo raises
+/// an error instead of silently reading 0.
+fn index(kind: &str, i: i64, count: u8) -> mlua::Result<u8> {
+    if (0..count as i64).contains(&i) {
+        Ok(i as u8)
+    } else {
+        Err(mlua::Error::RuntimeError(format!(
+            "{kind} index {i} out of range (0-{})",
+            count - 1
+        )))
+    }
+}
+
+/// Readable/writable `u32` special registers as fields (`ctx.lr = x`).
+macro_rules! spr_accessors {
+    ($fields:ident, $($name:ident),*) => {
+        $(
+            $fields.add_field_method_get(stringify!($name), |_, this| Ok(this.lock()?.$name));
+            $fields.add_field_method_set(stringify!($name), |_, this, val: u32| {
+                this.lock()?.$name = val;
+                Ok(())
+            });
+        )*
+    };
+}
+
 impl UserData for LuaCpuContext {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        spr_accessors!(fields, pc, lr, ctr, cr, xer, msr);
+    }
+
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method("get_gpr", |_, this, reg: u8| {
-            let ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            Ok(ctx.get_register(reg))
+        methods.add_method("get_gpr", |_, this, reg: i64| {
+            let reg = index("GPR", reg, 32)?;
+            Ok(this.lock()?.get_register(reg))
         });
 
-        methods.add_method("set_gpr", |_, this, (reg, val): (u8, u32)| {
-            let mut ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            ctx.set_register(reg, val);
+        methods.add_method("set_gpr", |_, this, (reg, val): (i64, u32)| {
+            let reg = index("GPR", reg, 32)?;
+            this.lock()?.set_register(reg, val);
             Ok(())
         });
 
-        methods.add_method("get_fpr", |_, this, reg: u8| {
-            let ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            Ok(ctx.get_fpr(reg))
+        methods.add_method("get_fpr", |_, this, reg: i64| {
+            let reg = index("FPR", reg, 32)?;
+            Ok(this.lock()?.get_fpr(reg))
         });
 
-        methods.add_method("set_fpr", |_, this, (reg, val): (u8, f64)| {
-            let mut ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            ctx.set_fpr(reg, val);
+        methods.add_method("set_fpr", |_, this, (reg, val): (i64, f64)| {
+            let reg = index("FPR", reg, 32)?;
+            this.lock()?.set_fpr(reg, val);
             Ok(())
         });
 
-        methods.add_method("get_pc", |_, this, ()| {
-            let ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            Ok(ctx.pc)
-        });
+        methods.add_method("get_pc", |_, this, ()| Ok(this.lock()?.pc));
 
         methods.add_method("set_pc", |_, this, val: u32| {
-            let mut ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            ctx.pc = val;
+            this.lock()?.pc = val;
             Ok(())
         });
 
-        methods.add_method("get_lr", |_, this, ()| {
-            let ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            Ok(ctx.lr)
-        });
+        methods.add_method("get_lr", |_, this, ()| Ok(this.lock()?.lr));
 
         methods.add_method("set_lr", |_, this, val: u32| {
-            let mut ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            ctx.lr = val;
+            this.lock()?.lr = val;
             Ok(())
         });
 
-        methods.add_method("get_cr", |_, this, ()| {
-            let ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            Ok(ctx.cr)
+        methods.add_method("get_ctr", |_, this, ()| Ok(this.lock()?.ctr));
+
+        methods.add_method("set_ctr", |_, this, val: u32| {
+            this.lock()?.ctr = val;
+            Ok(())
         });
 
-        methods.add_method("get_cr_field", |_, this, field: u8| {
-            let ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            Ok(ctx.get_cr_field(field))
+        methods.add_method("get_cr", |_, this, ()| Ok(this.lock()?.cr));
+
+        methods.add_method("set_cr", |_, this, val: u32| {
+            this.lock()?.cr = val;
+            Ok(())
         });
 
-        methods.add_method("set_cr_field", |_, this, (field, val): (u8, u8)| {
-            let mut ctx = this
-                .inner
-                .lock()
-                .map_err(|e| mlua::Error::external(e.to_string()))?;
-            ctx.set_cr_field(field, val);
+        methods.add_method("get_cr_field", |_, this, field: i64| {
+            let field = index("CR field", field, 8)?;
+            Ok(this.lock()?.get_cr_field(field))
+        });
+
+        methods.add_method("set_cr_field", |_, this, (field, val): (i64, u8)| {
+            let field = index("CR field", field, 8)?;
+            this.lock()?.set_cr_field(field, val);
             Ok(())
         });
     }
 }
 
+/// `{kind = ..., value = ...}` for one decoded operand.
+fn operand_table(lua: &Lua, operand: &Operand) -> mlua::Result<Table> {
+    let (kind, value) = match *operand {
+        Operand::Register(r) => ("gpr", i64::from(r)),
+        Operand::FpRegister(r) => ("fpr", i64::from(r)),
+        Operand::Immediate(v) => ("immediate", i64::from(v)),
+        Operand::Immediate32(v) => ("immediate", i64::from(v)),
+        Operand::Address(a) => ("address", i64::from(a)),
+        Operand::Condition(c) => ("condition", i64::from(c)),
+        Operand::SpecialRegister(s) => ("spr", i64::from(s)),
+        Operand::ShiftAmount(s) => ("shift", i64::from(s)),
+        Operand::Mask(m) => ("mask", i64::from(m)),
+    };
+    let table = lua.create_table()?;
+    table.set("kind", kind)?;
+    table.set("value", value)?;
+    Ok(table)
+}
+
 pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     let cpu_table = lua.create_table().into_anyhow()?;
 
@@ -117,7 +145,128 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
         })
         .into_anyhow()?;
 
+    // Run the function at `address` under the interpreter (the code must be in
+    // `mem`); returns r3.
+    let run_fn = lua
+        .create_function(
+            |_,
+             (ctx, mem, address): (
+                UserDataRef<LuaCpuContext>,
+                UserDataRef<LuaMemoryManager>,
+                u32,
+            )| {
+                let mut ctx = ctx.lock()?;
+                let mut mem = mem
+                    .inner
+                    .lock()
+                    .map_err(|e| mlua::Error::external(e.to_string()))?;
+                interpreter::interpret_function(address, &mut ctx, &mut mem)
+                    .map_err(|e| mlua::Error::RuntimeError(format!("{:#}", e)))
+            },
+        )
+        .into_anyhow()?;
+
+    // Decode one instruction word: `{opcode, type, operands = {{kind, value}, ...},
+    // word, address, text}`, with `type` the decoder's category ("Arithmetic",
+    // "Load", "Branch", ...).
+    let decode_fn = lua
+        .create_function(|lua, (word, address): (u32, Option<u32>)| {
+            let address = address.unwrap_or(0);
+            let decoded = Instruction::decode(word, address).map_err(|e| {
+                mlua::Error::RuntimeError(format!(
+                    "cannot decode 0x{word:08X} at 0x{address:08X}: {e}"
+                ))
+            })?;
+            let operands = lua.create_sequence_from(
+                decoded
+                    .instruction
+                    .operands
+                    .iter()
+                    .map(|op| operand_table(lua, op))
+                    .collect::<mlua::Result<Vec<_>>>()?,
+            )?;
+            let table = lua.create_table()?;
+            table.set("opcode", decoded.instruction.opcode)?;
+            table.set(
+                "type",
+                format!("{:?}", decoded.instruction.instruction_type),
+            )?;
+            table.set("operands", operands)?;
+            table.set("word", word)?;
+            table.set("address", address)?;
+            table.set("text", decoded.to_string())?;
+            Ok(table)
+        })
+        .into_anyhow()?;
+
     cpu_table.set("new", new_fn).into_anyhow()?;
+    cpu_table.set("run", run_fn).into_anyhow()?;
+    cpu_table.set("decode", decode_fn).into_anyhow()?;
     gcrecomp.set("cpu", cpu_table).into_anyhow()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    #[test]
+    fn script_runs_add_function_and_reads_result() {
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.load(
+            r#"
+            local mem = gcrecomp.memory.new()
+            -- add r3,r3,r4 ; blr
+            mem:write_u32(0x80004000, 0x7C632214)
+            mem:write_u32(0x80004004, 0x4E800020)
+
+            local ctx = gcrecomp.cpu.new()
+            ctx:set_gpr(3, 40)
+            ctx:set_gpr(4, 2)
+            ctx.lr = 0x80001234
+            returned = gcrecomp.cpu.run(ctx, mem, 0x80004000)
+            r3 = ctx:get_gpr(3)
+            lr = ctx.lr
+            bad_index = select(2, pcall(ctx.get_gpr, ctx, 32))
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        let globals = lua.globals();
+        assert_eq!(globals.get::<u32>("r3").unwrap(), 42);
+        assert_eq!(globals.get::<u32>("returned").unwrap(), 42);
+        assert_eq!(globals.get::<u32>("lr").unwrap(), 0x8000_1234);
+        let err: String = globals
+            .get::<mlua::Value>("bad_index")
+            .unwrap()
+            .to_string()
+            .unwrap();
+        assert!(err.contains("GPR index 32 out of range (0-31)"), "{err}");
+    }
+
+    #[test]
+    fn script_decodes_an_instruction() {
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.load(
+            r#"
+            local inst = gcrecomp.cpu.decode(0x3864002A, 0x80003000)
+            assert(inst.type == "Arithmetic", inst.type)
+            assert(inst.opcode == 14)
+            assert(inst.address == 0x80003000)
+            -- addi r3,r4,42
+            assert(#inst.operands == 3)
+            assert(inst.operands[1].kind == "gpr" and inst.operands[1].value == 3)
+            assert(inst.operands[2].kind == "gpr" and inst.operands[2].value == 4)
+            assert(inst.operands[3].kind == "immediate" and inst.operands[3].value == 42)
+            text = inst.text
+            "#,
+        )
+        .exec()
+        .unwrap();
+        let text: String = lua.globals().get("text").unwrap();
+        assert!(text.starts_with("addi"), "{text}");
+    }
+}
@@ -0,0 +1,63 @@
+use mlua::{Lua, Table};
+use std::path::Path;
+
+use gcrecomp_core::logging;
+use log::LevelFilter;
+
+use crate::error::IntoAnyhow;
+
+fn parse_level(level: &str) -> mlua::Result<LevelFilter> {
+    level.parse().map_err(|_| {
+        mlua::Error::RuntimeError(format!(
+            "'{}' is not a log level (off, error, warn, info, debug, trace)",
+            level
+        ))
+    })
+}
+
+pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
+    let log_table = lua.create_table().into_anyhow()?;
+
+    // gcrecomp.log.set_level("debug") or
+    // gcrecomp.log.set_level("gcrecomp_runtime::graphics", "debug")
+    let set_level_fn = lua
+        .create_function(|_, (first, second): (String, Option<String>)| {
+            match second {
+                Some(level) => logging::set_module_level(&first, parse_level(&level)?),
+                None => logging::set_level(parse_level(&first)?),
+            }
+            Ok(())
+        })
+        .into_anyhow()?;
+
+    // gcrecomp.log.set_filter("warn,gcrecomp_core::recompiler=debug")
+    let set_filter_fn = lua
+        .create_function(|_, spec: String| {
+            logging::set_filter_spec(&spec).map_err(mlua::Error::RuntimeError)
+        })
+        .into_anyhow()?;
+
+    // gcrecomp.log.set_file("gcrecomp.log"), or nil to stop
+    let set_file_fn = lua
+        .create_function(|_, path: Option<String>| {
+            logging::set_file_sink(path.as_deref().map(Path::new)).map_err(mlua::Error::external)
+        })
+        .into_anyhow()?;
+
+    let level_fn = lua
+        .create_function(|_, module: String| {
+            Ok(logging::logger()
+                .filter()
+                .level_for(&module)
+                .to_string()
+                .to_lowercase())
+        })
+        .into_anyhow()?;
+
+    log_table.set("set_level", set_level_fn).into_anyhow()?;
+    log_table.set("set_filter", set_filter_fn).into_anyhow()?;
+    log_table.set("set_file", set_file_fn).into_anyhow()?;
+    log_table.set("level", level_fn).into_anyhow()?;
+    gcrecomp.set("log", log_table).into_anyhow()?;
+    Ok(())
+}
@@ -1,7 +1,8 @@
-use mlua::{Lua, Table, UserData, UserDataMethods};
+use mlua::{Lua, Table, UserData, UserDataMethods, UserDataRef};
 use std::sync::{Arc, Mutex};
 
 use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::memsearch::{MemorySearch, SearchFilter, ValueWidth};
 
 use crate::error::IntoAnyhow;
 
@@ -9,6 +10,31 @@ pub struct LuaMemoryManager {
     pub inner: Arc<Mutex<MemoryManager>>,
 }
 
+/// The Lua state's own memory, behind the `gcrecomp.memory.read_*`/`write_*`
+/// functions, so a script can set up and check RAM without making one.
+struct EngineMemory(Arc<Mutex<MemoryManager>>);
+
+/// The memory the Lua state owns (see `gcrecomp.memory.shared`).
+pub(crate) fn engine_memory(lua: &Lua) -> mlua::Result<Arc<Mutex<MemoryManager>>> {
+    lua.app_data_ref::<EngineMemory>()
+        .map(|m| m.0.clone())
+        .ok_or_else(|| mlua::Error::RuntimeError("memory bindings not registered".to_string()))
+}
+
+/// Run `f` on the engine memory, reporting a failed access at `addr` as a
+/// Lua error.
+fn with_engine_memory<R>(
+    lua: &Lua,
+    addr: u32,
+    f: impl FnOnce(&mut MemoryManager) -> anyhow::Result<R>,
+) -> mlua::Result<R> {
+    let memory = engine_memory(lua)?;
+    let mut mem = memory
+        .lock()
+        .map_err(|e| mlua::Error::external(e.to_string()))?;
+    f(&mut mem).map_err(|e| mlua::Error::RuntimeError(format!("memory at 0x{addr:08X}: {e:#}")))
+}
+
 impl UserData for LuaMemoryManager {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("read_u8", |_, this, addr: u32| {
@@ -94,6 +120,46 @@ impl UserData for LuaMemoryManager {
     }
 }
 
+/// A cheat search over a memory's MEM1 (`gcrecomp.memory.search(width)`).
+pub struct LuaMemorySearch {
+    pub inner: MemorySearch,
+}
+
+impl UserData for LuaMemorySearch {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method_mut("reset", |_, this, mem: UserDataRef<LuaMemoryManager>| {
+            let mem = mem
+                .inner
+                .lock()
+                .map_err(|e| mlua::Error::external(e.to_string()))?;
+            this.inner.reset(&mem);
+            Ok(())
+        });
+
+        // search:filter(mem, "exact" | "greater" | "less" | "changed" | "unchanged", value)
+        methods.add_method_mut(
+            "filter",
+            |_, this, (mem, kind, value): (UserDataRef<LuaMemoryManager>, String, Option<f64>)| {
+                let filter = SearchFilter::parse(&kind, value).map_err(mlua::Error::external)?;
+                let mem = mem
+                    .inner
+                    .lock()
+                    .map_err(|e| mlua::Error::external(e.to_string()))?;
+                Ok(this.inner.filter(&mem, filter))
+            },
+        );
+
+        methods.add_method("candidates", |_, this, ()| {
+            Ok(this.inner.candidates().to_vec())
+        });
+        methods.add_method("count", |_, this, ()| Ok(this.inner.count()));
+        methods.add_method("value_at", |_, this, addr: u32| {
+            Ok(this.inner.value_at(addr))
+        });
+        methods.add_method("width", |_, this, ()| Ok(this.inner.width().to_string()));
+    }
+}
+
 pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     let memory_table = lua.create_table().into_anyhow()?;
 
@@ -106,6 +172,127 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
         .into_anyhow()?;
 
     memory_table.set("new", new_fn).into_anyhow()?;
+
+    // gcrecomp.memory.read_u32(addr) / write_u32(addr, val) /
+    // read_bytes(addr, len) / write_bytes(addr, {bytes}) on the state's own
+    // memory. Cached (0x80...) and uncached (0xC0...) addresses reach the
+    // same RAM.
+    lua.set_app_data(EngineMemory(Arc::new(Mutex::new(MemoryManager::new()))));
+    let shared_fn = lua
+        .create_function(|lua, ()| {
+            Ok(LuaMemoryManager {
+                inner: engine_memory(lua)?,
+            })
+        })
+        .into_anyhow()?;
+    let read_u32_fn = lua
+        .create_function(|lua, addr: u32| with_engine_memory(lua, addr, |mem| mem.read_u32(addr)))
+        .into_anyhow()?;
+    let write_u32_fn = lua
+        .create_function(|lua, (addr, val): (u32, u32)| {
+            with_engine_memory(lua, addr, |mem| mem.write_u32(addr, val))
+        })
+        .into_anyhow()?;
+    let read_bytes_fn = lua
+        .create_function(|lua, (addr, len): (u32, usize)| {
+            with_engine_memory(lua, addr, |mem| mem.read_bytes(addr, len))
+        })
+        .into_anyhow()?;
+    let write_bytes_fn = lua
+        .create_function(|lua, (addr, data): (u32, Vec<u8>)| {
+            with_engine_memory(lua, addr, |mem| mem.write_bytes(addr, &data))
+        })
+        .into_anyhow()?;
+    memory_table.set("shared", shared_fn).into_anyhow()?;
+    memory_table.set("read_u32", read_u32_fn).into_anyhow()?;
+    memory_table.set("write_u32", write_u32_fn).into_anyhow()?;
+    memory_table
+        .set("read_bytes", read_bytes_fn)
+        .into_anyhow()?;
+    memory_table
+        .set("write_bytes", write_bytes_fn)
+        .into_anyhow()?;
+
+    let search_fn = lua
+        .create_function(|_, width: Option<String>| {
+            let width = match width {
+                Some(width) => width.parse().map_err(mlua::Error::external)?,
+                None => ValueWidth::default(),
+            };
+            Ok(LuaMemorySearch {
+                inner: MemorySearch::new(width),
+            })
+        })
+        .into_anyhow()?;
+    memory_table.set("search", search_fn).into_anyhow()?;
     gcrecomp.set("memory", memory_table).into_anyhow()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    #[test]
+    fn script_narrows_a_search() {
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.load(
+            r#"
+            local mem = gcrecomp.memory.new()
+            mem:write_u32(0x80123450, 99)
+            mem:write_u32(0x80400000, 99)
+            local search = gcrecomp.memory.search("u32")
+            first = search:filter(mem, "exact", 99)
+            mem:write_u32(0x80400000, 100)
+            second = search:filter(mem, "unchanged")
+            found = search:candidates()[1]
+            bad = select(2, pcall(search.filter, search, mem, "bigger", 1))
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        let globals = lua.globals();
+        assert_eq!(globals.get::<usize>("first").unwrap(), 2);
+        assert_eq!(globals.get::<usize>("second").unwrap(), 1);
+        assert_eq!(globals.get::<u32>("found").unwrap(), 0x8012_3450);
+        let err: String = globals
+            .get::<mlua::Value>("bad")
+            .unwrap()
+            .to_string()
+            .unwrap();
+        assert!(err.contains("unknown filter 'bigger'"), "{err}");
+    }
+
+    #[test]
+    fn script_pokes_and_reads_engine_memory() {
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.load(
+            r#"
+            gcrecomp.memory.write_u32(0x80001000, 0xDEADBEEF)
+            word = gcrecomp.memory.read_u32(0x80001000)
+            uncached = gcrecomp.memory.read_u32(0xC0001000)
+            gcrecomp.memory.write_bytes(0xC0002000, {1, 2, 3, 4})
+            bytes = gcrecomp.memory.read_bytes(0x80002000, 4)
+            via_handle = gcrecomp.memory.shared():read_u32(0x80002000)
+            bad = select(2, pcall(gcrecomp.memory.read_u32, 0x90000000))
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        let globals = lua.globals();
+        assert_eq!(globals.get::<u32>("word").unwrap(), 0xDEAD_BEEF);
+        assert_eq!(globals.get::<u32>("uncached").unwrap(), 0xDEAD_BEEF);
+        assert_eq!(globals.get::<Vec<u8>>("bytes").unwrap(), vec![1, 2, 3, 4]);
+        assert_eq!(globals.get::<u32>("via_handle").unwrap(), 0x0102_0304);
+        let err: String = globals
+            .get::<mlua::Value>("bad")
+            .unwrap()
+            .to_string()
+            .unwrap();
+        assert!(err.contains("memory at 0x90000000"), "{err}");
+    }
+}
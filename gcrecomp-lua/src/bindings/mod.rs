@@ -2,6 +2,7 @@ pub mod callbacks;
 pub mod config;
 pub mod cpu;
 pub mod disc_fs;
+pub mod log;
 pub mod memory;
 pub mod optimize;
 pub mod pipeline;
@@ -26,6 +27,7 @@ pub fn register_all(lua: &Lua) -> anyhow::Result<()> {
     optimize::register(lua, &gcrecomp)?;
     runtime::register(lua, &gcrecomp)?;
     web::register(lua, &gcrecomp)?;
+    log::register(lua, &gcrecomp)?;
 
     lua.globals().set("gcrecomp", gcrecomp).into_anyhow()?;
     Ok(())
@@ -1,10 +1,117 @@
 use mlua::{Lua, Table};
 use std::path::Path;
 
+use gcrecomp_core::recompiler::decoder::Instruction;
+use gcrecomp_core::recompiler::optimizer::{OptLevel, Optimizer, OptimizerStats};
+
 use crate::error::IntoAnyhow;
 
+/// Per-Lua-state optimizer settings for the next `gcrecomp.pipeline.recompile`,
+/// and the counts from the last one.
+struct OptimizeState {
+    optimizer: Optimizer,
+    last: OptimizerStats,
+}
+
+/// The optimizer `gcrecomp.pipeline.recompile` should use.
+pub(crate) fn configured_optimizer(lua: &Lua) -> Optimizer {
+    lua.app_data_ref::<OptimizeState>()
+        .map(|s| s.optimizer.clone())
+        .unwrap_or_else(|| Optimizer::with_level(OptLevel::None))
+}
+
+pub(crate) fn record_stats(lua: &Lua, stats: OptimizerStats) {
+    if let Some(mut state) = lua.app_data_mut::<OptimizeState>() {
+        state.last = stats;
+    }
+}
+
+fn state(lua: &Lua) -> mlua::Result<mlua::AppDataRefMut<'_, OptimizeState>> {
+    lua.app_data_mut::<OptimizeState>()
+        .ok_or_else(|| mlua::Error::RuntimeError("optimize bindings not registered".to_string()))
+}
+
 pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     let optimize_table = lua.create_table().into_anyhow()?;
+    lua.set_app_data(OptimizeState {
+        optimizer: Optimizer::with_level(OptLevel::None),
+        last: OptimizerStats::default(),
+    });
+
+    let set_level_fn = lua
+        .create_function(|lua, level: String| {
+            let level: OptLevel = level.parse().map_err(mlua::Error::RuntimeError)?;
+            state(lua)?.optimizer = Optimizer::with_level(level);
+            Ok(())
+        })
+        .into_anyhow()?;
+
+    let set_pass_fn = lua
+        .create_function(|lua, (name, enabled): (String, bool)| {
+            if !state(lua)?.optimizer.set_pass(&name, enabled) {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "unknown optimization pass '{}' (known: {})",
+                    name,
+                    Optimizer::PASSES.join(", ")
+                )));
+            }
+            Ok(())
+        })
+        .into_anyhow()?;
+
+    let get_passes_fn = lua
+        .create_function(|lua, ()| {
+            let table = lua.create_table()?;
+            let state = state(lua)?;
+            for &name in Optimizer::PASSES {
+                table.set(name, state.optimizer.pass_enabled(name).unwrap_or(false))?;
+            }
+            Ok(table)
+        })
+        .into_anyhow()?;
+
+    let get_stats_fn = lua
+        .create_function(|lua, ()| {
+            let last = state(lua)?.last;
+            let table = lua.create_table()?;
+            table.set("folded_constants", last.folded_constants)?;
+            table.set("eliminated_instructions", last.eliminated_instructions)?;
+            table.set("unrolled_loops", last.unrolled_loops)?;
+            table.set("hoisted_instructions", last.hoisted_instructions)?;
+            table.set("eliminated_loads", last.eliminated_loads)?;
+            Ok(table)
+        })
+        .into_anyhow()?;
+
+    // gcrecomp.optimize.run({words...}, address) → optimized words, with the
+    // current level and passes. `address` (default 0x80000000) is where the
+    // first word sits, for branch targets.
+    let run_fn = lua
+        .create_function(|lua, (words, address): (Vec<u32>, Option<u32>)| {
+            let base = address.unwrap_or(0x8000_0000);
+            let instructions = words
+                .iter()
+                .enumerate()
+                .map(|(i, &word)| {
+                    let address = base.wrapping_add(i as u32 * 4);
+                    Instruction::decode(word, address).map_err(|e| {
+                        mlua::Error::RuntimeError(format!(
+                            "cannot decode 0x{word:08X} at 0x{address:08X}: {e}"
+                        ))
+                    })
+                })
+                .collect::<mlua::Result<Vec<_>>>()?;
+            let mut optimizer = state(lua)?.optimizer.clone();
+            optimizer.take_stats();
+            let optimized: Vec<u32> = optimizer
+                .optimize(&instructions)
+                .iter()
+                .map(|inst| inst.raw)
+                .collect();
+            state(lua)?.last = optimizer.take_stats();
+            Ok(optimized)
+        })
+        .into_anyhow()?;
 
     let dce_fn = lua
         .create_function(|_, path: String| {
@@ -88,6 +195,17 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
         .into_anyhow()?;
 
     optimize_table.set("dce", dce_fn).into_anyhow()?;
+    optimize_table
+        .set("set_level", set_level_fn)
+        .into_anyhow()?;
+    optimize_table.set("set_pass", set_pass_fn).into_anyhow()?;
+    optimize_table.set("run", run_fn).into_anyhow()?;
+    optimize_table
+        .set("get_passes", get_passes_fn)
+        .into_anyhow()?;
+    optimize_table
+        .set("get_stats", get_stats_fn)
+        .into_anyhow()?;
     optimize_table
         .set("strip_comments", strip_comments_fn)
         .into_anyhow()?;
@@ -97,3 +215,98 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     gcrecomp.set("optimize", optimize_table).into_anyhow()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    #[test]
+    fn disabling_dce_generates_more_instructions() {
+        let dir =
+            std::env::temp_dir().join(format!("gcrecomp_lua_optimize_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dol = dir.join("test.dol");
+        // li r4,1 ; li r4,2 ; li r5,3 ; li r5,4 ; li r3,0 ; blr
+        let words = [
+            0x3880_0001,
+            0x3880_0002,
+            0x38A0_0003,
+            0x38A0_0004,
+            0x3860_0000,
+            0x4E80_0020,
+        ];
+        std::fs::write(&dol, crate::bindings::pipeline::tests::test_dol(&words)).unwrap();
+
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.globals()
+            .set("dol_path", dol.to_str().unwrap())
+            .unwrap();
+        lua.globals()
+            .set("out_path", dir.join("out.rs").to_str().unwrap())
+            .unwrap();
+        lua.load(
+            r#"
+            gcrecomp.optimize.set_level("aggressive")
+            gcrecomp.optimize.set_pass("dead_code_elimination", false)
+            without_dce = gcrecomp.pipeline.recompile{dol = dol_path, out = out_path}.generated_instructions
+            gcrecomp.optimize.set_pass("dead_code_elimination", true)
+            with_dce = gcrecomp.pipeline.recompile{dol = dol_path, out = out_path}.generated_instructions
+            eliminated = gcrecomp.optimize.get_stats().eliminated_instructions
+            bad_pass = not pcall(gcrecomp.optimize.set_pass, "no_such_pass", true)
+            "#,
+        )
+        .exec()
+        .unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+
+        let globals = lua.globals();
+        let without: usize = globals.get("without_dce").unwrap();
+        let with: usize = globals.get("with_dce").unwrap();
+        assert_eq!(without, 6);
+        assert!(with < without, "{with} >= {without}");
+        assert_eq!(globals.get::<usize>("eliminated").unwrap(), without - with);
+        assert!(globals.get::<bool>("bad_pass").unwrap());
+    }
+
+    #[test]
+    fn basic_level_folds_a_two_instruction_sequence() {
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.load(
+            r#"
+            -- li r3,5 ; addi r3,r3,2
+            local input = {0x38600005, 0x38630002}
+            gcrecomp.optimize.set_level("none")
+            unchanged = gcrecomp.optimize.run(input)
+            gcrecomp.optimize.set_level("basic")
+            folded = gcrecomp.optimize.run(input)
+            count = gcrecomp.optimize.get_stats().folded_constants
+            bad_level = select(2, pcall(gcrecomp.optimize.set_level, "extreme"))
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        let globals = lua.globals();
+        assert_eq!(
+            globals.get::<Vec<u32>>("unchanged").unwrap(),
+            vec![0x3860_0005, 0x3863_0002]
+        );
+        // li r3,7
+        assert_eq!(
+            globals.get::<Vec<u32>>("folded").unwrap(),
+            vec![0x3860_0005, 0x3860_0007]
+        );
+        assert_eq!(globals.get::<usize>("count").unwrap(), 1);
+        let err: String = globals
+            .get::<mlua::Value>("bad_level")
+            .unwrap()
+            .to_string()
+            .unwrap();
+        assert!(
+            err.contains("unknown optimization level 'extreme'"),
+            "{err}"
+        );
+    }
+}
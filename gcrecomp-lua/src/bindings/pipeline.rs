@@ -1,7 +1,17 @@
 use mlua::{Lua, Table, UserData, UserDataMethods};
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
-use gcrecomp_core::recompiler::pipeline::{PipelineContext, RecompilationPipeline};
+use gcrecomp_core::recompiler::analysis::similarity::SimilarityIndex;
+use gcrecomp_core::recompiler::codegen::sda::SdaBases;
+use gcrecomp_core::recompiler::error::RecompileError;
+use gcrecomp_core::recompiler::fidb::FidDatabase;
+use gcrecomp_core::recompiler::parser::DolFile;
+use gcrecomp_core::recompiler::pipeline::{
+    PipelineContext, RecompilationPipeline, RecompileOptions, RecompileSummary,
+};
+use gcrecomp_core::recompiler::profile::HotProfile;
+use gcrecomp_core::recompiler::symbols::SymbolMap;
 
 use crate::error::IntoAnyhow;
 
@@ -105,11 +115,145 @@ impl UserData for LuaPipelineContext {
             table.set("successful_functions", ctx.stats.successful_functions)?;
             table.set("failed_functions", ctx.stats.failed_functions)?;
             table.set("total_instructions", ctx.stats.total_instructions)?;
+            table.set("interpreted_functions", ctx.stats.interpreted_functions)?;
             Ok(table)
         });
     }
 }
 
+/// Recompiler errors become Lua errors carrying the `RecompileError` message.
+fn recompile_error(err: RecompileError) -> mlua::Error {
+    mlua::Error::RuntimeError(err.to_string())
+}
+
+fn load_dol(path: &str) -> mlua::Result<DolFile> {
+    let data = std::fs::read(path).map_err(|e| recompile_error(e.into()))?;
+    DolFile::parse(&data, path).map_err(recompile_error)
+}
+
+fn required(args: &Table, key: &str) -> mlua::Result<String> {
+    args.get::<Option<String>>(key)?
+        .ok_or_else(|| mlua::Error::RuntimeError(format!("missing required field '{key}'")))
+}
+
+/// `gcrecomp.pipeline.recompile{dol=, out=, symbols=, opt_level=, profile_guided=, hierarchical=, fidb=, native_bsim=, sda_base=, sda2_base=, struct_accessors=, report=, jobs=, only=, cache=}`
+fn recompile(lua: &Lua, args: Table) -> mlua::Result<Table> {
+    let dol_path = required(&args, "dol")?;
+    let out = required(&args, "out")?;
+    if args.get::<Option<bool>>("hierarchical")?.unwrap_or(false) {
+        return Err(mlua::Error::RuntimeError(
+            "hierarchical recompilation is not supported".to_string(),
+        ));
+    }
+    let symbols = match args.get::<Option<String>>("symbols")? {
+        Some(path) => {
+            let text = std::fs::read_to_string(&path).map_err(|e| recompile_error(e.into()))?;
+            Some(SymbolMap::parse(&text))
+        }
+        None => None,
+    };
+    let profile = match args.get::<Option<String>>("profile_guided")? {
+        Some(path) => Some(
+            HotProfile::load(Path::new(&path))
+                .map_err(|e| mlua::Error::RuntimeError(format!("{e:#}")))?,
+        ),
+        None => None,
+    };
+    let fidb = match args.get::<Option<String>>("fidb")? {
+        Some(path) => Some(
+            FidDatabase::load(Path::new(&path))
+                .map_err(|e| mlua::Error::RuntimeError(format!("{e:#}")))?,
+        ),
+        None => None,
+    };
+    // native_bsim=true: fuzzy-match the fidb signatures too.
+    let similarity = match &fidb {
+        Some(db) if args.get::<Option<bool>>("native_bsim")?.unwrap_or(false) => {
+            Some(SimilarityIndex::from_database(db))
+        }
+        _ => None,
+    };
+    // sda_base=: r13 at boot; r2 defaults to the same unless sda2_base= says otherwise.
+    let sda_bases = match args.get::<Option<u32>>("sda_base")? {
+        Some(sda) => Some(SdaBases {
+            sda,
+            sda2: args.get::<Option<u32>>("sda2_base")?.unwrap_or(sda),
+        }),
+        None => None,
+    };
+    let options = RecompileOptions {
+        symbols,
+        fidb,
+        similarity,
+        optimize: args.get::<Option<u32>>("opt_level")?.unwrap_or(1) > 0,
+        optimizer: super::optimize::configured_optimizer(lua),
+        profile,
+        sda_bases,
+        struct_accessors: args
+            .get::<Option<bool>>("struct_accessors")?
+            .unwrap_or(false),
+        report_dir: args.get::<Option<String>>("report")?.map(Into::into),
+        jobs: args.get::<Option<usize>>("jobs")?,
+        // only={0x80003100, ...}: those functions and their direct callees.
+        only: args.get::<Option<Vec<u32>>>("only")?,
+        cache_dir: args.get::<Option<String>>("cache")?.map(Into::into),
+        progress: None,
+    };
+
+    let dol = load_dol(&dol_path)?;
+    let summary = RecompilationPipeline::recompile_with_options(&dol, &out, &options)
+        .map_err(recompile_error)?;
+    super::optimize::record_stats(lua, summary.optimizer);
+    summary_table(lua, &summary, &out)
+}
+
+fn summary_table(lua: &Lua, summary: &RecompileSummary, out: &str) -> mlua::Result<Table> {
+    let stats = &summary.stats;
+    let table = lua.create_table()?;
+    table.set("output", out)?;
+    table.set("functions", stats.total_functions)?;
+    table.set("successful_functions", stats.successful_functions)?;
+    table.set("failed_functions", stats.failed_functions)?;
+    table.set("interpreted_functions", stats.interpreted_functions)?;
+    table.set("instructions", stats.total_instructions)?;
+    table.set("generated_instructions", stats.generated_instructions)?;
+    table.set("cache_hits", stats.cache_hits)?;
+    table.set("cache_misses", stats.cache_misses)?;
+    table.set(
+        "unknown_instructions",
+        summary.coverage.unknown_instructions,
+    )?;
+    table.set("coverage", summary.coverage.translated_percent())?;
+    Ok(table)
+}
+
+/// `gcrecomp.pipeline.analyze{dol=}`: function discovery and coverage, no codegen.
+fn analyze(lua: &Lua, args: Table) -> mlua::Result<Table> {
+    let dol = load_dol(&required(&args, "dol")?)?;
+    let (facts, report) = RecompilationPipeline::analyze(&dol).map_err(recompile_error)?;
+
+    let table = lua.create_table()?;
+    table.set("functions", report.functions)?;
+    table.set("leaf_functions", report.leaf_functions)?;
+    table.set("functions_with_loops", report.functions_with_loops)?;
+    table.set("instructions", report.total_instructions)?;
+    table.set("translated_instructions", report.translated_instructions)?;
+    table.set("coverage", report.instruction_coverage() as f64 * 100.0)?;
+    let list = lua.create_table()?;
+    for (i, f) in facts.iter().enumerate() {
+        let entry = lua.create_table()?;
+        entry.set("address", f.address)?;
+        entry.set("name", f.name.as_str())?;
+        entry.set("size", f.byte_size)?;
+        entry.set("instructions", f.instruction_count)?;
+        entry.set("is_leaf", f.is_leaf)?;
+        entry.set("coverage", f.coverage as f64 * 100.0)?;
+        list.set(i + 1, entry)?;
+    }
+    table.set("function_list", list)?;
+    Ok(table)
+}
+
 pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     let pipeline_table = lua.create_table().into_anyhow()?;
 
@@ -131,9 +275,80 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     pipeline_table
         .set("new_context", new_context_fn)
         .into_anyhow()?;
+    pipeline_table
+        .set("recompile", lua.create_function(recompile).into_anyhow()?)
+        .into_anyhow()?;
+    pipeline_table
+        .set("analyze", lua.create_function(analyze).into_anyhow()?)
+        .into_anyhow()?;
     pipeline_table
         .set("embed_assets", embed_assets_fn)
         .into_anyhow()?;
     gcrecomp.set("pipeline", pipeline_table).into_anyhow()?;
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use mlua::Lua;
+
+    /// A DOL whose single text section at 0x80003000 (also the entry) holds `words`.
+    pub(crate) fn test_dol(words: &[u32]) -> Vec<u8> {
+        let mut dol = vec![0u8; 0x100];
+        dol[0x00..0x04].copy_from_slice(&0x100u32.to_be_bytes());
+        dol[0x48..0x4C].copy_from_slice(&0x8000_3000u32.to_be_bytes());
+        dol[0x90..0x94].copy_from_slice(&(words.len() as u32 * 4).to_be_bytes());
+        dol[0xE0..0xE4].copy_from_slice(&0x8000_3000u32.to_be_bytes());
+        for w in words {
+            dol.extend_from_slice(&w.to_be_bytes());
+        }
+        dol
+    }
+
+    #[test]
+    fn recompile_from_lua_returns_function_count() {
+        let dir =
+            std::env::temp_dir().join(format!("gcrecomp_lua_pipeline_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let dol = dir.join("test.dol");
+        // Two functions: `li r3,1 ; blr` and `li r3,2 ; blr`.
+        let words = [0x3860_0001, 0x4E80_0020, 0x3860_0002, 0x4E80_0020];
+        std::fs::write(&dol, test_dol(&words)).unwrap();
+
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.globals()
+            .set("dol_path", dol.to_str().unwrap())
+            .unwrap();
+        lua.globals()
+            .set("out_path", dir.join("out.rs").to_str().unwrap())
+            .unwrap();
+        lua.load(
+            r#"
+            local r = gcrecomp.pipeline.recompile{dol = dol_path, out = out_path, opt_level = 2}
+            functions = r.functions
+            coverage = r.coverage
+            analyzed = gcrecomp.pipeline.analyze{dol = dol_path}.functions
+            "#,
+        )
+        .exec()
+        .unwrap();
+        let globals = lua.globals();
+        assert_eq!(globals.get::<usize>("functions").unwrap(), 2);
+        assert_eq!(globals.get::<f64>("coverage").unwrap(), 100.0);
+        assert_eq!(globals.get::<usize>("analyzed").unwrap(), 2);
+        assert!(dir.join("coverage.json").exists());
+
+        // A malformed DOL surfaces the recompiler's error message.
+        std::fs::write(&dol, [0u8; 16]).unwrap();
+        let err = lua
+            .load("gcrecomp.pipeline.recompile{dol = dol_path, out = out_path}")
+            .exec()
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Parse error: DOL file too small"),
+            "{err}"
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,8 +1,85 @@
 /// Runtime Lua bindings — expose runtime state to Lua scripts.
-use mlua::{Lua, Table};
+use mlua::{Lua, Table, Value};
+use std::sync::{LazyLock, Mutex};
 
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter;
+use gcrecomp_core::runtime::memory::MemoryManager;
+
+use super::memory::engine_memory;
 use crate::error::IntoAnyhow;
 
+/// Screenshot paths requested from Lua. The game loop owns the renderer, so it
+/// drains this queue once per frame and does the actual capture.
+static SCREENSHOT_REQUESTS: LazyLock<Mutex<Vec<String>>> = LazyLock::new(|| Mutex::new(Vec::new()));
+
+/// Take all pending `gcrecomp.runtime.screenshot(path)` requests.
+pub fn take_screenshot_requests() -> Vec<String> {
+    SCREENSHOT_REQUESTS
+        .lock()
+        .map(|mut q| std::mem::take(&mut *q))
+        .unwrap_or_default()
+}
+
+/// Set registers from `{r3 = 1, lr = 0x80001234, ...}`: `r0`-`r31`, `lr`,
+/// `ctr`, `cr` and `xer`.
+fn set_registers(ctx: &mut CpuContext, regs: Table) -> mlua::Result<()> {
+    for pair in regs.pairs::<String, u32>() {
+        let (name, value) = pair?;
+        match name.as_str() {
+            "lr" => ctx.lr = value,
+            "ctr" => ctx.ctr = value,
+            "cr" => ctx.cr = value,
+            "xer" => ctx.xer = value,
+            _ => match name.strip_prefix('r').and_then(|n| n.parse::<u8>().ok()) {
+                Some(reg) if reg < 32 => ctx.set_register(reg, value),
+                _ => {
+                    return Err(mlua::Error::RuntimeError(format!(
+                        "unknown register '{name}' (expected r0-r31, lr, ctr, cr or xer)"
+                    )))
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Write `{[addr] = word | {bytes...}, ...}` into `memory`.
+fn initialize_memory(memory: &mut MemoryManager, init: Table) -> mlua::Result<()> {
+    for pair in init.pairs::<u32, Value>() {
+        let (addr, value) = pair?;
+        let written = match value {
+            Value::Table(bytes) => memory.write_bytes(
+                addr,
+                &bytes.sequence_values().collect::<mlua::Result<Vec<u8>>>()?,
+            ),
+            Value::Integer(word) => memory.write_u32(addr, word as u32),
+            other => {
+                return Err(mlua::Error::RuntimeError(format!(
+                    "memory at 0x{addr:08X}: expected a word or a table of bytes, got {}",
+                    other.type_name()
+                )))
+            }
+        };
+        written.map_err(|e| mlua::Error::RuntimeError(format!("memory at 0x{addr:08X}: {e:#}")))?;
+    }
+    Ok(())
+}
+
+/// `{returned = r3 or nil, r0 = ..., ..., r31 = ..., lr, ctr, cr, xer}`.
+fn registers_table(lua: &Lua, ctx: &CpuContext, returned: Option<u32>) -> mlua::Result<Table> {
+    let table = lua.create_table()?;
+    table.set("returned", returned)?;
+    for reg in 0..32u8 {
+        table.set(format!("r{reg}"), ctx.get_register(reg))?;
+    }
+    table.set("lr", ctx.lr)?;
+    table.set("ctr", ctx.ctr)?;
+    table.set("cr", ctx.cr)?;
+    table.set("xer", ctx.xer)?;
+    Ok(table)
+}
+
 pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     let runtime_table = lua.create_table().into_anyhow()?;
 
@@ -35,7 +112,53 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     // gcrecomp.runtime.is_running() → boolean
     let is_running_fn = lua.create_function(|_, ()| Ok(true)).into_anyhow()?;
 
+    // gcrecomp.runtime.screenshot(path) — saved at the end of the current frame
+    let screenshot_fn = lua
+        .create_function(|_, path: String| {
+            if let Ok(mut q) = SCREENSHOT_REQUESTS.lock() {
+                q.push(path);
+            }
+            Ok(())
+        })
+        .into_anyhow()?;
+
+    // gcrecomp.runtime.call(address, regs, memory_init) → registers table.
+    // Runs the function through the registered dispatcher (interpreting it
+    // without one) on the state's memory (`gcrecomp.memory.read_u32` etc.),
+    // after applying `memory_init`.
+    let call_fn = lua
+        .create_function(
+            |lua, (address, regs, init): (u32, Option<Table>, Option<Table>)| {
+                let memory = engine_memory(lua)?;
+                let mut memory = memory
+                    .lock()
+                    .map_err(|e| mlua::Error::external(e.to_string()))?;
+                if let Some(init) = init {
+                    initialize_memory(&mut memory, init)?;
+                }
+                let mut ctx = CpuContext::new();
+                if let Some(regs) = regs {
+                    set_registers(&mut ctx, regs)?;
+                }
+                let returned = gcrecomp_core::runtime::trampoline(
+                    address,
+                    &mut ctx,
+                    &mut memory,
+                    interpreter::call_function,
+                )
+                .map_err(|e| {
+                    mlua::Error::RuntimeError(format!("call to 0x{address:08X} failed: {e:#}"))
+                })?;
+                registers_table(lua, &ctx, returned)
+            },
+        )
+        .into_anyhow()?;
+
+    runtime_table.set("call", call_fn).into_anyhow()?;
     runtime_table.set("get_fps", get_fps_fn).into_anyhow()?;
+    runtime_table
+        .set("screenshot", screenshot_fn)
+        .into_anyhow()?;
     runtime_table
         .set("get_controller_count", get_controller_count_fn)
         .into_anyhow()?;
@@ -52,3 +175,46 @@ pub fn register(lua: &Lua, gcrecomp: &Table) -> anyhow::Result<()> {
     gcrecomp.set("runtime", runtime_table).into_anyhow()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use mlua::Lua;
+
+    #[test]
+    fn script_calls_an_identity_function() {
+        let lua = Lua::new();
+        crate::bindings::register_all(&lua).unwrap();
+        lua.load(
+            r#"
+            -- blr: returns r3 as it came in
+            local result = gcrecomp.runtime.call(0x80004000, {r3 = 1234, r4 = 5}, {
+                [0x80004000] = 0x4E800020,
+            })
+            r3 = result.r3
+            r4 = result.r4
+            returned = result.returned
+
+            -- li r3,7 ; blr, given as bytes
+            local seven = gcrecomp.runtime.call(0x80004100, nil, {
+                [0x80004100] = {0x38, 0x60, 0x00, 0x07, 0x4E, 0x80, 0x00, 0x20},
+            })
+            returned_seven = seven.returned
+            bad = select(2, pcall(gcrecomp.runtime.call, 0x80004000, {r32 = 1}))
+            "#,
+        )
+        .exec()
+        .unwrap();
+
+        let globals = lua.globals();
+        assert_eq!(globals.get::<u32>("r3").unwrap(), 1234);
+        assert_eq!(globals.get::<u32>("r4").unwrap(), 5);
+        assert_eq!(globals.get::<u32>("returned").unwrap(), 1234);
+        assert_eq!(globals.get::<u32>("returned_seven").unwrap(), 7);
+        let err: String = globals
+            .get::<mlua::Value>("bad")
+            .unwrap()
+            .to_string()
+            .unwrap();
+        assert!(err.contains("unknown register 'r32'"), "{err}");
+    }
+}
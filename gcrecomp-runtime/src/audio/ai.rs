@@ -1,16 +1,25 @@
 /// Audio Interface (AI) — manages sample rate, DMA, and streaming.
+use super::master::{ChannelConfig, MasterControl};
+use crate::runtime::savestate::{Snapshot, StateReader, StateWriter};
+use anyhow::Result;
+use gcrecomp_core::runtime::sdk::interrupt::{InterruptSystem, OS_INTERRUPT_DSP_AI};
 use log::info;
+use std::sync::Arc;
 
 pub struct AudioInterface {
     sample_rate: u32,
     dma_address: u32,
     dma_length: u32,
     dma_active: bool,
+    /// Bytes of the current buffer not yet played.
+    dma_remaining: u32,
     _streaming: bool,
     volume_left: u8,
     volume_right: u8,
     dma_callback: Option<u32>, // GC function address for AI DMA interrupt
     initialized: bool,
+    /// Host-side master volume and channel layout, read by `AudioOutput`.
+    master: Arc<MasterControl>,
 }
 
 impl AudioInterface {
@@ -20,11 +29,13 @@ impl AudioInterface {
             dma_address: 0,
             dma_length: 0,
             dma_active: false,
+            dma_remaining: 0,
             _streaming: false,
             volume_left: 255,
             volume_right: 255,
             dma_callback: None,
             initialized: false,
+            master: Arc::new(MasterControl::new()),
         }
     }
 
@@ -46,6 +57,7 @@ impl AudioInterface {
     /// AIStartDMA
     pub fn start_dma(&mut self) {
         self.dma_active = true;
+        self.dma_remaining = self.dma_length;
         info!("AIStartDMA");
     }
 
@@ -55,6 +67,32 @@ impl AudioInterface {
         info!("AIStopDMA");
     }
 
+    /// Play `bytes` of the active DMA buffer. Each time the buffer drains the
+    /// AI raises `OS_INTERRUPT_DSP_AI` and, like the hardware, starts over from
+    /// the address and length programmed now, so a handler that calls
+    /// `AIInitDMA` queues the buffer after next. Returns how many buffers
+    /// completed.
+    pub fn advance_dma(&mut self, bytes: u32, interrupts: &mut InterruptSystem) -> u32 {
+        if !self.dma_active || self.dma_length == 0 {
+            return 0;
+        }
+        let mut bytes = bytes;
+        let mut completed = 0;
+        while bytes >= self.dma_remaining {
+            bytes -= self.dma_remaining;
+            completed += 1;
+            interrupts.raise(OS_INTERRUPT_DSP_AI);
+            self.dma_remaining = self.dma_length;
+        }
+        self.dma_remaining -= bytes;
+        completed
+    }
+
+    /// DMA bytes played per VI field at `fields_per_second` (16-bit stereo).
+    pub fn bytes_per_field(&self, fields_per_second: f64) -> u32 {
+        (self.sample_rate as f64 * 4.0 / fields_per_second) as u32
+    }
+
     /// AISetStreamSampleRate
     pub fn set_stream_sample_rate(&mut self, rate: u32) {
         self.sample_rate = if rate == 0 { 32000 } else { 48000 };
@@ -78,6 +116,26 @@ impl AudioInterface {
         self.volume_right = vol;
     }
 
+    /// Host output volume, 0.0..=1.0 (clamped). Changes ramp in over a few
+    /// samples instead of stepping.
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.master.set_volume(volume);
+    }
+
+    pub fn master_volume(&self) -> f32 {
+        self.master.volume()
+    }
+
+    /// Mono, stereo or 5.1 host output.
+    pub fn set_channel_config(&mut self, config: ChannelConfig) {
+        self.master.set_channel_config(config);
+    }
+
+    /// The control the output fill reads (see `AudioOutput::with_master`).
+    pub fn master_control(&self) -> Arc<MasterControl> {
+        self.master.clone()
+    }
+
     pub fn sample_rate(&self) -> u32 {
         self.sample_rate
     }
@@ -90,6 +148,11 @@ impl AudioInterface {
         self.dma_length
     }
 
+    /// Bytes left in the buffer currently playing.
+    pub fn dma_remaining(&self) -> u32 {
+        self.dma_remaining
+    }
+
     pub fn is_dma_active(&self) -> bool {
         self.dma_active
     }
@@ -108,3 +171,79 @@ impl Default for AudioInterface {
         Self::new()
     }
 }
+
+impl Snapshot for AudioInterface {
+    // `master` is host-side (volume slider, channel layout) and stays put.
+    fn save(&self, w: &mut StateWriter) {
+        w.u32(self.sample_rate);
+        w.u32(self.dma_address);
+        w.u32(self.dma_length);
+        w.bool(self.dma_active);
+        w.u32(self.dma_remaining);
+        w.bool(self._streaming);
+        w.u8(self.volume_left);
+        w.u8(self.volume_right);
+        w.opt_u32(self.dma_callback);
+        w.bool(self.initialized);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        self.sample_rate = r.u32()?;
+        self.dma_address = r.u32()?;
+        self.dma_length = r.u32()?;
+        self.dma_active = r.bool()?;
+        self.dma_remaining = r.u32()?;
+        self._streaming = r.bool()?;
+        self.volume_left = r.u8()?;
+        self.volume_right = r.u8()?;
+        self.dma_callback = r.opt_u32()?;
+        self.initialized = r.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use gcrecomp_core::runtime::context::CpuContext;
+    use gcrecomp_core::runtime::memory::MemoryManager;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const HANDLER: u32 = 0x8000_5000;
+    static CALLED: AtomicU32 = AtomicU32::new(0);
+
+    /// Stands in for the game's `__AIDHandler`: queues the next buffer.
+    fn call(address: u32, ctx: &mut CpuContext, memory: &mut MemoryManager) -> Result<Option<u32>> {
+        CALLED.store(address, Ordering::SeqCst);
+        memory.write_u32(0x8000_0100, ctx.get_register(3))?;
+        Ok(None)
+    }
+
+    #[test]
+    fn drained_buffer_raises_dsp_ai_and_dispatches_handler() {
+        let mut interrupts = InterruptSystem::new();
+        interrupts.set_master_enable(true);
+        interrupts.enable_interrupt(OS_INTERRUPT_DSP_AI);
+        interrupts.set_handler(OS_INTERRUPT_DSP_AI, HANDLER);
+
+        let mut ai = AudioInterface::new();
+        ai.init_dma(0x8010_0000, 0x400);
+        ai.start_dma();
+        assert_eq!(ai.advance_dma(0x300, &mut interrupts), 0);
+        assert!(!interrupts.is_pending(OS_INTERRUPT_DSP_AI));
+        assert_eq!(ai.advance_dma(0x180, &mut interrupts), 1);
+        assert!(interrupts.is_pending(OS_INTERRUPT_DSP_AI));
+        assert_eq!(ai.dma_remaining(), 0x380);
+
+        let mut ctx = CpuContext::new();
+        let mut memory = MemoryManager::new();
+        assert_eq!(interrupts.dispatch(&mut ctx, &mut memory, call).unwrap(), 1);
+        assert_eq!(CALLED.load(Ordering::SeqCst), HANDLER);
+        assert_eq!(
+            memory.read_u32(0x8000_0100).unwrap(),
+            OS_INTERRUPT_DSP_AI as u32
+        );
+        assert!(!interrupts.is_pending(OS_INTERRUPT_DSP_AI));
+    }
+}
@@ -1,5 +1,44 @@
 /// DSP processor — voice management and Nintendo ADPCM decoding.
-use log::info;
+use log::{info, warn};
+
+/// DSP microcode families, each with its own mail and command-list protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Microcode {
+    /// AX: most first- and third-party games.
+    #[default]
+    Ax,
+    /// Nintendo's "Zelda" / MusyX-style synth microcode.
+    Zelda,
+    /// Memory card unlock microcode.
+    Card,
+    /// The IPL's boot microcode.
+    Ipl,
+    /// GBA link (KeyGen) microcode.
+    Gba,
+}
+
+impl Microcode {
+    /// The microcode whose IRAM image hashes to `hash` (see [`ucode_hash`]).
+    pub fn from_hash(hash: u32) -> Option<Self> {
+        // Hashes as Dolphin's ucode factory identifies them.
+        Some(match hash {
+            0x07F8_8145 | 0x3AD3_B7AC | 0x3DAF_59B9 | 0x4E8A_8B21 | 0xE213_6399 => Microcode::Ax,
+            0x24B2_2038 | 0x2FCD_F1EC | 0x42F6_4AC4 | 0x4BE6_A5CB | 0x56D3_6052 | 0x6BA3_B3EA
+            | 0x6CA3_3A6D | 0x8684_0740 => Microcode::Zelda,
+            0x65D6_CC6F => Microcode::Card,
+            0x088E_38A5 | 0xD733_38CF => Microcode::Ipl,
+            0xDD7E_72D5 => Microcode::Gba,
+            _ => return None,
+        })
+    }
+}
+
+/// Hash of an uploaded microcode image: XOR each byte in, rotate left by 3.
+pub fn ucode_hash(image: &[u8]) -> u32 {
+    image
+        .iter()
+        .fold(0u32, |hash, &byte| (hash ^ byte as u32).rotate_left(3))
+}
 
 /// State for a single DSP voice.
 #[derive(Debug, Clone)]
@@ -44,6 +83,10 @@ impl Default for DspVoice {
 pub struct DspProcessor {
     pub voices: Vec<DspVoice>,
     pub initialized: bool,
+    /// Interpreter for the uploaded microcode's commands.
+    microcode: Microcode,
+    /// `ucode_hash` of the last upload.
+    ucode_hash: Option<u32>,
 }
 
 impl DspProcessor {
@@ -51,6 +94,8 @@ impl DspProcessor {
         Self {
             voices: (0..64).map(|_| DspVoice::default()).collect(),
             initialized: false,
+            microcode: Microcode::default(),
+            ucode_hash: None,
         }
     }
 
@@ -60,6 +105,42 @@ impl DspProcessor {
         self.initialized = true;
     }
 
+    /// The game uploaded `image` to DSP IRAM: identify it and route commands
+    /// to its interpreter. An unrecognised image keeps AX, the most common.
+    pub fn upload_microcode(&mut self, image: &[u8]) -> Microcode {
+        let hash = ucode_hash(image);
+        self.ucode_hash = Some(hash);
+        self.microcode = match Microcode::from_hash(hash) {
+            Some(microcode) => {
+                info!("DSP microcode 0x{hash:08X}: {microcode:?}");
+                microcode
+            }
+            None => {
+                warn!(
+                    "Unknown DSP microcode 0x{hash:08X} ({} bytes); assuming AX",
+                    image.len()
+                );
+                Microcode::Ax
+            }
+        };
+        self.microcode
+    }
+
+    /// Command interpreter in use.
+    pub fn microcode(&self) -> Microcode {
+        self.microcode
+    }
+
+    /// Whether the last upload matched a known microcode.
+    pub fn microcode_recognized(&self) -> bool {
+        self.ucode_hash
+            .is_some_and(|h| Microcode::from_hash(h).is_some())
+    }
+
+    pub fn ucode_hash(&self) -> Option<u32> {
+        self.ucode_hash
+    }
+
     /// Decode a block of Nintendo DSP-ADPCM data into PCM samples.
     ///
     /// Each DSP-ADPCM frame is 8 bytes and decodes to 14 samples.
@@ -130,3 +211,45 @@ impl Default for DspProcessor {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image whose hash is `target`. Each XOR-and-rotate step is
+    /// invertible, so walk back from `target`, choosing every byte to clear
+    /// the low bits; each step leaves three more bits zero until none remain.
+    fn image_with_hash(target: u32) -> Vec<u8> {
+        let mut image = Vec::new();
+        let mut hash = target;
+        for _ in 0..11 {
+            hash = hash.rotate_right(3);
+            let byte = (hash & 0xFF) as u8;
+            image.push(byte);
+            hash ^= byte as u32;
+        }
+        assert_eq!(hash, 0);
+        image.reverse();
+        image
+    }
+
+    #[test]
+    fn known_ax_hash_selects_ax_and_unknown_falls_back() {
+        assert_eq!(Microcode::from_hash(0x4E8A_8B21), Some(Microcode::Ax));
+
+        let mut dsp = DspProcessor::new();
+        let zelda = image_with_hash(0x6CA3_3A6D);
+        assert_eq!(ucode_hash(&zelda), 0x6CA3_3A6D);
+        assert_eq!(dsp.upload_microcode(&zelda), Microcode::Zelda);
+
+        let ax = image_with_hash(0x4E8A_8B21);
+        assert_eq!(dsp.upload_microcode(&ax), Microcode::Ax);
+        assert!(dsp.microcode_recognized());
+
+        let unknown = [0x12, 0x34, 0x56, 0x78];
+        assert_eq!(Microcode::from_hash(ucode_hash(&unknown)), None);
+        assert_eq!(dsp.upload_microcode(&unknown), Microcode::Ax);
+        assert_eq!(dsp.microcode(), Microcode::Ax);
+        assert!(!dsp.microcode_recognized());
+    }
+}
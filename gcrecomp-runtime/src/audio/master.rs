@@ -0,0 +1,213 @@
+// Master stage between the mixer and the host device: master volume and the
+// output channel layout.
+//
+// `MasterControl` is the shared knob: `AudioInterface` sets it, the output
+// fill reads it. `MasterStage` applies it to the mixer's interleaved stereo,
+// moving its gain towards the requested volume by at most 1/`RAMP_FRAMES`
+// per frame so volume changes don't click, and maps each stereo frame onto
+// the device's channels.
+use std::sync::atomic::{AtomicU32, AtomicU8, Ordering};
+
+/// Frames a full-scale (0.0 <-> 1.0) volume change is spread over.
+pub const RAMP_FRAMES: u32 = 64;
+
+/// Channel layout of the host device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelConfig {
+    /// One channel: (L + R) / 2.
+    Mono,
+    #[default]
+    Stereo,
+    /// 5.1 in L, R, C, LFE, Ls, Rs order (see [`upmix_surround`]).
+    Surround,
+}
+
+impl ChannelConfig {
+    pub fn channels(self) -> usize {
+        match self {
+            ChannelConfig::Mono => 1,
+            ChannelConfig::Stereo => 2,
+            ChannelConfig::Surround => 6,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => ChannelConfig::Mono,
+            2 => ChannelConfig::Surround,
+            _ => ChannelConfig::Stereo,
+        }
+    }
+
+    fn to_u8(self) -> u8 {
+        match self {
+            ChannelConfig::Mono => 0,
+            ChannelConfig::Stereo => 1,
+            ChannelConfig::Surround => 2,
+        }
+    }
+}
+
+/// Master volume and channel layout, shared between threads.
+#[derive(Debug)]
+pub struct MasterControl {
+    volume: AtomicU32,
+    channels: AtomicU8,
+}
+
+impl MasterControl {
+    pub fn new() -> Self {
+        Self {
+            volume: AtomicU32::new(1.0f32.to_bits()),
+            channels: AtomicU8::new(ChannelConfig::Stereo.to_u8()),
+        }
+    }
+
+    /// Clamped to 0.0..=1.0; NaN counts as 0.
+    pub fn set_volume(&self, volume: f32) {
+        let volume = if volume.is_nan() {
+            0.0
+        } else {
+            volume.clamp(0.0, 1.0)
+        };
+        self.volume.store(volume.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn volume(&self) -> f32 {
+        f32::from_bits(self.volume.load(Ordering::Relaxed))
+    }
+
+    pub fn set_channel_config(&self, config: ChannelConfig) {
+        self.channels.store(config.to_u8(), Ordering::Relaxed);
+    }
+
+    pub fn channel_config(&self) -> ChannelConfig {
+        ChannelConfig::from_u8(self.channels.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for MasterControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stereo to 5.1: fronts keep L/R, the center carries the common part at
+/// -3 dB, surrounds carry the difference at -3 dB (out of phase), LFE stays
+/// silent.
+pub fn upmix_surround(left: f32, right: f32) -> [f32; 6] {
+    const MINUS_3DB: f32 = std::f32::consts::FRAC_1_SQRT_2;
+    let center = (left + right) * 0.5 * MINUS_3DB;
+    let side = (left - right) * 0.5 * MINUS_3DB;
+    [left, right, center, 0.0, side, -side]
+}
+
+/// Applies a [`MasterControl`] to the output stream; lives with the fill.
+#[derive(Debug)]
+pub struct MasterStage {
+    gain: f32,
+}
+
+impl MasterStage {
+    /// Starts at `control`'s current volume, so the first buffer isn't a ramp.
+    pub fn new(control: &MasterControl) -> Self {
+        Self {
+            gain: control.volume(),
+        }
+    }
+
+    /// The gain the next frame is played at.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Write the interleaved stereo `input` to `output` in `control`'s layout:
+    /// one `output` frame per `input` frame, as many as both hold.
+    pub fn process(&mut self, control: &MasterControl, input: &[f32], output: &mut [f32]) {
+        let target = control.volume();
+        let step = 1.0 / RAMP_FRAMES as f32;
+        let config = control.channel_config();
+        let frames = output.chunks_exact_mut(config.channels());
+        for (frame, pair) in frames.zip(input.chunks_exact(2)) {
+            self.gain += (target - self.gain).clamp(-step, step);
+            let (left, right) = (pair[0] * self.gain, pair[1] * self.gain);
+            match config {
+                ChannelConfig::Mono => frame[0] = (left + right) * 0.5,
+                ChannelConfig::Stereo => frame.copy_from_slice(&[left, right]),
+                ChannelConfig::Surround => frame.copy_from_slice(&upmix_surround(left, right)),
+            }
+            for sample in frame.iter_mut() {
+                *sample = sample.clamp(-1.0, 1.0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frames: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|i| {
+                let s = (i as f32 * 0.05).sin() * 0.8;
+                [s, s]
+            })
+            .collect()
+    }
+
+    fn render(control: &MasterControl, input: &[f32]) -> Vec<f32> {
+        let mut stage = MasterStage::new(control);
+        let mut output = vec![0.0; input.len()];
+        stage.process(control, input, &mut output);
+        output
+    }
+
+    #[test]
+    fn zero_volume_is_silent_and_half_volume_halves() {
+        let input = tone(256);
+        let control = MasterControl::new();
+
+        control.set_volume(0.0);
+        assert!(render(&control, &input).iter().all(|&s| s == 0.0));
+
+        control.set_volume(0.5);
+        for (out, inp) in render(&control, &input).iter().zip(&input) {
+            assert!((out - inp * 0.5).abs() < 1e-6, "{out} vs {inp}");
+        }
+    }
+
+    #[test]
+    fn volume_change_ramps_without_a_jump() {
+        let input = vec![1.0f32; 512]; // DC at full scale
+        let control = MasterControl::new();
+        let mut stage = MasterStage::new(&control);
+        let mut output = vec![0.0; input.len()];
+        stage.process(&control, &input[..128], &mut output[..128]);
+        control.set_volume(0.0);
+        stage.process(&control, &input[128..], &mut output[128..]);
+
+        let max_step = output
+            .windows(2)
+            .map(|w| (w[1] - w[0]).abs())
+            .fold(0.0f32, f32::max);
+        assert!(max_step <= 1.0 / RAMP_FRAMES as f32 + 1e-6, "{max_step}");
+        assert_eq!(*output.last().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn mono_and_surround_layouts() {
+        let control = MasterControl::new();
+        control.set_channel_config(ChannelConfig::Mono);
+        let mut stage = MasterStage::new(&control);
+        let mut mono = [0.0; 1];
+        stage.process(&control, &[0.6, 0.2], &mut mono);
+        assert!((mono[0] - 0.4).abs() < 1e-6);
+
+        control.set_channel_config(ChannelConfig::Surround);
+        let mut surround = [0.0; 6];
+        stage.process(&control, &[0.5, 0.5], &mut surround);
+        assert_eq!(surround[..2], [0.5, 0.5]);
+        assert!(surround[2] > 0.0 && surround[4] == 0.0 && surround[5] == 0.0);
+    }
+}
@@ -1,9 +1,26 @@
+/// How audio behaves when emulation runs faster or slower than real time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedMode {
+    /// Resample to real time: fast-forward plays higher, slow-motion lower.
+    #[default]
+    PitchShift,
+    /// Keep pitch by dropping excess audio (fast) or padding with silence (slow).
+    /// Sounds choppy, but voices stay intelligible.
+    KeepPitch,
+}
+
+use std::collections::VecDeque;
+
 /// Audio mixer — combines DSP voices into stereo output.
 pub struct AudioMixer {
     pub master_volume: f32,
     pub sample_rate: u32,
     buffer: Vec<[f32; 2]>, // Stereo samples
     buffer_pos: usize,
+    speed: f32,
+    speed_mode: SpeedMode,
+    /// Copy of every mixed sample for the recorder, independent of playback.
+    tee: Option<VecDeque<[f32; 2]>>,
 }
 
 impl AudioMixer {
@@ -15,16 +32,37 @@ impl AudioMixer {
             sample_rate,
             buffer: vec![[0.0; 2]; Self::BUFFER_SIZE],
             buffer_pos: 0,
+            speed: 1.0,
+            speed_mode: SpeedMode::default(),
+            tee: None,
         }
     }
 
+    /// Match playback to the emulation speed multiplier. The buffer grows with
+    /// speed since each real-time pull consumes `speed` times more samples.
+    pub fn set_playback_speed(&mut self, speed: f32, mode: SpeedMode) {
+        self.speed = speed.max(0.01);
+        self.speed_mode = mode;
+        let size = Self::BUFFER_SIZE * self.speed.ceil().max(1.0) as usize;
+        if size > self.buffer.len() {
+            self.buffer.resize(size, [0.0; 2]);
+        }
+    }
+
+    pub fn playback_speed(&self) -> (f32, SpeedMode) {
+        (self.speed, self.speed_mode)
+    }
+
     /// Mix a mono voice into the stereo buffer with volume panning.
     pub fn mix_voice(&mut self, samples: &[i16], volume_left: f32, volume_right: f32) {
         for &sample in samples {
+            let s = sample as f32 / 32768.0;
+            if let Some(tee) = self.tee.as_mut() {
+                tee.push_back([s * volume_left, s * volume_right]);
+            }
             if self.buffer_pos >= self.buffer.len() {
-                break;
+                continue;
             }
-            let s = sample as f32 / 32768.0;
             self.buffer[self.buffer_pos][0] += s * volume_left;
             self.buffer[self.buffer_pos][1] += s * volume_right;
             self.buffer_pos += 1;
@@ -34,11 +72,17 @@ impl AudioMixer {
     /// Mix raw PCM stereo data (interleaved i16) into the buffer.
     pub fn mix_stereo_pcm(&mut self, data: &[i16]) {
         for chunk in data.chunks(2) {
-            if chunk.len() < 2 || self.buffer_pos >= self.buffer.len() {
+            if chunk.len() < 2 {
                 break;
             }
             let left = chunk[0] as f32 / 32768.0;
             let right = chunk[1] as f32 / 32768.0;
+            if let Some(tee) = self.tee.as_mut() {
+                tee.push_back([left, right]);
+            }
+            if self.buffer_pos >= self.buffer.len() {
+                continue;
+            }
             self.buffer[self.buffer_pos][0] += left;
             self.buffer[self.buffer_pos][1] += right;
             self.buffer_pos += 1;
@@ -59,6 +103,44 @@ impl AudioMixer {
         output
     }
 
+    /// Pull `count` stereo samples for the host audio device at real-time rate,
+    /// compensating for the playback speed (see `SpeedMode`). Always returns
+    /// `count * 2` interleaved values.
+    pub fn pull_realtime(&mut self, count: usize) -> Vec<f32> {
+        if (self.speed - 1.0).abs() < f32::EPSILON {
+            let mut out = self.pull_samples(count);
+            out.resize(count * 2, 0.0);
+            return out;
+        }
+        let source = ((count as f32) * self.speed).round() as usize;
+        let produced = self.pull_samples(source);
+        let mut out = match self.speed_mode {
+            SpeedMode::PitchShift => {
+                // Squeeze/stretch `source` frames into `count`, per channel
+                let frames = produced.len() / 2;
+                let mut out = Vec::with_capacity(count * 2);
+                for i in 0..count {
+                    let pos = i as f32 * frames as f32 / count as f32;
+                    let idx = pos as usize;
+                    let frac = pos - idx as f32;
+                    for ch in 0..2 {
+                        let a = produced.get(idx * 2 + ch).copied().unwrap_or(0.0);
+                        let b = produced.get((idx + 1) * 2 + ch).copied().unwrap_or(a);
+                        out.push(a + (b - a) * frac);
+                    }
+                }
+                out
+            }
+            SpeedMode::KeepPitch => {
+                let mut out = produced;
+                out.truncate(count * 2);
+                out
+            }
+        };
+        out.resize(count * 2, 0.0);
+        out
+    }
+
     /// Pull exactly `count` interleaved stereo samples for the audio output thread.
     pub fn pull_samples(&mut self, count: usize) -> Vec<f32> {
         let available = self.buffer_pos.min(count);
@@ -77,6 +159,27 @@ impl AudioMixer {
         output
     }
 
+    /// Start or stop copying mixed audio aside for `take_tee`. The copy keeps
+    /// everything that was mixed, even samples the playback buffer drops.
+    pub fn set_tee(&mut self, enabled: bool) {
+        self.tee = enabled.then(VecDeque::new);
+    }
+
+    /// Take up to `count` teed stereo samples (interleaved, master volume
+    /// applied) without touching what the audio output will play.
+    pub fn take_tee(&mut self, count: usize) -> Vec<f32> {
+        let Some(tee) = self.tee.as_mut() else {
+            return Vec::new();
+        };
+        let available = tee.len().min(count);
+        let mut output = Vec::with_capacity(available * 2);
+        for [left, right] in tee.drain(..available) {
+            output.push((left * self.master_volume).clamp(-1.0, 1.0));
+            output.push((right * self.master_volume).clamp(-1.0, 1.0));
+        }
+        output
+    }
+
     pub fn clear(&mut self) {
         for sample in &mut self.buffer {
             *sample = [0.0; 2];
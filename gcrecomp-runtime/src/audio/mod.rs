@@ -1,7 +1,9 @@
 pub mod ai;
 pub mod dsp;
+pub mod master;
 pub mod mixer;
 pub mod output;
 
 pub use ai::AudioInterface;
-pub use mixer::AudioMixer;
+pub use master::{ChannelConfig, MasterControl};
+pub use mixer::{AudioMixer, SpeedMode};
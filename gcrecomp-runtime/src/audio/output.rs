@@ -4,22 +4,52 @@
 /// that fills the output buffer on demand.
 use std::sync::{Arc, Mutex};
 
+use super::master::{MasterControl, MasterStage};
 use super::mixer::AudioMixer;
 
 /// Audio output configuration.
 pub struct AudioOutput {
     mixer: Arc<Mutex<AudioMixer>>,
     active: bool,
+    /// Host buffer length requested from the device.
+    latency_ms: u32,
+    /// Master volume and channel layout applied in the fill.
+    master: Arc<MasterControl>,
+    stage: Mutex<MasterStage>,
 }
 
 impl AudioOutput {
     pub fn new(mixer: Arc<Mutex<AudioMixer>>) -> Self {
+        Self::with_latency(mixer, 40)
+    }
+
+    pub fn with_latency(mixer: Arc<Mutex<AudioMixer>>, latency_ms: u32) -> Self {
+        let master = Arc::new(MasterControl::new());
         Self {
             mixer,
             active: false,
+            latency_ms,
+            stage: Mutex::new(MasterStage::new(&master)),
+            master,
         }
     }
 
+    /// Follow `master` (usually `AudioInterface::master_control`).
+    pub fn with_master(mut self, master: Arc<MasterControl>) -> Self {
+        self.stage = Mutex::new(MasterStage::new(&master));
+        self.master = master;
+        self
+    }
+
+    pub fn latency_ms(&self) -> u32 {
+        self.latency_ms
+    }
+
+    /// Stereo frames per host buffer at `sample_rate`.
+    pub fn buffer_frames(&self, sample_rate: u32) -> u32 {
+        sample_rate * self.latency_ms / 1000
+    }
+
     /// Start the audio output stream.
     /// This is a no-op placeholder — actual cpal integration requires the cpal
     /// dependency. When cpal is available, this spawns a stream that pulls
@@ -29,13 +59,16 @@ impl AudioOutput {
             return Ok(());
         }
         self.active = true;
-        log::info!("AudioOutput: started (host audio output ready)");
+        log::info!(
+            "AudioOutput: started (host audio output ready, {} ms buffer)",
+            self.latency_ms
+        );
         // cpal stream would be created here:
         // let host = cpal::default_host();
         // let device = host.default_output_device()...;
         // let stream = device.build_output_stream(config, move |data, _| {
         //     let mut mixer = mixer_clone.lock().unwrap();
-        //     let samples = mixer.pull_samples(data.len() / 2);
+        //     let samples = mixer.pull_realtime(data.len() / 2);
         //     for (i, sample) in samples.iter().enumerate() {
         //         data[i] = *sample;
         //     }
@@ -53,16 +86,21 @@ impl AudioOutput {
         self.active
     }
 
-    /// Fill a buffer with audio samples (for manual pull mode / testing).
+    /// Fill a buffer with audio samples (for manual pull mode / testing),
+    /// interleaved in the master channel layout.
     pub fn fill_buffer(&self, output: &mut [f32]) {
-        if let Ok(mut mixer) = self.mixer.lock() {
-            let samples = mixer.pull_samples(output.len() / 2);
-            let copy_len = samples.len().min(output.len());
-            output[..copy_len].copy_from_slice(&samples[..copy_len]);
-            // Zero-fill remainder
-            for sample in &mut output[copy_len..] {
-                *sample = 0.0;
-            }
+        let channels = self.master.channel_config().channels();
+        let frames = output.len() / channels;
+        let samples = match self.mixer.lock() {
+            Ok(mut mixer) => mixer.pull_realtime(frames),
+            Err(_) => return,
+        };
+        if let Ok(mut stage) = self.stage.lock() {
+            stage.process(&self.master, &samples, output);
+        }
+        // Zero-fill a partial trailing frame
+        for sample in &mut output[frames * channels..] {
+            *sample = 0.0;
         }
     }
 }
@@ -0,0 +1,163 @@
+// GPU texture readback for screenshots
+use anyhow::Result;
+use image::RgbaImage;
+use wgpu::*;
+
+/// Copy a 2D RGBA/BGRA 8-bit texture back to the CPU.
+///
+/// The texture needs `COPY_SRC` usage. wgpu requires buffer rows to be padded
+/// to `COPY_BYTES_PER_ROW_ALIGNMENT`, so the padding is stripped here. Bytes are
+/// taken as stored: an `*Srgb` texture already holds sRGB-encoded values (what
+/// PNG expects) and a linear one holds whatever the shader wrote, so no gamma
+/// conversion is applied either way — converting again is what washes colors out.
+pub fn read_texture_rgba(
+    device: &Device,
+    queue: &Queue,
+    texture: &Texture,
+    width: u32,
+    height: u32,
+    format: TextureFormat,
+) -> Result<RgbaImage> {
+    let bgra = match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        other => anyhow::bail!("Unsupported capture format: {:?}", other),
+    };
+
+    let unpadded_row = width * 4;
+    let align = COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded_row = unpadded_row.div_ceil(align) * align;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("capture readback"),
+        size: padded_row as u64 * height as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("capture enc"),
+    });
+    encoder.copy_texture_to_buffer(
+        ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row),
+                rows_per_image: Some(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (tx, rx) = std::sync::mpsc::channel();
+    slice.map_async(MapMode::Read, move |r| {
+        let _ = tx.send(r);
+    });
+    device.poll(Maintain::Wait);
+    rx.recv()??;
+
+    let mut pixels = Vec::with_capacity((unpadded_row * height) as usize);
+    {
+        let data = slice.get_mapped_range();
+        for row in data.chunks(padded_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if bgra {
+        for px in pixels.chunks_exact_mut(4) {
+            px.swap(0, 2);
+        }
+    }
+
+    RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| anyhow::anyhow!("Capture buffer size mismatch"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Headless device on the software fallback adapter, so no GPU is needed.
+    fn test_device() -> (Device, Queue) {
+        let instance = Instance::new(InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+            power_preference: PowerPreference::LowPower,
+            compatible_surface: None,
+            force_fallback_adapter: true,
+        }))
+        .expect("no fallback wgpu adapter");
+        pollster::block_on(adapter.request_device(&DeviceDescriptor::default(), None))
+            .expect("failed to create wgpu device")
+    }
+
+    #[test]
+    fn captures_solid_color_with_row_padding_and_srgb() {
+        let (device, queue) = test_device();
+
+        // 100px wide -> 400 byte rows, padded to 512 by wgpu
+        let (w, h) = (100, 3);
+        let format = TextureFormat::Bgra8UnormSrgb;
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("capture test"),
+            size: Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor::default());
+        encoder.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: Operations {
+                    // Linear 0.5 is sRGB ~188 once stored in an *Srgb target
+                    load: LoadOp::Clear(Color {
+                        r: 0.5,
+                        g: 0.0,
+                        b: 1.0,
+                        a: 1.0,
+                    }),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let image = read_texture_rgba(&device, &queue, &texture, w, h, format).unwrap();
+        assert_eq!(image.dimensions(), (w, h));
+        for px in image.pixels() {
+            assert!((px[0] as i32 - 188).abs() <= 1, "red {}", px[0]);
+            assert_eq!(px[1], 0);
+            assert_eq!(px[2], 255);
+            assert_eq!(px[3], 255);
+        }
+    }
+}
@@ -0,0 +1,943 @@
+// GX command stream decoding (display lists; shared with the GP FIFO).
+//
+// A command stream is a sequence of one-byte opcodes, each followed by a
+// big-endian payload:
+//
+//   0x00        NOP
+//   0x08 rr vv  LOAD_CP_REG: 8-bit register, 32-bit value
+//   0x10 hh vv  LOAD_XF_REG: header ((count - 1) << 16 | address), count words
+//   0x20..0x38  LOAD_INDX_A..D: 32-bit payload
+//   0x40 aa ss  CALL_DL: 32-bit address, 32-bit size
+//   0x48        INVALIDATE_VTX_CACHE
+//   0x61 vv     LOAD_BP_REG: 32-bit value (register in the top byte; BP
+//               registers include the setup unit's scissor and genmode)
+//   0x80..0xBF  draw: primitive in the top 5 bits, vertex format in the low 3,
+//               16-bit vertex count, then the vertices laid out per VCD/VAT.
+
+use super::state::{
+    BlendFactor, CompareFunction, CullMode, GxState, LogicOp, TevIndirect, TexImage, VtxAttr,
+    VtxAttrFmt, VtxInputType,
+};
+use super::GXProcessor;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use log::{trace, warn};
+
+pub const OP_NOP: u8 = 0x00;
+pub const OP_LOAD_CP_REG: u8 = 0x08;
+pub const OP_LOAD_XF_REG: u8 = 0x10;
+pub const OP_LOAD_INDX_A: u8 = 0x20;
+pub const OP_LOAD_INDX_B: u8 = 0x28;
+pub const OP_LOAD_INDX_C: u8 = 0x30;
+pub const OP_LOAD_INDX_D: u8 = 0x38;
+pub const OP_CALL_DL: u8 = 0x40;
+pub const OP_INVALIDATE_VTX_CACHE: u8 = 0x48;
+pub const OP_LOAD_BP_REG: u8 = 0x61;
+
+/// CP registers (LOAD_CP_REG addresses).
+pub const CP_VCD_LO: u8 = 0x50;
+pub const CP_VCD_HI: u8 = 0x60;
+pub const CP_VAT_A: u8 = 0x70;
+pub const CP_VAT_B: u8 = 0x80;
+pub const CP_VAT_C: u8 = 0x90;
+pub const CP_ARRAY_BASE: u8 = 0xA0;
+pub const CP_ARRAY_STRIDE: u8 = 0xB0;
+
+/// Component types (`GX_U8` .. `GX_F32`).
+const COMP_U8: u8 = 0;
+const COMP_S8: u8 = 1;
+const COMP_U16: u8 = 2;
+const COMP_S16: u8 = 3;
+
+/// Color formats (`GX_RGB565` .. `GX_RGBA8`).
+const CLR_RGB565: u8 = 0;
+const CLR_RGB8: u8 = 1;
+const CLR_RGBX8: u8 = 2;
+const CLR_RGBA4: u8 = 3;
+const CLR_RGBA6: u8 = 4;
+
+/// Execute every complete command in `bytes`; returns the bytes consumed.
+/// A command cut off at the end of `bytes` is left unconsumed. `CALL_DL` is
+/// followed only when `allow_call` is set (hardware ignores it inside a
+/// display list).
+pub fn execute(
+    gx: &mut GXProcessor,
+    bytes: &[u8],
+    memory: &MemoryManager,
+    allow_call: bool,
+) -> usize {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match execute_one(gx, &bytes[pos..], memory, allow_call) {
+            Some(len) => pos += len,
+            None => break,
+        }
+    }
+    pos
+}
+
+/// Execute the command at the start of `bytes`; `None` if it is incomplete.
+fn execute_one(
+    gx: &mut GXProcessor,
+    bytes: &[u8],
+    memory: &MemoryManager,
+    allow_call: bool,
+) -> Option<usize> {
+    let op = bytes[0];
+    match op {
+        OP_NOP | OP_INVALIDATE_VTX_CACHE => Some(1),
+        OP_LOAD_CP_REG => {
+            let reg = *bytes.get(1)?;
+            let value = be32(bytes, 2)?;
+            load_cp_reg(&mut gx.state, reg, value);
+            Some(6)
+        }
+        OP_LOAD_XF_REG => {
+            let header = be32(bytes, 1)?;
+            let count = ((header >> 16) & 0xF) as usize + 1;
+            let len = 5 + count * 4;
+            if bytes.len() < len {
+                return None;
+            }
+            let words: Vec<u32> = (0..count)
+                .map(|i| be32(bytes, 5 + i * 4).unwrap())
+                .collect();
+            load_xf_regs(&mut gx.state, (header & 0xFFFF) as u16, &words);
+            Some(len)
+        }
+        OP_LOAD_INDX_A | OP_LOAD_INDX_B | OP_LOAD_INDX_C | OP_LOAD_INDX_D => {
+            be32(bytes, 1)?;
+            Some(5)
+        }
+        OP_CALL_DL => {
+            let addr = be32(bytes, 1)?;
+            let size = be32(bytes, 5)?;
+            if allow_call {
+                gx.call_display_list(addr, size, memory);
+            } else {
+                warn!("GX: CALL_DL inside a display list ignored");
+            }
+            Some(9)
+        }
+        OP_LOAD_BP_REG => {
+            let value = be32(bytes, 1)?;
+            gx.write_bp_reg((value >> 24) as u8, value & 0xFF_FFFF);
+            Some(5)
+        }
+        0x80..=0xBF => {
+            let count = u16::from_be_bytes([*bytes.get(1)?, *bytes.get(2)?]);
+            let fmt = op & 0x07;
+            let layout = VertexLayout::new(&gx.state, fmt);
+            let len = 3 + count as usize * layout.size;
+            if bytes.len() < len {
+                return None;
+            }
+            draw(gx, op & 0xF8, fmt, count, &layout, &bytes[3..len], memory);
+            Some(len)
+        }
+        _ => {
+            warn!("GX: unknown command 0x{:02X}, skipping", op);
+            Some(1)
+        }
+    }
+}
+
+fn be32(bytes: &[u8], at: usize) -> Option<u32> {
+    let b = bytes.get(at..at + 4)?;
+    Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// Apply a LOAD_CP_REG: vertex descriptor, attribute format or array setup.
+pub fn load_cp_reg(state: &mut GxState, reg: u8, value: u32) {
+    let input = |bits: u32| match bits & 3 {
+        1 => VtxInputType::Direct,
+        2 => VtxInputType::Index8,
+        3 => VtxInputType::Index16,
+        _ => VtxInputType::None,
+    };
+    let flag = |bit: u32| {
+        if value >> bit & 1 != 0 {
+            VtxInputType::Direct
+        } else {
+            VtxInputType::None
+        }
+    };
+    match reg {
+        CP_VCD_LO => {
+            state.set_vtx_desc(VtxAttr::PositionMatrixIdx, flag(0));
+            for i in 0..8 {
+                state.set_vtx_desc(tex_mtx_attr(i), flag(1 + i as u32));
+            }
+            state.set_vtx_desc(VtxAttr::Position, input(value >> 9));
+            state.set_vtx_desc(VtxAttr::Normal, input(value >> 11));
+            state.set_vtx_desc(VtxAttr::Color0, input(value >> 13));
+            state.set_vtx_desc(VtxAttr::Color1, input(value >> 15));
+        }
+        CP_VCD_HI => {
+            for i in 0..8 {
+                state.set_vtx_desc(tex_attr(i), input(value >> (i * 2)));
+            }
+        }
+        r if r & 0xF0 == CP_VAT_A && r & 0x08 == 0 => load_vat_a(state, r & 7, value),
+        r if r & 0xF0 == CP_VAT_B && r & 0x08 == 0 => load_vat_b(state, r & 7, value),
+        r if r & 0xF0 == CP_VAT_C && r & 0x08 == 0 => load_vat_c(state, r & 7, value),
+        r if r & 0xF0 == CP_ARRAY_BASE && r & 0x0F < 12 => {
+            let array = (r & 0x0F) as usize;
+            state.array_bases[array] = 0x8000_0000 | (value & 0x03FF_FFFF);
+        }
+        r if r & 0xF0 == CP_ARRAY_STRIDE && r & 0x0F < 12 => {
+            state.array_strides[(r & 0x0F) as usize] = value & 0xFF;
+        }
+        _ => trace!("GX: CP[0x{:02X}] = 0x{:08X} ignored", reg, value),
+    }
+}
+
+/// BP registers (top byte of a LOAD_BP_REG value).
+pub const BP_GENMODE: u8 = 0x00;
+/// Indirect matrices: three registers (one column each) per matrix.
+pub const BP_IND_MTXA_0: u8 = 0x06;
+pub const BP_IND_MTXC_2: u8 = 0x0E;
+/// Per-TEV-stage indirect command (`GXSetTevIndirect`).
+pub const BP_IND_CMD_0: u8 = 0x10;
+pub const BP_IND_CMD_F: u8 = 0x1F;
+pub const BP_SCISSOR_TL: u8 = 0x20;
+pub const BP_SCISSOR_BR: u8 = 0x21;
+/// Texture images: size and format (`TX_SETIMAGE0`) and address
+/// (`TX_SETIMAGE3`), maps 0-3 then 4-7.
+pub const BP_TX_SETIMAGE0_0: u8 = 0x88;
+pub const BP_TX_SETIMAGE0_4: u8 = 0xA8;
+pub const BP_TX_SETIMAGE3_0: u8 = 0x94;
+pub const BP_TX_SETIMAGE3_4: u8 = 0xB4;
+/// Indirect coordinate scales for stages 0/1 and 2/3.
+pub const BP_RAS1_SS0: u8 = 0x25;
+pub const BP_RAS1_SS1: u8 = 0x26;
+/// Indirect texture order (map and coordinate per indirect stage).
+pub const BP_RAS1_IREF: u8 = 0x27;
+pub const BP_ZMODE: u8 = 0x40;
+pub const BP_BLENDMODE: u8 = 0x41;
+pub const BP_CLEAR_AR: u8 = 0x4F;
+pub const BP_CLEAR_GB: u8 = 0x50;
+pub const BP_CLEAR_Z: u8 = 0x51;
+pub const BP_TEV_COLOR_ENV_0: u8 = 0xC0;
+pub const BP_TEV_ALPHA_ENV_F: u8 = 0xDF;
+/// Write mask for the next BP load.
+pub const BP_MASK: u8 = 0xFE;
+
+/// XF addresses (LOAD_XF_REG). Matrix memory holds 3x4 row-major matrices,
+/// four words per row; GX matrix ids count rows.
+const XF_MATRIX_END: u16 = 0x0100;
+const XF_VIEWPORT: u16 = 0x101A;
+const XF_PROJECTION: u16 = 0x1020;
+
+/// Screen coordinates in the setup unit and XF are offset by 342.
+const SCREEN_OFFSET: i32 = 342;
+
+/// Apply a 24-bit BP register value (already merged with any BP mask).
+pub fn load_bp_reg(state: &mut GxState, reg: u8, value: u32) {
+    match reg {
+        BP_GENMODE => {
+            state.num_tex_gens = bits(value, 0, 4);
+            state.num_channels = bits(value, 4, 3);
+            state.num_tev_stages = bits(value, 10, 4) + 1;
+            state.set_num_ind_stages(bits(value, 16, 3));
+            // Hardware order: none, back, front, all.
+            state.cull_mode = match bits(value, 14, 2) {
+                0 => CullMode::None,
+                1 => CullMode::Back,
+                2 => CullMode::Front,
+                _ => CullMode::All,
+            };
+        }
+        BP_IND_MTXA_0..=BP_IND_MTXC_2 => {
+            // Column `col` of matrix `index`: two signed s1.10 entries and
+            // two of the six scale bits (exponent + 17).
+            let index = (reg - BP_IND_MTXA_0) / 3;
+            let col = ((reg - BP_IND_MTXA_0) % 3) as usize;
+            let entry = |shift: u32| ((value << (21 - shift)) as i32 >> 21) as f32 / 1024.0;
+            let m = state.ind_matrices[index as usize];
+            let mut offset = m.offset;
+            offset[0][col] = entry(0);
+            offset[1][col] = entry(11);
+            let raw = ((m.scale_exp as i32 + 17) as u32 & !(3 << (2 * col)))
+                | (bits(value, 22, 2) as u32) << (2 * col);
+            state.set_ind_tex_matrix(index, offset, raw as i8 - 17);
+        }
+        BP_IND_CMD_0..=BP_IND_CMD_F => state.set_tev_indirect(
+            reg - BP_IND_CMD_0,
+            TevIndirect {
+                ind_stage: bits(value, 0, 2),
+                format: bits(value, 2, 2),
+                bias: bits(value, 4, 3),
+                matrix: bits(value, 9, 4),
+                wrap_s: bits(value, 13, 3),
+                wrap_t: bits(value, 16, 3),
+                add_prev: value >> 20 & 1 != 0,
+            },
+        ),
+        BP_RAS1_SS0 | BP_RAS1_SS1 => {
+            let first = (reg - BP_RAS1_SS0) * 2;
+            for i in 0..2 {
+                let shift = i * 8;
+                state.set_ind_tex_coord_scale(
+                    first + i as u8,
+                    bits(value, shift, 4),
+                    bits(value, shift + 4, 4),
+                );
+            }
+        }
+        BP_RAS1_IREF => {
+            for stage in 0..4 {
+                let shift = stage * 6;
+                state.set_ind_tex_order(
+                    stage as u8,
+                    bits(value, shift + 3, 3),
+                    bits(value, shift, 3),
+                );
+            }
+        }
+        BP_TX_SETIMAGE0_0..=0x8B | BP_TX_SETIMAGE0_4..=0xAB => {
+            let map = (reg & 3) + if reg >= BP_TX_SETIMAGE0_4 { 4 } else { 0 };
+            let image = state.tex_images[map as usize].unwrap_or_default();
+            state.set_tex_image(
+                map,
+                Some(TexImage {
+                    width: (value & 0x3FF) as u16 + 1,
+                    height: (value >> 10 & 0x3FF) as u16 + 1,
+                    format: bits(value, 20, 4),
+                    ..image
+                }),
+            );
+        }
+        BP_TX_SETIMAGE3_0..=0x97 | BP_TX_SETIMAGE3_4..=0xB7 => {
+            let map = (reg & 3) + if reg >= BP_TX_SETIMAGE3_4 { 4 } else { 0 };
+            let image = state.tex_images[map as usize].unwrap_or_default();
+            state.set_tex_image(
+                map,
+                Some(TexImage {
+                    address: (value & 0xFF_FFFF) << 5,
+                    ..image
+                }),
+            );
+        }
+        BP_SCISSOR_TL => {
+            // Keep the bottom-right corner until SCISSOR_BR arrives.
+            let right = state.scissor.x as i32 + state.scissor.width as i32;
+            let bottom = state.scissor.y as i32 + state.scissor.height as i32;
+            let x = (bits11(value, 12) - SCREEN_OFFSET).max(0);
+            let y = (bits11(value, 0) - SCREEN_OFFSET).max(0);
+            state.set_scissor(
+                x as u16,
+                y as u16,
+                (right - x).max(0) as u16,
+                (bottom - y).max(0) as u16,
+            );
+        }
+        BP_SCISSOR_BR => {
+            // Inclusive edges.
+            let right = bits11(value, 12) - SCREEN_OFFSET + 1;
+            let bottom = bits11(value, 0) - SCREEN_OFFSET + 1;
+            let (x, y) = (state.scissor.x, state.scissor.y);
+            state.set_scissor(
+                x,
+                y,
+                (right - x as i32).max(0) as u16,
+                (bottom - y as i32).max(0) as u16,
+            );
+        }
+        BP_ZMODE => state.set_z_mode(
+            value & 1 != 0,
+            CompareFunction::from_u8(bits(value, 1, 3)),
+            value >> 4 & 1 != 0,
+        ),
+        BP_BLENDMODE => {
+            let logic = if value >> 1 & 1 != 0 {
+                logic_op(bits(value, 12, 4))
+            } else {
+                LogicOp::Copy
+            };
+            state.set_blend_mode(
+                value & 1 != 0,
+                blend_factor(bits(value, 8, 3)),
+                blend_factor(bits(value, 5, 3)),
+                logic,
+            );
+            state.set_color_update(value >> 3 & 1 != 0, value >> 4 & 1 != 0);
+        }
+        BP_CLEAR_AR | BP_CLEAR_GB => {
+            let [mut r, mut g, mut b, mut a] = state.copy_clear_color;
+            let hi = bits(value, 8, 8) as f32 / 255.0;
+            let lo = bits(value, 0, 8) as f32 / 255.0;
+            if reg == BP_CLEAR_AR {
+                (a, r) = (hi, lo);
+            } else {
+                (g, b) = (hi, lo);
+            }
+            state.set_copy_clear_color(r, g, b, a);
+        }
+        BP_CLEAR_Z => state.set_copy_clear_z(value),
+        BP_TEV_COLOR_ENV_0..=BP_TEV_ALPHA_ENV_F => {
+            let stage = (reg - BP_TEV_COLOR_ENV_0) / 2;
+            let (op, scale) = tev_op(value);
+            let clamp = value >> 19 & 1 != 0;
+            let dest = bits(value, 22, 2);
+            if reg & 1 == 0 {
+                state.set_tev_color_in(
+                    stage,
+                    bits(value, 12, 4),
+                    bits(value, 8, 4),
+                    bits(value, 4, 4),
+                    bits(value, 0, 4),
+                );
+                state.set_tev_color_op(stage, op, clamp, scale, dest);
+            } else {
+                state.set_tev_alpha_in(
+                    stage,
+                    bits(value, 13, 3),
+                    bits(value, 10, 3),
+                    bits(value, 7, 3),
+                    bits(value, 4, 3),
+                );
+                state.set_tev_alpha_op(stage, op, clamp, scale, dest);
+            }
+        }
+        _ => trace!("GX: BP[0x{:02X}] = 0x{:06X} ignored", reg, value),
+    }
+}
+
+/// Apply a LOAD_XF_REG of `words` starting at `addr`: matrix memory,
+/// viewport and projection.
+pub fn load_xf_regs(state: &mut GxState, addr: u16, words: &[u32]) {
+    let raw_at = |a: u16| {
+        a.checked_sub(addr)
+            .and_then(|i| words.get(i as usize))
+            .copied()
+    };
+    let word_at = |a: u16| raw_at(a).map(f32::from_bits);
+    for (i, &word) in words.iter().enumerate() {
+        let a = addr.wrapping_add(i as u16);
+        if a < XF_MATRIX_END {
+            load_matrix_word(state, a, f32::from_bits(word));
+        }
+    }
+    // Viewport and projection are only applied when loaded as a block, which
+    // is how GXSetViewport / GXSetProjection write them.
+    let viewport: Option<Vec<f32>> = (0..6).map(|i| word_at(XF_VIEWPORT + i)).collect();
+    if let Some(v) = viewport {
+        let (sx, sy, sz, ox, oy, oz) = (v[0], v[1], v[2], v[3], v[4], v[5]);
+        let offset = SCREEN_OFFSET as f32;
+        let far = oz / 16_777_215.0;
+        let near = far - sz / 16_777_215.0;
+        state.set_viewport(
+            ox - offset - sx,
+            oy - offset + sy,
+            sx * 2.0,
+            -sy * 2.0,
+            near,
+            far,
+        );
+    }
+    let projection: Option<Vec<f32>> = (0..6).map(|i| word_at(XF_PROJECTION + i)).collect();
+    if let (Some(p), Some(kind)) = (projection, raw_at(XF_PROJECTION + 6)) {
+        // Column-major; GX_PERSPECTIVE = 0, GX_ORTHOGRAPHIC = 1.
+        let mut m = [0.0f32; 16];
+        m[0] = p[0];
+        m[5] = p[2];
+        m[10] = p[4];
+        m[14] = p[5];
+        if kind == 0 {
+            m[8] = p[1];
+            m[9] = p[3];
+            m[11] = -1.0;
+        } else {
+            m[12] = p[1];
+            m[13] = p[3];
+            m[15] = 1.0;
+        }
+        state.set_projection(&m);
+    }
+}
+
+/// Store one word of XF matrix memory. Rows 0..30 are the ten position
+/// matrices, rows 30..60 the ten texture matrices.
+fn load_matrix_word(state: &mut GxState, addr: u16, value: f32) {
+    let row = (addr / 4) as usize;
+    let (slot, r, c) = (row / 3, row % 3, (addr % 4) as usize);
+    let m = match slot {
+        0..=9 => &mut state.matrices.position[slot],
+        10..=19 => &mut state.matrices.texture[slot - 10],
+        _ => return,
+    };
+    m[c * 4 + r] = value;
+}
+
+fn bits11(value: u32, shift: u32) -> i32 {
+    (value >> shift & 0x7FF) as i32
+}
+
+/// TEV op and scale from an env register. With bias = 3 the stage is a
+/// compare (GX_TEV_COMP_*), encoded in the op and scale fields.
+fn tev_op(value: u32) -> (u8, u8) {
+    let sub = bits(value, 18, 1);
+    let scale = bits(value, 20, 2);
+    if bits(value, 16, 2) == 3 {
+        (8 + (scale << 1 | sub), 0)
+    } else {
+        (sub, scale)
+    }
+}
+
+fn blend_factor(v: u8) -> BlendFactor {
+    match v {
+        0 => BlendFactor::Zero,
+        1 => BlendFactor::One,
+        2 => BlendFactor::SrcColor,
+        3 => BlendFactor::InvSrcColor,
+        4 => BlendFactor::SrcAlpha,
+        5 => BlendFactor::InvSrcAlpha,
+        6 => BlendFactor::DstAlpha,
+        _ => BlendFactor::InvDstAlpha,
+    }
+}
+
+fn logic_op(v: u8) -> LogicOp {
+    match v {
+        0 => LogicOp::Clear,
+        1 => LogicOp::And,
+        2 => LogicOp::RevAnd,
+        3 => LogicOp::Copy,
+        4 => LogicOp::InvAnd,
+        5 => LogicOp::Noop,
+        6 => LogicOp::Xor,
+        7 => LogicOp::Or,
+        8 => LogicOp::Nor,
+        9 => LogicOp::Equiv,
+        10 => LogicOp::Inv,
+        11 => LogicOp::RevOr,
+        12 => LogicOp::InvCopy,
+        13 => LogicOp::InvOr,
+        14 => LogicOp::Nand,
+        _ => LogicOp::Set,
+    }
+}
+
+fn bits(value: u32, shift: u32, width: u32) -> u8 {
+    ((value >> shift) & ((1 << width) - 1)) as u8
+}
+
+/// One texcoord's VAT fields: elements bit, then 3-bit format, then 5-bit frac.
+fn tex_fmt(value: u32, shift: u32) -> VtxAttrFmt {
+    VtxAttrFmt {
+        component_count: 1 + bits(value, shift, 1),
+        component_type: bits(value, shift + 1, 3),
+        frac_bits: bits(value, shift + 4, 5),
+    }
+}
+
+fn load_vat_a(state: &mut GxState, fmt: u8, v: u32) {
+    let table = &mut state.vertex_formats[fmt as usize];
+    table[VtxAttr::Position as usize] = VtxAttrFmt {
+        component_count: 2 + bits(v, 0, 1),
+        component_type: bits(v, 1, 3),
+        frac_bits: bits(v, 4, 5),
+    };
+    table[VtxAttr::Normal as usize] = VtxAttrFmt {
+        component_count: if bits(v, 9, 1) != 0 { 9 } else { 3 },
+        component_type: bits(v, 10, 3),
+        frac_bits: 0,
+    };
+    for (attr, shift) in [(VtxAttr::Color0, 13), (VtxAttr::Color1, 17)] {
+        table[attr as usize] = VtxAttrFmt {
+            component_count: 3 + bits(v, shift, 1),
+            component_type: bits(v, shift + 1, 3),
+            frac_bits: 0,
+        };
+    }
+    table[VtxAttr::Tex0 as usize] = tex_fmt(v, 21);
+}
+
+fn load_vat_b(state: &mut GxState, fmt: u8, v: u32) {
+    let table = &mut state.vertex_formats[fmt as usize];
+    table[VtxAttr::Tex1 as usize] = tex_fmt(v, 0);
+    table[VtxAttr::Tex2 as usize] = tex_fmt(v, 9);
+    table[VtxAttr::Tex3 as usize] = tex_fmt(v, 18);
+    // Tex4's frac bits continue in VAT_C.
+    let tex4 = &mut table[VtxAttr::Tex4 as usize];
+    tex4.component_count = 1 + bits(v, 27, 1);
+    tex4.component_type = bits(v, 28, 3);
+}
+
+fn load_vat_c(state: &mut GxState, fmt: u8, v: u32) {
+    let table = &mut state.vertex_formats[fmt as usize];
+    table[VtxAttr::Tex4 as usize].frac_bits = bits(v, 0, 5);
+    table[VtxAttr::Tex5 as usize] = tex_fmt(v, 5);
+    table[VtxAttr::Tex6 as usize] = tex_fmt(v, 14);
+    table[VtxAttr::Tex7 as usize] = tex_fmt(v, 23);
+}
+
+fn tex_attr(i: usize) -> VtxAttr {
+    VtxAttr::from_index(VtxAttr::Tex0 as u8 + i as u8).unwrap_or(VtxAttr::Tex0)
+}
+
+fn tex_mtx_attr(i: usize) -> VtxAttr {
+    VtxAttr::from_index(VtxAttr::Tex0MatrixIdx as u8 + i as u8).unwrap_or(VtxAttr::Tex0MatrixIdx)
+}
+
+fn component_size(ty: u8) -> usize {
+    match ty {
+        COMP_U8 | COMP_S8 => 1,
+        COMP_U16 | COMP_S16 => 2,
+        _ => 4,
+    }
+}
+
+fn color_size(ty: u8) -> usize {
+    match ty {
+        CLR_RGB565 | CLR_RGBA4 => 2,
+        CLR_RGB8 | CLR_RGBA6 => 3,
+        _ => 4,
+    }
+}
+
+/// Which attributes a vertex carries, in stream order, and their sizes.
+struct VertexLayout {
+    /// (attribute, input type, format, size of the attribute's data).
+    attrs: Vec<(VtxAttr, VtxInputType, VtxAttrFmt, usize)>,
+    /// Bytes per vertex in the stream.
+    size: usize,
+}
+
+impl VertexLayout {
+    fn new(state: &GxState, fmt: u8) -> Self {
+        let table = &state.vertex_formats[fmt as usize & 7];
+        let mut attrs = Vec::new();
+        let mut size = 0;
+        for desc in &state.vertex_descriptors {
+            if desc.input_type == VtxInputType::None {
+                continue;
+            }
+            // Matrix indices are always inline.
+            let input = if (desc.attr as u8) < VtxAttr::Position as u8 {
+                VtxInputType::Direct
+            } else {
+                desc.input_type
+            };
+            let mut f = table[desc.attr as usize];
+            let data = match desc.attr {
+                VtxAttr::Position => {
+                    if f.component_count == 0 {
+                        f.component_count = 3;
+                    }
+                    f.component_count as usize * component_size(f.component_type)
+                }
+                VtxAttr::Normal => {
+                    if f.component_count == 0 {
+                        f.component_count = 3;
+                    }
+                    f.component_count as usize * component_size(f.component_type)
+                }
+                VtxAttr::Color0 | VtxAttr::Color1 => color_size(f.component_type),
+                a if (a as u8) >= VtxAttr::Tex0 as u8 => {
+                    if f.component_count == 0 {
+                        f.component_count = 2;
+                    }
+                    f.component_count as usize * component_size(f.component_type)
+                }
+                // Matrix indices: one byte, always direct.
+                _ => 1,
+            };
+            size += match input {
+                VtxInputType::Index8 => 1,
+                VtxInputType::Index16 => 2,
+                _ => data,
+            };
+            attrs.push((desc.attr, input, f, data));
+        }
+        Self { attrs, size }
+    }
+}
+
+fn read_component(b: &[u8], ty: u8, frac: u8) -> f32 {
+    let raw = match ty {
+        COMP_U8 => b[0] as f32,
+        COMP_S8 => b[0] as i8 as f32,
+        COMP_U16 => u16::from_be_bytes([b[0], b[1]]) as f32,
+        COMP_S16 => i16::from_be_bytes([b[0], b[1]]) as f32,
+        _ => return f32::from_bits(u32::from_be_bytes([b[0], b[1], b[2], b[3]])),
+    };
+    raw / (1u32 << frac) as f32
+}
+
+fn read_components<const N: usize>(b: &[u8], f: &VtxAttrFmt, frac: u8) -> [f32; N] {
+    let size = component_size(f.component_type);
+    let mut out = [0.0; N];
+    for (i, v) in out.iter_mut().enumerate().take(f.component_count as usize) {
+        *v = read_component(&b[i * size..], f.component_type, frac);
+    }
+    out
+}
+
+fn read_color(b: &[u8], ty: u8) -> [u8; 4] {
+    let expand = |v: u32, bits: u32| ((v * 255) / ((1 << bits) - 1)) as u8;
+    match ty {
+        CLR_RGB565 => {
+            let v = u16::from_be_bytes([b[0], b[1]]) as u32;
+            [
+                expand(v >> 11, 5),
+                expand((v >> 5) & 0x3F, 6),
+                expand(v & 0x1F, 5),
+                255,
+            ]
+        }
+        CLR_RGB8 | CLR_RGBX8 => [b[0], b[1], b[2], 255],
+        CLR_RGBA4 => {
+            let v = u16::from_be_bytes([b[0], b[1]]) as u32;
+            [
+                expand(v >> 12, 4),
+                expand((v >> 8) & 0xF, 4),
+                expand((v >> 4) & 0xF, 4),
+                expand(v & 0xF, 4),
+            ]
+        }
+        CLR_RGBA6 => {
+            let v = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+            [
+                expand(v >> 18, 6),
+                expand((v >> 12) & 0x3F, 6),
+                expand((v >> 6) & 0x3F, 6),
+                expand(v & 0x3F, 6),
+            ]
+        }
+        _ => [b[0], b[1], b[2], b[3]],
+    }
+}
+
+/// Array index for an attribute in `GxState::array_bases`.
+pub(super) fn array_index(attr: VtxAttr) -> usize {
+    match attr {
+        VtxAttr::Position => 0,
+        VtxAttr::Normal => 1,
+        VtxAttr::Color0 => 2,
+        VtxAttr::Color1 => 3,
+        a => 4 + (a as usize - VtxAttr::Tex0 as usize),
+    }
+}
+
+fn draw(
+    gx: &mut GXProcessor,
+    primitive: u8,
+    fmt: u8,
+    count: u16,
+    layout: &VertexLayout,
+    data: &[u8],
+    memory: &MemoryManager,
+) {
+    gx.accumulator.begin(primitive, fmt, count);
+    let mut pos = 0;
+    for _ in 0..count {
+        for &(attr, input, f, size) in &layout.attrs {
+            // Direct data is inline; indexed data is fetched from its array.
+            let fetched;
+            let bytes: &[u8] = match input {
+                VtxInputType::Index8 | VtxInputType::Index16 => {
+                    let index = if input == VtxInputType::Index8 {
+                        pos += 1;
+                        data[pos - 1] as u32
+                    } else {
+                        pos += 2;
+                        u16::from_be_bytes([data[pos - 2], data[pos - 1]]) as u32
+                    };
+                    let array = array_index(attr);
+                    let addr = gx.state.array_bases[array]
+                        .wrapping_add(index * gx.state.array_strides[array]);
+                    fetched = memory
+                        .read_bytes(addr, size)
+                        .unwrap_or_else(|_| vec![0; size]);
+                    &fetched
+                }
+                _ => {
+                    pos += size;
+                    &data[pos - size..pos]
+                }
+            };
+            match attr {
+                VtxAttr::Position => {
+                    let [x, y, z] = read_components::<3>(bytes, &f, f.frac_bits);
+                    gx.accumulator.position_3f32(x, y, z);
+                }
+                VtxAttr::Normal => {
+                    // Normals have fixed scaling: s8 is 1.6, s16 is 1.14.
+                    let frac = match f.component_type {
+                        COMP_S8 => 6,
+                        COMP_S16 => 14,
+                        _ => 0,
+                    };
+                    let first = VtxAttrFmt {
+                        component_count: 3,
+                        ..f
+                    };
+                    let [x, y, z] = read_components::<3>(bytes, &first, frac);
+                    gx.accumulator.normal_3f32(x, y, z);
+                }
+                VtxAttr::Color0 => {
+                    let [r, g, b, a] = read_color(bytes, f.component_type);
+                    gx.accumulator.color_4u8(r, g, b, a);
+                }
+                VtxAttr::Color1 => {
+                    let [r, g, b, a] = read_color(bytes, f.component_type);
+                    gx.accumulator.color1_4u8(r, g, b, a);
+                }
+                a if (a as u8) >= VtxAttr::Tex0 as u8 => {
+                    let [s, t] = read_components::<2>(bytes, &f, f.frac_bits);
+                    gx.accumulator.texcoord_2f32(s, t);
+                }
+                // Matrix indices select transforms; not applied per vertex yet.
+                _ => {}
+            }
+        }
+    }
+    gx.end();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vat_and_vcd_loads_decode_into_state() {
+        let mut state = GxState::new();
+        // Position direct, color0 index8; tex0 index16
+        load_cp_reg(&mut state, CP_VCD_LO, 1 << 9 | 2 << 13);
+        load_cp_reg(&mut state, CP_VCD_HI, 3);
+        // VTXFMT1: position XYZ s16 frac 4, color0 RGBA8, tex0 ST f32
+        let vat_a = 1 | (COMP_S16 as u32) << 1 | 4 << 4 | 1 << 13 | 5 << 14 | 1 << 21 | 4 << 22;
+        load_cp_reg(&mut state, CP_VAT_A | 1, vat_a);
+
+        let desc = |a: VtxAttr| state.vertex_descriptors[a as usize].input_type;
+        assert_eq!(desc(VtxAttr::Position), VtxInputType::Direct);
+        assert_eq!(desc(VtxAttr::Color0), VtxInputType::Index8);
+        assert_eq!(desc(VtxAttr::Tex0), VtxInputType::Index16);
+        assert_eq!(desc(VtxAttr::Normal), VtxInputType::None);
+
+        let table = &state.vertex_formats[1];
+        assert_eq!(
+            table[VtxAttr::Position as usize],
+            VtxAttrFmt {
+                component_count: 3,
+                component_type: COMP_S16,
+                frac_bits: 4
+            }
+        );
+        assert_eq!(table[VtxAttr::Color0 as usize].component_type, 5);
+        assert_eq!(table[VtxAttr::Tex0 as usize].component_count, 2);
+        assert_eq!(table[VtxAttr::Tex0 as usize].component_type, 4);
+    }
+
+    #[test]
+    fn display_list_draws_one_triangle() {
+        let mut dl = Vec::new();
+        // Position direct; VTXFMT0 position XYZ f32
+        dl.push(OP_LOAD_CP_REG);
+        dl.push(CP_VCD_LO);
+        dl.extend_from_slice(&(1u32 << 9).to_be_bytes());
+        dl.push(OP_LOAD_CP_REG);
+        dl.push(CP_VAT_A);
+        dl.extend_from_slice(&(1u32 | 4 << 1).to_be_bytes());
+        // GX_TRIANGLES, VTXFMT0, 3 vertices
+        dl.push(0x90);
+        dl.extend_from_slice(&3u16.to_be_bytes());
+        for v in [0.0f32, 1.0, 0.0, -1.0, -1.0, 0.0, 1.0, -1.0, 0.0] {
+            dl.extend_from_slice(&v.to_be_bytes());
+        }
+        // Display lists are padded to 32 bytes with NOPs
+        while dl.len() % 32 != 0 {
+            dl.push(OP_NOP);
+        }
+
+        let mut memory = MemoryManager::new();
+        memory.write_bytes(0x8000_1000, &dl).unwrap();
+        let mut gx = GXProcessor::new();
+        gx.call_display_list(0x8000_1000, dl.len() as u32, &memory);
+
+        let draws = gx.take_draw_list();
+        assert_eq!(draws.len(), 1);
+        assert_eq!(
+            draws[0].primitive,
+            super::super::vertex::GxPrimitive::Triangles
+        );
+        assert_eq!(draws[0].vertex_count, 3);
+        assert_eq!(draws[0].stride, 3);
+        assert_eq!(&draws[0].vertex_data[3..6], &[-1.0, -1.0, 0.0]);
+    }
+
+    #[test]
+    fn bp_genmode_and_xf_projection() {
+        let mut state = GxState::new();
+        // 2 texgens, 1 channel, 3 TEV stages, hardware cull "front"
+        load_bp_reg(&mut state, BP_GENMODE, 2 | 1 << 4 | 2 << 10 | 2 << 14);
+        assert_eq!(state.num_tex_gens, 2);
+        assert_eq!(state.num_channels, 1);
+        assert_eq!(state.num_tev_stages, 3);
+        assert_eq!(state.cull_mode, CullMode::Front);
+
+        let p = [1.5f32, 0.25, 2.0, -0.5, -1.0, -0.2];
+        let mut words: Vec<u32> = p.iter().map(|f| f.to_bits()).collect();
+        words.push(0); // GX_PERSPECTIVE
+        load_xf_regs(&mut state, XF_PROJECTION, &words);
+        let m = state.matrices.projection;
+        assert_eq!((m[0], m[8], m[5], m[9]), (1.5, 0.25, 2.0, -0.5));
+        assert_eq!((m[10], m[14], m[11], m[15]), (-1.0, -0.2, -1.0, 0.0));
+    }
+
+    #[test]
+    fn bp_indirect_registers() {
+        let mut state = GxState::new();
+        load_bp_reg(&mut state, BP_GENMODE, 1 << 16);
+        assert_eq!(state.num_ind_stages, 1);
+
+        // Indirect stage 0 reads map 2 with coordinate 1, halved in S and T.
+        load_bp_reg(&mut state, BP_RAS1_IREF, 2 | 1 << 3);
+        load_bp_reg(&mut state, BP_RAS1_SS0, 1 | 1 << 4);
+        assert_eq!(state.ind_stages[0].tex_map, 2);
+        assert_eq!(state.ind_stages[0].tex_coord, 1);
+        assert_eq!(
+            (state.ind_stages[0].scale_s, state.ind_stages[0].scale_t),
+            (1, 1)
+        );
+
+        // Matrix 0 = [[0.5, 0, 0], [0, -0.5, 0]], scale exponent 0 (raw 17).
+        load_bp_reg(&mut state, BP_IND_MTXA_0, 512 | 1 << 22);
+        load_bp_reg(
+            &mut state,
+            BP_IND_MTXA_0 + 1,
+            (0x7FF & -512i32 as u32) << 11,
+        );
+        load_bp_reg(&mut state, BP_IND_MTXA_0 + 2, 1 << 22);
+        let m = state.ind_matrices[0];
+        assert_eq!(m.offset, [[0.5, 0.0, 0.0], [0.0, -0.5, 0.0]]);
+        assert_eq!(m.scale_exp, 0);
+
+        // TEV stage 1: 8-bit offsets biased in S and T, matrix 0, add prev.
+        load_bp_reg(&mut state, BP_IND_CMD_0 + 1, 3 << 4 | 1 << 9 | 1 << 20);
+        let ind = state.tev_stages[1].indirect;
+        assert_eq!((ind.bias, ind.matrix, ind.add_prev), (3, 1, true));
+        assert!(!ind.is_direct());
+        assert!(state.tev_stages[0].indirect.is_direct());
+    }
+
+    #[test]
+    fn bp_tex_image_binds_map() {
+        let mut state = GxState::new();
+        // GX_TEXMAP5: 128x64 RGBA8 at 0x00123400.
+        load_bp_reg(&mut state, BP_TX_SETIMAGE0_4 + 1, 127 | 63 << 10 | 6 << 20);
+        load_bp_reg(&mut state, BP_TX_SETIMAGE3_4 + 1, 0x0012_3400 >> 5);
+        assert_eq!(
+            state.tex_images[5],
+            Some(TexImage {
+                address: 0x0012_3400,
+                width: 128,
+                height: 64,
+                format: 6,
+            })
+        );
+        assert_eq!(state.tex_images[1], None);
+    }
+}
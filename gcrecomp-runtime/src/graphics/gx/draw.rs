@@ -1,4 +1,5 @@
 /// Translates accumulated GX vertex data + state into wgpu draw calls.
+use super::state::{Scissor, Viewport};
 use super::vertex::DrawCall;
 use wgpu::util::DeviceExt;
 use wgpu::*;
@@ -10,6 +11,100 @@ pub struct PreparedDraw {
     pub primitive_topology: PrimitiveTopology,
 }
 
+/// GX coordinates (viewport, scissor) are in native EFB pixels.
+pub const EFB_WIDTH: u32 = 640;
+pub const EFB_HEIGHT: u32 = 480;
+
+/// Arguments for `RenderPass::set_viewport`, plus the clip-space correction
+/// needed when the GX viewport had to be clamped to the render target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderViewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+    /// Apply `ndc.xy * ndc_scale + ndc_offset` in the vertex shader so
+    /// geometry lands where the unclamped viewport would have put it.
+    /// Identity (`[1, 1]`, `[0, 0]`) when no clamping happened.
+    pub ndc_scale: [f32; 2],
+    pub ndc_offset: [f32; 2],
+}
+
+/// Map a GX viewport to a render target of `target` pixels.
+///
+/// wgpu rejects viewports that leave the target, while GX games routinely
+/// set larger ones (guard bands, screen shake), so the rectangle is clamped
+/// and the difference folded into `ndc_scale` / `ndc_offset`. The depth
+/// range is the GX near/far pair; with the projection remapped by
+/// `transform::to_wgpu_depth_range` it applies as-is.
+pub fn render_viewport(vp: &Viewport, target: (u32, u32)) -> Option<RenderViewport> {
+    let sx = target.0 as f32 / EFB_WIDTH as f32;
+    let sy = target.1 as f32 / EFB_HEIGHT as f32;
+    // GX allows a negative height (flipped); normalize to a positive rect.
+    let (x, w) = (vp.x.min(vp.x + vp.width) * sx, vp.width.abs() * sx);
+    let (y, h) = (vp.y.min(vp.y + vp.height) * sy, vp.height.abs() * sy);
+    let x0 = x.clamp(0.0, target.0 as f32);
+    let y0 = y.clamp(0.0, target.1 as f32);
+    let x1 = (x + w).clamp(0.0, target.0 as f32);
+    let y1 = (y + h).clamp(0.0, target.1 as f32);
+    if x1 - x0 <= 0.0 || y1 - y0 <= 0.0 {
+        return None;
+    }
+    let (cw, ch) = (x1 - x0, y1 - y0);
+    let near = vp.near.clamp(0.0, 1.0);
+    let far = vp.far.clamp(0.0, 1.0);
+    Some(RenderViewport {
+        x: x0,
+        y: y0,
+        width: cw,
+        height: ch,
+        min_depth: near.min(far),
+        max_depth: near.max(far),
+        ndc_scale: [w / cw, h / ch],
+        // Centre shift in NDC units of the clamped viewport (y points down
+        // in window space, up in NDC).
+        ndc_offset: [
+            ((x + w / 2.0) - (x0 + cw / 2.0)) / (cw / 2.0),
+            -((y + h / 2.0) - (y0 + ch / 2.0)) / (ch / 2.0),
+        ],
+    })
+}
+
+/// Arguments for `RenderPass::set_scissor_rect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScissorRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Map a GX scissor rectangle to a render target of `target` pixels,
+/// clamping it to the target. `None` if nothing is left to draw.
+pub fn scissor_rect(scissor: &Scissor, target: (u32, u32)) -> Option<ScissorRect> {
+    let scale = |v: u16, num: u32, den: u32| (v as u64 * num as u64 / den as u64) as u32;
+    let x0 = scale(scissor.x, target.0, EFB_WIDTH).min(target.0);
+    let y0 = scale(scissor.y, target.1, EFB_HEIGHT).min(target.1);
+    let x1 = scale(scissor.x.saturating_add(scissor.width), target.0, EFB_WIDTH).min(target.0);
+    let y1 = scale(
+        scissor.y.saturating_add(scissor.height),
+        target.1,
+        EFB_HEIGHT,
+    )
+    .min(target.1);
+    if x1 <= x0 || y1 <= y0 {
+        return None;
+    }
+    Some(ScissorRect {
+        x: x0,
+        y: y0,
+        width: x1 - x0,
+        height: y1 - y0,
+    })
+}
+
 /// Convert a GX primitive type byte to wgpu PrimitiveTopology.
 pub fn gx_primitive_to_topology(prim: u8) -> PrimitiveTopology {
     match prim {
@@ -109,3 +204,71 @@ pub fn prepare_draw_call(device: &Device, draw_call: &DrawCall) -> PreparedDraw
         primitive_topology: topology,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_screen_viewport_maps_to_target() {
+        // Right half of the screen (split-screen player 2), full depth range.
+        let vp = Viewport {
+            x: 320.0,
+            y: 0.0,
+            width: 320.0,
+            height: 480.0,
+            near: 0.0,
+            far: 1.0,
+        };
+        let rv = render_viewport(&vp, (1280, 960)).unwrap();
+        assert_eq!(
+            (rv.x, rv.y, rv.width, rv.height),
+            (640.0, 0.0, 640.0, 960.0)
+        );
+        assert_eq!((rv.min_depth, rv.max_depth), (0.0, 1.0));
+        assert_eq!(rv.ndc_scale, [1.0, 1.0]);
+        assert_eq!(rv.ndc_offset, [0.0, 0.0]);
+
+        // A guard-band viewport is clamped and compensated in clip space.
+        let wide = Viewport {
+            x: -320.0,
+            width: 1280.0,
+            ..Viewport::default()
+        };
+        let rv = render_viewport(&wide, (640, 480)).unwrap();
+        assert_eq!((rv.x, rv.width), (0.0, 640.0));
+        assert_eq!(rv.ndc_scale, [2.0, 1.0]);
+        assert_eq!(rv.ndc_offset, [0.0, 0.0]);
+    }
+
+    #[test]
+    fn scissor_clamps_to_target() {
+        let sc = Scissor {
+            x: 600,
+            y: 400,
+            width: 200,
+            height: 200,
+        };
+        assert_eq!(
+            scissor_rect(&sc, (640, 480)),
+            Some(ScissorRect {
+                x: 600,
+                y: 400,
+                width: 40,
+                height: 80
+            })
+        );
+        // Scaled 2x for an upscaled EFB.
+        assert_eq!(
+            scissor_rect(&sc, (1280, 960)),
+            Some(ScissorRect {
+                x: 1200,
+                y: 800,
+                width: 80,
+                height: 160
+            })
+        );
+        let off = Scissor { x: 700, ..sc };
+        assert_eq!(scissor_rect(&off, (640, 480)), None);
+    }
+}
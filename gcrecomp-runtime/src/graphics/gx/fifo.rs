@@ -0,0 +1,244 @@
+// GP FIFO command processor.
+//
+// Games don't call into the GX processor directly: the SDK writes GP
+// commands through the write-gather pipe into a ring buffer in main RAM
+// (`GXSetCPUFifo` / `GXSetGPFifo`), and the command processor consumes them
+// between its read and write pointers. The ring is drained here and the
+// commands are executed with the same decoder as display lists.
+//
+// A command straddling the current write pointer stays buffered until the
+// rest of it arrives; one straddling the end of the ring is read across the
+// wrap like any other.
+
+use super::command;
+use super::GXProcessor;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use log::warn;
+
+/// PI FIFO registers (the CPU side of the ring).
+pub const PI_FIFO_BASE: u32 = 0xCC00_300C;
+pub const PI_FIFO_END: u32 = 0xCC00_3010;
+pub const PI_FIFO_WPTR: u32 = 0xCC00_3014;
+/// Set in `PI_FIFO_WPTR` each time the write pointer wraps.
+const PI_WPTR_WRAP: u32 = 1 << 26;
+const PHYS_MASK: u32 = 0x03FF_FFFF;
+
+#[derive(Debug, Default)]
+pub struct GpFifo {
+    base: u32,
+    size: u32,
+    read_ptr: u32,
+    write_ptr: u32,
+    /// Bytes written but not yet read.
+    distance: u32,
+    /// Tail of the last batch that didn't form a complete command.
+    pending: Vec<u8>,
+}
+
+impl GpFifo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach the ring at `base` (`size` bytes), emptying it (`GXSetGPFifo`).
+    pub fn set_fifo(&mut self, base: u32, size: u32) {
+        *self = Self {
+            base,
+            size,
+            read_ptr: base,
+            write_ptr: base,
+            ..Self::default()
+        };
+    }
+
+    /// Detach the ring and drop anything buffered.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.size != 0
+    }
+
+    pub fn read_ptr(&self) -> u32 {
+        self.read_ptr
+    }
+
+    pub fn write_ptr(&self) -> u32 {
+        self.write_ptr
+    }
+
+    /// Bytes waiting to be processed.
+    pub fn distance(&self) -> u32 {
+        self.distance
+    }
+
+    /// Move the write pointer to `ptr` (the CPU wrote up to there).
+    pub fn set_write_ptr(&mut self, ptr: u32) {
+        if !self.contains(ptr) {
+            warn!("GX FIFO: write pointer 0x{:08X} outside the ring", ptr);
+            return;
+        }
+        self.write_ptr = ptr;
+        self.distance = (ptr.wrapping_sub(self.read_ptr)).wrapping_add(self.size) % self.size;
+    }
+
+    /// Write `bytes` at the write pointer, wrapping at the end of the ring,
+    /// as the write-gather pipe does.
+    pub fn push(&mut self, memory: &mut MemoryManager, bytes: &[u8]) -> anyhow::Result<()> {
+        if !self.is_attached() {
+            anyhow::bail!("GX FIFO: write with no FIFO attached");
+        }
+        if self.distance as usize + bytes.len() > self.size as usize {
+            anyhow::bail!("GX FIFO: overflow ({} bytes queued)", self.distance);
+        }
+        let mut rest = bytes;
+        while !rest.is_empty() {
+            let room = (self.base + self.size - self.write_ptr) as usize;
+            let (now, later) = rest.split_at(room.min(rest.len()));
+            memory.write_bytes(self.write_ptr, now)?;
+            self.write_ptr = self.advance(self.write_ptr, now.len() as u32);
+            rest = later;
+        }
+        self.distance += bytes.len() as u32;
+        Ok(())
+    }
+
+    /// Store the write pointer in `PI_FIFO_WPTR`, as the hardware does after
+    /// each burst, so the game sees its progress and `sync_registers` doesn't
+    /// move it back.
+    pub fn publish_write_ptr(&self, memory: &mut MemoryManager) {
+        if self.is_attached() {
+            let _ = memory.write_io_u32(PI_FIFO_WPTR, self.write_ptr & PHYS_MASK);
+        }
+    }
+
+    /// Pick up the ring configuration and write pointer from the PI
+    /// registers, reattaching if the game moved the FIFO.
+    pub fn sync_registers(&mut self, memory: &MemoryManager) -> anyhow::Result<()> {
+        let base = 0x8000_0000 | (memory.read_u32(PI_FIFO_BASE)? & PHYS_MASK);
+        let end = 0x8000_0000 | (memory.read_u32(PI_FIFO_END)? & PHYS_MASK);
+        let wptr = memory.read_u32(PI_FIFO_WPTR)?;
+        if end <= base {
+            return Ok(());
+        }
+        if base != self.base || end - base != self.size {
+            self.set_fifo(base, end - base);
+        }
+        let ptr = 0x8000_0000 | (wptr & PHYS_MASK & !PI_WPTR_WRAP);
+        // A wrap flag with an unchanged pointer means a full lap was written.
+        if ptr == self.write_ptr && wptr & PI_WPTR_WRAP != 0 && self.distance == 0 {
+            self.distance = self.size;
+        } else if ptr != self.write_ptr {
+            self.set_write_ptr(ptr);
+        }
+        Ok(())
+    }
+
+    /// Execute every command queued since the last call; returns the bytes
+    /// read from the ring.
+    pub fn process(&mut self, gx: &mut GXProcessor, memory: &MemoryManager) -> usize {
+        let available = self.distance;
+        if available == 0 {
+            return 0;
+        }
+        let mut remaining = available;
+        while remaining > 0 {
+            let chunk = remaining.min(self.base + self.size - self.read_ptr);
+            match memory.read_bytes(self.read_ptr, chunk as usize) {
+                Ok(bytes) => self.pending.extend_from_slice(&bytes),
+                Err(e) => {
+                    warn!("GX FIFO: read at 0x{:08X} failed: {}", self.read_ptr, e);
+                    self.pending.clear();
+                }
+            }
+            self.read_ptr = self.advance(self.read_ptr, chunk);
+            remaining -= chunk;
+        }
+        self.distance = 0;
+
+        let consumed = command::execute(gx, &self.pending, memory, true);
+        self.pending.drain(..consumed);
+        available as usize
+    }
+
+    fn contains(&self, addr: u32) -> bool {
+        self.is_attached() && addr >= self.base && addr < self.base + self.size
+    }
+
+    fn advance(&self, ptr: u32, by: u32) -> u32 {
+        self.base + (ptr - self.base + by) % self.size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gx::command::{
+        BP_ZMODE, CP_VAT_A, CP_VCD_LO, OP_LOAD_BP_REG, OP_LOAD_CP_REG,
+    };
+    use crate::graphics::gx::state::CompareFunction;
+    use crate::graphics::gx::vertex::GxPrimitive;
+
+    fn bp(reg: u8, value: u32) -> Vec<u8> {
+        let mut out = vec![OP_LOAD_BP_REG];
+        out.extend_from_slice(&((reg as u32) << 24 | value).to_be_bytes());
+        out
+    }
+
+    fn cp(reg: u8, value: u32) -> Vec<u8> {
+        let mut out = vec![OP_LOAD_CP_REG, reg];
+        out.extend_from_slice(&value.to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn bp_load_and_draw_through_wrapping_fifo() {
+        let mut memory = MemoryManager::new();
+        let mut gx = GXProcessor::new();
+        // Small ring so the draw wraps around its end.
+        gx.fifo_mut().set_fifo(0x8010_0000, 48);
+
+        // Z test GREATER, no update; position-only XYZ f32 vertices.
+        let mut setup = bp(BP_ZMODE, 1 | 4 << 1);
+        setup.extend(cp(CP_VCD_LO, 1 << 9));
+        setup.extend(cp(CP_VAT_A, 1 | 4 << 1));
+        gx.fifo_mut().push(&mut memory, &setup).unwrap();
+        assert_eq!(gx.process_fifo(&memory), setup.len());
+        assert_eq!(gx.state.z_mode.function, CompareFunction::Greater);
+        assert!(gx.state.z_mode.enable);
+        assert!(!gx.state.z_mode.update);
+
+        // GX_TRIANGLES, VTXFMT0: 3 + 36 bytes, crossing the end of the ring.
+        let mut draw = vec![0x90, 0x00, 0x03];
+        for v in [0.0f32, 1.0, 0.0, -1.0, -1.0, 0.0, 1.0, -1.0, 0.0] {
+            draw.extend_from_slice(&v.to_be_bytes());
+        }
+        let (first, second) = draw.split_at(20);
+        gx.fifo_mut().push(&mut memory, first).unwrap();
+        gx.process_fifo(&memory);
+        // Incomplete: nothing drawn yet.
+        assert!(gx.take_draw_list().is_empty());
+
+        gx.fifo_mut().push(&mut memory, second).unwrap();
+        assert!(gx.fifo().write_ptr() < 0x8010_0000 + setup.len() as u32);
+        gx.process_fifo(&memory);
+        let draws = gx.take_draw_list();
+        assert_eq!(draws.len(), 1);
+        assert_eq!(draws[0].primitive, GxPrimitive::Triangles);
+        assert_eq!(draws[0].vertex_count, 3);
+        assert_eq!(gx.fifo().read_ptr(), gx.fifo().write_ptr());
+    }
+
+    #[test]
+    fn masked_bp_write_keeps_unmasked_bits() {
+        let mut gx = GXProcessor::new();
+        gx.write_bp_reg(BP_ZMODE, 1 | 3 << 1 | 1 << 4);
+        // Only touch the compare function.
+        gx.write_bp_reg(command::BP_MASK, 0x00_000E);
+        gx.write_bp_reg(BP_ZMODE, 7 << 1);
+        assert_eq!(gx.state.z_mode.function, CompareFunction::Always);
+        assert!(gx.state.z_mode.enable);
+        assert!(gx.state.z_mode.update);
+    }
+}
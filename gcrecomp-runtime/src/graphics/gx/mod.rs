@@ -3,7 +3,9 @@
 // Submodules implement individual hardware subsystems; `GXProcessor`
 // is the top-level façade exposed to the rest of the runtime.
 
+pub mod command;
 pub mod draw;
+pub mod fifo;
 pub mod lighting;
 pub mod pipeline;
 pub mod state;
@@ -11,9 +13,12 @@ pub mod tev;
 pub mod transform;
 pub mod vertex;
 
+use self::fifo::GpFifo;
 use self::pipeline::PipelineCache;
 use self::state::GxState;
 use self::vertex::{DrawCall, VertexAccumulator};
+use crate::memory::WriteGatherPipe;
+use gcrecomp_core::runtime::memory::MemoryManager;
 
 /// Top-level GX processor that games interact with through SDK calls.
 ///
@@ -28,6 +33,14 @@ pub struct GXProcessor {
     draw_list: Vec<DrawCall>,
     /// Cached wgpu render pipelines keyed by GX state hash.
     pipeline_cache: PipelineCache,
+    /// Command processor for the GP FIFO.
+    fifo: GpFifo,
+    /// Raw BP register values, for masked writes.
+    bp_regs: [u32; 0x100],
+    /// Write mask for the next BP load (`BP_MASK`); reset after each load.
+    bp_mask: u32,
+    /// `GxState::validate` problems already logged this frame.
+    reported: Vec<String>,
 }
 
 impl GXProcessor {
@@ -37,6 +50,10 @@ impl GXProcessor {
             accumulator: VertexAccumulator::new(),
             draw_list: Vec::new(),
             pipeline_cache: PipelineCache::new(),
+            fifo: GpFifo::new(),
+            bp_regs: [0; 0x100],
+            bp_mask: 0xFF_FFFF,
+            reported: Vec::new(),
         }
     }
 
@@ -51,12 +68,40 @@ impl GXProcessor {
         self.accumulator.begin(primitive, vtx_fmt, count);
     }
 
+    /// `GXEnd`: close the primitive, recording the viewport and scissor it
+    /// is drawn with. An inconsistent GX state (`GxState::validate`) is
+    /// logged, once per problem per frame.
     pub fn end(&mut self) {
-        if let Some(dc) = self.accumulator.end() {
+        if let Some(mut dc) = self.accumulator.end() {
+            if let Err(problems) = self.state.validate() {
+                for problem in problems {
+                    if !self.reported.contains(&problem) {
+                        log::warn!("GX: draw with invalid state: {problem}");
+                        self.reported.push(problem);
+                    }
+                }
+            }
+            dc.viewport = self.state.viewport;
+            dc.scissor = self.state.scissor;
             self.draw_list.push(dc);
         }
     }
 
+    /// `GXSetViewport`: EFB-space rectangle and depth range (0..1).
+    pub fn set_viewport(&mut self, x: f32, y: f32, w: f32, h: f32, near: f32, far: f32) {
+        self.state.set_viewport(x, y, w, h, near, far);
+    }
+
+    /// `GXSetScissor`: EFB-space rectangle outside which nothing is drawn.
+    pub fn set_scissor(&mut self, x: u16, y: u16, w: u16, h: u16) {
+        self.state.set_scissor(x, y, w, h);
+    }
+
+    /// `GXSetCullMode`: which triangle faces to reject (`GX_CULL_*`).
+    pub fn set_cull_mode(&mut self, mode: u8) {
+        self.state.set_cull_mode(state::CullMode::from_u8(mode));
+    }
+
     pub fn position_3f32(&mut self, x: f32, y: f32, z: f32) {
         self.accumulator.position_3f32(x, y, z);
     }
@@ -77,10 +122,87 @@ impl GXProcessor {
         self.accumulator.texcoord_2f32(s, t);
     }
 
+    // -- Display lists ---------------------------------------------------
+
+    /// `GXCallDisplayList`: execute the `size`-byte command stream at `addr`,
+    /// appending its draws to the frame's draw list. Vertices are decoded
+    /// with the current vertex descriptor and formats, which register loads
+    /// in the list itself can change.
+    pub fn call_display_list(&mut self, addr: u32, size: u32, memory: &MemoryManager) {
+        let bytes = match memory.read_bytes(addr, size as usize) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                log::warn!("GX: display list at 0x{:08X} unreadable: {}", addr, e);
+                return;
+            }
+        };
+        let consumed = command::execute(self, &bytes, memory, false);
+        if consumed < bytes.len() && bytes[consumed..].iter().any(|&b| b != 0) {
+            log::warn!(
+                "GX: display list at 0x{:08X} ends mid-command ({} bytes left)",
+                addr,
+                bytes.len() - consumed
+            );
+        }
+    }
+
+    // -- Command processor -----------------------------------------------
+
+    /// Write a BP register, honouring a pending `BP_MASK`.
+    pub fn write_bp_reg(&mut self, reg: u8, value: u32) {
+        if reg == command::BP_MASK {
+            self.bp_mask = value & 0xFF_FFFF;
+            return;
+        }
+        let old = self.bp_regs[reg as usize];
+        let value = (old & !self.bp_mask) | (value & self.bp_mask);
+        self.bp_mask = 0xFF_FFFF;
+        self.bp_regs[reg as usize] = value;
+        command::load_bp_reg(&mut self.state, reg, value);
+    }
+
+    pub fn fifo(&self) -> &GpFifo {
+        &self.fifo
+    }
+
+    pub fn fifo_mut(&mut self) -> &mut GpFifo {
+        &mut self.fifo
+    }
+
+    /// Execute everything written to the GP FIFO since the last call.
+    /// Returns the number of bytes consumed.
+    pub fn process_fifo(&mut self, memory: &MemoryManager) -> usize {
+        let mut fifo = std::mem::take(&mut self.fifo);
+        let consumed = fifo.process(self, memory);
+        self.fifo = fifo;
+        consumed
+    }
+
+    /// Copy the write-gather pipe's full bursts into the GP FIFO, in order,
+    /// and execute what is queued. Returns the bytes consumed.
+    pub fn flush_write_gather(
+        &mut self,
+        pipe: &WriteGatherPipe,
+        memory: &mut MemoryManager,
+    ) -> usize {
+        let bursts = pipe.take_bursts();
+        if !bursts.is_empty() {
+            for (i, burst) in bursts.iter().enumerate() {
+                if let Err(e) = self.fifo.push(memory, burst) {
+                    log::warn!("{:#}; {} gathered bursts dropped", e, bursts.len() - i);
+                    break;
+                }
+            }
+            self.fifo.publish_write_ptr(memory);
+        }
+        self.process_fifo(memory)
+    }
+
     // -- Frame lifecycle -------------------------------------------------
 
     /// Take the accumulated draw list for rendering and clear it.
     pub fn take_draw_list(&mut self) -> Vec<DrawCall> {
+        self.reported.clear();
         std::mem::take(&mut self.draw_list)
     }
 
@@ -96,6 +218,9 @@ impl GXProcessor {
     /// Reset all GX state to power-on defaults.
     pub fn reset(&mut self) {
         self.state.reset();
+        self.fifo.reset();
+        self.bp_regs = [0; 0x100];
+        self.bp_mask = 0xFF_FFFF;
         self.draw_list.clear();
         self.pipeline_cache.clear();
     }
@@ -1,4 +1,6 @@
 /// Pipeline cache: creates/caches wgpu::RenderPipeline from GX state.
+use super::state::{CullMode, GxState, TexFilter, TexSampler, TexWrap, ZMode};
+use image::RgbaImage;
 use std::collections::HashMap;
 use wgpu::*;
 
@@ -12,15 +14,66 @@ pub struct PipelineKey {
     pub z_write: bool,
     pub z_func: u8,
     pub cull_mode: u8,
+    /// The viewport is Y-flipped (negative height), which reverses the
+    /// screen-space winding GX culls by; see `primitive_state`.
+    pub flip_y: bool,
     pub color_update: bool,
     pub alpha_update: bool,
     pub primitive_topology: u32,
+    /// Fog curve; baked into the fragment shader (see `tev::generate_fog_wgsl`).
+    pub fog_type: u8,
 }
 
+impl PipelineKey {
+    /// Key for drawing `primitive_topology` with the current GX state.
+    pub fn from_state(state: &GxState, primitive_topology: u32) -> Self {
+        Self {
+            num_tev_stages: state.num_tev_stages,
+            blend_src: state.blend_mode.src_factor as u32,
+            blend_dst: state.blend_mode.dst_factor as u32,
+            z_enable: state.z_mode.enable,
+            z_write: state.z_mode.update,
+            z_func: state.z_mode.function as u8,
+            cull_mode: state.cull_mode as u8,
+            flip_y: state.viewport.height < 0.0,
+            color_update: state.color_update,
+            alpha_update: state.alpha_update,
+            primitive_topology,
+            fog_type: state.fog.fog_type as u8,
+        }
+    }
+
+    pub fn z_mode(&self) -> ZMode {
+        ZMode {
+            enable: self.z_enable,
+            function: super::state::CompareFunction::from_u8(self.z_func),
+            update: self.z_write,
+        }
+    }
+
+    /// `GX_CULL_ALL` on triangles: GX rejects every face, which wgpu has no
+    /// cull mode for, so these draws are skipped instead. Lines and points
+    /// are never culled.
+    pub fn culls_all(&self) -> bool {
+        CullMode::from_u8(self.cull_mode) == CullMode::All
+            && matches!(self.primitive_topology, 3 | 4)
+    }
+}
+
+/// Format of the EFB depth attachment. GX depth is 24-bit; Depth24Plus holds
+/// at least that.
+pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24Plus;
+
+/// Largest 24-bit EFB depth value (the far plane).
+pub const MAX_DEPTH_24: u32 = 0x00FF_FFFF;
+
 pub struct PipelineCache {
     cache: HashMap<PipelineKey, RenderPipeline>,
     bind_group_layout: Option<BindGroupLayout>,
     pipeline_layout: Option<PipelineLayout>,
+    samplers: HashMap<TexSampler, Sampler>,
+    /// User anisotropy level replacing the GX `max_aniso` of every sampler.
+    anisotropy_override: Option<u16>,
 }
 
 impl PipelineCache {
@@ -29,9 +82,29 @@ impl PipelineCache {
             cache: HashMap::new(),
             bind_group_layout: None,
             pipeline_layout: None,
+            samplers: HashMap::new(),
+            anisotropy_override: None,
         }
     }
 
+    /// Force an anisotropy level (1-16) on all GX samplers, or `None` to use
+    /// what the game configured.
+    pub fn set_anisotropy_override(&mut self, level: Option<u16>) {
+        let level = level.map(|l| l.clamp(1, 16));
+        if level != self.anisotropy_override {
+            self.anisotropy_override = level;
+            self.samplers.clear();
+        }
+    }
+
+    /// Get or create the wgpu sampler for a texture map's GX sampler state.
+    pub fn sampler(&mut self, device: &Device, sampler: &TexSampler) -> &Sampler {
+        let anisotropy = self.anisotropy_override;
+        self.samplers
+            .entry(*sampler)
+            .or_insert_with(|| device.create_sampler(&sampler_descriptor(sampler, anisotropy)))
+    }
+
     /// Initialize the shared bind group layout and pipeline layout.
     pub fn init_layouts(&mut self, device: &Device) {
         let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -108,12 +181,6 @@ impl PipelineCache {
         fragment_shader: &ShaderModule,
         surface_format: TextureFormat,
     ) -> RenderPipeline {
-        let cull_mode = match key.cull_mode {
-            1 => Some(Face::Front),
-            2 => Some(Face::Back),
-            _ => None,
-        };
-
         let topology = match key.primitive_topology {
             1 => PrimitiveTopology::LineList,
             2 => PrimitiveTopology::LineStrip,
@@ -144,18 +211,6 @@ impl PipelineCache {
             }
         };
 
-        let depth_compare = match key.z_func {
-            0 => CompareFunction::Never,
-            1 => CompareFunction::Less,
-            2 => CompareFunction::Equal,
-            3 => CompareFunction::LessEqual,
-            4 => CompareFunction::Greater,
-            5 => CompareFunction::NotEqual,
-            6 => CompareFunction::GreaterEqual,
-            7 => CompareFunction::Always,
-            _ => CompareFunction::LessEqual,
-        };
-
         let pipeline_layout = self
             .pipeline_layout
             .as_ref()
@@ -210,26 +265,10 @@ impl PipelineCache {
                     write_mask,
                 })],
             }),
-            primitive: PrimitiveState {
-                topology,
-                strip_index_format: None,
-                front_face: FrontFace::Cw, // GameCube uses CW winding
-                cull_mode,
-                unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
-                conservative: false,
-            },
-            depth_stencil: if key.z_enable {
-                Some(DepthStencilState {
-                    format: TextureFormat::Depth24Plus,
-                    depth_write_enabled: key.z_write,
-                    depth_compare,
-                    stencil: StencilState::default(),
-                    bias: DepthBiasState::default(),
-                })
-            } else {
-                None
-            },
+            primitive: primitive_state(key, topology),
+            // Every GX pass has a depth attachment, so every pipeline declares
+            // one; a disabled Z mode becomes an always-pass, no-write state.
+            depth_stencil: Some(depth_stencil_state(&key.z_mode())),
             multisample: MultisampleState::default(),
             multiview: None,
         })
@@ -237,6 +276,7 @@ impl PipelineCache {
 
     pub fn clear(&mut self) {
         self.cache.clear();
+        self.samplers.clear();
     }
 }
 
@@ -246,6 +286,151 @@ impl Default for PipelineCache {
     }
 }
 
+/// Rasterizer state for a pipeline key. GX treats clockwise triangles (in
+/// window space) as front-facing and culls per `GXSetCullMode`. The viewport
+/// is always set with a positive height (`draw::render_viewport`), so when GX
+/// had it Y-flipped, as games do when rendering into an EFB copy that is
+/// later sampled upside down, the winding wgpu sees is reversed and the front
+/// face becomes counter-clockwise. `GX_CULL_ALL` maps to no culling here;
+/// callers skip those draws (`PipelineKey::culls_all`).
+pub fn primitive_state(key: &PipelineKey, topology: PrimitiveTopology) -> PrimitiveState {
+    let cull_mode = match CullMode::from_u8(key.cull_mode) {
+        CullMode::Front => Some(Face::Front),
+        CullMode::Back => Some(Face::Back),
+        CullMode::None | CullMode::All => None,
+    };
+    PrimitiveState {
+        topology,
+        strip_index_format: None,
+        front_face: if key.flip_y {
+            FrontFace::Ccw
+        } else {
+            FrontFace::Cw
+        },
+        cull_mode,
+        unclipped_depth: false,
+        polygon_mode: PolygonMode::Fill,
+        conservative: false,
+    }
+}
+
+/// Depth test and write for a GX Z mode (`GXSetZMode`). With the test
+/// disabled GX neither compares nor updates Z, whatever the update flag says.
+pub fn depth_stencil_state(z_mode: &ZMode) -> DepthStencilState {
+    use super::state::CompareFunction as Gx;
+    let (depth_compare, depth_write_enabled) = if z_mode.enable {
+        let compare = match z_mode.function {
+            Gx::Never => CompareFunction::Never,
+            Gx::Less => CompareFunction::Less,
+            Gx::Equal => CompareFunction::Equal,
+            Gx::LessEqual => CompareFunction::LessEqual,
+            Gx::Greater => CompareFunction::Greater,
+            Gx::NotEqual => CompareFunction::NotEqual,
+            Gx::GreaterEqual => CompareFunction::GreaterEqual,
+            Gx::Always => CompareFunction::Always,
+        };
+        (compare, z_mode.update)
+    } else {
+        (CompareFunction::Always, false)
+    };
+    DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled,
+        depth_compare,
+        stencil: StencilState::default(),
+        bias: DepthBiasState::default(),
+    }
+}
+
+/// Clear value for the depth attachment from the 24-bit GX clear Z
+/// (`GXSetCopyClear`).
+pub fn clear_depth(copy_clear_z: u32) -> f32 {
+    (copy_clear_z & MAX_DEPTH_24) as f32 / MAX_DEPTH_24 as f32
+}
+
+/// wgpu sampler settings for a GX texture map. Non-mipmapped GX filters sample
+/// only the base level. wgpu only allows anisotropy with all-linear filtering,
+/// so an anisotropy above 1 upgrades the filters to linear.
+pub fn sampler_descriptor(
+    sampler: &TexSampler,
+    anisotropy_override: Option<u16>,
+) -> SamplerDescriptor<'static> {
+    let address = |wrap: TexWrap| match wrap {
+        TexWrap::Clamp => AddressMode::ClampToEdge,
+        TexWrap::Repeat => AddressMode::Repeat,
+        TexWrap::Mirror => AddressMode::MirrorRepeat,
+    };
+    let filter = |linear: bool| {
+        if linear {
+            FilterMode::Linear
+        } else {
+            FilterMode::Nearest
+        }
+    };
+    let anisotropy = anisotropy_override
+        .unwrap_or(sampler.max_aniso.samples())
+        .clamp(1, 16);
+    let min: TexFilter = sampler.min_filter;
+    let all_linear = anisotropy > 1;
+
+    SamplerDescriptor {
+        label: Some("GX sampler"),
+        address_mode_u: address(sampler.wrap_s),
+        address_mode_v: address(sampler.wrap_t),
+        address_mode_w: AddressMode::ClampToEdge,
+        mag_filter: filter(all_linear || sampler.mag_filter.is_linear()),
+        min_filter: filter(all_linear || min.is_linear()),
+        mipmap_filter: filter(all_linear || min.mip_linear()),
+        lod_min_clamp: 0.0,
+        lod_max_clamp: if min.uses_mipmaps() { 32.0 } else { 0.0 },
+        compare: None,
+        anisotropy_clamp: anisotropy,
+        border_color: None,
+    }
+}
+
+/// Upload a mip chain (`chain[0]` is the base level, each next level half the
+/// size) as one RGBA8 texture.
+pub fn upload_mip_chain(device: &Device, queue: &Queue, chain: &[RgbaImage]) -> Option<Texture> {
+    let base = chain.first()?;
+    let texture = device.create_texture(&TextureDescriptor {
+        label: Some("GX texture"),
+        size: Extent3d {
+            width: base.width(),
+            height: base.height(),
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: chain.len() as u32,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: TextureFormat::Rgba8Unorm,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+    for (level, image) in chain.iter().enumerate() {
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: level as u32,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            image.as_raw(),
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * image.width()),
+                rows_per_image: Some(image.height()),
+            },
+            Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    Some(texture)
+}
+
 fn u32_to_blend_factor(f: u32) -> BlendFactor {
     match f {
         0 => BlendFactor::Zero,
@@ -259,3 +444,104 @@ fn u32_to_blend_factor(f: u32) -> BlendFactor {
         _ => BlendFactor::One,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gx::state::TexAniso;
+
+    #[test]
+    fn z_mode_maps_to_depth_stencil_state() {
+        let mut state = GxState::new();
+        state.set_z_mode(
+            true,
+            crate::graphics::gx::state::CompareFunction::LessEqual,
+            true,
+        );
+        let key = PipelineKey::from_state(&state, 3);
+        let ds = depth_stencil_state(&key.z_mode());
+        assert_eq!(ds.depth_compare, CompareFunction::LessEqual);
+        assert!(ds.depth_write_enabled);
+        assert_eq!(ds.format, DEPTH_FORMAT);
+
+        // Test off: always passes and never writes, even with update set.
+        state.set_z_mode(
+            false,
+            crate::graphics::gx::state::CompareFunction::Less,
+            true,
+        );
+        let ds = depth_stencil_state(&PipelineKey::from_state(&state, 3).z_mode());
+        assert_eq!(ds.depth_compare, CompareFunction::Always);
+        assert!(!ds.depth_write_enabled);
+
+        assert_eq!(clear_depth(MAX_DEPTH_24), 1.0);
+        assert_eq!(clear_depth(0), 0.0);
+    }
+
+    #[test]
+    fn cull_back_maps_to_clockwise_front_back_culling() {
+        let mut state = GxState::new();
+        state.set_cull_mode(CullMode::from_u8(2)); // GX_CULL_BACK
+        let key = PipelineKey::from_state(&state, 3);
+        let prim = primitive_state(&key, PrimitiveTopology::TriangleList);
+        assert_eq!(prim.cull_mode, Some(Face::Back));
+        assert_eq!(prim.front_face, FrontFace::Cw);
+        assert!(!key.culls_all());
+
+        // A Y-flipped viewport reverses the winding wgpu rasterizes.
+        state.set_viewport(0.0, 480.0, 640.0, -480.0, 0.0, 1.0);
+        let prim = primitive_state(
+            &PipelineKey::from_state(&state, 3),
+            PrimitiveTopology::TriangleList,
+        );
+        assert_eq!(prim.cull_mode, Some(Face::Back));
+        assert_eq!(prim.front_face, FrontFace::Ccw);
+
+        state.set_cull_mode(CullMode::from_u8(1)); // GX_CULL_FRONT
+        let key = PipelineKey::from_state(&state, 3);
+        assert_eq!(
+            primitive_state(&key, PrimitiveTopology::TriangleList).cull_mode,
+            Some(Face::Front)
+        );
+
+        state.set_cull_mode(CullMode::from_u8(3)); // GX_CULL_ALL
+        assert!(PipelineKey::from_state(&state, 3).culls_all());
+        assert!(!PipelineKey::from_state(&state, 1).culls_all());
+        state.set_cull_mode(CullMode::from_u8(0)); // GX_CULL_NONE
+        let key = PipelineKey::from_state(&state, 3);
+        assert_eq!(
+            primitive_state(&key, PrimitiveTopology::TriangleList).cull_mode,
+            None
+        );
+    }
+
+    #[test]
+    fn sampler_follows_gx_state_and_override() {
+        let gx = TexSampler {
+            wrap_s: TexWrap::Repeat,
+            wrap_t: TexWrap::Mirror,
+            min_filter: TexFilter::NearMipLin,
+            mag_filter: TexFilter::Near,
+            max_aniso: TexAniso::One,
+        };
+        let desc = sampler_descriptor(&gx, None);
+        assert_eq!(desc.address_mode_u, AddressMode::Repeat);
+        assert_eq!(desc.address_mode_v, AddressMode::MirrorRepeat);
+        assert_eq!(desc.mag_filter, FilterMode::Nearest);
+        assert_eq!(desc.min_filter, FilterMode::Nearest);
+        assert_eq!(desc.mipmap_filter, FilterMode::Linear);
+        assert_eq!(desc.anisotropy_clamp, 1);
+
+        // Without mip filtering only the base level is sampled
+        let base_only = TexSampler {
+            min_filter: TexFilter::Linear,
+            ..gx
+        };
+        assert_eq!(sampler_descriptor(&base_only, None).lod_max_clamp, 0.0);
+
+        let hq = sampler_descriptor(&gx, Some(8));
+        assert_eq!(hq.anisotropy_clamp, 8);
+        assert_eq!(hq.min_filter, FilterMode::Linear);
+        assert_eq!(hq.mag_filter, FilterMode::Linear);
+    }
+}
@@ -139,6 +139,9 @@ pub struct TevStage {
     pub tex_map: u8,
     /// Color channel feeding this stage (GX_COLOR0A0, GX_COLOR1A1, ...).
     pub channel: u8,
+
+    /// Indirect texture coordinate modification (`GXSetTevIndirect`).
+    pub indirect: TevIndirect,
 }
 
 impl Default for TevStage {
@@ -164,10 +167,71 @@ impl Default for TevStage {
             tex_coord: 0xFF,
             tex_map: 0xFF,
             channel: 0xFF,
+            indirect: TevIndirect::default(),
         }
     }
 }
 
+// ---------------------------------------------------------------------------
+// Indirect texturing
+// ---------------------------------------------------------------------------
+
+/// How a TEV stage offsets its texture coordinate by an indirect texture
+/// lookup (`GXSetTevIndirect`). Fields hold the `GX_IT*` values, which are
+/// also the hardware encodings. The default is a direct stage.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TevIndirect {
+    /// Indirect stage supplying the offsets (GX_INDTEXSTAGE0..3).
+    pub ind_stage: u8,
+    /// Offset precision (GX_ITF_8, _5, _4, _3).
+    pub format: u8,
+    /// Components that get the signed bias (GX_ITB_S = 1, _T = 2, _U = 4).
+    pub bias: u8,
+    /// GX_ITM_OFF (0), static matrices GX_ITM_0..2 (1..3), dynamic
+    /// GX_ITM_S0..2 (5..7) and GX_ITM_T0..2 (9..11).
+    pub matrix: u8,
+    /// Wrap of the regular coordinate (GX_ITW_OFF, _256, _128, _64, _32,
+    /// _16, _0).
+    pub wrap_s: u8,
+    pub wrap_t: u8,
+    /// Add the previous stage's computed coordinate (`addPrev`).
+    pub add_prev: bool,
+}
+
+impl TevIndirect {
+    /// True when the stage samples with its plain texture coordinate.
+    pub fn is_direct(&self) -> bool {
+        self.matrix == 0 && self.wrap_s == 0 && self.wrap_t == 0 && !self.add_prev
+    }
+}
+
+/// One of the four indirect texture lookups (`GXSetIndTexOrder`,
+/// `GXSetIndTexCoordScale`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IndTexStage {
+    pub tex_coord: u8,
+    pub tex_map: u8,
+    /// Coordinate divisors as powers of two (GX_ITS_1 = 0 .. GX_ITS_256 = 8).
+    pub scale_s: u8,
+    pub scale_t: u8,
+}
+
+/// An indirect offset matrix (`GXSetIndTexMtx`): 2x3, scaled by
+/// `2^scale_exp`. Results are texel offsets.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct IndTexMatrix {
+    pub offset: [[f32; 3]; 2],
+    pub scale_exp: i8,
+}
+
+impl IndTexMatrix {
+    /// The S and T rows with the scale applied, as uploaded to the shader.
+    pub fn scaled_rows(&self) -> [[f32; 3]; 2] {
+        let scale = 2f32.powi(self.scale_exp as i32);
+        self.offset.map(|row| row.map(|v| v * scale))
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Blend, depth, and rasterizer state
 // ---------------------------------------------------------------------------
@@ -245,6 +309,22 @@ pub enum CompareFunction {
     Always = 7,
 }
 
+impl CompareFunction {
+    /// Decode a 3-bit GX compare function.
+    pub fn from_u8(v: u8) -> Self {
+        match v & 7 {
+            0 => CompareFunction::Never,
+            1 => CompareFunction::Less,
+            2 => CompareFunction::Equal,
+            3 => CompareFunction::LessEqual,
+            4 => CompareFunction::Greater,
+            5 => CompareFunction::NotEqual,
+            6 => CompareFunction::GreaterEqual,
+            _ => CompareFunction::Always,
+        }
+    }
+}
+
 /// Z-buffer (depth) mode state.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ZMode {
@@ -318,6 +398,196 @@ pub enum CullMode {
     All = 3,
 }
 
+impl CullMode {
+    /// Decode a `GX_CULL_*` value as passed to `GXSetCullMode`.
+    pub fn from_u8(v: u8) -> Self {
+        match v & 3 {
+            0 => CullMode::None,
+            1 => CullMode::Front,
+            2 => CullMode::Back,
+            _ => CullMode::All,
+        }
+    }
+}
+
+/// Fog curve (`GXFogType`). Orthographic variants use the same curves and
+/// are folded onto the perspective ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum FogType {
+    #[default]
+    None = 0,
+    Linear = 2,
+    Exp = 4,
+    Exp2 = 5,
+    RevExp = 6,
+    RevExp2 = 7,
+}
+
+impl FogType {
+    /// Decode a `GX_FOG_*` value (perspective 0x00-0x07, orthographic 0x08-0x0F).
+    pub fn from_u8(v: u8) -> Self {
+        match v & 7 {
+            2 => FogType::Linear,
+            4 => FogType::Exp,
+            5 => FogType::Exp2,
+            6 => FogType::RevExp,
+            7 => FogType::RevExp2,
+            _ => FogType::None,
+        }
+    }
+}
+
+/// Fog state (`GXSetFog`). `start`/`end` are eye-space distances where the
+/// fog ramp begins and saturates; `near`/`far` are the projection's planes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fog {
+    pub fog_type: FogType,
+    pub start: f32,
+    pub end: f32,
+    pub near: f32,
+    pub far: f32,
+    pub color: [f32; 4],
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            fog_type: FogType::None,
+            start: 0.0,
+            end: 1.0,
+            near: 0.1,
+            far: 1.0,
+            color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+impl Fog {
+    pub fn is_enabled(&self) -> bool {
+        self.fog_type != FogType::None
+    }
+
+    /// Fog density at eye-space distance `eye_z` (0 = clear, 1 = fog color).
+    /// The generated WGSL (`tev::generate_fog_wgsl`) computes the same.
+    pub fn factor(&self, eye_z: f32) -> f32 {
+        let range = self.end - self.start;
+        let t = if range.abs() > f32::EPSILON {
+            ((eye_z - self.start) / range).clamp(0.0, 1.0)
+        } else if eye_z >= self.end {
+            1.0
+        } else {
+            0.0
+        };
+        match self.fog_type {
+            FogType::None => 0.0,
+            FogType::Linear => t,
+            FogType::Exp => 1.0 - (-8.0 * t).exp2(),
+            FogType::Exp2 => 1.0 - (-8.0 * t * t).exp2(),
+            FogType::RevExp => (-8.0 * (1.0 - t)).exp2(),
+            FogType::RevExp2 => (-8.0 * (1.0 - t) * (1.0 - t)).exp2(),
+        }
+    }
+
+    /// Blend `color`'s RGB toward the fog color; alpha is untouched.
+    pub fn apply(&self, color: [f32; 4], eye_z: f32) -> [f32; 4] {
+        let f = self.factor(eye_z);
+        let mix = |c: f32, fog: f32| c + (fog - c) * f;
+        [
+            mix(color[0], self.color[0]),
+            mix(color[1], self.color[1]),
+            mix(color[2], self.color[2]),
+            color[3],
+        ]
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Texture sampler state
+// ---------------------------------------------------------------------------
+
+/// Texture coordinate wrap mode (GX_CLAMP / GX_REPEAT / GX_MIRROR).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum TexWrap {
+    #[default]
+    Clamp = 0,
+    Repeat = 1,
+    Mirror = 2,
+}
+
+/// Texture filter (GX_NEAR .. GX_LIN_MIP_LIN). The `Mip` variants select how
+/// mip levels are blended and only apply to the minification filter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum TexFilter {
+    Near = 0,
+    #[default]
+    Linear = 1,
+    NearMipNear = 2,
+    LinMipNear = 3,
+    NearMipLin = 4,
+    LinMipLin = 5,
+}
+
+impl TexFilter {
+    /// Whether texels within a level are blended.
+    pub fn is_linear(self) -> bool {
+        matches!(
+            self,
+            TexFilter::Linear | TexFilter::LinMipNear | TexFilter::LinMipLin
+        )
+    }
+
+    /// Whether mip levels are blended (false also for non-mipmapped filters).
+    pub fn mip_linear(self) -> bool {
+        matches!(self, TexFilter::NearMipLin | TexFilter::LinMipLin)
+    }
+
+    /// Whether the filter samples mip levels at all.
+    pub fn uses_mipmaps(self) -> bool {
+        !matches!(self, TexFilter::Near | TexFilter::Linear)
+    }
+}
+
+/// Maximum anisotropy (GX_ANISO_1 / GX_ANISO_2 / GX_ANISO_4).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum TexAniso {
+    #[default]
+    One = 0,
+    Two = 1,
+    Four = 2,
+}
+
+impl TexAniso {
+    pub fn samples(self) -> u16 {
+        1 << (self as u16)
+    }
+}
+
+/// Image bound to a texture map (`GXLoadTexObj`, BP `TX_SETIMAGE0/3`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TexImage {
+    /// Physical address of the texel data.
+    pub address: u32,
+    pub width: u16,
+    pub height: u16,
+    /// GX_TF_* format.
+    pub format: u8,
+}
+
+/// Sampling parameters of one texture map, as set by `GXInitTexObj` /
+/// `GXInitTexObjLOD`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TexSampler {
+    pub wrap_s: TexWrap,
+    pub wrap_t: TexWrap,
+    pub min_filter: TexFilter,
+    pub mag_filter: TexFilter,
+    pub max_aniso: TexAniso,
+}
+
 // ---------------------------------------------------------------------------
 // Matrix state
 // ---------------------------------------------------------------------------
@@ -380,6 +650,11 @@ pub struct GxState {
     /// layout (component count, type, fractional bits).
     pub vertex_formats: [[VtxAttrFmt; VtxAttr::COUNT]; 8],
 
+    /// Indexed-attribute arrays (`GXSetArray`): base address and byte stride
+    /// for position, normal, color 0/1 and texcoord 0..7, in that order.
+    pub array_bases: [u32; 12],
+    pub array_strides: [u32; 12],
+
     // -- TEV pipeline ----------------------------------------------------
     /// The 16 TEV combiner stages.
     pub tev_stages: [TevStage; 16],
@@ -393,6 +668,13 @@ pub struct GxState {
     /// Four TEV constant-color registers (RGBA).
     pub tev_konst_colors: [[f32; 4]; 4],
 
+    /// Indirect texture lookups and how many of them are active (0..=4).
+    pub ind_stages: [IndTexStage; 4],
+    pub num_ind_stages: u8,
+
+    /// The three indirect offset matrices.
+    pub ind_matrices: [IndTexMatrix; 3],
+
     // -- Transform -------------------------------------------------------
     /// Projection, position, and texture matrices.
     pub matrices: GxMatrices,
@@ -413,6 +695,9 @@ pub struct GxState {
     /// Triangle face-culling mode.
     pub cull_mode: CullMode,
 
+    /// Distance fog applied after the last TEV stage.
+    pub fog: Fog,
+
     // -- Lighting / channels ---------------------------------------------
     /// Two material channel diffuse colors (RGBA).
     pub material_colors: [[f32; 4]; 2],
@@ -426,6 +711,14 @@ pub struct GxState {
     /// Number of active texture-coordinate generators (0..=8).
     pub num_tex_gens: u8,
 
+    // -- Textures --------------------------------------------------------
+    /// Sampler state of the eight texture maps (GX_TEXMAP0..GX_TEXMAP7).
+    pub tex_samplers: [TexSampler; 8],
+
+    /// Images loaded into the eight texture maps; `None` until the game
+    /// loads one.
+    pub tex_images: [Option<TexImage>; 8],
+
     // -- Copy / clear ----------------------------------------------------
     /// Clear color used by EFB-to-XFB copy (RGBA).
     pub copy_clear_color: [f32; 4],
@@ -475,11 +768,16 @@ impl GxState {
         Self {
             vertex_descriptors: default_vertex_descriptors(),
             vertex_formats: [[VtxAttrFmt::default(); VtxAttr::COUNT]; 8],
+            array_bases: [0; 12],
+            array_strides: [0; 12],
 
             tev_stages: [TevStage::default(); 16],
             num_tev_stages: 1,
             tev_colors: [[0.0; 4]; 4],
             tev_konst_colors: [[1.0; 4]; 4],
+            ind_stages: [IndTexStage::default(); 4],
+            num_ind_stages: 0,
+            ind_matrices: [IndTexMatrix::default(); 3],
 
             matrices: GxMatrices::default(),
 
@@ -488,12 +786,16 @@ impl GxState {
             scissor: Scissor::default(),
             viewport: Viewport::default(),
             cull_mode: CullMode::default(),
+            fog: Fog::default(),
 
             material_colors: [[1.0, 1.0, 1.0, 1.0]; 2],
             ambient_colors: [[0.0, 0.0, 0.0, 1.0]; 2],
             num_channels: 1,
             num_tex_gens: 0,
 
+            tex_samplers: [TexSampler::default(); 8],
+            tex_images: [None; 8],
+
             copy_clear_color: [0.0, 0.0, 0.0, 1.0],
             copy_clear_z: 0x00FF_FFFF, // max 24-bit depth
 
@@ -507,6 +809,222 @@ impl GxState {
         *self = Self::new();
     }
 
+    /// Check that the state is consistent enough to draw with: the vertex
+    /// descriptor, the active TEV stages and what they read, the textures
+    /// they sample and the current matrix. Each problem is one message
+    /// naming the offending register and the GX call that usually fixes it.
+    /// wgpu would otherwise reject such a draw with an opaque error, or the
+    /// shader would read a binding that does not exist.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        // -- Vertex descriptor -------------------------------------------
+        let input = |attr: VtxAttr| self.vertex_descriptors[attr as usize].input_type;
+        if input(VtxAttr::Position) == VtxInputType::None {
+            problems.push("vertex descriptor has no position (GXSetVtxDesc(GX_VA_POS))".into());
+        }
+        for desc in &self.vertex_descriptors {
+            let indexed = matches!(
+                desc.input_type,
+                VtxInputType::Index8 | VtxInputType::Index16
+            );
+            if !indexed {
+                continue;
+            }
+            if (desc.attr as u8) < VtxAttr::Position as u8 {
+                problems.push(format!(
+                    "{:?} is indexed, but matrix indices can only be direct",
+                    desc.attr
+                ));
+            } else if self.array_bases[super::command::array_index(desc.attr)] == 0 {
+                problems.push(format!(
+                    "{:?} is indexed but has no array (GXSetArray)",
+                    desc.attr
+                ));
+            }
+        }
+
+        // -- Counts -------------------------------------------------------
+        if !(1..=16).contains(&self.num_tev_stages) {
+            problems.push(format!(
+                "{} TEV stages active (GXSetNumTevStages takes 1-16)",
+                self.num_tev_stages
+            ));
+        }
+        if self.num_tex_gens > 8 {
+            problems.push(format!(
+                "{} texgens active (GXSetNumTexGens takes 0-8)",
+                self.num_tex_gens
+            ));
+        }
+        if self.num_channels > 2 {
+            problems.push(format!(
+                "{} color channels active (GXSetNumChans takes 0-2)",
+                self.num_channels
+            ));
+        }
+
+        // -- TEV stages and the textures they sample ----------------------
+        let stages = (self.num_tev_stages as usize).min(16);
+        for (n, stage) in self.tev_stages[..stages].iter().enumerate() {
+            let color_in = [
+                stage.color_in_a,
+                stage.color_in_b,
+                stage.color_in_c,
+                stage.color_in_d,
+            ];
+            let alpha_in = [
+                stage.alpha_in_a,
+                stage.alpha_in_b,
+                stage.alpha_in_c,
+                stage.alpha_in_d,
+            ];
+            if let Some(bad) = color_in.iter().find(|&&i| i > 0x0F) {
+                problems.push(format!("TEV stage {n}: undefined color input {bad}"));
+            }
+            if let Some(bad) = alpha_in.iter().find(|&&i| i > 0x07) {
+                problems.push(format!("TEV stage {n}: undefined alpha input {bad}"));
+            }
+            if stage.color_dest > 3 || stage.alpha_dest > 3 {
+                problems.push(format!(
+                    "TEV stage {n}: output register {} is not GX_TEVPREV..GX_TEVREG2",
+                    stage.color_dest.max(stage.alpha_dest)
+                ));
+            }
+
+            // GX_CC_TEXC/TEXA, GX_CA_TEXA; GX_CC_RASC/RASA, GX_CA_RASA.
+            let uses_texture = color_in.iter().any(|&i| i == 0x08 || i == 0x09)
+                || alpha_in.contains(&0x04)
+                || !stage.indirect.is_direct();
+            let uses_raster =
+                color_in.iter().any(|&i| i == 0x0A || i == 0x0B) || alpha_in.contains(&0x05);
+
+            if uses_texture {
+                if stage.tex_map > 7 {
+                    problems.push(format!(
+                        "TEV stage {n} reads the texture but has no texture map (GXSetTevOrder)"
+                    ));
+                } else if self.tex_images[stage.tex_map as usize].is_none() {
+                    problems.push(format!(
+                        "TEV stage {n} samples GX_TEXMAP{}, which has no texture loaded (GXLoadTexObj)",
+                        stage.tex_map
+                    ));
+                }
+                if stage.tex_coord >= self.num_tex_gens {
+                    problems.push(format!(
+                        "TEV stage {n} uses texcoord {} but only {} texgens are active (GXSetNumTexGens)",
+                        stage.tex_coord, self.num_tex_gens
+                    ));
+                }
+            }
+            if uses_raster && (stage.channel == 0xFF || self.num_channels == 0) {
+                problems.push(format!(
+                    "TEV stage {n} reads the rasterized color but no color channel feeds it (GXSetTevOrder/GXSetNumChans)"
+                ));
+            }
+            if !stage.indirect.is_direct() && stage.indirect.ind_stage >= self.num_ind_stages {
+                problems.push(format!(
+                    "TEV stage {n} uses indirect stage {} but only {} are active (GXSetNumIndStages)",
+                    stage.indirect.ind_stage, self.num_ind_stages
+                ));
+            }
+        }
+        for (k, ind) in self.ind_stages[..self.num_ind_stages.min(4) as usize]
+            .iter()
+            .enumerate()
+        {
+            if ind.tex_map > 7 || self.tex_images[ind.tex_map as usize].is_none() {
+                problems.push(format!(
+                    "indirect stage {k} samples GX_TEXMAP{}, which has no texture loaded (GXLoadTexObj)",
+                    ind.tex_map
+                ));
+            }
+        }
+
+        // -- Matrices -----------------------------------------------------
+        let current = self.matrices.current_position_mtx as usize;
+        if current >= self.matrices.position.len() {
+            problems.push(format!(
+                "current position matrix {current} is out of range (GXSetCurrentMtx takes GX_PNMTX0-9)"
+            ));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    /// A short, human-readable summary for crash reports: the vertex inputs,
+    /// active stage counts, bound textures, raster state and whatever
+    /// `validate` objects to.
+    pub fn summary(&self) -> String {
+        let inputs: Vec<String> = self
+            .vertex_descriptors
+            .iter()
+            .filter(|d| d.input_type != VtxInputType::None)
+            .map(|d| format!("{:?}={:?}", d.attr, d.input_type))
+            .collect();
+        let textures: Vec<String> = self
+            .tex_images
+            .iter()
+            .enumerate()
+            .filter_map(|(map, image)| {
+                image.map(|t| {
+                    format!(
+                        "GX_TEXMAP{map}: 0x{:08X} {}x{} fmt 0x{:X}",
+                        t.address, t.width, t.height, t.format
+                    )
+                })
+            })
+            .collect();
+        let v = &self.viewport;
+        let mut out = format!(
+            "vertex inputs: {}\n\
+             tev stages: {}, indirect stages: {}, tex gens: {}, channels: {}\n\
+             textures: {}\n\
+             current matrix: {}\n\
+             viewport: {}x{} at ({}, {}), depth {}..{}\n\
+             cull: {:?}\n\
+             blend: {:?}\n\
+             z: {:?}\n",
+            if inputs.is_empty() {
+                "none".to_string()
+            } else {
+                inputs.join(" ")
+            },
+            self.num_tev_stages,
+            self.num_ind_stages,
+            self.num_tex_gens,
+            self.num_channels,
+            if textures.is_empty() {
+                "none".to_string()
+            } else {
+                textures.join(", ")
+            },
+            self.matrices.current_position_mtx,
+            v.width,
+            v.height,
+            v.x,
+            v.y,
+            v.near,
+            v.far,
+            self.cull_mode,
+            self.blend_mode,
+            self.z_mode,
+        );
+        match self.validate() {
+            Ok(()) => out.push_str("validation: ok\n"),
+            Err(problems) => {
+                for problem in problems {
+                    out.push_str(&format!("problem: {problem}\n"));
+                }
+            }
+        }
+        out
+    }
+
     // -- Vertex descriptor helpers ---------------------------------------
 
     /// Set the input type for a single vertex attribute.
@@ -538,6 +1056,13 @@ impl GxState {
         };
     }
 
+    /// Set the array indexed vertex data is fetched from (`GXSetArray`).
+    /// `array` is 0 = position, 1 = normal, 2/3 = color 0/1, 4..=11 = texcoord 0..7.
+    pub fn set_array(&mut self, array: u8, base: u32, stride: u32) {
+        self.array_bases[array as usize] = base;
+        self.array_strides[array as usize] = stride;
+    }
+
     // -- TEV helpers -----------------------------------------------------
 
     /// Configure the color combiner inputs for a TEV stage.
@@ -584,6 +1109,37 @@ impl GxState {
         s.channel = channel;
     }
 
+    /// Configure indirect texturing for a TEV stage (`GXSetTevIndirect`);
+    /// `TevIndirect::default()` makes it direct again (`GXSetTevDirect`).
+    pub fn set_tev_indirect(&mut self, stage: u8, indirect: TevIndirect) {
+        self.tev_stages[stage as usize].indirect = indirect;
+    }
+
+    /// Set the number of active indirect lookups (`GXSetNumIndStages`).
+    pub fn set_num_ind_stages(&mut self, count: u8) {
+        self.num_ind_stages = count.min(4);
+    }
+
+    /// Bind a texture coordinate and map to an indirect stage
+    /// (`GXSetIndTexOrder`).
+    pub fn set_ind_tex_order(&mut self, stage: u8, tex_coord: u8, tex_map: u8) {
+        let s = &mut self.ind_stages[stage as usize];
+        s.tex_coord = tex_coord;
+        s.tex_map = tex_map;
+    }
+
+    /// Set an indirect stage's coordinate divisors (`GXSetIndTexCoordScale`).
+    pub fn set_ind_tex_coord_scale(&mut self, stage: u8, scale_s: u8, scale_t: u8) {
+        let s = &mut self.ind_stages[stage as usize];
+        s.scale_s = scale_s;
+        s.scale_t = scale_t;
+    }
+
+    /// Load an indirect offset matrix (`GXSetIndTexMtx`, GX_ITM_0..2).
+    pub fn set_ind_tex_matrix(&mut self, index: u8, offset: [[f32; 3]; 2], scale_exp: i8) {
+        self.ind_matrices[index as usize] = IndTexMatrix { offset, scale_exp };
+    }
+
     /// Set a TEV color register (0=CPREV, 1=C0, 2=C1, 3=C2).
     pub fn set_tev_color(&mut self, reg: u8, r: f32, g: f32, b: f32, a: f32) {
         self.tev_colors[reg as usize] = [r, g, b, a];
@@ -594,6 +1150,16 @@ impl GxState {
         self.tev_konst_colors[reg as usize] = [r, g, b, a];
     }
 
+    /// Bind (or with `None` unbind) the image of a texture map (0..=7).
+    pub fn set_tex_image(&mut self, map: u8, image: Option<TexImage>) {
+        self.tex_images[map as usize] = image;
+    }
+
+    /// Set the sampling parameters of a texture map (0..=7).
+    pub fn set_tex_sampler(&mut self, map: u8, sampler: TexSampler) {
+        self.tex_samplers[map as usize] = sampler;
+    }
+
     // -- Matrix helpers --------------------------------------------------
 
     /// Load a 4x4 projection matrix (column-major).
@@ -670,6 +1236,26 @@ impl GxState {
         self.cull_mode = mode;
     }
 
+    /// Set distance fog (`GXSetFog`). `FogType::None` disables it.
+    pub fn set_fog(
+        &mut self,
+        fog_type: FogType,
+        start: f32,
+        end: f32,
+        near: f32,
+        far: f32,
+        color: [f32; 4],
+    ) {
+        self.fog = Fog {
+            fog_type,
+            start,
+            end,
+            near,
+            far,
+            color,
+        };
+    }
+
     // -- Channel / lighting helpers --------------------------------------
 
     /// Set a material channel color (index 0 or 1).
@@ -801,6 +1387,74 @@ mod tests {
         assert_eq!(state.matrices.projection[10], -1.0);
     }
 
+    /// A textured quad: position, one texgen, TEV stage 0 modulating the
+    /// texture by the rasterized color.
+    fn textured_draw_state() -> GxState {
+        let mut state = GxState::new();
+        state.vertex_descriptors[VtxAttr::Position as usize].input_type = VtxInputType::Direct;
+        state.num_tex_gens = 1;
+        state.set_tev_color_in(0, 0x0F, 0x08, 0x0A, 0x0F); // ZERO, TEXC, RASC, ZERO
+        state.set_tev_order(0, 0, 0, 4); // GX_TEXCOORD0, GX_TEXMAP0, GX_COLOR0A0
+        state
+    }
+
+    #[test]
+    fn validate_flags_unbound_texture_stage() {
+        let mut state = textured_draw_state();
+        let problems = state.validate().unwrap_err();
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(problems[0].contains("GX_TEXMAP0"), "{problems:?}");
+        assert!(problems[0].contains("GXLoadTexObj"), "{problems:?}");
+
+        state.set_tex_image(
+            0,
+            Some(TexImage {
+                address: 0x0040_0000,
+                width: 64,
+                height: 64,
+                format: 0x6, // GX_TF_RGBA8
+            }),
+        );
+        assert_eq!(state.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_flags_out_of_range_current_matrix() {
+        let mut state = textured_draw_state();
+        state.set_tex_image(0, Some(TexImage::default()));
+        state.set_current_position_matrix(12);
+        let problems = state.validate().unwrap_err();
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert!(
+            problems[0].contains("current position matrix 12"),
+            "{problems:?}"
+        );
+    }
+
+    #[test]
+    fn summary_lists_textures_and_problems() {
+        let mut state = textured_draw_state();
+        let summary = state.summary();
+        assert!(summary.contains("Position=Direct"), "{summary}");
+        assert!(summary.contains("problem:"), "{summary}");
+
+        state.set_tex_image(
+            0,
+            Some(TexImage {
+                address: 0x0040_0000,
+                width: 64,
+                height: 32,
+                format: 0x6,
+            }),
+        );
+        let summary = state.summary();
+        assert!(
+            summary.contains("GX_TEXMAP0: 0x00400000 64x32"),
+            "{summary}"
+        );
+        assert!(summary.contains("validation: ok"), "{summary}");
+    }
+
     #[test]
     fn copy_clear_z_masked_to_24_bits() {
         let mut state = GxState::new();
@@ -5,7 +5,17 @@
 // computes: d + (1 - c) * a + c * b, with configurable bias, scale, and
 // clamping. This module stores per-stage configuration and generates
 // dynamic WGSL fragment shader code for the active TEV stages.
-
+//
+// Indirect texturing: `generate_indirect_wgsl` samples the indirect textures
+// (`ind_tex0..3`), and a stage whose `indirect` is set turns one of those
+// samples into a texel offset (format mask, bias, matrix), adds it to its
+// wrapped texture coordinate and samples its own texture there instead of
+// using `tex_color`. Only the static matrices GX_ITM_0..2 are implemented;
+// the dynamic GX_ITM_S*/T* matrices (offsets scaled by the texture
+// coordinate itself) produce no offset, and the bump alpha (`alpha_sel`) and
+// `utc_lod` bits are ignored.
+
+use super::state::{FogType, IndTexStage, TevIndirect};
 use std::fmt::Write;
 
 // ---------------------------------------------------------------------------
@@ -156,6 +166,9 @@ pub struct TevStageConfig {
     pub konst_color_sel: u8,
     /// Konst alpha selector (hardware register value).
     pub konst_alpha_sel: u8,
+
+    /// Indirect texture coordinate modification; direct by default.
+    pub indirect: TevIndirect,
 }
 
 impl Default for TevStageConfig {
@@ -193,6 +206,7 @@ impl Default for TevStageConfig {
             channel: 0,
             konst_color_sel: 0,
             konst_alpha_sel: 0,
+            indirect: TevIndirect::default(),
         }
     }
 }
@@ -285,7 +299,11 @@ fn reg_to_wgsl(reg: TevRegId) -> &'static str {
 /// iterates over the active stages, and writes the final color to
 /// `tev_prev`. The caller is responsible for embedding this into a
 /// complete shader that provides `tex_color`, `ras_color`, and
-/// `konst_color` bindings.
+/// `konst_color` bindings. Indirect stages additionally read
+/// `tex_coord{i}` (normalized texgen output), `tex_size{m}` (texture size
+/// in texels), `sample_tex{m}(uv)`, the `ind_mtx0..2: mat2x3<f32>` uniforms
+/// (`IndTexMatrix::scaled_rows` as columns) and the `ind_tex{k}` samples
+/// from `generate_indirect_wgsl`.
 ///
 /// # Arguments
 ///
@@ -305,6 +323,10 @@ pub fn generate_tev_wgsl(stages: &[TevStageConfig], num_stages: u8) -> String {
     writeln!(out, "    var tev_reg0: vec4<f32> = vec4<f32>(0.0);").unwrap();
     writeln!(out, "    var tev_reg1: vec4<f32> = vec4<f32>(0.0);").unwrap();
     writeln!(out, "    var tev_reg2: vec4<f32> = vec4<f32>(0.0);").unwrap();
+    if stages[..count].iter().any(|s| !s.indirect.is_direct()) {
+        // Coordinate of the last indirect stage, for `add_prev`.
+        writeln!(out, "    var ind_prev: vec2<f32> = vec2<f32>(0.0);").unwrap();
+    }
     writeln!(out).unwrap();
 
     for (i, stage) in stages[..count].iter().enumerate() {
@@ -314,17 +336,81 @@ pub fn generate_tev_wgsl(stages: &[TevStageConfig], num_stages: u8) -> String {
     out
 }
 
+/// Generates the WGSL for the active indirect texture lookups: `ind_tex{k}`
+/// is the indirect stage's map sampled at its texture coordinate divided by
+/// the `GXSetIndTexCoordScale` factors. Emit it before the TEV stages.
+pub fn generate_indirect_wgsl(ind_stages: &[IndTexStage], num_stages: u8) -> String {
+    let count = (num_stages as usize).min(ind_stages.len()).min(4);
+    let mut out = String::with_capacity(256);
+    for (k, stage) in ind_stages[..count].iter().enumerate() {
+        let sx = (1u32 << stage.scale_s.min(8)) as f32;
+        let sy = (1u32 << stage.scale_t.min(8)) as f32;
+        writeln!(out, "    // Indirect stage {k}").unwrap();
+        writeln!(
+            out,
+            "    let ind_tex{k} = sample_tex{}(tex_coord{} / vec2<f32>({sx:?}, {sy:?}));",
+            stage.tex_map, stage.tex_coord
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Generates the WGSL that applies fog to `tev_prev` after the last stage.
+///
+/// Expects `eye_depth` (positive eye-space distance of the fragment) and the
+/// fog uniforms `fog_start`, `fog_end` and `fog_color` to be in scope; the
+/// curve is baked in, so each fog type is its own pipeline variant. With fog
+/// disabled nothing is emitted and the uniforms need not be bound.
+pub fn generate_fog_wgsl(fog_type: FogType) -> String {
+    let curve = match fog_type {
+        FogType::None => return String::new(),
+        FogType::Linear => "fog_t",
+        FogType::Exp => "1.0 - exp2(-8.0 * fog_t)",
+        FogType::Exp2 => "1.0 - exp2(-8.0 * fog_t * fog_t)",
+        FogType::RevExp => "exp2(-8.0 * (1.0 - fog_t))",
+        FogType::RevExp2 => "exp2(-8.0 * (1.0 - fog_t) * (1.0 - fog_t))",
+    };
+    let mut out = String::with_capacity(512);
+    writeln!(out, "    // Fog ({fog_type:?})").unwrap();
+    writeln!(
+        out,
+        "    let fog_t = clamp((eye_depth - fog_start) / max(fog_end - fog_start, 1e-6), 0.0, 1.0);"
+    )
+    .unwrap();
+    writeln!(out, "    let fog_f = {curve};").unwrap();
+    writeln!(
+        out,
+        "    tev_prev = vec4<f32>(mix(tev_prev.rgb, fog_color.rgb, fog_f), tev_prev.a);"
+    )
+    .unwrap();
+    out
+}
+
 /// Appends the WGSL code for a single TEV stage to `out`.
 fn generate_stage_wgsl(out: &mut String, stage: &TevStageConfig, index: usize) {
     let n = index;
 
     writeln!(out, "    // TEV Stage {n}").unwrap();
 
+    // An indirect stage samples its own texture at the offset coordinate.
+    let indirect = !stage.indirect.is_direct();
+    if indirect {
+        generate_indirect_coord_wgsl(out, stage, n);
+    }
+    let tex = |arg: &str| {
+        if indirect {
+            arg.replace("tex_color", &format!("tex_color_{n}"))
+        } else {
+            arg.to_string()
+        }
+    };
+
     // Color inputs.
-    let ca = color_arg_to_wgsl(stage.color_in[0]);
-    let cb = color_arg_to_wgsl(stage.color_in[1]);
-    let cc = color_arg_to_wgsl(stage.color_in[2]);
-    let cd = color_arg_to_wgsl(stage.color_in[3]);
+    let ca = tex(color_arg_to_wgsl(stage.color_in[0]));
+    let cb = tex(color_arg_to_wgsl(stage.color_in[1]));
+    let cc = tex(color_arg_to_wgsl(stage.color_in[2]));
+    let cd = tex(color_arg_to_wgsl(stage.color_in[3]));
 
     writeln!(out, "    let ca_{n} = {ca};").unwrap();
     writeln!(out, "    let cb_{n} = {cb};").unwrap();
@@ -332,10 +418,10 @@ fn generate_stage_wgsl(out: &mut String, stage: &TevStageConfig, index: usize) {
     writeln!(out, "    let cd_{n} = {cd};").unwrap();
 
     // Alpha inputs.
-    let aa = alpha_arg_to_wgsl(stage.alpha_in[0]);
-    let ab = alpha_arg_to_wgsl(stage.alpha_in[1]);
-    let ac = alpha_arg_to_wgsl(stage.alpha_in[2]);
-    let ad = alpha_arg_to_wgsl(stage.alpha_in[3]);
+    let aa = tex(alpha_arg_to_wgsl(stage.alpha_in[0]));
+    let ab = tex(alpha_arg_to_wgsl(stage.alpha_in[1]));
+    let ac = tex(alpha_arg_to_wgsl(stage.alpha_in[2]));
+    let ad = tex(alpha_arg_to_wgsl(stage.alpha_in[3]));
 
     writeln!(out, "    let aa_{n} = {aa};").unwrap();
     writeln!(out, "    let ab_{n} = {ab};").unwrap();
@@ -397,6 +483,76 @@ fn generate_stage_wgsl(out: &mut String, stage: &TevStageConfig, index: usize) {
     writeln!(out).unwrap();
 }
 
+/// Appends the indirect coordinate computation for stage `n`, ending in
+/// `tex_color_{n}`. GX reads the offsets from the indirect texel's A, B and
+/// G channels (S, T, U), masks them to the offset format, biases them (-128
+/// for 8-bit, +1 otherwise) and transforms them to texels with the matrix.
+fn generate_indirect_coord_wgsl(out: &mut String, stage: &TevStageConfig, n: usize) {
+    let ind = &stage.indirect;
+    let k = ind.ind_stage & 3;
+    let (mask, bias) = match ind.format & 3 {
+        0 => (0xFF, -128.0),
+        1 => (0x1F, 1.0),
+        2 => (0x0F, 1.0),
+        _ => (0x07, 1.0),
+    };
+    let b = |bit: u8| if ind.bias & bit != 0 { bias } else { 0.0 };
+    let m = stage.tex_map;
+
+    writeln!(out, "    // Indirect coordinate (indirect stage {k})").unwrap();
+    writeln!(
+        out,
+        "    let ind_crd_{n} = vec3<f32>(vec3<u32>(round(ind_tex{k}.abg * 255.0)) & vec3<u32>({mask}u)) \
+         + vec3<f32>({:?}, {:?}, {:?});",
+        b(1),
+        b(2),
+        b(4)
+    )
+    .unwrap();
+    match ind.matrix {
+        1..=3 => writeln!(
+            out,
+            "    let ind_off_{n} = ind_crd_{n} * ind_mtx{};",
+            ind.matrix - 1
+        ),
+        0 => writeln!(out, "    let ind_off_{n} = vec2<f32>(0.0);"),
+        other => writeln!(
+            out,
+            "    let ind_off_{n} = vec2<f32>(0.0); // dynamic matrix {other} unsupported"
+        ),
+    }
+    .unwrap();
+
+    writeln!(
+        out,
+        "    let crd_{n} = tex_coord{} * tex_size{m};",
+        stage.tex_coord
+    )
+    .unwrap();
+    let wrap = |axis: &str, w: u8| match w {
+        1..=5 => {
+            let size = (256 >> (w - 1)) as f32;
+            format!("crd_{n}.{axis} - floor(crd_{n}.{axis} / {size:?}) * {size:?}")
+        }
+        6 => "0.0".to_string(),
+        _ => format!("crd_{n}.{axis}"),
+    };
+    let prev = if ind.add_prev { "ind_prev + " } else { "" };
+    writeln!(
+        out,
+        "    let tc_{n} = {prev}vec2<f32>({}, {}) + ind_off_{n};",
+        wrap("x", ind.wrap_s),
+        wrap("y", ind.wrap_t)
+    )
+    .unwrap();
+    writeln!(out, "    ind_prev = tc_{n};").unwrap();
+    writeln!(
+        out,
+        "    let tex_color_{n} = sample_tex{m}(tc_{n} / tex_size{m});"
+    )
+    .unwrap();
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -405,6 +561,30 @@ fn generate_stage_wgsl(out: &mut String, stage: &TevStageConfig, index: usize) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn linear_fog_reaches_color_at_far_plane() {
+        use crate::graphics::gx::state::Fog;
+
+        let fog = Fog {
+            fog_type: FogType::Linear,
+            start: 10.0,
+            end: 100.0,
+            near: 10.0,
+            far: 100.0,
+            color: [0.5, 0.6, 0.7, 1.0],
+        };
+        let lit = [1.0, 0.0, 0.25, 0.8];
+        assert_eq!(fog.apply(lit, fog.near), lit);
+        assert_eq!(fog.apply(lit, fog.far), [0.5, 0.6, 0.7, 0.8]);
+        assert!((fog.factor(55.0) - 0.5).abs() < 1e-6);
+
+        let wgsl = generate_fog_wgsl(FogType::Linear);
+        assert!(wgsl.contains("mix(tev_prev.rgb, fog_color.rgb, fog_f)"));
+        assert!(wgsl.contains("let fog_f = fog_t;"));
+        assert!(generate_fog_wgsl(FogType::None).is_empty());
+        assert!(generate_fog_wgsl(FogType::Exp2).contains("exp2(-8.0 * fog_t * fog_t)"));
+    }
+
     #[test]
     fn default_stage_is_passthrough() {
         let stage = TevStageConfig::default();
@@ -596,6 +776,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn indirect_scale_stage_offsets_the_direct_texcoord() {
+        // Heat haze: indirect stage 0 samples map 1 at half-scale texcoord 1,
+        // stage 0 offsets texcoord 0 by it through matrix 0 (biased S/T).
+        let ind_stages = [IndTexStage {
+            tex_coord: 1,
+            tex_map: 1,
+            scale_s: 1,
+            scale_t: 1,
+        }];
+        let lookup = generate_indirect_wgsl(&ind_stages, 1);
+        assert!(
+            lookup.contains("let ind_tex0 = sample_tex1(tex_coord1 / vec2<f32>(2.0, 2.0));"),
+            "{lookup}"
+        );
+
+        let mut stage = TevStageConfig {
+            indirect: TevIndirect {
+                bias: 3,
+                matrix: 1,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        stage.color_in[3] = TevColorArg::TexcRgb;
+        let wgsl = generate_tev_wgsl(&[stage], 1);
+        assert!(wgsl.contains("var ind_prev"), "{wgsl}");
+        assert!(
+            wgsl.contains("round(ind_tex0.abg * 255.0)) & vec3<u32>(255u))"),
+            "{wgsl}"
+        );
+        assert!(wgsl.contains("vec3<f32>(-128.0, -128.0, 0.0)"), "{wgsl}");
+        assert!(
+            wgsl.contains("let ind_off_0 = ind_crd_0 * ind_mtx0;"),
+            "{wgsl}"
+        );
+        assert!(
+            wgsl.contains("let crd_0 = tex_coord0 * tex_size0;"),
+            "{wgsl}"
+        );
+        assert!(
+            wgsl.contains("let tc_0 = vec2<f32>(crd_0.x, crd_0.y) + ind_off_0;"),
+            "{wgsl}"
+        );
+        assert!(
+            wgsl.contains("let tex_color_0 = sample_tex0(tc_0 / tex_size0);"),
+            "{wgsl}"
+        );
+        assert!(wgsl.contains("let cd_0 = tex_color_0.rgb;"), "{wgsl}");
+
+        // Direct stages keep the plain sample and emit no indirect code.
+        let direct = generate_tev_wgsl(&[TevStageConfig::default()], 1);
+        assert!(!direct.contains("ind_"), "{direct}");
+        assert!(generate_indirect_wgsl(&ind_stages, 0).is_empty());
+    }
+
     #[test]
     fn konst_color_appears_in_output() {
         let mut stage = TevStageConfig::default();
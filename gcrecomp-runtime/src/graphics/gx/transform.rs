@@ -90,9 +90,44 @@ pub fn load_tex_mtx_imm(matrices: &mut [[f32; 16]; 10], slot: u8, data: &[f32])
     m[15] = 1.0;
 }
 
+/// Remap a GX projection (column-major) to wgpu's depth range.
+///
+/// GX projections put the near plane at NDC z = -1 and the far plane at 0
+/// (the viewport then scales that onto the 24-bit Z range); wgpu clips to
+/// [0, 1]. Adding w to z (row 3 to row 2) shifts one onto the other, keeping
+/// near < far so Z modes compare the same way they do on hardware.
+pub fn to_wgpu_depth_range(projection: &[f32; 16]) -> [f32; 16] {
+    let mut m = *projection;
+    for col in 0..4 {
+        m[col * 4 + 2] += m[col * 4 + 3];
+    }
+    m
+}
+
 /// Create an identity 4x4 matrix.
 pub fn identity() -> [f32; 16] {
     [
         1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0,
     ]
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gx_depth_range_maps_to_zero_one() {
+        // GXSetProjection-style perspective, near 1, far 100.
+        let (n, f) = (1.0f32, 100.0f32);
+        let mut gx = [0.0; 16];
+        load_projection_mtx(
+            &mut gx,
+            &[1.0, 1.0, 0.0, 0.0, -n / (f - n), -(f * n) / (f - n)],
+            0,
+        );
+        let m = to_wgpu_depth_range(&gx);
+        let ndc_z = |eye_z: f32| (m[10] * eye_z + m[14]) / (m[11] * eye_z + m[15]);
+        assert!(ndc_z(-n).abs() < 1e-6);
+        assert!((ndc_z(-f) - 1.0).abs() < 1e-6);
+    }
+}
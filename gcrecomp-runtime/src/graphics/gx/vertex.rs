@@ -5,6 +5,7 @@
 // components (position, normal, color, texcoord) which are
 // accumulated into a flat f32 buffer for GPU upload.
 
+use super::state::{Scissor, Viewport};
 use log::warn;
 
 // ── GX primitive types ──────────────────────────────────────────
@@ -98,6 +99,10 @@ pub struct DrawCall {
     pub vertex_count: u16,
     /// Number of f32 values per vertex (stride).
     pub stride: u32,
+    /// Viewport in effect when the draw was issued (EFB coordinates).
+    pub viewport: Viewport,
+    /// Scissor rectangle in effect when the draw was issued.
+    pub scissor: Scissor,
 }
 
 // ── Vertex accumulator ──────────────────────────────────────────
@@ -243,6 +248,8 @@ impl VertexAccumulator {
             vertex_data: std::mem::take(&mut self.vertices),
             vertex_count: self.current_count,
             stride,
+            viewport: Viewport::default(),
+            scissor: Scissor::default(),
         })
     }
 
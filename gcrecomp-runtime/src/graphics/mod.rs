@@ -1,10 +1,17 @@
+pub mod capture;
 pub mod framebuffer;
 pub mod gx;
+pub mod overlay;
+pub mod present;
 pub mod renderer;
 pub mod shaders;
+pub mod splash;
 pub mod upscaler;
 
 pub use framebuffer::FrameBuffer;
 pub use gx::GXProcessor;
+pub use overlay::{Overlay, OverlayStats};
+pub use present::PresentModeSetting;
 pub use renderer::Renderer;
+pub use splash::{FadeCurve, SplashScreen};
 pub use upscaler::Upscaler;
@@ -0,0 +1,273 @@
+// Debug overlay: FPS, frame time, input state and PC as a translucent panel
+//
+// Text is rasterized on the CPU with a built-in 5x7 bitmap font into a small
+// RGBA panel, which the renderer alpha-blends over the game frame at present.
+
+/// Glyph cell size in panel pixels.
+pub const GLYPH_W: u32 = 5;
+pub const GLYPH_H: u32 = 7;
+/// Horizontal advance and line height, including spacing.
+pub const ADVANCE_X: u32 = GLYPH_W + 1;
+pub const LINE_HEIGHT: u32 = GLYPH_H + 2;
+/// Padding between the panel edge and the text.
+pub const PADDING: u32 = 4;
+
+/// Panel background (straight-alpha RGBA); text is drawn opaque white.
+const PANEL_BG: [u8; 4] = [0, 0, 0, 160];
+const TEXT_FG: [u8; 4] = [255, 255, 255, 255];
+
+/// 5x7 glyphs for ASCII 0x20..=0x5F, one byte per row, bit 4 = leftmost column.
+/// Lowercase letters are drawn with their uppercase glyphs.
+#[rustfmt::skip]
+const FONT: [[u8; 7]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // '!'
+    [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00], // '"'
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // '#'
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // '$'
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // '%'
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // '&'
+    [0x04, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00], // '''
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // '('
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // ')'
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // '*'
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // '+'
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ','
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // '-'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // '.'
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // '/'
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // '0'
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // '1'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // '2'
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // '3'
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // '4'
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // '5'
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // '6'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // '7'
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // '8'
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // '9'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // ':'
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ';'
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // '<'
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // '='
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // '>'
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // '?'
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // '@'
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // 'A'
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // 'B'
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // 'C'
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // 'D'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // 'E'
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // 'F'
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // 'G'
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // 'H'
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // 'I'
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // 'J'
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // 'K'
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // 'L'
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // 'M'
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // 'N'
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'O'
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // 'P'
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // 'Q'
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // 'R'
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // 'S'
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // 'T'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // 'U'
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // 'V'
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // 'W'
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // 'X'
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // 'Y'
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // 'Z'
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // '['
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // '\'
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ']'
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // '^'
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // '_'
+];
+
+/// Look up the glyph for `ch`; characters outside the font render as '?'.
+pub(crate) fn glyph(ch: char) -> &'static [u8; 7] {
+    let c = ch.to_ascii_uppercase() as u32;
+    if (0x20..0x60).contains(&c) {
+        &FONT[(c - 0x20) as usize]
+    } else {
+        &FONT[(b'?' - 0x20) as usize]
+    }
+}
+
+/// A positioned glyph, in pixels relative to the top-left of the text area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphPos {
+    pub ch: char,
+    pub x: u32,
+    pub y: u32,
+}
+
+/// Lay out `text` into lines no wider than `max_width` pixels. Breaks on `\n`
+/// and wraps at the character that would overflow; spaces that land at the
+/// start of a wrapped line are dropped.
+pub fn layout_text(text: &str, max_width: u32) -> Vec<GlyphPos> {
+    let cols = (max_width.saturating_sub(GLYPH_W) / ADVANCE_X + 1).max(1);
+    let mut out = Vec::with_capacity(text.len());
+    let (mut col, mut row) = (0u32, 0u32);
+
+    for ch in text.chars() {
+        if ch == '\n' {
+            col = 0;
+            row += 1;
+            continue;
+        }
+        if col >= cols {
+            col = 0;
+            row += 1;
+            if ch == ' ' {
+                continue;
+            }
+        }
+        if ch != ' ' {
+            out.push(GlyphPos {
+                ch,
+                x: col * ADVANCE_X,
+                y: row * LINE_HEIGHT,
+            });
+        }
+        col += 1;
+    }
+    out
+}
+
+/// Values shown on the overlay.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OverlayStats {
+    pub fps: f32,
+    pub frame_time_ms: f32,
+    /// Controller 0 buttons as a `PADStatus.button` bitmask.
+    pub pad_buttons: u16,
+    pub pc: u32,
+}
+
+impl OverlayStats {
+    pub fn to_text(self) -> String {
+        format!(
+            "FPS {:5.1}\nFRAME {:5.2} MS\nPAD {:04X}\nPC {:08X}",
+            self.fps, self.frame_time_ms, self.pad_buttons, self.pc
+        )
+    }
+}
+
+/// CPU-side overlay state; the renderer uploads `panel()` and blends it.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    visible: bool,
+    max_width: u32,
+    panel: Vec<u8>,
+    panel_size: (u32, u32),
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            max_width: 160,
+            panel: Vec::new(),
+            panel_size: (0, 0),
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn update(&mut self, stats: &OverlayStats) {
+        self.set_text(&stats.to_text());
+    }
+
+    /// Re-rasterize the panel for `text`.
+    pub fn set_text(&mut self, text: &str) {
+        let glyphs = layout_text(text, self.max_width);
+        let text_w = glyphs.iter().map(|g| g.x + GLYPH_W).max().unwrap_or(0);
+        let text_h = glyphs.iter().map(|g| g.y + GLYPH_H).max().unwrap_or(0);
+        let (w, h) = (text_w + PADDING * 2, text_h + PADDING * 2);
+
+        self.panel.clear();
+        self.panel.reserve((w * h * 4) as usize);
+        for _ in 0..w * h {
+            self.panel.extend_from_slice(&PANEL_BG);
+        }
+
+        for g in &glyphs {
+            for (row, bits) in glyph(g.ch).iter().enumerate() {
+                for col in 0..GLYPH_W {
+                    if bits & (0x10 >> col) != 0 {
+                        let px = PADDING + g.x + col;
+                        let py = PADDING + g.y + row as u32;
+                        let i = ((py * w + px) * 4) as usize;
+                        self.panel[i..i + 4].copy_from_slice(&TEXT_FG);
+                    }
+                }
+            }
+        }
+        self.panel_size = (w, h);
+    }
+
+    /// The rasterized RGBA8 panel and its size, if there is anything to draw.
+    pub fn panel(&self) -> Option<(&[u8], u32, u32)> {
+        if !self.visible || self.panel.is_empty() {
+            return None;
+        }
+        Some((&self.panel, self.panel_size.0, self.panel_size.1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layout_wraps_and_breaks_lines() {
+        // 20px fits 3 columns (0, 6, 12; last glyph ends at 17)
+        let glyphs = layout_text("FPS 60\nPC", 20);
+        let pos: Vec<(char, u32, u32)> = glyphs.iter().map(|g| (g.ch, g.x, g.y)).collect();
+        assert_eq!(
+            pos,
+            vec![
+                ('F', 0, 0),
+                ('P', 6, 0),
+                ('S', 12, 0),
+                // wrapped: leading space dropped
+                ('6', 0, 9),
+                ('0', 6, 9),
+                ('P', 0, 18),
+                ('C', 6, 18),
+            ]
+        );
+    }
+
+    #[test]
+    fn panel_has_background_and_lit_pixels() {
+        let mut overlay = Overlay::new();
+        overlay.set_visible(true);
+        overlay.set_text("I");
+        let (rgba, w, h) = overlay.panel().unwrap();
+        assert_eq!((w, h), (GLYPH_W + PADDING * 2, GLYPH_H + PADDING * 2));
+        assert_eq!(&rgba[0..4], &PANEL_BG);
+        // 'I' top row is 0x0E: columns 1..=3 lit
+        let i = ((PADDING * w + PADDING + 2) * 4) as usize;
+        assert_eq!(&rgba[i..i + 4], &TEXT_FG);
+    }
+}
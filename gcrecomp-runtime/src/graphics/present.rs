@@ -0,0 +1,114 @@
+// Present mode selection: vsync / low-latency / uncapped
+//
+// Surfaces only support some present modes (Fifo is the one every backend
+// guarantees), so a requested mode is resolved against the surface's
+// capabilities, falling back to the nearest supported one.
+
+use std::str::FromStr;
+use wgpu::PresentMode;
+
+/// User-facing present mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PresentModeSetting {
+    /// Wait for vblank (`Fifo`); never tears.
+    #[default]
+    Vsync,
+    /// Replace the queued frame (`Mailbox`); no tearing, lower latency.
+    LowLatency,
+    /// Present immediately (`Immediate`); may tear.
+    Uncapped,
+}
+
+impl PresentModeSetting {
+    /// The wgpu modes to try, best match first.
+    fn preference(self) -> &'static [PresentMode] {
+        match self {
+            PresentModeSetting::Vsync => &[PresentMode::Fifo, PresentMode::FifoRelaxed],
+            PresentModeSetting::LowLatency => &[
+                PresentMode::Mailbox,
+                PresentMode::Immediate,
+                PresentMode::Fifo,
+            ],
+            PresentModeSetting::Uncapped => &[
+                PresentMode::Immediate,
+                PresentMode::Mailbox,
+                PresentMode::Fifo,
+            ],
+        }
+    }
+}
+
+impl FromStr for PresentModeSetting {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vsync" => Ok(PresentModeSetting::Vsync),
+            "low_latency" => Ok(PresentModeSetting::LowLatency),
+            "uncapped" => Ok(PresentModeSetting::Uncapped),
+            other => Err(format!(
+                "unknown present mode '{other}' (expected vsync, low_latency or uncapped)"
+            )),
+        }
+    }
+}
+
+/// Resolve `requested` against the modes a surface reports. Falls back along
+/// the setting's preference list, then to whatever the surface lists first.
+pub fn select_present_mode(
+    requested: PresentModeSetting,
+    supported: &[PresentMode],
+) -> PresentMode {
+    let chosen = requested
+        .preference()
+        .iter()
+        .copied()
+        .find(|mode| supported.contains(mode))
+        .or_else(|| supported.first().copied())
+        .unwrap_or(PresentMode::Fifo);
+    if chosen != requested.preference()[0] {
+        log::info!(
+            "Present mode {:?} not supported by the surface; using {:?}",
+            requested,
+            chosen
+        );
+    }
+    chosen
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unsupported_mode_clamps_to_supported_one() {
+        // A typical Wayland/Vulkan surface without Immediate.
+        let caps = [PresentMode::Fifo, PresentMode::Mailbox];
+        assert_eq!(
+            select_present_mode(PresentModeSetting::Uncapped, &caps),
+            PresentMode::Mailbox
+        );
+        assert_eq!(
+            select_present_mode(PresentModeSetting::Vsync, &caps),
+            PresentMode::Fifo
+        );
+
+        // Fifo-only surface.
+        let caps = [PresentMode::Fifo];
+        assert_eq!(
+            select_present_mode(PresentModeSetting::LowLatency, &caps),
+            PresentMode::Fifo
+        );
+        assert_eq!(
+            select_present_mode(PresentModeSetting::Uncapped, &caps),
+            PresentMode::Fifo
+        );
+
+        // No preferred mode at all: take what the surface offers.
+        let caps = [PresentMode::AutoNoVsync];
+        assert_eq!(
+            select_present_mode(PresentModeSetting::Vsync, &caps),
+            PresentMode::AutoNoVsync
+        );
+    }
+}
@@ -1,6 +1,11 @@
 // Main renderer
+use crate::graphics::capture::read_texture_rgba;
 use crate::graphics::framebuffer::FrameBuffer;
+use crate::graphics::gx::draw::{render_viewport, scissor_rect};
+use crate::graphics::gx::pipeline::{clear_depth, DEPTH_FORMAT};
 use crate::graphics::gx::GXProcessor;
+use crate::graphics::overlay::Overlay;
+use crate::graphics::present::{select_present_mode, PresentModeSetting};
 use crate::graphics::shaders::ShaderManager;
 use crate::graphics::upscaler::Upscaler;
 use anyhow::Result;
@@ -28,18 +33,33 @@ pub struct Renderer {
     blit: Option<Blit>,
     /// Cached XFB upload texture (recreated when the framebuffer size changes).
     xfb: Option<(Texture, u32, u32)>,
+    /// Debug overlay, alpha-blended over the presented frame when visible.
+    overlay: Overlay,
+    overlay_tex: Option<(Texture, u32, u32)>,
+    /// Present modes the surface supports (for `set_present_mode`).
+    present_modes: Vec<PresentMode>,
 }
 
 /// Fullscreen-quad blit pipeline used to present a memory framebuffer.
 struct Blit {
     pipeline: RenderPipeline,
+    /// Same shader with alpha blending, for compositing the debug overlay.
+    overlay_pipeline: RenderPipeline,
     bind_group_layout: BindGroupLayout,
     sampler: Sampler,
 }
 
 impl Renderer {
     pub fn new(window: Arc<winit::window::Window>) -> Result<Self> {
-        let instance = Instance::new(InstanceDescriptor::default());
+        Self::with_backends(window, Backends::all())
+    }
+
+    /// Like `new`, restricted to `backends` (see `backends_from_name`).
+    pub fn with_backends(window: Arc<winit::window::Window>, backends: Backends) -> Result<Self> {
+        let instance = Instance::new(InstanceDescriptor {
+            backends,
+            ..Default::default()
+        });
         // SAFETY: The window is stored in Arc in the struct, ensuring it outlives the surface
         let surface = instance.create_surface(window.clone())?;
 
@@ -64,6 +84,7 @@ impl Renderer {
             .get_default_config(&adapter, size.width, size.height)
             .ok_or_else(|| anyhow::anyhow!("Failed to get surface config"))?;
 
+        let present_modes = surface.get_capabilities(&adapter).present_modes;
         surface.configure(&device, &config);
 
         let upscaler = Upscaler::new(&device, &config)?;
@@ -128,6 +149,9 @@ impl Renderer {
             depth_view: Some(depth_view),
             blit: None,
             xfb: None,
+            overlay: Overlay::new(),
+            overlay_tex: None,
+            present_modes,
         })
     }
 
@@ -166,7 +190,7 @@ impl Renderer {
             mip_level_count: 1,
             sample_count: 1,
             dimension: TextureDimension::D2,
-            format: TextureFormat::Depth24Plus,
+            format: DEPTH_FORMAT,
             usage: TextureUsages::RENDER_ATTACHMENT,
             view_formats: &[],
         });
@@ -181,6 +205,22 @@ impl Renderer {
         self.surface.configure(&self.device, &self.config);
     }
 
+    /// Switch vsync / low-latency / uncapped presentation. Modes the surface
+    /// doesn't support fall back (see `select_present_mode`); returns the mode
+    /// actually applied.
+    pub fn set_present_mode(&mut self, setting: PresentModeSetting) -> PresentMode {
+        let mode = select_present_mode(setting, &self.present_modes);
+        if mode != self.config.present_mode {
+            self.config.present_mode = mode;
+            self.surface.configure(&self.device, &self.config);
+        }
+        mode
+    }
+
+    pub fn present_mode(&self) -> PresentMode {
+        self.config.present_mode
+    }
+
     pub fn set_resolution(&mut self, width: u32, height: u32) {
         self.current_resolution = (width, height);
         let (efb, efb_view) = Self::create_efb(&self.device, width, height, self.config.format);
@@ -220,6 +260,7 @@ impl Renderer {
         };
 
         let clear_color = self.gx_processor.state.copy_clear_color;
+        let clear_z = clear_depth(self.gx_processor.state.copy_clear_z);
 
         let mut encoder = self
             .device
@@ -234,13 +275,13 @@ impl Renderer {
                     .map(|dv| RenderPassDepthStencilAttachment {
                         view: dv,
                         depth_ops: Some(Operations {
-                            load: LoadOp::Clear(1.0),
+                            load: LoadOp::Clear(clear_z),
                             store: StoreOp::Store,
                         }),
                         stencil_ops: None,
                     });
 
-            let _pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("GX Render Pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
                     view: efb_view,
@@ -260,10 +301,20 @@ impl Renderer {
                 occlusion_query_set: None,
             });
 
-            // Draw calls would be issued here using prepared draw commands
-            // from draw_list + pipeline_cache. For now we create and clear
-            // the render pass; per-draw-call submission requires the full
-            // pipeline/bind-group wiring which is set up in pipeline.rs.
+            // Per-draw raster state. Draws whose viewport or scissor is
+            // entirely off the EFB are dropped.
+            for dc in &draw_list {
+                let (Some(vp), Some(sc)) = (
+                    render_viewport(&dc.viewport, self.current_resolution),
+                    scissor_rect(&dc.scissor, self.current_resolution),
+                ) else {
+                    continue;
+                };
+                pass.set_viewport(vp.x, vp.y, vp.width, vp.height, vp.min_depth, vp.max_depth);
+                pass.set_scissor_rect(sc.x, sc.y, sc.width, sc.height);
+                // The draw itself is issued here once the pipeline and
+                // bind-group wiring in pipeline.rs is hooked up.
+            }
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
@@ -341,38 +392,203 @@ impl Renderer {
                 bind_group_layouts: &[&bind_group_layout],
                 push_constant_ranges: &[],
             });
-        let pipeline = self
-            .device
-            .create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("blit pipeline"),
-                layout: Some(&layout),
-                vertex: VertexState {
-                    module: &shader,
-                    entry_point: "vs_main",
-                    buffers: &[],
-                },
-                fragment: Some(FragmentState {
-                    module: &shader,
-                    entry_point: "fs_main",
-                    targets: &[Some(ColorTargetState {
-                        format: self.config.format,
-                        blend: None,
-                        write_mask: ColorWrites::ALL,
-                    })],
-                }),
-                primitive: PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                multiview: None,
-            });
+        let make_pipeline = |label: &str, blend: Option<BlendState>| {
+            self.device
+                .create_render_pipeline(&RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    vertex: VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[],
+                    },
+                    fragment: Some(FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(ColorTargetState {
+                            format: self.config.format,
+                            blend,
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    multiview: None,
+                })
+        };
+        let pipeline = make_pipeline("blit pipeline", None);
+        let overlay_pipeline = make_pipeline("overlay pipeline", Some(BlendState::ALPHA_BLENDING));
         let sampler = self.device.create_sampler(&SamplerDescriptor::default());
         self.blit = Some(Blit {
             pipeline,
+            overlay_pipeline,
             bind_group_layout,
             sampler,
         });
     }
 
+    pub fn overlay(&self) -> &Overlay {
+        &self.overlay
+    }
+
+    pub fn overlay_mut(&mut self) -> &mut Overlay {
+        &mut self.overlay
+    }
+
+    /// Read the last presented game frame back to the CPU, at window size.
+    ///
+    /// Swapchain images generally can't be copied from, so the current XFB
+    /// texture is re-blitted into an offscreen target of the surface format (so
+    /// the colors match what's on screen) and read back from there. The debug
+    /// overlay is not included.
+    pub fn capture_frame(&mut self) -> Result<image::RgbaImage> {
+        self.ensure_blit();
+        let xfb = self
+            .xfb
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No frame has been presented yet"))?;
+        let blit = self.blit.as_ref().unwrap();
+        let (w, h) = (self.config.width.max(1), self.config.height.max(1));
+        let format = self.config.format;
+
+        let target = self.device.create_texture(&TextureDescriptor {
+            label: Some("capture target"),
+            size: Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let target_view = target.create_view(&TextureViewDescriptor::default());
+        let xfb_view = xfb.0.create_view(&TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("capture bg"),
+            layout: &blit.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&xfb_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&blit.sampler),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("capture blit"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("capture pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &target_view,
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            pass.set_pipeline(&blit.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.draw(0..3, 0..1);
+        }
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        read_texture_rgba(&self.device, &self.queue, &target, w, h, format)
+    }
+
+    /// Capture the current frame and write it to `path` (format from the extension).
+    pub fn save_screenshot(&mut self, path: &std::path::Path) -> Result<()> {
+        let image = self.capture_frame()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        image.save(path)?;
+        log::info!("Saved screenshot to {}", path.display());
+        Ok(())
+    }
+
+    /// Upload the overlay panel (if visible) and build its bind group.
+    fn prepare_overlay(&mut self) -> Option<(BindGroup, u32, u32)> {
+        let (rgba, w, h) = self.overlay.panel()?;
+
+        let need_new = self
+            .overlay_tex
+            .as_ref()
+            .map(|(_, tw, th)| *tw != w || *th != h)
+            .unwrap_or(true);
+        if need_new {
+            let tex = self.device.create_texture(&TextureDescriptor {
+                label: Some("overlay"),
+                size: Extent3d {
+                    width: w,
+                    height: h,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+                view_formats: &[],
+            });
+            self.overlay_tex = Some((tex, w, h));
+        }
+        let tex = &self.overlay_tex.as_ref()?.0;
+        self.queue.write_texture(
+            ImageCopyTexture {
+                texture: tex,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            rgba,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * w),
+                rows_per_image: Some(h),
+            },
+            Extent3d {
+                width: w,
+                height: h,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        let view = tex.create_view(&TextureViewDescriptor::default());
+        let blit = self.blit.as_ref()?;
+        let bind_group = self.device.create_bind_group(&BindGroupDescriptor {
+            label: Some("overlay bg"),
+            layout: &blit.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&blit.sampler),
+                },
+            ],
+        });
+        Some((bind_group, w, h))
+    }
+
     /// Present an RGBA8 framebuffer (read from emulated RAM) to the window by
     /// uploading it to a texture and blitting it fullscreen. `rgba.len()` must be
     /// `w * h * 4`.
@@ -442,6 +658,9 @@ impl Renderer {
             ],
         });
 
+        let overlay_bind_group = self.prepare_overlay();
+        let blit = self.blit.as_ref().unwrap();
+
         let output = self.surface.get_current_texture()?;
         let out_view = output
             .texture
@@ -469,9 +688,36 @@ impl Renderer {
             pass.set_pipeline(&blit.pipeline);
             pass.set_bind_group(0, &bind_group, &[]);
             pass.draw(0..3, 0..1);
+
+            // Debug overlay goes on top of the game frame, top-left, at 2x
+            if let Some((overlay_bg, w, h)) = &overlay_bind_group {
+                let scale = 2.0;
+                let (vw, vh) = (
+                    (*w as f32 * scale).min(self.config.width as f32 - 8.0),
+                    (*h as f32 * scale).min(self.config.height as f32 - 8.0),
+                );
+                if vw > 0.0 && vh > 0.0 {
+                    pass.set_viewport(8.0, 8.0, vw, vh, 0.0, 1.0);
+                    pass.set_pipeline(&blit.overlay_pipeline);
+                    pass.set_bind_group(0, overlay_bg, &[]);
+                    pass.draw(0..3, 0..1);
+                }
+            }
         }
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
         Ok(())
     }
 }
+
+/// wgpu backends for a `[graphics] backend` config value; `auto` (or anything
+/// unrecognised) allows all of them.
+pub fn backends_from_name(name: &str) -> Backends {
+    match name {
+        "vulkan" => Backends::VULKAN,
+        "metal" => Backends::METAL,
+        "dx12" => Backends::DX12,
+        "gl" => Backends::GL,
+        _ => Backends::all(),
+    }
+}
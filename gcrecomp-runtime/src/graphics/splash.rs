@@ -0,0 +1,280 @@
+// Splash screen shown before the first game frame
+//
+// A logo centred on a black canvas, faded in and out over a configurable
+// duration. Everything is composited on the CPU into an RGBA8 frame that the
+// renderer presents like the XFB. Any key press skips it.
+
+use crate::graphics::overlay::{glyph, ADVANCE_X, GLYPH_H, GLYPH_W};
+use image::RgbaImage;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Canvas the logo is centred on (the XFB size, so presentation scales alike).
+pub const CANVAS_W: u32 = 640;
+pub const CANVAS_H: u32 = 480;
+
+const DEFAULT_DURATION: Duration = Duration::from_millis(2000);
+const DEFAULT_FADE: Duration = Duration::from_millis(500);
+
+/// Where a custom logo comes from.
+#[derive(Debug, Clone)]
+pub enum SplashImage {
+    Path(PathBuf),
+    Bytes(Vec<u8>),
+    /// Already-decoded pixels.
+    Rgba(RgbaImage),
+}
+
+impl From<PathBuf> for SplashImage {
+    fn from(path: PathBuf) -> Self {
+        SplashImage::Path(path)
+    }
+}
+
+impl From<&std::path::Path> for SplashImage {
+    fn from(path: &std::path::Path) -> Self {
+        SplashImage::Path(path.to_path_buf())
+    }
+}
+
+impl From<Vec<u8>> for SplashImage {
+    fn from(bytes: Vec<u8>) -> Self {
+        SplashImage::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for SplashImage {
+    fn from(bytes: &[u8]) -> Self {
+        SplashImage::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<RgbaImage> for SplashImage {
+    fn from(image: RgbaImage) -> Self {
+        SplashImage::Rgba(image)
+    }
+}
+
+/// Shape of the fade-in and fade-out ramps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FadeCurve {
+    Linear,
+    /// Smoothstep: eases in and out of the ramp.
+    #[default]
+    Smooth,
+}
+
+impl FadeCurve {
+    /// Map linear progress `t` in `[0, 1]` to an alpha in `[0, 1]`.
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => t,
+            FadeCurve::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SplashScreen {
+    logo: RgbaImage,
+    duration: Duration,
+    fade: Duration,
+    curve: FadeCurve,
+    started: Option<Instant>,
+    skipped: bool,
+}
+
+impl Default for SplashScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SplashScreen {
+    /// The built-in logo with the default timing.
+    pub fn new() -> Self {
+        Self {
+            logo: default_logo(),
+            duration: DEFAULT_DURATION,
+            fade: DEFAULT_FADE,
+            curve: FadeCurve::default(),
+            started: None,
+            skipped: false,
+        }
+    }
+
+    /// Use a custom logo. If it can't be loaded the built-in one stays.
+    /// Logos larger than the canvas are scaled down to fit.
+    pub fn with_image(mut self, image: impl Into<SplashImage>) -> Self {
+        let decoded = match image.into() {
+            SplashImage::Path(path) => image::open(&path)
+                .map(|img| img.to_rgba8())
+                .map_err(|e| format!("{}: {}", path.display(), e)),
+            SplashImage::Bytes(bytes) => image::load_from_memory(&bytes)
+                .map(|img| img.to_rgba8())
+                .map_err(|e| e.to_string()),
+            SplashImage::Rgba(img) => Ok(img),
+        };
+        match decoded {
+            Ok(img) if img.width() > 0 && img.height() > 0 => self.logo = fit_to_canvas(img),
+            Ok(_) => log::warn!("Splash image is empty; using the default"),
+            Err(e) => log::warn!("Failed to load splash image ({}); using the default", e),
+        }
+        self
+    }
+
+    /// Total time on screen, fades included. `0` disables the splash.
+    pub fn with_duration(mut self, ms: u64) -> Self {
+        self.duration = Duration::from_millis(ms);
+        self
+    }
+
+    /// Length of each of the fade-in and fade-out ramps, and their shape.
+    pub fn with_fade(mut self, ms: u64, curve: FadeCurve) -> Self {
+        self.fade = Duration::from_millis(ms);
+        self.curve = curve;
+        self
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// End the splash early (key press).
+    pub fn skip(&mut self) {
+        self.skipped = true;
+    }
+
+    /// Logo opacity `elapsed` after the splash started.
+    pub fn alpha_at(&self, elapsed: Duration) -> f32 {
+        if self.is_finished_at(elapsed) {
+            return 0.0;
+        }
+        // Ramps never overlap: on a short splash each takes at most half.
+        let fade = self.fade.min(self.duration / 2);
+        if fade.is_zero() {
+            return 1.0;
+        }
+        let remaining = self.duration - elapsed;
+        let t = if elapsed < fade {
+            elapsed.as_secs_f32() / fade.as_secs_f32()
+        } else if remaining < fade {
+            remaining.as_secs_f32() / fade.as_secs_f32()
+        } else {
+            1.0
+        };
+        self.curve.apply(t)
+    }
+
+    pub fn is_finished_at(&self, elapsed: Duration) -> bool {
+        self.skipped || elapsed >= self.duration
+    }
+
+    /// The canvas at `elapsed`: the logo centred on black, scaled by its alpha.
+    pub fn render_at(&self, elapsed: Duration) -> Vec<u8> {
+        let alpha = self.alpha_at(elapsed);
+        let mut out = vec![0u8; (CANVAS_W * CANVAS_H * 4) as usize];
+        for px in out.chunks_exact_mut(4) {
+            px[3] = 255;
+        }
+        let x0 = (CANVAS_W - self.logo.width()) / 2;
+        let y0 = (CANVAS_H - self.logo.height()) / 2;
+        for (x, y, p) in self.logo.enumerate_pixels() {
+            let a = alpha * p[3] as f32 / 255.0;
+            let i = (((y0 + y) * CANVAS_W + x0 + x) * 4) as usize;
+            for c in 0..3 {
+                out[i + c] = (p[c] as f32 * a).round() as u8;
+            }
+        }
+        out
+    }
+
+    /// Start the clock on first call. Returns the RGBA8 canvas
+    /// (`CANVAS_W` x `CANVAS_H`) to present, or `None` once the splash is over.
+    pub fn frame(&mut self, now: Instant) -> Option<Vec<u8>> {
+        let started = *self.started.get_or_insert(now);
+        let elapsed = now.saturating_duration_since(started);
+        if self.is_finished_at(elapsed) {
+            return None;
+        }
+        Some(self.render_at(elapsed))
+    }
+}
+
+fn fit_to_canvas(img: RgbaImage) -> RgbaImage {
+    if img.width() <= CANVAS_W && img.height() <= CANVAS_H {
+        return img;
+    }
+    let scale = (CANVAS_W as f32 / img.width() as f32).min(CANVAS_H as f32 / img.height() as f32);
+    let w = ((img.width() as f32 * scale) as u32).max(1);
+    let h = ((img.height() as f32 * scale) as u32).max(1);
+    image::imageops::resize(&img, w, h, image::imageops::FilterType::Triangle)
+}
+
+/// "GCRECOMP" in the overlay font at 6x, on transparency.
+fn default_logo() -> RgbaImage {
+    const TEXT: &str = "GCRECOMP";
+    const SCALE: u32 = 6;
+    let w = (TEXT.len() as u32 * ADVANCE_X - 1) * SCALE;
+    let h = GLYPH_H * SCALE;
+    let mut img = RgbaImage::new(w, h);
+    for (i, ch) in TEXT.chars().enumerate() {
+        for (row, bits) in glyph(ch).iter().enumerate() {
+            for col in 0..GLYPH_W {
+                if bits & (0x10 >> col) == 0 {
+                    continue;
+                }
+                let gx = (i as u32 * ADVANCE_X + col) * SCALE;
+                let gy = row as u32 * SCALE;
+                for dy in 0..SCALE {
+                    for dx in 0..SCALE {
+                        img.put_pixel(gx + dx, gy + dy, image::Rgba([120, 100, 230, 255]));
+                    }
+                }
+            }
+        }
+    }
+    img
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fade_midpoint_alpha_and_finish() {
+        let red = RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255]));
+        let splash = SplashScreen::new()
+            .with_image(red)
+            .with_duration(1000)
+            .with_fade(200, FadeCurve::Linear);
+
+        let ms = Duration::from_millis;
+        assert_eq!(splash.alpha_at(ms(0)), 0.0);
+        assert!((splash.alpha_at(ms(100)) - 0.5).abs() < 1e-6);
+        assert_eq!(splash.alpha_at(ms(500)), 1.0);
+        assert!((splash.alpha_at(ms(900)) - 0.5).abs() < 1e-6);
+
+        // Centre pixel of the canvas is the logo at half intensity.
+        let frame = splash.render_at(ms(100));
+        let i = (((CANVAS_H / 2) * CANVAS_W + CANVAS_W / 2) * 4) as usize;
+        assert_eq!(&frame[i..i + 4], &[128, 0, 0, 255]);
+        assert_eq!(&frame[0..4], &[0, 0, 0, 255]);
+
+        assert!(!splash.is_finished_at(ms(999)));
+        assert!(splash.is_finished_at(ms(1000)));
+        assert_eq!(splash.alpha_at(ms(1000)), 0.0);
+    }
+
+    #[test]
+    fn bad_image_falls_back_and_skip_ends_it() {
+        let mut splash = SplashScreen::new().with_image(&b"not an image"[..]);
+        assert_eq!(splash.logo, default_logo());
+
+        let now = Instant::now();
+        assert!(splash.frame(now).is_some());
+        splash.skip();
+        assert!(splash.frame(now).is_none());
+    }
+}
@@ -91,6 +91,7 @@ impl Backend for GilrsBackend {
                 axes,
                 triggers,
                 hat: None,
+                gyro: None,
             })
         } else {
             anyhow::bail!("Controller not found: {}", controller_id);
@@ -11,6 +11,12 @@ pub trait Backend {
     fn update(&mut self) -> Result<()>;
     fn enumerate_controllers(&mut self) -> Result<Vec<ControllerInfo>>;
     fn get_input(&self, controller_id: usize) -> Result<RawInput>;
+
+    /// Drive the controller's rumble motors. `low` and `high` are the low/high
+    /// frequency motor strengths in 0.0..=1.0. Backends without rumble ignore it.
+    fn set_rumble(&mut self, _controller_id: usize, _low: f32, _high: f32) -> Result<()> {
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -37,6 +43,19 @@ pub struct RawInput {
     pub axes: Vec<f32>,
     pub triggers: Vec<f32>,
     pub hat: Option<HatState>,
+    /// Motion sample, for controllers with a native IMU (Switch Pro).
+    pub gyro: Option<GyroData>,
+}
+
+/// One IMU sample: angular velocity in degrees/second and acceleration in g.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GyroData {
+    pub pitch: f32,
+    pub yaw: f32,
+    pub roll: f32,
+    pub accel_x: f32,
+    pub accel_y: f32,
+    pub accel_z: f32,
 }
 
 #[derive(Debug, Clone)]
@@ -108,11 +108,23 @@ impl Backend for SDL2Backend {
                 axes,
                 triggers,
                 hat: None,
+                gyro: None,
             })
         } else {
             anyhow::bail!("Controller not found: {}", controller_id);
         }
     }
+
+    fn set_rumble(&mut self, controller_id: usize, low: f32, high: f32) -> Result<()> {
+        if let Some(controller) = self.controllers.get_mut(&controller_id) {
+            let scale = |v: f32| (v.clamp(0.0, 1.0) * u16::MAX as f32) as u16;
+            // Re-sent every frame by callers, so a short duration is enough
+            controller
+                .set_rumble(scale(low), scale(high), 100)
+                .map_err(|e| anyhow::anyhow!("SDL2 rumble failed: {}", e))?;
+        }
+        Ok(())
+    }
 }
 
 fn detect_controller_type(name: &str) -> ControllerType {
@@ -56,6 +56,7 @@ impl Backend for XInputBackend {
             axes: vec![0.0; 6],
             triggers: vec![0.0; 2],
             hat: None,
+            gyro: None,
         })
     }
 }
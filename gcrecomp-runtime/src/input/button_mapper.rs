@@ -0,0 +1,322 @@
+// Turbo and macro bindings layered on top of the GameCube mapping
+//
+// Runs once per SI poll, after `GameCubeMapping` has produced the raw button
+// state and before the PAD origin step, so timing is counted in polled frames.
+use crate::input::controller::GameCubeButtons;
+use crate::input::profiles::ControllerProfile;
+use serde::{Deserialize, Serialize};
+
+/// A single GameCube controller button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GcButton {
+    A,
+    B,
+    X,
+    Y,
+    Start,
+    DUp,
+    DDown,
+    DLeft,
+    DRight,
+    L,
+    R,
+    Z,
+}
+
+impl GcButton {
+    pub fn get(self, buttons: &GameCubeButtons) -> bool {
+        match self {
+            GcButton::A => buttons.a,
+            GcButton::B => buttons.b,
+            GcButton::X => buttons.x,
+            GcButton::Y => buttons.y,
+            GcButton::Start => buttons.start,
+            GcButton::DUp => buttons.d_up,
+            GcButton::DDown => buttons.d_down,
+            GcButton::DLeft => buttons.d_left,
+            GcButton::DRight => buttons.d_right,
+            GcButton::L => buttons.l,
+            GcButton::R => buttons.r,
+            GcButton::Z => buttons.z,
+        }
+    }
+
+    pub fn set(self, buttons: &mut GameCubeButtons, pressed: bool) {
+        let slot = match self {
+            GcButton::A => &mut buttons.a,
+            GcButton::B => &mut buttons.b,
+            GcButton::X => &mut buttons.x,
+            GcButton::Y => &mut buttons.y,
+            GcButton::Start => &mut buttons.start,
+            GcButton::DUp => &mut buttons.d_up,
+            GcButton::DDown => &mut buttons.d_down,
+            GcButton::DLeft => &mut buttons.d_left,
+            GcButton::DRight => &mut buttons.d_right,
+            GcButton::L => &mut buttons.l,
+            GcButton::R => &mut buttons.r,
+            GcButton::Z => &mut buttons.z,
+        };
+        *slot = pressed;
+    }
+}
+
+/// While `button` is held, it is reported as repeatedly pressed and released
+/// `rate_hz` times per second.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurboBinding {
+    pub button: GcButton,
+    pub rate_hz: f32,
+}
+
+/// One macro step: `buttons` are held for `frames` polls. An empty step is a delay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroStep {
+    pub buttons: Vec<GcButton>,
+    pub frames: u32,
+}
+
+/// Pressing `trigger` plays `steps` in order. The trigger itself is consumed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroBinding {
+    pub trigger: GcButton,
+    pub steps: Vec<MacroStep>,
+}
+
+#[derive(Debug, Clone)]
+struct ActiveMacro {
+    binding: usize,
+    step: usize,
+    frames_left: u32,
+}
+
+pub struct ButtonMapper {
+    turbo: Vec<TurboBinding>,
+    /// Polls each turbo button has been held, so its cycle starts on the press.
+    turbo_held: Vec<u64>,
+    macros: Vec<MacroBinding>,
+    poll_rate_hz: f32,
+    active_macro: Option<ActiveMacro>,
+    /// Trigger state from the previous poll, for edge detection.
+    prev_triggers: Vec<bool>,
+}
+
+impl ButtonMapper {
+    pub fn new(poll_rate_hz: f32) -> Self {
+        Self {
+            turbo: Vec::new(),
+            turbo_held: Vec::new(),
+            macros: Vec::new(),
+            poll_rate_hz,
+            active_macro: None,
+            prev_triggers: Vec::new(),
+        }
+    }
+
+    pub fn from_profile(profile: &ControllerProfile, poll_rate_hz: f32) -> Self {
+        let mut mapper = Self::new(poll_rate_hz);
+        mapper.turbo_held = vec![0; profile.turbo.len()];
+        mapper.turbo = profile.turbo.clone();
+        mapper.set_macros(profile.macros.clone());
+        mapper
+    }
+
+    /// SI poll rate changed with the VI mode; turbo rates are relative to it.
+    pub fn set_poll_rate(&mut self, poll_rate_hz: f32) {
+        self.poll_rate_hz = poll_rate_hz;
+    }
+
+    pub fn add_turbo(&mut self, binding: TurboBinding) {
+        self.turbo.push(binding);
+        self.turbo_held.push(0);
+    }
+
+    pub fn add_macro(&mut self, binding: MacroBinding) {
+        self.macros.push(binding);
+        self.prev_triggers.push(false);
+    }
+
+    fn set_macros(&mut self, macros: Vec<MacroBinding>) {
+        self.prev_triggers = vec![false; macros.len()];
+        self.macros = macros;
+    }
+
+    pub fn is_macro_running(&self) -> bool {
+        self.active_macro.is_some()
+    }
+
+    /// Apply turbo and macros to one polled frame of button state.
+    pub fn apply(&mut self, buttons: &mut GameCubeButtons) {
+        self.apply_turbo(buttons);
+        self.apply_macros(buttons);
+    }
+
+    fn apply_turbo(&mut self, buttons: &mut GameCubeButtons) {
+        for (binding, held) in self.turbo.iter().zip(&mut self.turbo_held) {
+            if !binding.button.get(buttons) {
+                *held = 0;
+                continue;
+            }
+            let polls = *held;
+            *held += 1;
+            if binding.rate_hz <= 0.0 {
+                continue;
+            }
+            // Each cycle is one press + one release, and neither can be shorter
+            // than a single poll, so the rate is capped at half the poll rate.
+            let rate = binding.rate_hz.min(self.poll_rate_hz / 2.0);
+            let half_period = (self.poll_rate_hz / (rate * 2.0)).round().max(1.0) as u64;
+            let pressed = (polls / half_period) % 2 == 0;
+            binding.button.set(buttons, pressed);
+        }
+    }
+
+    fn apply_macros(&mut self, buttons: &mut GameCubeButtons) {
+        // Edge-detect triggers; held or re-pressed triggers don't restart a running macro
+        let mut started = None;
+        for (i, binding) in self.macros.iter().enumerate() {
+            let down = binding.trigger.get(buttons);
+            if down && !self.prev_triggers[i] && started.is_none() && self.active_macro.is_none() {
+                started = Some(i);
+            }
+            self.prev_triggers[i] = down;
+            binding.trigger.set(buttons, false);
+        }
+
+        if let Some(binding) = started {
+            self.active_macro = self.macros[binding].steps.first().map(|step| ActiveMacro {
+                binding,
+                step: 0,
+                frames_left: step.frames,
+            });
+        }
+
+        let Some(active) = self.active_macro.as_mut() else {
+            return;
+        };
+        let steps = &self.macros[active.binding].steps;
+
+        // Skip zero-length steps
+        while active.frames_left == 0 {
+            active.step += 1;
+            match steps.get(active.step) {
+                Some(step) => active.frames_left = step.frames,
+                None => {
+                    self.active_macro = None;
+                    return;
+                }
+            }
+        }
+
+        for &button in &steps[active.step].buttons {
+            button.set(buttons, true);
+        }
+        active.frames_left -= 1;
+
+        if active.frames_left == 0 && active.step + 1 >= steps.len() {
+            self.active_macro = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(button: GcButton) -> GameCubeButtons {
+        let mut buttons = GameCubeButtons::default();
+        button.set(&mut buttons, true);
+        buttons
+    }
+
+    #[test]
+    fn turbo_15hz_alternates_at_60hz_poll() {
+        let mut mapper = ButtonMapper::new(60.0);
+        mapper.add_turbo(TurboBinding {
+            button: GcButton::A,
+            rate_hz: 15.0,
+        });
+
+        let pattern: Vec<bool> = (0..8)
+            .map(|_| {
+                let mut buttons = held(GcButton::A);
+                mapper.apply(&mut buttons);
+                buttons.a
+            })
+            .collect();
+        assert_eq!(
+            pattern,
+            [true, true, false, false, true, true, false, false]
+        );
+
+        // Released button stays released
+        let mut buttons = GameCubeButtons::default();
+        mapper.apply(&mut buttons);
+        assert!(!buttons.a);
+    }
+
+    #[test]
+    fn turbo_cycle_starts_on_the_press() {
+        let mut mapper = ButtonMapper::new(60.0);
+        mapper.add_turbo(TurboBinding {
+            button: GcButton::A,
+            rate_hz: 15.0,
+        });
+
+        // Polls before the press don't shift the turbo phase
+        for _ in 0..3 {
+            mapper.apply(&mut GameCubeButtons::default());
+        }
+        let pattern: Vec<bool> = (0..4)
+            .map(|_| {
+                let mut buttons = held(GcButton::A);
+                mapper.apply(&mut buttons);
+                buttons.a
+            })
+            .collect();
+        assert_eq!(pattern, [true, true, false, false]);
+
+        // Releasing and pressing again restarts the cycle pressed
+        mapper.apply(&mut GameCubeButtons::default());
+        let mut buttons = held(GcButton::A);
+        mapper.apply(&mut buttons);
+        assert!(buttons.a);
+    }
+
+    #[test]
+    fn two_step_macro_fires_in_order_without_retrigger() {
+        let mut mapper = ButtonMapper::new(60.0);
+        mapper.add_macro(MacroBinding {
+            trigger: GcButton::Z,
+            steps: vec![
+                MacroStep {
+                    buttons: vec![GcButton::B],
+                    frames: 1,
+                },
+                MacroStep {
+                    buttons: vec![GcButton::A],
+                    frames: 2,
+                },
+            ],
+        });
+
+        let mut frames = Vec::new();
+        for i in 0..5 {
+            // Re-pressing the trigger on frame 2 must not restart the running macro
+            let mut buttons = if i % 2 == 0 {
+                held(GcButton::Z)
+            } else {
+                GameCubeButtons::default()
+            };
+            mapper.apply(&mut buttons);
+            assert!(!buttons.z, "trigger is consumed");
+            frames.push((buttons.b, buttons.a));
+        }
+
+        assert_eq!(frames[0], (true, false));
+        assert_eq!(frames[1], (false, true));
+        assert_eq!(frames[2], (false, true));
+        assert_eq!(frames[3], (false, false));
+        // Frame 4 is a fresh press after completion, so the macro starts again
+        assert_eq!(frames[4], (true, false));
+    }
+}
@@ -1,6 +1,9 @@
 // Controller detection and management
-use crate::input::backends::{Backend, ControllerInfo};
-use crate::input::gamecube_mapping::GameCubeMapping;
+use crate::input::backends::{Backend, ControllerInfo, GyroData};
+use crate::input::button_mapper::ButtonMapper;
+use crate::input::gamecube_mapping::{
+    si_poll_interval, GameCubeMapping, PadError, PadPort, SI_POLL_INTERVAL,
+};
 use crate::input::profiles::ControllerProfile;
 use anyhow::Result;
 use std::collections::HashMap;
@@ -9,7 +12,13 @@ pub struct ControllerManager {
     backends: Vec<Box<dyn Backend>>,
     controllers: HashMap<usize, ControllerState>,
     gamecube_mappings: HashMap<usize, GameCubeMapping>,
+    pad_ports: HashMap<usize, PadPort>,
+    /// SI poll interval of the current VI mode, shared by every port.
+    poll_interval: std::time::Duration,
+    button_mappers: HashMap<usize, ButtonMapper>,
     profiles: HashMap<String, ControllerProfile>,
+    /// Profile applied to newly connected controllers, if saved.
+    default_profile: Option<String>,
     _next_id: usize,
 }
 
@@ -35,6 +44,13 @@ impl ControllerManager {
             backends.push(Box::new(gilrs_backend));
         }
 
+        // Switch Pro over HID, for gyro and HD rumble the generic backends don't expose
+        if let Ok(switch_pro) = crate::input::switch_pro::SwitchProController::new() {
+            if switch_pro.is_connected() {
+                backends.push(Box::new(switch_pro));
+            }
+        }
+
         #[cfg(target_os = "windows")]
         {
             // Try XInput backend on Windows
@@ -47,7 +63,11 @@ impl ControllerManager {
             backends,
             controllers: HashMap::new(),
             gamecube_mappings: HashMap::new(),
+            pad_ports: HashMap::new(),
+            poll_interval: SI_POLL_INTERVAL,
+            button_mappers: HashMap::new(),
             profiles: HashMap::new(),
+            default_profile: None,
             _next_id: 0,
         })
     }
@@ -75,6 +95,11 @@ impl ControllerManager {
                 };
                 entry.insert(state);
 
+                // New or re-plugged pad: games see PAD_ERR_NOT_READY until the origin is read
+                let port = self.pad_ports.entry(controller.id).or_default();
+                port.set_poll_interval(self.poll_interval);
+                port.reconnect();
+
                 // Load default profile or create new mapping
                 self.load_default_mapping(controller.id)?;
             }
@@ -83,6 +108,7 @@ impl ControllerManager {
         // Check for disconnected controllers
         let connected_ids: Vec<usize> = all_controller_infos.iter().map(|c| c.id).collect();
 
+        self.pad_ports.retain(|id, _| connected_ids.contains(id));
         self.controllers.retain(|id, state| {
             if !connected_ids.contains(id) {
                 state.connected = false;
@@ -104,10 +130,20 @@ impl ControllerManager {
     }
 
     pub fn get_gamecube_input(&self, controller_id: usize) -> Option<GameCubeInput> {
-        let mapping = self.gamecube_mappings.get(&controller_id)?;
+        Self::sample(&self.backends, &self.gamecube_mappings, controller_id)
+    }
+
+    /// The mapped input from the first backend that has the controller. Takes
+    /// the fields it reads so `pad_read` can call it while a port is borrowed.
+    fn sample(
+        backends: &[Box<dyn Backend>],
+        mappings: &HashMap<usize, GameCubeMapping>,
+        controller_id: usize,
+    ) -> Option<GameCubeInput> {
+        let mapping = mappings.get(&controller_id)?;
 
         // Get raw input from backend
-        for backend in &self.backends {
+        for backend in backends {
             if let Ok(input) = backend.get_input(controller_id) {
                 return Some(mapping.map_to_gamecube(&input));
             }
@@ -116,6 +152,56 @@ impl ControllerManager {
         None
     }
 
+    /// Set rumble strength (0.0..=1.0 per motor) on whichever backend owns the controller.
+    pub fn set_rumble(&mut self, controller_id: usize, low: f32, high: f32) -> Result<()> {
+        for backend in &mut self.backends {
+            if backend.get_input(controller_id).is_ok() {
+                return backend.set_rumble(controller_id, low, high);
+            }
+        }
+        Ok(())
+    }
+
+    /// `PADRead` semantics: origin-relative input latched at the SI poll rate.
+    pub fn pad_read(&mut self, controller_id: usize) -> (GameCubeInput, PadError) {
+        let (backends, mappings) = (&self.backends, &self.gamecube_mappings);
+        let mapper = self.button_mappers.get_mut(&controller_id);
+        match self.pad_ports.get_mut(&controller_id) {
+            // The backend is read, and turbo/macros advance, only when SI
+            // actually polls; between polls the port hands back its latch.
+            Some(port) => port.poll(std::time::Instant::now(), || {
+                let mut sample = Self::sample(backends, mappings, controller_id)?;
+                if let Some(mapper) = mapper {
+                    mapper.apply(&mut sample.buttons);
+                }
+                Some(sample)
+            }),
+            None => (GameCubeInput::default(), PadError::NoController),
+        }
+    }
+
+    /// Poll at the VI field rate, like SI does; call when the video mode changes.
+    pub fn set_field_rate(&mut self, field_rate: f64) {
+        let interval = si_poll_interval(field_rate);
+        if interval == self.poll_interval {
+            return;
+        }
+        self.poll_interval = interval;
+        for port in self.pad_ports.values_mut() {
+            port.set_poll_interval(interval);
+        }
+        for mapper in self.button_mappers.values_mut() {
+            mapper.set_poll_rate(field_rate as f32);
+        }
+    }
+
+    /// `PADRecalibrate`: re-capture the analog origin on the next read.
+    pub fn pad_recalibrate(&mut self, controller_id: usize) {
+        if let Some(port) = self.pad_ports.get_mut(&controller_id) {
+            port.recalibrate();
+        }
+    }
+
     pub fn set_mapping(&mut self, controller_id: usize, mapping: GameCubeMapping) {
         self.gamecube_mappings.insert(controller_id, mapping);
     }
@@ -123,6 +209,11 @@ impl ControllerManager {
     pub fn load_profile(&mut self, controller_id: usize, profile_name: &str) -> Result<()> {
         if let Some(profile) = self.profiles.get(profile_name) {
             let mapping = profile.to_gamecube_mapping()?;
+            let poll_rate_hz = 1.0 / self.poll_interval.as_secs_f32();
+            self.button_mappers.insert(
+                controller_id,
+                ButtonMapper::from_profile(profile, poll_rate_hz),
+            );
             self.set_mapping(controller_id, mapping);
         }
         Ok(())
@@ -136,23 +227,58 @@ impl ControllerManager {
         Ok(())
     }
 
+    /// Profile to apply to controllers as they connect; `None` (or a name with
+    /// no saved profile) keeps the built-in per-controller mapping.
+    pub fn set_default_profile(&mut self, name: Option<String>) {
+        self.default_profile = name;
+    }
+
+    /// Switch every controller, connected now or later, to `profile`; `None`
+    /// goes back to the built-in per-controller mappings.
+    pub fn use_profile(&mut self, profile: Option<ControllerProfile>) -> Result<()> {
+        let ids: Vec<usize> = self.controllers.keys().copied().collect();
+        match profile {
+            Some(profile) => {
+                let name = profile.name.clone();
+                self.profiles.insert(name.clone(), profile);
+                self.default_profile = Some(name.clone());
+                for id in ids {
+                    self.load_profile(id, &name)?;
+                }
+            }
+            None => {
+                self.default_profile = None;
+                for id in ids {
+                    self.button_mappers.remove(&id);
+                    self.load_default_mapping(id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn load_default_mapping(&mut self, controller_id: usize) -> Result<()> {
         // Try to detect controller type and load appropriate default
         if let Some(state) = self.controllers.get(&controller_id) {
             let default_mapping = GameCubeMapping::default_for_controller(&state.info)?;
             self.set_mapping(controller_id, default_mapping);
         }
+        if let Some(name) = self.default_profile.clone() {
+            self.load_profile(controller_id, &name)?;
+        }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct GameCubeInput {
     pub buttons: GameCubeButtons,
     pub left_stick: (f32, f32),
     pub right_stick: (f32, f32),
     pub left_trigger: f32,
     pub right_trigger: f32,
+    /// Motion sample, when the controller has an IMU.
+    pub gyro: Option<GyroData>,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -170,3 +296,29 @@ pub struct GameCubeButtons {
     pub r: bool,
     pub z: bool,
 }
+
+impl GameCubeButtons {
+    /// Pack into the SDK's `PADStatus.button` bitmask (`PAD_BUTTON_*`).
+    pub fn to_pad_bits(&self) -> u16 {
+        let mut bits = 0u16;
+        for (pressed, bit) in [
+            (self.d_left, 0x0001),
+            (self.d_right, 0x0002),
+            (self.d_down, 0x0004),
+            (self.d_up, 0x0008),
+            (self.z, 0x0010),
+            (self.r, 0x0020),
+            (self.l, 0x0040),
+            (self.a, 0x0100),
+            (self.b, 0x0200),
+            (self.x, 0x0400),
+            (self.y, 0x0800),
+            (self.start, 0x1000),
+        ] {
+            if pressed {
+                bits |= bit;
+            }
+        }
+        bits
+    }
+}
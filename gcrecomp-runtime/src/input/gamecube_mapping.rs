@@ -139,11 +139,35 @@ impl GameCubeMapping {
         xbox
     }
 
+    /// Defaults for the native HID path (`switch_pro::button_index`). Face
+    /// buttons follow the printed Nintendo labels rather than Xbox positions.
     pub fn switch_pro_default() -> Self {
-        let mut xbox = Self::xbox_default();
-        xbox.controller_type = ControllerType::SwitchPro;
-        // Switch Pro has similar layout to Xbox
-        xbox
+        use crate::input::switch_pro::button_index as idx;
+
+        let mut mapping = Self::xbox_default();
+        mapping.controller_type = ControllerType::SwitchPro;
+        mapping.button_mappings = ButtonMappings {
+            a: ButtonMapping::Button(idx::A),
+            b: ButtonMapping::Button(idx::B),
+            x: ButtonMapping::Button(idx::X),
+            y: ButtonMapping::Button(idx::Y),
+            start: ButtonMapping::Button(idx::PLUS),
+            d_up: ButtonMapping::Button(idx::D_UP),
+            d_down: ButtonMapping::Button(idx::D_DOWN),
+            d_left: ButtonMapping::Button(idx::D_LEFT),
+            d_right: ButtonMapping::Button(idx::D_RIGHT),
+            l: ButtonMapping::Trigger(0, 0.5), // ZL (digital)
+            r: ButtonMapping::Trigger(1, 0.5), // ZR (digital)
+            z: ButtonMapping::Button(idx::R),
+        };
+        // HID report sticks are already up-positive
+        mapping.stick_mappings.left_stick.invert_y = false;
+        mapping.stick_mappings.right_stick.invert_y = false;
+        mapping.trigger_mappings = TriggerMappings {
+            left_trigger: 0,
+            right_trigger: 1,
+        };
+        mapping
     }
 
     pub fn generic_default() -> Self {
@@ -201,6 +225,7 @@ impl GameCubeMapping {
             right_stick,
             left_trigger,
             right_trigger,
+            gyro: input.gyro,
         }
     }
 
@@ -266,3 +291,243 @@ impl GameCubeMapping {
 }
 
 use anyhow::Result;
+
+/// SI polls each controller once per video field; `PADRead` returns the most
+/// recently latched sample rather than reading the hardware directly. This is
+/// the NTSC field; ports use it until the VI mode is known.
+pub const SI_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_micros(16_683);
+
+/// SI poll interval for a VI mode running at `field_rate` fields per second.
+pub fn si_poll_interval(field_rate: f64) -> std::time::Duration {
+    std::time::Duration::from_secs_f64(1.0 / field_rate)
+}
+
+/// `PADStatus.err` values as reported by the SDK's `PADRead`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i8)]
+pub enum PadError {
+    None = 0,
+    NoController = -1,
+    /// Controller is present but its origin hasn't been captured yet
+    /// (after `PADInit`, `PADRecalibrate`, or a reconnect).
+    NotReady = -2,
+    Transfer = -3,
+}
+
+/// Analog neutral positions captured at `PADInit`/`PADRecalibrate`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PadOrigin {
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+impl PadOrigin {
+    pub fn from_input(input: &GameCubeInput) -> Self {
+        Self {
+            left_stick: input.left_stick,
+            right_stick: input.right_stick,
+            left_trigger: input.left_trigger,
+            right_trigger: input.right_trigger,
+        }
+    }
+}
+
+/// Per-port PAD state: origin calibration plus the SI poll latch.
+#[derive(Debug, Clone)]
+pub struct PadPort {
+    origin: Option<PadOrigin>,
+    poll_interval: std::time::Duration,
+    last_poll: Option<std::time::Instant>,
+    latched: Option<(GameCubeInput, PadError)>,
+}
+
+impl Default for PadPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PadPort {
+    /// A freshly initialized port; the first sample becomes the origin.
+    pub fn new() -> Self {
+        Self {
+            origin: None,
+            poll_interval: SI_POLL_INTERVAL,
+            last_poll: None,
+            latched: None,
+        }
+    }
+
+    /// Follow the VI timing mode (see `si_poll_interval`).
+    pub fn set_poll_interval(&mut self, interval: std::time::Duration) {
+        self.poll_interval = interval;
+    }
+
+    pub fn origin(&self) -> Option<PadOrigin> {
+        self.origin
+    }
+
+    pub fn set_origin(&mut self, origin: PadOrigin) {
+        self.origin = Some(origin);
+    }
+
+    pub fn needs_origin(&self) -> bool {
+        self.origin.is_none()
+    }
+
+    /// `PADRecalibrate`: drop the origin so the next sample is re-captured.
+    pub fn recalibrate(&mut self) {
+        self.origin = None;
+        self.latched = None;
+    }
+
+    /// Controller was unplugged and plugged back in; real pads re-report
+    /// their origin, so games see `NotReady` for one poll.
+    pub fn reconnect(&mut self) {
+        self.recalibrate();
+        self.last_poll = None;
+    }
+
+    /// Convert a mapped sample into origin-relative values. If no origin is
+    /// set yet, this sample is captured as the origin and `NotReady` is returned.
+    pub fn read(&mut self, raw: GameCubeInput) -> (GameCubeInput, PadError) {
+        let Some(origin) = self.origin else {
+            self.origin = Some(PadOrigin::from_input(&raw));
+            return (
+                GameCubeInput {
+                    gyro: raw.gyro,
+                    ..GameCubeInput::default()
+                },
+                PadError::NotReady,
+            );
+        };
+
+        let stick = |(x, y): (f32, f32), (ox, oy): (f32, f32)| {
+            ((x - ox).clamp(-1.0, 1.0), (y - oy).clamp(-1.0, 1.0))
+        };
+        let trigger = |v: f32, o: f32| (v - o).clamp(0.0, 1.0);
+
+        let relative = GameCubeInput {
+            left_stick: stick(raw.left_stick, origin.left_stick),
+            right_stick: stick(raw.right_stick, origin.right_stick),
+            left_trigger: trigger(raw.left_trigger, origin.left_trigger),
+            right_trigger: trigger(raw.right_trigger, origin.right_trigger),
+            ..raw
+        };
+        (relative, PadError::None)
+    }
+
+    /// `PADRead` with SI timing: only call `sample` once per poll interval and
+    /// return the latched result in between.
+    pub fn poll(
+        &mut self,
+        now: std::time::Instant,
+        sample: impl FnOnce() -> Option<GameCubeInput>,
+    ) -> (GameCubeInput, PadError) {
+        let due = self
+            .last_poll
+            .map_or(true, |last| now.duration_since(last) >= self.poll_interval);
+
+        if due || self.latched.is_none() {
+            self.last_poll = Some(now);
+            self.latched = Some(match sample() {
+                Some(raw) => self.read(raw),
+                None => (GameCubeInput::default(), PadError::NoController),
+            });
+        }
+
+        self.latched
+            .clone()
+            .unwrap_or((GameCubeInput::default(), PadError::NoController))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(lx: f32, ly: f32, l: f32) -> GameCubeInput {
+        GameCubeInput {
+            left_stick: (lx, ly),
+            left_trigger: l,
+            ..GameCubeInput::default()
+        }
+    }
+
+    #[test]
+    fn readings_are_relative_to_origin() {
+        let mut port = PadPort::new();
+        port.set_origin(PadOrigin {
+            left_stick: (0.1, -0.05),
+            left_trigger: 0.2,
+            ..PadOrigin::default()
+        });
+
+        let (out, err) = port.read(sample(0.6, -0.05, 0.5));
+        assert_eq!(err, PadError::None);
+        assert!((out.left_stick.0 - 0.5).abs() < 1e-6);
+        assert!(out.left_stick.1.abs() < 1e-6);
+        assert!((out.left_trigger - 0.3).abs() < 1e-6);
+
+        // Trigger below origin never goes negative
+        let (out, _) = port.read(sample(0.1, -0.05, 0.0));
+        assert_eq!(out.left_trigger, 0.0);
+    }
+
+    #[test]
+    fn recalibrate_recaptures_origin() {
+        let mut port = PadPort::new();
+        port.set_origin(PadOrigin::default());
+        port.recalibrate();
+        assert!(port.needs_origin());
+
+        // First read after recalibration latches the new origin
+        let (out, err) = port.read(sample(0.2, 0.1, 0.0));
+        assert_eq!(err, PadError::NotReady);
+        assert_eq!(out.left_stick, (0.0, 0.0));
+        assert_eq!(port.origin().unwrap().left_stick, (0.2, 0.1));
+
+        let (out, err) = port.read(sample(0.2, 0.1, 0.0));
+        assert_eq!(err, PadError::None);
+        assert_eq!(out.left_stick, (0.0, 0.0));
+    }
+
+    #[test]
+    fn reconnect_reports_not_ready_then_latches_per_poll() {
+        let mut port = PadPort::new();
+        port.set_origin(PadOrigin::default());
+        port.reconnect();
+
+        let t0 = std::time::Instant::now();
+        let (_, err) = port.poll(t0, || Some(sample(0.0, 0.0, 0.0)));
+        assert_eq!(err, PadError::NotReady);
+
+        // Within the same field the latched status is returned without sampling
+        let (_, err) = port.poll(t0, || panic!("sampled twice in one field"));
+        assert_eq!(err, PadError::NotReady);
+
+        let (out, err) = port.poll(t0 + SI_POLL_INTERVAL, || Some(sample(0.5, 0.0, 0.0)));
+        assert_eq!(err, PadError::None);
+        assert_eq!(out.left_stick, (0.5, 0.0));
+
+        let (_, err) = port.poll(t0 + SI_POLL_INTERVAL * 2, || None);
+        assert_eq!(err, PadError::NoController);
+    }
+
+    #[test]
+    fn pal_ports_poll_once_per_50hz_field() {
+        let mut port = PadPort::new();
+        port.set_origin(PadOrigin::default());
+        port.set_poll_interval(si_poll_interval(50.0));
+
+        let t0 = std::time::Instant::now();
+        port.poll(t0, || Some(sample(0.0, 0.0, 0.0)));
+        // An NTSC field later is still inside the same PAL field
+        let (out, _) = port.poll(t0 + SI_POLL_INTERVAL, || Some(sample(0.5, 0.0, 0.0)));
+        assert_eq!(out.left_stick, (0.0, 0.0));
+        let (out, _) = port.poll(t0 + si_poll_interval(50.0), || Some(sample(0.5, 0.0, 0.0)));
+        assert_eq!(out.left_stick, (0.5, 0.0));
+    }
+}
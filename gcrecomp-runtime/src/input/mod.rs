@@ -1,9 +1,11 @@
 pub mod backends;
+pub mod button_mapper;
 pub mod controller;
 pub mod gamecube_mapping;
 pub mod profiles;
 pub mod switch_pro;
 
+pub use button_mapper::ButtonMapper;
 pub use controller::ControllerManager;
 pub use gamecube_mapping::GameCubeMapping;
 pub use profiles::ControllerProfile;
@@ -1,16 +1,22 @@
 // Controller profile management
+use crate::input::button_mapper::{MacroBinding, TurboBinding};
 use crate::input::gamecube_mapping::{
     AxisMapping, ButtonMapping, ButtonMappings, DeadZones, GameCubeMapping, Sensitivity,
     StickMappings, TriggerMappings,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ControllerProfile {
     pub name: String,
     pub controller_type: String,
     pub mapping: SerializedMapping,
+    #[serde(default)]
+    pub turbo: Vec<TurboBinding>,
+    #[serde(default)]
+    pub macros: Vec<MacroBinding>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -158,6 +164,8 @@ impl ControllerProfile {
                     right_stick: sn.right_stick,
                 },
             },
+            turbo: Vec::new(),
+            macros: Vec::new(),
         }
     }
 
@@ -232,4 +240,36 @@ impl ControllerProfile {
         let profile: ControllerProfile = serde_json::from_str(&json)?;
         Ok(profile)
     }
+
+    /// Load `<dir>/<name>.json`, the file `[input] profile = "<name>"` names.
+    pub fn load_named(dir: &Path, name: &str) -> Result<Self> {
+        let path = dir.join(format!("{name}.json"));
+        Self::load_from_file(&path)
+            .with_context(|| format!("loading controller profile {}", path.display()))
+    }
+}
+
+/// Where named profiles live: `profiles/` next to the config file.
+pub fn profile_dir() -> Option<PathBuf> {
+    let config = std::env::var_os(gcrecomp_core::config::CONFIG_PATH_ENV)
+        .map(PathBuf::from)
+        .or_else(gcrecomp_core::config::Config::default_path)?;
+    Some(config.parent()?.join("profiles"))
+}
+
+/// Names of the profiles in `dir`, sorted.
+pub fn list_profiles(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            (path.extension()? == "json")
+                .then(|| path.file_stem()?.to_str().map(str::to_string))
+                .flatten()
+        })
+        .collect();
+    names.sort();
+    names
 }
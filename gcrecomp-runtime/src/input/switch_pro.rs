@@ -1,24 +1,100 @@
 // Nintendo Switch Pro Controller support
+//
+// Talks to the controller over HID directly so we get what the generic
+// gilrs/SDL2 paths flatten away: the Nintendo face-button layout, the 12-bit
+// sticks, the 6-axis IMU and HD rumble.
+use crate::input::backends::{
+    Backend, ControllerInfo, ControllerType, GyroData, HatState, RawInput,
+};
 use anyhow::Result;
 use hidapi::HidApi;
 
+/// Switch Pro Controller USB vendor/product IDs
+const NINTENDO_VENDOR_ID: u16 = 0x057e;
+const PRO_CONTROLLER_PRODUCT_ID: u16 = 0x2009;
+
+/// Controller id reported through `ControllerInfo`. The HID path only drives a
+/// single Pro Controller, so a fixed id outside the gilrs/SDL2 index range works.
+pub const SWITCH_PRO_CONTROLLER_ID: usize = 0x2009;
+
+/// Standard full input report (buttons + sticks + 3 IMU samples).
+const REPORT_FULL: u8 = 0x30;
+/// Output report carrying rumble data only.
+const REPORT_RUMBLE: u8 = 0x10;
+/// Output report carrying rumble data plus a subcommand.
+const REPORT_SUBCOMMAND: u8 = 0x01;
+
+/// Subcommand: set input report mode (argument: report id).
+const SUBCMD_SET_REPORT_MODE: u8 = 0x03;
+/// Subcommand: enable/disable the IMU (argument: 1 = on).
+const SUBCMD_ENABLE_IMU: u8 = 0x40;
+
+/// Gyro sensitivity at the default ±2000 dps range, in degrees/second per LSB.
+const GYRO_DPS_PER_LSB: f32 = 4000.0 / 65535.0;
+/// Accelerometer sensitivity at the default ±8 g range, in g per LSB.
+const ACCEL_G_PER_LSB: f32 = 16.0 / 65535.0;
+
+/// `RawInput::buttons` indices produced by this backend. The order is what
+/// `GameCubeMapping::switch_pro_default` is built against.
+pub mod button_index {
+    pub const A: usize = 0;
+    pub const B: usize = 1;
+    pub const X: usize = 2;
+    pub const Y: usize = 3;
+    pub const L: usize = 4;
+    pub const R: usize = 5;
+    pub const ZL: usize = 6;
+    pub const ZR: usize = 7;
+    pub const MINUS: usize = 8;
+    pub const PLUS: usize = 9;
+    pub const HOME: usize = 10;
+    pub const CAPTURE: usize = 11;
+    pub const D_UP: usize = 12;
+    pub const D_DOWN: usize = 13;
+    pub const D_LEFT: usize = 14;
+    pub const D_RIGHT: usize = 15;
+    pub const L_STICK: usize = 16;
+    pub const R_STICK: usize = 17;
+    pub const COUNT: usize = 18;
+}
+
 pub struct SwitchProController {
     device: Option<hidapi::HidDevice>,
     connected: bool,
+    /// Last report parsed by `update`; `Backend::get_input` only has `&self`.
+    last_input: SwitchProInput,
+    /// Rolling 4-bit packet counter the controller expects on output reports.
+    packet_counter: u8,
 }
 
 impl SwitchProController {
     pub fn new() -> Result<Self> {
         let api = HidApi::new()?;
 
-        // Switch Pro Controller USB vendor/product IDs
-        const NINTENDO_VENDOR_ID: u16 = 0x057e;
-        const PRO_CONTROLLER_PRODUCT_ID: u16 = 0x2009;
-
         let device = api.open(NINTENDO_VENDOR_ID, PRO_CONTROLLER_PRODUCT_ID).ok();
         let connected = device.is_some();
 
-        Ok(Self { device, connected })
+        let mut controller = Self {
+            device,
+            connected,
+            last_input: SwitchProInput::default(),
+            packet_counter: 0,
+        };
+        if connected {
+            // The controller starts in simple HID mode (0x3F): no IMU and 8-bit
+            // sticks. Switch to full reports and turn the IMU on.
+            controller.send_subcommand(SUBCMD_SET_REPORT_MODE, &[REPORT_FULL])?;
+            controller.send_subcommand(SUBCMD_ENABLE_IMU, &[0x01])?;
+        }
+        Ok(controller)
+    }
+
+    fn send_subcommand(&mut self, id: u8, args: &[u8]) -> Result<()> {
+        if let Some(ref device) = self.device {
+            device.write(&subcommand_report(self.packet_counter, id, args))?;
+            self.packet_counter = (self.packet_counter + 1) & 0x0F;
+        }
+        Ok(())
     }
 
     pub fn is_connected(&self) -> bool {
@@ -38,44 +114,176 @@ impl SwitchProController {
         Ok(SwitchProInput::default())
     }
 
-    fn parse_input(data: &[u8]) -> Result<SwitchProInput> {
-        if data.len() < 10 {
+    /// Parse a standard full input report (0x30).
+    ///
+    /// Layout: `[0]` report id, `[3..6]` right/shared/left button bytes,
+    /// `[6..9]`/`[9..12]` packed 12-bit left/right stick, `[13..49]` three IMU
+    /// samples of accel xyz + gyro xyz (i16 LE), oldest first. Only the newest
+    /// (`[37..49]`) is kept.
+    pub fn parse_input(data: &[u8]) -> Result<SwitchProInput> {
+        if data.len() < 12 || data[0] != REPORT_FULL {
             return Ok(SwitchProInput::default());
         }
 
-        let buttons = u16::from_le_bytes([data[3], data[4]]);
+        let right = data[3];
+        let shared = data[4];
+        let left = data[5];
+
+        let (lx, ly) = unpack_stick(&data[6..9]);
+        let (rx, ry) = unpack_stick(&data[9..12]);
+
+        let gyro = if data.len() >= 49 {
+            let axis = |i: usize| i16::from_le_bytes([data[37 + i * 2], data[38 + i * 2]]) as f32;
+            Some(GyroData {
+                accel_x: axis(0) * ACCEL_G_PER_LSB,
+                accel_y: axis(1) * ACCEL_G_PER_LSB,
+                accel_z: axis(2) * ACCEL_G_PER_LSB,
+                roll: axis(3) * GYRO_DPS_PER_LSB,
+                pitch: axis(4) * GYRO_DPS_PER_LSB,
+                yaw: axis(5) * GYRO_DPS_PER_LSB,
+            })
+        } else {
+            None
+        };
 
         Ok(SwitchProInput {
-            a: (buttons & 0x0001) != 0,
-            b: (buttons & 0x0002) != 0,
-            x: (buttons & 0x0004) != 0,
-            y: (buttons & 0x0008) != 0,
-            minus: (buttons & 0x0010) != 0,
-            plus: (buttons & 0x0020) != 0,
-            l: (buttons & 0x0040) != 0,
-            r: (buttons & 0x0080) != 0,
-            zl: (buttons & 0x0100) != 0,
-            zr: (buttons & 0x0200) != 0,
-            left_stick_x: data[6] as f32 / 128.0 - 1.0,
-            left_stick_y: data[7] as f32 / 128.0 - 1.0,
-            right_stick_x: data[8] as f32 / 128.0 - 1.0,
-            right_stick_y: data[9] as f32 / 128.0 - 1.0,
+            y: right & 0x01 != 0,
+            x: right & 0x02 != 0,
+            b: right & 0x04 != 0,
+            a: right & 0x08 != 0,
+            r: right & 0x40 != 0,
+            zr: right & 0x80 != 0,
+            minus: shared & 0x01 != 0,
+            plus: shared & 0x02 != 0,
+            r_stick: shared & 0x04 != 0,
+            l_stick: shared & 0x08 != 0,
+            home: shared & 0x10 != 0,
+            capture: shared & 0x20 != 0,
+            d_down: left & 0x01 != 0,
+            d_up: left & 0x02 != 0,
+            d_right: left & 0x04 != 0,
+            d_left: left & 0x08 != 0,
+            l: left & 0x40 != 0,
+            zl: left & 0x80 != 0,
+            left_stick_x: lx,
+            left_stick_y: ly,
+            right_stick_x: rx,
+            right_stick_y: ry,
+            gyro,
         })
     }
 
-    pub fn set_rumble(&mut self, low_freq: u8, high_freq: u8) -> Result<()> {
+    /// Send an HD rumble frame. `low`/`high` are amplitudes (0.0..=1.0) for the
+    /// low (160 Hz) and high (320 Hz) bands, applied to both actuators.
+    pub fn set_rumble(&mut self, low: f32, high: f32) -> Result<()> {
+        let frame = encode_hd_rumble(160.0, low, 320.0, high);
         if let Some(ref device) = self.device {
             let mut buf = [0u8; 10];
-            buf[0] = 0x10; // Rumble command
-            buf[1] = 0x80;
-            buf[3] = low_freq;
-            buf[4] = high_freq;
+            buf[0] = REPORT_RUMBLE;
+            buf[1] = self.packet_counter;
+            buf[2..6].copy_from_slice(&frame); // left actuator
+            buf[6..10].copy_from_slice(&frame); // right actuator
             device.write(&buf)?;
+            self.packet_counter = (self.packet_counter + 1) & 0x0F;
+        }
+        Ok(())
+    }
+}
+
+impl Backend for SwitchProController {
+    fn update(&mut self) -> Result<()> {
+        if self.connected {
+            let input = self.read_input()?;
+            // A timed-out read returns the default report; keep the last state.
+            if input.gyro.is_some() {
+                self.last_input = input;
+            }
+        }
+        Ok(())
+    }
+
+    fn enumerate_controllers(&mut self) -> Result<Vec<ControllerInfo>> {
+        if !self.connected {
+            return Ok(Vec::new());
+        }
+        Ok(vec![ControllerInfo {
+            id: SWITCH_PRO_CONTROLLER_ID,
+            name: "Nintendo Switch Pro Controller".to_string(),
+            controller_type: ControllerType::SwitchPro,
+            button_count: button_index::COUNT,
+            axis_count: 4,
+        }])
+    }
+
+    fn get_input(&self, controller_id: usize) -> Result<RawInput> {
+        if controller_id != SWITCH_PRO_CONTROLLER_ID || !self.connected {
+            anyhow::bail!("Controller not found: {}", controller_id);
+        }
+        Ok(self.last_input.to_raw_input())
+    }
+
+    fn set_rumble(&mut self, controller_id: usize, low: f32, high: f32) -> Result<()> {
+        if controller_id == SWITCH_PRO_CONTROLLER_ID {
+            SwitchProController::set_rumble(self, low, high)?;
         }
         Ok(())
     }
 }
 
+/// Unpack a 3-byte, two-channel 12-bit stick sample into -1.0..=1.0 (up = +Y).
+fn unpack_stick(b: &[u8]) -> (f32, f32) {
+    let x = (b[0] as u16) | (((b[1] & 0x0F) as u16) << 8);
+    let y = ((b[1] >> 4) as u16) | ((b[2] as u16) << 4);
+    let norm = |v: u16| ((v as f32 - 2048.0) / 2048.0).clamp(-1.0, 1.0);
+    (norm(x), norm(y))
+}
+
+/// Output report 0x01: packet counter, neutral rumble for both actuators,
+/// then the subcommand id and its arguments.
+fn subcommand_report(counter: u8, id: u8, args: &[u8]) -> Vec<u8> {
+    let silence = encode_hd_rumble(160.0, 0.0, 320.0, 0.0);
+    let mut buf = vec![REPORT_SUBCOMMAND, counter];
+    buf.extend_from_slice(&silence);
+    buf.extend_from_slice(&silence);
+    buf.push(id);
+    buf.extend_from_slice(args);
+    buf
+}
+
+/// Encode one actuator's HD rumble frame (4 bytes) from a low/high band pair.
+///
+/// Frequencies are log2-encoded relative to 10 Hz (the scheme the controller
+/// firmware uses); amplitudes use the piecewise log curve from the same scheme.
+pub fn encode_hd_rumble(low_freq: f32, low_amp: f32, high_freq: f32, high_amp: f32) -> [u8; 4] {
+    let encode_freq = |f: f32| ((f.clamp(40.875, 1252.0) / 10.0).log2() * 32.0).round() as i32;
+    let encode_amp = |a: f32| -> i32 {
+        let a = a.clamp(0.0, 1.0);
+        if a <= 0.0 {
+            0
+        } else if a > 0.23 {
+            ((a * 8.7).log2() * 32.0).round() as i32
+        } else if a > 0.12 {
+            ((a * 17.0).log2() * 16.0).round() as i32
+        } else {
+            (((a.log2() * 32.0) - 96.0) / (5.0 - a.exp2()))
+                .round()
+                .max(0.0) as i32
+        }
+    };
+
+    let hf = ((encode_freq(high_freq) - 0x60).clamp(0, 0x7F) * 4) as u16;
+    let lf = (encode_freq(low_freq) - 0x40).clamp(0, 0x7F) as u8;
+    let hf_amp = (encode_amp(high_amp) * 2).clamp(0, 0xFE) as u8;
+    let lf_amp = (encode_amp(low_amp) / 2 + 0x40).clamp(0x40, 0x72) as u16;
+
+    [
+        (hf & 0xFF) as u8,
+        hf_amp.wrapping_add((hf >> 8) as u8),
+        lf.wrapping_add((lf_amp >> 8) as u8),
+        (lf_amp & 0xFF) as u8,
+    ]
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct SwitchProInput {
     pub a: bool,
@@ -84,12 +292,131 @@ pub struct SwitchProInput {
     pub y: bool,
     pub minus: bool,
     pub plus: bool,
+    pub home: bool,
+    pub capture: bool,
     pub l: bool,
     pub r: bool,
     pub zl: bool,
     pub zr: bool,
+    pub l_stick: bool,
+    pub r_stick: bool,
+    pub d_up: bool,
+    pub d_down: bool,
+    pub d_left: bool,
+    pub d_right: bool,
     pub left_stick_x: f32,
     pub left_stick_y: f32,
     pub right_stick_x: f32,
     pub right_stick_y: f32,
+    pub gyro: Option<GyroData>,
+}
+
+impl SwitchProInput {
+    /// Flatten into the backend-neutral `RawInput` (see `button_index`).
+    /// ZL/ZR are digital on this controller, so they surface as 0.0/1.0 triggers.
+    pub fn to_raw_input(&self) -> RawInput {
+        let mut buttons = vec![false; button_index::COUNT];
+        buttons[button_index::A] = self.a;
+        buttons[button_index::B] = self.b;
+        buttons[button_index::X] = self.x;
+        buttons[button_index::Y] = self.y;
+        buttons[button_index::L] = self.l;
+        buttons[button_index::R] = self.r;
+        buttons[button_index::ZL] = self.zl;
+        buttons[button_index::ZR] = self.zr;
+        buttons[button_index::MINUS] = self.minus;
+        buttons[button_index::PLUS] = self.plus;
+        buttons[button_index::HOME] = self.home;
+        buttons[button_index::CAPTURE] = self.capture;
+        buttons[button_index::D_UP] = self.d_up;
+        buttons[button_index::D_DOWN] = self.d_down;
+        buttons[button_index::D_LEFT] = self.d_left;
+        buttons[button_index::D_RIGHT] = self.d_right;
+        buttons[button_index::L_STICK] = self.l_stick;
+        buttons[button_index::R_STICK] = self.r_stick;
+
+        let digital = |b: bool| if b { 1.0 } else { 0.0 };
+
+        RawInput {
+            buttons,
+            axes: vec![
+                self.left_stick_x,
+                self.left_stick_y,
+                self.right_stick_x,
+                self.right_stick_y,
+            ],
+            triggers: vec![digital(self.zl), digital(self.zr)],
+            hat: Some(HatState {
+                up: self.d_up,
+                down: self.d_down,
+                left: self.d_left,
+                right: self.d_right,
+            }),
+            gyro: self.gyro,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::gamecube_mapping::GameCubeMapping;
+
+    fn pack_stick(x: u16, y: u16) -> [u8; 3] {
+        [
+            (x & 0xFF) as u8,
+            ((x >> 8) as u8 & 0x0F) | (((y & 0x0F) as u8) << 4),
+            (y >> 4) as u8,
+        ]
+    }
+
+    #[test]
+    fn full_report_maps_to_gamecube_input() {
+        let mut report = [0u8; 49];
+        report[0] = REPORT_FULL;
+        report[3] = 0x08 | 0x80; // A + ZR
+        report[4] = 0x02; // Plus
+        report[5] = 0x02; // D-pad up
+        report[6..9].copy_from_slice(&pack_stick(4095, 2048)); // left stick full right
+        report[9..12].copy_from_slice(&pack_stick(2048, 2048));
+        // An older IMU sample the parser skips...
+        report[23..25].copy_from_slice(&(-500i16).to_le_bytes());
+        // ...and the newest: accel z = +1 g (4096 LSB), gyro yaw = 1000 LSB.
+        report[41..43].copy_from_slice(&4096i16.to_le_bytes());
+        report[47..49].copy_from_slice(&1000i16.to_le_bytes());
+
+        let parsed = SwitchProController::parse_input(&report).unwrap();
+        let raw = parsed.to_raw_input();
+        let gc = GameCubeMapping::switch_pro_default().map_to_gamecube(&raw);
+
+        assert!(gc.buttons.a);
+        assert!(gc.buttons.start);
+        assert!(gc.buttons.d_up);
+        assert!(gc.buttons.r, "ZR fully pressed drives GC R");
+        assert!(!gc.buttons.b && !gc.buttons.l && !gc.buttons.z);
+        assert!((gc.left_stick.0 - 1.0).abs() < 0.01);
+        assert!(gc.left_stick.1.abs() < 0.01);
+        assert_eq!(gc.right_trigger, 1.0);
+
+        let gyro = gc.gyro.expect("gyro sample");
+        assert!((gyro.accel_z - 1.0).abs() < 0.01);
+        assert!((gyro.yaw - 1000.0 * GYRO_DPS_PER_LSB).abs() < 1e-3);
+    }
+
+    #[test]
+    fn open_sequence_requests_full_reports_and_imu() {
+        let mode = subcommand_report(0, SUBCMD_SET_REPORT_MODE, &[REPORT_FULL]);
+        assert_eq!(mode[..2], [REPORT_SUBCOMMAND, 0]);
+        assert_eq!(mode[10..], [0x03, 0x30]);
+        let imu = subcommand_report(1, SUBCMD_ENABLE_IMU, &[0x01]);
+        assert_eq!(imu[1], 1);
+        assert_eq!(imu[10..], [0x40, 0x01]);
+    }
+
+    #[test]
+    fn hd_rumble_silence_encodes_zero_amplitude() {
+        let frame = encode_hd_rumble(160.0, 0.0, 320.0, 0.0);
+        assert_eq!(frame[1] & 0xFE, 0, "high band amplitude");
+        assert_eq!(frame[3], 0x40, "low band amplitude floor");
+    }
 }
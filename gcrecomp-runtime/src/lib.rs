@@ -2,6 +2,7 @@ pub mod audio;
 pub mod graphics;
 pub mod input;
 pub mod memory;
+pub mod perf;
 pub mod runtime;
 pub mod texture;
 pub mod video;
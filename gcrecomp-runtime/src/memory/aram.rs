@@ -1,4 +1,5 @@
 // Audio RAM simulation (16MB)
+use crate::runtime::savestate::{Snapshot, StateReader, StateWriter};
 use anyhow::Result;
 
 pub struct ARam {
@@ -64,3 +65,13 @@ impl ARam {
         }
     }
 }
+
+impl Snapshot for ARam {
+    fn save(&self, w: &mut StateWriter) {
+        w.bytes(&self.data);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        r.bytes_into(&mut self.data)
+    }
+}
@@ -1,5 +1,106 @@
 // Memory mapping and address translation
+//
+// `MmioTable` is the hardware side of the I/O window: subsystems register
+// closures for the registers they implement, and once the table is attached
+// to the `MemoryManager` (`set_mmio`) recompiled `lwz`/`stw`/... to those
+// addresses run them. Registers nobody claims stay plain storage, so a read
+// returns the last value written.
 use anyhow::Result;
+use gcrecomp_core::runtime::memory::MmioBus;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Base address of each hardware register block.
+pub const CP_BASE: u32 = 0xCC00_0000;
+pub const PE_BASE: u32 = 0xCC00_1000;
+pub const VI_BASE: u32 = 0xCC00_2000;
+pub const PI_BASE: u32 = 0xCC00_3000;
+pub const MI_BASE: u32 = 0xCC00_4000;
+pub const DSP_BASE: u32 = 0xCC00_5000;
+pub const DI_BASE: u32 = 0xCC00_6000;
+pub const SI_BASE: u32 = 0xCC00_6400;
+pub const EXI_BASE: u32 = 0xCC00_6800;
+pub const AI_BASE: u32 = 0xCC00_6C00;
+pub const WGP_BASE: u32 = 0xCC00_8000;
+
+type ReadFn = Box<dyn Fn() -> u32 + Send + Sync>;
+/// Called with the value and the access width in bytes.
+type WriteFn = Box<dyn Fn(u32, u8) + Send + Sync>;
+
+#[derive(Default)]
+struct Register {
+    read: Option<ReadFn>,
+    write: Option<WriteFn>,
+}
+
+/// Register address -> handlers. Shared (`Arc`) between the memory manager
+/// and the subsystems that register into it.
+#[derive(Default)]
+pub struct MmioTable {
+    registers: RwLock<HashMap<u32, Register>>,
+}
+
+impl MmioTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `handler` with the written value on every store to `address`.
+    /// Replaces an earlier write handler for it.
+    pub fn on_write(&self, address: u32, handler: impl Fn(u32) + Send + Sync + 'static) {
+        self.on_sized_write(address, move |value, _| handler(value));
+    }
+
+    /// Like `on_write`, for registers where the store width matters (the
+    /// write-gather pipe): `handler` also gets the width in bytes.
+    pub fn on_sized_write(&self, address: u32, handler: impl Fn(u32, u8) + Send + Sync + 'static) {
+        self.registers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(address)
+            .or_default()
+            .write = Some(Box::new(handler));
+    }
+
+    /// Answer loads from `address` with `handler` (e.g. a status register).
+    pub fn on_read(&self, address: u32, handler: impl Fn() -> u32 + Send + Sync + 'static) {
+        self.registers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(address)
+            .or_default()
+            .read = Some(Box::new(handler));
+    }
+
+    /// Drop every handler in `base..base + len` (a subsystem going away).
+    pub fn unregister_block(&self, base: u32, len: u32) {
+        self.registers
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|&addr, _| !(base..base + len).contains(&addr));
+    }
+
+    pub fn is_mapped(&self, address: u32) -> bool {
+        self.registers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(&address)
+    }
+}
+
+impl MmioBus for MmioTable {
+    fn read(&self, address: u32, _size: u8) -> Option<u32> {
+        let registers = self.registers.read().unwrap_or_else(|e| e.into_inner());
+        registers.get(&address)?.read.as_ref().map(|read| read())
+    }
+
+    fn write(&self, address: u32, size: u8, value: u32) {
+        let registers = self.registers.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(write) = registers.get(&address).and_then(|r| r.write.as_ref()) {
+            write(value, size);
+        }
+    }
+}
 
 pub struct MemoryMapper {
     // Maps virtual addresses to physical memory regions
@@ -38,3 +139,34 @@ pub enum MemoryRegion {
     Ram(u32), // Physical RAM offset
     IO(u32),  // I/O register address
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcrecomp_core::runtime::memory::MemoryManager;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn store_to_registered_vi_register_runs_handler() {
+        const VI_TFBL: u32 = VI_BASE + 0x1C;
+        let table = Arc::new(MmioTable::new());
+        let written = Arc::new(AtomicU32::new(0));
+        let seen = written.clone();
+        table.on_write(VI_TFBL, move |value| seen.store(value, Ordering::SeqCst));
+        table.on_read(VI_BASE + 0x2C, || 0x0123); // VI_DPV (beam position)
+
+        let mut memory = MemoryManager::new();
+        memory.set_mmio(Some(table.clone()));
+        memory.write_u32(VI_TFBL, 0x1000_2000).unwrap();
+        assert_eq!(written.load(Ordering::SeqCst), 0x1000_2000);
+        // Still stored, and the handler doesn't see unrelated registers.
+        assert_eq!(memory.read_u32(VI_TFBL).unwrap(), 0x1000_2000);
+        memory.write_u32(VI_BASE + 0x20, 5).unwrap();
+        assert_eq!(written.load(Ordering::SeqCst), 0x1000_2000);
+
+        assert_eq!(memory.read_u16(VI_BASE + 0x2C).unwrap(), 0x0123);
+        table.unregister_block(VI_BASE, 0x100);
+        assert_eq!(memory.read_u16(VI_BASE + 0x2C).unwrap(), 0);
+    }
+}
@@ -3,8 +3,10 @@ pub mod dma;
 pub mod mapper;
 pub mod ram;
 pub mod vram;
+pub mod wgp;
 
 pub use aram::ARam;
 pub use dma::DmaSystem;
 pub use ram::Ram;
 pub use vram::VRam;
+pub use wgp::WriteGatherPipe;
@@ -12,6 +12,7 @@
 //! GameCube uses 24-bit addressing for RAM (addresses 0x00000000-0x00FFFFFF).
 //! The upper 8 bits are masked off to get the physical RAM offset.
 
+use crate::runtime::savestate::{Snapshot, StateReader, StateWriter};
 use anyhow::Result;
 
 /// Main RAM implementation (24MB).
@@ -262,3 +263,13 @@ impl Default for Ram {
         Self::new()
     }
 }
+
+impl Snapshot for Ram {
+    fn save(&self, w: &mut StateWriter) {
+        w.bytes(&self.data);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        r.bytes_into(&mut self.data)
+    }
+}
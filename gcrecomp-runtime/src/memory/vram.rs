@@ -1,4 +1,5 @@
 // Video RAM simulation (2MB)
+use crate::runtime::savestate::{Snapshot, StateReader, StateWriter};
 use anyhow::Result;
 
 pub struct VRam {
@@ -69,3 +70,13 @@ impl VRam {
         }
     }
 }
+
+impl Snapshot for VRam {
+    fn save(&self, w: &mut StateWriter) {
+        w.bytes(&self.data);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        r.bytes_into(&mut self.data)
+    }
+}
@@ -0,0 +1,159 @@
+// Write-gather pipe
+//
+// The SDK doesn't store GP commands into the FIFO ring itself: `GXWrite*`
+// store bytes, halfwords and words to 0xCC008000, the CPU's write-gather pipe
+// collects them in a 32-byte buffer, and each full buffer goes out as one
+// burst to the ring `GXSetGPFifo` attached. A partly filled buffer stays put
+// until more writes fill it; `GXFlush` pads it with 32 bytes of NOPs for that.
+//
+// `WriteGatherPipe` is that buffer, fed by MMIO handlers on the pipe's
+// 32-byte line. The handlers can't reach RAM, so full bursts queue here until
+// `GXProcessor::flush_write_gather` copies them into the ring.
+
+use super::mapper::{MmioTable, WGP_BASE};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Size of the gather buffer and of every burst.
+pub const WGP_BURST: usize = 32;
+
+#[derive(Debug, Default)]
+struct Gather {
+    /// Bytes written since the last burst; always shorter than a burst.
+    buffer: Vec<u8>,
+    /// Full bursts not yet copied into the ring, oldest first.
+    bursts: VecDeque<[u8; WGP_BURST]>,
+}
+
+#[derive(Debug, Default)]
+pub struct WriteGatherPipe {
+    gather: Mutex<Gather>,
+}
+
+impl WriteGatherPipe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gather every store to the pipe's line through `table`.
+    pub fn attach(self: &Arc<Self>, table: &MmioTable) {
+        for offset in 0..WGP_BURST as u32 {
+            let pipe = self.clone();
+            table.on_sized_write(WGP_BASE + offset, move |value, size| {
+                pipe.write(value, size)
+            });
+        }
+    }
+
+    /// Append a `size`-byte store of `value` (big-endian, as the bus sees
+    /// it), sending a burst each time the buffer fills.
+    pub fn write(&self, value: u32, size: u8) {
+        let bytes = value.to_be_bytes();
+        let size = (size as usize).clamp(1, 4);
+        let mut gather = self.lock();
+        gather.buffer.extend_from_slice(&bytes[4 - size..]);
+        if gather.buffer.len() >= WGP_BURST {
+            let mut burst = [0u8; WGP_BURST];
+            burst.copy_from_slice(&gather.buffer[..WGP_BURST]);
+            gather.buffer.drain(..WGP_BURST);
+            gather.bursts.push_back(burst);
+        }
+    }
+
+    /// Bytes sitting in the gather buffer, short of a burst.
+    pub fn buffered(&self) -> usize {
+        self.lock().buffer.len()
+    }
+
+    /// Full bursts waiting for the ring.
+    pub fn pending_bursts(&self) -> usize {
+        self.lock().bursts.len()
+    }
+
+    /// Remove and return the waiting bursts, oldest first.
+    pub fn take_bursts(&self) -> Vec<[u8; WGP_BURST]> {
+        self.lock().bursts.drain(..).collect()
+    }
+
+    /// Drop the gather buffer and anything queued.
+    pub fn reset(&self) {
+        *self.lock() = Gather::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Gather> {
+        self.gather.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::gx::command::{BP_ZMODE, CP_VCD_LO, OP_LOAD_BP_REG, OP_LOAD_CP_REG};
+    use crate::graphics::gx::fifo::PI_FIFO_WPTR;
+    use crate::graphics::gx::state::CompareFunction;
+    use crate::graphics::gx::GXProcessor;
+    use gcrecomp_core::runtime::memory::MemoryManager;
+
+    #[test]
+    fn gp_commands_reach_the_fifo_in_order() {
+        let table = Arc::new(MmioTable::new());
+        let pipe = Arc::new(WriteGatherPipe::new());
+        pipe.attach(&table);
+        let mut memory = MemoryManager::new();
+        memory.set_mmio(Some(table));
+        let mut gx = GXProcessor::new();
+        gx.fifo_mut().set_fifo(0x8010_0000, 0x1000);
+
+        // GXSetZMode(GX_TRUE, GX_GREATER, GX_FALSE), a CP load, a NOP pair:
+        // byte, word and halfword stores, as GXWrite* makes them.
+        let zmode = (BP_ZMODE as u32) << 24 | 1 | 4 << 1;
+        memory.write_u8(WGP_BASE, OP_LOAD_BP_REG).unwrap();
+        memory.write_u32(WGP_BASE, zmode).unwrap();
+        memory.write_u8(WGP_BASE, OP_LOAD_CP_REG).unwrap();
+        memory.write_u8(WGP_BASE, CP_VCD_LO).unwrap();
+        memory.write_u32(WGP_BASE, 1 << 9).unwrap();
+        memory.write_u16(WGP_BASE, 0).unwrap();
+        let mut expected = vec![OP_LOAD_BP_REG];
+        expected.extend_from_slice(&zmode.to_be_bytes());
+        expected.extend_from_slice(&[OP_LOAD_CP_REG, CP_VCD_LO]);
+        expected.extend_from_slice(&(1u32 << 9).to_be_bytes());
+        expected.extend_from_slice(&[0, 0]);
+
+        // Short of a burst: nothing reaches the ring.
+        assert_eq!(pipe.buffered(), expected.len());
+        assert_eq!(gx.flush_write_gather(&pipe, &mut memory), 0);
+        assert_ne!(gx.state.z_mode.function, CompareFunction::Greater);
+
+        // GXFlush: 32 bytes of NOPs push the command out.
+        for _ in 0..8 {
+            memory.write_u32(WGP_BASE, 0).unwrap();
+        }
+        assert_eq!(pipe.pending_bursts(), 1);
+        assert_eq!(pipe.buffered(), expected.len());
+        expected.resize(WGP_BURST, 0);
+
+        assert_eq!(gx.flush_write_gather(&pipe, &mut memory), WGP_BURST);
+        assert_eq!(memory.read_bytes(0x8010_0000, WGP_BURST).unwrap(), expected);
+        assert_eq!(gx.state.z_mode.function, CompareFunction::Greater);
+        assert!(!gx.state.z_mode.update);
+        assert_eq!(memory.read_io_u32(PI_FIFO_WPTR).unwrap(), 0x0010_0020);
+        assert_eq!(pipe.pending_bursts(), 0);
+    }
+
+    #[test]
+    fn doubleword_stores_split_high_word_first() {
+        let table = Arc::new(MmioTable::new());
+        let pipe = Arc::new(WriteGatherPipe::new());
+        pipe.attach(&table);
+        let mut memory = MemoryManager::new();
+        memory.set_mmio(Some(table));
+        for i in 0..4u64 {
+            memory
+                .write_u64(WGP_BASE, 0x0001_0203_0405_0607 + i * 0x0808_0808_0808_0808)
+                .unwrap();
+        }
+        let burst = pipe.take_bursts();
+        assert_eq!(burst.len(), 1);
+        assert_eq!(burst[0].to_vec(), (0..32).collect::<Vec<u8>>());
+    }
+}
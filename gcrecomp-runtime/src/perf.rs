@@ -0,0 +1,101 @@
+// Frame timing statistics
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Number of frames averaged for FPS/frame-time readouts (~1 second at 60 Hz).
+const WINDOW: usize = 60;
+
+/// Rolling frame-time tracker. Call `record_frame` once per presented frame.
+/// Also accumulates per-function call counts for profile-guided recompiles.
+#[derive(Debug, Clone)]
+pub struct PerformanceMonitor {
+    last_frame: Option<Instant>,
+    frame_times: VecDeque<Duration>,
+    total_frames: u64,
+    calls: HashMap<u32, u64>,
+}
+
+impl Default for PerformanceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PerformanceMonitor {
+    pub fn new() -> Self {
+        Self {
+            last_frame: None,
+            frame_times: VecDeque::with_capacity(WINDOW),
+            total_frames: 0,
+            calls: HashMap::new(),
+        }
+    }
+
+    pub fn record_frame(&mut self) {
+        self.record_frame_at(Instant::now());
+    }
+
+    pub fn record_frame_at(&mut self, now: Instant) {
+        if let Some(last) = self.last_frame {
+            if self.frame_times.len() == WINDOW {
+                self.frame_times.pop_front();
+            }
+            self.frame_times.push_back(now.duration_since(last));
+        }
+        self.last_frame = Some(now);
+        self.total_frames += 1;
+    }
+
+    /// Average frame time over the window, in milliseconds.
+    pub fn frame_time_ms(&self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        let total: Duration = self.frame_times.iter().sum();
+        total.as_secs_f32() * 1000.0 / self.frame_times.len() as f32
+    }
+
+    pub fn fps(&self) -> f32 {
+        let ms = self.frame_time_ms();
+        if ms > 0.0 {
+            1000.0 / ms
+        } else {
+            0.0
+        }
+    }
+
+    pub fn total_frames(&self) -> u64 {
+        self.total_frames
+    }
+
+    /// Add `(address, calls)` samples, e.g. from `runtime::take_call_counts`.
+    pub fn record_calls(&mut self, samples: impl IntoIterator<Item = (u32, u64)>) {
+        for (address, calls) in samples {
+            *self.calls.entry(address).or_insert(0) += calls;
+        }
+    }
+
+    /// Functions by call count, hottest first (ties by address). Serialized as
+    /// JSON this is the `--profile-guided` input.
+    pub fn hot_functions(&self) -> Vec<(u32, u64)> {
+        let mut hot: Vec<(u32, u64)> = self.calls.iter().map(|(&a, &c)| (a, c)).collect();
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        hot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hot_functions_sorted_by_calls() {
+        let mut perf = PerformanceMonitor::new();
+        perf.record_calls([(0x8000_3000, 5), (0x8000_2000, 40)]);
+        perf.record_calls([(0x8000_3000, 50), (0x8000_1000, 5)]);
+        assert_eq!(
+            perf.hot_functions(),
+            vec![(0x8000_3000, 55), (0x8000_2000, 40), (0x8000_1000, 5)]
+        );
+    }
+}
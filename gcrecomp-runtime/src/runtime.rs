@@ -1,13 +1,36 @@
 // Complete runtime system integration
+pub mod pacing;
+pub mod recorder;
+pub mod savestate;
+
 use crate::audio::ai::AudioInterface;
-use crate::audio::mixer::AudioMixer;
+use crate::audio::mixer::{AudioMixer, SpeedMode};
 use crate::audio::output::AudioOutput;
-use crate::graphics::Renderer;
-use crate::input::ControllerManager;
-use crate::memory::{ARam, DmaSystem, Ram, VRam};
-use crate::texture::TextureLoader;
+use crate::graphics::renderer::backends_from_name;
+use crate::graphics::{PresentModeSetting, Renderer};
+use crate::input::controller::GameCubeInput;
+use crate::input::gamecube_mapping::PadError;
+use crate::input::profiles::profile_dir;
+use crate::input::{ControllerManager, ControllerProfile};
+use crate::memory::mapper::MmioTable;
+use crate::memory::{ARam, DmaSystem, Ram, VRam, WriteGatherPipe};
+use crate::perf::PerformanceMonitor;
+use crate::texture::{ReplacementRegistry, TextureLoader};
+use crate::video::modes::VideoMode;
 use crate::video::VideoInterface;
-use anyhow::Result;
+use anyhow::{ensure, Result};
+use gcrecomp_core::config::{Config, MASTER_VOLUME_RANGE, RESOLUTION_SCALE_RANGE};
+use gcrecomp_core::runtime::clock::FieldClock;
+use gcrecomp_core::runtime::context::CpuContext;
+use gcrecomp_core::runtime::interpreter::CallFn;
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::rng::{MemoryFill, SeededRng};
+use gcrecomp_core::runtime::sdk::interrupt::{InterruptSystem, OS_INTERRUPT_DSP_AI};
+use gcrecomp_core::runtime::sdk::os::OsState;
+use gcrecomp_core::runtime::sdk::region::Region;
+use gcrecomp_core::runtime::sdk::timer::TimeSource;
+use pacing::{FrameLimiter, FramePacer};
+use recorder::{RawFileSink, Recorder, RecorderConfig, RecordingStats};
 use std::sync::{Arc, Mutex};
 
 pub struct Runtime {
@@ -20,45 +43,344 @@ pub struct Runtime {
     dma: DmaSystem,
     video: VideoInterface,
     audio: AudioInterface,
+    /// Interrupts raised by emulated hardware (AI DMA), run by
+    /// `dispatch_interrupts`.
+    interrupts: InterruptSystem,
     audio_mixer: Arc<Mutex<AudioMixer>>,
     audio_output: AudioOutput,
+    perf: PerformanceMonitor,
+    recorder: Option<Recorder>,
+    pacer: FramePacer,
+    /// A field ran while paused (single step) and its interrupts haven't
+    /// been dispatched yet.
+    step_pending: bool,
+    /// On an instruction time source, fields end by instructions executed
+    /// (scaled by the clock ratio) instead of by host time.
+    field_clock: Option<FieldClock>,
+    /// Software cap on presented frames (`[graphics] frame_limit`).
+    limiter: Option<FrameLimiter>,
+    config: Config,
+    /// Hardware register handlers, attached to the game's memory with
+    /// `attach_mmio`.
+    mmio: Arc<MmioTable>,
+    /// Gathers GP command stores on the way to the GX FIFO; fed through
+    /// `mmio`, drained by `process_gx_fifo`.
+    wgp: Arc<WriteGatherPipe>,
+    /// Source of every nondeterministic value the game sees.
+    rng: SeededRng,
+    /// Whether `rng` came from `new_seeded` (reproducible run).
+    seeded: bool,
+    /// Console region the game runs as (`set_region`).
+    region: Region,
 }
 
 impl Runtime {
+    /// Build a runtime from the user config (file + `GCRECOMP_*` overrides,
+    /// see `gcrecomp_core::config`).
     pub fn new() -> Result<Self> {
+        let loaded = Config::load()?;
+        for line in loaded.describe().lines() {
+            log::debug!("config: {}", line);
+        }
+        Self::with_config(loaded.config)
+    }
+
+    /// A runtime for a reproducible run: memory and OS state it creates
+    /// are derived from `seed` (see `gcrecomp_core::runtime::rng`).
+    pub fn new_seeded(seed: u64) -> Result<Self> {
+        let mut runtime = Self::new()?;
+        runtime.rng = SeededRng::new(seed);
+        runtime.seeded = true;
+        Ok(runtime)
+    }
+
+    pub fn with_config(config: Config) -> Result<Self> {
         let audio_mixer = Arc::new(Mutex::new(AudioMixer::new(48000)));
-        let audio_output = AudioOutput::new(audio_mixer.clone());
+        let mut audio = AudioInterface::new();
+        audio.set_master_volume(config.audio.master_volume as f32 / 100.0);
+        let audio_output = AudioOutput::with_latency(audio_mixer.clone(), config.audio.latency_ms)
+            .with_master(audio.master_control());
+
+        let mut texture_loader = TextureLoader::new();
+        if !config.graphics.texture_pack.is_empty() {
+            let pack = std::path::Path::new(&config.graphics.texture_pack);
+            match ReplacementRegistry::load_dir(pack) {
+                Ok(replacements) => texture_loader.set_replacements(replacements),
+                Err(e) => log::warn!("Texture pack not loaded: {:#}", e),
+            }
+        }
+
+        // Recompiled code doesn't track MSR[EE]; hardware interrupts are
+        // always deliverable and gated per source by the mask.
+        let mut interrupts = InterruptSystem::new();
+        interrupts.set_master_enable(true);
+
+        let mut controller_manager = ControllerManager::new()?;
+        if config.input.profile != "default" {
+            match profile_dir()
+                .map(|dir| ControllerProfile::load_named(&dir, &config.input.profile))
+            {
+                Some(Ok(profile)) => controller_manager.use_profile(Some(profile))?,
+                Some(Err(e)) => log::warn!("input.profile: {:#}", e),
+                None => log::warn!("input.profile: no config directory to load it from"),
+            }
+        }
+
+        let mmio = Arc::new(MmioTable::new());
+        let wgp = Arc::new(WriteGatherPipe::new());
+        wgp.attach(&mmio);
 
         Ok(Self {
-            controller_manager: ControllerManager::new()?,
+            controller_manager,
             renderer: None,
-            texture_loader: TextureLoader::new(),
+            texture_loader,
             ram: Ram::new(),
             vram: VRam::new(),
             aram: ARam::new(),
             dma: DmaSystem::new(),
             video: VideoInterface::new(),
-            audio: AudioInterface::new(),
+            audio,
+            interrupts,
             audio_mixer,
             audio_output,
+            perf: PerformanceMonitor::new(),
+            recorder: None,
+            pacer: FramePacer::new(VideoInterface::new().current_mode().target_fps()),
+            step_pending: false,
+            field_clock: (config.time_source() == TimeSource::Instructions).then(|| {
+                FieldClock::new(
+                    VideoInterface::new().current_mode().target_fps(),
+                    config.runtime.ticks_per_instruction,
+                )
+            }),
+            limiter: config
+                .graphics
+                .frame_limit
+                .then(|| FrameLimiter::new(VideoInterface::new().current_mode().target_fps())),
+            config,
+            mmio,
+            wgp,
+            rng: SeededRng::from_entropy(),
+            seeded: false,
+            region: Region::default(),
         })
     }
 
+    /// The register table subsystems add their handlers to.
+    pub fn mmio(&self) -> &Arc<MmioTable> {
+        &self.mmio
+    }
+
+    /// Route `memory`'s hardware register accesses through [`Runtime::mmio`].
+    pub fn attach_mmio(&self, memory: &mut MemoryManager) {
+        memory.set_mmio(Some(self.mmio.clone()));
+    }
+
+    pub fn write_gather_pipe(&self) -> &Arc<WriteGatherPipe> {
+        &self.wgp
+    }
+
+    /// Move the GP commands the game wrote through the write-gather pipe
+    /// into the GX FIFO and execute them. Without a renderer they're dropped.
+    pub fn process_gx_fifo(&mut self, memory: &mut MemoryManager) -> usize {
+        match self.renderer.as_mut() {
+            Some(renderer) => renderer
+                .gx_processor_mut()
+                .flush_write_gather(&self.wgp, memory),
+            None => {
+                self.wgp.take_bursts();
+                0
+            }
+        }
+    }
+
+    /// The seed of a `new_seeded` runtime.
+    pub fn seed(&self) -> Option<u64> {
+        self.seeded.then(|| self.rng.seed())
+    }
+
+    pub fn rng_mut(&mut self) -> &mut SeededRng {
+        &mut self.rng
+    }
+
+    /// Fresh RAM: a pattern from the seed on seeded runs, zeros otherwise.
+    pub fn create_memory(&mut self) -> MemoryManager {
+        let fill = if self.seeded {
+            self.rng.memory_fill()
+        } else {
+            MemoryFill::Zero
+        };
+        MemoryManager::with_fill(fill)
+    }
+
+    /// Fresh OS state, on a virtual timebase on seeded runs.
+    pub fn create_os_state(&mut self) -> OsState {
+        if self.seeded {
+            OsState::seeded(&mut self.rng)
+        } else {
+            OsState::new()
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Run as `region`: the VI boots in its video mode and frames are paced
+    /// at its field rate.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.video.configure(VideoMode::for_region(region));
+        let rate = self.video.current_mode().target_fps();
+        self.pacer.set_field_rate(rate);
+        if let Some(clock) = &mut self.field_clock {
+            clock.set_field_rate(rate);
+        }
+        if let Some(limiter) = &mut self.limiter {
+            limiter.set_rate(rate);
+        }
+    }
+
+    /// The region for the game in `memory`: `[runtime] region` if forced,
+    /// otherwise the disc header's.
+    pub fn resolve_region(&self, memory: &MemoryManager) -> Region {
+        Region::resolve(Region::detect(memory), self.config.region_override())
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Change `[graphics] present_mode` while running.
+    pub fn set_present_mode(&mut self, mode: &str) -> Result<()> {
+        let setting = mode
+            .parse::<PresentModeSetting>()
+            .map_err(anyhow::Error::msg)?;
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_present_mode(setting);
+        }
+        self.config.graphics.present_mode = mode.to_string();
+        Ok(())
+    }
+
+    /// Change `[graphics] resolution_scale` while running.
+    pub fn set_resolution_scale(&mut self, scale: u32) -> Result<()> {
+        ensure!(
+            RESOLUTION_SCALE_RANGE.contains(&scale),
+            "resolution scale {scale} is out of range {}-{}",
+            RESOLUTION_SCALE_RANGE.start(),
+            RESOLUTION_SCALE_RANGE.end()
+        );
+        if let Some(renderer) = self.renderer.as_mut() {
+            renderer.set_upscale_factor(scale as f32)?;
+        }
+        self.config.graphics.resolution_scale = scale;
+        Ok(())
+    }
+
+    /// Change `[audio] master_volume` (percent, clamped) while running.
+    pub fn set_master_volume(&mut self, percent: u32) {
+        let percent = percent.min(*MASTER_VOLUME_RANGE.end());
+        self.audio.set_master_volume(percent as f32 / 100.0);
+        self.config.audio.master_volume = percent;
+    }
+
+    /// Switch all controllers to `profile` (`None`: the built-in mappings).
+    pub fn set_input_profile(&mut self, profile: Option<ControllerProfile>) -> Result<()> {
+        let name = profile
+            .as_ref()
+            .map_or_else(|| "default".to_string(), |p| p.name.clone());
+        self.controller_manager.use_profile(profile)?;
+        self.config.input.profile = name;
+        Ok(())
+    }
+
     pub fn initialize_graphics(&mut self, window: Arc<winit::window::Window>) -> Result<()> {
-        self.renderer = Some(Renderer::new(window)?);
+        let backends = backends_from_name(&self.config.graphics.backend);
+        let mut renderer = Renderer::with_backends(window, backends)?;
+        let present_mode = self
+            .config
+            .graphics
+            .present_mode
+            .parse::<PresentModeSetting>()
+            .unwrap_or_default();
+        renderer.set_present_mode(present_mode);
+        renderer.set_upscale_factor(self.config.graphics.resolution_scale as f32)?;
+        let anisotropy = self.config.graphics.anisotropy;
+        renderer
+            .gx_processor_mut()
+            .pipeline_cache_mut()
+            .set_anisotropy_override((anisotropy > 0).then_some(anisotropy as u16));
+        self.renderer = Some(renderer);
         Ok(())
     }
 
+    /// Whether the host should present a frame at `now`. Always true unless the
+    /// software frame limiter is enabled.
+    pub fn frame_due(&mut self, now: std::time::Instant) -> bool {
+        match self.limiter.as_mut() {
+            Some(limiter) => {
+                limiter.set_rate(self.video.current_mode().target_fps());
+                limiter.frame_due(now)
+            }
+            None => true,
+        }
+    }
+
+    /// When the frame limiter wants the next frame, if it is enabled.
+    pub fn next_frame_deadline(&self) -> Option<std::time::Instant> {
+        self.limiter.as_ref().and_then(|l| l.deadline())
+    }
+
     pub fn initialize_audio(&mut self) -> Result<()> {
         self.audio.init();
         self.audio_output.start()?;
         Ok(())
     }
 
+    /// Quiesce for a game-requested reset or exit: stop audio DMA and the
+    /// output stream, drop queued samples, and finalize any recording.
+    /// `initialize_audio` brings audio back after a restart.
+    pub fn shutdown(&mut self) {
+        self.audio.stop_dma();
+        self.audio_output.stop();
+        if let Ok(mut mixer) = self.audio_mixer.lock() {
+            mixer.clear();
+        }
+        if let Err(e) = self.stop_recording() {
+            log::warn!("Failed to finalize recording: {e}");
+        }
+    }
+
     pub fn update(&mut self) -> Result<()> {
-        // Update controller manager
+        // Update controller manager (also while paused, so hotplug still works)
         self.controller_manager.update()?;
 
+        let rate = self.video.current_mode().target_fps();
+        self.pacer.set_field_rate(rate);
+        self.controller_manager.set_field_rate(rate);
+        let fields = match &mut self.field_clock {
+            Some(clock) if !self.pacer.is_paused() => {
+                clock.set_field_rate(rate);
+                clock.fields_due()
+            }
+            _ => self.pacer.fields_due(std::time::Instant::now()),
+        };
+        self.step_pending |= self.pacer.is_paused() && fields > 0;
+        for _ in 0..fields {
+            self.run_field();
+        }
+
+        Ok(())
+    }
+
+    /// Emulate one VI field of hardware work.
+    fn run_field(&mut self) {
+        let bytes = self
+            .audio
+            .bytes_per_field(self.video.current_mode().target_fps());
+        self.audio.advance_dma(bytes, &mut self.interrupts);
+
         // Process any active DMA transfers
         for ch in 0..4 {
             if self.dma.is_active(ch) {
@@ -66,15 +388,63 @@ impl Runtime {
                 self.dma.complete_transfer(ch);
             }
         }
+    }
 
-        Ok(())
+    /// Pause or resume emulation. While paused no fields run and the last
+    /// presented frame stays on screen.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.pacer.set_paused(paused);
+        log::info!("Emulation {}", if paused { "paused" } else { "resumed" });
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pacer.is_paused()
+    }
+
+    /// A single-field step ran while paused and still needs its interrupts
+    /// dispatched. Hosts skip `dispatch_interrupts` while paused otherwise.
+    pub fn step_pending(&self) -> bool {
+        self.step_pending
+    }
+
+    /// While paused, run exactly one VI field now.
+    pub fn step_frame(&mut self) {
+        if !self.pacer.is_paused() {
+            return;
+        }
+        self.pacer.step();
+        let fields = self.pacer.fields_due(std::time::Instant::now());
+        self.step_pending |= fields > 0;
+        for _ in 0..fields {
+            self.run_field();
+        }
     }
 
-    pub fn get_controller_input(
-        &self,
-        controller_id: usize,
-    ) -> Option<crate::input::controller::GameCubeInput> {
-        self.controller_manager.get_gamecube_input(controller_id)
+    /// Fast-forward / slow-motion. Scales how many VI fields run per real
+    /// second (clamped to `pacing::MIN_SPEED..=MAX_SPEED`); audio follows in
+    /// `mode` (see `SpeedMode`). Returns the applied multiplier.
+    pub fn set_speed(&mut self, multiplier: f32, mode: SpeedMode) -> f32 {
+        let speed = self.pacer.set_speed(multiplier);
+        if let Ok(mut mixer) = self.audio_mixer.lock() {
+            mixer.set_playback_speed(speed, mode);
+        }
+        log::info!("Emulation speed: {:.2}x", speed);
+        speed
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.pacer.speed()
+    }
+
+    /// VI fields emulated since startup.
+    pub fn frame_count(&self) -> u64 {
+        self.pacer.field_count()
+    }
+
+    /// `PADRead` for one port: origin-relative, latched at the SI poll rate,
+    /// with the port's turbo and macros applied.
+    pub fn get_controller_input(&mut self, controller_id: usize) -> (GameCubeInput, PadError) {
+        self.controller_manager.pad_read(controller_id)
     }
 
     pub fn ram_mut(&mut self) -> &mut Ram {
@@ -129,7 +499,126 @@ impl Runtime {
         &mut self.audio
     }
 
+    /// AIRegisterDMACallback: `callback` runs (via `dispatch_interrupts`)
+    /// each time an AI DMA buffer drains. Returns the previous callback.
+    pub fn register_ai_dma_callback(&mut self, callback: u32) -> Option<u32> {
+        self.interrupts.set_handler(OS_INTERRUPT_DSP_AI, callback);
+        self.interrupts.enable_interrupt(OS_INTERRUPT_DSP_AI);
+        self.audio.register_dma_callback(callback)
+    }
+
+    pub fn interrupts_mut(&mut self) -> &mut InterruptSystem {
+        &mut self.interrupts
+    }
+
+    /// Run the recompiled handlers of interrupts raised since the last call.
+    pub fn dispatch_interrupts(
+        &mut self,
+        ctx: &mut CpuContext,
+        memory: &mut MemoryManager,
+        call: CallFn,
+    ) -> Result<usize> {
+        self.step_pending = false;
+        self.interrupts.dispatch(ctx, memory, call)
+    }
+
     pub fn audio_mixer(&self) -> &Arc<Mutex<AudioMixer>> {
         &self.audio_mixer
     }
+
+    pub fn perf(&self) -> &PerformanceMonitor {
+        &self.perf
+    }
+
+    pub fn perf_mut(&mut self) -> &mut PerformanceMonitor {
+        &mut self.perf
+    }
+
+    /// Refresh the debug overlay from the current frame stats and controller 0.
+    pub fn update_overlay(&mut self, pc: u32) {
+        let (input, _) = self.controller_manager.pad_read(0);
+        let pad_buttons = input.buttons.to_pad_bits();
+        let stats = crate::graphics::OverlayStats {
+            fps: self.perf.fps(),
+            frame_time_ms: self.perf.frame_time_ms(),
+            pad_buttons,
+            pc,
+        };
+        if let Some(renderer) = self.renderer.as_mut() {
+            if renderer.overlay().is_visible() {
+                renderer.overlay_mut().update(&stats);
+            }
+        }
+    }
+
+    /// Start dumping presented frames and mixed audio to `dir` (see `RawFileSink`).
+    pub fn start_recording(&mut self, dir: &std::path::Path) -> Result<()> {
+        if self.recorder.is_some() {
+            anyhow::bail!("Already recording");
+        }
+        let sample_rate = self
+            .audio_mixer
+            .lock()
+            .map(|mut m| {
+                m.set_tee(true);
+                m.sample_rate
+            })
+            .unwrap_or(48000);
+        let config = RecorderConfig {
+            field_rate: self.video.current_mode().target_fps(),
+            sample_rate,
+            ..RecorderConfig::default()
+        };
+        let recorder = RawFileSink::create(dir, &config)
+            .and_then(|sink| Recorder::start(config, Box::new(sink)));
+        match recorder {
+            Ok(recorder) => self.recorder = Some(recorder),
+            Err(e) => {
+                self.set_audio_tee(false);
+                return Err(e);
+            }
+        }
+        log::info!("Recording to {}", dir.display());
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Result<Option<RecordingStats>> {
+        let Some(recorder) = self.recorder.take() else {
+            return Ok(None);
+        };
+        self.set_audio_tee(false);
+        let stats = recorder.stop()?;
+        log::info!(
+            "Recording stopped: {} frames, {} audio samples",
+            stats.frames,
+            stats.audio_samples
+        );
+        Ok(Some(stats))
+    }
+
+    fn set_audio_tee(&self, enabled: bool) {
+        if let Ok(mut mixer) = self.audio_mixer.lock() {
+            mixer.set_tee(enabled);
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Call once per presented field; captures it if a recording is active.
+    pub fn record_frame(&mut self) -> Result<()> {
+        let (Some(recorder), Some(renderer)) = (self.recorder.as_mut(), self.renderer.as_mut())
+        else {
+            return Ok(());
+        };
+        let frame = renderer.capture_frame()?;
+        if let Err(e) = recorder.record_field(frame, &self.audio_mixer) {
+            // A failed sink can't recover; drop the recorder so play continues.
+            self.recorder = None;
+            self.set_audio_tee(false);
+            return Err(e);
+        }
+        Ok(())
+    }
 }
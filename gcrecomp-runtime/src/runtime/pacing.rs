@@ -0,0 +1,235 @@
+// VI field pacing: how many fields of emulation to run per host tick
+use std::time::{Duration, Instant};
+
+/// Accepted range for `set_speed`.
+pub const MIN_SPEED: f32 = 0.1;
+pub const MAX_SPEED: f32 = 8.0;
+
+/// Most fields run in one tick (at 1.0x) after a stall, so a long hitch (debugger break,
+/// window drag) doesn't turn into a burst of catch-up frames.
+const MAX_CATCH_UP_FIELDS: u32 = 4;
+
+/// Converts elapsed host time into whole VI fields, with pause and single-step.
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    field_duration: Duration,
+    paused: bool,
+    pending_steps: u32,
+    fields: u64,
+    last_tick: Option<Instant>,
+    accum: Duration,
+    speed: f32,
+}
+
+impl FramePacer {
+    pub fn new(field_rate: f64) -> Self {
+        Self {
+            field_duration: Duration::from_secs_f64(1.0 / field_rate),
+            paused: false,
+            pending_steps: 0,
+            fields: 0,
+            last_tick: None,
+            accum: Duration::ZERO,
+            speed: 1.0,
+        }
+    }
+
+    pub fn set_field_rate(&mut self, field_rate: f64) {
+        self.field_duration = Duration::from_secs_f64(1.0 / field_rate);
+    }
+
+    /// Emulation speed relative to real time, clamped to `MIN_SPEED..=MAX_SPEED`.
+    /// Returns the value actually applied.
+    pub fn set_speed(&mut self, multiplier: f32) -> f32 {
+        self.speed = if multiplier.is_finite() {
+            multiplier.clamp(MIN_SPEED, MAX_SPEED)
+        } else {
+            1.0
+        };
+        self.speed
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused && !paused {
+            // Time spent paused must not be caught up on resume
+            self.last_tick = None;
+            self.accum = Duration::ZERO;
+        }
+        self.paused = paused;
+        self.pending_steps = 0;
+    }
+
+    /// Queue one field to run while paused. Ignored when running freely.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    /// Total fields handed out so far.
+    pub fn field_count(&self) -> u64 {
+        self.fields
+    }
+
+    /// Number of fields to emulate at host time `now`.
+    pub fn fields_due(&mut self, now: Instant) -> u32 {
+        let due = if self.paused {
+            std::mem::take(&mut self.pending_steps)
+        } else {
+            let elapsed = self
+                .last_tick
+                .map_or(self.field_duration, |last| now.duration_since(last));
+            self.last_tick = Some(now);
+            // Speed scales emulated time, so 2.0x runs twice the fields per real second
+            self.accum += elapsed.mul_f64(self.speed as f64);
+
+            let due = (self.accum.as_nanos() / self.field_duration.as_nanos().max(1)) as u32;
+            self.accum -= self.field_duration * due;
+            let cap = (MAX_CATCH_UP_FIELDS as f32 * self.speed).ceil() as u32;
+            if due > cap {
+                self.accum = Duration::ZERO;
+                cap
+            } else {
+                due
+            }
+        };
+        self.fields += due as u64;
+        due
+    }
+}
+
+/// Software frame limiter: caps presentation at the VI field rate when the
+/// present mode doesn't (uncapped / low-latency), so the host loop can sleep.
+#[derive(Debug, Clone)]
+pub struct FrameLimiter {
+    frame_duration: Duration,
+    next: Option<Instant>,
+}
+
+impl FrameLimiter {
+    pub fn new(field_rate: f64) -> Self {
+        Self {
+            frame_duration: Duration::from_secs_f64(1.0 / field_rate),
+            next: None,
+        }
+    }
+
+    pub fn set_rate(&mut self, field_rate: f64) {
+        self.frame_duration = Duration::from_secs_f64(1.0 / field_rate);
+    }
+
+    /// Whether a frame should be presented at `now`; if so the next one is
+    /// scheduled a frame later (or from `now`, after a stall).
+    pub fn frame_due(&mut self, now: Instant) -> bool {
+        match self.next {
+            Some(next) if now < next => false,
+            Some(next) => {
+                let after = next + self.frame_duration;
+                self.next = Some(if after <= now {
+                    now + self.frame_duration
+                } else {
+                    after
+                });
+                true
+            }
+            None => {
+                self.next = Some(now + self.frame_duration);
+                true
+            }
+        }
+    }
+
+    /// When the next frame is due, for `ControlFlow::WaitUntil`.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn step_advances_exactly_one_field_while_paused() {
+        let mut pacer = FramePacer::new(60.0);
+        let t0 = Instant::now();
+        pacer.fields_due(t0);
+        pacer.set_paused(true);
+        let start = pacer.field_count();
+
+        // Time passing while paused runs nothing
+        assert_eq!(pacer.fields_due(t0 + Duration::from_secs(1)), 0);
+
+        pacer.step();
+        assert_eq!(pacer.fields_due(t0 + Duration::from_secs(2)), 1);
+        pacer.step();
+        assert_eq!(pacer.fields_due(t0 + Duration::from_secs(3)), 1);
+        assert_eq!(pacer.fields_due(t0 + Duration::from_secs(4)), 0);
+
+        assert_eq!(pacer.field_count() - start, 2);
+    }
+
+    fn fields_over_one_second(speed: f32) -> u64 {
+        let mut pacer = FramePacer::new(60.0);
+        pacer.set_speed(speed);
+        let t0 = Instant::now();
+        pacer.fields_due(t0);
+        let start = pacer.field_count();
+        // Host ticking at 60 Hz for one simulated second. The 1ms offset keeps
+        // the window clear of the nanosecond rounding in the field duration.
+        for i in 1..=60u32 {
+            pacer.fields_due(t0 + Duration::from_millis(1) + Duration::from_secs(1) * i / 60);
+        }
+        pacer.field_count() - start
+    }
+
+    #[test]
+    fn double_speed_runs_twice_the_fields() {
+        let normal = fields_over_one_second(1.0);
+        let fast = fields_over_one_second(2.0);
+        assert_eq!(normal, 60);
+        assert_eq!(fast, normal * 2);
+        assert_eq!(fields_over_one_second(0.5), 30);
+    }
+
+    #[test]
+    fn speed_is_clamped() {
+        let mut pacer = FramePacer::new(60.0);
+        assert_eq!(pacer.set_speed(100.0), MAX_SPEED);
+        assert_eq!(pacer.set_speed(0.0), MIN_SPEED);
+        assert_eq!(pacer.set_speed(f32::NAN), 1.0);
+    }
+
+    #[test]
+    fn resume_does_not_catch_up_paused_time() {
+        let mut pacer = FramePacer::new(60.0);
+        let t0 = Instant::now();
+        pacer.fields_due(t0);
+        pacer.set_paused(true);
+        pacer.set_paused(false);
+        // First tick after resume counts as one field, not ten seconds' worth
+        assert_eq!(pacer.fields_due(t0 + Duration::from_secs(10)), 1);
+    }
+
+    #[test]
+    fn limiter_spaces_frames_at_the_field_rate() {
+        let mut limiter = FrameLimiter::new(50.0);
+        let t0 = Instant::now();
+        assert!(limiter.frame_due(t0));
+        assert!(!limiter.frame_due(t0 + Duration::from_millis(10)));
+        assert_eq!(limiter.deadline(), Some(t0 + Duration::from_millis(20)));
+        assert!(limiter.frame_due(t0 + Duration::from_millis(21)));
+        assert_eq!(limiter.deadline(), Some(t0 + Duration::from_millis(40)));
+        // A long stall doesn't queue a burst of frames
+        assert!(limiter.frame_due(t0 + Duration::from_millis(500)));
+        assert!(!limiter.frame_due(t0 + Duration::from_millis(505)));
+    }
+}
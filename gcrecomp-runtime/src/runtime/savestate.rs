@@ -0,0 +1,381 @@
+// Save states: the runtime's machine state as one blob
+//
+// Every subsystem with state implements `Snapshot` (usually next to its own
+// fields) and gets a named section in `SaveState::capture` / `restore`. A
+// section is only as good as its `save`/`load` pair, and a field one of them
+// forgets is silently lost on load. `SaveState::verify_roundtrip` catches
+// that: it captures a runtime, restores into a fresh one, captures again and
+// requires identical bytes, naming each section that changed. Run it (see
+// the tests below) whenever a subsystem gains save/load support.
+use super::Runtime;
+use anyhow::{bail, ensure, Context, Result};
+use gcrecomp_core::runtime::sdk::interrupt::InterruptSystem;
+
+const MAGIC: &[u8; 4] = b"GCSS";
+const VERSION: u32 = 1;
+
+/// A subsystem's part of a save state. `load` must read back exactly what
+/// `save` wrote, in order.
+pub trait Snapshot {
+    fn save(&self, w: &mut StateWriter);
+    fn load(&mut self, r: &mut StateReader) -> Result<()>;
+}
+
+/// Big-endian field writer for [`Snapshot::save`].
+#[derive(Debug, Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, v: u8) {
+        self.buf.push(v);
+    }
+
+    pub fn u16(&mut self, v: u16) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn u32(&mut self, v: u32) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn u64(&mut self, v: u64) {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+    }
+
+    pub fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    pub fn opt_u32(&mut self, v: Option<u32>) {
+        self.bool(v.is_some());
+        self.u32(v.unwrap_or(0));
+    }
+
+    /// Length-prefixed bytes.
+    pub fn bytes(&mut self, v: &[u8]) {
+        self.u32(v.len() as u32);
+        self.buf.extend_from_slice(v);
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Reader matching [`StateWriter`], for [`Snapshot::load`].
+#[derive(Debug)]
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.data.get(self.pos..self.pos + n) else {
+            bail!(
+                "needs {} more bytes at offset {} but only {} remain",
+                n,
+                self.pos,
+                self.remaining()
+            );
+        };
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap_or([0; N]))
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    pub fn bool(&mut self) -> Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub fn opt_u32(&mut self) -> Result<Option<u32>> {
+        let some = self.bool()?;
+        let v = self.u32()?;
+        Ok(some.then_some(v))
+    }
+
+    pub fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    /// Length-prefixed bytes into a buffer that must be exactly that long
+    /// (memory sizes don't change between save and load).
+    pub fn bytes_into(&mut self, dest: &mut [u8]) -> Result<()> {
+        let bytes = self.bytes()?;
+        ensure!(
+            bytes.len() == dest.len(),
+            "{} bytes saved for a {}-byte buffer",
+            bytes.len(),
+            dest.len()
+        );
+        dest.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+}
+
+impl Snapshot for InterruptSystem {
+    fn save(&self, w: &mut StateWriter) {
+        w.bytes(&serde_json::to_vec(self).unwrap_or_default());
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        *self = serde_json::from_slice(r.bytes()?)?;
+        Ok(())
+    }
+}
+
+/// Save `value` as one section.
+pub fn save_section<T: Snapshot + ?Sized>(value: &T) -> Vec<u8> {
+    let mut w = StateWriter::new();
+    value.save(&mut w);
+    w.into_inner()
+}
+
+/// Load one section into `value`; everything `save` wrote must be read.
+pub fn load_section<T: Snapshot + ?Sized>(value: &mut T, data: &[u8]) -> Result<()> {
+    let mut r = StateReader::new(data);
+    value.load(&mut r)?;
+    ensure!(
+        r.remaining() == 0,
+        "{} bytes left unread (load reads less than save writes)",
+        r.remaining()
+    );
+    Ok(())
+}
+
+/// Round-trip one subsystem: save `original`, load into `fresh`, save again
+/// and compare. Fails if a field `save` wrote was not restored.
+pub fn verify_section<T: Snapshot>(name: &str, original: &T, fresh: &mut T) -> Result<()> {
+    let first = save_section(original);
+    load_section(fresh, &first).with_context(|| format!("loading section '{name}'"))?;
+    ensure!(
+        save_section(fresh) == first,
+        "section '{name}' changed across save/load; a field isn't restored"
+    );
+    Ok(())
+}
+
+/// The runtime's machine state, one named section per subsystem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    sections: Vec<(String, Vec<u8>)>,
+}
+
+impl SaveState {
+    /// Snapshot every subsystem of `runtime`.
+    pub fn capture(runtime: &Runtime) -> Self {
+        let sections: [(&str, &dyn Snapshot); 6] = [
+            ("ram", &runtime.ram),
+            ("vram", &runtime.vram),
+            ("aram", &runtime.aram),
+            ("vi", &runtime.video),
+            ("ai", &runtime.audio),
+            ("interrupts", &runtime.interrupts),
+        ];
+        Self {
+            sections: sections
+                .into_iter()
+                .map(|(name, s)| (name.to_string(), save_section(s)))
+                .collect(),
+        }
+    }
+
+    /// Load into `runtime`. Every subsystem needs its section.
+    pub fn restore(&self, runtime: &mut Runtime) -> Result<()> {
+        let targets: [(&str, &mut dyn Snapshot); 6] = [
+            ("ram", &mut runtime.ram),
+            ("vram", &mut runtime.vram),
+            ("aram", &mut runtime.aram),
+            ("vi", &mut runtime.video),
+            ("ai", &mut runtime.audio),
+            ("interrupts", &mut runtime.interrupts),
+        ];
+        for (name, target) in targets {
+            let data = self
+                .section(name)
+                .with_context(|| format!("save state has no '{name}' section"))?;
+            load_section(target, data).with_context(|| format!("loading section '{name}'"))?;
+        }
+        Ok(())
+    }
+
+    pub fn section(&self, name: &str) -> Option<&[u8]> {
+        self.sections
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn section_names(&self) -> impl Iterator<Item = &str> {
+        self.sections.iter().map(|(n, _)| n.as_str())
+    }
+
+    /// `GCSS`, version, then `(name, length, data)` per section.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        MAGIC.iter().for_each(|&b| w.u8(b));
+        w.u32(VERSION);
+        w.u32(self.sections.len() as u32);
+        for (name, data) in &self.sections {
+            w.bytes(name.as_bytes());
+            w.bytes(data);
+        }
+        w.into_inner()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut r = StateReader::new(data);
+        ensure!(r.array::<4>()? == *MAGIC, "not a save state");
+        let version = r.u32()?;
+        ensure!(
+            version == VERSION,
+            "save state version {version} (expected {VERSION})"
+        );
+        let count = r.u32()?;
+        let mut sections = Vec::with_capacity(count.min(64) as usize);
+        for _ in 0..count {
+            let name = String::from_utf8_lossy(r.bytes()?).into_owned();
+            sections.push((name, r.bytes()?.to_vec()));
+        }
+        Ok(Self { sections })
+    }
+
+    /// Capture `runtime`, restore into a fresh runtime with the same config,
+    /// capture that, and require byte-identical blobs. The error names every
+    /// section whose state didn't survive.
+    pub fn verify_roundtrip(runtime: &Runtime) -> Result<()> {
+        let first = Self::capture(runtime);
+        let mut fresh = Runtime::with_config(runtime.config().clone())?;
+        first.restore(&mut fresh)?;
+        let second = Self::capture(&fresh);
+
+        let lost: Vec<&str> = first
+            .sections
+            .iter()
+            .zip(&second.sections)
+            .filter(|((_, a), (_, b))| a != b)
+            .map(|((name, _), _)| name.as_str())
+            .collect();
+        ensure!(
+            lost.is_empty(),
+            "save state round trip changed: {}",
+            lost.join(", ")
+        );
+        ensure!(
+            first.to_bytes() == second.to_bytes(),
+            "save state blobs differ"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A subsystem whose `load` forgets `b`.
+    #[derive(Default)]
+    struct Forgetful {
+        a: u32,
+        b: u32,
+    }
+
+    impl Snapshot for Forgetful {
+        fn save(&self, w: &mut StateWriter) {
+            w.u32(self.a);
+            w.u32(self.b);
+        }
+
+        fn load(&mut self, r: &mut StateReader) -> Result<()> {
+            self.a = r.u32()?;
+            Ok(())
+        }
+    }
+
+    /// One whose `save` forgets `b` but whose `load` expects it.
+    #[derive(Default)]
+    struct Unsaved {
+        a: u32,
+        b: u32,
+    }
+
+    impl Snapshot for Unsaved {
+        fn save(&self, w: &mut StateWriter) {
+            w.u32(self.a);
+        }
+
+        fn load(&mut self, r: &mut StateReader) -> Result<()> {
+            self.a = r.u32()?;
+            self.b = r.u32()?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn omitted_fields_fail_the_roundtrip() {
+        let original = Forgetful { a: 1, b: 2 };
+        let err = verify_section("forgetful", &original, &mut Forgetful::default()).unwrap_err();
+        assert!(format!("{err:#}").contains("left unread"), "{err:#}");
+
+        let original = Unsaved { a: 1, b: 2 };
+        let err = verify_section("unsaved", &original, &mut Unsaved::default()).unwrap_err();
+        assert!(format!("{err:#}").contains("section 'unsaved'"), "{err:#}");
+    }
+
+    #[test]
+    fn runtime_roundtrips() {
+        let mut runtime = Runtime::with_config(Default::default()).unwrap();
+        runtime
+            .ram_mut()
+            .write_u32(0x8000_1234, 0xCAFE_F00D)
+            .unwrap();
+        runtime.aram_mut().write_u16(0x10, 0xBEEF).unwrap();
+        runtime.video_mut().set_next_frame_buffer(0x8030_0000);
+        runtime.audio_mut().init_dma(0x8040_0000, 0x400);
+        runtime.audio_mut().start_dma();
+        runtime.register_ai_dma_callback(0x8000_5000);
+        SaveState::verify_roundtrip(&runtime).unwrap();
+
+        let state = SaveState::capture(&runtime);
+        let reloaded = SaveState::from_bytes(&state.to_bytes()).unwrap();
+        assert_eq!(reloaded, state);
+        assert_eq!(
+            reloaded.section_names().collect::<Vec<_>>(),
+            ["ram", "vram", "aram", "vi", "ai", "interrupts"]
+        );
+    }
+}
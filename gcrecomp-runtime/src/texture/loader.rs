@@ -1,11 +1,25 @@
 // Texture loading
 use crate::texture::cache::TextureCache;
 use crate::texture::formats::GameCubeTextureFormat;
+use crate::texture::replacement::{content_hash, ReplacementRegistry};
 use anyhow::Result;
 use image::RgbaImage;
 
 pub struct TextureLoader {
     cache: TextureCache,
+    replacements: ReplacementRegistry,
+}
+
+/// A texture ready to upload: the decoded original or its pack replacement.
+#[derive(Debug, Clone)]
+pub struct LoadedTexture {
+    pub image: RgbaImage,
+    /// Content hash of the source data (the pack file name).
+    pub hash: u64,
+    pub replaced: bool,
+    /// `image` size over the original size. Normalized UVs need no change;
+    /// coordinates computed in original texels must be multiplied by this.
+    pub uv_scale: (f32, f32),
 }
 
 impl Default for TextureLoader {
@@ -18,7 +32,47 @@ impl TextureLoader {
     pub fn new() -> Self {
         Self {
             cache: TextureCache::new(),
+            replacements: ReplacementRegistry::new(),
+        }
+    }
+
+    /// Use `replacements` (an HD texture pack) for subsequent `load`s.
+    pub fn set_replacements(&mut self, replacements: ReplacementRegistry) {
+        self.replacements = replacements;
+    }
+
+    pub fn replacements_mut(&mut self) -> &mut ReplacementRegistry {
+        &mut self.replacements
+    }
+
+    /// Load a texture for upload, substituting its pack replacement when one
+    /// matches the content hash of `data`.
+    pub fn load(
+        &mut self,
+        data: &[u8],
+        format: GameCubeTextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<LoadedTexture> {
+        let hash = content_hash(data);
+        if let Some(image) = self.replacements.get(hash) {
+            let uv_scale = (
+                image.width() as f32 / width.max(1) as f32,
+                image.height() as f32 / height.max(1) as f32,
+            );
+            return Ok(LoadedTexture {
+                image: image.clone(),
+                hash,
+                replaced: true,
+                uv_scale,
+            });
         }
+        Ok(LoadedTexture {
+            image: self.load_texture(data, format, width, height)?,
+            hash,
+            replaced: false,
+            uv_scale: (1.0, 1.0),
+        })
     }
 
     pub fn load_texture(
@@ -29,7 +83,7 @@ impl TextureLoader {
         height: u32,
     ) -> Result<RgbaImage> {
         // Check cache first
-        let cache_key = format!("{:?}_{}_{}", format, width, height);
+        let cache_key = cache_key(data, format, width, height);
         if let Some(cached) = self.cache.get(&cache_key) {
             return Ok(cached.clone());
         }
@@ -70,6 +124,84 @@ impl TextureLoader {
 
         Ok(mipmaps)
     }
+
+    /// Decode the base level and box-filter a full mip chain down to 1x1
+    /// (for upscaled / replacement textures that don't carry their own).
+    /// Levels are cached alongside the base image.
+    pub fn load_texture_mipmapped(
+        &mut self,
+        data: &[u8],
+        format: GameCubeTextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<RgbaImage>> {
+        let cache_key = cache_key(data, format, width, height);
+        let base = self.load_texture(data, format, width, height)?;
+        let levels = mip_level_count(width, height);
+
+        let mut chain = vec![base];
+        for level in 1..levels {
+            let key = format!("{}_mip{}", cache_key, level);
+            let image = match self.cache.get(&key) {
+                Some(cached) => cached.clone(),
+                None => {
+                    let image = downsample(&chain[level as usize - 1]);
+                    self.cache.insert(key, image.clone());
+                    image
+                }
+            };
+            chain.push(image);
+        }
+        Ok(chain)
+    }
+}
+
+/// Cache key: different textures can share a format and size, so the content
+/// hash is part of it.
+fn cache_key(data: &[u8], format: GameCubeTextureFormat, width: u32, height: u32) -> String {
+    format!(
+        "{:?}_{}_{}_{:016x}",
+        format,
+        width,
+        height,
+        content_hash(data)
+    )
+}
+
+/// Number of levels in a full mip chain for a `width` x `height` base.
+pub fn mip_level_count(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+/// Full mip chain from `base` down to 1x1, `base` first.
+pub fn generate_mipmaps(base: &RgbaImage) -> Vec<RgbaImage> {
+    let levels = mip_level_count(base.width(), base.height());
+    let mut chain = vec![base.clone()];
+    for _ in 1..levels {
+        let next = downsample(chain.last().unwrap());
+        chain.push(next);
+    }
+    chain
+}
+
+/// Half-size level with a 2x2 box filter. An odd edge reuses its last
+/// row/column, and a 1-pixel dimension stays 1.
+fn downsample(src: &RgbaImage) -> RgbaImage {
+    let (w, h) = ((src.width() / 2).max(1), (src.height() / 2).max(1));
+    RgbaImage::from_fn(w, h, |x, y| {
+        let (x0, y0) = (x * 2, y * 2);
+        let (x1, y1) = (
+            (x0 + 1).min(src.width() - 1),
+            (y0 + 1).min(src.height() - 1),
+        );
+        let mut sum = [0u32; 4];
+        for (sx, sy) in [(x0, y0), (x1, y0), (x0, y1), (x1, y1)] {
+            for (c, v) in src.get_pixel(sx, sy).0.iter().enumerate() {
+                sum[c] += *v as u32;
+            }
+        }
+        image::Rgba(sum.map(|s| ((s + 2) / 4) as u8))
+    })
 }
 
 impl GameCubeTextureFormat {
@@ -86,3 +218,61 @@ impl GameCubeTextureFormat {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_by_four_builds_three_level_chain() {
+        // Left half black, right half white
+        let base = RgbaImage::from_fn(4, 4, |x, _| {
+            let v = if x < 2 { 0 } else { 255 };
+            image::Rgba([v, v, v, 255])
+        });
+        let chain = generate_mipmaps(&base);
+        let dims: Vec<(u32, u32)> = chain.iter().map(|m| m.dimensions()).collect();
+        assert_eq!(dims, vec![(4, 4), (2, 2), (1, 1)]);
+        assert_eq!(chain[1].get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(chain[1].get_pixel(1, 0).0, [255, 255, 255, 255]);
+        assert_eq!(chain[2].get_pixel(0, 0).0, [128, 128, 128, 255]);
+
+        assert_eq!(mip_level_count(8, 2), 4);
+        let wide: Vec<(u32, u32)> = generate_mipmaps(&RgbaImage::new(8, 2))
+            .iter()
+            .map(|m| m.dimensions())
+            .collect();
+        assert_eq!(wide, vec![(8, 2), (4, 1), (2, 1), (1, 1)]);
+    }
+
+    #[test]
+    fn registered_replacement_wins_over_decoded_source() {
+        // 4x4 RGBA8 source in GX tile layout (AR block then GB block), all zero
+        let source = vec![0u8; 64];
+        let mut loader = TextureLoader::new();
+        let original = loader
+            .load(&source, GameCubeTextureFormat::RGBA8, 4, 4)
+            .unwrap();
+        assert!(!original.replaced);
+        assert_eq!(original.image.dimensions(), (4, 4));
+
+        let hd = RgbaImage::from_pixel(8, 8, image::Rgba([10, 20, 30, 255]));
+        loader
+            .replacements_mut()
+            .register(content_hash(&source), hd.clone());
+        let loaded = loader
+            .load(&source, GameCubeTextureFormat::RGBA8, 4, 4)
+            .unwrap();
+        assert!(loaded.replaced);
+        assert_eq!(loaded.image, hd);
+        assert_eq!(loaded.uv_scale, (2.0, 2.0));
+
+        // Other content of the same format and size is untouched
+        let other = vec![0xFFu8; 64];
+        let loaded = loader
+            .load(&other, GameCubeTextureFormat::RGBA8, 4, 4)
+            .unwrap();
+        assert!(!loaded.replaced);
+        assert_ne!(loaded.image, hd);
+    }
+}
@@ -2,8 +2,10 @@ pub mod cache;
 pub mod formats;
 pub mod loader;
 pub mod mapper;
+pub mod replacement;
 pub mod upscaler;
 
 pub use cache::TextureCache;
 pub use formats::GameCubeTextureFormat;
-pub use loader::TextureLoader;
+pub use loader::{generate_mipmaps, LoadedTexture, TextureLoader};
+pub use replacement::ReplacementRegistry;
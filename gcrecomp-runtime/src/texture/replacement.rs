@@ -0,0 +1,114 @@
+// Texture replacement (HD texture packs)
+//
+// A pack is a directory of PNGs, each named after the content hash of the
+// game texture it replaces: `<16 hex digits>.png`, e.g.
+// `texpack/9a3f00c2d41b7e55.png`. Files are only decoded the first time
+// their texture is loaded.
+
+use anyhow::{Context, Result};
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Content hash of a texture's encoded source bytes (64-bit FNV-1a).
+pub fn content_hash(data: &[u8]) -> u64 {
+    const OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01B3;
+    data.iter()
+        .fold(OFFSET, |h, &b| (h ^ b as u64).wrapping_mul(PRIME))
+}
+
+/// File name a pack uses for the texture with `hash`.
+pub fn replacement_file_name(hash: u64) -> String {
+    format!("{:016x}.png", hash)
+}
+
+enum Replacement {
+    /// Not decoded yet.
+    File(PathBuf),
+    Image(RgbaImage),
+}
+
+/// Replacement images keyed by content hash.
+#[derive(Default)]
+pub struct ReplacementRegistry {
+    entries: HashMap<u64, Replacement>,
+}
+
+impl ReplacementRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index every `<hash>.png` in `dir`. Other files are ignored.
+    pub fn load_dir(dir: &Path) -> Result<Self> {
+        let mut registry = Self::new();
+        let entries = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to read texture pack {}", dir.display()))?;
+        for entry in entries {
+            let path = entry?.path();
+            let is_png = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+            let hash = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .filter(|s| s.len() == 16)
+                .and_then(|s| u64::from_str_radix(s, 16).ok());
+            match hash {
+                Some(hash) if is_png => registry.register_file(hash, path),
+                _ => log::debug!("Texture pack: skipping {}", path.display()),
+            }
+        }
+        log::info!(
+            "Texture pack {}: {} replacements",
+            dir.display(),
+            registry.len()
+        );
+        Ok(registry)
+    }
+
+    /// Replace the texture with `hash` by an already-decoded image.
+    pub fn register(&mut self, hash: u64, image: RgbaImage) {
+        self.entries.insert(hash, Replacement::Image(image));
+    }
+
+    /// Replace the texture with `hash` by a PNG, decoded on first use.
+    pub fn register_file(&mut self, hash: u64, path: PathBuf) {
+        self.entries.insert(hash, Replacement::File(path));
+    }
+
+    pub fn contains(&self, hash: u64) -> bool {
+        self.entries.contains_key(&hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The replacement for `hash`, decoding it if needed. A file that fails to
+    /// decode is dropped (with a warning) so the original is used from then on.
+    pub fn get(&mut self, hash: u64) -> Option<&RgbaImage> {
+        if let Some(Replacement::File(path)) = self.entries.get(&hash) {
+            match image::open(path) {
+                Ok(img) => {
+                    self.entries
+                        .insert(hash, Replacement::Image(img.to_rgba8()));
+                }
+                Err(e) => {
+                    log::warn!("Texture pack: failed to load {}: {}", path.display(), e);
+                    self.entries.remove(&hash);
+                    return None;
+                }
+            }
+        }
+        match self.entries.get(&hash) {
+            Some(Replacement::Image(img)) => Some(img),
+            _ => None,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use gcrecomp_core::runtime::sdk::region::Region;
+
 /// GameCube video mode definitions.
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -80,6 +82,14 @@ impl VideoMode {
         }
     }
 
+    /// The mode a console of `region` boots in.
+    pub fn for_region(region: Region) -> Self {
+        match region {
+            Region::Pal => Self::pal_576i(),
+            Region::NtscU | Region::NtscJ => Self::ntsc_480i(),
+        }
+    }
+
     /// Target frame rate based on timing standard.
     pub fn target_fps(&self) -> f64 {
         match self.timing {
@@ -93,3 +103,18 @@ impl VideoMode {
         (1_000_000_000.0 / self.target_fps()) as u64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_picks_the_field_rate() {
+        assert_eq!(VideoMode::for_region(Region::Pal).target_fps(), 50.0);
+        assert_eq!(VideoMode::for_region(Region::Pal).timing, VideoTiming::Pal);
+        assert_eq!(
+            VideoMode::for_region(Region::NtscU).timing,
+            VideoTiming::Ntsc
+        );
+    }
+}
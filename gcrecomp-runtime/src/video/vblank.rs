@@ -1,4 +1,6 @@
 /// VBlank timing: tracks frame timing and fires retrace callbacks.
+use crate::runtime::savestate::{Snapshot, StateReader, StateWriter};
+use anyhow::Result;
 use std::time::Instant;
 
 pub struct VBlankTimer {
@@ -56,3 +58,18 @@ impl VBlankTimer {
         self.retrace_count
     }
 }
+
+impl Snapshot for VBlankTimer {
+    // `last_retrace` is host time and restarts from the load.
+    fn save(&self, w: &mut StateWriter) {
+        w.u32(self.retrace_count);
+        w.u64(self.target_frame_ns);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        self.retrace_count = r.u32()?;
+        self.target_frame_ns = r.u64()?;
+        self.last_retrace = Instant::now();
+        Ok(())
+    }
+}
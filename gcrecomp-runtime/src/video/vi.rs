@@ -1,6 +1,8 @@
 /// Video Interface (VI) — manages video modes, frame buffers, and retrace callbacks.
-use super::modes::VideoMode;
+use super::modes::{VideoMode, VideoTiming, XfbMode};
 use super::vblank::VBlankTimer;
+use crate::runtime::savestate::{Snapshot, StateReader, StateWriter};
+use anyhow::Result;
 use log::info;
 
 pub struct VideoInterface {
@@ -122,3 +124,72 @@ impl Default for VideoInterface {
         Self::new()
     }
 }
+
+impl Snapshot for VideoInterface {
+    fn save(&self, w: &mut StateWriter) {
+        let m = &self.current_mode;
+        for v in [
+            m.fb_width,
+            m.efb_height,
+            m.xfb_height,
+            m.vi_x_origin,
+            m.vi_y_origin,
+            m.vi_width,
+            m.vi_height,
+        ] {
+            w.u16(v);
+        }
+        w.bool(m.xfb_mode == XfbMode::Double);
+        w.bool(m.field_rendering);
+        w.bool(m.anti_aliasing);
+        w.u8(match m.timing {
+            VideoTiming::Ntsc => 0,
+            VideoTiming::Pal => 1,
+            VideoTiming::Mpal => 2,
+        });
+        w.u32(self.next_xfb_addr);
+        w.u32(self.current_xfb_addr);
+        w.bool(self.flush_pending);
+        w.bool(self.black);
+        w.bool(self.enabled);
+        w.opt_u32(self.pre_retrace_callback);
+        w.opt_u32(self.post_retrace_callback);
+        self.vblank.save(w);
+    }
+
+    fn load(&mut self, r: &mut StateReader) -> Result<()> {
+        let m = &mut self.current_mode;
+        for v in [
+            &mut m.fb_width,
+            &mut m.efb_height,
+            &mut m.xfb_height,
+            &mut m.vi_x_origin,
+            &mut m.vi_y_origin,
+            &mut m.vi_width,
+            &mut m.vi_height,
+        ] {
+            *v = r.u16()?;
+        }
+        m.xfb_mode = if r.bool()? {
+            XfbMode::Double
+        } else {
+            XfbMode::Single
+        };
+        m.field_rendering = r.bool()?;
+        m.anti_aliasing = r.bool()?;
+        m.timing = match r.u8()? {
+            0 => VideoTiming::Ntsc,
+            1 => VideoTiming::Pal,
+            2 => VideoTiming::Mpal,
+            t => anyhow::bail!("unknown video timing {}", t),
+        };
+        self.next_xfb_addr = r.u32()?;
+        self.current_xfb_addr = r.u32()?;
+        self.flush_pending = r.bool()?;
+        self.black = r.bool()?;
+        self.enabled = r.bool()?;
+        self.pre_retrace_callback = r.opt_u32()?;
+        self.post_retrace_callback = r.opt_u32()?;
+        self.vblank.load(r)
+    }
+}
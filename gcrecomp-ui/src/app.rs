@@ -1,10 +1,17 @@
 // Menu application state — renders Lua-defined screens via Iced
 use crate::config::GameConfig;
+use crate::hex_viewer::{HexViewerMessage, HexViewerState};
+use crate::inspector::{InspectorMessage, InspectorState};
+use crate::options::{OptionsMessage, OptionsState};
+use crate::trace_viewer::{TraceViewerMessage, TraceViewerState};
+use gcrecomp_core::runtime::memory::MemoryManager;
 use gcrecomp_lua::bindings::ui::{LuaScreenDef, LuaWidget, LUA_SCREENS, NAV_STACK};
+use gcrecomp_runtime::runtime::Runtime;
 use iced::{
     widget::{Button, Checkbox, Column, Container, PickList, Row, Slider, Space, Text, TextInput},
-    Application, Command, Element, Length, Theme,
+    Application, Command, Element, Length, Subscription, Theme,
 };
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -18,25 +25,62 @@ pub enum Message {
     LuaCheckboxToggled(String, String, bool),
     LuaPickListSelected(String, String, String),
     LuaTextInputChanged(String, String, String),
+    OpenOptions,
+    CloseOptions,
+    Options(OptionsMessage),
+    OpenInspector,
+    CloseInspector,
+    Inspector(InspectorMessage),
+    OpenTraceViewer,
+    CloseTraceViewer,
+    TraceViewer(TraceViewerMessage),
+    OpenHexViewer,
+    CloseHexViewer,
+    HexViewer(HexViewerMessage),
 }
 
 pub struct App {
     menu_visible: bool,
     config: GameConfig,
+    /// The options screen, while open.
+    options: Option<OptionsState>,
+    /// The game's runtime, when the menu runs inside it; options apply to it live.
+    runtime: Option<Runtime>,
+    /// The memory search screen, while open.
+    inspector: Option<InspectorState>,
+    /// The game's memory, which the inspector searches.
+    memory: Option<Arc<Mutex<MemoryManager>>>,
+    /// The trace viewer, while open.
+    trace_viewer: Option<TraceViewerState>,
+    /// The memory hex dump, while open; reads `memory`.
+    hex_viewer: Option<HexViewerState>,
+}
+
+/// What the menu is attached to when it runs inside a game.
+#[derive(Default)]
+pub struct AppFlags {
+    pub runtime: Option<Runtime>,
+    pub memory: Option<Arc<Mutex<MemoryManager>>>,
 }
 
 impl Application for App {
     type Message = Message;
     type Theme = Theme;
     type Executor = iced::executor::Default;
-    type Flags = ();
+    type Flags = AppFlags;
 
-    fn new(_flags: ()) -> (Self, Command<Message>) {
+    fn new(flags: AppFlags) -> (Self, Command<Message>) {
         let config = GameConfig::load().unwrap_or_default();
         (
             Self {
                 menu_visible: false,
                 config,
+                options: None,
+                runtime: flags.runtime,
+                inspector: None,
+                memory: flags.memory,
+                trace_viewer: None,
+                hex_viewer: None,
             },
             Command::none(),
         )
@@ -53,6 +97,10 @@ impl Application for App {
             }
             Message::CloseMenu => {
                 self.menu_visible = false;
+                self.options = None;
+                self.inspector = None;
+                self.trace_viewer = None;
+                self.hex_viewer = None;
                 if let Ok(mut stack) = NAV_STACK.lock() {
                     stack.clear();
                 }
@@ -73,6 +121,59 @@ impl Application for App {
                     stack.pop();
                 }
             }
+            Message::OpenOptions => {
+                self.options = Some(OptionsState::open());
+            }
+            Message::CloseOptions => {
+                self.options = None;
+            }
+            Message::Options(message) => {
+                if let Some(options) = self.options.as_mut() {
+                    options.update(message, self.runtime.as_mut());
+                }
+            }
+            Message::OpenInspector => {
+                self.inspector = Some(InspectorState::new());
+            }
+            Message::CloseInspector => {
+                self.inspector = None;
+            }
+            Message::Inspector(message) => {
+                if let Some(inspector) = self.inspector.as_mut() {
+                    let memory = self
+                        .memory
+                        .as_ref()
+                        .map(|m| m.lock().unwrap_or_else(|e| e.into_inner()));
+                    inspector.update(message, memory.as_deref());
+                }
+            }
+            Message::OpenTraceViewer => {
+                self.trace_viewer = Some(TraceViewerState::new());
+            }
+            Message::CloseTraceViewer => {
+                self.trace_viewer = None;
+            }
+            Message::TraceViewer(message) => {
+                if let Some(viewer) = self.trace_viewer.as_mut() {
+                    viewer.update(message);
+                }
+            }
+            Message::OpenHexViewer => {
+                self.hex_viewer = Some(HexViewerState::new());
+                return self.update(Message::HexViewer(HexViewerMessage::Tick));
+            }
+            Message::CloseHexViewer => {
+                self.hex_viewer = None;
+            }
+            Message::HexViewer(message) => {
+                if let Some(viewer) = self.hex_viewer.as_mut() {
+                    let memory = self
+                        .memory
+                        .as_ref()
+                        .map(|m| m.lock().unwrap_or_else(|e| e.into_inner()));
+                    viewer.update(message, memory.as_deref());
+                }
+            }
             Message::LuaWidgetClicked(_screen_id, _widget_id) => {
                 // Callback invocation handled by the game loop
             }
@@ -136,7 +237,31 @@ impl Application for App {
             .ok()
             .and_then(|stack| stack.last().cloned());
 
-        let content: Element<Message> = if let Some(screen_id) = current_screen_id {
+        let content: Element<Message> = if let Some(options) = &self.options {
+            Column::new()
+                .spacing(20)
+                .push(options.view().map(Message::Options))
+                .push(Button::new(Text::new("Back")).on_press(Message::CloseOptions))
+                .into()
+        } else if let Some(inspector) = &self.inspector {
+            Column::new()
+                .spacing(20)
+                .push(inspector.view().map(Message::Inspector))
+                .push(Button::new(Text::new("Back")).on_press(Message::CloseInspector))
+                .into()
+        } else if let Some(viewer) = &self.trace_viewer {
+            Column::new()
+                .spacing(20)
+                .push(viewer.view().map(Message::TraceViewer))
+                .push(Button::new(Text::new("Back")).on_press(Message::CloseTraceViewer))
+                .into()
+        } else if let Some(viewer) = &self.hex_viewer {
+            Column::new()
+                .spacing(20)
+                .push(viewer.view().map(Message::HexViewer))
+                .push(Button::new(Text::new("Back")).on_press(Message::CloseHexViewer))
+                .into()
+        } else if let Some(screen_id) = current_screen_id {
             // Render a Lua-defined screen
             let screens = LUA_SCREENS.lock().ok();
             if let Some(ref screens) = screens {
@@ -164,6 +289,15 @@ impl Application for App {
             .into()
     }
 
+    fn subscription(&self) -> Subscription<Message> {
+        if self.hex_viewer.is_some() {
+            iced::time::every(crate::hex_viewer::REFRESH)
+                .map(|_| Message::HexViewer(HexViewerMessage::Tick))
+        } else {
+            Subscription::none()
+        }
+    }
+
     fn theme(&self) -> Theme {
         Theme::Dark
     }
@@ -187,6 +321,30 @@ fn render_main_menu() -> Element<'static, Message> {
         }
     }
 
+    menu = menu.push(
+        Button::new(Text::new("Options"))
+            .on_press(Message::OpenOptions)
+            .width(Length::Fixed(250.0)),
+    );
+
+    menu = menu.push(
+        Button::new(Text::new("Memory Search"))
+            .on_press(Message::OpenInspector)
+            .width(Length::Fixed(250.0)),
+    );
+
+    menu = menu.push(
+        Button::new(Text::new("Trace Viewer"))
+            .on_press(Message::OpenTraceViewer)
+            .width(Length::Fixed(250.0)),
+    );
+
+    menu = menu.push(
+        Button::new(Text::new("Hex Viewer"))
+            .on_press(Message::OpenHexViewer)
+            .width(Length::Fixed(250.0)),
+    );
+
     menu = menu.push(Space::with_height(Length::Fixed(20.0))).push(
         Button::new(Text::new("Close Menu (ESC)"))
             .on_press(Message::CloseMenu)
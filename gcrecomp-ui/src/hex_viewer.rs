@@ -0,0 +1,306 @@
+// Hex viewer: a live dump of the game's memory
+//
+// Shows `length` bytes from `base` as a classic hex + ASCII dump, sixteen to
+// a line, read from the running game's `MemoryManager`. `App::subscription`
+// sends a `Tick` every `REFRESH` while the screen is open; each one re-reads
+// the window and highlights the bytes that differ from the previous read, so
+// a value the game is updating stands out. Moving the window (a line, a page
+// or the go-to box) starts the comparison over. The window is kept inside
+// MEM1.
+
+use gcrecomp_core::runtime::memory::MemoryManager;
+use iced::{
+    widget::{Button, Column, Row, Text, TextInput},
+    Color, Element, Font, Length,
+};
+use std::time::Duration;
+
+/// How often the dump is re-read.
+pub const REFRESH: Duration = Duration::from_millis(250);
+
+const BYTES_PER_LINE: u32 = 16;
+
+/// Cached MEM1, the range the window stays in.
+const MEM1_BASE: u32 = 0x8000_0000;
+const MEM1_END: u32 = 0x8180_0000;
+
+const DEFAULT_LENGTH: u32 = 256;
+const MAX_LENGTH: u32 = 4096;
+
+const CHANGED: Color = Color::from_rgb(1.0, 0.4, 0.3);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HexViewerMessage {
+    SetAddress(String),
+    /// Jump to the address box.
+    GoTo,
+    /// Bytes shown, rounded up to whole lines.
+    SetLength(String),
+    LineUp,
+    LineDown,
+    PageUp,
+    PageDown,
+    /// Re-read the window.
+    Tick,
+}
+
+pub struct HexViewerState {
+    base: u32,
+    length: u32,
+    address: String,
+    length_text: String,
+    /// The window as of the last read.
+    bytes: Vec<u8>,
+    /// Per byte of `bytes`: whether it differs from the read before.
+    changed: Vec<bool>,
+    error: Option<String>,
+}
+
+impl Default for HexViewerState {
+    fn default() -> Self {
+        Self {
+            base: MEM1_BASE,
+            length: DEFAULT_LENGTH,
+            address: format!("{MEM1_BASE:08X}"),
+            length_text: DEFAULT_LENGTH.to_string(),
+            bytes: Vec::new(),
+            changed: Vec::new(),
+            error: None,
+        }
+    }
+}
+
+impl HexViewerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn base(&self) -> u32 {
+        self.base
+    }
+
+    pub fn length(&self) -> u32 {
+        self.length
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn changed(&self) -> &[bool] {
+        &self.changed
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Apply `message`, reading from `memory`, the running game's.
+    pub fn update(&mut self, message: HexViewerMessage, memory: Option<&MemoryManager>) {
+        let page = self.length as i64;
+        match message {
+            HexViewerMessage::SetAddress(text) => {
+                self.address = text;
+                return;
+            }
+            HexViewerMessage::SetLength(text) => {
+                let length = parse_number(&text).filter(|&length| length > 0);
+                self.length_text = text;
+                let Some(length) = length else {
+                    self.error = Some(format!("'{}' is not a length", self.length_text.trim()));
+                    return;
+                };
+                self.length = length.min(MAX_LENGTH).div_ceil(BYTES_PER_LINE) * BYTES_PER_LINE;
+                self.move_to(self.base as i64);
+            }
+            HexViewerMessage::GoTo => match parse_number(&self.address) {
+                Some(address) => self.move_to(address as i64),
+                None => {
+                    self.error = Some(format!("'{}' is not an address", self.address.trim()));
+                    return;
+                }
+            },
+            HexViewerMessage::LineUp => self.move_to(self.base as i64 - BYTES_PER_LINE as i64),
+            HexViewerMessage::LineDown => self.move_to(self.base as i64 + BYTES_PER_LINE as i64),
+            HexViewerMessage::PageUp => self.move_to(self.base as i64 - page),
+            HexViewerMessage::PageDown => self.move_to(self.base as i64 + page),
+            HexViewerMessage::Tick => {}
+        }
+        self.refresh(memory);
+    }
+
+    /// Put the window at `address`, line-aligned and clamped to MEM1.
+    fn move_to(&mut self, address: i64) {
+        let last = (MEM1_END - self.length) as i64;
+        let base = address.clamp(MEM1_BASE as i64, last) as u32;
+        let base = base - (base - MEM1_BASE) % BYTES_PER_LINE;
+        if base != self.base {
+            self.base = base;
+            self.bytes.clear();
+        }
+        self.address = format!("{:08X}", self.base);
+    }
+
+    fn refresh(&mut self, memory: Option<&MemoryManager>) {
+        let Some(memory) = memory else {
+            self.error = Some("No game is running".to_string());
+            return;
+        };
+        match memory.read_bytes(self.base, self.length as usize) {
+            Ok(bytes) => {
+                self.error = None;
+                self.changed = if bytes.len() == self.bytes.len() {
+                    bytes.iter().zip(&self.bytes).map(|(a, b)| a != b).collect()
+                } else {
+                    vec![false; bytes.len()]
+                };
+                self.bytes = bytes;
+            }
+            Err(e) => self.error = Some(format!("{e:#}")),
+        }
+    }
+
+    pub fn view(&self) -> Element<'_, HexViewerMessage> {
+        let mut col = Column::new()
+            .spacing(15)
+            .push(Text::new("Hex viewer").size(28))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new("Address", &self.address)
+                            .on_input(HexViewerMessage::SetAddress)
+                            .on_submit(HexViewerMessage::GoTo)
+                            .width(Length::Fixed(120.0)),
+                    )
+                    .push(Button::new(Text::new("Go")).on_press(HexViewerMessage::GoTo))
+                    .push(
+                        TextInput::new("Length", &self.length_text)
+                            .on_input(HexViewerMessage::SetLength)
+                            .width(Length::Fixed(80.0)),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(Button::new(Text::new("Page up")).on_press(HexViewerMessage::PageUp))
+                    .push(Button::new(Text::new("Line up")).on_press(HexViewerMessage::LineUp))
+                    .push(Button::new(Text::new("Line down")).on_press(HexViewerMessage::LineDown))
+                    .push(Button::new(Text::new("Page down")).on_press(HexViewerMessage::PageDown)),
+            );
+        if let Some(error) = &self.error {
+            col = col.push(Text::new(error.clone()));
+        }
+
+        let mut dump = Column::new().spacing(2);
+        for (line, chunk) in self.bytes.chunks(BYTES_PER_LINE as usize).enumerate() {
+            let start = line * BYTES_PER_LINE as usize;
+            let address = self.base + start as u32;
+            let mut row = Row::new()
+                .spacing(6)
+                .push(mono(format!("{address:08X}")).width(Length::Fixed(90.0)));
+            for (i, byte) in chunk.iter().enumerate() {
+                let text = mono(format!("{byte:02X}"));
+                row = row.push(if self.changed.get(start + i) == Some(&true) {
+                    text.style(CHANGED)
+                } else {
+                    text
+                });
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            dump = dump.push(row.push(mono(ascii)));
+        }
+        col.push(dump).into()
+    }
+}
+
+fn mono<'a>(text: String) -> Text<'a> {
+    Text::new(text).font(Font::MONOSPACE)
+}
+
+/// Hex, with or without `0x`; a plain decimal also works for lengths.
+fn parse_number(text: &str) -> Option<u32> {
+    let text = text.trim();
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None if text.len() == 8 => u32::from_str_radix(text, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn navigation_moves_the_base_and_clamps_to_mem1() {
+        let memory = MemoryManager::new();
+        let mut state = HexViewerState::new();
+        state.update(HexViewerMessage::Tick, Some(&memory));
+        assert_eq!(state.base(), MEM1_BASE);
+        assert_eq!(state.bytes().len(), 256);
+
+        state.update(HexViewerMessage::LineUp, Some(&memory));
+        assert_eq!(state.base(), MEM1_BASE);
+        state.update(HexViewerMessage::PageDown, Some(&memory));
+        assert_eq!(state.base(), 0x8000_0100);
+        state.update(HexViewerMessage::LineDown, Some(&memory));
+        assert_eq!(state.base(), 0x8000_0110);
+
+        state.update(
+            HexViewerMessage::SetAddress("0x80003007".into()),
+            Some(&memory),
+        );
+        state.update(HexViewerMessage::GoTo, Some(&memory));
+        assert_eq!(state.base(), 0x8000_3000);
+
+        state.update(
+            HexViewerMessage::SetAddress("0x81FFFFFF".into()),
+            Some(&memory),
+        );
+        state.update(HexViewerMessage::GoTo, Some(&memory));
+        assert_eq!(state.base(), MEM1_END - 256);
+        state.update(HexViewerMessage::PageDown, Some(&memory));
+        assert_eq!(state.base(), MEM1_END - 256);
+        assert_eq!(state.bytes().len(), 256);
+
+        state.update(HexViewerMessage::SetLength("4000".into()), Some(&memory));
+        assert_eq!(state.length(), 4000_u32.div_ceil(16) * 16);
+        assert_eq!(state.base(), MEM1_END - state.length());
+        assert_eq!(state.error(), None);
+    }
+
+    #[test]
+    fn changed_bytes_are_flagged_until_the_next_read() {
+        let mut memory = MemoryManager::new();
+        let mut state = HexViewerState::new();
+        state.update(HexViewerMessage::Tick, Some(&memory));
+        assert!(state.changed().iter().all(|&c| !c));
+
+        memory.write_u8(0x8000_0005, 0x41).unwrap();
+        state.update(HexViewerMessage::Tick, Some(&memory));
+        let changed: Vec<usize> = (0..state.changed().len())
+            .filter(|&i| state.changed()[i])
+            .collect();
+        assert_eq!(changed, vec![5]);
+        state.update(HexViewerMessage::Tick, Some(&memory));
+        assert!(state.changed().iter().all(|&c| !c));
+
+        state.update(HexViewerMessage::SetAddress("nowhere".into()), None);
+        state.update(HexViewerMessage::GoTo, None);
+        assert_eq!(state.error(), Some("'nowhere' is not an address"));
+        state.update(HexViewerMessage::Tick, None);
+        assert_eq!(state.error(), Some("No game is running"));
+        state.update(HexViewerMessage::Tick, Some(&memory));
+        assert_eq!(state.error(), None);
+    }
+}
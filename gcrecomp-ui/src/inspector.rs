@@ -0,0 +1,175 @@
+// Memory inspector: search MEM1 for a value
+//
+// A front end for `gcrecomp_core::runtime::memsearch`. The user picks a value
+// width, types the value the game shows and presses a filter; each press
+// narrows the candidate list against the game's memory as it is now. The
+// view lists the first candidates with their values from the last snapshot.
+
+use gcrecomp_core::runtime::memory::MemoryManager;
+use gcrecomp_core::runtime::memsearch::{MemorySearch, SearchFilter, ValueWidth};
+use iced::{
+    widget::{Button, Column, PickList, Row, Text, TextInput},
+    Element, Length,
+};
+
+/// Filters offered as buttons, by `SearchFilter::parse` name.
+const FILTERS: [(&str, &str); 5] = [
+    ("exact", "="),
+    ("greater", ">"),
+    ("less", "<"),
+    ("changed", "Changed"),
+    ("unchanged", "Unchanged"),
+];
+
+/// Candidates listed in the view; the rest are only counted.
+const SHOWN: usize = 50;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InspectorMessage {
+    /// Switch width; starts a new search.
+    SetWidth(ValueWidth),
+    SetValue(String),
+    /// Apply a filter by `SearchFilter::parse` name.
+    Filter(&'static str),
+    Reset,
+}
+
+#[derive(Default)]
+pub struct InspectorState {
+    search: MemorySearch,
+    /// The value box, parsed when a filter needs it.
+    value: String,
+    /// Why the last filter was rejected.
+    error: Option<String>,
+}
+
+impl InspectorState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn search(&self) -> &MemorySearch {
+        &self.search
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Apply `message` to the search over `memory`, the running game's.
+    pub fn update(&mut self, message: InspectorMessage, memory: Option<&MemoryManager>) {
+        self.error = self.apply(message, memory).err();
+    }
+
+    fn apply(
+        &mut self,
+        message: InspectorMessage,
+        memory: Option<&MemoryManager>,
+    ) -> Result<(), String> {
+        let memory = || memory.ok_or_else(|| "No game is running".to_string());
+        match message {
+            InspectorMessage::SetWidth(width) => match memory() {
+                Ok(memory) => self.search.set_width(width, memory),
+                Err(_) => self.search = MemorySearch::new(width),
+            },
+            InspectorMessage::SetValue(value) => self.value = value,
+            InspectorMessage::Filter(kind) => {
+                let value = match self.value.trim() {
+                    "" => None,
+                    text => Some(
+                        text.parse::<f64>()
+                            .map_err(|_| format!("'{text}' is not a number"))?,
+                    ),
+                };
+                let filter = SearchFilter::parse(kind, value)?;
+                self.search.filter(memory()?, filter);
+            }
+            InspectorMessage::Reset => self.search.reset(memory()?),
+        }
+        Ok(())
+    }
+
+    pub fn view(&self) -> Element<'_, InspectorMessage> {
+        let mut filters = Row::new().spacing(10);
+        for (kind, label) in FILTERS {
+            filters = filters
+                .push(Button::new(Text::new(label)).on_press(InspectorMessage::Filter(kind)));
+        }
+        filters = filters.push(Button::new(Text::new("Reset")).on_press(InspectorMessage::Reset));
+
+        let mut col = Column::new()
+            .spacing(15)
+            .push(Text::new("Memory search").size(28))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        PickList::new(
+                            ValueWidth::ALL.to_vec(),
+                            Some(self.search.width()),
+                            InspectorMessage::SetWidth,
+                        )
+                        .width(Length::Fixed(100.0)),
+                    )
+                    .push(
+                        TextInput::new("Value", &self.value)
+                            .on_input(InspectorMessage::SetValue)
+                            .width(Length::Fixed(200.0)),
+                    ),
+            )
+            .push(filters)
+            .push(Text::new(format!("{} candidates", self.search.count())));
+        for &address in self.search.candidates().iter().take(SHOWN) {
+            let value = self
+                .search
+                .value_at(address)
+                .map_or_else(|| "?".to_string(), |v| v.to_string());
+            col = col.push(Text::new(format!("{address:08X}  {value}")));
+        }
+        if let Some(error) = &self.error {
+            col = col.push(Text::new(error.clone()));
+        }
+        col.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_narrow_the_candidates() {
+        let mut memory = MemoryManager::new();
+        memory.write_u16(0x8000_2000, 480).unwrap();
+        memory.write_u16(0x8030_0000, 480).unwrap();
+        let mut state = InspectorState::new();
+
+        state.update(InspectorMessage::SetWidth(ValueWidth::U16), Some(&memory));
+        state.update(InspectorMessage::SetValue("480".into()), Some(&memory));
+        state.update(InspectorMessage::Filter("exact"), Some(&memory));
+        assert_eq!(state.search().candidates(), &[0x8000_2000, 0x8030_0000]);
+
+        memory.write_u16(0x8030_0000, 479).unwrap();
+        state.update(InspectorMessage::Filter("changed"), Some(&memory));
+        assert_eq!(state.search().candidates(), &[0x8030_0000]);
+        assert_eq!(state.search().value_at(0x8030_0000), Some(479.0));
+        assert_eq!(state.error(), None);
+    }
+
+    #[test]
+    fn bad_input_and_no_game_set_an_error() {
+        let memory = MemoryManager::new();
+        let mut state = InspectorState::new();
+        state.update(InspectorMessage::SetValue("lots".into()), None);
+        state.update(InspectorMessage::Filter("exact"), Some(&memory));
+        assert_eq!(state.error(), Some("'lots' is not a number"));
+
+        state.update(InspectorMessage::SetValue(String::new()), None);
+        state.update(InspectorMessage::Filter("greater"), Some(&memory));
+        assert_eq!(state.error(), Some("'greater' needs a value"));
+
+        state.update(InspectorMessage::Filter("changed"), None);
+        assert_eq!(state.error(), Some("No game is running"));
+        assert_eq!(state.search().count(), 0);
+    }
+}
@@ -1,4 +1,8 @@
 pub mod app;
 pub mod config;
+pub mod hex_viewer;
+pub mod inspector;
 pub mod integration;
+pub mod options;
+pub mod trace_viewer;
 pub mod ui;
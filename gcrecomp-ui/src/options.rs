@@ -0,0 +1,310 @@
+// Options screen bound to the real settings
+//
+// `OptionsState` holds the `Config` being edited. Each control sends an
+// `OptionsMessage`; `update` validates it, applies it to the live `Runtime`
+// when there is one, and saves the config file, so a change takes effect
+// immediately and survives a restart. The view reads everything back from the
+// state, so it shows the current values whenever it is opened. The opt level
+// only matters to the recompiler and applies on the next rebuild.
+
+use gcrecomp_core::config::{Config, PRESENT_MODES, RESOLUTION_SCALE_RANGE};
+use gcrecomp_core::recompiler::optimizer::OptLevel;
+use gcrecomp_runtime::input::profiles::{list_profiles, profile_dir};
+use gcrecomp_runtime::input::ControllerProfile;
+use gcrecomp_runtime::runtime::Runtime;
+use iced::{
+    widget::{Column, PickList, Row, Slider, Text},
+    Element, Length,
+};
+use std::path::PathBuf;
+
+/// Values `[recompiler] opt_level` accepts.
+const OPT_LEVELS: [&str; 3] = ["none", "basic", "aggressive"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionsMessage {
+    SetResolutionScale(u32),
+    SetPresentMode(String),
+    /// Master volume in percent; clamped to 0-100.
+    SetVolume(f32),
+    /// A profile name from `profiles()`, or `default`.
+    SelectProfile(String),
+    SetOptLevel(String),
+}
+
+pub struct OptionsState {
+    config: Config,
+    /// File changes are saved to; `None` keeps them in memory.
+    path: Option<PathBuf>,
+    /// Where named controller profiles are loaded from.
+    profile_dir: Option<PathBuf>,
+    /// `default` followed by the profiles in `profile_dir`.
+    profiles: Vec<String>,
+    loaded_profile: Option<ControllerProfile>,
+    /// Why the last change was rejected or not saved.
+    error: Option<String>,
+    rebuild_needed: bool,
+}
+
+impl OptionsState {
+    /// Edit the user's config file (see `gcrecomp_core::config`).
+    pub fn open() -> Self {
+        let path = std::env::var_os(gcrecomp_core::config::CONFIG_PATH_ENV)
+            .map(PathBuf::from)
+            .or_else(Config::default_path);
+        let (config, error) = match Config::load() {
+            Ok(loaded) => (loaded.config, None),
+            Err(e) => (Config::default(), Some(format!("{e:#}"))),
+        };
+        let mut state = Self::new(config, path, profile_dir());
+        state.error = error;
+        state
+    }
+
+    pub fn new(config: Config, path: Option<PathBuf>, profile_dir: Option<PathBuf>) -> Self {
+        let mut profiles = vec!["default".to_string()];
+        if let Some(dir) = &profile_dir {
+            profiles.extend(list_profiles(dir));
+        }
+        Self {
+            config,
+            path,
+            profile_dir,
+            profiles,
+            loaded_profile: None,
+            error: None,
+            rebuild_needed: false,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn profiles(&self) -> &[String] {
+        &self.profiles
+    }
+
+    /// The profile the last `SelectProfile` loaded (`None` for `default`).
+    pub fn loaded_profile(&self) -> Option<&ControllerProfile> {
+        self.loaded_profile.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// The opt level changed; it applies when the game is recompiled.
+    pub fn rebuild_needed(&self) -> bool {
+        self.rebuild_needed
+    }
+
+    /// Apply `message` to the config (and `runtime`, if running), then save.
+    /// Invalid input leaves everything unchanged and sets `error`.
+    pub fn update(&mut self, message: OptionsMessage, runtime: Option<&mut Runtime>) {
+        self.error = self
+            .apply(message, runtime)
+            .and_then(|()| match &self.path {
+                Some(path) => self.config.save(path),
+                None => Ok(()),
+            })
+            .err()
+            .map(|e| format!("{e:#}"));
+    }
+
+    fn apply(
+        &mut self,
+        message: OptionsMessage,
+        runtime: Option<&mut Runtime>,
+    ) -> anyhow::Result<()> {
+        match message {
+            OptionsMessage::SetResolutionScale(scale) => {
+                anyhow::ensure!(
+                    RESOLUTION_SCALE_RANGE.contains(&scale),
+                    "Resolution scale must be {}-{}x",
+                    RESOLUTION_SCALE_RANGE.start(),
+                    RESOLUTION_SCALE_RANGE.end()
+                );
+                if let Some(runtime) = runtime {
+                    runtime.set_resolution_scale(scale)?;
+                }
+                self.config.graphics.resolution_scale = scale;
+            }
+            OptionsMessage::SetPresentMode(mode) => {
+                anyhow::ensure!(
+                    PRESENT_MODES.contains(&mode.as_str()),
+                    "Unknown present mode '{mode}'"
+                );
+                if let Some(runtime) = runtime {
+                    runtime.set_present_mode(&mode)?;
+                }
+                self.config.graphics.present_mode = mode;
+            }
+            OptionsMessage::SetVolume(percent) => {
+                let percent = if percent.is_nan() {
+                    0
+                } else {
+                    percent.clamp(0.0, 100.0).round() as u32
+                };
+                if let Some(runtime) = runtime {
+                    runtime.set_master_volume(percent);
+                }
+                self.config.audio.master_volume = percent;
+            }
+            OptionsMessage::SelectProfile(name) => {
+                let profile = if name == "default" {
+                    None
+                } else {
+                    let dir = self
+                        .profile_dir
+                        .as_deref()
+                        .ok_or_else(|| anyhow::anyhow!("No profile directory"))?;
+                    Some(ControllerProfile::load_named(dir, &name)?)
+                };
+                if let Some(runtime) = runtime {
+                    runtime.set_input_profile(profile.clone())?;
+                }
+                self.config.input.profile = name;
+                self.loaded_profile = profile;
+            }
+            OptionsMessage::SetOptLevel(level) => {
+                level.parse::<OptLevel>().map_err(anyhow::Error::msg)?;
+                self.rebuild_needed |= level != self.config.recompiler.opt_level;
+                self.config.recompiler.opt_level = level;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn view(&self) -> Element<'_, OptionsMessage> {
+        let label = |text: &str| Text::new(text.to_string()).width(Length::Fixed(150.0));
+        let choices = |values: &[&str]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+        let graphics = &self.config.graphics;
+        let volume = self.config.audio.master_volume;
+
+        let mut col = Column::new()
+            .spacing(15)
+            .push(Text::new("Options").size(28))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(label("Resolution scale"))
+                    .push(
+                        Slider::new(
+                            RESOLUTION_SCALE_RANGE,
+                            graphics.resolution_scale,
+                            OptionsMessage::SetResolutionScale,
+                        )
+                        .width(Length::Fixed(200.0)),
+                    )
+                    .push(Text::new(format!("{}x", graphics.resolution_scale))),
+            )
+            .push(
+                Row::new().spacing(10).push(label("Present mode")).push(
+                    PickList::new(
+                        choices(&PRESENT_MODES),
+                        Some(graphics.present_mode.clone()),
+                        OptionsMessage::SetPresentMode,
+                    )
+                    .width(Length::Fixed(200.0)),
+                ),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(label("Master volume"))
+                    .push(
+                        Slider::new(0.0..=100.0, volume as f32, OptionsMessage::SetVolume)
+                            .width(Length::Fixed(200.0)),
+                    )
+                    .push(Text::new(format!("{volume}%"))),
+            )
+            .push(
+                Row::new().spacing(10).push(label("Input profile")).push(
+                    PickList::new(
+                        self.profiles.clone(),
+                        Some(self.config.input.profile.clone()),
+                        OptionsMessage::SelectProfile,
+                    )
+                    .width(Length::Fixed(200.0)),
+                ),
+            )
+            .push(
+                Row::new().spacing(10).push(label("Opt level")).push(
+                    PickList::new(
+                        choices(&OPT_LEVELS),
+                        Some(self.config.recompiler.opt_level.clone()),
+                        OptionsMessage::SetOptLevel,
+                    )
+                    .width(Length::Fixed(200.0)),
+                ),
+            );
+        if self.rebuild_needed {
+            col = col.push(Text::new("Recompile the game to apply the new opt level."));
+        }
+        if let Some(error) = &self.error {
+            col = col.push(Text::new(error.clone()));
+        }
+        col.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcrecomp_runtime::input::GameCubeMapping;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("gcrecomp_options_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn set_volume_clamps_and_saves() {
+        let dir = temp_dir("volume");
+        let path = dir.join("config.toml");
+        let mut state = OptionsState::new(Config::default(), Some(path.clone()), None);
+
+        state.update(OptionsMessage::SetVolume(140.0), None);
+        assert_eq!(state.config().audio.master_volume, 100);
+        state.update(OptionsMessage::SetVolume(-3.0), None);
+        assert_eq!(state.config().audio.master_volume, 0);
+        state.update(OptionsMessage::SetVolume(42.4), None);
+        assert_eq!(state.config().audio.master_volume, 42);
+        assert_eq!(state.error(), None);
+        let saved = Config::load_from(Some(&path), |_| None).unwrap();
+        assert_eq!(saved.config.audio.master_volume, 42);
+
+        state.update(OptionsMessage::SetResolutionScale(20), None);
+        assert!(state.error().is_some());
+        assert_eq!(state.config().graphics.resolution_scale, 1);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn select_profile_loads_it() {
+        let dir = temp_dir("profile");
+        let profiles = dir.join("profiles");
+        std::fs::create_dir_all(&profiles).unwrap();
+        ControllerProfile::from_mapping("arcade".to_string(), GameCubeMapping::xbox_default())
+            .save_to_file(&profiles.join("arcade.json"))
+            .unwrap();
+        let mut state = OptionsState::new(Config::default(), None, Some(profiles));
+        assert_eq!(state.profiles(), ["default", "arcade"]);
+
+        state.update(OptionsMessage::SelectProfile("arcade".to_string()), None);
+        assert_eq!(state.error(), None);
+        assert_eq!(state.loaded_profile().unwrap().name, "arcade");
+        assert_eq!(state.config().input.profile, "arcade");
+
+        state.update(OptionsMessage::SelectProfile("missing".to_string()), None);
+        assert!(state.error().is_some());
+        assert_eq!(state.config().input.profile, "arcade");
+
+        state.update(OptionsMessage::SelectProfile("default".to_string()), None);
+        assert!(state.loaded_profile().is_none());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
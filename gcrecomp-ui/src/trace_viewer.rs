@@ -0,0 +1,316 @@
+// Trace viewer: browse an exported instruction trace
+//
+// Loads a trace written by `RuntimeTracer::export_to_files` (compact
+// `.gctrace` or JSON) and lists one row per executed instruction: address,
+// disassembly and the registers it changed. Rows can be narrowed to an
+// address prefix and an instruction type. Traces run to millions of entries,
+// so rows are built once on load and the view only lays out the first
+// `SHOWN` of those that pass the filters.
+
+use gcrecomp_core::recompiler::decoder::{Instruction, InstructionType};
+use gcrecomp_core::recompiler::disasm::disassemble;
+use gcrecomp_core::runtime::trace::{RegisterState, RuntimeTracer};
+use iced::{
+    widget::{Button, Column, PickList, Row, Scrollable, Text, TextInput},
+    Element, Length,
+};
+use std::fmt;
+use std::path::Path;
+
+/// Rows laid out in the view; the rest are only counted.
+const SHOWN: usize = 500;
+
+/// Types offered in the filter, in `InstructionType` order.
+const TYPES: [InstructionType; 12] = [
+    InstructionType::Arithmetic,
+    InstructionType::Branch,
+    InstructionType::Load,
+    InstructionType::Store,
+    InstructionType::Compare,
+    InstructionType::Move,
+    InstructionType::System,
+    InstructionType::FloatingPoint,
+    InstructionType::ConditionRegister,
+    InstructionType::Shift,
+    InstructionType::Rotate,
+    InstructionType::Unknown,
+];
+
+/// The instruction-type filter: one type, or all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TypeFilter(pub Option<InstructionType>);
+
+impl TypeFilter {
+    fn all() -> Vec<TypeFilter> {
+        std::iter::once(TypeFilter(None))
+            .chain(TYPES.iter().map(|&t| TypeFilter(Some(t))))
+            .collect()
+    }
+}
+
+impl fmt::Display for TypeFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(kind) => write!(f, "{kind:?}"),
+            None => f.write_str("All types"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceViewerMessage {
+    SetPath(String),
+    /// Load the trace at the path box.
+    Load,
+    /// Keep rows whose hex address starts with this.
+    SetAddressFilter(String),
+    SetTypeFilter(TypeFilter),
+}
+
+/// One executed instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceRow {
+    pub address: u32,
+    pub asm: String,
+    pub kind: InstructionType,
+    /// `r3=00000001 lr=80003010`, in slot order.
+    pub changed: String,
+}
+
+#[derive(Default)]
+pub struct TraceViewerState {
+    path: String,
+    rows: Vec<TraceRow>,
+    /// Indices into `rows` that pass the filters.
+    visible: Vec<usize>,
+    address_filter: String,
+    type_filter: TypeFilter,
+    /// Why the last load failed.
+    error: Option<String>,
+}
+
+impl TraceViewerState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rows(&self) -> &[TraceRow] {
+        &self.rows
+    }
+
+    /// Rows that pass the filters, in trace order.
+    pub fn visible(&self) -> impl Iterator<Item = &TraceRow> {
+        self.visible.iter().map(|&i| &self.rows[i])
+    }
+
+    pub fn visible_count(&self) -> usize {
+        self.visible.len()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn update(&mut self, message: TraceViewerMessage) {
+        match message {
+            TraceViewerMessage::SetPath(path) => self.path = path,
+            TraceViewerMessage::Load => {
+                match RuntimeTracer::read_from_file(Path::new(self.path.trim())) {
+                    Ok(trace) => {
+                        self.rows = rows(&trace);
+                        self.error = None;
+                    }
+                    Err(e) => {
+                        self.rows.clear();
+                        self.error = Some(format!("{e:#}"));
+                    }
+                }
+                self.refilter();
+            }
+            TraceViewerMessage::SetAddressFilter(prefix) => {
+                self.address_filter = prefix;
+                self.refilter();
+            }
+            TraceViewerMessage::SetTypeFilter(filter) => {
+                self.type_filter = filter;
+                self.refilter();
+            }
+        }
+    }
+
+    fn refilter(&mut self) {
+        let prefix = self.address_filter.trim();
+        let prefix = prefix
+            .strip_prefix("0x")
+            .or_else(|| prefix.strip_prefix("0X"))
+            .unwrap_or(prefix)
+            .to_ascii_uppercase();
+        let kind = self.type_filter.0;
+        self.visible = self
+            .rows
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| kind.map_or(true, |k| row.kind == k))
+            .filter(|(_, row)| format!("{:08X}", row.address).starts_with(&prefix))
+            .map(|(i, _)| i)
+            .collect();
+    }
+
+    pub fn view(&self) -> Element<'_, TraceViewerMessage> {
+        let mut col = Column::new()
+            .spacing(15)
+            .push(Text::new("Trace viewer").size(28))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new("trace.gctrace or trace.json", &self.path)
+                            .on_input(TraceViewerMessage::SetPath)
+                            .on_submit(TraceViewerMessage::Load)
+                            .width(Length::Fixed(400.0)),
+                    )
+                    .push(Button::new(Text::new("Load")).on_press(TraceViewerMessage::Load)),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new("Address prefix", &self.address_filter)
+                            .on_input(TraceViewerMessage::SetAddressFilter)
+                            .width(Length::Fixed(150.0)),
+                    )
+                    .push(
+                        PickList::new(
+                            TypeFilter::all(),
+                            Some(self.type_filter),
+                            TraceViewerMessage::SetTypeFilter,
+                        )
+                        .width(Length::Fixed(200.0)),
+                    ),
+            )
+            .push(Text::new(format!(
+                "{} of {} instructions",
+                self.visible.len(),
+                self.rows.len()
+            )));
+        if let Some(error) = &self.error {
+            col = col.push(Text::new(error.clone()));
+        }
+
+        let mut table = Column::new().spacing(2).push(table_row(
+            "Address".to_string(),
+            "Instruction".to_string(),
+            "Changed registers".to_string(),
+        ));
+        for row in self.visible().take(SHOWN) {
+            table = table.push(table_row(
+                format!("{:08X}", row.address),
+                row.asm.clone(),
+                row.changed.clone(),
+            ));
+        }
+        col.push(Scrollable::new(table).height(Length::Fixed(400.0)))
+            .into()
+    }
+}
+
+fn table_row<'a>(address: String, asm: String, changed: String) -> Element<'a, TraceViewerMessage> {
+    Row::new()
+        .spacing(20)
+        .push(Text::new(address).width(Length::Fixed(90.0)))
+        .push(Text::new(asm).width(Length::Fixed(220.0)))
+        .push(Text::new(changed))
+        .into()
+}
+
+/// A row per entry, with registers diffed against the entry before (the
+/// first against all-zero registers, as in the compact format).
+fn rows(trace: &RuntimeTracer) -> Vec<TraceRow> {
+    let mut prev = RegisterState::default();
+    trace
+        .entries()
+        .iter()
+        .map(|entry| {
+            let changed = entry
+                .registers
+                .changed_since(&prev)
+                .into_iter()
+                .map(|slot| {
+                    let value = entry.registers.slot(slot);
+                    match slot {
+                        32..=63 => format!(
+                            "{}={}",
+                            RegisterState::slot_name(slot),
+                            f64::from_bits(value)
+                        ),
+                        _ => format!("{}={value:08X}", RegisterState::slot_name(slot)),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            prev = entry.registers;
+            TraceRow {
+                address: entry.address,
+                asm: disassemble(entry.raw, entry.address),
+                kind: Instruction::decode(entry.raw, entry.address)
+                    .map_or(InstructionType::Unknown, |d| d.instruction.instruction_type),
+                changed,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gcrecomp_core::runtime::context::CpuContext;
+    use gcrecomp_core::runtime::trace::TraceFormat;
+
+    #[test]
+    fn filters_reduce_the_displayed_rows() {
+        let mut ctx = CpuContext::new();
+        let mut tracer = RuntimeTracer::new();
+        ctx.gpr[3] = 1;
+        tracer.record(0x8000_3000, 0x3860_0001, &ctx); // li r3,1
+        ctx.gpr[4] = 2;
+        tracer.record(0x8000_3004, 0x3880_0002, &ctx); // li r4,2
+        ctx.lr = 0x8000_300C;
+        tracer.record(0x8000_3008, 0x4800_0101, &ctx); // bl
+        tracer.record(0x8000_3108, 0x4E80_0020, &ctx); // blr
+        let dir = std::env::temp_dir().join(format!("gcrecomp_ui_trace_{}", std::process::id()));
+        let path = tracer.export_to_files(&dir, TraceFormat::Compact).unwrap();
+
+        let mut state = TraceViewerState::new();
+        state.update(TraceViewerMessage::SetPath(path.display().to_string()));
+        state.update(TraceViewerMessage::Load);
+        assert_eq!(state.error(), None);
+        assert_eq!(state.visible_count(), 4);
+        let first = &state.rows()[0];
+        assert_eq!(first.asm, "li r3, 1");
+        assert_eq!(first.changed, "r3=00000001");
+
+        state.update(TraceViewerMessage::SetTypeFilter(TypeFilter(Some(
+            InstructionType::Branch,
+        ))));
+        assert_eq!(state.visible_count(), 2);
+        state.update(TraceViewerMessage::SetAddressFilter("0x800031".into()));
+        assert_eq!(state.visible_count(), 1);
+        assert_eq!(state.visible().next().unwrap().address, 0x8000_3108);
+
+        state.update(TraceViewerMessage::SetTypeFilter(TypeFilter(None)));
+        state.update(TraceViewerMessage::SetAddressFilter(String::new()));
+        assert_eq!(state.visible_count(), 4);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn a_bad_file_sets_an_error() {
+        let mut state = TraceViewerState::new();
+        state.update(TraceViewerMessage::SetPath(
+            "/nonexistent/trace.gctrace".into(),
+        ));
+        state.update(TraceViewerMessage::Load);
+        assert!(state.error().unwrap().contains("opening"));
+        assert_eq!(state.visible_count(), 0);
+    }
+}
@@ -0,0 +1,324 @@
+// Recompile jobs
+//
+// `POST /api/recompile` hands a DOL (and its options) to `JobQueue::submit`,
+// which stores it under the jobs directory and returns an id straight away.
+// The job then waits for one of a fixed number of worker slots, so however
+// many uploads arrive only `workers` recompiles run at once; the rest stay
+// queued. `GET /api/jobs/:id` reads its status, progress and the last lines
+// of its log. Everything a job writes stays under the jobs directory: the
+// generated module goes to `job-<id>/out/recompiled.rs` unless the request
+// names another path, which `security::sandboxed_path` has already confined.
+//
+// Each job also broadcasts a `JobEvent` for every progress step the pipeline
+// reports and a final `Status` when it finishes, for `GET /api/jobs/:id/stream`.
+// A subscriber starts with the latest progress step, so one that connects
+// late still sees where the job is (or how it ended).
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, Semaphore};
+
+use gcrecomp_core::config::Config;
+use gcrecomp_core::recompiler::analysis::similarity::SimilarityIndex;
+use gcrecomp_core::recompiler::fidb::FidDatabase;
+use gcrecomp_core::recompiler::optimizer::{OptLevel, Optimizer};
+use gcrecomp_core::recompiler::parser::DolFile;
+use gcrecomp_core::recompiler::pipeline::{
+    default_jobs, FunctionProgress, ProgressSink, RecompilationPipeline, RecompileOptions,
+};
+
+/// Recompiles running at once.
+pub const JOB_WORKERS: usize = 2;
+
+/// Log lines kept per job.
+const LOG_TAIL: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Complete,
+    Failed,
+}
+
+impl JobState {
+    pub fn finished(self) -> bool {
+        matches!(self, JobState::Complete | JobState::Failed)
+    }
+}
+
+/// What `GET /api/jobs/:id` returns.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: u64,
+    pub state: JobState,
+    /// Fraction of functions generated, 0.0 to 1.0.
+    pub progress: f64,
+    pub log: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The generated `recompiled.rs`, once complete.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats: Option<serde_json::Value>,
+}
+
+/// Pushed to `GET /api/jobs/:id/stream` subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JobEvent {
+    /// A function is being generated.
+    Progress(FunctionProgress),
+    /// The job finished; always the last event.
+    Status(JobStatus),
+}
+
+/// A DOL to recompile and how.
+#[derive(Debug, Clone, Default)]
+pub struct JobRequest {
+    pub dol: Vec<u8>,
+    /// Function ID database (JSON signatures) naming SDK functions.
+    pub fidb: Option<Vec<u8>>,
+    /// Also name functions by structural similarity to `fidb`.
+    pub enable_bsim: bool,
+    /// `none`, `basic` or `aggressive`; `[recompiler] opt_level` when unset.
+    pub opt_level: Option<OptLevel>,
+    /// Where to write the generated module, inside the jobs directory.
+    pub output: Option<PathBuf>,
+}
+
+struct Job {
+    state: JobState,
+    progress: f64,
+    log: VecDeque<String>,
+    error: Option<String>,
+    output: Option<PathBuf>,
+    stats: Option<serde_json::Value>,
+    last_progress: Option<FunctionProgress>,
+    events: broadcast::Sender<JobEvent>,
+}
+
+impl Job {
+    fn status(&self, id: u64) -> JobStatus {
+        JobStatus {
+            id,
+            state: self.state,
+            progress: self.progress,
+            log: self.log.iter().cloned().collect(),
+            error: self.error.clone(),
+            output: self.output.as_ref().map(|p| p.display().to_string()),
+            stats: self.stats.clone(),
+        }
+    }
+
+    fn log(&mut self, line: impl Into<String>) {
+        if self.log.len() == LOG_TAIL {
+            self.log.pop_front();
+        }
+        self.log.push_back(line.into());
+    }
+}
+
+pub struct JobQueue {
+    root: PathBuf,
+    jobs: Mutex<HashMap<u64, Job>>,
+    next_id: AtomicU64,
+    workers: Arc<Semaphore>,
+}
+
+impl JobQueue {
+    /// Jobs keep their files under `root`; `workers` run at once.
+    pub fn new(root: impl Into<PathBuf>, workers: usize) -> Self {
+        Self {
+            root: root.into(),
+            jobs: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+        }
+    }
+
+    /// Take every worker slot, so jobs submitted while the permit is held
+    /// stay queued.
+    #[cfg(test)]
+    pub(crate) async fn hold_workers(&self) -> tokio::sync::OwnedSemaphorePermit {
+        let all = self.workers.available_permits() as u32;
+        self.workers.clone().acquire_many_owned(all).await.unwrap()
+    }
+
+    /// Store `request`'s files and queue it. Returns the job id.
+    pub fn submit(self: &Arc<Self>, request: JobRequest) -> Result<u64> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let dir = self.job_dir(id);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create job directory: {}", dir.display()))?;
+        std::fs::write(dir.join("input.dol"), &request.dol)?;
+        if let Some(fidb) = &request.fidb {
+            std::fs::write(dir.join("fidb.json"), fidb)?;
+        }
+
+        let mut job = Job {
+            state: JobState::Queued,
+            progress: 0.0,
+            log: VecDeque::new(),
+            error: None,
+            output: None,
+            stats: None,
+            last_progress: None,
+            events: broadcast::channel(64).0,
+        };
+        job.log(format!("Queued ({} byte DOL)", request.dol.len()));
+        self.lock().insert(id, job);
+
+        let queue = self.clone();
+        tokio::spawn(async move {
+            // Held until the recompile finishes: this is the worker bound.
+            let Ok(_permit) = queue.workers.clone().acquire_owned().await else {
+                return;
+            };
+            queue.update(id, |job| {
+                job.state = JobState::Running;
+                job.log("Started");
+            });
+            let worker = queue.clone();
+            let outcome = tokio::task::spawn_blocking(move || worker.run(id, &request))
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("recompiler panicked: {e}")));
+            queue.update(id, |job| match outcome {
+                Ok((output, stats)) => {
+                    job.state = JobState::Complete;
+                    job.progress = 1.0;
+                    job.log(format!("Complete: {}", output.display()));
+                    job.output = Some(output);
+                    job.stats = Some(stats);
+                }
+                Err(e) => {
+                    job.state = JobState::Failed;
+                    job.log(format!("Failed: {e:#}"));
+                    job.error = Some(format!("{e:#}"));
+                }
+            });
+            queue.update(id, |job| {
+                let _ = job.events.send(JobEvent::Status(job.status(id)));
+            });
+        });
+        Ok(id)
+    }
+
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        self.lock().get(&id).map(|job| job.status(id))
+    }
+
+    /// Events for job `id` so far (its latest progress, and its final status
+    /// if it's done), and a receiver for the rest while it's still going.
+    pub fn subscribe(
+        &self,
+        id: u64,
+    ) -> Option<(Vec<JobEvent>, Option<broadcast::Receiver<JobEvent>>)> {
+        let jobs = self.lock();
+        let job = jobs.get(&id)?;
+        let mut backlog: Vec<JobEvent> = job
+            .last_progress
+            .clone()
+            .map(JobEvent::Progress)
+            .into_iter()
+            .collect();
+        if job.state.finished() {
+            backlog.push(JobEvent::Status(job.status(id)));
+            return Some((backlog, None));
+        }
+        Some((backlog, Some(job.events.subscribe())))
+    }
+
+    /// The jobs directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn job_dir(&self, id: u64) -> PathBuf {
+        self.root.join(format!("job-{id}"))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, Job>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.lock().get_mut(&id) {
+            f(job);
+        }
+    }
+
+    /// Recompile job `id` (on a blocking thread). Returns the output path and
+    /// the pipeline stats.
+    fn run(
+        self: &Arc<Self>,
+        id: u64,
+        request: &JobRequest,
+    ) -> Result<(PathBuf, serde_json::Value)> {
+        let dir = self.job_dir(id);
+        let fidb = match &request.fidb {
+            Some(_) => Some(FidDatabase::load(&dir.join("fidb.json"))?),
+            None => None,
+        };
+        let similarity = match &fidb {
+            Some(db) if request.enable_bsim => Some(SimilarityIndex::from_database(db)),
+            _ => None,
+        };
+        let level = match request.opt_level {
+            Some(level) => level,
+            None => Config::load()
+                .map(|loaded| loaded.config.opt_level())
+                .unwrap_or_else(|_| Config::default().opt_level()),
+        };
+        let queue = self.clone();
+        let options = RecompileOptions {
+            fidb,
+            similarity,
+            optimizer: Optimizer::with_level(level),
+            jobs: Some((default_jobs() / JOB_WORKERS).max(1)),
+            progress: Some(ProgressSink::new(move |p: FunctionProgress| {
+                queue.update(id, |job| {
+                    job.progress = p.done as f64 / p.total.max(1) as f64;
+                    job.log(format!(
+                        "Generating code for function {}/{} - {}",
+                        p.done, p.total, p.function
+                    ));
+                    job.last_progress = Some(p.clone());
+                    let _ = job.events.send(JobEvent::Progress(p));
+                });
+            })),
+            ..Default::default()
+        };
+
+        let dol_path = dir.join("input.dol");
+        let dol = DolFile::parse(&request.dol, &dol_path.to_string_lossy())
+            .context("Failed to parse DOL file")?;
+        let output = request
+            .output
+            .clone()
+            .unwrap_or_else(|| dir.join("out").join("recompiled.rs"));
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let summary = RecompilationPipeline::recompile_with_options(
+            &dol,
+            output.to_str().context("Invalid output path")?,
+            &options,
+        )
+        .context("Recompilation pipeline failed")?;
+        let stats = serde_json::json!({
+            "functions": summary.stats.total_functions,
+            "successful_functions": summary.stats.successful_functions,
+            "failed_functions": summary.stats.failed_functions,
+            "instructions": summary.stats.total_instructions,
+            "coverage": summary.coverage.translated_percent(),
+        });
+        Ok((output, stats))
+    }
+}
@@ -1,3 +1,4 @@
+mod jobs;
 mod routes;
 mod security;
 mod server;
@@ -6,7 +7,7 @@ use anyhow::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
+    let _ = gcrecomp_core::logging::init(None, log::LevelFilter::Info);
     let server = server::WebServer::new()?;
     server.run().await
 }
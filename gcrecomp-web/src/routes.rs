@@ -1,10 +1,12 @@
 use axum::{
     body::Bytes,
-    extract::{DefaultBodyLimit, State},
+    extract::ws::{close_code, CloseFrame, Message, WebSocket, WebSocketUpgrade},
+    extract::{DefaultBodyLimit, Multipart, Path, Request, State},
     http::StatusCode,
+    middleware::{self, Next},
     response::{
         sse::{Event, KeepAlive, Sse},
-        Html,
+        Html, Response,
     },
     routing::{get, post, put},
     Json, Router,
@@ -12,29 +14,55 @@ use axum::{
 use std::convert::Infallible;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::{Stream, StreamExt};
 
 use gcrecomp_lua::convert::{json_to_lua_value, lua_table_to_json};
 use gcrecomp_lua::engine::LuaEngine;
 
+use crate::jobs::{JobEvent, JobRequest, JobStatus};
 use crate::security;
 use crate::server::{AppState, StatusEvent};
 
-/// All application routes (no nesting required).
-pub fn app_routes() -> Router<Arc<AppState>> {
+/// All application routes (no nesting required). The job routes go through
+/// `security::authorize` first.
+pub fn app_routes(state: &Arc<AppState>) -> Router<Arc<AppState>> {
+    let jobs = Router::new()
+        .route(
+            "/api/recompile",
+            post(handle_recompile).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/api/jobs/:id", get(handle_job_status))
+        .route("/api/jobs/:id/stream", get(handle_job_stream))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize));
     Router::new()
         .route("/", get(handle_index))
         .route(
             "/api/upload",
             post(handle_upload).layer(DefaultBodyLimit::disable()),
         )
+        .merge(jobs)
         .route("/api/status", get(sse_status))
         .route("/api/config", get(handle_config_get))
         .route("/api/config", put(handle_config_set))
         .route("/api/targets", get(handle_targets))
 }
 
+/// Reject requests without the API token or from another origin.
+async fn authorize(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    security::authorize(
+        request.headers(),
+        request.uri().query(),
+        state.api_token.as_deref(),
+    )?;
+    Ok(next.run(request).await)
+}
+
 // ---------------------------------------------------------------------------
 // GET / — Lua renders the full HTML page
 // ---------------------------------------------------------------------------
@@ -65,7 +93,7 @@ async fn handle_index(
 }
 
 // ---------------------------------------------------------------------------
-// POST /api/upload — body limit disabled, manual 5 GB check
+// POST /api/upload — body limit disabled, manual size check
 // ---------------------------------------------------------------------------
 
 async fn handle_upload(
@@ -74,11 +102,8 @@ async fn handle_upload(
     body: Bytes,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     // Manual size enforcement (DefaultBodyLimit is disabled on this route)
-    if body.len() > security::MAX_UPLOAD_SIZE {
-        return Err((
-            StatusCode::PAYLOAD_TOO_LARGE,
-            "File too large (max 5 GB).".to_string(),
-        ));
+    if body.len() > state.max_upload {
+        return Err(security::too_large(state.max_upload));
     }
 
     // Check recompile lock early
@@ -356,6 +381,192 @@ fn run_lua_pipeline(
     Ok(stats_json)
 }
 
+// ---------------------------------------------------------------------------
+// POST /api/recompile — multipart DOL + options, queued as a job
+// ---------------------------------------------------------------------------
+
+/// A checkbox-style multipart field: `true`/`1`/`on` or `false`/`0`/`off`.
+fn parse_flag(name: &str, value: &str) -> Result<bool, (StatusCode, String)> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "on" | "yes" => Ok(true),
+        "false" | "0" | "off" | "no" | "" => Ok(false),
+        other => Err((
+            StatusCode::BAD_REQUEST,
+            format!("'{name}' must be true or false, not '{other}'"),
+        )),
+    }
+}
+
+/// Fields: `dol` (the file, required), `fidb` (signature JSON file),
+/// `hierarchical`, `enable_bsim` (needs `fidb`), `opt_level` and `output`
+/// (path of the generated module, inside the jobs directory). Returns
+/// `{"id": n}` for `GET /api/jobs/:id`.
+///
+/// The body limit is enforced here rather than by `DefaultBodyLimit`, so an
+/// oversized upload gets a 413 instead of a multipart read error. The DOL
+/// header is checked before the job is queued.
+async fn handle_recompile(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let bad_request = |message: String| (StatusCode::BAD_REQUEST, message);
+    let declared = headers
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > state.max_upload) {
+        return Err(security::too_large(state.max_upload));
+    }
+
+    let mut request = JobRequest::default();
+    let mut dol = None;
+    let mut hierarchical = false;
+    let mut received = 0usize;
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| bad_request(format!("Malformed upload: {e}")))?
+    {
+        let name = field.name().unwrap_or_default().to_string();
+        let mut data = Vec::new();
+        while let Some(chunk) = field
+            .chunk()
+            .await
+            .map_err(|e| bad_request(format!("Failed to read '{name}': {e}")))?
+        {
+            received += chunk.len();
+            if received > state.max_upload {
+                return Err(security::too_large(state.max_upload));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        let text = || String::from_utf8_lossy(&data).into_owned();
+        match name.as_str() {
+            "output" => {
+                request.output = Some(security::sandboxed_path(state.jobs.root(), &text())?)
+            }
+            "dol" => dol = Some(data),
+            "fidb" => request.fidb = Some(data),
+            "hierarchical" => hierarchical = parse_flag(&name, &text())?,
+            "enable_bsim" => request.enable_bsim = parse_flag(&name, &text())?,
+            "opt_level" => request.opt_level = Some(text().trim().parse().map_err(bad_request)?),
+            other => return Err(bad_request(format!("Unknown field '{other}'"))),
+        }
+    }
+    request.dol = dol.ok_or_else(|| bad_request("Missing 'dol' file".to_string()))?;
+    if hierarchical {
+        return Err(bad_request(
+            "hierarchical recompilation is not supported".to_string(),
+        ));
+    }
+    if request.enable_bsim && request.fidb.is_none() {
+        return Err(bad_request("'enable_bsim' needs a 'fidb' file".to_string()));
+    }
+    security::validate_dol(&request.dol)?;
+
+    let id = state
+        .jobs
+        .submit(request)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+    Ok(Json(serde_json::json!({ "id": id })))
+}
+
+// ---------------------------------------------------------------------------
+// GET /api/jobs/:id — status, progress and log tail
+// ---------------------------------------------------------------------------
+
+async fn handle_job_status(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+) -> Result<Json<JobStatus>, (StatusCode, String)> {
+    state
+        .jobs
+        .status(id)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("No job {id}")))
+}
+
+// ---------------------------------------------------------------------------
+// GET /api/jobs/:id/stream — WebSocket of progress events, then the status
+// ---------------------------------------------------------------------------
+
+async fn handle_job_stream(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<u64>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, (StatusCode, String)> {
+    let (backlog, events) = state
+        .jobs
+        .subscribe(id)
+        .ok_or((StatusCode::NOT_FOUND, format!("No job {id}")))?;
+    Ok(ws.on_upgrade(move |socket| async move {
+        if let Err(e) = stream_job_events(socket, backlog, events).await {
+            log::debug!("Job {id} stream ended early: {e}");
+        }
+    }))
+}
+
+/// How long to wait for the client to answer our close frame.
+const CLOSE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Send `backlog` and then live events as text messages until the final
+/// status, then close. Client messages are read throughout so pings get
+/// their pong and a client close ends the stream.
+async fn stream_job_events(
+    mut socket: WebSocket,
+    backlog: Vec<JobEvent>,
+    events: Option<broadcast::Receiver<JobEvent>>,
+) -> Result<(), axum::Error> {
+    let text = |event: &JobEvent| Message::Text(serde_json::to_string(event).unwrap_or_default());
+    for event in &backlog {
+        socket.send(text(event)).await?;
+    }
+    if let Some(mut events) = events {
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        socket.send(text(&event)).await?;
+                        if matches!(event, JobEvent::Status(_)) {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                // The socket answers pings and client closes by itself; the
+                // reply goes out on the next read.
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Close(_))) => {
+                        while socket.recv().await.is_some() {}
+                        return Ok(());
+                    }
+                    None => return Ok(()),
+                    Some(Ok(_)) => {}
+                    Some(Err(e)) => return Err(e),
+                },
+            }
+        }
+    }
+    socket
+        .send(Message::Close(Some(CloseFrame {
+            code: close_code::NORMAL,
+            reason: "".into(),
+        })))
+        .await?;
+    // Finish the closing handshake; the socket is done once the client replies.
+    let _ = tokio::time::timeout(CLOSE_TIMEOUT, async {
+        while let Some(Ok(message)) = socket.recv().await {
+            if matches!(message, Message::Close(_)) {
+                break;
+            }
+        }
+    })
+    .await;
+    Ok(())
+}
+
 // ---------------------------------------------------------------------------
 // GET /api/status — SSE stream (stays in Rust, async requirement)
 // ---------------------------------------------------------------------------
@@ -430,3 +641,403 @@ async fn handle_targets(
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
     call_lua_handler(&state, "handle_list_targets", None).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobQueue;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tokio::io::AsyncWriteExt;
+    use tower::ServiceExt;
+
+    const BOUNDARY: &str = "gcrecomp-test-boundary";
+
+    /// A DOL with one text section at 0x80003000 holding `words`.
+    fn build_dol(words: &[u32]) -> Vec<u8> {
+        let mut dol = vec![0u8; 0x100];
+        dol[0x00..0x04].copy_from_slice(&0x100u32.to_be_bytes()); // text0 offset
+        dol[0x48..0x4C].copy_from_slice(&0x8000_3000u32.to_be_bytes()); // text0 address
+        dol[0x90..0x94].copy_from_slice(&(words.len() as u32 * 4).to_be_bytes()); // text0 size
+        dol[0xE0..0xE4].copy_from_slice(&0x8000_3000u32.to_be_bytes()); // entry
+        for w in words {
+            dol.extend_from_slice(&w.to_be_bytes());
+        }
+        dol
+    }
+
+    /// A multipart body with `dol` as the `dol` file and `fields` as text.
+    fn multipart(dol: &[u8], fields: &[(&str, &str)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(
+                format!(
+                    "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n"
+                )
+                .as_bytes(),
+            );
+        }
+        body.extend_from_slice(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"dol\"; filename=\"game.dol\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(dol);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+        body
+    }
+
+    fn app(root: &std::path::Path) -> Router {
+        app_with_limit(root, security::MAX_UPLOAD_SIZE)
+    }
+
+    fn app_with_limit(root: &std::path::Path, max_upload: usize) -> Router {
+        let mut state = AppState::new(LuaEngine::new().unwrap(), JobQueue::new(root, 1));
+        state.max_upload = max_upload;
+        let state = Arc::new(state);
+        app_routes(&state).with_state(state)
+    }
+
+    async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Vec<u8>) {
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, body.to_vec())
+    }
+
+    fn post_recompile(body: Vec<u8>) -> Request<Body> {
+        Request::post("/api/recompile")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn posted_dol_recompiles_as_a_job() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_jobs_{}", std::process::id()));
+        let app = app(&root);
+        // li r3,1 ; blr
+        let dol = build_dol(&[0x3860_0001, 0x4E80_0020]);
+        let (status, body) = send(
+            &app,
+            post_recompile(multipart(
+                &dol,
+                &[("enable_bsim", "false"), ("opt_level", "basic")],
+            )),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        let id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"]
+            .as_u64()
+            .unwrap();
+
+        let mut job = serde_json::Value::Null;
+        for _ in 0..500 {
+            let (status, body) = send(
+                &app,
+                Request::get(format!("/api/jobs/{id}"))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await;
+            assert_eq!(status, StatusCode::OK);
+            job = serde_json::from_slice(&body).unwrap();
+            if job["state"] == "complete" || job["state"] == "failed" {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(job["state"], "complete", "{job}");
+        assert_eq!(job["progress"], 1.0);
+        assert_eq!(job["stats"]["functions"], 1);
+        assert!(
+            job["log"][0].as_str().unwrap().starts_with("Queued"),
+            "{job}"
+        );
+        let output = job["output"].as_str().unwrap();
+        assert!(std::fs::read_to_string(output)
+            .unwrap()
+            .contains("call_function_by_address"));
+
+        let (status, _) = send(
+            &app,
+            Request::get("/api/jobs/999").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn unsupported_options_are_rejected() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_reject_{}", std::process::id()));
+        let app = app(&root);
+        let dol = build_dol(&[0x4E80_0020]);
+        for (fields, expected) in [
+            (&[("hierarchical", "true")][..], "hierarchical"),
+            (&[("enable_bsim", "true")][..], "needs a 'fidb'"),
+            (
+                &[("opt_level", "fastest")][..],
+                "unknown optimization level",
+            ),
+        ] {
+            let (status, body) = send(&app, post_recompile(multipart(&dol, fields))).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.contains(expected), "{body}");
+        }
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn oversized_upload_is_rejected() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_large_{}", std::process::id()));
+        let app = app_with_limit(&root, 1024);
+        let dol = build_dol(&[0x6000_0000; 512]);
+        let (status, body) = send(&app, post_recompile(multipart(&dol, &[]))).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            String::from_utf8_lossy(&body),
+            "Upload too large (max 1024 bytes)."
+        );
+        assert!(!root.join("job-1").exists());
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn output_paths_stay_in_the_jobs_directory() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_paths_{}", std::process::id()));
+        let app = app(&root);
+        let dol = build_dol(&[0x4E80_0020]);
+        let outside = std::env::temp_dir().join("escape.rs");
+        for (output, expected) in [
+            ("../escape.rs", "must not contain '..'"),
+            ("job-1/../../escape.rs", "must not contain '..'"),
+            (outside.to_str().unwrap(), "is outside the jobs directory"),
+            ("", "does not name a file"),
+        ] {
+            let (status, body) =
+                send(&app, post_recompile(multipart(&dol, &[("output", output)]))).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{output}");
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.contains(expected), "{output}: {body}");
+        }
+
+        let (status, body) = send(
+            &app,
+            post_recompile(multipart(&dol, &[("output", "games/test/recompiled.rs")])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn malformed_dol_is_rejected_before_queueing() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_baddol_{}", std::process::id()));
+        let app = app(&root);
+        let mut outside_text = build_dol(&[0x4E80_0020]);
+        outside_text[0xE0..0xE4].copy_from_slice(&0x8000_4000u32.to_be_bytes());
+        let mut past_end = build_dol(&[0x4E80_0020]);
+        past_end[0x90..0x94].copy_from_slice(&0x1000u32.to_be_bytes());
+        let mut not_ram = build_dol(&[0x4E80_0020]);
+        not_ram[0x48..0x4C].copy_from_slice(&0x0000_3000u32.to_be_bytes());
+        for (dol, expected) in [
+            (b"not a dol".to_vec(), "too small"),
+            (vec![0; 0x100], "no text sections"),
+            (past_end, "extends beyond file"),
+            (not_ram, "outside main RAM"),
+            (outside_text, "entry point 0x80004000"),
+        ] {
+            let (status, body) = send(&app, post_recompile(multipart(&dol, &[]))).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            let body = String::from_utf8_lossy(&body);
+            assert!(body.starts_with("Not a valid DOL: "), "{body}");
+            assert!(body.contains(expected), "{body}");
+        }
+        assert!(!root.join("job-1").exists());
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn job_routes_require_the_api_token() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_auth_{}", std::process::id()));
+        let mut state = AppState::new(LuaEngine::new().unwrap(), JobQueue::new(&root, 1));
+        state.api_token = Some("secret".to_string());
+        let state = Arc::new(state);
+        let app = app_routes(&state).with_state(state.clone());
+        let dol = build_dol(&[0x4E80_0020]);
+
+        let (status, _) = send(&app, post_recompile(multipart(&dol, &[]))).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+        let (status, _) = send(&app, get("/api/jobs/1")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(!root.join("job-1").exists());
+
+        let mut request = post_recompile(multipart(&dol, &[]));
+        request
+            .headers_mut()
+            .insert("authorization", "Bearer secret".parse().unwrap());
+        let (status, body) = send(&app, request).await;
+        assert_eq!(status, StatusCode::OK, "{}", String::from_utf8_lossy(&body));
+        let (status, _) = send(&app, get("/api/jobs/1?token=secret")).await;
+        assert_eq!(status, StatusCode::OK);
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    /// Open `path` as a WebSocket on `addr` with `extra` headers; returns the
+    /// response head and the stream positioned at the first frame.
+    async fn open_stream(
+        addr: std::net::SocketAddr,
+        path: &str,
+        extra: &str,
+    ) -> (String, tokio::net::TcpStream) {
+        use tokio::io::AsyncReadExt;
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {path} HTTP/1.1\r\nHost: {addr}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n{extra}\r\n"
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        (String::from_utf8(head).unwrap(), stream)
+    }
+
+    /// Read one unmasked server frame: (opcode, payload).
+    async fn read_frame(stream: &mut tokio::net::TcpStream) -> (u8, Vec<u8>) {
+        use tokio::io::AsyncReadExt;
+        let opcode = stream.read_u8().await.unwrap() & 0x0F;
+        let len = match stream.read_u8().await.unwrap() & 0x7F {
+            126 => stream.read_u16().await.unwrap() as usize,
+            127 => stream.read_u64().await.unwrap() as usize,
+            len => len as usize,
+        };
+        let mut payload = vec![0; len];
+        stream.read_exact(&mut payload).await.unwrap();
+        (opcode, payload)
+    }
+
+    /// Send one short client frame. Clients must mask; an all-zero key leaves
+    /// the payload as is.
+    async fn write_frame(stream: &mut tokio::net::TcpStream, opcode: u8, payload: &[u8]) {
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(payload);
+        stream.write_all(&frame).await.unwrap();
+    }
+
+    /// Read server frames up to and including the close; returns the text
+    /// payloads and the close code.
+    async fn read_frames(stream: &mut tokio::net::TcpStream) -> (Vec<String>, u16) {
+        let mut texts = Vec::new();
+        loop {
+            match read_frame(stream).await {
+                (0x1, payload) => texts.push(String::from_utf8(payload).unwrap()),
+                (0x8, payload) => return (texts, u16::from_be_bytes([payload[0], payload[1]])),
+                (other, _) => panic!("unexpected opcode {other}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn job_stream_pushes_progress_then_status() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_stream_{}", std::process::id()));
+        let mut state = AppState::new(LuaEngine::new().unwrap(), JobQueue::new(&root, 1));
+        state.api_token = Some("secret".to_string());
+        let state = Arc::new(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app_routes(&state).with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // Two functions, so there's progress to report.
+        let dol = build_dol(&[0x4800_0009, 0x4E80_0020, 0x3860_0001, 0x4E80_0020]);
+        let id = state
+            .jobs
+            .submit(JobRequest {
+                dol,
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (head, mut stream) =
+            open_stream(addr, &format!("/api/jobs/{id}/stream?token=secret"), "").await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        assert!(
+            head.to_ascii_lowercase()
+                .contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="),
+            "{head}"
+        );
+        let (texts, code) = read_frames(&mut stream).await;
+        assert_eq!(code, close_code::NORMAL);
+        let events: Vec<serde_json::Value> = texts
+            .iter()
+            .map(|t| serde_json::from_str(t).unwrap())
+            .collect();
+        assert!(events.iter().any(|e| e["type"] == "progress"), "{texts:?}");
+        let last = events.last().unwrap();
+        assert_eq!(last["type"], "status", "{texts:?}");
+        assert_eq!(last["state"], "complete", "{texts:?}");
+
+        let (head, _) = open_stream(addr, &format!("/api/jobs/{id}/stream"), "").await;
+        assert!(head.starts_with("HTTP/1.1 401"), "{head}");
+        let (head, _) = open_stream(
+            addr,
+            &format!("/api/jobs/{id}/stream"),
+            "Authorization: Bearer secret\r\nOrigin: http://evil.example\r\n",
+        )
+        .await;
+        assert!(head.starts_with("HTTP/1.1 403"), "{head}");
+        let (head, _) = open_stream(
+            addr,
+            "/api/jobs/999/stream",
+            "Authorization: Bearer secret\r\n",
+        )
+        .await;
+        assert!(head.starts_with("HTTP/1.1 404"), "{head}");
+        std::fs::remove_dir_all(&root).ok();
+    }
+
+    #[tokio::test]
+    async fn job_stream_answers_pings_and_client_close() {
+        let root = std::env::temp_dir().join(format!("gcrecomp_web_ping_{}", std::process::id()));
+        let state = Arc::new(AppState::new(
+            LuaEngine::new().unwrap(),
+            JobQueue::new(&root, 1),
+        ));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = app_routes(&state).with_state(state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        // Keep the job queued so the stream stays open on live events.
+        let _workers = state.jobs.hold_workers().await;
+        let id = state
+            .jobs
+            .submit(JobRequest {
+                dol: build_dol(&[0x4E80_0020]),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let (head, mut stream) = open_stream(addr, &format!("/api/jobs/{id}/stream"), "").await;
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        write_frame(&mut stream, 0x9, b"hi").await;
+        assert_eq!(read_frame(&mut stream).await, (0xA, b"hi".to_vec()));
+
+        write_frame(&mut stream, 0x8, &close_code::NORMAL.to_be_bytes()).await;
+        let (opcode, payload) = read_frame(&mut stream).await;
+        assert_eq!(opcode, 0x8);
+        assert_eq!(payload[..2], close_code::NORMAL.to_be_bytes());
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
@@ -1,4 +1,8 @@
+use axum::http::header::{AUTHORIZATION, ORIGIN};
+use axum::http::{HeaderMap, StatusCode};
+use gcrecomp_core::recompiler::parser::DolFile;
 use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
 
 /// Bind to localhost only for security.
 pub fn bind_address() -> SocketAddr {
@@ -8,3 +12,150 @@ pub fn bind_address() -> SocketAddr {
 /// Maximum upload size: 5 GB.
 /// Used by the Rust dispatcher for the first-line-of-defense manual check.
 pub const MAX_UPLOAD_SIZE: usize = 5 * 1024 * 1024 * 1024;
+
+/// Overrides `MAX_UPLOAD_SIZE` (in bytes) when set.
+pub const MAX_UPLOAD_ENV: &str = "GCRECOMP_WEB_MAX_UPLOAD";
+
+pub fn max_upload_from_env() -> usize {
+    std::env::var(MAX_UPLOAD_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .filter(|&size| size > 0)
+        .unwrap_or(MAX_UPLOAD_SIZE)
+}
+
+/// The 413 for an upload over `limit` bytes.
+pub fn too_large(limit: usize) -> (StatusCode, String) {
+    (
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("Upload too large (max {limit} bytes)."),
+    )
+}
+
+/// GameCube main RAM, where every DOL section has to load.
+const RAM: std::ops::Range<u64> = 0x8000_0000..0x8180_0000;
+
+/// Check an uploaded DOL's header before any work is queued for it. DOLs have
+/// no magic number, so this is the parser's bounds checks plus what a real one
+/// always has: a text section, sections that load into main RAM and an entry
+/// point inside the text.
+pub fn validate_dol(data: &[u8]) -> Result<(), (StatusCode, String)> {
+    let invalid = |why: String| (StatusCode::BAD_REQUEST, format!("Not a valid DOL: {why}"));
+    let dol = DolFile::parse(data, "upload.dol").map_err(|e| invalid(e.to_string()))?;
+    if dol.text_sections.is_empty() {
+        return Err(invalid("no text sections".to_string()));
+    }
+    for section in dol.text_sections.iter().chain(&dol.data_sections) {
+        let start = u64::from(section.address);
+        if !RAM.contains(&start) || start + u64::from(section.size) > RAM.end {
+            return Err(invalid(format!(
+                "section at 0x{:08X} (0x{:X} bytes) is outside main RAM",
+                section.address, section.size
+            )));
+        }
+    }
+    let entry = dol.entry_point;
+    if !dol
+        .text_sections
+        .iter()
+        .any(|s| (s.address..s.address + s.size).contains(&entry))
+    {
+        return Err(invalid(format!(
+            "entry point 0x{entry:08X} is not in a text section"
+        )));
+    }
+    Ok(())
+}
+
+/// Resolve a client-supplied output path inside `root`. Relative paths are
+/// taken from `root`; absolute ones must already be under it. `..` is refused
+/// outright rather than normalized, so there's no way to step back out.
+pub fn sandboxed_path(root: &Path, requested: &str) -> Result<PathBuf, (StatusCode, String)> {
+    let bad = |why: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Output path '{requested}' {why}."),
+        )
+    };
+    let path = Path::new(requested);
+    if requested.trim().is_empty() || path.file_name().is_none() {
+        return Err(bad("does not name a file"));
+    }
+    if path.components().any(|c| c == Component::ParentDir) {
+        return Err(bad("must not contain '..'"));
+    }
+    if path.is_absolute() || path.has_root() {
+        let root = std::path::absolute(root)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !path.starts_with(&root) {
+            return Err(bad("is outside the jobs directory"));
+        }
+        return Ok(path.to_path_buf());
+    }
+    Ok(root.join(path))
+}
+
+/// Token API clients must present, read from this variable at startup. Unset
+/// means no token: the server only listens on localhost.
+pub const TOKEN_ENV: &str = "GCRECOMP_WEB_TOKEN";
+
+pub fn token_from_env() -> Option<String> {
+    std::env::var(TOKEN_ENV).ok().filter(|t| !t.is_empty())
+}
+
+/// Check a request against the server's `token` (as `Authorization: Bearer`
+/// or, for browser WebSockets that can't set headers, a `token=` query
+/// parameter) and reject pages on other origins, which a browser would
+/// otherwise let connect to localhost.
+pub fn authorize(
+    headers: &HeaderMap,
+    query: Option<&str>,
+    token: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    if let Some(origin) = headers.get(ORIGIN) {
+        let host = origin
+            .to_str()
+            .ok()
+            .and_then(|o| o.split_once("://"))
+            .map(|(_, rest)| rest.split('/').next().unwrap_or(""))
+            .map(|authority| match authority.rsplit_once(':') {
+                Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+                _ => authority,
+            });
+        if !matches!(host, Some("localhost" | "127.0.0.1" | "[::1]")) {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Cross-origin requests are not allowed.".to_string(),
+            ));
+        }
+    }
+    let Some(token) = token else {
+        return Ok(());
+    };
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let query_token = query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .find_map(|pair| pair.strip_prefix("token="));
+    if [bearer, query_token]
+        .into_iter()
+        .flatten()
+        .any(|given| constant_time_eq(given.as_bytes(), token.as_bytes()))
+    {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::UNAUTHORIZED,
+            "Missing or wrong API token.".to_string(),
+        ))
+    }
+}
+
+/// Compare without stopping at the first difference, so response timing
+/// doesn't tell how much of a guessed token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
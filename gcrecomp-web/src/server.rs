@@ -7,6 +7,7 @@ use tower_http::services::ServeDir;
 
 use gcrecomp_lua::engine::LuaEngine;
 
+use crate::jobs::{JobQueue, JOB_WORKERS};
 use crate::routes;
 use crate::security;
 
@@ -14,6 +15,26 @@ pub struct AppState {
     pub lua_engine: Mutex<LuaEngine>,
     pub status_tx: broadcast::Sender<StatusEvent>,
     pub recompiling: AtomicBool,
+    /// `POST /api/recompile` jobs.
+    pub jobs: Arc<JobQueue>,
+    /// Token for `security::authorize` (`GCRECOMP_WEB_TOKEN`), if any.
+    pub api_token: Option<String>,
+    /// Largest accepted upload in bytes (`GCRECOMP_WEB_MAX_UPLOAD`).
+    pub max_upload: usize,
+}
+
+impl AppState {
+    pub fn new(lua_engine: LuaEngine, jobs: JobQueue) -> Self {
+        let (status_tx, _) = broadcast::channel(64);
+        Self {
+            lua_engine: Mutex::new(lua_engine),
+            status_tx,
+            recompiling: AtomicBool::new(false),
+            jobs: Arc::new(jobs),
+            api_token: security::token_from_env(),
+            max_upload: security::max_upload_from_env(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, serde::Serialize)]
@@ -54,13 +75,7 @@ impl WebServer {
                 .map_err(|e| anyhow::anyhow!("Failed to set web_routes global: {}", e))?;
         }
 
-        let (status_tx, _) = broadcast::channel(64);
-
-        let state = Arc::new(AppState {
-            lua_engine: Mutex::new(engine),
-            status_tx,
-            recompiling: AtomicBool::new(false),
-        });
+        let state = Arc::new(AppState::new(engine, JobQueue::new("jobs", JOB_WORKERS)));
 
         Ok(Self { state })
     }
@@ -70,7 +85,7 @@ impl WebServer {
         std::fs::create_dir_all("output").ok();
 
         let app = Router::new()
-            .merge(routes::app_routes())
+            .merge(routes::app_routes(&self.state))
             .nest_service("/output", ServeDir::new("output"))
             .with_state(self.state);
 
@@ -30,3 +30,30 @@ pub fn call_function_by_address(
 ) -> Result<Option<u32>> {
     Ok(None)
 }
+
+/// Hot-reload entry points (`runtime::hot_reload`).
+#[no_mangle]
+pub extern "C" fn gcrecomp_abi_version() -> u32 {
+    gcrecomp_core::runtime::hot_reload::ABI_VERSION
+}
+
+/// # Safety
+///
+/// Called by `runtime::hot_reload::dispatch` with live pointers.
+#[no_mangle]
+pub unsafe extern "C" fn gcrecomp_dispatch(
+    address: u32,
+    ctx: *mut CpuContext,
+    memory: *mut MemoryManager,
+    ret: *mut u32,
+) -> i32 {
+    gcrecomp_core::runtime::hot_reload::export(address, ctx, memory, ret, call_function_by_address)
+}
+
+/// # Safety
+///
+/// Called by `runtime::hot_reload::HotReloader` with the host's logger.
+#[no_mangle]
+pub unsafe extern "C" fn gcrecomp_set_logger(logger: *const std::ffi::c_void, max_level: usize) {
+    gcrecomp_core::runtime::hot_reload::install_logger(logger, max_level)
+}